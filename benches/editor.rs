@@ -0,0 +1,25 @@
+//! Times loading, typing into and drawing generated files of a few sizes. Run with
+//! `cargo bench` from a terminal; without one, only loading and typing into a bare
+//! document are timed.
+use hecto::Benchmark;
+
+fn main() {
+    for lines in [1_000, 100_000, 1_000_000] {
+        let benchmark = Benchmark {
+            lines,
+            ..Benchmark::default()
+        };
+        match benchmark.run() {
+            Ok(samples) => {
+                println!("{} lines", lines);
+                for samples in samples {
+                    println!("  {}", samples);
+                }
+            }
+            Err(error) => {
+                eprintln!("bench: {}", error);
+                std::process::exit(1);
+            }
+        }
+    }
+}
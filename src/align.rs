@@ -0,0 +1,114 @@
+use crate::regex::Regex;
+use crate::unicode;
+
+/// What `:align` lines up.
+pub enum Delimiter {
+    Text(String),
+    Pattern(Regex),
+}
+
+impl Delimiter {
+    /// `/pattern/` is a regular expression, anything else is taken literally.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the delimiter is empty or the pattern does not parse.
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let spec = spec.trim();
+        match spec.strip_prefix('/') {
+            Some(pattern) if spec.len() > 1 => {
+                let pattern = pattern.strip_suffix('/').unwrap_or(pattern);
+                Regex::new(pattern)
+                    .map(Self::Pattern)
+                    .map_err(|error| format!("bad pattern /{}/: {}", pattern, error))
+            }
+            _ if spec.is_empty() => Err("no delimiter to align on".to_string()),
+            _ => Ok(Self::Text(spec.to_string())),
+        }
+    }
+    fn find_all(&self, line: &str) -> Vec<(usize, usize)> {
+        match self {
+            Self::Text(text) => line
+                .match_indices(text.as_str())
+                .map(|(start, found)| (start, start.saturating_add(found.len())))
+                .collect(),
+            Self::Pattern(regex) => regex.find_all(line),
+        }
+    }
+}
+
+/// `lines` with each occurrence of `delimiter` in the same column as the ones of the
+/// same number on the other lines, one space either side of it. Indentation is kept,
+/// and lines without the delimiter are left as they are.
+pub fn align(lines: &[&str], delimiter: &Delimiter) -> Vec<String> {
+    // Each line as its cells with the delimiters between them, or `None` to keep it.
+    let split: Vec<Option<(Vec<String>, Vec<String>)>> = lines
+        .iter()
+        .map(|line| {
+            let matches = delimiter.find_all(line);
+            if matches.is_empty() {
+                return None;
+            }
+            let mut cells = Vec::new();
+            let mut delimiters = Vec::new();
+            let mut cell_start = 0;
+            for (start, end) in matches {
+                cells.push(line.get(cell_start..start).unwrap_or_default());
+                delimiters.push(line.get(start..end).unwrap_or_default().trim().to_string());
+                cell_start = end;
+            }
+            cells.push(line.get(cell_start..).unwrap_or_default());
+            let cells = cells
+                .into_iter()
+                .enumerate()
+                .map(|(index, cell)| {
+                    if index == 0 {
+                        cell.trim_end().to_string()
+                    } else {
+                        cell.trim().to_string()
+                    }
+                })
+                .collect();
+            Some((cells, delimiters))
+        })
+        .collect();
+    let mut widths: Vec<usize> = Vec::new();
+    for (cells, delimiters) in split.iter().flatten() {
+        for (index, cell) in cells.iter().take(delimiters.len()).enumerate() {
+            let width = unicode::text_width(cell);
+            match widths.get_mut(index) {
+                Some(widest) => *widest = (*widest).max(width),
+                None => widths.push(width),
+            }
+        }
+    }
+    lines
+        .iter()
+        .zip(&split)
+        .map(|(line, split)| {
+            let (cells, delimiters) = match split {
+                Some(split) => split,
+                None => return (*line).to_string(),
+            };
+            let mut aligned = String::new();
+            for (index, cell) in cells.iter().enumerate() {
+                if index > 0 && !cell.is_empty() {
+                    aligned.push(' ');
+                }
+                aligned.push_str(cell);
+                if let Some(delimiter) = delimiters.get(index) {
+                    let width = widths.get(index).copied().unwrap_or(0);
+                    let padding = width.saturating_sub(unicode::text_width(cell));
+                    aligned.push_str(&" ".repeat(padding));
+                    // A column empty on every line, like the start of a table row,
+                    // takes no space before its delimiter.
+                    if width > 0 || index > 0 {
+                        aligned.push(' ');
+                    }
+                    aligned.push_str(delimiter);
+                }
+            }
+            aligned.trim_end().to_string()
+        })
+        .collect()
+}
@@ -0,0 +1,129 @@
+use crate::Position;
+use std::collections::BTreeMap;
+
+/// One edit of a document, described so that positions into the text can follow it.
+pub enum Change {
+    InsertChar(Position),
+    /// The character at the position is removed, without joining lines.
+    DeleteChar(Position),
+    /// A line break goes in at the position.
+    SplitLine(Position),
+    /// The line after the position's is joined onto it, which ends at the position.
+    JoinLine(Position),
+    /// Text inserted at the first position ends at the second.
+    InsertText(Position, Position),
+    /// `count` lines at a line are replaced by `new_count` lines.
+    ReplaceLines(usize, usize, usize),
+}
+
+impl Change {
+    /// The lines replaced, as a line, the number replaced and the number put in their
+    /// place, or `None` for a change within a line.
+    pub fn lines(&self) -> Option<(usize, usize, usize)> {
+        match self {
+            Self::InsertChar(_) | Self::DeleteChar(_) => None,
+            Self::SplitLine(at) => Some((at.y, 1, 2)),
+            Self::JoinLine(at) => Some((at.y, 2, 1)),
+            Self::InsertText(at, end) => {
+                Some((at.y, 1, end.y.saturating_sub(at.y).saturating_add(1)))
+            }
+            Self::ReplaceLines(at, count, new_count) => Some((*at, *count, *new_count)),
+        }
+    }
+    /// Moves `position` to where its text is after the change. Returns `false` when
+    /// its line was deleted, leaving nothing for it to point at.
+    pub fn shift(&self, position: &mut Position) -> bool {
+        match self {
+            Self::InsertChar(at) => {
+                if position.y == at.y && position.x >= at.x {
+                    position.x = position.x.saturating_add(1);
+                }
+            }
+            Self::DeleteChar(at) => {
+                if position.y == at.y && position.x > at.x {
+                    position.x = position.x.saturating_sub(1);
+                }
+            }
+            Self::SplitLine(at) => {
+                if position.y > at.y {
+                    position.y = position.y.saturating_add(1);
+                } else if position.y == at.y && position.x >= at.x {
+                    position.y = position.y.saturating_add(1);
+                    position.x = position.x.saturating_sub(at.x);
+                }
+            }
+            Self::JoinLine(at) => {
+                let next = at.y.saturating_add(1);
+                if position.y == next {
+                    position.y = at.y;
+                    position.x = position.x.saturating_add(at.x);
+                } else if position.y > next {
+                    position.y = position.y.saturating_sub(1);
+                }
+            }
+            Self::InsertText(at, end) => {
+                if position.y > at.y {
+                    position.y = position.y.saturating_add(end.y.saturating_sub(at.y));
+                } else if position.y == at.y && position.x >= at.x {
+                    position.y = end.y;
+                    position.x = position.x.saturating_sub(at.x).saturating_add(end.x);
+                }
+            }
+            Self::ReplaceLines(at, count, new_count) => {
+                let end = at.saturating_add(*count);
+                if position.y >= end {
+                    position.y = position.y.saturating_add(*new_count).saturating_sub(*count);
+                } else if position.y >= *at && *new_count == 0 {
+                    return false;
+                } else if position.y >= *at {
+                    // Positions in the block stay on their line, or on its last line if
+                    // the block got shorter.
+                    position.y = position
+                        .y
+                        .min(at.saturating_add(*new_count).saturating_sub(1));
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Positions into a document, looked up by key, that move with the text around them as
+/// it is edited, so they neither drift onto other text nor point past its end.
+pub struct Anchors<K> {
+    positions: BTreeMap<K, Position>,
+}
+
+impl<K> Default for Anchors<K> {
+    fn default() -> Self {
+        Self {
+            positions: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K: Ord> Anchors<K> {
+    pub fn set(&mut self, key: K, position: &Position) {
+        self.positions.insert(key, position.clone());
+    }
+    pub fn get(&self, key: K) -> Option<&Position> {
+        self.positions.get(&key)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &Position)> {
+        self.positions.iter()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+    /// Follows `change`, dropping the anchors on deleted lines.
+    pub fn shift(&mut self, change: &Change) {
+        self.positions.retain(|_, position| change.shift(position));
+    }
+}
+
+/// Positions the editor keeps in a buffer.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Anchor {
+    /// Where visual mode started, the end of the selection the cursor does not move.
+    Selection,
+}
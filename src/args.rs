@@ -0,0 +1,138 @@
+use crate::Benchmark;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "Usage: hecto [options] [+N | +/pattern] [file ...]
+
+Options:
+  +N                 Start at line N of the first file
+  +/pattern          Start at the first match of pattern in the first file
+  -                  Read the buffer from standard input
+  -R, --readonly     Open files read-only
+  -c, --exec <command>
+                     Run an ex command on the files, such as '%s/foo/bar/g', without
+                     taking over the terminal; repeat for more, ending in 'wq' to
+                     save. Errors go to stderr and make the exit status 1
+  --config <path>    Use the given configuration file
+  -S, --session <path>
+                     Restore the buffers of a session saved with :mksession
+  --record <path>    Write every key and resize to a file, to attach to a bug report
+  --replay <path>    Play back a recording made with --record, at the pace it was
+                     made, then take keys from the keyboard again
+  --tutor            Open a tutorial that walks through moving, editing, saving
+                     and searching
+  --bench[=lines]    Time loading, typing into and drawing a generated file of
+                     the given length (100000 lines by default) and exit
+  -h, --help         Print this help and exit
+  -V, --version      Print the version and exit";
+
+#[derive(Default)]
+pub struct Args {
+    pub files: Vec<String>,
+    pub line: Option<usize>,
+    pub pattern: Option<String>,
+    pub read_only: bool,
+    /// Ex commands to run in batch mode, in order.
+    pub exec: Vec<String>,
+    pub config: Option<PathBuf>,
+    pub session: Option<PathBuf>,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    /// The length of the file `--bench` generates.
+    pub bench: Option<usize>,
+    pub tutor: bool,
+    pub help: bool,
+    pub version: bool,
+}
+
+impl Args {
+    pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.peekable();
+        let mut options_done = false;
+        while let Some(arg) = args.next() {
+            if options_done {
+                parsed.files.push(arg);
+                continue;
+            }
+            match arg.as_str() {
+                "--" => options_done = true,
+                "-R" | "--readonly" => parsed.read_only = true,
+                "-h" | "--help" => parsed.help = true,
+                "-V" | "--version" => parsed.version = true,
+                "--tutor" => parsed.tutor = true,
+                "-c" | "--exec" => {
+                    let command = args
+                        .next()
+                        .ok_or_else(|| format!("{} requires a command", arg))?;
+                    parsed.exec.push(command);
+                }
+                _ if arg.starts_with("--exec=") => {
+                    parsed
+                        .exec
+                        .push(arg.trim_start_matches("--exec=").to_string());
+                }
+                "--config" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| "--config requires a path".to_string())?;
+                    parsed.config = Some(PathBuf::from(path));
+                }
+                "-S" | "--session" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| format!("{} requires a path", arg))?;
+                    parsed.session = Some(PathBuf::from(path));
+                }
+                _ if arg.starts_with("--session=") => {
+                    parsed.session = Some(PathBuf::from(arg.trim_start_matches("--session=")));
+                }
+                "--record" | "--replay" => {
+                    let path = PathBuf::from(
+                        args.next()
+                            .ok_or_else(|| format!("{} requires a path", arg))?,
+                    );
+                    if arg == "--record" {
+                        parsed.record = Some(path);
+                    } else {
+                        parsed.replay = Some(path);
+                    }
+                }
+                _ if arg.starts_with("--record=") => {
+                    parsed.record = Some(PathBuf::from(arg.trim_start_matches("--record=")));
+                }
+                _ if arg.starts_with("--replay=") => {
+                    parsed.replay = Some(PathBuf::from(arg.trim_start_matches("--replay=")));
+                }
+                "--bench" => parsed.bench = Some(Benchmark::default().lines),
+                _ if arg.starts_with("--bench=") => {
+                    let lines = arg.trim_start_matches("--bench=");
+                    parsed.bench = Some(
+                        lines
+                            .parse()
+                            .map_err(|_| format!("invalid line count: {}", arg))?,
+                    );
+                }
+                _ if arg.starts_with("--config=") => {
+                    parsed.config = Some(PathBuf::from(arg.trim_start_matches("--config=")));
+                }
+                _ if arg.starts_with("+/") => {
+                    parsed.pattern = Some(arg.trim_start_matches("+/").to_string());
+                }
+                _ if arg.starts_with('+') => {
+                    let line = arg.trim_start_matches('+');
+                    parsed.line = Some(if line.is_empty() {
+                        usize::MAX
+                    } else {
+                        line.parse()
+                            .map_err(|_| format!("invalid line number: {}", arg))?
+                    });
+                }
+                _ if arg.starts_with('-') && arg != "-" => {
+                    return Err(format!("unknown option: {}", arg));
+                }
+                _ => parsed.files.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+}
@@ -0,0 +1,408 @@
+use crate::commands;
+use crate::json::Value;
+use crate::paths;
+use crate::word_index;
+use crate::Document;
+use crate::Position;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// Items shown at once; the list scrolls to keep the selection visible.
+pub const VISIBLE_ITEMS: usize = 8;
+const MAX_LABEL_WIDTH: usize = 30;
+const MAX_DETAIL_WIDTH: usize = 30;
+/// The LSP `CompletionItemKind`s given to file system entries.
+const FILE: usize = 17;
+const FOLDER: usize = 19;
+
+pub struct Item {
+    pub label: String,
+    pub detail: Option<String>,
+    /// LSP `CompletionItemKind`; `None` for words taken from the buffer.
+    pub kind: Option<usize>,
+    pub insert_text: String,
+    filter_text: String,
+    /// Start of the text the item replaces, as an LSP line and UTF-16 offset.
+    pub replace_start: Option<(usize, usize)>,
+}
+
+/// The insert-mode completion menu for the word starting at `start`.
+pub struct Popup {
+    pub start: Position,
+    items: Vec<Item>,
+    /// Indices into `items` matching the typed prefix, best first.
+    visible: Vec<usize>,
+    selected: usize,
+    /// Whether a language server reply may still replace the items.
+    pub awaiting: bool,
+}
+
+impl Popup {
+    pub fn new(start: Position, items: Vec<Item>) -> Self {
+        Self {
+            start,
+            items,
+            visible: Vec::new(),
+            selected: 0,
+            awaiting: false,
+        }
+    }
+    pub fn set_items(&mut self, items: Vec<Item>) {
+        self.items = items;
+        self.selected = 0;
+    }
+    /// Keeps the items that fuzzily match `typed`, best match first.
+    pub fn filter(&mut self, typed: &str) {
+        let mut scored: Vec<(usize, usize)> = self
+            .items
+            .iter()
+            .enumerate()
+            // The word being typed is in the buffer too; it is no completion of itself.
+            .filter(|(_, item)| item.kind.is_some() || item.filter_text != typed)
+            .filter_map(|(index, item)| {
+                commands::fuzzy_score(typed, &item.filter_text).map(|score| (score, index))
+            })
+            .collect();
+        scored.sort_by(|(a, a_index), (b, b_index)| b.cmp(a).then(a_index.cmp(b_index)));
+        self.visible = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = self.selected.min(self.visible.len().saturating_sub(1));
+    }
+    pub fn is_empty(&self) -> bool {
+        self.visible.is_empty()
+    }
+    pub fn select_next(&mut self, backward: bool) {
+        let last = self.visible.len().saturating_sub(1);
+        self.selected = if backward {
+            self.selected.checked_sub(1).unwrap_or(last)
+        } else if self.selected >= last {
+            0
+        } else {
+            self.selected.saturating_add(1)
+        };
+    }
+    pub fn selected(&self) -> Option<&Item> {
+        self.visible
+            .get(self.selected)
+            .and_then(|index| self.items.get(*index))
+    }
+    /// The visible slice of the menu as `(line, is_selected)`, padded to equal width.
+    pub fn lines(&self) -> Vec<(String, bool)> {
+        let first = self
+            .selected
+            .saturating_sub(VISIBLE_ITEMS.saturating_sub(1));
+        let shown: Vec<&Item> = self
+            .visible
+            .iter()
+            .skip(first)
+            .take(VISIBLE_ITEMS)
+            .filter_map(|index| self.items.get(*index))
+            .collect();
+        let label_width = shown
+            .iter()
+            .map(|item| item.label.chars().count().min(MAX_LABEL_WIDTH))
+            .max()
+            .unwrap_or(0);
+        let detail_width = shown
+            .iter()
+            .filter_map(|item| item.detail.as_ref())
+            .map(|detail| detail.chars().count().min(MAX_DETAIL_WIDTH))
+            .max()
+            .unwrap_or(0);
+        shown
+            .iter()
+            .enumerate()
+            .map(|(offset, item)| {
+                let label: String = item.label.chars().take(MAX_LABEL_WIDTH).collect();
+                let detail: String = item
+                    .detail
+                    .as_deref()
+                    .unwrap_or_default()
+                    .chars()
+                    .take(MAX_DETAIL_WIDTH)
+                    .collect();
+                let mut line = format!(
+                    " {} {:<width$}",
+                    kind_icon(item.kind),
+                    label,
+                    width = label_width
+                );
+                if detail_width > 0 {
+                    line.push_str(&format!("  {:<width$}", detail, width = detail_width));
+                }
+                line.push(' ');
+                (line, first.saturating_add(offset) == self.selected)
+            })
+            .collect()
+    }
+}
+
+/// A one-character marker for an LSP `CompletionItemKind`.
+fn kind_icon(kind: Option<usize>) -> char {
+    match kind {
+        None => 'w',
+        Some(2) => 'm',
+        Some(3) => 'f',
+        Some(4) => 'c',
+        Some(5) => '.',
+        Some(6) => 'v',
+        Some(7) => 'C',
+        Some(8) => 'I',
+        Some(9) => 'M',
+        Some(10) => 'p',
+        Some(13) => 'E',
+        Some(14) => 'k',
+        Some(15) => 's',
+        Some(17) => 'F',
+        Some(19) => 'D',
+        Some(20) => 'e',
+        Some(21) => 'K',
+        Some(22) => 'S',
+        Some(25) => 'T',
+        Some(_) => '·',
+    }
+}
+
+pub fn is_word_char(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// The start of the word ending at `at`, and the word itself.
+pub fn word_before(document: &Document, at: &Position) -> (Position, String) {
+    let graphemes: Vec<&str> = document
+        .row(at.y)
+        .map(|row| row.graphemes().take(at.x).collect())
+        .unwrap_or_default();
+    let length = graphemes
+        .iter()
+        .rev()
+        .take_while(|grapheme| is_word_char(grapheme))
+        .count();
+    let start = graphemes.len().saturating_sub(length);
+    (
+        Position { x: start, y: at.y },
+        graphemes.get(start..).unwrap_or_default().concat(),
+    )
+}
+
+/// The word under or just before `at`, empty if there is none.
+pub fn word_at(document: &Document, at: &Position) -> String {
+    let graphemes: Vec<&str> = document
+        .row(at.y)
+        .map(|row| row.graphemes().collect())
+        .unwrap_or_default();
+    let at_word = |x: usize| graphemes.get(x).is_some_and(|g| is_word_char(g));
+    let x = if at_word(at.x) {
+        at.x
+    } else if at.x > 0 && at_word(at.x.saturating_sub(1)) {
+        at.x.saturating_sub(1)
+    } else {
+        return String::new();
+    };
+    let start = graphemes
+        .get(..x)
+        .unwrap_or_default()
+        .iter()
+        .rposition(|g| !is_word_char(g))
+        .map_or(0, |index| index.saturating_add(1));
+    let end = graphemes
+        .iter()
+        .skip(x)
+        .position(|g| !is_word_char(g))
+        .map_or(graphemes.len(), |length| x.saturating_add(length));
+    graphemes.get(start..end).unwrap_or_default().concat()
+}
+
+/// Where text that looks like a path ends at `at`: the start of its last component,
+/// the directory part and the name typed so far. Only text starting with `./`,
+/// `../`, `/` or `~/` counts, so ordinary words and fractions are left alone, and
+/// comment leaders like `//` are no paths.
+pub fn path_before(document: &Document, at: &Position) -> Option<(Position, String, String)> {
+    let graphemes: Vec<&str> = document
+        .row(at.y)
+        .map(|row| row.graphemes().take(at.x).collect())
+        .unwrap_or_default();
+    let length = graphemes
+        .iter()
+        .rev()
+        .take_while(|grapheme| {
+            !grapheme
+                .chars()
+                .any(|c| c.is_whitespace() || "\"'`<>()[]{}=,;:".contains(c))
+        })
+        .count();
+    let path_start = graphemes.len().saturating_sub(length);
+    let path = graphemes.get(path_start..).unwrap_or_default().concat();
+    if path.contains("//")
+        || !["./", "../", "/", "~/"]
+            .iter()
+            .any(|lead| path.starts_with(lead))
+    {
+        return None;
+    }
+    let start = graphemes
+        .iter()
+        .rposition(|grapheme| *grapheme == "/")
+        .map_or(path_start, |index| index.saturating_add(1));
+    Some((
+        Position { x: start, y: at.y },
+        graphemes
+            .get(path_start..start)
+            .unwrap_or_default()
+            .concat(),
+        graphemes.get(start..).unwrap_or_default().concat(),
+    ))
+}
+
+/// The entries of `dir`, relative ones read from `base`; directories end in `/`.
+/// Hidden entries are only offered once the name being typed starts with a dot.
+pub fn path_items(dir: &str, typed: &str, base: &Path) -> Vec<Item> {
+    let path = if dir.starts_with('~') || dir.starts_with('/') {
+        paths::expand_home(dir)
+    } else {
+        base.join(dir)
+    };
+    let entries = if let Ok(entries) = fs::read_dir(path) {
+        entries
+    } else {
+        return Vec::new();
+    };
+    let mut items: Vec<Item> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if name.starts_with('.') && !typed.starts_with('.') {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            let label = format!("{}{}", name, if is_dir { "/" } else { "" });
+            Some(Item {
+                insert_text: label.clone(),
+                filter_text: name,
+                detail: None,
+                kind: Some(if is_dir { FOLDER } else { FILE }),
+                label,
+                replace_start: None,
+            })
+        })
+        .collect();
+    items.sort_by(|a, b| a.label.cmp(&b.label));
+    items
+}
+
+/// Distinct words in the document, nearest lines to `at` first.
+pub fn buffer_words(document: &Document, at: &Position) -> Vec<Item> {
+    let mut lines: Vec<usize> = (0..document.len()).collect();
+    lines.sort_by_key(|y| y.max(&at.y).saturating_sub(*y.min(&at.y)));
+    let mut seen = HashSet::new();
+    let mut items = Vec::new();
+    for y in lines {
+        let row = if let Some(row) = document.row(y) {
+            row
+        } else {
+            continue;
+        };
+        for word in word_index::words(row.as_str()) {
+            if seen.insert(word) {
+                items.push(Item {
+                    label: word.to_string(),
+                    detail: None,
+                    kind: None,
+                    insert_text: word.to_string(),
+                    filter_text: word.to_string(),
+                    replace_start: None,
+                });
+            }
+        }
+    }
+    items
+}
+
+/// Reads the items of a `textDocument/completion` result.
+pub fn from_lsp(result: &Value) -> Vec<Item> {
+    let items = result
+        .as_array()
+        .or_else(|| result.get("items").and_then(Value::as_array))
+        .unwrap_or_default();
+    items
+        .iter()
+        .filter_map(|item| {
+            let label = item.get("label")?.as_str()?.to_string();
+            let text_edit = item.get("textEdit");
+            let mut insert_text = text_edit
+                .and_then(|edit| edit.get("newText"))
+                .or_else(|| item.get("insertText"))
+                .and_then(Value::as_str)
+                .unwrap_or(&label)
+                .to_string();
+            if item.get("insertTextFormat").and_then(Value::as_usize) == Some(2) {
+                insert_text = strip_snippet(&insert_text);
+            }
+            let range =
+                text_edit.and_then(|edit| edit.get("range").or_else(|| edit.get("replace")));
+            let replace_start = range.and_then(|range| {
+                let start = range.get("start")?;
+                Some((
+                    start.get("line")?.as_usize()?,
+                    start.get("character")?.as_usize()?,
+                ))
+            });
+            Some(Item {
+                filter_text: item
+                    .get("filterText")
+                    .and_then(Value::as_str)
+                    .unwrap_or(&label)
+                    .to_string(),
+                detail: item
+                    .get("detail")
+                    .and_then(Value::as_str)
+                    .map(|detail| detail.lines().next().unwrap_or_default().to_string()),
+                kind: item.get("kind").and_then(Value::as_usize),
+                label,
+                insert_text,
+                replace_start,
+            })
+        })
+        .collect()
+}
+
+/// Reduces snippet syntax to plain text: `${1:name}` becomes `name` and tab stops
+/// like `$0` disappear.
+fn strip_snippet(snippet: &str) -> String {
+    let mut text = String::new();
+    let mut chars = snippet.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                while chars.peek().is_some_and(char::is_ascii_digit) {
+                    chars.next();
+                }
+                if chars.peek() == Some(&':') {
+                    chars.next();
+                }
+                let mut depth = 1_usize;
+                for c in chars.by_ref() {
+                    match c {
+                        '{' => depth = depth.saturating_add(1),
+                        '}' => {
+                            depth = depth.saturating_sub(1);
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        _ => text.push(c),
+                    }
+                }
+            }
+            '$' if chars.peek().is_some_and(char::is_ascii_digit) => {
+                while chars.peek().is_some_and(char::is_ascii_digit) {
+                    chars.next();
+                }
+            }
+            c => text.push(c),
+        }
+    }
+    text
+}
@@ -0,0 +1,71 @@
+use crate::paths;
+use std::cmp;
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const DEFAULT_KEEP: usize = 5;
+
+pub struct Backup {
+    pub enabled: bool,
+    /// Centralized backup directory; when unset, backups go next to the file as `name~`.
+    pub dir: Option<PathBuf>,
+    /// How many timestamped backups to keep per file in the centralized directory.
+    pub keep: usize,
+}
+
+impl Default for Backup {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: None,
+            keep: DEFAULT_KEEP,
+        }
+    }
+}
+
+impl Backup {
+    /// Copies the current on-disk contents of `file_name` aside before it is overwritten.
+    pub fn write(&self, file_name: &str) -> Result<(), Error> {
+        if !self.enabled || !Path::new(file_name).is_file() {
+            return Ok(());
+        }
+        let dir = if let Some(dir) = &self.dir {
+            dir
+        } else {
+            fs::copy(file_name, format!("{}~", file_name))?;
+            return Ok(());
+        };
+        fs::create_dir_all(dir)?;
+        let prefix = format!("{}.", paths::encode(file_name));
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_millis());
+        fs::copy(file_name, dir.join(format!("{}{:020}~", prefix, timestamp)))?;
+        self.prune(dir, &prefix)
+    }
+    fn prune(&self, dir: &Path, prefix: &str) -> Result<(), Error> {
+        let mut backups: Vec<PathBuf> = fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.strip_prefix(prefix).is_some_and(|rest| {
+                            rest.trim_end_matches('~')
+                                .bytes()
+                                .all(|b| b.is_ascii_digit())
+                        })
+                    })
+            })
+            .collect();
+        backups.sort();
+        let excess = backups.len().saturating_sub(cmp::max(self.keep, 1));
+        for path in backups.iter().take(excess) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
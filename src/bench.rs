@@ -0,0 +1,201 @@
+use crate::Args;
+use crate::Document;
+use crate::Editor;
+use crate::Key;
+use crate::Position;
+use crate::Terminal;
+use std::fmt;
+use std::fs;
+use std::io::{self, Error};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The line typed over and over to time edits.
+const TYPED_LINE: &str = "let total = values.iter().map(|value| value * 2).sum::<u64>();";
+
+/// Times loading, editing and drawing a generated file, for `hecto --bench` and
+/// `cargo bench`.
+pub struct Benchmark {
+    /// Length of the generated file.
+    pub lines: usize,
+    /// Number of times the file is loaded.
+    pub loads: usize,
+    /// Number of keys typed, both into a bare document and through the editor.
+    pub keys: usize,
+    /// Number of pages scrolled, each drawn as a full frame.
+    pub frames: usize,
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Self {
+            lines: 100_000,
+            loads: 5,
+            keys: 2000,
+            frames: 200,
+        }
+    }
+}
+
+/// The times a piece of work took, one per run.
+pub struct Samples {
+    pub name: &'static str,
+    pub times: Vec<Duration>,
+}
+
+impl Samples {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            times: Vec::new(),
+        }
+    }
+    fn time<T>(&mut self, work: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = work();
+        self.times.push(start.elapsed());
+        result
+    }
+    pub fn mean(&self) -> Duration {
+        self.times
+            .iter()
+            .sum::<Duration>()
+            .checked_div(u32::try_from(self.times.len()).unwrap_or(u32::MAX))
+            .unwrap_or_default()
+    }
+    /// The time `percent` of the runs took at most.
+    pub fn percentile(&self, percent: usize) -> Duration {
+        let mut times = self.times.clone();
+        times.sort_unstable();
+        let index = times.len().saturating_mul(percent) / 100;
+        times
+            .get(index.min(times.len().saturating_sub(1)))
+            .copied()
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Samples {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let milliseconds = |time: Duration| format!("{:.3} ms", time.as_secs_f64() * 1000.0);
+        write!(
+            f,
+            "{:<8}{:>7} runs   mean {:>12}   median {:>12}   p99 {:>12}   max {:>12}",
+            self.name,
+            self.times.len(),
+            milliseconds(self.mean()),
+            milliseconds(self.percentile(50)),
+            milliseconds(self.percentile(99)),
+            milliseconds(self.times.iter().max().copied().unwrap_or_default()),
+        )
+    }
+}
+
+impl Benchmark {
+    /// Runs every measurement against a file generated in a temporary directory.
+    /// Typing through the editor and drawing frames need a terminal, and are left out
+    /// without one.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the file cannot be written or read, or the terminal drawn on.
+    pub fn run(&self) -> Result<Vec<Samples>, Error> {
+        let dir = std::env::temp_dir().join(format!("hecto-bench-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("bench.rs");
+        fs::write(&path, generate(self.lines))?;
+        let result = self.measure(&dir, &path);
+        let _ = fs::remove_dir_all(&dir);
+        result
+    }
+    fn measure(&self, dir: &Path, path: &Path) -> Result<Vec<Samples>, Error> {
+        let file_name = path.to_string_lossy().to_string();
+        let mut load = Samples::new("load");
+        let mut document = Document::default();
+        for _ in 0..self.loads {
+            document = load.time(|| Document::open(&file_name))?;
+        }
+        let mut insert = Samples::new("insert");
+        let mut at = Position {
+            x: 0,
+            y: self.lines / 2,
+        };
+        for c in TYPED_LINE.chars().chain(['\n']).cycle().take(self.keys) {
+            insert.time(|| document.insert(&at, c));
+            if c == '\n' {
+                at = Position {
+                    x: 0,
+                    y: at.y.saturating_add(1),
+                };
+            } else {
+                at.x = at.x.saturating_add(1);
+            }
+        }
+        let mut samples = vec![load, insert];
+        if termion::is_tty(&io::stdout()) {
+            samples.extend(self.measure_editor(dir, file_name)?);
+        }
+        Ok(samples)
+    }
+    /// Types into the middle of the file with a frame drawn after every key, then
+    /// scrolls a page at a time.
+    fn measure_editor(&self, dir: &Path, file_name: String) -> Result<Vec<Samples>, Error> {
+        // A config file that does not exist, so that the user's settings do not skew
+        // the results.
+        let config: PathBuf = dir.join("config.toml");
+        let args = Args {
+            files: vec![file_name],
+            line: Some(self.lines / 2),
+            config: Some(config),
+            ..Args::default()
+        };
+        let mut editor = Editor::new(&args);
+        editor.start();
+        editor.draw()?;
+        let mut key = Samples::new("key");
+        let mut frame = Samples::new("frame");
+        for c in TYPED_LINE.chars().chain(['\n']).cycle().take(self.keys) {
+            editor.feed_keys([Key::Char(c)]);
+            key.time(|| editor.handle_queued_keys())?;
+            frame.times.push(editor.draw()?);
+        }
+        let mut scroll = Samples::new("scroll");
+        for _ in 0..self.frames {
+            editor.feed_keys([Key::PageDown]);
+            editor.handle_queued_keys()?;
+            scroll.times.push(editor.draw()?);
+        }
+        Terminal::clear_screen();
+        Ok(vec![key, frame, scroll])
+    }
+}
+
+/// Rust-like source `lines` long, varied enough to exercise highlighting, folding and
+/// wide lines.
+pub fn generate(lines: usize) -> String {
+    let mut text = String::new();
+    let mut line = 0;
+    while line < lines {
+        let block = [
+            format!("/// Computes the value of item {}.", line),
+            format!("pub fn item_{}(values: &[u64]) -> u64 {{", line),
+            "    let mut total = 0;".to_string(),
+            "    for (index, value) in values.iter().enumerate() {".to_string(),
+            format!(
+                "        total += value * {} + index as u64; // \"{}\"",
+                line % 97,
+                "x".repeat(line % 40)
+            ),
+            "    }".to_string(),
+            "    total".to_string(),
+            "}".to_string(),
+            String::new(),
+        ];
+        for row in block.iter().take(lines.saturating_sub(line)) {
+            text.push_str(row);
+            text.push('\n');
+        }
+        line = line.saturating_add(block.len());
+    }
+    text
+}
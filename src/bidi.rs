@@ -0,0 +1,321 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Which way rows run, for the `bidi` option. Each row is a paragraph of its own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Direction {
+    /// Rows are drawn as stored, and any reordering is left to the terminal.
+    Off,
+    /// Each row runs the way its first letter does, left to right if it has none.
+    Auto,
+    Ltr,
+    Rtl,
+}
+
+impl Direction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "off" => Some(Self::Off),
+            "auto" => Some(Self::Auto),
+            "ltr" => Some(Self::Ltr),
+            "rtl" => Some(Self::Rtl),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Auto => "auto",
+            Self::Ltr => "ltr",
+            Self::Rtl => "rtl",
+        }
+    }
+}
+
+/// The bidirectional character types the algorithm tells apart; explicit embeddings
+/// and isolates are not among them and count as neutral.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Class {
+    /// Left-to-right letters.
+    L,
+    /// Hebrew and other right-to-left letters.
+    R,
+    /// Arabic letters.
+    AL,
+    /// European digits.
+    EN,
+    /// Arabic-Indic digits.
+    AN,
+    /// Signs between European numbers: `+` and `-`.
+    ES,
+    /// Signs ending European numbers, such as `%` and currency.
+    ET,
+    /// Separators within numbers: `,` `.` `:` `/`.
+    CS,
+    /// Combining marks, which take the type of what they follow.
+    Nsm,
+    WS,
+    /// Everything else.
+    ON,
+}
+
+fn class(c: char) -> Class {
+    match u32::from(c) {
+        0x30..=0x39 | 0x06F0..=0x06F9 => Class::EN,
+        0x0660..=0x0669 | 0x066B..=0x066C => Class::AN,
+        0x2B | 0x2D | 0x2212 => Class::ES,
+        0x23..=0x25 | 0xA2..=0xA5 | 0xB0..=0xB1 | 0x066A | 0x2030..=0x2034 | 0x20A0..=0x20CF => {
+            Class::ET
+        }
+        0x2C | 0x2E | 0x2F | 0x3A | 0xA0 | 0x060C => Class::CS,
+        0x09 | 0x20 | 0x2000..=0x200A | 0x3000 => Class::WS,
+        0x0300..=0x036F
+        | 0x0591..=0x05BD
+        | 0x05BF
+        | 0x05C1..=0x05C2
+        | 0x05C4..=0x05C5
+        | 0x05C7
+        | 0x0610..=0x061A
+        | 0x064B..=0x065F
+        | 0x0670
+        | 0x06D6..=0x06DC
+        | 0x06DF..=0x06E4
+        | 0x06E7..=0x06E8
+        | 0x06EA..=0x06ED => Class::Nsm,
+        0x200E => Class::L,
+        0x200F | 0x0590..=0x05FF | 0x07C0..=0x085F | 0xFB1D..=0xFB4F | 0x10800..=0x10FFF => {
+            Class::R
+        }
+        0x061C | 0x0600..=0x07BF | 0x0860..=0x08FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF => Class::AL,
+        _ if c.is_alphanumeric() => Class::L,
+        _ => Class::ON,
+    }
+}
+
+/// A row's graphemes with the embedding levels the Unicode bidirectional algorithm
+/// gives them: even levels run left to right, odd ones right to left.
+pub struct Paragraph {
+    levels: Vec<u8>,
+    whitespace: Vec<bool>,
+    base: u8,
+}
+
+impl Paragraph {
+    /// Resolves the levels of `text`, or returns `None` when it reads left to right as
+    /// stored, which is every row without right-to-left text in a left-to-right
+    /// paragraph.
+    pub fn new(text: &str, direction: Direction) -> Option<Self> {
+        let classes: Vec<Class> = text
+            .graphemes(true)
+            .map(|grapheme| grapheme.chars().next().map_or(Class::ON, class))
+            .collect();
+        let first_strong = classes
+            .iter()
+            .find(|class| matches!(class, Class::L | Class::R | Class::AL));
+        let base = match direction {
+            Direction::Off => return None,
+            Direction::Ltr => 0,
+            Direction::Rtl => 1,
+            Direction::Auto => u8::from(matches!(first_strong, Some(Class::R | Class::AL))),
+        };
+        if base == 0
+            && !classes
+                .iter()
+                .any(|class| matches!(class, Class::R | Class::AL | Class::AN))
+        {
+            return None;
+        }
+        let whitespace = classes.iter().map(|class| *class == Class::WS).collect();
+        Some(Self {
+            levels: resolve(classes, base),
+            whitespace,
+            base,
+        })
+    }
+    /// The indices of graphemes `start..end`, one screen line of the row, in the order
+    /// they are shown from left to right.
+    pub fn order(&self, start: usize, end: usize) -> Vec<usize> {
+        let end = end.min(self.levels.len());
+        let start = start.min(end);
+        let mut levels: Vec<u8> = self.levels.get(start..end).unwrap_or_default().to_vec();
+        // Whitespace at the end of a line goes back to the paragraph level.
+        for (level, is_whitespace) in levels
+            .iter_mut()
+            .zip(self.whitespace.get(start..end).unwrap_or_default())
+            .rev()
+        {
+            if !is_whitespace {
+                break;
+            }
+            *level = self.base;
+        }
+        let mut order: Vec<usize> = (start..end).collect();
+        let highest = levels.iter().copied().max().unwrap_or(0);
+        let lowest_odd = levels
+            .iter()
+            .copied()
+            .filter(|level| level % 2 == 1)
+            .min()
+            .unwrap_or(highest.saturating_add(1));
+        // From the highest level down to the lowest odd one, reverse every run at
+        // that level or above.
+        let mut level = highest;
+        while level >= lowest_odd && level > 0 {
+            let mut index = 0;
+            while index < levels.len() {
+                if levels.get(index).is_none_or(|at| *at < level) {
+                    index = index.saturating_add(1);
+                    continue;
+                }
+                let run_end = (index..levels.len())
+                    .find(|at| levels.get(*at).is_none_or(|at| *at < level))
+                    .unwrap_or(levels.len());
+                if let Some(run) = order.get_mut(index..run_end) {
+                    run.reverse();
+                }
+                if let Some(run) = levels.get_mut(index..run_end) {
+                    run.reverse();
+                }
+                index = run_end;
+            }
+            level = level.saturating_sub(1);
+        }
+        order
+    }
+    /// Whether grapheme `index` runs right to left, and so shows mirrored brackets.
+    pub fn is_rtl(&self, index: usize) -> bool {
+        self.levels.get(index).is_some_and(|level| level % 2 == 1)
+    }
+}
+
+/// Resolves weak and neutral types and the levels they give (rules W1-W7, N1-N2 and
+/// I1-I2), with `base` at both ends of the paragraph.
+fn resolve(mut classes: Vec<Class>, base: u8) -> Vec<u8> {
+    let edge = if base == 0 { Class::L } else { Class::R };
+    // W1: marks take the type of what they follow.
+    let mut previous = edge;
+    for class in &mut classes {
+        if *class == Class::Nsm {
+            *class = previous;
+        }
+        previous = *class;
+    }
+    // W2 and W3: European digits after Arabic letters are Arabic numbers, and Arabic
+    // letters are then right to left.
+    let mut strong = edge;
+    for class in &mut classes {
+        match *class {
+            Class::L | Class::R | Class::AL => strong = *class,
+            Class::EN if strong == Class::AL => *class = Class::AN,
+            _ => (),
+        }
+        if *class == Class::AL {
+            *class = Class::R;
+        }
+    }
+    // W4: a single separator between two numbers of one kind joins them.
+    for index in 1..classes.len().saturating_sub(1) {
+        let before = classes.get(index.saturating_sub(1)).copied();
+        let after = classes.get(index.saturating_add(1)).copied();
+        let joined = match (before, classes.get(index).copied(), after) {
+            (Some(Class::EN), Some(Class::ES | Class::CS), Some(Class::EN)) => Class::EN,
+            (Some(Class::AN), Some(Class::CS), Some(Class::AN)) => Class::AN,
+            _ => continue,
+        };
+        if let Some(class) = classes.get_mut(index) {
+            *class = joined;
+        }
+    }
+    // W5: terminators next to European numbers belong to them.
+    let mut index = 0;
+    while index < classes.len() {
+        if classes.get(index) != Some(&Class::ET) {
+            index = index.saturating_add(1);
+            continue;
+        }
+        let run_end = (index..classes.len())
+            .find(|at| classes.get(*at) != Some(&Class::ET))
+            .unwrap_or(classes.len());
+        let touches_number = index.checked_sub(1).and_then(|before| classes.get(before))
+            == Some(&Class::EN)
+            || classes.get(run_end) == Some(&Class::EN);
+        if touches_number {
+            for class in classes.get_mut(index..run_end).unwrap_or_default() {
+                *class = Class::EN;
+            }
+        }
+        index = run_end;
+    }
+    // W6 and W7: other separators are neutral, and European numbers in left-to-right
+    // text are left to right.
+    let mut strong = edge;
+    for class in &mut classes {
+        match *class {
+            Class::ES | Class::ET | Class::CS => *class = Class::ON,
+            Class::L | Class::R => strong = *class,
+            Class::EN if strong == Class::L => *class = Class::L,
+            _ => (),
+        }
+    }
+    // N1 and N2: neutrals between text of one direction take it, others the
+    // paragraph's. Numbers count as right to left here.
+    let direction = |class: Class| match class {
+        Class::L => Some(Class::L),
+        Class::R | Class::EN | Class::AN => Some(Class::R),
+        _ => None,
+    };
+    let mut index = 0;
+    while index < classes.len() {
+        if classes.get(index).copied().and_then(direction).is_some() {
+            index = index.saturating_add(1);
+            continue;
+        }
+        let run_end = (index..classes.len())
+            .find(|at| classes.get(*at).copied().and_then(direction).is_some())
+            .unwrap_or(classes.len());
+        let before = index
+            .checked_sub(1)
+            .and_then(|before| classes.get(before).copied())
+            .and_then(direction)
+            .unwrap_or(edge);
+        let after = classes
+            .get(run_end)
+            .copied()
+            .and_then(direction)
+            .unwrap_or(edge);
+        let resolved = if before == after { before } else { edge };
+        for class in classes.get_mut(index..run_end).unwrap_or_default() {
+            *class = resolved;
+        }
+        index = run_end;
+    }
+    // I1 and I2.
+    classes
+        .into_iter()
+        .map(|class| match (base, class) {
+            (0, Class::R) => 1,
+            (0, Class::AN | Class::EN) => 2,
+            (0, _) => 0,
+            (_, Class::L | Class::EN | Class::AN) => 2,
+            _ => 1,
+        })
+        .collect()
+}
+
+/// The bracket facing the other way, for text shown right to left.
+pub fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        '‹' => '›',
+        '›' => '‹',
+        _ => c,
+    }
+}
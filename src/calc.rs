@@ -0,0 +1,355 @@
+use crate::statusline;
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What an expression works out to.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    /// Text from a conversion such as `hex(255)` or `date()`, or a quoted format.
+    Text(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Int(value) => write!(f, "{}", value),
+            // Twelve decimals hide the noise of binary fractions, as in 0.1 + 0.2.
+            Self::Float(value) if value.is_finite() => {
+                let text = format!("{:.12}", value);
+                let text = text.trim_end_matches('0').trim_end_matches('.');
+                f.write_str(if text == "-0" { "0" } else { text })
+            }
+            Self::Float(value) => write!(f, "{}", value),
+            Self::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+/// Works out `expression`: integers and decimals, written in decimal or with `0x`,
+/// `0b` or `0o`, combined with `+ - * / %`, `**` and parentheses, and the functions
+/// `hex`, `bin`, `oct`, `dec`, `abs`, `min`, `max`, `round`, `floor`, `ceil`, `sqrt`,
+/// `now` (the Unix time), and `date` and `time`, which format a Unix time, by default
+/// now, in local time, `date` also after a `strftime` format in quotes.
+///
+/// # Errors
+///
+/// Says what is wrong with the expression, or that it divides by zero or overflows.
+pub fn evaluate(expression: &str) -> Result<Value, String> {
+    let mut parser = Parser {
+        chars: expression.chars().peekable(),
+    };
+    let value = parser.sum()?;
+    match parser.next_token() {
+        None => Ok(value),
+        Some(c) => Err(format!("unexpected `{}`", c)),
+    }
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Parser<'_> {
+    /// The next character that is not whitespace, without taking it.
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+    fn next_token(&mut self) -> Option<char> {
+        self.peek()?;
+        self.chars.next()
+    }
+    fn eat(&mut self, expected: char) -> bool {
+        if self.peek() == Some(expected) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(format!("expected `{}`", expected))
+        }
+    }
+    fn sum(&mut self) -> Result<Value, String> {
+        let mut value = self.product()?;
+        loop {
+            let operator = match self.peek() {
+                Some(c @ ('+' | '-')) => c,
+                _ => return Ok(value),
+            };
+            self.chars.next();
+            let right = self.product()?;
+            value = arithmetic(operator, &value, &right)?;
+        }
+    }
+    fn product(&mut self) -> Result<Value, String> {
+        let mut value = self.unary()?;
+        loop {
+            let operator = match self.peek() {
+                Some(c @ ('*' | '/' | '%')) => c,
+                _ => return Ok(value),
+            };
+            self.chars.next();
+            let right = self.unary()?;
+            value = arithmetic(operator, &value, &right)?;
+        }
+    }
+    fn unary(&mut self) -> Result<Value, String> {
+        if self.eat('-') {
+            let value = self.unary()?;
+            return arithmetic('-', &Value::Int(0), &value);
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+    /// A primary, raised by `**` to a power that binds to the right.
+    fn power(&mut self) -> Result<Value, String> {
+        let base = self.primary()?;
+        if self.peek() == Some('*') && self.chars.clone().nth(1) == Some('*') {
+            self.chars.nth(1);
+            let exponent = self.unary()?;
+            return arithmetic('^', &base, &exponent);
+        }
+        Ok(base)
+    }
+    fn primary(&mut self) -> Result<Value, String> {
+        match self.peek() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.sum()?;
+                self.expect(')')?;
+                Ok(value)
+            }
+            Some('"') => {
+                self.chars.next();
+                let text: String = self.chars.by_ref().take_while(|c| *c != '"').collect();
+                Ok(Value::Text(text))
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() => {
+                let mut name = String::new();
+                while let Some(letter) = self
+                    .chars
+                    .next_if(|letter| letter.is_alphanumeric() || *letter == '_')
+                {
+                    name.push(letter);
+                }
+                let mut arguments = Vec::new();
+                if self.eat('(') && !self.eat(')') {
+                    loop {
+                        arguments.push(self.sum()?);
+                        if !self.eat(',') {
+                            break;
+                        }
+                    }
+                    self.expect(')')?;
+                }
+                call(&name, &arguments)
+            }
+            Some(c) => Err(format!("unexpected `{}`", c)),
+            None => Err("expression ends early".to_string()),
+        }
+    }
+    fn number(&mut self) -> Result<Value, String> {
+        let mut text = String::new();
+        while let Some(digit) = self
+            .chars
+            .next_if(|digit| digit.is_ascii_alphanumeric() || *digit == '.')
+        {
+            text.push(digit);
+        }
+        let radix = match text.get(..2) {
+            Some("0x" | "0X") => 16,
+            Some("0b" | "0B") => 2,
+            Some("0o" | "0O") => 8,
+            _ => 10,
+        };
+        let digits = if radix == 10 {
+            text.as_str()
+        } else {
+            text.get(2..).unwrap_or_default()
+        };
+        if let Ok(value) = i64::from_str_radix(digits, radix) {
+            return Ok(Value::Int(value));
+        }
+        match text.parse::<f64>() {
+            Ok(value) if radix == 10 => Ok(Value::Float(value)),
+            _ => Err(format!("`{}` is not a number", text)),
+        }
+    }
+}
+
+#[allow(clippy::as_conversions, clippy::cast_precision_loss)]
+fn float(value: &Value) -> Result<f64, String> {
+    match value {
+        Value::Int(value) => Ok(*value as f64),
+        Value::Float(value) => Ok(*value),
+        Value::Text(text) => Err(format!("`{}` is not a number", text)),
+    }
+}
+
+fn int(value: &Value) -> Result<i64, String> {
+    match value {
+        Value::Int(value) => Ok(*value),
+        Value::Float(_) | Value::Text(_) => Err(format!("{} is not a whole number", value)),
+    }
+}
+
+/// Applies `operator`, with `^` for `**`, keeping whole numbers whole where the
+/// result is one.
+#[allow(clippy::float_arithmetic)]
+fn arithmetic(operator: char, left: &Value, right: &Value) -> Result<Value, String> {
+    if let (Value::Int(a), Value::Int(b)) = (left, right) {
+        let exact = match operator {
+            '+' => a.checked_add(*b),
+            '-' => a.checked_sub(*b),
+            '*' => a.checked_mul(*b),
+            '/' | '%' if *b == 0 => return Err("division by zero".to_string()),
+            '/' if a.checked_rem(*b) == Some(0) => a.checked_div(*b),
+            '/' => None,
+            '%' => a.checked_rem(*b),
+            _ => u32::try_from(*b).ok().and_then(|b| a.checked_pow(b)),
+        };
+        if let Some(value) = exact {
+            return Ok(Value::Int(value));
+        }
+        if matches!(operator, '+' | '-' | '*') {
+            return Err("the result is too large".to_string());
+        }
+    }
+    let (a, b) = (float(left)?, float(right)?);
+    let value = match operator {
+        '+' => a + b,
+        '-' => a - b,
+        '*' => a * b,
+        '/' | '%' if b == 0.0 => return Err("division by zero".to_string()),
+        '/' => a / b,
+        '%' => a % b,
+        _ => a.powf(b),
+    };
+    Ok(Value::Float(value))
+}
+
+#[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+fn call(name: &str, arguments: &[Value]) -> Result<Value, String> {
+    let argument = |index: usize| {
+        arguments
+            .get(index)
+            .ok_or_else(|| format!("{}() needs a number", name))
+    };
+    let whole = |value: f64| {
+        if value.is_finite() && value.abs() < 9.0e18 {
+            Ok(Value::Int(value as i64))
+        } else {
+            Err("the result is too large".to_string())
+        }
+    };
+    match name {
+        "pi" => Ok(Value::Float(std::f64::consts::PI)),
+        "e" => Ok(Value::Float(std::f64::consts::E)),
+        "hex" => Ok(Value::Text(radix(int(argument(0)?)?, "0x", 16))),
+        "bin" => Ok(Value::Text(radix(int(argument(0)?)?, "0b", 2))),
+        "oct" => Ok(Value::Text(radix(int(argument(0)?)?, "0o", 8))),
+        "dec" => Ok(Value::Int(int(argument(0)?)?)),
+        "abs" => match argument(0)? {
+            Value::Int(value) => value
+                .checked_abs()
+                .map(Value::Int)
+                .ok_or_else(|| "the result is too large".to_string()),
+            value => Ok(Value::Float(float(value)?.abs())),
+        },
+        "min" | "max" => {
+            let mut best = argument(0)?.clone();
+            for value in arguments.iter().skip(1) {
+                let further = if name == "min" {
+                    float(value)? < float(&best)?
+                } else {
+                    float(value)? > float(&best)?
+                };
+                if further {
+                    best = value.clone();
+                }
+            }
+            Ok(best)
+        }
+        "round" => whole(float(argument(0)?)?.round()),
+        "floor" => whole(float(argument(0)?)?.floor()),
+        "ceil" => whole(float(argument(0)?)?.ceil()),
+        "sqrt" => {
+            let value = float(argument(0)?)?;
+            if value < 0.0 {
+                return Err("square root of a negative number".to_string());
+            }
+            Ok(Value::Float(value.sqrt()))
+        }
+        "now" => Ok(Value::Int(now())),
+        "date" | "time" => {
+            let (time, format) = match arguments {
+                [] => (now(), None),
+                [Value::Text(format)] => (now(), Some(format.as_str())),
+                [time] => (int(time)?, None),
+                [time, Value::Text(format)] => (int(time)?, Some(format.as_str())),
+                _ => return Err(format!("{}() takes a time and a format", name)),
+            };
+            let default = if name == "date" {
+                "%Y-%m-%d"
+            } else {
+                "%H:%M:%S"
+            };
+            strftime(time, format.unwrap_or(default)).map(Value::Text)
+        }
+        _ => Err(format!("unknown function `{}`", name)),
+    }
+}
+
+/// `value` in base 16, 2 or 8 after `prefix`, with its sign before that.
+fn radix(value: i64, prefix: &str, base: u32) -> String {
+    let magnitude = value.unsigned_abs();
+    let digits = match base {
+        16 => format!("{:x}", magnitude),
+        2 => format!("{:b}", magnitude),
+        _ => format!("{:o}", magnitude),
+    };
+    let sign = if value < 0 { "-" } else { "" };
+    format!("{}{}{}", sign, prefix, digits)
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|elapsed| i64::try_from(elapsed.as_secs()).ok())
+        .unwrap_or(0)
+}
+
+/// The Unix time `time` in local time, after the `strftime` format `format`.
+fn strftime(time: i64, format: &str) -> Result<String, String> {
+    let seconds = libc::time_t::try_from(time).map_err(|_| "time out of range".to_string())?;
+    let format = std::ffi::CString::new(format).map_err(|_| "bad format".to_string())?;
+    let tm = statusline::local_tm(seconds).ok_or_else(|| "time out of range".to_string())?;
+    let mut buffer = [0_u8; 256];
+    // SAFETY: `strftime` writes at most `buffer.len()` bytes to `buffer`, reads the
+    // NUL-terminated `format`, and `tm` was filled in above.
+    let length = unsafe {
+        libc::strftime(
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+            format.as_ptr(),
+            &tm,
+        )
+    };
+    buffer
+        .get(..length)
+        .map(|bytes| String::from_utf8_lossy(bytes).into_owned())
+        .ok_or_else(|| "the formatted date is too long".to_string())
+}
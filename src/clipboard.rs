@@ -0,0 +1,86 @@
+use crate::transform;
+use std::env;
+use std::fmt::Write as _;
+use std::io::{Error, ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+/// Puts `text` on the system clipboard with the platform's clipboard tool, and
+/// `html` beside it for programs that paste formatted text. Returns what it was put
+/// there with, for messages.
+///
+/// On macOS the clipboard holds both. `wl-copy` and `xclip` hold one type each time
+/// they run, so there `html` replaces the plain text: a rich editor pastes it in
+/// color, while a terminal pastes nothing.
+///
+/// # Errors
+///
+/// Fails with `NotFound` when there is no clipboard tool for the session, and with
+/// the tool's message when it fails.
+pub fn copy(text: &str, html: Option<&str>) -> Result<&'static str, Error> {
+    if cfg!(target_os = "macos") {
+        return match html {
+            Some(html) => {
+                let script = format!(
+                    "set the clipboard to {{«class HTML»:«data HTML{}», string:\"{}\"}}",
+                    hex(html.as_bytes()),
+                    text.replace('\\', "\\\\").replace('"', "\\\"")
+                );
+                run("osascript", &["-"], &script).map(|()| "osascript")
+            }
+            None => run("pbcopy", &[], text).map(|()| "pbcopy"),
+        };
+    }
+    if env::var_os("WAYLAND_DISPLAY").is_some() {
+        return match html {
+            Some(html) => run("wl-copy", &["--type", "text/html"], html),
+            None => run("wl-copy", &[], text),
+        }
+        .map(|()| "wl-copy");
+    }
+    if env::var_os("DISPLAY").is_some() {
+        return match html {
+            Some(html) => run(
+                "xclip",
+                &["-selection", "clipboard", "-t", "text/html"],
+                html,
+            ),
+            None => run("xclip", &["-selection", "clipboard"], text),
+        }
+        .map(|()| "xclip");
+    }
+    Err(Error::new(ErrorKind::NotFound, "no clipboard tool"))
+}
+
+/// The OSC 52 sequence asking the terminal to put `text` on the clipboard, which
+/// also works over ssh. Terminals take plain text only this way.
+pub fn osc52(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", transform::base64_encode(text.as_bytes()))
+}
+
+/// Runs `program` with `input` on stdin. The clipboard tools stay behind to serve
+/// the clipboard, so their output is not waited for.
+fn run(program: &str, args: &[&str], input: &str) -> Result<(), Error> {
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| Error::new(error.kind(), format!("{}: {}", program, error)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::other(format!("{} failed ({})", program, status)))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut hex, byte| {
+        let _ = write!(hex, "{:02X}", byte);
+        hex
+    })
+}
@@ -0,0 +1,113 @@
+use crate::json::Value;
+
+/// A `CodeAction` or bare `Command` offered by the language server.
+pub struct Action {
+    pub title: String,
+    pub kind: Option<String>,
+    pub edit: Option<Value>,
+    /// The `Command` to execute after applying `edit`.
+    pub command: Option<Value>,
+    preferred: bool,
+    /// The action as the server sent it, for `codeAction/resolve`.
+    pub raw: Value,
+}
+
+impl Action {
+    pub fn from_lsp(value: &Value) -> Option<Self> {
+        let title = value.get("title")?.as_str()?.to_string();
+        // A bare `Command` has a string `command`; a `CodeAction` nests a `Command`.
+        let command = match value.get("command") {
+            Some(Value::String(_)) => Some(value.clone()),
+            Some(command @ Value::Object(_)) => Some(command.clone()),
+            _ => None,
+        };
+        Some(Self {
+            title,
+            kind: value
+                .get("kind")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            edit: value.get("edit").cloned(),
+            command,
+            preferred: value.get("isPreferred") == Some(&Value::Bool(true)),
+            raw: value.clone(),
+        })
+    }
+    /// Whether the server has to fill in the action before it can be applied.
+    pub fn needs_resolve(&self) -> bool {
+        self.edit.is_none() && self.command.is_none()
+    }
+}
+
+/// Reads a `textDocument/codeAction` result, preferred quick fixes first.
+pub fn parse(result: &Value) -> Vec<Action> {
+    let mut actions: Vec<Action> = result
+        .as_array()
+        .unwrap_or_default()
+        .iter()
+        .filter(|action| action.get("disabled").is_none())
+        .filter_map(Action::from_lsp)
+        .collect();
+    actions.sort_by_key(|action| !action.preferred);
+    actions
+}
+
+/// The popup listing the code actions for the cursor or selection.
+pub struct Menu {
+    actions: Vec<Action>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(actions: Vec<Action>) -> Self {
+        Self {
+            actions,
+            selected: 0,
+        }
+    }
+    pub fn select_next(&mut self, backward: bool) {
+        let last = self.actions.len().saturating_sub(1);
+        self.selected = if backward {
+            self.selected.checked_sub(1).unwrap_or(last)
+        } else if self.selected >= last {
+            0
+        } else {
+            self.selected.saturating_add(1)
+        };
+    }
+    /// Takes the selected action, or action `index` when given.
+    pub fn take(mut self, index: Option<usize>) -> Option<Action> {
+        let index = index.unwrap_or(self.selected);
+        (index < self.actions.len()).then(|| self.actions.swap_remove(index))
+    }
+    /// Numbered `(line, is_selected)` lines, padded to equal width.
+    pub fn lines(&self) -> Vec<(String, bool)> {
+        let lines: Vec<String> = self
+            .actions
+            .iter()
+            .enumerate()
+            .map(|(index, action)| {
+                let kind = action
+                    .kind
+                    .as_deref()
+                    .map_or(String::new(), |kind| format!(" ({})", kind));
+                format!(" {}. {}{}", index.saturating_add(1), action.title, kind)
+            })
+            .collect();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(index, line)| {
+                (
+                    format!("{:<width$} ", line, width = width),
+                    index == self.selected,
+                )
+            })
+            .collect()
+    }
+}
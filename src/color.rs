@@ -0,0 +1,168 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use termion::color::Rgb;
+
+/// How many colors the terminal can show.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Depth {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+/// The six levels of each channel in the 256-color cube.
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// The 16 basic colors as xterm shows them.
+const ANSI16: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Index of the `max_colors` number among the standard terminfo capabilities.
+const MAX_COLORS: usize = 13;
+
+/// Works out the terminal's colors from `COLORTERM`, then the terminfo entry for
+/// `TERM`, then the name in `TERM`.
+pub fn detect() -> Depth {
+    if env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit") {
+        return Depth::TrueColor;
+    }
+    let term = env::var("TERM").unwrap_or_default();
+    match terminfo_colors(&term) {
+        Some(colors) if colors >= 256 => Depth::Ansi256,
+        Some(_) => Depth::Ansi16,
+        None if term.contains("256color") => Depth::Ansi256,
+        None => Depth::Ansi16,
+    }
+}
+
+/// The escape sequence setting the foreground or background to `color`.
+pub fn sequence(color: Rgb, depth: Depth, background: bool) -> String {
+    let Rgb(r, g, b) = color;
+    let layer = if background { 4 } else { 3 };
+    match depth {
+        Depth::TrueColor => format!("\x1b[{}8;2;{};{};{}m", layer, r, g, b),
+        Depth::Ansi256 => format!("\x1b[{}8;5;{}m", layer, ansi256(color)),
+        Depth::Ansi16 => {
+            let index = ansi16(color);
+            if index < 8 {
+                format!("\x1b[{}{}m", layer, index)
+            } else {
+                // The bright colors: 90-97 for text, 100-107 for backgrounds.
+                let base: u8 = if background { 100 } else { 90 };
+                format!("\x1b[{}m", base.saturating_add(index).saturating_sub(8))
+            }
+        }
+    }
+}
+
+/// The nearest color of the 6x6x6 cube or the gray ramp of a 256-color terminal.
+#[allow(clippy::integer_arithmetic)]
+pub fn ansi256(color: Rgb) -> u8 {
+    let Rgb(r, g, b) = color;
+    let level = |value: u8| -> u8 {
+        let nearest = CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| value.abs_diff(**level))
+            .map_or(0, |(index, _)| index);
+        u8::try_from(nearest).unwrap_or(0)
+    };
+    let (cube_r, cube_g, cube_b) = (level(r), level(g), level(b));
+    let cube = 16 + 36 * cube_r + 6 * cube_g + cube_b;
+    let cube_level = |level: u8| CUBE_LEVELS.get(usize::from(level)).copied().unwrap_or(0);
+    let cube_color = (cube_level(cube_r), cube_level(cube_g), cube_level(cube_b));
+    let average = (u16::from(r) + u16::from(g) + u16::from(b)) / 3;
+    // The ramp runs from 8 to 238 in steps of 10.
+    let step = u8::try_from(average.saturating_sub(3) / 10)
+        .unwrap_or(23)
+        .min(23);
+    let gray = 8 + step * 10;
+    if distance((r, g, b), (gray, gray, gray)) < distance((r, g, b), cube_color) {
+        232 + step
+    } else {
+        cube
+    }
+}
+
+/// The nearest of the 16 basic colors.
+pub fn ansi16(color: Rgb) -> u8 {
+    let Rgb(r, g, b) = color;
+    let nearest = ANSI16
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, basic)| distance((r, g, b), **basic))
+        .map_or(0, |(index, _)| index);
+    u8::try_from(nearest).unwrap_or(0)
+}
+
+#[allow(clippy::integer_arithmetic)]
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let square = |a: u8, b: u8| u32::from(a.abs_diff(b)).pow(2);
+    square(a.0, b.0) + square(a.1, b.1) + square(a.2, b.2)
+}
+
+/// Reads `max_colors` from the compiled terminfo entry for `term`.
+fn terminfo_colors(term: &str) -> Option<u32> {
+    let first = term.chars().next()?;
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Some(dir) = env::var_os("TERMINFO") {
+        dirs.push(dir.into());
+    }
+    if let Some(home) = env::var_os("HOME") {
+        dirs.push(PathBuf::from(home).join(".terminfo"));
+    }
+    dirs.extend(
+        ["/etc/terminfo", "/lib/terminfo", "/usr/share/terminfo"]
+            .iter()
+            .map(PathBuf::from),
+    );
+    let data = dirs.iter().find_map(|dir| {
+        // Most systems file entries under their first letter, macOS under its hex code.
+        fs::read(dir.join(first.to_string()).join(term))
+            .or_else(|_| fs::read(dir.join(format!("{:x}", u32::from(first))).join(term)))
+            .ok()
+    })?;
+    let number = |index: usize| -> Option<usize> {
+        let bytes = data.get(index..index.checked_add(2)?)?;
+        Some(usize::from(u16::from_le_bytes(bytes.try_into().ok()?)))
+    };
+    // The extended format stores numbers in 32 rather than 16 bits.
+    let width = match number(0)? {
+        0o432 => 2,
+        0o1036 => 4,
+        _ => return None,
+    };
+    let (names, booleans, numbers) = (number(2)?, number(4)?, number(6)?);
+    if numbers <= MAX_COLORS {
+        return None;
+    }
+    // Numbers follow the 12-byte header, the names and the booleans, on an even byte.
+    let start = 12_usize.checked_add(names)?.checked_add(booleans)?;
+    let start = start.checked_add(start % 2)?;
+    let offset = start.checked_add(MAX_COLORS.checked_mul(width)?)?;
+    let bytes = data.get(offset..offset.checked_add(width)?)?;
+    let colors = if width == 2 {
+        u32::from(u16::from_le_bytes(bytes.try_into().ok()?))
+    } else {
+        u32::from_le_bytes(bytes.try_into().ok()?)
+    };
+    // -1, stored as all ones, means the capability is missing.
+    (colors != u32::from(u16::MAX) && colors != u32::MAX).then_some(colors)
+}
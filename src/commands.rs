@@ -0,0 +1,1817 @@
+use crate::mode_keys::Lookup;
+use crate::Key;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Command {
+    Save,
+    Quit,
+    ForceQuit,
+    SaveAndQuit,
+    Revert,
+    ExLine,
+    Palette,
+    Find,
+    NextMatch,
+    PreviousMatch,
+    ClearSearchHighlight,
+    GotoLine,
+    JumpBack,
+    JumpForward,
+    NextBuffer,
+    PreviousBuffer,
+    ShowMarks,
+    ShowOptions,
+    ToggleLineNumbers,
+    ToggleWrap,
+    ToggleReadOnly,
+    Help,
+    Complete,
+    NextDiagnostic,
+    PreviousDiagnostic,
+    FixLint,
+    Hover,
+    GotoDefinition,
+    FindReferences,
+    Rename,
+    CodeAction,
+    Format,
+    Outline,
+    Undo,
+    Redo,
+    JumpToTag,
+    PopTag,
+    NextHunk,
+    PreviousHunk,
+    ToggleBlame,
+    WritingMode,
+    NextConflict,
+    PreviousConflict,
+    AcceptOurs,
+    AcceptTheirs,
+    AcceptBoth,
+    StageHunk,
+    RevertHunk,
+    PreviewHunk,
+    DiffView,
+    ToggleSpell,
+    NextMisspelling,
+    PreviousMisspelling,
+    SpellSuggest,
+    SpellAddWord,
+    Thesaurus,
+    InsertUnicode,
+    InspectCharacter,
+    WordCount,
+    ToggleColorColumn,
+    ToggleMinimap,
+    ScrollLineDown,
+    ScrollLineUp,
+    CenterCursorLine,
+    CursorLineToTop,
+    CursorLineToBottom,
+    OpenFold,
+    CloseFold,
+    ToggleFold,
+    OpenAllFolds,
+    CloseAllFolds,
+    RecentFiles,
+    BufferPicker,
+    GotoAnything,
+    Filter,
+    Terminal,
+    OpenUrl,
+    OpenFileUnderCursor,
+    ToggleCheckbox,
+    AlignTable,
+    MarkdownPreview,
+    GrowPanel,
+    ShrinkPanel,
+    EqualizePanels,
+    SplitWindow,
+    CloseWindow,
+    OnlyWindow,
+    NextWindow,
+    PreviousWindow,
+    GrowWindow,
+    ShrinkWindow,
+    EqualizeWindows,
+    RotateWindows,
+    SwapWindow,
+    NextCell,
+    PreviousCell,
+    FormatJson,
+    MinifyJson,
+    Transform,
+    UndoTree,
+    DebugOverlay,
+    ExportHtml,
+    CopyHtml,
+}
+
+pub struct CommandInfo {
+    pub command: Command,
+    /// Identifier used in the `[keys]` config table.
+    pub id: &'static str,
+    /// Human-readable name shown in the command palette.
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+pub const COMMANDS: &[CommandInfo] = &[
+    CommandInfo {
+        command: Command::Save,
+        id: "save",
+        name: "Save File",
+        description: "Write the buffer to disk",
+    },
+    CommandInfo {
+        command: Command::Quit,
+        id: "quit",
+        name: "Quit",
+        description: "Exit, asking again if buffers have unsaved changes",
+    },
+    CommandInfo {
+        command: Command::ForceQuit,
+        id: "force_quit",
+        name: "Quit Without Saving",
+        description: "Exit and discard unsaved changes",
+    },
+    CommandInfo {
+        command: Command::SaveAndQuit,
+        id: "save_and_quit",
+        name: "Save and Quit",
+        description: "Write the buffer, then exit",
+    },
+    CommandInfo {
+        command: Command::Revert,
+        id: "revert",
+        name: "Revert to Saved",
+        description: "Reload the file from disk, discarding changes",
+    },
+    CommandInfo {
+        command: Command::ExLine,
+        id: "command_line",
+        name: "Command Line",
+        description: "Enter an ex-style command",
+    },
+    CommandInfo {
+        command: Command::Palette,
+        id: "command_palette",
+        name: "Command Palette",
+        description: "Search and run any command",
+    },
+    CommandInfo {
+        command: Command::Find,
+        id: "find",
+        name: "Find",
+        description: "Search the buffer incrementally",
+    },
+    CommandInfo {
+        command: Command::NextMatch,
+        id: "next_match",
+        name: "Next Match",
+        description: "Go to the next match of the last search, wrapping at the end",
+    },
+    CommandInfo {
+        command: Command::PreviousMatch,
+        id: "previous_match",
+        name: "Previous Match",
+        description: "Go to the previous match of the last search, wrapping at the start",
+    },
+    CommandInfo {
+        command: Command::ClearSearchHighlight,
+        id: "clear_search_highlight",
+        name: "Clear Search Highlight",
+        description: "Stop highlighting the matches of the last search until the next one",
+    },
+    CommandInfo {
+        command: Command::GotoLine,
+        id: "goto_line",
+        name: "Go to Line",
+        description: "Jump to a line number",
+    },
+    CommandInfo {
+        command: Command::JumpBack,
+        id: "jump_back",
+        name: "Jump Back",
+        description: "Return to the previous position in the jump list",
+    },
+    CommandInfo {
+        command: Command::JumpForward,
+        id: "jump_forward",
+        name: "Jump Forward",
+        description: "Go to the next position in the jump list",
+    },
+    CommandInfo {
+        command: Command::NextBuffer,
+        id: "next_buffer",
+        name: "Next Buffer",
+        description: "Switch to the next open file",
+    },
+    CommandInfo {
+        command: Command::PreviousBuffer,
+        id: "previous_buffer",
+        name: "Previous Buffer",
+        description: "Switch to the previous open file",
+    },
+    CommandInfo {
+        command: Command::ShowMarks,
+        id: "show_marks",
+        name: "Show Marks",
+        description: "List the marks set in the buffer",
+    },
+    CommandInfo {
+        command: Command::ShowOptions,
+        id: "show_options",
+        name: "Show Options",
+        description: "List all options and their values",
+    },
+    CommandInfo {
+        command: Command::ToggleLineNumbers,
+        id: "toggle_line_numbers",
+        name: "Toggle Line Numbers",
+        description: "Show or hide the line number gutter",
+    },
+    CommandInfo {
+        command: Command::ToggleWrap,
+        id: "toggle_wrap",
+        name: "Toggle Line Wrap",
+        description: "Wrap long lines or scroll them sideways",
+    },
+    CommandInfo {
+        command: Command::ToggleReadOnly,
+        id: "toggle_read_only",
+        name: "Toggle Read-Only",
+        description: "Allow or refuse edits to the buffer",
+    },
+    CommandInfo {
+        command: Command::Help,
+        id: "help",
+        name: "Help",
+        description: "Open the help buffer",
+    },
+    CommandInfo {
+        command: Command::Complete,
+        id: "complete",
+        name: "Complete Word",
+        description: "Show completions for the word before the cursor",
+    },
+    CommandInfo {
+        command: Command::NextDiagnostic,
+        id: "next_diagnostic",
+        name: "Next Diagnostic",
+        description: "Jump to the next language server diagnostic",
+    },
+    CommandInfo {
+        command: Command::PreviousDiagnostic,
+        id: "previous_diagnostic",
+        name: "Previous Diagnostic",
+        description: "Jump to the previous language server diagnostic",
+    },
+    CommandInfo {
+        command: Command::FixLint,
+        id: "fix_lint",
+        name: "Fix Lint",
+        description: "Remove trailing whitespace and redo mixed indentation on the cursor line",
+    },
+    CommandInfo {
+        command: Command::Hover,
+        id: "hover",
+        name: "Show Hover Documentation",
+        description: "Show the language server's documentation for the symbol under the cursor",
+    },
+    CommandInfo {
+        command: Command::GotoDefinition,
+        id: "goto_definition",
+        name: "Go to Definition",
+        description: "Jump to where the symbol under the cursor is defined",
+    },
+    CommandInfo {
+        command: Command::FindReferences,
+        id: "find_references",
+        name: "Find References",
+        description: "List every use of the symbol under the cursor in the quickfix list",
+    },
+    CommandInfo {
+        command: Command::Rename,
+        id: "rename",
+        name: "Rename Symbol",
+        description: "Rename the symbol under the cursor across the workspace",
+    },
+    CommandInfo {
+        command: Command::CodeAction,
+        id: "code_action",
+        name: "Code Actions",
+        description:
+            "List the language server's fixes and refactorings for the cursor or selection",
+    },
+    CommandInfo {
+        command: Command::Format,
+        id: "format",
+        name: "Format Buffer",
+        description: "Format the buffer with its formatter or language server",
+    },
+    CommandInfo {
+        command: Command::Outline,
+        id: "outline",
+        name: "Toggle Outline",
+        description: "Show or hide the panel listing the buffer's symbols",
+    },
+    CommandInfo {
+        command: Command::Undo,
+        id: "undo",
+        name: "Undo",
+        description: "Revert the last change to the buffer",
+    },
+    CommandInfo {
+        command: Command::Redo,
+        id: "redo",
+        name: "Redo",
+        description: "Reapply the last undone change",
+    },
+    CommandInfo {
+        command: Command::JumpToTag,
+        id: "jump_to_tag",
+        name: "Jump to Tag",
+        description: "Jump to the ctags definition of the identifier under the cursor",
+    },
+    CommandInfo {
+        command: Command::PopTag,
+        id: "pop_tag",
+        name: "Pop Tag",
+        description: "Return to where the last tag jump started",
+    },
+    CommandInfo {
+        command: Command::NextHunk,
+        id: "next_hunk",
+        name: "Next Change",
+        description: "Jump to the next change against the committed file",
+    },
+    CommandInfo {
+        command: Command::PreviousHunk,
+        id: "previous_hunk",
+        name: "Previous Change",
+        description: "Jump to the previous change against the committed file",
+    },
+    CommandInfo {
+        command: Command::ToggleBlame,
+        id: "toggle_blame",
+        name: "Toggle Blame",
+        description: "Show or hide the commit that last changed the cursor line",
+    },
+    CommandInfo {
+        command: Command::WritingMode,
+        id: "writing_mode",
+        name: "Toggle Writing Mode",
+        description: "Center a wrapped text column, keep the cursor line in the middle \
+                      and hide line numbers and panels",
+    },
+    CommandInfo {
+        command: Command::NextConflict,
+        id: "next_conflict",
+        name: "Next Conflict",
+        description: "Jump to the next merge conflict",
+    },
+    CommandInfo {
+        command: Command::PreviousConflict,
+        id: "previous_conflict",
+        name: "Previous Conflict",
+        description: "Jump to the previous merge conflict",
+    },
+    CommandInfo {
+        command: Command::AcceptOurs,
+        id: "accept_ours",
+        name: "Accept Ours",
+        description: "Resolve the conflict under the cursor with our side",
+    },
+    CommandInfo {
+        command: Command::AcceptTheirs,
+        id: "accept_theirs",
+        name: "Accept Theirs",
+        description: "Resolve the conflict under the cursor with their side",
+    },
+    CommandInfo {
+        command: Command::AcceptBoth,
+        id: "accept_both",
+        name: "Accept Both",
+        description: "Resolve the conflict under the cursor keeping both sides",
+    },
+    CommandInfo {
+        command: Command::StageHunk,
+        id: "stage_hunk",
+        name: "Stage Hunk",
+        description: "Add the change under the cursor to the git index",
+    },
+    CommandInfo {
+        command: Command::RevertHunk,
+        id: "revert_hunk",
+        name: "Revert Hunk",
+        description: "Restore the committed lines for the change under the cursor",
+    },
+    CommandInfo {
+        command: Command::PreviewHunk,
+        id: "preview_hunk",
+        name: "Preview Hunk",
+        description: "Show the lines the change under the cursor removed and added",
+    },
+    CommandInfo {
+        command: Command::DiffView,
+        id: "diff_view",
+        name: "Diff Against Saved File",
+        description: "Compare the buffer side by side with the file on disk",
+    },
+    CommandInfo {
+        command: Command::ToggleSpell,
+        id: "toggle_spell",
+        name: "Toggle Spell Checking",
+        description: "Underline misspelled words, or stop doing so",
+    },
+    CommandInfo {
+        command: Command::NextMisspelling,
+        id: "next_misspelling",
+        name: "Next Misspelling",
+        description: "Jump to the next misspelled word",
+    },
+    CommandInfo {
+        command: Command::PreviousMisspelling,
+        id: "previous_misspelling",
+        name: "Previous Misspelling",
+        description: "Jump to the previous misspelled word",
+    },
+    CommandInfo {
+        command: Command::SpellSuggest,
+        id: "spell_suggest",
+        name: "Suggest Spelling",
+        description: "Offer corrections for the misspelled word under the cursor",
+    },
+    CommandInfo {
+        command: Command::SpellAddWord,
+        id: "spell_add_word",
+        name: "Add Word to Dictionary",
+        description: "Accept the word under the cursor from now on",
+    },
+    CommandInfo {
+        command: Command::Thesaurus,
+        id: "thesaurus",
+        name: "Look Up Synonyms",
+        description: "Offer synonyms for the word under the cursor from the thesaurus",
+    },
+    CommandInfo {
+        command: Command::InsertUnicode,
+        id: "insert_unicode",
+        name: "Insert Unicode Character",
+        description: "Pick a character to insert by searching its name",
+    },
+    CommandInfo {
+        command: Command::InspectCharacter,
+        id: "inspect_character",
+        name: "Inspect Character",
+        description:
+            "Show the code points, bytes, name and width of the character under the cursor",
+    },
+    CommandInfo {
+        command: Command::WordCount,
+        id: "word_count",
+        name: "Word Count",
+        description: "Count the lines, words, characters and bytes of the buffer or selection",
+    },
+    CommandInfo {
+        command: Command::ToggleColorColumn,
+        id: "toggle_color_column",
+        name: "Toggle Color Column",
+        description: "Hide or show the line length guides set by colorcolumn",
+    },
+    CommandInfo {
+        command: Command::ToggleMinimap,
+        id: "toggle_minimap",
+        name: "Toggle Minimap",
+        description: "Show or hide an overview of the buffer beside the text",
+    },
+    CommandInfo {
+        command: Command::ScrollLineDown,
+        id: "scroll_line_down",
+        name: "Scroll Line Down",
+        description:
+            "Scroll the text up one line, keeping the cursor where it is if it stays on screen",
+    },
+    CommandInfo {
+        command: Command::ScrollLineUp,
+        id: "scroll_line_up",
+        name: "Scroll Line Up",
+        description:
+            "Scroll the text down one line, keeping the cursor where it is if it stays on screen",
+    },
+    CommandInfo {
+        command: Command::CenterCursorLine,
+        id: "center_cursor_line",
+        name: "Center Cursor Line",
+        description: "Scroll the cursor line to the middle of the screen",
+    },
+    CommandInfo {
+        command: Command::CursorLineToTop,
+        id: "cursor_line_to_top",
+        name: "Cursor Line to Top",
+        description: "Scroll the cursor line to the top of the screen",
+    },
+    CommandInfo {
+        command: Command::CursorLineToBottom,
+        id: "cursor_line_to_bottom",
+        name: "Cursor Line to Bottom",
+        description: "Scroll the cursor line to the bottom of the screen",
+    },
+    CommandInfo {
+        command: Command::OpenFold,
+        id: "open_fold",
+        name: "Open Fold",
+        description: "Show the lines of the closed fold at the cursor",
+    },
+    CommandInfo {
+        command: Command::CloseFold,
+        id: "close_fold",
+        name: "Close Fold",
+        description: "Hide the indented block (or language server folding range) at the cursor behind its first line",
+    },
+    CommandInfo {
+        command: Command::ToggleFold,
+        id: "toggle_fold",
+        name: "Toggle Fold",
+        description: "Open the fold at the cursor if it is closed, otherwise close it",
+    },
+    CommandInfo {
+        command: Command::OpenAllFolds,
+        id: "open_all_folds",
+        name: "Open All Folds",
+        description: "Show every line of the buffer",
+    },
+    CommandInfo {
+        command: Command::CloseAllFolds,
+        id: "close_all_folds",
+        name: "Close All Folds",
+        description: "Fold every block of the buffer",
+    },
+    CommandInfo {
+        command: Command::RecentFiles,
+        id: "recent_files",
+        name: "Recent Files",
+        description: "Pick a recently opened file to edit again",
+    },
+    CommandInfo {
+        command: Command::BufferPicker,
+        id: "buffer_picker",
+        name: "Buffers",
+        description: "Pick an open buffer to switch to, or close buffers from the list",
+    },
+    CommandInfo {
+        command: Command::GotoAnything,
+        id: "goto_anything",
+        name: "Go to Anything",
+        description: "Pick a definition, file or word of the project to go to",
+    },
+    CommandInfo {
+        command: Command::Filter,
+        id: "filter",
+        name: "Filter Through Command",
+        description: "Replace the selected lines, or the whole buffer, with their output through a shell command",
+    },
+    CommandInfo {
+        command: Command::Terminal,
+        id: "terminal",
+        name: "Terminal",
+        description: "Open a shell in a pane beside the text, or move between it and the buffer",
+    },
+    CommandInfo {
+        command: Command::OpenUrl,
+        id: "open_url",
+        name: "Open URL",
+        description: "Open the URL under the cursor in the browser",
+    },
+    CommandInfo {
+        command: Command::OpenFileUnderCursor,
+        id: "open_file_under_cursor",
+        name: "Open File Under Cursor",
+        description: "Open the file named under the cursor, at the line given after it",
+    },
+    CommandInfo {
+        command: Command::ToggleCheckbox,
+        id: "toggle_checkbox",
+        name: "Toggle Checkbox",
+        description: "Tick or clear the markdown task on the cursor line",
+    },
+    CommandInfo {
+        command: Command::AlignTable,
+        id: "align_table",
+        name: "Align Table",
+        description: "Pad the cells of the markdown table under the cursor into columns",
+    },
+    CommandInfo {
+        command: Command::MarkdownPreview,
+        id: "markdown_preview",
+        name: "Toggle Markdown Preview",
+        description: "Show or hide a pane rendering the markdown buffer as it is edited",
+    },
+    CommandInfo {
+        command: Command::GrowPanel,
+        id: "grow_panel",
+        name: "Grow Side Panel",
+        description: "Widen the outline panel, or the markdown preview when it is alone",
+    },
+    CommandInfo {
+        command: Command::ShrinkPanel,
+        id: "shrink_panel",
+        name: "Shrink Side Panel",
+        description: "Narrow the outline panel, or the markdown preview when it is alone",
+    },
+    CommandInfo {
+        command: Command::EqualizePanels,
+        id: "equalize_panels",
+        name: "Equalize Side Panels",
+        description: "Give the text and the open side panels the same width",
+    },
+    CommandInfo {
+        command: Command::SplitWindow,
+        id: "split_window",
+        name: "Split Window",
+        description: "Split the window in two, one above the other, both on the buffer",
+    },
+    CommandInfo {
+        command: Command::CloseWindow,
+        id: "close_window",
+        name: "Close Window",
+        description: "Close the window, giving its rows to the one next to it",
+    },
+    CommandInfo {
+        command: Command::OnlyWindow,
+        id: "only_window",
+        name: "Close Other Windows",
+        description: "Close every window but this one",
+    },
+    CommandInfo {
+        command: Command::NextWindow,
+        id: "next_window",
+        name: "Next Window",
+        description: "Move to the window below, or the top one from the last",
+    },
+    CommandInfo {
+        command: Command::PreviousWindow,
+        id: "previous_window",
+        name: "Previous Window",
+        description: "Move to the window above, or the last one from the top",
+    },
+    CommandInfo {
+        command: Command::GrowWindow,
+        id: "grow_window",
+        name: "Grow Window",
+        description: "Give the window a row more, taken from the window next to it",
+    },
+    CommandInfo {
+        command: Command::ShrinkWindow,
+        id: "shrink_window",
+        name: "Shrink Window",
+        description: "Give a row of the window to the window next to it",
+    },
+    CommandInfo {
+        command: Command::EqualizeWindows,
+        id: "equalize_windows",
+        name: "Equalize Windows",
+        description: "Give every window the same height",
+    },
+    CommandInfo {
+        command: Command::RotateWindows,
+        id: "rotate_windows",
+        name: "Rotate Windows",
+        description: "Move every window down a place and the last one to the top",
+    },
+    CommandInfo {
+        command: Command::SwapWindow,
+        id: "swap_window",
+        name: "Swap Windows",
+        description: "Exchange the window with the one below it, or above it for the last",
+    },
+    CommandInfo {
+        command: Command::NextCell,
+        id: "next_cell",
+        name: "Next Cell",
+        description: "Move to the start of the next cell of a CSV or TSV row",
+    },
+    CommandInfo {
+        command: Command::PreviousCell,
+        id: "previous_cell",
+        name: "Previous Cell",
+        description: "Move to the start of this or the previous cell of a CSV or TSV row",
+    },
+    CommandInfo {
+        command: Command::FormatJson,
+        id: "format_json",
+        name: "Format JSON",
+        description: "Pretty-print the JSON in the selection or buffer",
+    },
+    CommandInfo {
+        command: Command::MinifyJson,
+        id: "minify_json",
+        name: "Minify JSON",
+        description: "Put the JSON in the selection or buffer on one line without spaces",
+    },
+    CommandInfo {
+        command: Command::Transform,
+        id: "transform",
+        name: "Transform Selection",
+        description: "Encode or decode the selection: Base64, URL encoding, hex dump or ROT13",
+    },
+    CommandInfo {
+        command: Command::UndoTree,
+        id: "undo_tree",
+        name: "Undo Tree",
+        description: "Browse every branch of the undo history and restore any state",
+    },
+    CommandInfo {
+        command: Command::DebugOverlay,
+        id: "debug_overlay",
+        name: "Toggle Debug Overlay",
+        description: "Show frame time, queued input and memory use in the corner",
+    },
+    CommandInfo {
+        command: Command::ExportHtml,
+        id: "export_html",
+        name: "Export as HTML",
+        description: "Write the buffer in its colors to a standalone page beside the file",
+    },
+    CommandInfo {
+        command: Command::CopyHtml,
+        id: "copy_html",
+        name: "Copy as HTML",
+        description: "Copy the selection, or the buffer, to the clipboard in its colors",
+    },
+];
+
+/// What an ex command does; the editor runs it with its `!` and arguments.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Ex {
+    Write,
+    Quit,
+    WriteQuit,
+    Edit,
+    NextBuffer,
+    PreviousBuffer,
+    Buffer,
+    DeleteBuffer,
+    Buffers,
+    Scratch,
+    Tutor,
+    Set,
+    FileFormat,
+    Encoding,
+    Bom,
+    NoBom,
+    Help,
+    LanguageServers,
+    Definition,
+    References,
+    NextQuickfix,
+    PreviousQuickfix,
+    Quickfix,
+    QuickfixList,
+    Rename,
+    CodeAction,
+    Format,
+    Substitute,
+    FixLint,
+    Outline,
+    Preview,
+    Json,
+    MinifyJson,
+    Blame,
+    Writing,
+    Ours,
+    Theirs,
+    Both,
+    Diff,
+    SpellGood,
+    Digraphs,
+    Ascii,
+    UndoTree,
+    Debug,
+    Align,
+    Transform,
+    Export,
+    Log,
+    Logs,
+    Earlier,
+    Later,
+    Recent,
+    Goto,
+    Search,
+    Replace,
+    Evaluate,
+    Shell,
+    Read,
+    Filter,
+    Job,
+    Jobs,
+    JobKill,
+    Plugins,
+    Lua,
+    Terminal,
+    Autocmd,
+    Make,
+    Session,
+    WordCount,
+    Abbreviate,
+    Undo,
+    Redo,
+    Tag,
+    Pop,
+    Tags,
+    NoHighlight,
+    Split,
+    Close,
+    Only,
+    Resize,
+    GotoLine,
+}
+
+impl Ex {
+    /// Whether the command rewrites lines of the buffer, which a hex view has none of.
+    pub fn edits_lines(self) -> bool {
+        matches!(
+            self,
+            Self::Rename
+                | Self::CodeAction
+                | Self::Format
+                | Self::Substitute
+                | Self::FixLint
+                | Self::Json
+                | Self::MinifyJson
+                | Self::Ours
+                | Self::Theirs
+                | Self::Both
+                | Self::Align
+                | Self::Transform
+                | Self::Evaluate
+                | Self::Read
+                | Self::Filter
+        )
+    }
+}
+
+/// An ex command as the command line dispatches it and help describes it.
+pub struct ExCommand {
+    pub ex: Ex,
+    /// The names it is typed as. Commands that start with a symbol, such as `!`, `=`
+    /// and a line number, have none and are recognized by that symbol instead.
+    pub names: &'static [&'static str],
+    /// Whether a `!` may follow the name.
+    pub bang: bool,
+    /// What may follow the name, as help shows it: optional parts in brackets,
+    /// anything else required.
+    pub arguments: &'static str,
+    pub description: &'static str,
+}
+
+impl ExCommand {
+    pub fn takes_arguments(&self) -> bool {
+        !self.arguments.is_empty()
+    }
+    pub fn needs_arguments(&self) -> bool {
+        self.takes_arguments() && !self.arguments.starts_with('[')
+    }
+    /// How help shows the command, without the leading `:`.
+    pub fn syntax(&self) -> String {
+        let bang = if self.bang { "[!]" } else { "" };
+        match (self.names.join(", "), self.arguments) {
+            (names, "") => format!("{}{}", names, bang),
+            (names, arguments) if names.is_empty() => arguments.to_string(),
+            (names, arguments) => format!("{}{} {}", names, bang, arguments),
+        }
+    }
+}
+
+/// Ex commands accepted on the command line.
+pub const EX_COMMANDS: &[ExCommand] = &[
+    ExCommand {
+        ex: Ex::Write,
+        names: &["w"],
+        bang: true,
+        arguments: "",
+        description: "Save the buffer; w! even if it is read-only",
+    },
+    ExCommand {
+        ex: Ex::Quit,
+        names: &["q"],
+        bang: true,
+        arguments: "",
+        description: "Quit unless there are unsaved changes; q! discards them",
+    },
+    ExCommand {
+        ex: Ex::WriteQuit,
+        names: &["wq", "x"],
+        bang: false,
+        arguments: "",
+        description: "Save and quit",
+    },
+    ExCommand {
+        ex: Ex::Edit,
+        names: &["e", "edit"],
+        bang: true,
+        arguments: "[file]",
+        description: "Open a file in a new buffer; e! reverts the buffer to the file on disk",
+    },
+    ExCommand {
+        ex: Ex::NextBuffer,
+        names: &["bn", "bnext"],
+        bang: false,
+        arguments: "",
+        description: "Switch to the next buffer",
+    },
+    ExCommand {
+        ex: Ex::PreviousBuffer,
+        names: &["bp", "bprevious"],
+        bang: false,
+        arguments: "",
+        description: "Switch to the previous buffer",
+    },
+    ExCommand {
+        ex: Ex::Buffer,
+        names: &["b"],
+        bang: false,
+        arguments: "<n>",
+        description: "Switch to buffer number n",
+    },
+    ExCommand {
+        ex: Ex::DeleteBuffer,
+        names: &["bd", "bdelete"],
+        bang: true,
+        arguments: "",
+        description: "Close the buffer; q does too in read-only special buffers",
+    },
+    ExCommand {
+        ex: Ex::Buffers,
+        names: &["ls", "buffers"],
+        bang: false,
+        arguments: "",
+        description: "Pick an open buffer to switch to; Ctrl-D closes one",
+    },
+    ExCommand {
+        ex: Ex::Scratch,
+        names: &["scratch"],
+        bang: false,
+        arguments: "",
+        description: "Open a buffer for notes that is never saved",
+    },
+    ExCommand {
+        ex: Ex::Tutor,
+        names: &["tutor"],
+        bang: false,
+        arguments: "",
+        description: "Open the tutorial on moving, editing, saving and searching",
+    },
+    ExCommand {
+        ex: Ex::Set,
+        names: &["set", "se"],
+        bang: false,
+        arguments: "[option...]",
+        description: "Show or change options; see |options|",
+    },
+    ExCommand {
+        ex: Ex::FileFormat,
+        names: &["ff", "fileformat"],
+        bang: false,
+        arguments: "unix|dos",
+        description: "Set the line endings written on save",
+    },
+    ExCommand {
+        ex: Ex::Encoding,
+        names: &["enc", "encoding"],
+        bang: false,
+        arguments: "<name>",
+        description: "Set the encoding written on save",
+    },
+    ExCommand {
+        ex: Ex::Bom,
+        names: &["bom"],
+        bang: false,
+        arguments: "",
+        description: "Write a byte order mark on save",
+    },
+    ExCommand {
+        ex: Ex::NoBom,
+        names: &["nobom"],
+        bang: false,
+        arguments: "",
+        description: "Drop the byte order mark on save",
+    },
+    ExCommand {
+        ex: Ex::Help,
+        names: &["h", "help"],
+        bang: false,
+        arguments: "[topic]",
+        description: "Open this help, optionally at a topic",
+    },
+    ExCommand {
+        ex: Ex::LanguageServers,
+        names: &["lsp"],
+        bang: false,
+        arguments: "",
+        description: "Show the language servers that were started",
+    },
+    ExCommand {
+        ex: Ex::Definition,
+        names: &["def", "definition"],
+        bang: false,
+        arguments: "",
+        description: "Go to the definition of the symbol under the cursor",
+    },
+    ExCommand {
+        ex: Ex::References,
+        names: &["refs", "references"],
+        bang: false,
+        arguments: "",
+        description: "List references to the symbol under the cursor",
+    },
+    ExCommand {
+        ex: Ex::NextQuickfix,
+        names: &["cn", "cnext"],
+        bang: false,
+        arguments: "",
+        description: "Go to the next quickfix entry",
+    },
+    ExCommand {
+        ex: Ex::PreviousQuickfix,
+        names: &["cp", "cprevious"],
+        bang: false,
+        arguments: "",
+        description: "Go to the previous quickfix entry",
+    },
+    ExCommand {
+        ex: Ex::Quickfix,
+        names: &["cc"],
+        bang: false,
+        arguments: "<n>",
+        description: "Go to quickfix entry n",
+    },
+    ExCommand {
+        ex: Ex::QuickfixList,
+        names: &["copen", "cl", "clist"],
+        bang: false,
+        arguments: "",
+        description: "List the quickfix entries in a buffer; Enter goes to one",
+    },
+    ExCommand {
+        ex: Ex::Rename,
+        names: &["rename"],
+        bang: false,
+        arguments: "[name]",
+        description: "Rename the symbol under the cursor",
+    },
+    ExCommand {
+        ex: Ex::CodeAction,
+        names: &["ca", "codeaction"],
+        bang: false,
+        arguments: "",
+        description: "List code actions for the cursor",
+    },
+    ExCommand {
+        ex: Ex::Format,
+        names: &["format", "fmt"],
+        bang: false,
+        arguments: "",
+        description: "Format the buffer",
+    },
+    ExCommand {
+        ex: Ex::Substitute,
+        names: &[],
+        bang: false,
+        arguments: "[range]s/pat/rep/[giI]",
+        description: "Replace pattern matches on the cursor line, selection, %, n or n,m",
+    },
+    ExCommand {
+        ex: Ex::FixLint,
+        names: &["fixlint"],
+        bang: false,
+        arguments: "",
+        description: "Remove trailing whitespace and redo mixed indentation everywhere",
+    },
+    ExCommand {
+        ex: Ex::Outline,
+        names: &["outline"],
+        bang: false,
+        arguments: "",
+        description: "Show or hide the symbol outline",
+    },
+    ExCommand {
+        ex: Ex::Preview,
+        names: &["preview"],
+        bang: false,
+        arguments: "",
+        description: "Show or hide the markdown preview",
+    },
+    ExCommand {
+        ex: Ex::Json,
+        names: &["json"],
+        bang: false,
+        arguments: "",
+        description: "Pretty-print the JSON of the buffer or selection",
+    },
+    ExCommand {
+        ex: Ex::MinifyJson,
+        names: &["jsonmin", "minify"],
+        bang: false,
+        arguments: "",
+        description: "Minify the JSON of the buffer or selection",
+    },
+    ExCommand {
+        ex: Ex::Blame,
+        names: &["blame"],
+        bang: false,
+        arguments: "",
+        description: "Show or hide the blame for the cursor line",
+    },
+    ExCommand {
+        ex: Ex::Writing,
+        names: &["writing"],
+        bang: false,
+        arguments: "",
+        description: "Turn writing mode on or off: a centered column, typewriter scrolling",
+    },
+    ExCommand {
+        ex: Ex::Ours,
+        names: &["ours"],
+        bang: false,
+        arguments: "",
+        description: "Resolve the conflict under the cursor with our side",
+    },
+    ExCommand {
+        ex: Ex::Theirs,
+        names: &["theirs"],
+        bang: false,
+        arguments: "",
+        description: "Resolve the conflict under the cursor with their side",
+    },
+    ExCommand {
+        ex: Ex::Both,
+        names: &["both"],
+        bang: false,
+        arguments: "",
+        description: "Resolve the conflict under the cursor with both sides",
+    },
+    ExCommand {
+        ex: Ex::Diff,
+        names: &["diff"],
+        bang: false,
+        arguments: "[head]",
+        description: "Compare the buffer with the saved file or HEAD",
+    },
+    ExCommand {
+        ex: Ex::SpellGood,
+        names: &["spellgood"],
+        bang: false,
+        arguments: "<word>",
+        description: "Add a word to the user dictionary",
+    },
+    ExCommand {
+        ex: Ex::Digraphs,
+        names: &["dig", "digraphs"],
+        bang: false,
+        arguments: "",
+        description: "List the digraphs typed after Ctrl-V in insert mode",
+    },
+    ExCommand {
+        ex: Ex::Ascii,
+        names: &["as", "ascii"],
+        bang: false,
+        arguments: "",
+        description: "Describe the character under the cursor",
+    },
+    ExCommand {
+        ex: Ex::UndoTree,
+        names: &["undotree"],
+        bang: false,
+        arguments: "",
+        description: "Browse the undo history and restore any state",
+    },
+    ExCommand {
+        ex: Ex::Debug,
+        names: &["debug"],
+        bang: false,
+        arguments: "",
+        description: "Show or hide frame time, queued input and memory use",
+    },
+    ExCommand {
+        ex: Ex::Align,
+        names: &["align"],
+        bang: false,
+        arguments: "{delimiter}",
+        description: "Line up =, :, | or any text, or /pattern/, across the selection or buffer",
+    },
+    ExCommand {
+        ex: Ex::Transform,
+        names: &["transform"],
+        bang: false,
+        arguments: "[name]",
+        description: "Encode or decode the selection by name, or pick how from a menu",
+    },
+    ExCommand {
+        ex: Ex::Export,
+        names: &["export"],
+        bang: true,
+        arguments: "[file]",
+        description: "Write the buffer in its colors as HTML (.html) or ANSI text",
+    },
+    ExCommand {
+        ex: Ex::Log,
+        names: &["log"],
+        bang: false,
+        arguments: "[filter]",
+        description: "Show or set the log levels, as in `info,lsp=debug`",
+    },
+    ExCommand {
+        ex: Ex::Logs,
+        names: &["logs"],
+        bang: false,
+        arguments: "",
+        description: "Page through the log file from its end",
+    },
+    ExCommand {
+        ex: Ex::Earlier,
+        names: &["ea", "earlier"],
+        bang: false,
+        arguments: "[n|10s|5m|2h|1d|3f]",
+        description: "Go back n changes, a length of time, or n saves",
+    },
+    ExCommand {
+        ex: Ex::Later,
+        names: &["lat", "later"],
+        bang: false,
+        arguments: "[n|10s|5m|2h|1d|3f]",
+        description: "Go forward n changes, a length of time, or n saves",
+    },
+    ExCommand {
+        ex: Ex::Recent,
+        names: &["recent", "ol", "oldfiles"],
+        bang: false,
+        arguments: "",
+        description: "Pick a recently opened file to edit",
+    },
+    ExCommand {
+        ex: Ex::Goto,
+        names: &["goto"],
+        bang: false,
+        arguments: "",
+        description: "Pick a definition, file or word of the project to go to",
+    },
+    ExCommand {
+        ex: Ex::Search,
+        names: &["search"],
+        bang: false,
+        arguments: "[word]",
+        description: "List a word's, or the cursor's, occurrences across the project",
+    },
+    ExCommand {
+        ex: Ex::Replace,
+        names: &["replace"],
+        bang: false,
+        arguments: "/pattern/replacement/[flags]",
+        description: "Preview replacing a pattern across the project's files, then apply the changes kept",
+    },
+    ExCommand {
+        ex: Ex::Evaluate,
+        names: &[],
+        bang: false,
+        arguments: "= [expression]",
+        description: "Put the result of arithmetic, hex(), bin(), date() and the like at the cursor, or in place of the selection",
+    },
+    ExCommand {
+        ex: Ex::Shell,
+        names: &[],
+        bang: false,
+        arguments: "!<command>",
+        description: "Run a shell command and show its output",
+    },
+    ExCommand {
+        ex: Ex::Read,
+        names: &["r", "read"],
+        bang: false,
+        arguments: "!<command>",
+        description: "Insert the output of a shell command at the cursor",
+    },
+    ExCommand {
+        ex: Ex::Filter,
+        names: &[],
+        bang: false,
+        arguments: "%!<command>",
+        description: "Filter the buffer through a shell command",
+    },
+    ExCommand {
+        ex: Ex::Job,
+        names: &["job"],
+        bang: false,
+        arguments: "<command>",
+        description: "Run a shell command in the background, its output paged as it arrives",
+    },
+    ExCommand {
+        ex: Ex::Jobs,
+        names: &["jobs"],
+        bang: false,
+        arguments: "",
+        description: "List the running jobs",
+    },
+    ExCommand {
+        ex: Ex::JobKill,
+        names: &["jobkill"],
+        bang: false,
+        arguments: "<n>",
+        description: "Stop job n",
+    },
+    ExCommand {
+        ex: Ex::Plugins,
+        names: &["plugins"],
+        bang: false,
+        arguments: "",
+        description: "List the loaded plugins with what they registered",
+    },
+    ExCommand {
+        ex: Ex::Lua,
+        names: &["lua"],
+        bang: false,
+        arguments: "<code>",
+        description: "Run Lua code, which reaches the editor through the hecto table",
+    },
+    ExCommand {
+        ex: Ex::Terminal,
+        names: &["term", "terminal"],
+        bang: false,
+        arguments: "",
+        description: "Open a shell in a pane beside the text",
+    },
+    ExCommand {
+        ex: Ex::Autocmd,
+        names: &["au", "autocmd"],
+        bang: false,
+        arguments: "[event command]",
+        description: "Run an ex command on an event (buf_open, buf_write_pre, buf_write_post, cursor_moved, mode_changed, resize, idle), or list the hooks",
+    },
+    ExCommand {
+        ex: Ex::Make,
+        names: &["mak", "make"],
+        bang: false,
+        arguments: "[arguments]",
+        description: "Run the build command in the background and list its errors in the quickfix list",
+    },
+    ExCommand {
+        ex: Ex::Session,
+        names: &["mks", "mksession"],
+        bang: true,
+        arguments: "[file]",
+        description: "Save the open files, cursor positions and windows to a session file (Session.hecto), restored with hecto -S <file>",
+    },
+    ExCommand {
+        ex: Ex::WordCount,
+        names: &["wc"],
+        bang: false,
+        arguments: "",
+        description: "Count the lines, words, characters and bytes of the buffer or selection",
+    },
+    ExCommand {
+        ex: Ex::Abbreviate,
+        names: &["ab", "abbreviate"],
+        bang: false,
+        arguments: "<word> <expansion>",
+        description: "Expand word when it is typed in insert mode",
+    },
+    ExCommand {
+        ex: Ex::Undo,
+        names: &["u", "undo"],
+        bang: false,
+        arguments: "",
+        description: "Undo the last change",
+    },
+    ExCommand {
+        ex: Ex::Redo,
+        names: &["red", "redo"],
+        bang: false,
+        arguments: "",
+        description: "Redo the last undone change",
+    },
+    ExCommand {
+        ex: Ex::Tag,
+        names: &["ta", "tag"],
+        bang: false,
+        arguments: "<name>",
+        description: "Jump to a tag from the tags file",
+    },
+    ExCommand {
+        ex: Ex::Pop,
+        names: &["po", "pop"],
+        bang: false,
+        arguments: "",
+        description: "Return from the last tag jump",
+    },
+    ExCommand {
+        ex: Ex::Tags,
+        names: &["tags"],
+        bang: false,
+        arguments: "",
+        description: "Show the tag stack",
+    },
+    ExCommand {
+        ex: Ex::NoHighlight,
+        names: &["noh", "nohlsearch"],
+        bang: false,
+        arguments: "",
+        description: "Stop highlighting search matches until the next search",
+    },
+    ExCommand {
+        ex: Ex::Split,
+        names: &["sp", "split"],
+        bang: false,
+        arguments: "[file]",
+        description: "Split the window in two, the upper one opening file if one is given",
+    },
+    ExCommand {
+        ex: Ex::Close,
+        names: &["clo", "close"],
+        bang: false,
+        arguments: "",
+        description: "Close the window",
+    },
+    ExCommand {
+        ex: Ex::Only,
+        names: &["on", "only"],
+        bang: false,
+        arguments: "",
+        description: "Close every other window",
+    },
+    ExCommand {
+        ex: Ex::Resize,
+        names: &["res", "resize"],
+        bang: false,
+        arguments: "<[+-]n>",
+        description: "Make the window n rows tall, or n rows taller or shorter",
+    },
+    ExCommand {
+        ex: Ex::GotoLine,
+        names: &[],
+        bang: false,
+        arguments: "<n>",
+        description: "Go to line n",
+    },
+];
+
+/// The ex command typed as `name`.
+pub fn find_ex(name: &str) -> Option<&'static ExCommand> {
+    EX_COMMANDS.iter().find(|info| info.names.contains(&name))
+}
+
+const DEFAULT_BINDINGS: &[(Key, Command)] = &[
+    (Key::Ctrl('s'), Command::Save),
+    (Key::Ctrl('q'), Command::Quit),
+    (Key::Alt('x'), Command::ExLine),
+    (Key::Ctrl('p'), Command::Palette),
+    (Key::Ctrl('g'), Command::Find),
+    (Key::F(3), Command::NextMatch),
+    (Key::ShiftF3, Command::PreviousMatch),
+    (Key::Ctrl('l'), Command::GotoLine),
+    (Key::Ctrl('o'), Command::JumpBack),
+    (Key::Alt('i'), Command::JumpForward),
+    (Key::F(1), Command::Help),
+    (Key::Null, Command::Complete),
+    (Key::F(8), Command::NextDiagnostic),
+    (Key::F(7), Command::PreviousDiagnostic),
+    (Key::Alt('w'), Command::FixLint),
+    (Key::Ctrl('k'), Command::Hover),
+    (Key::F(12), Command::GotoDefinition),
+    (Key::Alt('r'), Command::FindReferences),
+    (Key::F(2), Command::Rename),
+    (Key::Alt('a'), Command::CodeAction),
+    (Key::Alt('o'), Command::Outline),
+    (Key::Alt('b'), Command::ToggleBlame),
+    (Key::Alt('u'), Command::InsertUnicode),
+    (Key::Ctrl('z'), Command::Undo),
+    (Key::Ctrl('y'), Command::Redo),
+    (Key::Ctrl('r'), Command::Redo),
+    (Key::Ctrl('t'), Command::PopTag),
+    (Key::Ctrl('e'), Command::ScrollLineDown),
+    (Key::Alt('z'), Command::ToggleFold),
+    (Key::Alt('e'), Command::RecentFiles),
+    (Key::Alt('s'), Command::BufferPicker),
+    (Key::Alt('g'), Command::GotoAnything),
+    (Key::Alt('|'), Command::Filter),
+    (Key::Alt('t'), Command::Terminal),
+    (Key::Alt('l'), Command::OpenUrl),
+    (Key::Alt('f'), Command::OpenFileUnderCursor),
+    (Key::Alt('c'), Command::ToggleCheckbox),
+    (Key::Alt('p'), Command::MarkdownPreview),
+    (Key::Alt('>'), Command::GrowPanel),
+    (Key::Alt('<'), Command::ShrinkPanel),
+    (Key::Alt('='), Command::EqualizePanels),
+    (Key::Ctrl('w'), Command::NextWindow),
+    (Key::AltRight, Command::NextCell),
+    (Key::AltLeft, Command::PreviousCell),
+    (Key::CtrlDown, Command::ScrollLineDown),
+    (Key::CtrlUp, Command::ScrollLineUp),
+];
+
+/// Stands for the leader key in normal mode sequences.
+pub const LEADER: &str = "<leader>";
+
+/// Normal mode key sequences, as Vim has them, and a few after the leader key.
+const DEFAULT_SEQUENCES: &[(&str, Command)] = &[
+    ("u", Command::Undo),
+    ("n", Command::NextMatch),
+    ("N", Command::PreviousMatch),
+    ("M", Command::ShowMarks),
+    ("K", Command::Hover),
+    (":", Command::ExLine),
+    ("gd", Command::GotoDefinition),
+    ("gr", Command::FindReferences),
+    ("g]", Command::JumpToTag),
+    ("ga", Command::InspectCharacter),
+    ("gx", Command::OpenUrl),
+    ("gf", Command::OpenFileUnderCursor),
+    ("ghs", Command::StageHunk),
+    ("ghr", Command::RevertHunk),
+    ("ghp", Command::PreviewHunk),
+    ("]d", Command::NextDiagnostic),
+    ("[d", Command::PreviousDiagnostic),
+    ("]c", Command::NextHunk),
+    ("[c", Command::PreviousHunk),
+    ("]n", Command::NextConflict),
+    ("[n", Command::PreviousConflict),
+    ("]s", Command::NextMisspelling),
+    ("[s", Command::PreviousMisspelling),
+    ("z=", Command::SpellSuggest),
+    ("zg", Command::SpellAddWord),
+    ("zs", Command::Thesaurus),
+    ("zo", Command::OpenFold),
+    ("zc", Command::CloseFold),
+    ("za", Command::ToggleFold),
+    ("zR", Command::OpenAllFolds),
+    ("zM", Command::CloseAllFolds),
+    ("zz", Command::CenterCursorLine),
+    ("zt", Command::CursorLineToTop),
+    ("zb", Command::CursorLineToBottom),
+    ("<leader>f", Command::GotoAnything),
+    ("<leader>b", Command::BufferPicker),
+    ("<leader>e", Command::RecentFiles),
+    ("<leader>o", Command::Outline),
+    ("<leader>a", Command::CodeAction),
+    ("<leader>r", Command::Rename),
+    ("<leader>p", Command::Palette),
+    ("<leader>ws", Command::SplitWindow),
+    ("<leader>wc", Command::CloseWindow),
+    ("<leader>wo", Command::OnlyWindow),
+    ("<leader>ww", Command::NextWindow),
+    ("<leader>wp", Command::PreviousWindow),
+    ("<leader>w+", Command::GrowWindow),
+    ("<leader>w-", Command::ShrinkWindow),
+    ("<leader>w=", Command::EqualizeWindows),
+    ("<leader>wr", Command::RotateWindows),
+    ("<leader>wx", Command::SwapWindow),
+];
+
+pub fn find_by_id(id: &str) -> Option<&'static CommandInfo> {
+    COMMANDS.iter().find(|info| info.id == id)
+}
+
+/// Maps keys to commands; every mode-independent shortcut resolves through here, and
+/// every normal mode key sequence that runs a command.
+#[derive(Clone)]
+pub struct Bindings {
+    bindings: Vec<(Key, Command)>,
+    sequences: Vec<(String, Command)>,
+    /// The key `<leader>` stands for in sequences.
+    leader: char,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            bindings: DEFAULT_BINDINGS.to_vec(),
+            sequences: DEFAULT_SEQUENCES
+                .iter()
+                .map(|(keys, command)| ((*keys).to_string(), *command))
+                .collect(),
+            leader: '\\',
+        }
+    }
+}
+
+impl Bindings {
+    pub fn command_for(&self, key: Key) -> Option<Command> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| *bound == key)
+            .map(|(_, command)| *command)
+    }
+    pub fn keys_for(&self, command: Command) -> Vec<Key> {
+        self.bindings
+            .iter()
+            .filter(|(_, bound)| *bound == command)
+            .map(|(key, _)| *key)
+            .collect()
+    }
+    /// The normal mode sequence `keys` has been typed towards. A complete sequence wins
+    /// over longer ones it starts.
+    pub fn sequence(&self, keys: &str) -> Lookup<Command> {
+        if let Some((_, command)) = self
+            .sequences
+            .iter()
+            .find(|(bound, _)| self.expand(bound) == keys)
+        {
+            return Lookup::Found(*command);
+        }
+        if self
+            .sequences
+            .iter()
+            .any(|(bound, _)| self.expand(bound).starts_with(keys))
+        {
+            Lookup::Pending
+        } else {
+            Lookup::Unknown
+        }
+    }
+    /// `sequence` with the leader key in place of `<leader>`.
+    pub fn expand(&self, sequence: &str) -> String {
+        sequence.replace(LEADER, &self.leader.to_string())
+    }
+    pub fn leader(&self) -> char {
+        self.leader
+    }
+    pub fn set_leader(&mut self, leader: char) {
+        self.leader = leader;
+    }
+    pub fn sequences_for(&self, command: Command) -> Vec<&str> {
+        self.sequences
+            .iter()
+            .filter(|(_, bound)| *bound == command)
+            .map(|(keys, _)| keys.as_str())
+            .collect()
+    }
+    /// Replaces the keys and normal mode sequences bound to `command`, taking each away
+    /// from any other command.
+    pub fn rebind(&mut self, command: Command, keys: &[Key], sequences: &[String]) {
+        self.bindings
+            .retain(|(key, bound)| *bound != command && !keys.contains(key));
+        self.bindings.extend(keys.iter().map(|key| (*key, command)));
+        self.sequences
+            .retain(|(keys, bound)| *bound != command && !sequences.contains(keys));
+        self.sequences
+            .extend(sequences.iter().map(|keys| (keys.clone(), command)));
+    }
+}
+
+/// Parses key names like `ctrl-s`, `alt-x`, `f5` or `pagedown`.
+/// Navigation keys held with a modifier, by the name they are bound with.
+const MODIFIED_KEYS: [(&str, Key); 17] = [
+    ("Shift-Left", Key::ShiftLeft),
+    ("Shift-Right", Key::ShiftRight),
+    ("Shift-Up", Key::ShiftUp),
+    ("Shift-Down", Key::ShiftDown),
+    ("Shift-Home", Key::ShiftHome),
+    ("Shift-End", Key::ShiftEnd),
+    ("Shift-F3", Key::ShiftF3),
+    ("Alt-Left", Key::AltLeft),
+    ("Alt-Right", Key::AltRight),
+    ("Alt-Up", Key::AltUp),
+    ("Alt-Down", Key::AltDown),
+    ("Ctrl-Left", Key::CtrlLeft),
+    ("Ctrl-Right", Key::CtrlRight),
+    ("Ctrl-Up", Key::CtrlUp),
+    ("Ctrl-Down", Key::CtrlDown),
+    ("Ctrl-Home", Key::CtrlHome),
+    ("Ctrl-End", Key::CtrlEnd),
+];
+
+pub fn parse_key(name: &str) -> Option<Key> {
+    let lower = name.to_ascii_lowercase();
+    if lower == "ctrl-space" || lower == "c-space" {
+        return Some(Key::Null);
+    }
+    if let Some((_, key)) = MODIFIED_KEYS
+        .iter()
+        .find(|(key_name, _)| key_name.eq_ignore_ascii_case(&lower))
+    {
+        return Some(*key);
+    }
+    let single = |rest: &str| {
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    };
+    if let Some(rest) = lower
+        .strip_prefix("ctrl-shift-")
+        .or_else(|| lower.strip_prefix("c-s-"))
+    {
+        return single(rest).map(Key::CtrlShift);
+    }
+    if let Some(rest) = lower
+        .strip_prefix("ctrl-")
+        .or_else(|| lower.strip_prefix("c-"))
+    {
+        return single(rest).map(Key::Ctrl);
+    }
+    if let Some(rest) = name
+        .strip_prefix("alt-")
+        .or_else(|| name.strip_prefix("Alt-"))
+        .or_else(|| name.strip_prefix("m-"))
+        .or_else(|| name.strip_prefix("M-"))
+    {
+        return single(rest).map(Key::Alt);
+    }
+    if let Some(number) = lower.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+        return (1..=12).contains(&number).then_some(Key::F(number));
+    }
+    match lower.as_str() {
+        "esc" | "escape" => Some(Key::Esc),
+        "tab" => Some(Key::Char('\t')),
+        "space" => Some(Key::Char(' ')),
+        "shift-tab" | "backtab" => Some(Key::BackTab),
+        "enter" | "return" => Some(Key::Char('\n')),
+        "backspace" => Some(Key::Backspace),
+        "delete" | "del" => Some(Key::Delete),
+        "insert" => Some(Key::Insert),
+        "home" => Some(Key::Home),
+        "end" => Some(Key::End),
+        "pageup" => Some(Key::PageUp),
+        "pagedown" => Some(Key::PageDown),
+        "up" => Some(Key::Up),
+        "down" => Some(Key::Down),
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        _ => single(name).map(Key::Char),
+    }
+}
+
+/// Parses a normal mode sequence such as `gd`, `zz` or `K`: printable keys, none of
+/// them named with a modifier.
+pub fn parse_sequence(name: &str) -> Option<String> {
+    let lower = name.to_ascii_lowercase();
+    let modified = ["ctrl-", "c-", "alt-", "m-", "shift-"]
+        .iter()
+        .any(|prefix| lower.starts_with(prefix));
+    (!name.is_empty() && !modified && name.chars().all(|c| c.is_ascii_graphic()))
+        .then(|| name.to_string())
+}
+
+pub fn key_name(key: Key) -> String {
+    match key {
+        Key::Ctrl(c) => format!("Ctrl-{}", c.to_ascii_uppercase()),
+        Key::CtrlShift(c) => format!("Ctrl-Shift-{}", c.to_ascii_uppercase()),
+        Key::Null => "Ctrl-Space".to_string(),
+        Key::Alt(c) => format!("Alt-{}", c),
+        Key::F(number) => format!("F{}", number),
+        Key::Char('\t') => "Tab".to_string(),
+        Key::Char(' ') => "Space".to_string(),
+        Key::BackTab => "Shift-Tab".to_string(),
+        Key::Char('\n') => "Enter".to_string(),
+        Key::Char(c) => c.to_string(),
+        Key::Esc => "Esc".to_string(),
+        Key::Backspace => "Backspace".to_string(),
+        Key::Delete => "Delete".to_string(),
+        Key::Insert => "Insert".to_string(),
+        Key::Home => "Home".to_string(),
+        Key::End => "End".to_string(),
+        Key::PageUp => "PageUp".to_string(),
+        Key::PageDown => "PageDown".to_string(),
+        Key::Up => "Up".to_string(),
+        Key::Down => "Down".to_string(),
+        Key::Left => "Left".to_string(),
+        Key::Right => "Right".to_string(),
+        _ => MODIFIED_KEYS
+            .iter()
+            .find(|(_, modified)| *modified == key)
+            .map_or_else(|| "?".to_string(), |(name, _)| (*name).to_string()),
+    }
+}
+
+/// Scores `text` against a fuzzy `query` whose characters must appear in order;
+/// consecutive and word-initial matches score higher. `None` means no match.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    let text: Vec<char> = text.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let mut score = 0_usize;
+    let mut position = 0_usize;
+    let mut previous: Option<usize> = None;
+    for c in query.chars().filter(|c| !c.is_whitespace()) {
+        let c = c.to_ascii_lowercase();
+        let index = text
+            .iter()
+            .skip(position)
+            .position(|t| *t == c)?
+            .saturating_add(position);
+        score = score.saturating_add(1);
+        if previous.is_some_and(|previous| previous.saturating_add(1) == index) {
+            score = score.saturating_add(3);
+        }
+        if index == 0
+            || text
+                .get(index.saturating_sub(1))
+                .is_some_and(|t| !t.is_alphanumeric())
+        {
+            score = score.saturating_add(2);
+        }
+        previous = Some(index);
+        position = index.saturating_add(1);
+    }
+    Some(score)
+}
@@ -0,0 +1,184 @@
+use crate::commands;
+use crate::options::{self, Kind};
+use crate::paths;
+use crate::tags;
+use crate::HistoryKind;
+use std::fs;
+use std::path::PathBuf;
+
+/// Completes the word ending `text`, returning the byte offset where that word
+/// starts and the candidates that could replace it.
+pub fn complete(kind: HistoryKind, text: &str) -> Option<(usize, Vec<String>)> {
+    match kind {
+        HistoryKind::FileName => Some((0, files(text))),
+        HistoryKind::Command => command_line(text),
+        HistoryKind::Search => None,
+    }
+}
+
+fn command_line(text: &str) -> Option<(usize, Vec<String>)> {
+    let command = text.trim_start();
+    let indent = text.len().saturating_sub(command.len());
+    let (name, arguments) = if let Some(split) = command.find(char::is_whitespace) {
+        command.split_at(split)
+    } else {
+        return Some((indent, command_names(command)));
+    };
+    let argument_start = text.len().saturating_sub(arguments.trim_start().len());
+    match name {
+        "e" | "edit" => Some((argument_start, files(arguments.trim_start()))),
+        "ta" | "tag" => Some((argument_start, tags::names(arguments.trim_start()))),
+        "set" | "se" => {
+            let word_start = text
+                .rfind(char::is_whitespace)
+                .map_or(0, |index| index.saturating_add(1));
+            let word = text.get(word_start..)?;
+            if word.contains(['=', ':']) {
+                return None;
+            }
+            Some((word_start, option_names(word)))
+        }
+        _ => None,
+    }
+}
+
+fn command_names(prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = commands::EX_COMMANDS
+        .iter()
+        .flat_map(|info| info.names)
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| (*name).to_string())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn option_names(prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = options::OPTIONS
+        .iter()
+        .filter(|option| option.name.starts_with(prefix))
+        .map(|option| option.name.to_string())
+        .collect();
+    if let Some(rest) = prefix.strip_prefix("no") {
+        names.extend(
+            options::OPTIONS
+                .iter()
+                .filter(|option| option.kind == Kind::Bool && option.name.starts_with(rest))
+                .map(|option| format!("no{}", option.name)),
+        );
+    }
+    names.sort();
+    names
+}
+
+/// Paths starting with `text`; directories end in `/`. Hidden entries are only
+/// offered once the name being typed starts with a dot.
+fn files(text: &str) -> Vec<String> {
+    let split = text.rfind('/').map_or(0, |index| index.saturating_add(1));
+    let (dir, prefix) = text.split_at(split);
+    let path = if dir.is_empty() {
+        PathBuf::from(".")
+    } else {
+        paths::expand_home(dir)
+    };
+    let entries = if let Ok(entries) = fs::read_dir(path) {
+        entries
+    } else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = entry.path().is_dir();
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+/// The longest prefix shared by every candidate.
+pub fn common_prefix(candidates: &[String]) -> &str {
+    let first = if let Some(first) = candidates.first() {
+        first
+    } else {
+        return "";
+    };
+    let mut length = first.len();
+    for candidate in candidates {
+        length = first
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or_else(|| length.min(candidate.len()), |((index, _), _)| index)
+            .min(length);
+    }
+    first.get(..length).unwrap_or("")
+}
+
+/// Short form of a candidate for the list: the last path component of file names.
+fn label(candidate: &str) -> &str {
+    let trimmed = candidate.trim_end_matches('/');
+    trimmed
+        .rfind('/')
+        .and_then(|index| candidate.get(index.saturating_add(1)..))
+        .unwrap_or(candidate)
+}
+
+/// Candidates listed above the prompt while Tab cycles through them.
+pub struct Menu {
+    /// Byte offset in the prompt text where the completed word starts.
+    pub start: usize,
+    pub candidates: Vec<String>,
+    pub selected: Option<usize>,
+}
+
+impl Menu {
+    /// Selects the next (or previous) candidate, returning it.
+    pub fn cycle(&mut self, backward: bool) -> Option<&str> {
+        let count = self.candidates.len();
+        let last = count.saturating_sub(1);
+        let selected = match (self.selected, backward) {
+            (None, false) => 0,
+            (None, true) => last,
+            (Some(index), false) => index.saturating_add(1).checked_rem(count)?,
+            (Some(index), true) => index.checked_sub(1).unwrap_or(last),
+        };
+        self.selected = Some(selected);
+        self.candidates.get(selected).map(String::as_str)
+    }
+    /// Lays the candidates out in columns, scrolled to keep the selection visible.
+    #[allow(clippy::integer_arithmetic, clippy::integer_division)]
+    pub fn lines(&self, width: usize, max_rows: usize) -> Vec<String> {
+        let labels: Vec<&str> = self.candidates.iter().map(|c| label(c)).collect();
+        let column_width = labels
+            .iter()
+            .map(|label| label.chars().count())
+            .max()
+            .unwrap_or(0)
+            .saturating_add(3);
+        let columns = (width / column_width).max(1);
+        let rows = labels.len().div_ceil(columns);
+        let selected_row = self.selected.map_or(0, |index| index / columns);
+        let first = selected_row.saturating_sub(max_rows.max(1) - 1);
+        (first..rows.min(first + max_rows.max(1)))
+            .map(|row| {
+                let mut line = String::new();
+                for (index, label) in labels.iter().enumerate().skip(row * columns).take(columns) {
+                    let item = if self.selected == Some(index) {
+                        format!("[{}]", label)
+                    } else {
+                        format!(" {} ", label)
+                    };
+                    line.push_str(&format!("{:<width$}", item, width = column_width));
+                }
+                line
+            })
+            .collect()
+    }
+}
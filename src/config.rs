@@ -0,0 +1,653 @@
+use crate::bidi::Direction;
+use crate::commands::{self, Bindings};
+use crate::diff::Algorithm;
+use crate::errorformat::Tool;
+use crate::event::{Action, Event, Hooks};
+use crate::log;
+use crate::paths;
+use crate::project;
+use crate::search::Case;
+use crate::statusline::{self, Layout, Segment};
+use crate::theme;
+use crate::toml::{self, Table, Value};
+use crate::url;
+use crate::Backup;
+use crate::Document;
+use crate::Indent;
+use crate::Key;
+use crate::ListChars;
+use crate::SaveOptions;
+use crate::Theme;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Keymap {
+    /// Modeless editing: hecto starts in insert mode.
+    Default,
+    /// Modal editing: hecto starts in normal mode.
+    Vim,
+}
+
+/// Settings from a `[filetype.<name>]` table; unset fields keep the global values.
+#[derive(Default, Clone)]
+pub struct FileTypeConfig {
+    pub use_tabs: Option<bool>,
+    pub indent_size: Option<usize>,
+    pub tab_width: Option<usize>,
+    pub comment: Option<String>,
+    pub color_columns: Option<Vec<usize>>,
+    pub format_on_save: Option<bool>,
+    /// Shell command that reads the buffer on stdin and prints it formatted.
+    pub formatter: Option<String>,
+    pub trim_trailing_whitespace: Option<bool>,
+    pub insert_final_newline: Option<bool>,
+    /// Command that starts a language server for the file type.
+    pub language_server: Option<String>,
+    pub path_completion: Option<bool>,
+    /// Abbreviations for the file type, used before the global ones.
+    pub abbreviations: HashMap<String, String>,
+}
+
+impl FileTypeConfig {
+    pub fn apply_to(&self, document: &mut Document) {
+        if let Some(use_tabs) = self.use_tabs {
+            document.indent.use_tabs = use_tabs;
+        }
+        if let Some(size) = self.indent_size {
+            document.indent.size = size;
+        }
+        if let Some(width) = self.tab_width {
+            document.indent.tab_width = width;
+        }
+        if self.comment.is_some() {
+            document.comment = self.comment.clone();
+        }
+        if let Some(columns) = &self.color_columns {
+            document.color_columns = columns.clone();
+        }
+        if self.formatter.is_some() {
+            document.formatter = self.formatter.clone();
+        }
+        if let Some(format_on_save) = self.format_on_save {
+            document.format_on_save = format_on_save;
+        }
+        if let Some(trim) = self.trim_trailing_whitespace {
+            document.save_options.trim_trailing_whitespace = trim;
+        }
+        if let Some(insert) = self.insert_final_newline {
+            document.save_options.insert_final_newline = insert;
+        }
+        if let Some(path_completion) = self.path_completion {
+            document.path_completion = path_completion;
+        }
+    }
+    fn set(&mut self, setting: &str, value: &Value, key: &str) -> Result<(), String> {
+        match setting {
+            "indent_style" => self.use_tabs = Some(indent_style(value, key)?),
+            "indent_size" => self.indent_size = Some(integer(value, key)?.max(1)),
+            "tab_width" => self.tab_width = Some(integer(value, key)?.max(1)),
+            "comment" => self.comment = Some(string(value, key)?.to_string()),
+            "color_column" => self.color_columns = Some(columns(value, key)?),
+            "format_on_save" => self.format_on_save = Some(boolean(value, key)?),
+            "formatter" => self.formatter = Some(string(value, key)?.to_string()),
+            "trim_trailing_whitespace" => {
+                self.trim_trailing_whitespace = Some(boolean(value, key)?);
+            }
+            "insert_final_newline" => self.insert_final_newline = Some(boolean(value, key)?),
+            "language_server" => self.language_server = Some(string(value, key)?.to_string()),
+            "path_completion" => self.path_completion = Some(boolean(value, key)?),
+            _ if setting.starts_with("abbreviations.") => {
+                self.abbreviations.insert(
+                    setting.trim_start_matches("abbreviations.").to_string(),
+                    string(value, key)?.to_string(),
+                );
+            }
+            _ => return Err(format!("unknown setting `{}`", key)),
+        }
+        Ok(())
+    }
+}
+
+pub struct Config {
+    pub indent: Indent,
+    /// The first window's `number`, `wrap` and `list`; `:set` changes the window's.
+    pub line_numbers: bool,
+    /// Highlights these 1-based screen columns as line length guides.
+    pub color_columns: Vec<usize>,
+    pub theme: Theme,
+    pub theme_name: String,
+    /// Soft-wrap long lines instead of scrolling horizontally.
+    pub wrap: bool,
+    /// Draw whitespace as the `list_chars` symbols.
+    pub list: bool,
+    pub list_chars: ListChars,
+    pub indent_guides: bool,
+    pub cursor_line: bool,
+    pub cursor_column: bool,
+    /// Show who last changed the cursor line at its end.
+    pub blame: bool,
+    /// Lines of context kept on screen above and below the cursor.
+    pub scroll_off: usize,
+    /// How wide the centered text column of writing mode is.
+    pub writing_width: usize,
+    /// Show where the screen is in the buffer along the right edge.
+    pub scrollbar: bool,
+    /// Show an overview of the buffer beside the text.
+    pub minimap: bool,
+    /// Use the kitty keyboard protocol where the terminal supports it.
+    pub kitty_keyboard: bool,
+    /// Dim the status bar while the terminal window is out of focus.
+    pub dim_unfocused: bool,
+    /// Color keywords, strings, comments and numbers.
+    pub syntax: bool,
+    /// Color brackets by how deeply they are nested.
+    pub rainbow_brackets: bool,
+    /// Draw color literals such as `#rrggbb` on the color they name.
+    pub color_swatches: bool,
+    /// Line up the columns of CSV and TSV buffers on screen.
+    pub column_view: bool,
+    /// Line up tab-separated cells of adjacent lines to the widest one, as elastic
+    /// tabstops do.
+    pub elastic_tabstops: bool,
+    /// Left and Right cross a whole indent level at a time in leading spaces.
+    pub soft_tab_motion: bool,
+    /// Windows scroll together, for reading related buffers in step; `:set
+    /// scrollbind` changes it for one window.
+    pub scroll_bind: bool,
+    /// Up and Down aim for the column the cursor was last moved to sideways, or the
+    /// line end after End, rather than the column a shorter line clamped it to.
+    pub sticky_column: bool,
+    /// How long PageUp, PageDown and other jumps of half a screen or more take to
+    /// scroll into place; `None` jumps at once.
+    pub smooth_scroll: Option<Duration>,
+    /// Warn about trailing whitespace, indentation mixing tabs with spaces and lines
+    /// past the color column, without a language server.
+    pub lint: bool,
+    /// How rows with right-to-left text are ordered on screen.
+    pub bidi: Direction,
+    /// The segments of the status bar, from the `[status_bar]` table.
+    pub status_bar: Layout,
+    /// Underline misspelled words, using the dictionary named by `spell_lang`.
+    pub spell: bool,
+    pub spell_lang: String,
+    /// The thesaurus for synonyms; by default the MyThes one for `spell_lang`.
+    pub thesaurus: Option<PathBuf>,
+    /// Show a live word count in the status bar of prose buffers.
+    pub word_count: bool,
+    /// Buffers with more bytes than this, or with a line of more than
+    /// `large_file_line_length` characters, open in large-file mode; 0 means no limit.
+    pub large_file_size: usize,
+    pub large_file_line_length: usize,
+    /// Index the words and definitions of the project's files in the background,
+    /// for go to anything and `:search`.
+    pub search_index: bool,
+    /// Keep a journal of the open buffers and their unsaved changes, offered back on
+    /// the next launch after a crash.
+    pub journal: bool,
+    /// How search matches letter case: `sensitive`, `insensitive` or `smart`, which
+    /// ignores case unless the query has an uppercase letter.
+    pub search_case: Case,
+    /// How the diff view lines up the buffer with the file it is compared to.
+    pub diff_algorithm: Algorithm,
+    /// Search matches only whole words.
+    pub search_whole_word: bool,
+    /// Search reads the query as a regular expression rather than literal text.
+    pub search_regex: bool,
+    /// The build command `:make` runs.
+    pub make: String,
+    /// The program, with any arguments, that opens URLs.
+    pub opener: String,
+    /// How `:make` reads errors from the build output; guessed from `make` when unset.
+    pub error_format: Option<Tool>,
+    pub keymap: Keymap,
+    pub auto_save: Option<Duration>,
+    pub quit_times: u8,
+    /// How long a pending key sequence waits before its continuations are shown.
+    pub hint_delay: Option<Duration>,
+    /// Whether Vim modelines in opened files may change buffer settings.
+    pub modelines: bool,
+    /// The age identity file encrypted files are opened with; asked for when unset.
+    pub age_identity: Option<PathBuf>,
+    pub backup: Backup,
+    pub save_options: SaveOptions,
+    pub filetypes: HashMap<String, FileTypeConfig>,
+    /// Words replaced by their expansion when typed in insert mode.
+    pub abbreviations: HashMap<String, String>,
+    pub bindings: Bindings,
+    /// Ex commands run on editor events, from the `[hooks]` table.
+    pub hooks: Hooks,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            indent: Indent::default(),
+            line_numbers: false,
+            color_columns: Vec::new(),
+            theme: Theme::default(),
+            theme_name: "default".to_string(),
+            wrap: false,
+            list: false,
+            list_chars: ListChars::default(),
+            indent_guides: false,
+            cursor_line: false,
+            cursor_column: false,
+            blame: false,
+            scroll_off: 0,
+            writing_width: 72,
+            scrollbar: false,
+            minimap: false,
+            kitty_keyboard: true,
+            dim_unfocused: false,
+            syntax: true,
+            rainbow_brackets: false,
+            color_swatches: false,
+            column_view: true,
+            elastic_tabstops: false,
+            soft_tab_motion: false,
+            scroll_bind: false,
+            sticky_column: false,
+            smooth_scroll: None,
+            lint: false,
+            bidi: Direction::Auto,
+            status_bar: Layout::default(),
+            spell: false,
+            spell_lang: "en_US".to_string(),
+            word_count: false,
+            large_file_size: 10_000_000,
+            large_file_line_length: 10_000,
+            search_index: true,
+            journal: true,
+            search_case: Case::Sensitive,
+            diff_algorithm: Algorithm::Myers,
+            search_whole_word: false,
+            search_regex: false,
+            make: "make".to_string(),
+            opener: url::default_opener().to_string(),
+            error_format: None,
+            keymap: Keymap::Default,
+            auto_save: None,
+            quit_times: 2,
+            hint_delay: Some(Duration::from_millis(500)),
+            modelines: true,
+            age_identity: None,
+            thesaurus: None,
+            backup: Backup::default(),
+            save_options: SaveOptions::default(),
+            filetypes: HashMap::new(),
+            abbreviations: HashMap::new(),
+            bindings: Bindings::default(),
+            hooks: Hooks::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns `$XDG_CONFIG_HOME/hecto/config.toml`, falling back to `~/.config`.
+    pub fn default_path() -> Option<PathBuf> {
+        paths::config_dir().map(|dir| dir.join("config.toml"))
+    }
+    /// Loads the configuration from `path` (or the default location), then the overrides
+    /// in the `.hecto/config.toml` of the project at `project_root`, then applies the
+    /// `HECTO_*` environment overrides. Missing files yield the defaults.
+    ///
+    /// The project's settings that run programs are left out unless the user trusts
+    /// the project: `trust` is asked with their keys, unless it was trusted before.
+    pub fn load(
+        path: Option<&Path>,
+        project_root: Option<&Path>,
+        trust: &mut dyn FnMut(&[String]) -> bool,
+    ) -> Result<Self, String> {
+        let mut config = Self::default();
+        let path = path.map(Path::to_path_buf).or_else(Self::default_path);
+        if let Some(path) = path {
+            if let Some((_, table)) = read_file(&path)? {
+                config.apply_from(&path, &table)?;
+            }
+        }
+        if let Some(root) = project_root {
+            let path = project::config_path(root);
+            if let Some((text, mut table)) = read_file(&path)? {
+                let commands: Vec<String> = table
+                    .keys()
+                    .filter(|key| runs_programs(key))
+                    .cloned()
+                    .collect();
+                if !commands.is_empty() && !project::is_trusted(root, &text) {
+                    if trust(&commands) {
+                        if let Err(error) = project::trust(root, &text) {
+                            log::warn("config", format!("could not remember trust: {}", error));
+                        }
+                    } else {
+                        table.retain(|key, _| !runs_programs(key));
+                    }
+                }
+                config.apply_from(&path, &table)?;
+            }
+        }
+        config.apply_env();
+        Ok(config)
+    }
+    fn apply_from(&mut self, path: &Path, table: &Table) -> Result<(), String> {
+        self.apply(table)
+            .map_err(|error| format!("{}: {}", path.display(), error))
+    }
+    fn apply(&mut self, table: &Table) -> Result<(), String> {
+        if let Some(value) = table.get("theme") {
+            let name = string(value, "theme")?;
+            self.theme = Theme::named(name).ok_or_else(|| format!("unknown theme `{}`", name))?;
+            self.theme_name = name.to_string();
+        }
+        for (key, value) in table {
+            match key.as_str() {
+                "theme" => (),
+                "tab_width" => self.indent.tab_width = integer(value, key)?.max(1),
+                "indent_size" => self.indent.size = integer(value, key)?.max(1),
+                "indent_style" => self.indent.use_tabs = indent_style(value, key)?,
+                "color_column" => self.color_columns = columns(value, key)?,
+                "line_numbers" => self.line_numbers = boolean(value, key)?,
+                "wrap" => self.wrap = boolean(value, key)?,
+                "list" => self.list = boolean(value, key)?,
+                "list_chars" => self.list_chars = ListChars::parse(string(value, key)?)?,
+                "indent_guides" => self.indent_guides = boolean(value, key)?,
+                "cursor_line" => self.cursor_line = boolean(value, key)?,
+                "cursor_column" => self.cursor_column = boolean(value, key)?,
+                "blame" => self.blame = boolean(value, key)?,
+                "scroll_off" => self.scroll_off = integer(value, key)?,
+                "writing_width" => self.writing_width = integer(value, key)?.max(1),
+                "scrollbar" => self.scrollbar = boolean(value, key)?,
+                "minimap" => self.minimap = boolean(value, key)?,
+                "kitty_keyboard" => self.kitty_keyboard = boolean(value, key)?,
+                "dim_unfocused" => self.dim_unfocused = boolean(value, key)?,
+                "syntax" => self.syntax = boolean(value, key)?,
+                "rainbow_brackets" => self.rainbow_brackets = boolean(value, key)?,
+                "color_swatches" => self.color_swatches = boolean(value, key)?,
+                "column_view" => self.column_view = boolean(value, key)?,
+                "elastic_tabstops" => self.elastic_tabstops = boolean(value, key)?,
+                "soft_tab_motion" => self.soft_tab_motion = boolean(value, key)?,
+                "scroll_bind" => self.scroll_bind = boolean(value, key)?,
+                "sticky_column" => self.sticky_column = boolean(value, key)?,
+                "smooth_scroll" => {
+                    let millis = integer(value, key)?;
+                    self.smooth_scroll = (millis > 0)
+                        .then(|| Duration::from_millis(u64::try_from(millis).unwrap_or(u64::MAX)));
+                }
+                "lint" => self.lint = boolean(value, key)?,
+                "bidi" => {
+                    let name = string(value, key)?;
+                    self.bidi = Direction::from_name(name).ok_or_else(|| {
+                        format!("unknown bidi direction `{}` (off, auto, ltr, rtl)", name)
+                    })?;
+                }
+                "status_bar.left" => self.status_bar.left = segments(value, key)?,
+                "status_bar.right" => self.status_bar.right = segments(value, key)?,
+                "spell" => self.spell = boolean(value, key)?,
+                "spell_lang" => self.spell_lang = string(value, key)?.to_string(),
+                "thesaurus" => self.thesaurus = Some(paths::expand_home(string(value, key)?)),
+                "word_count" => self.word_count = boolean(value, key)?,
+                "large_file_size" => self.large_file_size = integer(value, key)?,
+                "large_file_line_length" => self.large_file_line_length = integer(value, key)?,
+                "search_index" => self.search_index = boolean(value, key)?,
+                "journal" => self.journal = boolean(value, key)?,
+                "diff_algorithm" => {
+                    let name = string(value, key)?;
+                    self.diff_algorithm = Algorithm::from_name(name).ok_or_else(|| {
+                        format!("unknown diff algorithm `{}` (myers, patience)", name)
+                    })?;
+                }
+                "search_case" => {
+                    let name = string(value, key)?;
+                    self.search_case = Case::from_name(name).ok_or_else(|| {
+                        format!(
+                            "unknown search case `{}` (sensitive, insensitive, smart)",
+                            name
+                        )
+                    })?;
+                }
+                "search_whole_word" => self.search_whole_word = boolean(value, key)?,
+                "search_regex" => self.search_regex = boolean(value, key)?,
+                "make" => self.make = string(value, key)?.to_string(),
+                "opener" => self.opener = string(value, key)?.to_string(),
+                "error_format" => {
+                    let name = string(value, key)?;
+                    self.error_format = Some(
+                        Tool::from_name(name)
+                            .ok_or_else(|| format!("unknown error format `{}`", name))?,
+                    );
+                }
+                "keymap" => {
+                    self.keymap = match string(value, key)? {
+                        "default" => Keymap::Default,
+                        "vim" => Keymap::Vim,
+                        other => return Err(format!("unknown keymap `{}`", other)),
+                    }
+                }
+                "auto_save" => {
+                    let seconds = integer(value, key)?;
+                    self.auto_save = (seconds > 0)
+                        .then(|| Duration::from_secs(u64::try_from(seconds).unwrap_or_default()));
+                }
+                "quit_times" => {
+                    self.quit_times = u8::try_from(integer(value, key)?).unwrap_or(u8::MAX);
+                }
+                "leader" => match commands::parse_key(string(value, key)?) {
+                    Some(Key::Char(c)) if c == ' ' || c.is_ascii_graphic() => {
+                        self.bindings.set_leader(c);
+                    }
+                    _ => return Err(format!("`{}` must be a printable key or space", key)),
+                },
+                "hint_delay" => {
+                    let millis = integer(value, key)?;
+                    self.hint_delay = (millis > 0)
+                        .then(|| Duration::from_millis(u64::try_from(millis).unwrap_or(u64::MAX)));
+                }
+                "modelines" => self.modelines = boolean(value, key)?,
+                "age_identity" => {
+                    self.age_identity = Some(paths::expand_home(string(value, key)?));
+                }
+                "log" => log::set_filter(string(value, key)?)?,
+                "backup.enabled" => self.backup.enabled = boolean(value, key)?,
+                "backup.dir" => {
+                    self.backup.dir = Some(paths::expand_home(string(value, key)?));
+                    self.backup.enabled = true;
+                }
+                "backup.keep" => self.backup.keep = integer(value, key)?,
+                "save.trim_trailing_whitespace" => {
+                    self.save_options.trim_trailing_whitespace = boolean(value, key)?;
+                }
+                "save.insert_final_newline" => {
+                    self.save_options.insert_final_newline = boolean(value, key)?;
+                }
+                "save.trim_trailing_blank_lines" => {
+                    self.save_options.trim_trailing_blank_lines = boolean(value, key)?;
+                }
+                _ if key.starts_with("colors.") => {
+                    let name = key.trim_start_matches("colors.");
+                    let color = theme::parse_color(string(value, key)?)
+                        .ok_or_else(|| format!("`{}` must be a #rrggbb color", key))?;
+                    if !self.theme.set_color(name, color) {
+                        return Err(format!("unknown color `{}`", name));
+                    }
+                }
+                _ if key.starts_with("abbreviations.") => {
+                    self.abbreviations.insert(
+                        key.trim_start_matches("abbreviations.").to_string(),
+                        string(value, key)?.to_string(),
+                    );
+                }
+                _ if key.starts_with("keys.") => {
+                    let id = key.trim_start_matches("keys.");
+                    let command = commands::find_by_id(id)
+                        .ok_or_else(|| format!("unknown command `{}`", id))?
+                        .command;
+                    let names = match value {
+                        Value::Array(items) => items
+                            .iter()
+                            .map(|item| string(item, key))
+                            .collect::<Result<Vec<_>, _>>()?,
+                        _ => vec![string(value, key)?],
+                    };
+                    // Printable keys make normal mode sequences; the rest bind in every mode.
+                    let mut keys = Vec::new();
+                    let mut sequences = Vec::new();
+                    for name in names.iter().filter(|name| !name.is_empty()) {
+                        match commands::parse_key(name) {
+                            Some(Key::Char(c)) if c == ' ' || c.is_ascii_graphic() => {
+                                sequences.push(c.to_string());
+                            }
+                            Some(bound) => keys.push(bound),
+                            None => {
+                                sequences.push(commands::parse_sequence(name).ok_or_else(|| {
+                                    format!("unknown key `{}` in `{}`", name, key)
+                                })?)
+                            }
+                        }
+                    }
+                    self.bindings.rebind(command, &keys, &sequences);
+                }
+                _ if key.starts_with("hooks.") => {
+                    let name = key.trim_start_matches("hooks.");
+                    let event = Event::from_name(name)
+                        .ok_or_else(|| format!("unknown event `{}`", name))?;
+                    let commands = match value {
+                        Value::Array(items) => items
+                            .iter()
+                            .map(|item| string(item, key))
+                            .collect::<Result<Vec<_>, _>>()?,
+                        _ => vec![string(value, key)?],
+                    };
+                    for command in commands {
+                        self.hooks.on(event, Action::Command(command.to_string()));
+                    }
+                }
+                _ if key.starts_with("filetype.") => {
+                    let (name, setting) = key
+                        .trim_start_matches("filetype.")
+                        .split_once('.')
+                        .ok_or_else(|| format!("`{}` must be a [filetype.<name>] table", key))?;
+                    self.filetypes
+                        .entry(name.to_string())
+                        .or_default()
+                        .set(setting, value, key)?;
+                }
+                _ => return Err(format!("unknown setting `{}`", key)),
+            }
+        }
+        Ok(())
+    }
+    /// Environment variables predate the config file and still take precedence over it.
+    fn apply_env(&mut self) {
+        if let Ok(seconds) = env::var("HECTO_AUTO_SAVE") {
+            if let Ok(seconds) = seconds.parse::<u64>() {
+                self.auto_save = (seconds > 0).then(|| Duration::from_secs(seconds));
+            }
+        }
+        if let Ok(filter) = env::var("HECTO_LOG") {
+            let _ = log::set_filter(&filter);
+        }
+        if env::var("HECTO_BACKUP").is_ok_and(|value| value == "1") {
+            self.backup.enabled = true;
+        }
+        if let Some(dir) = env::var_os("HECTO_BACKUP_DIR") {
+            self.backup.dir = Some(PathBuf::from(dir));
+            self.backup.enabled = true;
+        }
+        if let Some(keep) = env::var("HECTO_BACKUP_KEEP")
+            .ok()
+            .and_then(|keep| keep.parse().ok())
+        {
+            self.backup.keep = keep;
+        }
+        if let Ok(value) = env::var("HECTO_SAVE_CLEANUP") {
+            for item in value.split(',') {
+                self.save_options.enable(item.trim());
+            }
+        }
+    }
+}
+
+fn string<'a>(value: &'a Value, key: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => Err(expected(value, key, "a string")),
+    }
+}
+
+fn indent_style(value: &Value, key: &str) -> Result<bool, String> {
+    match string(value, key)? {
+        "tab" => Ok(true),
+        "space" => Ok(false),
+        other => Err(format!("unknown indent_style `{}`", other)),
+    }
+}
+
+/// Reads and parses the config file at `path`, keeping its text. `None` when missing.
+fn read_file(path: &Path) -> Result<Option<(String, Table)>, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => {
+            let table =
+                toml::parse(&text).map_err(|error| format!("{}: {}", path.display(), error))?;
+            Ok(Some((text, table)))
+        }
+        Err(error) if error.kind() == ErrorKind::NotFound => Ok(None),
+        Err(error) => Err(format!("{}: {}", path.display(), error)),
+    }
+}
+
+/// Whether the setting `key` names a program to run, or ex commands, which can too.
+fn runs_programs(key: &str) -> bool {
+    matches!(key, "make" | "opener")
+        || key.starts_with("hooks.")
+        || key.starts_with("filetype.")
+            && (key.ends_with(".formatter") || key.ends_with(".language_server"))
+}
+
+fn boolean(value: &Value, key: &str) -> Result<bool, String> {
+    match value {
+        Value::Boolean(boolean) => Ok(*boolean),
+        _ => Err(expected(value, key, "a boolean")),
+    }
+}
+
+fn integer(value: &Value, key: &str) -> Result<usize, String> {
+    match value {
+        Value::Integer(integer) => {
+            usize::try_from(*integer).map_err(|_| format!("`{}` must not be negative", key))
+        }
+        _ => Err(expected(value, key, "an integer")),
+    }
+}
+
+/// One column or an array of them; 0 stands for none.
+fn columns(value: &Value, key: &str) -> Result<Vec<usize>, String> {
+    let columns = match value {
+        Value::Array(items) => items
+            .iter()
+            .map(|item| integer(item, key))
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => vec![integer(value, key)?],
+    };
+    Ok(columns.into_iter().filter(|column| *column > 0).collect())
+}
+
+/// An array of status bar segment names.
+fn segments(value: &Value, key: &str) -> Result<Vec<Segment>, String> {
+    match value {
+        Value::Array(items) => statusline::parse(
+            &items
+                .iter()
+                .map(|item| string(item, key))
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+        _ => Err(expected(value, key, "an array")),
+    }
+}
+
+fn expected(value: &Value, key: &str, what: &str) -> String {
+    format!("`{}` must be {}, not {}", key, what, value.type_name())
+}
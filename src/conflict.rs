@@ -0,0 +1,91 @@
+/// A merge conflict left in a file by git, as the line numbers of its markers.
+#[derive(Clone, Copy)]
+pub struct Conflict {
+    /// The `<<<<<<<` line opening our side.
+    pub start: usize,
+    /// The `|||||||` line opening the common ancestor, in diff3 style conflicts.
+    pub base: Option<usize>,
+    /// The `=======` line between the sides.
+    pub separator: usize,
+    /// The `>>>>>>>` line closing their side.
+    pub end: usize,
+}
+
+/// Which lines of a conflict to keep.
+#[derive(Clone, Copy)]
+pub enum Resolution {
+    Ours,
+    Theirs,
+    Both,
+}
+
+/// The part of a conflict a line belongs to, for highlighting.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Ours,
+    Base,
+    Theirs,
+    Marker,
+}
+
+impl Conflict {
+    pub fn contains(&self, y: usize) -> bool {
+        self.start <= y && y <= self.end
+    }
+    pub fn section(&self, y: usize) -> Option<Section> {
+        if !self.contains(y) {
+            None
+        } else if y == self.start || Some(y) == self.base || y == self.separator || y == self.end {
+            Some(Section::Marker)
+        } else if y > self.separator {
+            Some(Section::Theirs)
+        } else if self.base.is_some_and(|base| y > base) {
+            Some(Section::Base)
+        } else {
+            Some(Section::Ours)
+        }
+    }
+    /// The lines replacing the whole conflict once it is resolved.
+    pub fn resolve<'a>(&self, lines: &[&'a str], resolution: Resolution) -> Vec<&'a str> {
+        let ours_end = self.base.unwrap_or(self.separator);
+        let ours = lines
+            .get(self.start.saturating_add(1)..ours_end)
+            .unwrap_or_default();
+        let theirs = lines
+            .get(self.separator.saturating_add(1)..self.end)
+            .unwrap_or_default();
+        match resolution {
+            Resolution::Ours => ours.to_vec(),
+            Resolution::Theirs => theirs.to_vec(),
+            Resolution::Both => ours.iter().chain(theirs).copied().collect(),
+        }
+    }
+}
+
+/// The complete conflicts in `lines`, in order. Unterminated markers are ignored.
+pub fn find(lines: &[&str]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    let mut open: Option<(usize, Option<usize>, Option<usize>)> = None;
+    for (y, line) in lines.iter().enumerate() {
+        if line.starts_with("<<<<<<<") {
+            open = Some((y, None, None));
+        } else if let Some((start, base, separator)) = open {
+            if line.starts_with("|||||||") && base.is_none() && separator.is_none() {
+                open = Some((start, Some(y), None));
+            } else if line.starts_with("=======") && separator.is_none() {
+                open = Some((start, base, Some(y)));
+            } else if line.starts_with(">>>>>>>") {
+                if let Some(separator) = separator {
+                    conflicts.push(Conflict {
+                        start,
+                        base,
+                        separator,
+                        end: y,
+                    });
+                }
+                open = None;
+            }
+        }
+    }
+    conflicts
+}
@@ -0,0 +1,303 @@
+use std::fs::File;
+use std::io::{Error, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// How many times a wrong passphrase may be entered before opening gives up.
+const PASSPHRASE_TRIES: usize = 3;
+
+/// A program files are encrypted with.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Tool {
+    Gpg,
+    Age,
+}
+
+impl Tool {
+    /// Recognizes an encrypted file from its first bytes, or for binary OpenPGP data,
+    /// which has no signature to speak of, from its extension as well.
+    pub fn detect(file_name: &str, bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"age-encryption.org/v1\n")
+            || bytes.starts_with(b"-----BEGIN AGE ENCRYPTED FILE-----")
+        {
+            return Some(Self::Age);
+        }
+        if bytes.starts_with(b"-----BEGIN PGP MESSAGE-----") {
+            return Some(Self::Gpg);
+        }
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|extension| extension.to_str());
+        // The first packet of an encrypted message holds a session key, encrypted to a
+        // public key (tag 1) or with a passphrase (tag 3).
+        let tag = bytes.first().map(|byte| {
+            if byte & 0x40 == 0 {
+                (byte >> 2) & 0x0f
+            } else {
+                byte & 0x3f
+            }
+        });
+        (matches!(extension, Some("gpg" | "pgp")) && matches!(tag, Some(1 | 3)))
+            .then_some(Self::Gpg)
+    }
+}
+
+/// Looks at the start of a file on disk for signs of encryption.
+pub fn detect_file(file_name: &str) -> Option<Tool> {
+    let mut head = Vec::with_capacity(64);
+    File::open(file_name)
+        .and_then(|file| file.take(64).read_to_end(&mut head))
+        .ok()?;
+    Tool::detect(file_name, &head)
+}
+
+/// What a buffer was decrypted with, kept in memory only so that saving can encrypt
+/// it the same way again.
+#[derive(Clone)]
+pub struct Encryption {
+    tool: Tool,
+    /// The gpg passphrase, for files encrypted with one or to a key that needs one.
+    passphrase: Option<String>,
+    /// The gpg key ids the file was encrypted to; none for a passphrase.
+    recipients: Vec<String>,
+    /// The age identity file, which also gives the recipient to encrypt to.
+    identity: Option<PathBuf>,
+    /// Whether the file is ASCII armored rather than binary.
+    armor: bool,
+}
+
+impl Drop for Encryption {
+    fn drop(&mut self) {
+        if let Some(passphrase) = &mut self.passphrase {
+            // SAFETY: zero bytes are valid UTF-8.
+            unsafe { passphrase.as_bytes_mut() }.fill(0);
+        }
+    }
+}
+
+/// Decrypts `bytes`, asking for a passphrase or identity file with `ask`, which is
+/// given a prompt and whether the answer is secret and returns `None` when the user
+/// gives up. `identity` is the configured age identity file.
+///
+/// # Errors
+///
+/// Fails with the tool's message when the file cannot be decrypted.
+pub fn open(
+    tool: Tool,
+    bytes: &[u8],
+    identity: Option<&Path>,
+    ask: &mut dyn FnMut(&str, bool) -> Option<String>,
+) -> Result<(Vec<u8>, Encryption), String> {
+    let mut encryption = Encryption {
+        tool,
+        passphrase: None,
+        recipients: Vec::new(),
+        identity: None,
+        armor: bytes.starts_with(b"-----BEGIN"),
+    };
+    match tool {
+        Tool::Age => {
+            let identity = match identity {
+                Some(identity) => identity.to_path_buf(),
+                None => ask("age identity file: ", false)
+                    .map(|path| crate::paths::expand_home(path.trim()))
+                    .ok_or_else(|| "no identity given".to_string())?,
+            };
+            encryption.identity = Some(identity);
+            let plaintext = encryption
+                .decrypt(bytes)
+                .map_err(|error| error.to_string())?;
+            Ok((plaintext, encryption))
+        }
+        Tool::Gpg => {
+            // Keys the agent holds unlocked need no passphrase, so try without first.
+            let (result, status) = gpg_decrypt(bytes, None);
+            encryption.recipients = status
+                .lines()
+                .filter_map(|line| line.strip_prefix("[GNUPG:] ENC_TO "))
+                .filter_map(|rest| rest.split_whitespace().next())
+                .map(str::to_string)
+                .collect();
+            let symmetric = status.contains("NEED_PASSPHRASE_SYM");
+            if let (Ok(plaintext), false) = (&result, symmetric) {
+                return Ok((plaintext.clone(), encryption));
+            }
+            let mut error = result.err().unwrap_or_default();
+            for _ in 0..PASSPHRASE_TRIES {
+                let passphrase =
+                    ask("Passphrase: ", true).ok_or_else(|| "no passphrase given".to_string())?;
+                match gpg_decrypt(bytes, Some(&passphrase)).0 {
+                    Ok(plaintext) => {
+                        encryption.passphrase = Some(passphrase);
+                        return Ok((plaintext, encryption));
+                    }
+                    Err(message) => error = message,
+                }
+            }
+            Err(error)
+        }
+    }
+}
+
+impl Encryption {
+    /// Decrypts the file again with what was given when it was opened, as when it is
+    /// reloaded.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the tool's message.
+    pub fn decrypt(&self, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+        match self.tool {
+            Tool::Gpg => gpg_decrypt(bytes, self.passphrase.as_deref())
+                .0
+                .map_err(Error::other),
+            Tool::Age => {
+                let mut command = Command::new("age");
+                command.arg("--decrypt");
+                if let Some(identity) = &self.identity {
+                    command.arg("--identity").arg(identity);
+                }
+                run(&mut command, None, bytes)
+            }
+        }
+    }
+    /// Encrypts `plaintext` the way the file was encrypted when it was opened.
+    ///
+    /// # Errors
+    ///
+    /// Fails with the tool's message.
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Vec<u8>, Error> {
+        match self.tool {
+            Tool::Gpg => {
+                let mut command = Command::new("gpg");
+                command.args(["--batch", "--quiet", "--yes", "--output", "-"]);
+                if self.armor {
+                    command.arg("--armor");
+                }
+                if self.recipients.is_empty() {
+                    command.args([
+                        "--pinentry-mode",
+                        "loopback",
+                        "--passphrase-fd",
+                        "0",
+                        "--symmetric",
+                    ]);
+                    run(&mut command, self.passphrase.as_deref(), plaintext)
+                } else {
+                    command.args(["--trust-model", "always", "--encrypt"]);
+                    for recipient in &self.recipients {
+                        command.arg("--recipient").arg(recipient);
+                    }
+                    run(&mut command, None, plaintext)
+                }
+            }
+            Tool::Age => {
+                let mut command = Command::new("age");
+                command.arg("--encrypt");
+                if self.armor {
+                    command.arg("--armor");
+                }
+                if let Some(identity) = &self.identity {
+                    command.arg("--identity").arg(identity);
+                }
+                run(&mut command, None, plaintext)
+            }
+        }
+    }
+    /// How the buffer is encrypted, for messages.
+    pub fn describe(&self) -> String {
+        match (self.tool, self.recipients.len()) {
+            (Tool::Gpg, 0) => "gpg with a passphrase".to_string(),
+            (Tool::Gpg, 1) => "gpg to 1 key".to_string(),
+            (Tool::Gpg, keys) => format!("gpg to {} keys", keys),
+            (Tool::Age, _) => "age".to_string(),
+        }
+    }
+}
+
+/// Decrypts with gpg, giving the passphrase if there is one and never asking for one
+/// otherwise. Returns the plaintext or gpg's message, and gpg's status lines.
+fn gpg_decrypt(bytes: &[u8], passphrase: Option<&str>) -> (Result<Vec<u8>, String>, String) {
+    let mut command = Command::new("gpg");
+    command.args([
+        "--batch",
+        "--quiet",
+        "--no-symkey-cache",
+        "--status-fd",
+        "2",
+        "--pinentry-mode",
+    ]);
+    if passphrase.is_some() {
+        command.args(["loopback", "--passphrase-fd", "0"]);
+    } else {
+        command.arg("error");
+    }
+    command.arg("--decrypt");
+    match output(&mut command, passphrase, bytes) {
+        Ok((stdout, stderr, true)) => (Ok(stdout), stderr),
+        Ok((_, stderr, false)) => (Err(message(&stderr)), stderr),
+        Err(error) => (Err(format!("gpg: {}", error)), String::new()),
+    }
+}
+
+/// Runs `command` with `input` on stdin, after `passphrase` on a line of its own, and
+/// returns its output.
+fn run(command: &mut Command, passphrase: Option<&str>, input: &[u8]) -> Result<Vec<u8>, Error> {
+    let program = command.get_program().to_string_lossy().to_string();
+    match output(command, passphrase, input) {
+        Ok((stdout, _, true)) => Ok(stdout),
+        Ok((_, stderr, false)) => Err(Error::other(message(&stderr))),
+        Err(error) => Err(Error::new(error.kind(), format!("{}: {}", program, error))),
+    }
+}
+
+/// Runs `command`, writing its input on another thread so that neither side blocks
+/// on a full pipe. Returns stdout, stderr and whether it succeeded.
+fn output(
+    command: &mut Command,
+    passphrase: Option<&str>,
+    input: &[u8],
+) -> Result<(Vec<u8>, String, bool), Error> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let (mut stdin, mut stdout, mut stderr) =
+        match (child.stdin.take(), child.stdout.take(), child.stderr.take()) {
+            (Some(stdin), Some(stdout), Some(stderr)) => (stdin, stdout, stderr),
+            _ => return Err(Error::other("no pipes to the program")),
+        };
+    let mut data = Vec::with_capacity(input.len().saturating_add(64));
+    if let Some(passphrase) = passphrase {
+        data.extend_from_slice(passphrase.as_bytes());
+        data.push(b'\n');
+    }
+    data.extend_from_slice(input);
+    let writer = thread::spawn(move || {
+        let _ = stdin.write_all(&data);
+        data.fill(0);
+    });
+    let errors = thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    });
+    let mut output = Vec::new();
+    stdout.read_to_end(&mut output)?;
+    let errors = errors.join().unwrap_or_default();
+    let _ = writer.join();
+    let status = child.wait()?;
+    Ok((output, errors, status.success()))
+}
+
+/// The last line a tool wrote that is not a gpg status line.
+fn message(stderr: &str) -> String {
+    stderr
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty() && !line.starts_with("[GNUPG:]"))
+        .unwrap_or("failed")
+        .to_string()
+}
@@ -0,0 +1,102 @@
+use crate::unicode;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The delimiter of a file type laid out in columns.
+pub fn delimiter(file_type: Option<&str>) -> Option<char> {
+    match file_type? {
+        "csv" => Some(','),
+        "tsv" => Some('\t'),
+        _ => None,
+    }
+}
+
+/// The cells of `line` as grapheme ranges, without their delimiters. Delimiters
+/// inside double quotes belong to the cell.
+pub fn cells(line: &str, delimiter: char) -> Vec<(usize, usize)> {
+    let mut cells = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    let mut length = 0;
+    for (index, grapheme) in line.graphemes(true).enumerate() {
+        length = index.saturating_add(1);
+        if grapheme == "\"" {
+            quoted = !quoted;
+        } else if !quoted && grapheme.starts_with(delimiter) {
+            cells.push((start, index));
+            start = index.saturating_add(1);
+        }
+    }
+    cells.push((start, length.max(start)));
+    cells
+}
+
+/// A delimited buffer drawn with its columns lined up, without touching its text:
+/// each cell is padded to the widest in its column.
+#[derive(Default)]
+pub struct ColumnView {
+    widths: Vec<usize>,
+    /// Screen columns scrolled off the left edge; one offset keeps every row aligned.
+    pub offset: usize,
+    /// The buffer name and revision the widths match.
+    pub revision: Option<(Option<String>, usize)>,
+}
+
+impl ColumnView {
+    pub fn update(&mut self, lines: &[&str], delimiter: char, revision: (Option<String>, usize)) {
+        self.widths.clear();
+        for line in lines {
+            let graphemes: Vec<&str> = line.graphemes(true).collect();
+            for (index, (start, end)) in cells(line, delimiter).into_iter().enumerate() {
+                let width = graphemes
+                    .get(start..end)
+                    .map_or(0, |cell| cell.iter().map(|g| grapheme_width(g)).sum());
+                match self.widths.get_mut(index) {
+                    Some(column) => *column = (*column).max(width),
+                    None => self.widths.push(width),
+                }
+            }
+        }
+        self.revision = Some(revision);
+    }
+    /// The screen column where cell `index` starts: the cells before it, each padded
+    /// and followed by its delimiter and a space.
+    pub fn cell_start(&self, index: usize) -> usize {
+        self.widths
+            .iter()
+            .take(index)
+            .map(|width| width.saturating_add(2))
+            .sum()
+    }
+    pub fn width(&self, index: usize) -> usize {
+        self.widths.get(index).copied().unwrap_or(0)
+    }
+    /// The screen column of grapheme `x` of `line`, before scrolling.
+    pub fn column(&self, line: &str, delimiter: char, x: usize) -> usize {
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
+        let cells = cells(line, delimiter);
+        let index = cell_at(&cells, x);
+        let (start, end) = cells.get(index).copied().unwrap_or_default();
+        let within: usize = graphemes
+            .get(start..x.min(end).max(start))
+            .map_or(0, |cell| cell.iter().map(|g| grapheme_width(g)).sum());
+        if x >= end && index.saturating_add(1) < cells.len() {
+            // The delimiter after the cell sits past its padding.
+            self.cell_start(index).saturating_add(self.width(index))
+        } else {
+            self.cell_start(index).saturating_add(within)
+        }
+    }
+}
+
+/// The index of the cell holding grapheme `x`; a delimiter belongs to the cell
+/// before it.
+pub fn cell_at(cells: &[(usize, usize)], x: usize) -> usize {
+    cells
+        .iter()
+        .position(|(_, end)| x <= *end)
+        .unwrap_or_else(|| cells.len().saturating_sub(1))
+}
+
+pub fn grapheme_width(grapheme: &str) -> usize {
+    grapheme.chars().next().map_or(0, unicode::width)
+}
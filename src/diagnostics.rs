@@ -0,0 +1,77 @@
+use crate::json::Value;
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+    Information,
+    Hint,
+}
+
+impl Severity {
+    fn from_lsp(value: Option<usize>) -> Self {
+        match value {
+            Some(2) => Self::Warning,
+            Some(3) => Self::Information,
+            Some(4) => Self::Hint,
+            _ => Self::Error,
+        }
+    }
+    /// The letter shown in the sign column and the message bar.
+    pub fn sign(self) -> char {
+        match self {
+            Self::Error => 'E',
+            Self::Warning => 'W',
+            Self::Information => 'I',
+            Self::Hint => 'H',
+        }
+    }
+}
+
+/// A language server or lint diagnostic; positions are LSP lines and UTF-16 offsets.
+pub struct Diagnostic {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub severity: Severity,
+    pub message: String,
+    /// The diagnostic as published, sent back when asking for code actions.
+    pub raw: Value,
+    /// Whether the built-in linter found it rather than a language server.
+    pub lint: bool,
+}
+
+/// Reads `textDocument/publishDiagnostics` params into the document URI and its
+/// diagnostics, ordered by position.
+pub fn parse(params: &Value) -> Option<(String, Vec<Diagnostic>)> {
+    let uri = params.get("uri")?.as_str()?.to_string();
+    let position = |value: Option<&Value>| -> Option<(usize, usize)> {
+        let value = value?;
+        Some((
+            value.get("line")?.as_usize()?,
+            value.get("character")?.as_usize()?,
+        ))
+    };
+    let mut diagnostics: Vec<Diagnostic> = params
+        .get("diagnostics")?
+        .as_array()?
+        .iter()
+        .filter_map(|diagnostic| {
+            let range = diagnostic.get("range")?;
+            let message = diagnostic.get("message")?.as_str()?;
+            let source = diagnostic.get("source").and_then(Value::as_str);
+            Some(Diagnostic {
+                start: position(range.get("start"))?,
+                end: position(range.get("end"))?,
+                severity: Severity::from_lsp(diagnostic.get("severity").and_then(Value::as_usize)),
+                message: match source {
+                    Some(source) => format!("{}: {}", source, message),
+                    None => message.to_string(),
+                },
+                raw: diagnostic.clone(),
+                lint: false,
+            })
+        })
+        .collect();
+    diagnostics.sort_by(|a, b| a.start.cmp(&b.start).then(a.severity.cmp(&b.severity)));
+    Some((uri, diagnostics))
+}
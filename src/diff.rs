@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+
+/// Edit distance beyond which the changed region is treated as one block.
+const MAX_EDITS: usize = 4000;
+
+/// How the lines two texts have in common are found.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Algorithm {
+    /// A shortest edit script, as `diff` finds by default.
+    Myers,
+    /// Lines that occur once in each text are lined up first, and the gaps between
+    /// them diffed on their own. Moved blocks and reordered functions read better,
+    /// though the script may be longer.
+    Patience,
+}
+
+impl Algorithm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "myers" => Some(Self::Myers),
+            "patience" => Some(Self::Patience),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Myers => "myers",
+            Self::Patience => "patience",
+        }
+    }
+}
+
+/// A run of `old_len` lines at `old_start` replaced by `new_len` lines at `new_start`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+}
+
+/// The hunks that turn `old` into `new`, in order, from a shortest edit script.
+/// Lines are compared without their endings, so callers split text with
+/// `str::lines` and a file that only switched between `\n` and `\r\n` has no hunks.
+pub fn lines(old: &[&str], new: &[&str]) -> Vec<Hunk> {
+    lines_with(old, new, Algorithm::Myers)
+}
+
+/// Like `lines`, with the common lines found by `algorithm`.
+pub fn lines_with(old: &[&str], new: &[&str], algorithm: Algorithm) -> Vec<Hunk> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old
+        .iter()
+        .skip(prefix)
+        .rev()
+        .zip(new.iter().skip(prefix).rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = old
+        .get(prefix..old.len().saturating_sub(suffix))
+        .unwrap_or_default();
+    let b = new
+        .get(prefix..new.len().saturating_sub(suffix))
+        .unwrap_or_default();
+    let matches = match algorithm {
+        Algorithm::Myers => common_lines(a, b).unwrap_or_default(),
+        Algorithm::Patience => {
+            let mut matches = Vec::new();
+            patience(a, b, (0, 0), &mut matches);
+            matches
+        }
+    };
+    let mut hunks = Vec::new();
+    let (mut x, mut y) = (0, 0);
+    for (match_x, match_y) in matches
+        .into_iter()
+        .chain(std::iter::once((a.len(), b.len())))
+    {
+        if match_x > x || match_y > y {
+            hunks.push(Hunk {
+                old_start: prefix.saturating_add(x),
+                old_len: match_x.saturating_sub(x),
+                new_start: prefix.saturating_add(y),
+                new_len: match_y.saturating_sub(y),
+            });
+        }
+        x = match_x.saturating_add(1);
+        y = match_y.saturating_add(1);
+    }
+    hunks
+}
+
+/// The line of `new` that old line `y` ends up on once `hunks` are applied. Lines
+/// inside a hunk keep their offset into it, clamped to its new lines.
+pub fn map_line(hunks: &[Hunk], y: usize) -> usize {
+    let mut shifted = y;
+    for hunk in hunks {
+        let old_end = hunk.old_start.saturating_add(hunk.old_len);
+        if y >= old_end {
+            shifted = shifted
+                .saturating_add(hunk.new_len)
+                .saturating_sub(hunk.old_len);
+        } else if y >= hunk.old_start {
+            let offset = y
+                .saturating_sub(hunk.old_start)
+                .min(hunk.new_len.saturating_sub(1));
+            return hunk.new_start.saturating_add(offset);
+        }
+    }
+    shifted
+}
+
+/// Pairs of equal lines `(index in a, index in b)` on a longest common subsequence,
+/// found with Myers' algorithm in linear space. `None` when the inputs differ too much.
+fn common_lines(a: &[&str], b: &[&str]) -> Option<Vec<(usize, usize)>> {
+    let mut matches = Vec::new();
+    let whole = Region {
+        left: 0,
+        top: 0,
+        right: a.len(),
+        bottom: b.len(),
+    };
+    // Each half of the search covers half of the edits.
+    let limit = MAX_EDITS.saturating_add(1) / 2;
+    if common_in(a, b, whole, Some(limit), &mut matches) {
+        Some(matches)
+    } else {
+        None
+    }
+}
+
+/// Lines `left..right` of `a` and `top..bottom` of `b`.
+#[derive(Clone, Copy)]
+struct Region {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+}
+
+/// Adds the equal lines of `region` to `matches`, in order: the middle snake of a
+/// shortest edit script splits the region in two, which are done the same way.
+/// Returns false when the middle snake takes more than `limit` edits to reach.
+fn common_in(
+    a: &[&str],
+    b: &[&str],
+    region: Region,
+    limit: Option<usize>,
+    matches: &mut Vec<(usize, usize)>,
+) -> bool {
+    if region.left == region.right || region.top == region.bottom {
+        return true;
+    }
+    let snake = match middle_snake(a, b, region, limit) {
+        Some(snake) => snake,
+        None => return false,
+    };
+    let before = Region {
+        right: snake.start.0,
+        bottom: snake.start.1,
+        ..region
+    };
+    let after = Region {
+        left: snake.end.0,
+        top: snake.end.1,
+        ..region
+    };
+    common_in(a, b, before, None, matches);
+    let (x, y) = snake.diagonal;
+    matches.extend((0..snake.length).map(|i| (x.saturating_add(i), y.saturating_add(i))));
+    common_in(a, b, after, None, matches);
+    true
+}
+
+/// Where the forward and backward searches of a region meet: one edit at most and
+/// `length` equal lines from `diagonal`, between `start` and `end`.
+struct Snake {
+    start: (usize, usize),
+    end: (usize, usize),
+    diagonal: (usize, usize),
+    length: usize,
+}
+
+/// Searches `region` from both corners at once, one edit further each round, until
+/// the paths overlap; `None` after `limit` rounds.
+#[allow(
+    clippy::integer_arithmetic,
+    clippy::indexing_slicing,
+    clippy::cast_possible_wrap,
+    clippy::cast_sign_loss,
+    clippy::as_conversions
+)]
+fn middle_snake(a: &[&str], b: &[&str], region: Region, limit: Option<usize>) -> Option<Snake> {
+    let (left, top) = (region.left as isize, region.top as isize);
+    let (right, bottom) = (region.right as isize, region.bottom as isize);
+    let delta = (right - left) - (bottom - top);
+    let max = ((right - left) + (bottom - top) + 1) / 2;
+    let rounds = limit.map_or(max, |limit| max.min(limit as isize));
+    let index = |k: isize| (k + max + 1) as usize;
+    // The furthest x reached forwards on each diagonal k = x - y (from the top left),
+    // and the furthest y reached backwards on each diagonal c = k - delta.
+    let mut forward = vec![0_isize; (2 * max + 3) as usize];
+    let mut backward = vec![0_isize; (2 * max + 3) as usize];
+    forward[index(1)] = left;
+    backward[index(1)] = bottom;
+    for d in 0..=rounds {
+        for k in (-d..=d).rev().step_by(2) {
+            let (previous, mut x) =
+                if k == -d || (k != d && forward[index(k - 1)] < forward[index(k + 1)]) {
+                    let x = forward[index(k + 1)];
+                    (x, x)
+                } else {
+                    let previous = forward[index(k - 1)];
+                    (previous, previous + 1)
+                };
+            let mut y = top + (x - left) - k;
+            let previous_y = if d == 0 || x != previous { y } else { y - 1 };
+            let diagonal = (x as usize, y as usize);
+            while x < right && y < bottom && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[index(k)] = x;
+            let c = k - delta;
+            if delta % 2 != 0 && -d < c && c < d && y >= backward[index(c)] {
+                return Some(Snake {
+                    start: (previous as usize, previous_y as usize),
+                    end: (x as usize, y as usize),
+                    diagonal,
+                    length: x as usize - diagonal.0,
+                });
+            }
+        }
+        for c in (-d..=d).rev().step_by(2) {
+            let (previous, mut y) =
+                if c == -d || (c != d && backward[index(c - 1)] > backward[index(c + 1)]) {
+                    let y = backward[index(c + 1)];
+                    (y, y)
+                } else {
+                    let previous = backward[index(c - 1)];
+                    (previous, previous - 1)
+                };
+            let k = c + delta;
+            let mut x = left + (y - top) + k;
+            let previous_x = if d == 0 || y != previous { x } else { x + 1 };
+            let diagonal_end = x;
+            while x > left && y > top && a[(x - 1) as usize] == b[(y - 1) as usize] {
+                x -= 1;
+                y -= 1;
+            }
+            backward[index(c)] = y;
+            if delta % 2 == 0 && -d <= k && k <= d && x <= forward[index(k)] {
+                return Some(Snake {
+                    start: (x as usize, y as usize),
+                    end: (previous_x as usize, previous as usize),
+                    diagonal: (x as usize, y as usize),
+                    length: (diagonal_end - x) as usize,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Adds to `matches` the pairs of equal lines of `a` and `b` that patience diff lines
+/// up, shifted by `offset` to where the slices start.
+fn patience(a: &[&str], b: &[&str], offset: (usize, usize), matches: &mut Vec<(usize, usize)>) {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a
+        .iter()
+        .skip(prefix)
+        .rev()
+        .zip(b.iter().skip(prefix).rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    matches.extend((0..prefix).map(|i| (offset.0.saturating_add(i), offset.1.saturating_add(i))));
+    let a_end = a.len().saturating_sub(suffix);
+    let b_end = b.len().saturating_sub(suffix);
+    let a_middle = a.get(prefix..a_end).unwrap_or_default();
+    let b_middle = b.get(prefix..b_end).unwrap_or_default();
+    let middle = (
+        offset.0.saturating_add(prefix),
+        offset.1.saturating_add(prefix),
+    );
+    if !a_middle.is_empty() && !b_middle.is_empty() {
+        let anchors = unique_common_lines(a_middle, b_middle);
+        if anchors.is_empty() {
+            // Nothing to line up on: fall back to a shortest edit script.
+            matches.extend(
+                common_lines(a_middle, b_middle)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|(x, y)| (middle.0.saturating_add(x), middle.1.saturating_add(y))),
+            );
+        } else {
+            let (mut x, mut y) = (0, 0);
+            for (anchor_x, anchor_y) in anchors
+                .into_iter()
+                .chain(std::iter::once((a_middle.len(), b_middle.len())))
+            {
+                patience(
+                    a_middle.get(x..anchor_x).unwrap_or_default(),
+                    b_middle.get(y..anchor_y).unwrap_or_default(),
+                    (middle.0.saturating_add(x), middle.1.saturating_add(y)),
+                    matches,
+                );
+                if anchor_x < a_middle.len() {
+                    matches.push((
+                        middle.0.saturating_add(anchor_x),
+                        middle.1.saturating_add(anchor_y),
+                    ));
+                }
+                x = anchor_x.saturating_add(1);
+                y = anchor_y.saturating_add(1);
+            }
+        }
+    }
+    matches.extend((0..suffix).map(|i| {
+        (
+            offset.0.saturating_add(a_end).saturating_add(i),
+            offset.1.saturating_add(b_end).saturating_add(i),
+        )
+    }));
+}
+
+/// The lines that occur exactly once in both `a` and `b`, as `(index in a, index in
+/// b)`, keeping the longest run that is in order in both.
+fn unique_common_lines(a: &[&str], b: &[&str]) -> Vec<(usize, usize)> {
+    // Per line: how often it occurs in `a`, where, how often in `b` and where.
+    let mut counts: HashMap<&str, (usize, usize, usize, usize)> = HashMap::new();
+    for (x, line) in a.iter().enumerate() {
+        let count = counts.entry(line).or_default();
+        count.0 = count.0.saturating_add(1);
+        count.1 = x;
+    }
+    for (y, line) in b.iter().enumerate() {
+        if let Some(count) = counts.get_mut(line) {
+            count.2 = count.2.saturating_add(1);
+            count.3 = y;
+        }
+    }
+    let mut pairs: Vec<(usize, usize)> = counts
+        .values()
+        .filter(|(in_a, _, in_b, _)| *in_a == 1 && *in_b == 1)
+        .map(|(_, x, _, y)| (*x, *y))
+        .collect();
+    pairs.sort_unstable();
+    longest_increasing(&pairs)
+}
+
+/// The longest subsequence of `pairs`, sorted by their first index, whose second
+/// indices increase too, found by patience sorting.
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // The index in `pairs` on top of each pile, and each pair's predecessor.
+    let mut tops: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = Vec::with_capacity(pairs.len());
+    for (index, (_, y)) in pairs.iter().enumerate() {
+        let pile = tops.partition_point(|top| pairs.get(*top).is_some_and(|(_, top_y)| top_y < y));
+        previous.push(
+            pile.checked_sub(1)
+                .and_then(|below| tops.get(below).copied()),
+        );
+        match tops.get_mut(pile) {
+            Some(top) => *top = index,
+            None => tops.push(index),
+        }
+    }
+    let mut result = Vec::new();
+    let mut next = tops.last().copied();
+    while let Some(index) = next {
+        result.extend(pairs.get(index));
+        next = previous.get(index).copied().flatten();
+    }
+    result.reverse();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hunk(old_start: usize, old_len: usize, new_start: usize, new_len: usize) -> Hunk {
+        Hunk {
+            old_start,
+            old_len,
+            new_start,
+            new_len,
+        }
+    }
+
+    /// The length of a longest common subsequence, by dynamic programming.
+    fn lcs_length(a: &[&str], b: &[&str]) -> usize {
+        let mut row = vec![0; b.len() + 1];
+        for line in a {
+            let mut diagonal = 0;
+            for (j, other) in b.iter().enumerate() {
+                let above = row[j + 1];
+                row[j + 1] = if line == other {
+                    diagonal + 1
+                } else {
+                    above.max(row[j])
+                };
+                diagonal = above;
+            }
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn identical_texts_have_no_hunks() {
+        let text = ["a", "b", "c"];
+        assert_eq!(lines(&text, &text), []);
+        assert_eq!(
+            common_lines(&text, &text),
+            Some(vec![(0, 0), (1, 1), (2, 2)])
+        );
+    }
+
+    #[test]
+    fn disjoint_texts_are_one_hunk() {
+        assert_eq!(lines(&["a", "b"], &["x", "y", "z"]), [hunk(0, 2, 0, 3)]);
+        assert_eq!(common_lines(&["a", "b"], &["x", "y", "z"]), Some(vec![]));
+    }
+
+    #[test]
+    fn insertions_only() {
+        assert_eq!(
+            lines(&["a", "b", "c"], &["x", "a", "b", "y", "c", "z"]),
+            [hunk(0, 0, 0, 1), hunk(2, 0, 3, 1), hunk(3, 0, 5, 1)]
+        );
+    }
+
+    #[test]
+    fn deletions_only() {
+        assert_eq!(
+            lines(&["x", "a", "b", "y", "c", "z"], &["a", "b", "c"]),
+            [hunk(0, 1, 0, 0), hunk(3, 1, 2, 0), hunk(5, 1, 3, 0)]
+        );
+    }
+
+    #[test]
+    fn matches_are_a_longest_common_subsequence() {
+        let cases: [(&[&str], &[&str]); 4] = [
+            (
+                &["a", "b", "c", "a", "b", "b", "a"],
+                &["c", "b", "a", "b", "a", "c"],
+            ),
+            (&["a", "x", "b", "x", "c"], &["x", "a", "x", "b", "x"]),
+            (&["a"], &["b", "a", "b"]),
+            (&["a", "b", "a", "b"], &["b", "a", "b", "a", "b", "a"]),
+        ];
+        for (a, b) in cases {
+            let matches = common_lines(a, b).expect("few edits");
+            assert_eq!(matches.len(), lcs_length(a, b), "{:?} {:?}", a, b);
+            assert!(matches
+                .windows(2)
+                .all(|pair| pair[0].0 < pair[1].0 && pair[0].1 < pair[1].1));
+            assert!(matches.iter().all(|&(x, y)| a[x] == b[y]));
+        }
+    }
+
+    #[test]
+    fn too_many_edits_become_one_block() {
+        let old: Vec<String> = (0..MAX_EDITS).map(|i| format!("old {}", i)).collect();
+        let mut new: Vec<String> = (0..MAX_EDITS).map(|i| format!("new {}", i)).collect();
+        new.insert(MAX_EDITS / 2, old[0].clone());
+        let old: Vec<&str> = old.iter().map(String::as_str).collect();
+        let new: Vec<&str> = new.iter().map(String::as_str).collect();
+        assert_eq!(common_lines(&old, &new), None);
+        assert_eq!(lines(&old, &new), [hunk(0, MAX_EDITS, 0, MAX_EDITS + 1)]);
+        // Within the limit the common line is still found.
+        assert_eq!(
+            common_lines(&old[..1000], &new[..MAX_EDITS / 2 + 1]),
+            Some(vec![(0, MAX_EDITS / 2)])
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use crate::diff::{self, Algorithm};
+
+/// How a line of one side relates to the other side.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Same,
+    Removed,
+    Added,
+    /// Paired with a changed line on the other side; `span` marks the differing part.
+    Changed,
+    /// Padding opposite lines the other side does not have.
+    Filler,
+}
+
+/// One side of a screen row, cut to the pane width.
+pub struct Cell {
+    pub text: String,
+    pub kind: Kind,
+    /// Character range differing from the paired line.
+    pub span: Option<(usize, usize)>,
+}
+
+/// Side-by-side comparison of older text (left) with the buffer (right). Both panes
+/// share one list of aligned rows, so they always scroll together.
+pub struct DiffView {
+    pub title: String,
+    old: Vec<String>,
+    new: Vec<String>,
+    /// `(old line, new line)` for each row; `None` is filler.
+    rows: Vec<(Option<usize>, Option<usize>)>,
+    pub selected: usize,
+    pub offset: usize,
+}
+
+impl DiffView {
+    pub fn new(title: String, old: Vec<String>, new: Vec<String>, algorithm: Algorithm) -> Self {
+        let hunks = {
+            let old: Vec<&str> = old.iter().map(String::as_str).collect();
+            let new: Vec<&str> = new.iter().map(String::as_str).collect();
+            diff::lines_with(&old, &new, algorithm)
+        };
+        let mut rows = Vec::new();
+        let (mut x, mut y) = (0, 0);
+        for hunk in &hunks {
+            while x < hunk.old_start && y < hunk.new_start {
+                rows.push((Some(x), Some(y)));
+                x = x.saturating_add(1);
+                y = y.saturating_add(1);
+            }
+            for offset in 0..hunk.old_len.max(hunk.new_len) {
+                rows.push((
+                    (offset < hunk.old_len).then(|| hunk.old_start.saturating_add(offset)),
+                    (offset < hunk.new_len).then(|| hunk.new_start.saturating_add(offset)),
+                ));
+            }
+            x = hunk.old_start.saturating_add(hunk.old_len);
+            y = hunk.new_start.saturating_add(hunk.new_len);
+        }
+        while x < old.len() || y < new.len() {
+            rows.push(((x < old.len()).then_some(x), (y < new.len()).then_some(y)));
+            x = x.saturating_add(1);
+            y = y.saturating_add(1);
+        }
+        Self {
+            title,
+            old,
+            new,
+            rows,
+            selected: 0,
+            offset: 0,
+        }
+    }
+    /// Whether the sides are identical.
+    pub fn is_unchanged(&self) -> bool {
+        (0..self.rows.len()).all(|row| !self.is_change(row))
+    }
+    fn is_change(&self, row: usize) -> bool {
+        match self.rows.get(row) {
+            Some((Some(x), Some(y))) => self.old.get(*x) != self.new.get(*y),
+            Some(_) => true,
+            None => false,
+        }
+    }
+    /// Moves the selection by `delta` rows, keeping it on screen.
+    pub fn move_by(&mut self, delta: isize, height: usize) {
+        self.selected = self
+            .selected
+            .saturating_add_signed(delta)
+            .min(self.rows.len().saturating_sub(1));
+        self.scroll(height);
+    }
+    /// Selects the first row of the next (or previous) block of changes.
+    pub fn goto_change(&mut self, forward: bool, height: usize) {
+        let starts = (0..self.rows.len()).filter(|row| {
+            self.is_change(*row) && (*row == 0 || !self.is_change(row.saturating_sub(1)))
+        });
+        let target = if forward {
+            starts.clone().find(|row| *row > self.selected)
+        } else {
+            starts.clone().rfind(|row| *row < self.selected)
+        };
+        if let Some(row) = target {
+            self.selected = row;
+            self.scroll(height);
+        }
+    }
+    fn scroll(&mut self, height: usize) {
+        if self.selected < self.offset {
+            self.offset = self.selected;
+        } else if self.selected >= self.offset.saturating_add(height) {
+            self.offset = self
+                .selected
+                .saturating_add(1)
+                .saturating_sub(height.max(1));
+        }
+    }
+    /// The row showing buffer line `y`.
+    pub fn row_of_buffer_line(&self, y: usize) -> usize {
+        self.rows
+            .iter()
+            .position(|(_, new)| *new == Some(y))
+            .unwrap_or(0)
+    }
+    /// The buffer line of the selected row, or of the nearest row above it that has one.
+    pub fn buffer_line(&self) -> usize {
+        self.rows
+            .iter()
+            .take(self.selected.saturating_add(1))
+            .rev()
+            .find_map(|(_, y)| *y)
+            .unwrap_or(0)
+    }
+    /// The `(left, right)` cells of the rows on screen, each cut to `width` characters
+    /// with tabs expanded to `tab_width` spaces.
+    pub fn lines(&self, width: usize, height: usize, tab_width: usize) -> Vec<(Cell, Cell)> {
+        let expand = |line: &str| line.replace('\t', &" ".repeat(tab_width));
+        self.rows
+            .iter()
+            .skip(self.offset)
+            .take(height)
+            .map(|(x, y)| {
+                let old = x.and_then(|x| self.old.get(x)).map(|line| expand(line));
+                let new = y.and_then(|y| self.new.get(y)).map(|line| expand(line));
+                let (old_kind, new_kind, spans) = match (&old, &new) {
+                    (Some(old), Some(new)) if old == new => (Kind::Same, Kind::Same, None),
+                    (Some(old), Some(new)) => {
+                        (Kind::Changed, Kind::Changed, Some(changed_spans(old, new)))
+                    }
+                    (Some(_), None) => (Kind::Removed, Kind::Filler, None),
+                    (None, _) => (Kind::Filler, Kind::Added, None),
+                };
+                let cell = |text: Option<String>, kind, span| Cell {
+                    text: text.unwrap_or_default().chars().take(width).collect(),
+                    kind,
+                    span,
+                };
+                (
+                    cell(old, old_kind, spans.map(|(old, _)| old)),
+                    cell(new, new_kind, spans.map(|(_, new)| new)),
+                )
+            })
+            .collect()
+    }
+}
+
+/// The character ranges of `old` and `new` between their common prefix and suffix.
+fn changed_spans(old: &str, new: &str) -> ((usize, usize), (usize, usize)) {
+    let old: Vec<char> = old.chars().collect();
+    let new: Vec<char> = new.chars().collect();
+    let prefix = old.iter().zip(&new).take_while(|(a, b)| a == b).count();
+    let suffix = old
+        .iter()
+        .skip(prefix)
+        .rev()
+        .zip(new.iter().skip(prefix).rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (
+        (prefix, old.len().saturating_sub(suffix)),
+        (prefix, new.len().saturating_sub(suffix)),
+    )
+}
@@ -1,56 +1,603 @@
+use crate::anchor::{Anchor, Anchors, Change};
+use crate::conflict::{self, Conflict, Resolution};
+use crate::crypt::Encryption;
+use crate::diagnostics::Diagnostic;
+use crate::diff::{self, Hunk};
+use crate::elastic;
+use crate::fold::Folds;
+use crate::git;
+use crate::hex;
+use crate::lint;
+use crate::outline::Symbol;
+use crate::remote;
+use crate::search::Query;
+use crate::special::Special;
+use crate::terminal::Waker;
+use crate::undo_tree::{Span, UndoTree};
+use crate::word_index::WordIndex;
+use crate::Encoding;
+use crate::Indent;
+use crate::LineEnding;
+use crate::Marks;
 use crate::Position;
 use crate::Row;
+use crate::SaveOptions;
 use crate::SearchDirection;
+use std::cmp;
+use std::collections::hash_map::RandomState;
 use std::fs;
-use std::io::{Error, Write};
+use std::hash::{BuildHasher, Hasher};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// How a buffer's file on disk compares with what was last read or written.
+#[derive(PartialEq, Eq)]
+pub enum DiskState {
+    Unchanged,
+    Modified,
+    Deleted,
+}
+
+/// A buffer of text as rows, with what the editor keeps alongside it: the file it
+/// came from and how to write it back, its undo history, marks, folds and diagnostics.
+/// Positions in it count lines and, within a line, graphemes from zero.
 #[derive(Default)]
 pub struct Document {
     rows: Vec<Row>,
+    /// The file the buffer was read from and is saved to.
     pub file_name: Option<String>,
+    /// Shown instead of a file name for special buffers such as help.
+    pub title: Option<String>,
+    /// What the buffer holds when it is not a file.
+    pub special: Option<Special>,
+    /// Whether the buffer is so big that costly features are off for it.
+    pub large: bool,
     dirty: bool,
+    /// The buffer's lettered marks.
+    pub marks: Marks,
+    /// Positions the editor keeps in the buffer, such as the selection's anchor.
+    pub anchors: Anchors<Anchor>,
+    /// Closed folds, by their line ranges.
+    pub folds: Folds,
+    /// Folding ranges from the language server, and the revision they were found for.
+    pub folding_ranges: Vec<(usize, usize)>,
+    pub folding_ranges_revision: Option<usize>,
+    disk_stamp: Option<(SystemTime, u64)>,
+    revision: usize,
+    /// The encoding the file was read in and is written back in.
+    pub encoding: Encoding,
+    bom: bool,
+    /// The line break written after each line.
+    pub line_ending: LineEnding,
+    /// Whether the file was read with more than one kind of line break.
+    pub mixed_line_endings: bool,
+    missing_final_newline: bool,
+    /// The cleanups done to the text on saving.
+    pub save_options: SaveOptions,
+    /// How the buffer is indented, from the config, `.editorconfig` or the text.
+    pub indent: Indent,
+    /// The language the buffer is highlighted and linted as, such as `rust`.
+    pub file_type: Option<String>,
+    /// Line comment leader for the file type, e.g. `//`.
+    pub comment: Option<String>,
+    /// Columns marked with a ruler.
+    pub color_columns: Vec<usize>,
+    /// Shell command that formats the buffer, reading it on stdin.
+    pub formatter: Option<String>,
+    /// Whether an explicit save formats the buffer first.
+    pub format_on_save: bool,
+    /// Whether text that starts like a path (`./`, `../`, `/`, `~/`) completes from
+    /// the file system.
+    pub path_completion: bool,
+    /// Raw contents of a binary file, shown as hex rows instead of text.
+    binary: Option<Vec<u8>>,
+    /// Whether edits are refused, as for files that cannot be written.
+    pub read_only: bool,
+    /// How the file is encrypted, for buffers decrypted on opening. Their contents
+    /// never reach the disk unencrypted.
+    pub encryption: Option<Encryption>,
+    rejected_edits: usize,
+    /// Latest diagnostics published by the language server, and those of the built-in
+    /// linter, ordered by position.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The revision, tab width and line length limit the lint diagnostics were found for.
+    lints_revision: Option<(usize, usize, Option<usize>)>,
+    /// Functions, types and sections, in document order.
+    pub symbols: Vec<Symbol>,
+    /// The revision `symbols` were found for.
+    pub symbols_revision: Option<usize>,
+    /// Changes against the file as committed at `HEAD`, for the gutter.
+    pub git: git::Diff,
+    /// Who last changed each line, filled in only while blame is shown.
+    pub blame: git::Blame,
+    /// Merge conflicts left in the text, in order.
+    pub conflicts: Vec<Conflict>,
+    /// The revision `conflicts` were found for.
+    conflicts_revision: Option<usize>,
+    /// The revision and tab width the rows' elastic tabstops were found for.
+    tab_stops_revision: Option<(usize, usize)>,
+    /// Words of the buffer, for Ctrl-N/Ctrl-P completion.
+    pub words: WordIndex,
+    undo_tree: UndoTree,
+    /// Whether further changes still belong to the newest undo step.
+    undo_open: bool,
+    /// How many transactions are open. While any is, edits join one undo step and the
+    /// revision moves only when the outermost is committed.
+    transaction: usize,
+    /// Whether the open transaction has changed the buffer.
+    transaction_changed: bool,
 }
+
+/// Puts a file's bytes on disk under its name, for `Document::save_with`.
+pub type WriteFile<'a> = dyn FnMut(&str, &[u8]) -> Result<(), Error> + 'a;
+
+/// A replacement of the text from `start` up to `end`, for `Document::apply_edits`.
+pub struct Edit {
+    pub start: Position,
+    pub end: Position,
+    pub text: String,
+}
+
 impl Document {
+    /// Reads the file `filename` into a document, detecting its encoding, line breaks
+    /// and whether it is binary.
     pub fn open(filename: &str) -> Result<Self, std::io::Error> {
+        let bytes = fs::read(filename)?;
+        let mut document = Self::from_bytes(bytes);
+        document.file_name = Some(filename.to_string());
+        document.disk_stamp = disk_stamp(filename);
+        document.read_only = !is_writable(filename);
+        Ok(document)
+    }
+    /// Creates a document named `file_name` from contents read elsewhere, as from
+    /// another machine.
+    pub fn from_contents(file_name: &str, bytes: Vec<u8>) -> Self {
+        let mut document = Self::from_bytes(bytes);
+        document.file_name = Some(file_name.to_string());
+        document.disk_stamp = disk_stamp(file_name);
+        document
+    }
+    /// Reads piped standard input into an unnamed document.
+    pub fn from_stdin() -> Result<Self, std::io::Error> {
+        let mut bytes = Vec::new();
+        std::io::stdin().read_to_end(&mut bytes)?;
+        Ok(Self::from_bytes(bytes))
+    }
+    /// Creates an unnamed buffer of the `special` kind holding `text`.
+    pub fn special(special: Special, title: &str, text: &str) -> Self {
+        let mut document = Self::from_bytes(text.as_bytes().to_vec());
+        document.title = Some(title.to_string());
+        document.read_only = special.is_read_only();
+        document.special = Some(special);
+        document
+    }
+    fn from_bytes(bytes: Vec<u8>) -> Self {
         let mut rows = Vec::new();
-        let contents = fs::read_to_string(filename)?;
-        for line in contents.lines() {
-            rows.push(Row::from(line));
-        }
-        Ok(Self {
+        let encoding = Encoding::detect(&bytes);
+        let (contents, bom) = encoding.decode_file(&bytes);
+        let (line_ending, mixed_line_endings) = LineEnding::detect(&contents);
+        let missing_final_newline = !contents.is_empty() && !contents.ends_with('\n');
+        let binary = if hex::is_binary(&bytes) {
+            rows = hex::render_rows(&bytes);
+            Some(bytes)
+        } else {
+            for line in contents.lines() {
+                rows.push(Row::from(line));
+            }
+            None
+        };
+        Self {
             rows,
-            file_name: Some(filename.to_string()),
+            file_name: None,
+            title: None,
+            special: None,
+            large: false,
             dirty: false,
-        })
+            marks: Marks::default(),
+            anchors: Anchors::default(),
+            folds: Folds::default(),
+            folding_ranges: Vec::new(),
+            folding_ranges_revision: None,
+            disk_stamp: None,
+            revision: 0,
+            encoding,
+            bom,
+            line_ending,
+            mixed_line_endings,
+            missing_final_newline,
+            save_options: SaveOptions::default(),
+            indent: Indent::default(),
+            file_type: None,
+            comment: None,
+            color_columns: Vec::new(),
+            formatter: None,
+            format_on_save: false,
+            path_completion: true,
+            binary,
+            read_only: false,
+            encryption: None,
+            rejected_edits: 0,
+            diagnostics: Vec::new(),
+            lints_revision: None,
+            symbols: Vec::new(),
+            symbols_revision: None,
+            git: git::Diff::default(),
+            blame: git::Blame::default(),
+            conflicts: Vec::new(),
+            conflicts_revision: None,
+            tab_stops_revision: None,
+            words: WordIndex::default(),
+            undo_tree: UndoTree::default(),
+            undo_open: false,
+            transaction: 0,
+            transaction_changed: false,
+        }
+    }
+    /// Replaces the buffer contents, e.g. with text recovered from a swap file.
+    pub fn load_contents(&mut self, contents: &str) {
+        if self.binary.is_some() || self.rejects_edit() {
+            return;
+        }
+        self.record_undo(&Position::default());
+        self.rows = contents.lines().map(Row::from).collect();
+        self.marks = Marks::default();
+        self.folds.open_all();
+        self.mark_dirty();
+    }
+    /// Changes the contents to `text` by replacing only the lines that differ, as one
+    /// undo step, so marks on unchanged lines stay put. Returns the changed hunks.
+    pub fn apply_text(&mut self, text: &str) -> Vec<Hunk> {
+        if self.binary.is_some() {
+            return Vec::new();
+        }
+        let new: Vec<&str> = text.lines().collect();
+        let hunks = {
+            let old: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+            diff::lines(&old, &new)
+        };
+        let first = if let Some(hunk) = hunks.first() {
+            hunk
+        } else {
+            return hunks;
+        };
+        if self.rejects_edit() {
+            return Vec::new();
+        }
+        self.end_undo_step();
+        self.record_undo(&Position {
+            x: 0,
+            y: first.new_start,
+        });
+        self.mark_dirty();
+        self.replace_hunks(&hunks, &new);
+        self.end_undo_step();
+        hunks
     }
+    /// Replaces the rows of each of `hunks` with its lines of `new`, shifting marks,
+    /// anchors and folds as for any other change of lines.
+    fn replace_hunks(&mut self, hunks: &[Hunk], new: &[&str]) {
+        for hunk in hunks.iter().rev() {
+            let end = cmp::min(hunk.old_start.saturating_add(hunk.old_len), self.rows.len());
+            let rows = new
+                .iter()
+                .skip(hunk.new_start)
+                .take(hunk.new_len)
+                .map(|line| Row::from(*line));
+            self.rows.splice(hunk.old_start..end, rows);
+            self.shift(&Change::ReplaceLines(
+                hunk.old_start,
+                hunk.old_len,
+                hunk.new_len,
+            ));
+        }
+    }
+    /// Brings the gutter's change markers up to date with the buffer.
+    pub fn refresh_git_diff(&mut self) {
+        if self.binary.is_some() || self.large {
+            return;
+        }
+        if self.git.needs_load() {
+            self.git.load(self.file_name.as_deref());
+        }
+        if self.git.revision != Some(self.revision) {
+            let lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+            self.git.update(&lines, self.revision);
+        }
+    }
+    /// Finds the merge conflicts in the text unless they are already current.
+    pub fn refresh_conflicts(&mut self) {
+        if self.binary.is_some() || self.large || self.conflicts_revision == Some(self.revision) {
+            return;
+        }
+        let lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+        self.conflicts = conflict::find(&lines);
+        self.conflicts_revision = Some(self.revision);
+    }
+    /// Adds `lines` at the end of a special buffer, such as a job's output as it is
+    /// printed.
+    pub fn append_lines(&mut self, lines: &[String]) {
+        if self.special.is_none() || lines.is_empty() {
+            return;
+        }
+        self.rows
+            .extend(lines.iter().map(|line| Row::from(line.as_str())));
+        self.mark_dirty();
+    }
+    /// Replaces the language server's diagnostics, keeping the linter's.
+    pub fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics.retain(|diagnostic| diagnostic.lint);
+        self.diagnostics.extend(diagnostics);
+        self.sort_diagnostics();
+    }
+    /// Lints the text when `enabled`, unless the lint diagnostics are already current,
+    /// and drops them otherwise. Lines may reach up to the first color column.
+    pub fn refresh_lints(&mut self, enabled: bool) {
+        if !enabled || self.binary.is_some() || self.large || self.special.is_some() {
+            if self.lints_revision.take().is_some() {
+                self.diagnostics.retain(|diagnostic| !diagnostic.lint);
+            }
+            return;
+        }
+        let limit = self
+            .color_columns
+            .iter()
+            .filter(|column| **column > 0)
+            .min()
+            .map(|column| column.saturating_sub(1));
+        let current = (self.revision, self.indent.tab_width, limit);
+        if self.lints_revision == Some(current) {
+            return;
+        }
+        let lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+        let lints = lint::check(&lines, &self.indent, limit);
+        self.diagnostics.retain(|diagnostic| !diagnostic.lint);
+        self.diagnostics.extend(lints);
+        self.sort_diagnostics();
+        self.lints_revision = Some(current);
+    }
+    fn sort_diagnostics(&mut self) {
+        self.diagnostics
+            .sort_by(|a, b| a.start.cmp(&b.start).then(a.severity.cmp(&b.severity)));
+    }
+    /// Lines up tab-separated cells across adjacent rows when `elastic` is set, unless
+    /// they are already current, and goes back to regular tab stops otherwise.
+    pub fn refresh_tab_stops(&mut self, elastic: bool) {
+        let current = (self.revision, self.indent.tab_width);
+        if !elastic || self.binary.is_some() || self.large {
+            if self.tab_stops_revision.take().is_some() {
+                for row in &mut self.rows {
+                    row.set_tab_stops(Vec::new());
+                }
+            }
+            return;
+        }
+        if self.tab_stops_revision == Some(current) {
+            return;
+        }
+        let lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+        let tab_stops = elastic::tab_stops(&lines, self.indent.tab_width);
+        for (row, tab_stops) in self.rows.iter_mut().zip(tab_stops) {
+            row.set_tab_stops(tab_stops);
+        }
+        self.tab_stops_revision = Some(current);
+    }
+    /// Resolves the conflict around line `y` as one undo step, returning the line the
+    /// conflict started on, or `None` when `y` is outside any conflict.
+    pub fn resolve_conflict(&mut self, y: usize, resolution: Resolution) -> Option<usize> {
+        self.refresh_conflicts();
+        let conflict = *self
+            .conflicts
+            .iter()
+            .find(|conflict| conflict.contains(y))?;
+        let resolved: Vec<String> = {
+            let lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+            conflict
+                .resolve(&lines, resolution)
+                .into_iter()
+                .map(str::to_string)
+                .collect()
+        };
+        self.replace_lines(
+            conflict.start,
+            conflict
+                .end
+                .saturating_sub(conflict.start)
+                .saturating_add(1),
+            &resolved,
+        );
+        Some(conflict.start)
+    }
+    /// Puts the committed lines back in place of the change marked on line `y`,
+    /// returning the line the change started on.
+    pub fn revert_hunk(&mut self, y: usize) -> Option<usize> {
+        self.refresh_git_diff();
+        let hunk = self.git.hunk_at(y)?;
+        let base: Vec<String> = self
+            .git
+            .base_lines(&hunk)
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        self.replace_lines(hunk.new_start, hunk.new_len, &base);
+        Some(git::marker_line(&hunk))
+    }
+    /// Replaces `len` lines at `start` with `lines` as one undo step.
+    fn replace_lines(&mut self, start: usize, len: usize, lines: &[String]) {
+        let mut text = String::new();
+        for line in self
+            .rows
+            .iter()
+            .take(start)
+            .map(Row::as_str)
+            .chain(lines.iter().map(String::as_str))
+            .chain(
+                self.rows
+                    .iter()
+                    .skip(start.saturating_add(len))
+                    .map(Row::as_str),
+            )
+        {
+            text.push_str(line);
+            text.push('\n');
+        }
+        self.apply_text(&text);
+    }
+    /// Brings the word index up to date with the changed lines.
+    pub fn refresh_words(&mut self) {
+        if self.binary.is_some() || self.large || self.words.revision == Some(self.revision) {
+            return;
+        }
+        let lines: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+        self.words.update(&lines, self.revision);
+    }
+    /// Takes in a finished blame, and starts blaming the buffer in the background
+    /// unless the blame is already current.
+    pub fn refresh_blame(&mut self, waker: &Waker) {
+        self.blame.poll();
+        if self.binary.is_some() || self.large || self.blame.revision == Some(self.revision) {
+            return;
+        }
+        let text = self.text();
+        self.blame
+            .update(self.file_name.as_deref(), text, self.revision, waker);
+    }
+    /// The buffer text with `\n` line endings and a trailing newline.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for row in &self.rows {
+            text.push_str(row.as_str());
+            text.push('\n');
+        }
+        text
+    }
+    /// The text from `start` up to `end`, with `\n` between lines.
+    pub fn text_between(&self, start: &Position, end: &Position) -> String {
+        let mut text = String::new();
+        for y in start.y..=end.y {
+            let row = if let Some(row) = self.rows.get(y) {
+                row
+            } else {
+                break;
+            };
+            let from = if y == start.y { start.x } else { 0 };
+            let to = if y == end.y { end.x } else { row.len() };
+            text.extend(row.graphemes().skip(from).take(to.saturating_sub(from)));
+            if y < end.y {
+                text.push('\n');
+            }
+        }
+        text
+    }
+    /// Puts `text` in place of everything from `start` up to `end`, as an undo step of
+    /// its own, and returns the position after it.
+    pub fn replace_range(&mut self, start: &Position, end: &Position, text: &str) -> Position {
+        if self.binary.is_some() || self.rejects_edit() {
+            return start.clone();
+        }
+        self.begin();
+        self.delete_range(start, end);
+        let after = self.insert_str(start, text);
+        self.commit();
+        after
+    }
+    /// Makes `edits`, whose ranges are positions in the text before any of them and do
+    /// not overlap, as one transaction. `positions`, such as cursors, move with the
+    /// text around them; one inside a replaced range goes to its start.
+    pub fn apply_edits(&mut self, edits: &[Edit], positions: &mut [Position]) {
+        if self.binary.is_some() || self.rejects_edit() {
+            return;
+        }
+        let mut edits: Vec<&Edit> = edits.iter().collect();
+        // Later edits first, so the ranges of earlier ones stay valid.
+        edits.sort_by_key(|edit| cmp::Reverse((edit.start.y, edit.start.x)));
+        self.begin();
+        for edit in edits {
+            self.delete_range(&edit.start, &edit.end);
+            let after = if edit.text.is_empty() {
+                edit.start.clone()
+            } else {
+                self.insert_block(&edit.start, &edit.text)
+            };
+            for position in positions.iter_mut() {
+                *position = moved(position, edit, &after);
+            }
+        }
+        self.commit();
+    }
+    /// The line at `index`, if there is one.
     pub fn row(&self, index: usize) -> Option<&Row> {
         self.rows.get(index)
     }
+    /// Whether the document has no lines at all.
     pub fn is_empty(&self) -> bool {
         self.rows.is_empty()
     }
+    /// The number of lines.
     pub fn len(&self) -> usize {
         self.rows.len()
     }
+    /// Deletes the character at `at`, or at the end of a line joins the next line onto
+    /// it.
     #[allow(clippy::integer_arithmetic, clippy::indexing_slicing)]
     pub fn delete(&mut self, at: &Position) {
+        if self.binary.is_some() || self.rejects_edit() {
+            return;
+        }
         let len = self.rows.len();
         if at.y == len {
             return;
         }
-        self.dirty = true;
+        self.record_undo(at);
+        self.mark_dirty();
         if at.x == self.rows[at.y].len() && at.y + 1 < len {
             let next_row = self.rows.remove(at.y + 1);
             let row = &mut self.rows[at.y];
             row.append(&next_row);
+            self.shift(&Change::JoinLine(at.clone()));
         } else {
             let row = &mut self.rows[at.y];
             row.delete(at.x);
+            self.shift(&Change::DeleteChar(at.clone()));
         }
     }
+    /// Deletes `count` whole lines from line `at`.
+    pub fn delete_rows(&mut self, at: usize, count: usize) {
+        if at >= self.rows.len() || self.binary.is_some() || self.rejects_edit() {
+            return;
+        }
+        let end = cmp::min(at.saturating_add(count), self.rows.len());
+        self.record_undo(&Position { x: 0, y: at });
+        self.mark_dirty();
+        self.rows.drain(at..end);
+        self.shift(&Change::ReplaceLines(at, end.saturating_sub(at), 0));
+    }
+    /// Deletes the text from `start` up to, not including, `end`.
+    pub fn delete_range(&mut self, start: &Position, end: &Position) {
+        let mut length = 0_usize;
+        let mut y = start.y;
+        let mut x = start.x;
+        while y < end.y {
+            let row_len = self.rows.get(y).map_or(0, Row::len);
+            length = length
+                .saturating_add(row_len.saturating_sub(x))
+                .saturating_add(1);
+            y = y.saturating_add(1);
+            x = 0;
+        }
+        length = length.saturating_add(end.x.saturating_sub(x));
+        for _ in 0..length {
+            self.delete(start);
+        }
+    }
+    /// Breaks the line at `at` in two, appending an empty line when `at` is past the
+    /// last one.
     pub fn insert_newline(&mut self, at: &Position) {
         if at.y > self.rows.len() {
             return;
         }
+        self.record_undo(at);
         if at.y == self.rows.len() {
             self.rows.push(Row::default());
         }
@@ -59,12 +606,15 @@ impl Document {
         let new_row = self.rows[at.y].split(at.x);
         #[allow(clippy::integer_arithmetic)]
         self.rows.insert(at.y + 1, new_row);
+        self.shift(&Change::SplitLine(at.clone()));
     }
+    /// Inserts `c` at `at`; a `'\n'` breaks the line there.
     pub fn insert(&mut self, at: &Position, c: char) {
-        if at.y > self.rows.len() {
+        if at.y > self.rows.len() || self.binary.is_some() || self.rejects_edit() {
             return;
         }
-        self.dirty = true;
+        self.record_undo(at);
+        self.mark_dirty();
         if c == '\n' {
             self.insert_newline(at);
             return;
@@ -78,23 +628,454 @@ impl Document {
             let row = &mut self.rows[at.y];
             row.insert(at.x, c);
         }
+        self.shift(&Change::InsertChar(at.clone()));
+    }
+    /// Inserts `text` at `at` a character at a time, returning the position after it.
+    pub fn insert_str(&mut self, at: &Position, text: &str) -> Position {
+        let mut position = at.clone();
+        for c in text.chars() {
+            self.insert(&position, c);
+            if c == '\n' {
+                position.y = position.y.saturating_add(1);
+                position.x = 0;
+            } else {
+                position.x = position.x.saturating_add(1);
+            }
+        }
+        position
+    }
+    /// Splices `text`, which may run over many lines, in at `at` in one go and as an
+    /// undo step of its own, returning the position after it. Rows are colored as
+    /// they are drawn, so only the lines on screen get highlighted.
+    pub fn insert_block(&mut self, at: &Position, text: &str) -> Position {
+        if at.y > self.rows.len() || self.binary.is_some() || self.rejects_edit() {
+            return at.clone();
+        }
+        self.end_undo_step();
+        self.record_undo(at);
+        self.mark_dirty();
+        if at.y == self.rows.len() {
+            self.rows.push(Row::default());
+        }
+        let mut lines = text.split('\n');
+        let first = Row::from(lines.next().unwrap_or_default());
+        let mut new_rows: Vec<Row> = lines.map(Row::from).collect();
+        let row = if let Some(row) = self.rows.get_mut(at.y) {
+            row
+        } else {
+            self.end_undo_step();
+            return at.clone();
+        };
+        let tail = row.split(at.x);
+        row.append(&first);
+        let count = new_rows.len();
+        let end = if let Some(last) = new_rows.last_mut() {
+            let end = Position {
+                x: last.len(),
+                y: at.y.saturating_add(count),
+            };
+            last.append(&tail);
+            end
+        } else {
+            row.append(&tail);
+            Position {
+                x: at.x.saturating_add(first.len()),
+                y: at.y,
+            }
+        };
+        let next = at.y.saturating_add(1);
+        self.rows.splice(next..next, new_rows);
+        self.end_undo_step();
+        self.shift(&Change::InsertText(at.clone(), end.clone()));
+        end
+    }
+    /// Moves what points into the text along with `change`: marks, anchors, closed
+    /// folds and diagnostics. Diagnostic columns count UTF-16 units rather than
+    /// graphemes, which is close enough until the language server publishes again.
+    fn shift(&mut self, change: &Change) {
+        self.marks.shift(change);
+        self.anchors.shift(change);
+        if let Some((at, count, new_count)) = change.lines() {
+            self.folds.replace_lines(at, count, new_count);
+        }
+        self.diagnostics.retain_mut(|diagnostic| {
+            let (mut start, mut end) = (
+                Position {
+                    x: diagnostic.start.1,
+                    y: diagnostic.start.0,
+                },
+                Position {
+                    x: diagnostic.end.1,
+                    y: diagnostic.end.0,
+                },
+            );
+            if !change.shift(&mut start) {
+                return false;
+            }
+            if !change.shift(&mut end) {
+                end = start.clone();
+            }
+            diagnostic.start = (start.y, start.x);
+            diagnostic.end = (end.y, end.x);
+            true
+        });
+    }
+    /// Saves the contents before a change, unless the change continues the open step.
+    fn record_undo(&mut self, at: &Position) {
+        if self.undo_open {
+            return;
+        }
+        self.undo_open = true;
+        self.undo_tree.record(&self.rows, at);
+    }
+    /// Closes the current undo step; the next change starts a new one. An open
+    /// transaction keeps its edits together until it is committed.
+    pub fn end_undo_step(&mut self) {
+        if self.transaction == 0 && self.undo_open {
+            self.undo_open = false;
+            self.undo_tree.finish(&self.rows);
+        }
+    }
+    /// Opens a transaction: the edits up to the matching `commit` are undone as one
+    /// step and change the revision once, however many there are. Transactions nest.
+    pub fn begin(&mut self) {
+        if self.transaction == 0 {
+            self.end_undo_step();
+            self.transaction_changed = false;
+        }
+        self.transaction = self.transaction.saturating_add(1);
+    }
+    /// Closes the transaction opened by the matching `begin`.
+    pub fn commit(&mut self) {
+        self.transaction = self.transaction.saturating_sub(1);
+        if self.transaction > 0 {
+            return;
+        }
+        self.undo_open = false;
+        self.undo_tree.finish(&self.rows);
+        if std::mem::take(&mut self.transaction_changed) {
+            self.revision = self.revision.wrapping_add(1);
+        }
+    }
+    /// Reverts the newest undo step, returning where its change was made.
+    pub fn undo(&mut self) -> Option<Position> {
+        self.end_undo_step();
+        if self.binary.is_some() || self.rejects_edit() {
+            return None;
+        }
+        let position = self.undo_tree.undo(&mut self.rows)?;
+        self.folds.open_all();
+        self.mark_dirty();
+        Some(position)
+    }
+    /// Reapplies the most recently undone step.
+    pub fn redo(&mut self) -> Option<Position> {
+        self.end_undo_step();
+        if self.binary.is_some() || self.rejects_edit() {
+            return None;
+        }
+        let position = self.undo_tree.redo(&mut self.rows)?;
+        self.folds.open_all();
+        self.mark_dirty();
+        Some(position)
+    }
+    /// Restores undo state `id` from any branch of the history, returning where the
+    /// change that led there was made.
+    pub fn undo_jump(&mut self, id: usize) -> Option<Position> {
+        self.end_undo_step();
+        if self.binary.is_some() || self.rejects_edit() {
+            return None;
+        }
+        let position = self.undo_tree.jump(id, &mut self.rows)?;
+        self.folds.open_all();
+        self.mark_dirty();
+        Some(position)
+    }
+    /// Restores the state `span` earlier or later in the order changes were made,
+    /// returning where the change that led there was made.
+    pub fn undo_travel(&mut self, span: Span, later: bool) -> Option<Position> {
+        let target = if later {
+            self.undo_tree.later(span)
+        } else {
+            self.undo_tree.earlier(span)
+        }?;
+        self.undo_jump(target)
+    }
+    /// The undo history drawn as a tree, with the state each line stands for.
+    pub fn undo_history(&self) -> Vec<(usize, String)> {
+        self.undo_tree.lines()
+    }
+    /// The undo state the document is in, as numbered in `undo_history`.
+    pub fn undo_state(&self) -> usize {
+        self.undo_tree.current()
+    }
+    /// Refuses a mutation of a read-only document, counting the attempt.
+    fn rejects_edit(&mut self) -> bool {
+        if self.read_only {
+            self.rejected_edits = self.rejected_edits.wrapping_add(1);
+        }
+        self.read_only
     }
+    /// Increases every time an edit is refused because the document is read-only.
+    pub fn rejected_edits(&self) -> usize {
+        self.rejected_edits
+    }
+    fn mark_dirty(&mut self) {
+        self.dirty = true;
+        if self.transaction > 0 {
+            self.transaction_changed = true;
+        } else {
+            self.revision = self.revision.wrapping_add(1);
+        }
+    }
+    /// Converts the document to `encoding` when it is next written. UTF-16 is written
+    /// with a byte order mark, so the file reads back as UTF-16 without guessing.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        if self.encoding != encoding && !self.rejects_edit() {
+            self.encoding = encoding;
+            self.bom = matches!(encoding, Encoding::Utf16Le | Encoding::Utf16Be);
+            self.mark_dirty();
+        }
+    }
+    /// Converts the whole document to `line_ending` when it is next written.
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if (self.line_ending != line_ending || self.mixed_line_endings) && !self.rejects_edit() {
+            self.line_ending = line_ending;
+            self.mixed_line_endings = false;
+            self.mark_dirty();
+        }
+    }
+    /// Whether the document holds a binary file, shown as hex rows.
+    pub fn is_binary(&self) -> bool {
+        self.binary.is_some()
+    }
+    /// Marks the buffer as large when its text is longer than `size` bytes or has a
+    /// line of more than `line_length` characters; a limit of 0 is no limit.
+    pub fn detect_large(&mut self, size: usize, line_length: usize) {
+        let bytes = self.rows.iter().fold(0_usize, |bytes, row| {
+            bytes.saturating_add(row.as_str().len()).saturating_add(1)
+        });
+        self.large = size > 0 && bytes > size
+            || line_length > 0 && self.rows.iter().any(|row| row.len() > line_length);
+    }
+    /// The byte at `index` of a binary document.
+    pub fn byte(&self, index: usize) -> Option<u8> {
+        self.binary
+            .as_ref()
+            .and_then(|bytes| bytes.get(index))
+            .copied()
+    }
+    /// Overwrites a single byte of a binary document and refreshes its hex row.
+    pub fn set_byte(&mut self, index: usize, value: u8) {
+        if self.rejects_edit() {
+            return;
+        }
+        let y = index / hex::BYTES_PER_ROW;
+        if let Some(bytes) = &mut self.binary {
+            if let Some(byte) = bytes.get_mut(index) {
+                *byte = value;
+            } else {
+                return;
+            }
+            let start = y.saturating_mul(hex::BYTES_PER_ROW);
+            let end = cmp::min(start.saturating_add(hex::BYTES_PER_ROW), bytes.len());
+            if let (Some(row), Some(chunk)) = (self.rows.get_mut(y), bytes.get(start..end)) {
+                *row = hex::render_row(y, chunk);
+            }
+            self.mark_dirty();
+        }
+    }
+    /// Whether the file is written with a byte order mark.
+    pub fn has_bom(&self) -> bool {
+        self.bom
+    }
+    /// Sets whether the file is written with a byte order mark.
+    pub fn set_bom(&mut self, bom: bool) {
+        if self.bom != bom && !self.rejects_edit() {
+            self.bom = bom;
+            self.mark_dirty();
+        }
+    }
+    /// Increases with every change to the contents.
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+    /// Whether saving ends the last line with a line break.
+    pub fn ends_with_newline(&self) -> bool {
+        !self.missing_final_newline || self.save_options.insert_final_newline
+    }
+    /// Writes the lines to `writer` as UTF-8 with the document's line breaks, or the
+    /// bytes of a binary document.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), Error> {
+        if let Some(bytes) = &self.binary {
+            return writer.write_all(bytes);
+        }
+        let final_newline = self.ends_with_newline();
+        for (index, row) in self.rows.iter().enumerate() {
+            writer.write_all(row.as_bytes())?;
+            if final_newline || index.saturating_add(1) < self.rows.len() {
+                writer.write_all(self.line_ending.as_str().as_bytes())?;
+            }
+        }
+        Ok(())
+    }
+    /// Applies the configured on-save cleanups to the buffer itself.
+    fn clean_up(&mut self) {
+        let trims_whitespace = self.save_options.trim_trailing_whitespace
+            && self
+                .rows
+                .iter()
+                .any(|row| row.as_str().len() != row.as_str().trim_end().len());
+        let trims_blank_lines = self.save_options.trim_trailing_blank_lines
+            && self
+                .rows
+                .last()
+                .is_some_and(|row| row.as_str().trim().is_empty());
+        if trims_whitespace || trims_blank_lines {
+            self.end_undo_step();
+            self.record_undo(&Position::default());
+        }
+        if self.save_options.trim_trailing_whitespace {
+            for row in &mut self.rows {
+                row.trim_end();
+            }
+        }
+        if self.save_options.trim_trailing_blank_lines {
+            while self
+                .rows
+                .last()
+                .is_some_and(|row| row.as_str().trim().is_empty())
+            {
+                self.rows.pop();
+            }
+        }
+        self.end_undo_step();
+        if self.save_options.insert_final_newline {
+            self.missing_final_newline = false;
+        }
+    }
+    /// Writes the document to its file in its encoding, after the cleanups of
+    /// `save_options`. A document without a file name is left as it is.
     pub fn save(&mut self) -> Result<(), Error> {
+        self.save_with_progress(&mut |_| ())
+    }
+    /// Saves like `save`, calling `progress` with a message while a remote file uploads.
+    pub fn save_with_progress(&mut self, progress: &mut dyn FnMut(&str)) -> Result<(), Error> {
+        self.save_with(&mut |file_name, contents| write_file(file_name, contents, progress))
+    }
+    /// Saves like `save`, handing the file name and the bytes to `write` to put them
+    /// on disk.
+    pub fn save_with(&mut self, write: &mut WriteFile<'_>) -> Result<(), Error> {
+        if let (Some(file_name), Some(bytes)) = (&self.file_name, &self.binary) {
+            write(file_name, &self.encrypt(bytes)?)?;
+            self.dirty = false;
+            self.disk_stamp = disk_stamp(file_name);
+            return Ok(());
+        }
+        if self.file_name.is_some() {
+            self.clean_up();
+        }
         if let Some(file_name) = &self.file_name {
-            let mut file = fs::File::create(file_name)?;
-            for row in &self.rows {
-                file.write_all(row.as_bytes())?;
-                file.write_all(b"\n")?;
+            let mut contents = Vec::new();
+            self.write_to(&mut contents)?;
+            let text = self.encoding.encode(&String::from_utf8_lossy(&contents))?;
+            let mut contents = Vec::with_capacity(text.len().saturating_add(3));
+            if self.bom {
+                contents.extend_from_slice(self.encoding.bom());
             }
+            contents.extend_from_slice(&text);
+            write(file_name, &self.encrypt(&contents)?)?;
             self.dirty = false;
+            self.disk_stamp = disk_stamp(file_name);
+            // Changes after a save start a new state, so the saved one stays as written.
+            self.end_undo_step();
+            self.undo_tree.mark_saved();
         }
         Ok(())
     }
+    fn encrypt(&self, contents: &[u8]) -> Result<Vec<u8>, Error> {
+        match &self.encryption {
+            Some(encryption) => encryption.encrypt(contents),
+            None => Ok(contents.to_vec()),
+        }
+    }
+    /// Whether the buffer has changes to save; special buffers never do.
     pub fn is_dirty(&self) -> bool {
-        self.dirty
+        self.dirty && self.special.is_none()
     }
+    /// Reports whether the file was modified or removed since it was last read or
+    /// written. A buffer never read from or written to disk is `Unchanged`.
+    pub fn disk_state(&self) -> DiskState {
+        match (&self.file_name, self.disk_stamp) {
+            (Some(file_name), Some(stamp)) => match disk_stamp(file_name) {
+                None => DiskState::Deleted,
+                Some(current) if current != stamp => DiskState::Modified,
+                Some(_) => DiskState::Unchanged,
+            },
+            _ => DiskState::Unchanged,
+        }
+    }
+    /// Whether the file was modified since it was last read or written, so that
+    /// writing it would overwrite someone else's changes.
+    pub fn changed_on_disk(&self) -> bool {
+        self.disk_state() == DiskState::Modified
+    }
+    /// Forgets the file's stamp once its deletion has been reported, so that it is
+    /// reported only once; the next save records a new one.
+    pub fn forget_disk_stamp(&mut self) {
+        self.disk_stamp = None;
+    }
+    /// Reads the file again. Only the lines that differ are replaced, so marks and
+    /// folds elsewhere stay put; the changed hunks are returned.
+    pub fn reload(&mut self) -> Result<Vec<Hunk>, Error> {
+        let mut hunks = Vec::new();
+        if let Some(file_name) = self.file_name.clone() {
+            let mut bytes = remote::read_file(&file_name)?;
+            if let Some(encryption) = &self.encryption {
+                bytes = encryption.decrypt(&bytes)?;
+            }
+            let (contents, bom) = self.encoding.decode_file(&bytes);
+            self.end_undo_step();
+            self.record_undo(&Position::default());
+            if self.binary.is_some() {
+                self.rows = hex::render_rows(&bytes);
+                self.binary = Some(bytes);
+                self.folds.open_all();
+            } else {
+                let new: Vec<&str> = contents.lines().collect();
+                hunks = {
+                    let old: Vec<&str> = self.rows.iter().map(Row::as_str).collect();
+                    diff::lines(&old, &new)
+                };
+                self.replace_hunks(&hunks, &new);
+            }
+            self.end_undo_step();
+            self.bom = bom;
+            self.missing_final_newline = !contents.is_empty() && !contents.ends_with('\n');
+            let (line_ending, mixed_line_endings) = LineEnding::detect(&contents);
+            self.line_ending = line_ending;
+            self.mixed_line_endings = mixed_line_endings;
+            self.disk_stamp = disk_stamp(&file_name);
+            self.dirty = false;
+            self.revision = self.revision.wrapping_add(1);
+        }
+        Ok(hunks)
+    }
+    /// The text of the file as it is now on disk.
+    pub fn disk_contents(&self) -> Option<String> {
+        self.file_name
+            .as_ref()
+            .and_then(|file_name| fs::read(file_name).ok())
+            .map(|bytes| self.encoding.decode_file(&bytes).0)
+    }
+    /// The next match of `query` from `at` in `direction`, without wrapping around.
     #[allow(clippy::indexing_slicing)]
-    pub fn find(&self, query: &str, at: &Position, direction: SearchDirection) -> Option<Position> {
+    pub fn find(
+        &self,
+        query: &Query,
+        at: &Position,
+        direction: SearchDirection,
+    ) -> Option<Position> {
         if at.y >= self.rows.len() {
             return None;
         }
@@ -112,7 +1093,7 @@ impl Document {
 
         for _ in start..end {
             if let Some(row) = self.rows.get(position.y) {
-                if let Some(x) = row.find(&query, position.x, direction) {
+                if let Some(x) = row.find(query, position.x, direction) {
                     position.x = x;
                     return Some(position);
                 }
@@ -130,3 +1111,142 @@ impl Document {
         None
     }
 }
+
+/// Where `position` ends up once `edit` has put text ending at `after` in its range.
+fn moved(position: &Position, edit: &Edit, after: &Position) -> Position {
+    let at = (position.y, position.x);
+    if at < (edit.start.y, edit.start.x) {
+        position.clone()
+    } else if at < (edit.end.y, edit.end.x) {
+        edit.start.clone()
+    } else if position.y == edit.end.y {
+        Position {
+            x: after
+                .x
+                .saturating_add(position.x.saturating_sub(edit.end.x)),
+            y: after.y,
+        }
+    } else {
+        Position {
+            x: position.x,
+            y: position
+                .y
+                .saturating_add(after.y)
+                .saturating_sub(edit.end.y),
+        }
+    }
+}
+
+/// Writes `contents` to a temporary file next to `path` and renames it into place, so
+/// a crash mid-save never leaves a truncated file behind. Files with several hard links
+/// are written in place instead, since a rename would detach them from their siblings.
+fn write_file(
+    file_name: &str,
+    contents: &[u8],
+    progress: &mut dyn FnMut(&str),
+) -> Result<(), Error> {
+    match remote::Url::parse(file_name) {
+        Some(url) => remote::write(&url, contents, progress),
+        None => atomic_write(Path::new(file_name), contents),
+    }
+}
+
+fn atomic_write(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let metadata = fs::metadata(&path).ok();
+    if metadata
+        .as_ref()
+        .is_some_and(|metadata| metadata.nlink() > 1)
+    {
+        return write_in_place(&path, contents);
+    }
+    let mode = metadata
+        .as_ref()
+        .map_or(0o666, |metadata| metadata.permissions().mode() & 0o777);
+    let (file, temp_path) = match create_temp(&path, mode) {
+        Ok(created) => created,
+        // A file can be writable in a directory we cannot create files in.
+        Err(error) if matches!(error.raw_os_error(), Some(libc::EACCES | libc::EROFS)) => {
+            return write_in_place(&path, contents);
+        }
+        Err(error) => return Err(error),
+    };
+    match write_temp(file, contents, metadata.as_ref()) {
+        Ok(true) => fs::rename(&temp_path, &path).inspect_err(|_| {
+            let _ = fs::remove_file(&temp_path);
+        }),
+        Ok(false) => {
+            let _ = fs::remove_file(&temp_path);
+            write_in_place(&path, contents)
+        }
+        Err(error) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(error)
+        }
+    }
+}
+
+/// Overwrites the file itself, for when replacing it would lose its links or owner.
+fn write_in_place(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Creates a temp file next to `path` with `mode`, under a random name. The file must
+/// not exist yet, so a link planted under the name is never followed.
+fn create_temp(path: &Path, mode: u32) -> Result<(fs::File, PathBuf), Error> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut attempts = 0_u32;
+    loop {
+        let suffix = RandomState::new().build_hasher().finish();
+        let temp_path = path.with_file_name(format!(".{}.hecto-{:016x}.tmp", file_name, suffix));
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(&temp_path)
+        {
+            Ok(file) => return Ok((file, temp_path)),
+            Err(error) if error.kind() == ErrorKind::AlreadyExists && attempts < 16 => {
+                attempts = attempts.saturating_add(1);
+            }
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Gives the temp file the original's owner and permissions, then fills it. Returns
+/// whether it could keep the owner, without which it must not replace the original.
+fn write_temp(
+    mut file: fs::File,
+    contents: &[u8],
+    original: Option<&fs::Metadata>,
+) -> Result<bool, Error> {
+    if let Some(original) = original {
+        let created = file.metadata()?;
+        if (created.uid(), created.gid()) != (original.uid(), original.gid())
+            // Changing ownership needs privileges we usually lack.
+            && std::os::unix::fs::fchown(&file, Some(original.uid()), Some(original.gid())).is_err()
+        {
+            return Ok(false);
+        }
+        // After the owner, since changing it clears the set-user-ID bit.
+        file.set_permissions(original.permissions())?;
+    }
+    file.write_all(contents)?;
+    file.sync_all()?;
+    Ok(true)
+}
+
+fn disk_stamp(file_name: &str) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(file_name).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn is_writable(file_name: &str) -> bool {
+    fs::OpenOptions::new().write(true).open(file_name).is_ok()
+}
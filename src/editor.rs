@@ -1,33 +1,297 @@
+use crate::align;
+use crate::anchor::Anchor;
+use crate::autocomplete;
+use crate::bidi::{self, Direction, Paragraph};
+use crate::calc;
+use crate::clipboard;
+use crate::code_action;
+use crate::commands::{self, Command, CommandInfo, Ex};
+use crate::completion;
+use crate::config::Keymap;
+use crate::conflict::{Resolution, Section};
+use crate::crypt;
+use crate::csv::{self, ColumnView};
+use crate::diagnostics::{self, Diagnostic, Severity};
+use crate::diff;
+use crate::diff_view::{self, Cell, DiffView};
+use crate::document::{DiskState, Edit};
+use crate::editorconfig;
+use crate::errorformat;
+use crate::event::{self, Action, Builtin, Event, Hooks};
+use crate::export::{self, Format};
+use crate::filetype;
+use crate::fold;
+use crate::formatter;
+use crate::git;
+use crate::help;
+use crate::hex;
+use crate::highlight::{self, Highlighter};
+use crate::hover;
+use crate::job::{Finished, Jobs};
+use crate::journal::{self, Journal};
+use crate::json;
+use crate::key_hints;
+use crate::lint;
+use crate::log;
+use crate::lsp::{self, LanguageServer};
+use crate::markdown;
+use crate::minimap::{self, Minimap};
+use crate::mode_keys::{self, KeyAction, KeyMode, Lookup};
+use crate::modeline;
+use crate::options::{self, OptionInfo};
+use crate::outline::{self, Outline};
+use crate::paths;
+use crate::plugin::{self, Plugins};
+use crate::preview::{self, Preview};
+use crate::project;
+use crate::quickfix::{self, Quickfix};
+use crate::rainbow::Rainbow;
+use crate::recent::{self, Recent};
+use crate::recording::Replay;
+use crate::remote;
+use crate::replace::{self, Plan};
+use crate::row::Piece;
+use crate::script::{self, Reply, Request, Scripts, Step};
+use crate::scrollbar;
+use crate::search::{self, Case, Query};
+use crate::search_index::{self, SearchIndex, Target};
+use crate::session::{self, Session};
+use crate::shell;
+use crate::signature::Signature;
+use crate::snippet::{self, Range, Snippet};
+use crate::special::{self, Special};
+use crate::spell::{self, Dictionary};
+use crate::stats::{self, Stats};
+use crate::statusline::{self, Segment};
+use crate::substitute::{self, Substitute};
+use crate::sudo;
+use crate::surround;
+use crate::swap;
+use crate::swatch;
+use crate::tags::{self, Tag};
+use crate::terminal::{self, CursorShape, Input};
+use crate::terminal_pane::{self, TerminalPane};
+use crate::text_object;
+use crate::thesaurus;
+use crate::transform::{self, Transform};
+use crate::tutor;
+use crate::undo_tree;
+use crate::unicode;
+use crate::url;
+use crate::Args;
+use crate::Config;
 use crate::Document;
+use crate::Encoding;
+use crate::History;
+use crate::HistoryKind;
+use crate::Jump;
+use crate::JumpList;
+use crate::Key;
+use crate::LineEnding;
+use crate::ListChars;
+use crate::Registers;
 use crate::Row;
 use crate::Terminal;
+use crate::Theme;
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
 use std::env;
-use std::io::Error;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, TryRecvError};
 use std::time::Duration;
 use std::time::Instant;
-use termion::color;
-use termion::event::Key;
+use termion::color::Rgb;
 
-const STATUS_FG_COLOR: color::Rgb = color::Rgb(63, 63, 63);
-const STATUS_BG_COLOR: color::Rgb = color::Rgb(239, 239, 239);
 const VERSION: &str = env!("CARGO_PKG_VERSION");
-const QUIT_TIMES: u8 = 2;
+const SWAP_IDLE: Duration = Duration::from_secs(1);
+const SWAP_INTERVAL: Duration = Duration::from_secs(10);
+/// How often the crash journal is brought up to date while something changes.
+const JOURNAL_INTERVAL: Duration = Duration::from_secs(30);
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+const GIT_STATUS_INTERVAL: Duration = Duration::from_secs(5);
+const JOB_POLL: Duration = Duration::from_millis(100);
+const TERMINAL_POLL: Duration = Duration::from_millis(30);
+const FORMAT_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a status message stays in the message bar.
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+/// The shortest time between frames while keys are waiting to be handled.
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+/// Number of recent frame times the debug overlay averages over.
+const FRAME_HISTORY: usize = 120;
+const HOVER_WIDTH: usize = 72;
+/// Synonyms offered at most, to keep the popup on screen.
+const MAX_SYNONYMS: usize = 20;
+/// Rows longer than this, in graphemes, are not scanned for misspellings, brackets,
+/// URLs or colors, which would stall drawing every frame.
+const MAX_HIGHLIGHT_LENGTH: usize = 4096;
+/// Columns a side panel grows or shrinks by at a time.
+const PANEL_STEP: usize = 4;
+/// The narrowest a side panel can be made.
+const MIN_PANEL_WIDTH: usize = 12;
 
+/// Which way a search runs from the cursor.
 #[derive(PartialEq, Clone, Copy)]
 pub enum SearchDirection {
     Forward,
     Backward,
 }
 
-#[derive(Default, Clone)]
+#[derive(PartialEq, Clone, Copy)]
+enum Mode {
+    Insert,
+    Normal,
+    Visual,
+}
+
+/// What the text area as a whole was drawn with; when any of it changes, every row is
+/// drawn again.
+#[derive(PartialEq)]
+struct View {
+    file_name: Option<String>,
+    revision: usize,
+    width: u16,
+    /// The rows of the window.
+    top: usize,
+    height: usize,
+    gutter_width: usize,
+    text_width: usize,
+    offset_x: usize,
+    column_offset: Option<usize>,
+    theme: String,
+    list: bool,
+    list_chars: String,
+    indent_guides: bool,
+    /// The cursor's screen column, while `cursorcolumn` tints it.
+    cursor_column: Option<usize>,
+    bidi: Direction,
+    tab_width: usize,
+    indent_width: usize,
+    color_columns: Vec<usize>,
+    /// Whether blame is shown.
+    blame: bool,
+    /// The revision blame was found for, while it is shown.
+    blame_revision: Option<usize>,
+    /// Whether anything floats over the rows, which are then always drawn again.
+    floating: bool,
+}
+
+/// What one row of the text area showed.
+#[derive(PartialEq)]
+enum DrawnRow {
+    /// Graphemes `start..end` of buffer line `y`, with everything drawn on them.
+    Line {
+        y: usize,
+        start: usize,
+        end: usize,
+        text: String,
+        spans: Vec<(usize, usize, SpanStyle)>,
+        fold: Option<(usize, usize)>,
+        change: Option<char>,
+        line_bg: bool,
+        selection: Option<(usize, usize)>,
+    },
+    /// Past the end of the buffer, with the welcome message or not.
+    Filler(bool),
+}
+
+/// How a span of a row is drawn.
+#[derive(Clone, Copy, PartialEq)]
+enum SpanStyle {
+    /// Underlined in a color, for diagnostics, misspellings and URLs.
+    Underline(Rgb),
+    /// In a color, for syntax and rainbow brackets.
+    Fg(Rgb),
+    /// On a color, for color literals.
+    Swatch(Rgb),
+    /// On a color in the usual text color, for search matches.
+    Match(Rgb),
+}
+
+/// A place in a document: `y` is the line and `x` the grapheme in it, both from zero.
+#[derive(Default, Clone, PartialEq)]
 pub struct Position {
     pub x: usize,
     pub y: usize,
 }
 
+#[derive(Default)]
+struct Buffer {
+    document: Document,
+    cursor_position: Position,
+    offset: Position,
+}
+
+/// The options with `Scope::Window`: how a window shows its buffer rather than what
+/// is in it.
+#[derive(Clone, Copy)]
+struct WindowOptions {
+    wrap: bool,
+    line_numbers: bool,
+    list: bool,
+    /// Scrolling this window scrolls the other windows that have it set too.
+    scroll_bind: bool,
+}
+
+/// A window onto a buffer: a band of rows across the text area. Windows are stacked
+/// from the top down, each but the last with a bar under it naming its buffer.
+struct Window {
+    buffer: usize,
+    /// Rows of text as last set; `window_layout` fits them to the screen.
+    height: usize,
+    /// What the window shows, parked while another window is current and has the
+    /// editor's fields.
+    state: Option<WindowState>,
+}
+
+/// The editor fields every window has its own of.
+struct WindowState {
+    cursor_position: Position,
+    offset: Position,
+    options: WindowOptions,
+    drawn_rows: Option<(View, Vec<DrawnRow>)>,
+    minimap: Minimap,
+    rainbow: Rainbow,
+    highlighter: Highlighter,
+    column_view: ColumnView,
+}
+
+/// A buffer as the crash journal sees it: its index, file name, cursor and, while it
+/// has unsaved changes, its revision.
+type JournalState = (usize, Option<String>, Position, Option<usize>);
+
+/// Ctrl-N/Ctrl-P cycling through words that complete `prefix`, typed at `start`.
+struct WordCycle {
+    start: Position,
+    prefix: String,
+    candidates: Vec<String>,
+    /// The candidate in the buffer; `None` while the typed prefix is shown.
+    index: Option<usize>,
+}
+
+/// The settings writing mode overrides, put back when it ends.
+struct Writing {
+    line_numbers: bool,
+    wrap: bool,
+    scrollbar: bool,
+    minimap: bool,
+    outline: bool,
+}
+
+/// A jump of the view being played out over a few frames: the screen shows a line
+/// between `from` and `offset.y` until `duration` has passed.
+struct ScrollAnimation {
+    from: usize,
+    start: Instant,
+    duration: Duration,
+}
+
 struct StatusMessage {
     text: String,
     time: Instant,
+    /// Whether the message reports a failure, which makes `-c` commands exit
+    /// unsuccessfully.
+    error: bool,
 }
 
 impl StatusMessage {
@@ -35,10 +299,18 @@ impl StatusMessage {
         Self {
             time: Instant::now(),
             text: message,
+            error: false,
+        }
+    }
+    fn error(message: String) -> Self {
+        Self {
+            error: true,
+            ..Self::from(message)
         }
     }
 }
 
+/// The editor: its buffers, windows and panels, and the terminal it runs on.
 pub struct Editor {
     should_quit: bool,
     terminal: Terminal,
@@ -47,164 +319,8869 @@ pub struct Editor {
     document: Document,
     status_message: StatusMessage,
     quit_times: u8,
+    jump_list: JumpList,
+    /// Where each tag jump started, most recent last.
+    tag_stack: Vec<Jump>,
+    mode: Mode,
+    pending: String,
+    overlay: Option<Vec<String>>,
+    count: usize,
+    registers: Registers,
+    recording: Option<(char, Vec<Key>)>,
+    last_macro: Option<char>,
+    input_queue: VecDeque<Key>,
+    last_edit: Vec<Key>,
+    current_edit: Vec<Key>,
+    edit_prefix: Vec<Key>,
+    last_input: Instant,
+    /// When the screen was last drawn.
+    last_frame: Instant,
+    /// What the text area showed when last drawn, so rows that only moved can be
+    /// scrolled into place instead of drawn again.
+    drawn_rows: Option<(View, Vec<DrawnRow>)>,
+    swap_revision: Option<usize>,
+    last_swap: Instant,
+    last_journal: Instant,
+    /// What the crash journal was last written for, from `journal_state`.
+    journal_state: Vec<JournalState>,
+    /// A journal left by a hecto that crashed, offered back by `start`.
+    orphan_journal: Option<(PathBuf, Journal)>,
+    last_watch: Instant,
+    disk_conflict_ignored: bool,
+    /// All open buffers; the slot at `current_buffer` is parked while its state lives in
+    /// `document`, `cursor_position` and `offset`.
+    buffers: Vec<Buffer>,
+    current_buffer: usize,
+    /// The current window's options; a new window starts out with those of the one
+    /// it was split from.
+    window: WindowOptions,
+    /// The windows, top to bottom; the one at `current_window` has its state in the
+    /// editor's fields.
+    windows: Vec<Window>,
+    current_window: usize,
+    /// Set while a window other than the current one is drawn, having the editor's
+    /// fields for the time: the columns the current window's side panels take, which
+    /// every window leaves to them.
+    drawing_other: Option<usize>,
+    config: Config,
+    /// Whether the overlay is a key hint popup, which the next key closes and still acts on.
+    hint_visible: bool,
+    history: History,
+    /// Completion candidates listed above the prompt.
+    completion: Option<completion::Menu>,
+    /// Language servers by file type; `None` when the server failed or exited.
+    language_servers: HashMap<String, Option<LanguageServer>>,
+    /// Insert-mode completion menu shown at the cursor.
+    popup: Option<autocomplete::Popup>,
+    /// Hover documentation shown at the cursor until the next key.
+    hover: Option<Vec<String>>,
+    code_actions: Option<code_action::Menu>,
+    signature: Option<Signature>,
+    outline: Option<Outline>,
+    preview: Option<Preview>,
+    /// Columns the outline panel and the preview pane take as resized, though neither
+    /// takes more than half the screen.
+    outline_size: usize,
+    preview_size: usize,
+    /// The job streaming into the command output buffer, and how many of its lines
+    /// are there.
+    output_job: Option<(usize, usize)>,
+    /// Whether the command output buffer keeps to its last line as a job prints.
+    follow_output: bool,
+    /// The screen column Up and Down aim for with `sticky_column`, `usize::MAX` for the
+    /// line end, and where they left the cursor. Any other move makes it stale.
+    sticky_column: Option<(Position, usize)>,
+    /// Set while writing mode is on.
+    writing: Option<Writing>,
+    diff_view: Option<DiffView>,
+    /// Loaded when spell checking is first turned on.
+    dictionary: Option<Dictionary>,
+    spell_menu: Option<spell::Menu>,
+    word_cycle: Option<WordCycle>,
+    /// Snippets by file type, read when a trigger is first expanded.
+    snippets: HashMap<String, Vec<Snippet>>,
+    snippet: Option<snippet::Session>,
+    /// What was typed after Ctrl-V in insert mode, while a character is being entered.
+    char_input: Option<String>,
+    /// Character names for the Unicode picker, read when it is first opened.
+    unicode_names: Option<Vec<(char, String)>>,
+    /// Hides the `colorcolumn` guides of every buffer without forgetting them.
+    hide_color_columns: bool,
+    /// The buffer revision and word count shown in the status bar.
+    word_count: Option<(usize, usize)>,
+    /// The last accepted search, whose matches the scrollbar marks.
+    last_search: Option<Query>,
+    /// Whether the matches of `last_search` are highlighted and counted; cleared by
+    /// `:nohlsearch` until the next search.
+    search_highlight: bool,
+    minimap: Minimap,
+    rainbow: Rainbow,
+    highlighter: Highlighter,
+    column_view: ColumnView,
+    /// A click read while waiting for a key.
+    click: Option<Position>,
+    /// Text pasted while waiting for a key.
+    paste: Option<String>,
+    /// A change of focus reported while waiting for a key.
+    focus: Option<bool>,
+    /// Whether the wait for a key was cut short by a resize or by output from a
+    /// language server, job or terminal pane.
+    woken: bool,
+    /// Whether the terminal window has focus, as far as the terminal reports it.
+    focused: bool,
+    /// The nearest directory above the first file marked by `.git` or `.hecto`.
+    project_root: Option<PathBuf>,
+    /// The words and definitions of the project's files, for go to anything and
+    /// `:search`.
+    search_index: Option<SearchIndex>,
+    jobs: Jobs,
+    /// The WebAssembly plugins loaded from the config directory.
+    plugins: Plugins,
+    /// The Lua state of `init.lua` and `:lua`, unless it could not be set up.
+    scripts: Option<Scripts>,
+    /// The job running `:make`.
+    make_job: Option<usize>,
+    terminal_pane: Option<TerminalPane>,
+    /// Whether frame time, queued input and memory use are shown over the text.
+    debug_overlay: bool,
+    /// Whether a prompt is reading a line in the message bar, which is where the
+    /// cursor goes, and the terminal draws input method text, meanwhile.
+    prompting: bool,
+    /// How long the last `FRAME_HISTORY` frames took to draw, oldest first.
+    frame_times: VecDeque<Duration>,
+    scroll_animation: Option<ScrollAnimation>,
+    hooks: Hooks,
+    /// Whether an ex command hook is running.
+    in_hook: bool,
+    /// The file and cursor position `CursorMoved` was last raised for.
+    last_cursor: (Option<String>, Position),
+    last_mode: Mode,
+    /// What went wrong in a `BufWritePre` hook, reported once the file is saved.
+    save_warning: Option<String>,
+    /// File name and revision of the buffer whose symbols were last requested.
+    symbols_request: Option<(Option<String>, usize)>,
+    recent: Recent,
+    /// File name and revision of the buffer whose folding ranges were last requested.
+    folding_request: Option<(Option<String>, usize)>,
+    quickfix: Quickfix,
+    /// The changes the replace preview buffer shows.
+    replace_plan: Option<Plan>,
+    tutor: tutor::Progress,
+    git_status: Option<git::Status>,
+    /// The file name `git_status` was read for, and when.
+    git_status_checked: Option<(Option<String>, Instant)>,
+    /// The file name of a status being read in the background, and where it arrives.
+    git_status_pending: Option<(Option<String>, Receiver<Option<git::Status>>)>,
 }
 
 impl Editor {
-    pub fn default() -> Self {
-        let args: Vec<String> = env::args().collect();
+    /// Sets up the editor for `args`: takes over the terminal, or goes headless for
+    /// `--exec`, and opens the files given.
+    pub fn new(args: &Args) -> Self {
         let mut initial_status =
             String::from("HELP: Ctrl-F = Find | Ctrl-S = Save | Ctrl-Q = Quit");
-        let document = if let Some(file_name) = args.get(1) {
-            let doc = Document::open(file_name);
-            if let Ok(doc) = doc {
-                doc
-            } else {
-                initial_status = format!("ERR: Could not open file: {}", file_name);
-                Document::default()
+        let session = match args.session.as_deref().map(Session::load) {
+            Some(Ok(session)) => Some(session),
+            Some(Err(error)) => {
+                initial_status = format!("ERR: session: {}", error);
+                None
+            }
+            None => None,
+        };
+        if let Some(dir) = session.as_ref().and_then(|session| session.dir.as_ref()) {
+            if let Err(error) = env::set_current_dir(dir) {
+                initial_status = format!("ERR: session: {}: {}", dir.display(), error);
+            }
+        }
+        let session_files = session
+            .iter()
+            .flat_map(|session| &session.buffers)
+            .map(|buffer| &buffer.file_name);
+        let file_names: Vec<&String> = session_files.chain(&args.files).collect();
+        // The project is the one holding the first file, or else the working directory.
+        let project_root = file_names
+            .first()
+            .and_then(|file_name| {
+                Path::new(&recent::absolute(file_name))
+                    .parent()
+                    .map(Path::to_path_buf)
+            })
+            .or_else(|| env::current_dir().ok())
+            .and_then(|dir| project::root(&dir));
+        let replay = match args
+            .replay
+            .as_deref()
+            .map(|path| (path, Replay::load(path)))
+        {
+            Some((_, Ok(replay))) => Some(replay),
+            Some((path, Err(error))) => {
+                initial_status = format!("ERR: replay: {}: {}", path.display(), error);
+                None
             }
+            None => None,
+        };
+        let terminal = if args.exec.is_empty() {
+            Terminal::new(replay).expect("Failed to initialise terminal")
         } else {
-            Document::default()
+            Terminal::headless()
         };
-        Self {
+        if let Some(path) = &args.record {
+            if let Err(error) = terminal.start_recording(path) {
+                initial_status = format!("ERR: record: {}: {}", path.display(), error);
+            }
+        }
+        // A project's config may only run programs once the user says so; there is no
+        // one to ask when running ex commands without a terminal.
+        let mut distrusted = Vec::new();
+        let mut trust = |commands: &[String]| {
+            let trusted = args.exec.is_empty()
+                && terminal
+                    .read_line(
+                        &format!(
+                            "This project's config sets {}, which can run programs. Trust it? (y/n): ",
+                            commands.join(", ")
+                        ),
+                        false,
+                    )
+                    .ok()
+                    .flatten()
+                    .is_some_and(|answer| answer.eq_ignore_ascii_case("y"));
+            if !trusted {
+                distrusted = commands.to_vec();
+            }
+            trusted
+        };
+        let config = Config::load(args.config.as_deref(), project_root.as_deref(), &mut trust)
+            .unwrap_or_else(|error| {
+                log::warn("config", &error);
+                initial_status = format!("ERR: config: {}", error);
+                Config::default()
+            });
+        if !distrusted.is_empty() {
+            initial_status = format!(
+                "Left out the untrusted project settings {}.",
+                distrusted.join(", ")
+            );
+        }
+        let mut buffers: Vec<Buffer> = Vec::new();
+        for file_name in file_names {
+            let (mut document, status) = open_document(file_name, &config, &terminal);
+            document.read_only |= args.read_only;
+            if let Some(status) = status {
+                initial_status = status;
+            }
+            buffers.push(Buffer {
+                document,
+                ..Buffer::default()
+            });
+        }
+        if buffers.is_empty() {
+            let (document, _) = open_document("", &config, &terminal);
+            buffers.push(Buffer {
+                document,
+                ..Buffer::default()
+            });
+        }
+        let document = std::mem::take(&mut buffers[0].document);
+        let mut hooks = Hooks::builtin();
+        hooks.extend(&config.hooks);
+        let search_index = project_root
+            .as_deref()
+            .filter(|_| config.search_index && args.exec.is_empty())
+            .map(|root| SearchIndex::start(root, terminal.waker()));
+        let highlighter = Highlighter::start(terminal.waker());
+        // A crashed session is only offered to a hecto started on nothing else.
+        let orphan_journal = (config.journal
+            && args.files.is_empty()
+            && args.session.is_none()
+            && args.exec.is_empty()
+            && !args.tutor)
+            .then(journal::orphan)
+            .flatten();
+        let (plugins, plugin_errors) = Plugins::load();
+        for error in &plugin_errors {
+            log::warn("plugin", error);
+        }
+        if let Some(error) = plugin_errors.first() {
+            initial_status = format!("ERR: plugin: {}", error);
+        }
+        let scripts = Scripts::new()
+            .map_err(|error| log::warn("lua", &error))
+            .ok();
+        let mut editor = Self {
             should_quit: false,
-            terminal: Terminal::default().expect("Failed to initialise terminal"),
+            terminal,
             cursor_position: Position::default(),
             offset: Position::default(),
-            status_message: StatusMessage::from(initial_status),
+            status_message: if initial_status.starts_with("ERR") {
+                StatusMessage::error(initial_status)
+            } else {
+                StatusMessage::from(initial_status)
+            },
             document,
-            quit_times: QUIT_TIMES,
+            quit_times: config.quit_times,
+            jump_list: JumpList::default(),
+            tag_stack: Vec::new(),
+            mode: if config.keymap == Keymap::Vim {
+                Mode::Normal
+            } else {
+                Mode::Insert
+            },
+            pending: String::new(),
+            overlay: None,
+            count: 0,
+            registers: Registers::default(),
+            recording: None,
+            last_macro: None,
+            input_queue: VecDeque::new(),
+            last_edit: Vec::new(),
+            current_edit: Vec::new(),
+            edit_prefix: Vec::new(),
+            last_input: Instant::now(),
+            last_frame: Instant::now(),
+            drawn_rows: None,
+            swap_revision: None,
+            last_swap: Instant::now(),
+            last_journal: Instant::now(),
+            journal_state: Vec::new(),
+            orphan_journal,
+            last_watch: Instant::now(),
+            disk_conflict_ignored: false,
+            buffers,
+            current_buffer: 0,
+            window: WindowOptions {
+                wrap: config.wrap,
+                line_numbers: config.line_numbers,
+                list: config.list,
+                scroll_bind: config.scroll_bind,
+            },
+            windows: vec![Window {
+                buffer: 0,
+                height: 0,
+                state: None,
+            }],
+            current_window: 0,
+            drawing_other: None,
+            config,
+            hint_visible: false,
+            history: History::load(),
+            completion: None,
+            language_servers: HashMap::new(),
+            popup: None,
+            hover: None,
+            code_actions: None,
+            signature: None,
+            outline: None,
+            preview: None,
+            outline_size: outline::WIDTH,
+            preview_size: preview::WIDTH,
+            output_job: None,
+            follow_output: true,
+            sticky_column: None,
+            writing: None,
+            diff_view: None,
+            dictionary: None,
+            spell_menu: None,
+            word_cycle: None,
+            snippets: HashMap::new(),
+            snippet: None,
+            char_input: None,
+            unicode_names: None,
+            word_count: None,
+            last_search: None,
+            search_highlight: false,
+            minimap: Minimap::default(),
+            rainbow: Rainbow::default(),
+            highlighter,
+            column_view: ColumnView::default(),
+            click: None,
+            paste: None,
+            focus: None,
+            woken: false,
+            focused: true,
+            project_root,
+            search_index,
+            jobs: Jobs::default(),
+            plugins,
+            scripts,
+            make_job: None,
+            terminal_pane: None,
+            debug_overlay: false,
+            prompting: false,
+            frame_times: VecDeque::new(),
+            scroll_animation: None,
+            hooks,
+            in_hook: false,
+            last_cursor: (None, Position::default()),
+            last_mode: Mode::Normal,
+            save_warning: None,
+            hide_color_columns: false,
+            symbols_request: None,
+            folding_request: None,
+            recent: Recent::load(),
+            quickfix: Quickfix::default(),
+            replace_plan: None,
+            tutor: tutor::Progress::default(),
+            git_status: None,
+            git_status_checked: None,
+            git_status_pending: None,
+        };
+        // The first file is restored before leaving it records its position.
+        editor.restore_position();
+        for index in (1..editor.buffers.len()).rev() {
+            editor.switch_buffer(index);
+            editor.restore_position();
+        }
+        editor.switch_buffer(0);
+        editor.remember_position();
+        if let Some(session) = &session {
+            editor.restore_session(session);
+        }
+        if let Some(path) = script::init_file() {
+            let started = editor
+                .scripts
+                .as_ref()
+                .map(|scripts| scripts.run_file(&path));
+            if let Some(started) = started {
+                editor.run_script(started);
+            }
+        }
+        if args.tutor {
+            editor.tutor();
+        }
+        if let Some(line) = args.line {
+            editor.jump_to(&Position {
+                x: 0,
+                y: line.saturating_sub(1),
+            });
+        }
+        if let Some(pattern) = &args.pattern {
+            let position = Query::new(pattern, editor.search_options()).map(|query| {
+                editor
+                    .document
+                    .find(&query, &Position::default(), SearchDirection::Forward)
+            });
+            match position {
+                Ok(Some(position)) => editor.jump_to(&position),
+                Ok(None) => {
+                    editor.status_message =
+                        StatusMessage::error(format!("Pattern not found: {}", pattern));
+                }
+                Err(error) => editor.status_message = StatusMessage::error(error),
+            }
         }
+        editor
     }
-    pub fn run(&mut self) {
-        loop {
-            if let Err(error) = self.refresh_screen() {
-                die(error);
+    /// Puts each buffer opened from `session` back where it was edited.
+    fn restore_session(&mut self, session: &Session) {
+        for (index, buffer) in session.buffers.iter().enumerate() {
+            self.switch_buffer(index);
+            self.jump_to(&buffer.cursor_position);
+            self.offset = buffer.offset.clone();
+        }
+        self.switch_buffer(session.current);
+        self.restore_windows(&session.windows, session.current_window);
+        if session.outline {
+            self.outline = Some(Outline::default());
+        }
+        if session.preview {
+            self.preview = Some(Preview::default());
+        }
+        self.outline_size = session.outline_width;
+        self.preview_size = session.preview_width;
+    }
+    /// Splits the one window into `windows`, as a session saved them, and moves to
+    /// window `current`.
+    fn restore_windows(&mut self, windows: &[session::Window], current: usize) {
+        let windows: Vec<&session::Window> = windows
+            .iter()
+            .filter(|window| window.buffer < self.buffers.len())
+            .collect();
+        for (index, window) in windows.iter().enumerate() {
+            if index > 0 {
+                let state = self.new_window_state();
+                self.windows.push(Window {
+                    buffer: self.current_buffer,
+                    height: 0,
+                    state: Some(state),
+                });
+                self.focus_window(index);
             }
-            if self.should_quit {
-                break;
+            self.switch_buffer(window.buffer);
+            self.jump_to(&window.cursor_position);
+            self.offset = window.offset.clone();
+        }
+        let heights: Vec<usize> = windows.iter().map(|window| window.height).collect();
+        self.set_window_heights(&heights);
+        self.focus_window(current);
+    }
+    /// Writes the buffer in the colors it is drawn with to `file_name`, or beside its
+    /// file with `.html` added: HTML for `.html` files, ANSI text for others.
+    fn export(&mut self, file_name: &str, force: bool) {
+        let path = if file_name.is_empty() {
+            match &self.document.file_name {
+                Some(name) if self.document.title.is_none() && !remote::is_remote(name) => {
+                    PathBuf::from(format!("{}.html", name))
+                }
+                _ => {
+                    self.status_message =
+                        StatusMessage::error("No file name to export to.".to_string());
+                    return;
+                }
+            }
+        } else {
+            paths::expand_home(file_name)
+        };
+        if path.exists() && !force {
+            self.status_message =
+                StatusMessage::error(format!("{} exists (add ! to overwrite).", path.display()));
+            return;
+        }
+        self.refresh_rainbow();
+        let end = Position {
+            x: 0,
+            y: self.document.len(),
+        };
+        let lines = self.export_lines(&Position::default(), &end);
+        let title = self
+            .document
+            .title
+            .clone()
+            .or_else(|| self.document.file_name.clone())
+            .unwrap_or_else(|| "[No Name]".to_string());
+        let text = export::render(Format::from_path(&path), &title, &lines, &self.config.theme);
+        self.status_message = match std::fs::write(&path, text) {
+            Ok(()) => StatusMessage::from(format!(
+                "Exported {} lines to {}.",
+                lines.len(),
+                path.display()
+            )),
+            Err(error) => StatusMessage::error(format!("ERR: {}: {}", path.display(), error)),
+        };
+    }
+    /// The text from `from` up to `to`, split into pieces by the spans `draw_spans`
+    /// colors. A range ending at the start of a line does not take that line.
+    fn export_lines(&mut self, from: &Position, to: &Position) -> Vec<export::Line> {
+        self.refresh_rainbow();
+        self.refresh_highlight();
+        self.highlighter.wait();
+        (from.y..=to.y)
+            .filter_map(|y| {
+                let row = self.document.row(y)?;
+                let first = if y == from.y { from.x } else { 0 };
+                let last = if y == to.y { to.x } else { row.len() };
+                (y < to.y || last > first).then(|| self.export_line(row, y, first, last))
+            })
+            .collect()
+    }
+    /// Graphemes `first..last` of row `y`, split into pieces by their spans.
+    fn export_line(&self, row: &Row, y: usize, first: usize, last: usize) -> export::Line {
+        let tab_width = self.document.indent.tab_width;
+        let last = last.min(row.len());
+        let mut line = Vec::new();
+        let mut x = first.min(last);
+        for (start, end, style) in self.spans(row, y) {
+            let start = start.clamp(x, last);
+            let end = end.clamp(start, last);
+            if start == end {
+                continue;
             }
-            if let Err(error) = self.process_keypress() {
-                die(error);
+            if x < start {
+                line.push((row.render(x, start, tab_width), export::Style::default()));
             }
+            let style = match style {
+                SpanStyle::Underline(color) => export::Style {
+                    underline: Some(color),
+                    ..export::Style::default()
+                },
+                SpanStyle::Fg(color) => export::Style {
+                    fg: Some(color),
+                    ..export::Style::default()
+                },
+                SpanStyle::Swatch(color) => export::Style {
+                    fg: Some(swatch::contrast(color)),
+                    bg: Some(color),
+                    underline: None,
+                },
+                // Search highlighting is not part of the text.
+                SpanStyle::Match(_) => export::Style::default(),
+            };
+            line.push((row.render(start, end, tab_width), style));
+            x = end;
         }
+        if x < last {
+            line.push((row.render(x, last, tab_width), export::Style::default()));
+        }
+        line
     }
-
-    pub fn save(&mut self) {
-        if self.document.file_name.is_none() {
-            let new_name = self.prompt("Save as: ", |_, _, _| {}).unwrap_or(None);
-            if new_name.is_none() {
-                self.status_message = StatusMessage::from("Save Aborted.".to_string());
-                return;
+    /// Copies the visual selection, or the whole buffer outside visual mode, to the
+    /// system clipboard, with `html` also as a colored HTML block. Without a clipboard
+    /// tool, the terminal is asked to take the plain text.
+    fn copy(&mut self, html: bool) {
+        let (start, end) = if self.mode == Mode::Visual {
+            self.selection()
+        } else {
+            (
+                Position::default(),
+                Position {
+                    x: 0,
+                    y: self.document.len(),
+                },
+            )
+        };
+        let text = self.document.text_between(&start, &end);
+        let html = html.then(|| {
+            let lines = self.export_lines(&start, &end);
+            export::html_fragment(&lines, &self.config.theme)
+        });
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+        let lines = end
+            .y
+            .saturating_sub(start.y)
+            .saturating_add(usize::from(end.x > 0));
+        let what = if html.is_some() { " as HTML" } else { "" };
+        self.status_message = match clipboard::copy(&text, html.as_deref()) {
+            Ok(tool) => {
+                StatusMessage::from(format!("Copied {} lines{} with {}.", lines, what, tool))
             }
-            self.document.file_name = new_name;
+            Err(error) if error.kind() == ErrorKind::NotFound && html.is_none() => {
+                Terminal::set_clipboard(&text);
+                StatusMessage::from(format!("Copied {} lines through the terminal.", lines))
+            }
+            Err(error) => StatusMessage::error(format!("ERR: copy: {}", error)),
+        };
+    }
+    /// Saves the open files, where they are being edited, the windows and the working
+    /// directory to `file_name`, or to `Session.hecto`, refusing to replace a file
+    /// unless `force`.
+    fn make_session(&mut self, file_name: &str, force: bool) {
+        let path = Path::new(if file_name.is_empty() {
+            session::DEFAULT_FILE
+        } else {
+            file_name
+        });
+        if path.exists() && !force {
+            self.status_message =
+                StatusMessage::error(format!("{} exists (add ! to overwrite).", path.display()));
+            return;
+        }
+        let mut session = Session {
+            dir: env::current_dir().ok(),
+            outline: self.outline.is_some(),
+            preview: self.preview.is_some(),
+            outline_width: self.outline_size,
+            preview_width: self.preview_size,
+            ..Session::default()
+        };
+        // The buffers saved, by their index here and in the session.
+        let mut saved = Vec::new();
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            let (document, cursor_position, offset) = if index == self.current_buffer {
+                (&self.document, &self.cursor_position, &self.offset)
+            } else {
+                (&buffer.document, &buffer.cursor_position, &buffer.offset)
+            };
+            // Help and other scratch buffers are not files to reopen.
+            let file_name = match &document.file_name {
+                Some(file_name) if document.title.is_none() => file_name,
+                _ => continue,
+            };
+            if index == self.current_buffer {
+                session.current = session.buffers.len();
+            }
+            session.buffers.push(session::Buffer {
+                file_name: file_name.clone(),
+                cursor_position: cursor_position.clone(),
+                offset: offset.clone(),
+            });
+            saved.push((index, session.buffers.len().saturating_sub(1)));
+        }
+        // Windows onto buffers that are not saved are left out.
+        let heights = self.window_heights();
+        for (index, (window, height)) in self.windows.iter().zip(heights).enumerate() {
+            let buffer = match saved.iter().find(|(buffer, _)| *buffer == window.buffer) {
+                Some((_, buffer)) => *buffer,
+                None => continue,
+            };
+            let (cursor_position, offset) = match &window.state {
+                Some(state) => (&state.cursor_position, &state.offset),
+                None => (&self.cursor_position, &self.offset),
+            };
+            if index == self.current_window {
+                session.current_window = session.windows.len();
+            }
+            session.windows.push(session::Window {
+                buffer,
+                height,
+                cursor_position: cursor_position.clone(),
+                offset: offset.clone(),
+            });
         }
-        if self.document.save().is_ok() {
-            self.status_message = StatusMessage::from("File Saved Successfully!".to_string());
+        self.status_message = match session.save(path) {
+            Ok(()) => StatusMessage::from(format!(
+                "Session saved to {} ({} files).",
+                path.display(),
+                session.buffers.len()
+            )),
+            Err(error) => StatusMessage::error(format!("ERR: Could not save session: {}", error)),
+        };
+    }
+    fn open_buffer(&mut self, file_name: &str) {
+        let (document, status) = open_document(file_name, &self.config, &self.terminal);
+        if let Some(status) = status {
+            self.status_message = StatusMessage::from(status);
+        }
+        self.push_buffer(document);
+        self.restore_position();
+        self.emit(Event::BufOpen);
+    }
+    /// Puts the cursor back where it was when the current file was last left, and
+    /// makes the file the most recent one.
+    fn restore_position(&mut self) {
+        if self.document.title.is_some() {
+            return;
+        }
+        let file_name = if let Some(file_name) = self.document.file_name.clone() {
+            file_name
         } else {
-            self.status_message = StatusMessage::from("Error Saving File!".to_string());
+            return;
+        };
+        if let Some(position) = self.recent.position(&file_name).cloned() {
+            self.jump_to(&position);
+            let height = self.text_height();
+            self.scroll_cursor_to(height / 2);
         }
+        self.remember_position();
     }
-    fn search(&mut self) {
-        let old_position = self.cursor_position.clone();
-        let mut direction = SearchDirection::Forward;
-        let query = self
-            .prompt(
-                "Search (ESC to cancel, Arrows to navigate): ",
-                |editor, key, query| {
-                    let mut moved = false;
-                    match key {
-                        Key::Right | Key::Down => {
-                            editor.move_cursor(Key::Right);
-                            direction = SearchDirection::Forward;
-                            moved = true;
-                        }
-                        Key::Up | Key::Left => direction = SearchDirection::Backward,
-                        _ => direction = SearchDirection::Forward,
-                    }
-                    if let Some(position) =
-                        editor
-                            .document
-                            .find(&query, &editor.cursor_position, direction)
-                    {
-                        editor.cursor_position = position;
-                        editor.scroll();
-                    } else if moved {
-                        editor.move_cursor(Key::Left);
+    /// Records the cursor position in the current file for the next time it is opened.
+    fn remember_position(&mut self) {
+        if let (Some(file_name), None) = (&self.document.file_name, &self.document.title) {
+            self.recent.record(file_name, &self.cursor_position);
+        }
+    }
+    /// Lists recently opened files to reopen one, most recent first; typing filters.
+    fn recent_files(&mut self) {
+        let files: Vec<String> = self.recent.files().map(str::to_string).collect();
+        let mut query = String::new();
+        let mut selected = 0_usize;
+        let chosen = loop {
+            let lower = query.to_lowercase();
+            let matches: Vec<&String> = files
+                .iter()
+                .filter(|file_name| file_name.to_lowercase().contains(&lower))
+                .collect();
+            selected = selected.min(matches.len().saturating_sub(1));
+            let height = self.terminal.size().height as usize;
+            self.overlay = Some(
+                matches
+                    .iter()
+                    .take(height.saturating_sub(1))
+                    .enumerate()
+                    .map(|(index, file_name)| {
+                        let marker = if index == selected { '>' } else { ' ' };
+                        format!("{} {}", marker, file_name)
+                    })
+                    .collect(),
+            );
+            self.status_message = StatusMessage::from(format!("Recent file: {}", query));
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+            match self.next_key() {
+                Ok(Key::Char('\n')) => break matches.get(selected).map(|name| (*name).clone()),
+                Ok(Key::Esc) | Err(_) => break None,
+                Ok(Key::Up | Key::Ctrl('p')) => selected = selected.saturating_sub(1),
+                Ok(Key::Down | Key::Ctrl('n')) => selected = selected.saturating_add(1),
+                Ok(Key::Backspace) => {
+                    query.pop();
+                    selected = 0;
+                }
+                Ok(Key::Char(c)) if !c.is_control() => {
+                    query.push(c);
+                    selected = 0;
+                }
+                Ok(_) => (),
+            }
+        };
+        self.overlay = None;
+        self.status_message = StatusMessage::from(String::new());
+        if let Some(file_name) = chosen {
+            self.edit_file(&file_name);
+        }
+    }
+    /// Lists the open buffers to switch to one, best match first as typing filters them;
+    /// Ctrl-D closes the selected buffer.
+    fn buffer_picker(&mut self) {
+        let mut query = String::new();
+        let mut selected = 0_usize;
+        let chosen = loop {
+            let mut matches: Vec<(usize, usize, String)> = (0..self.buffers.len())
+                .filter_map(|index| {
+                    let document = if index == self.current_buffer {
+                        &self.document
+                    } else {
+                        &self.buffers.get(index)?.document
+                    };
+                    let path = document
+                        .file_name
+                        .clone()
+                        .or_else(|| document.title.clone())
+                        .unwrap_or_else(|| "[No Name]".to_string());
+                    let score = commands::fuzzy_score(&query, &path)?;
+                    let name = Path::new(&path)
+                        .file_name()
+                        .map_or(path.clone(), |name| name.to_string_lossy().into_owned());
+                    let label = format!(
+                        "{:>2} {:<24} {:<2} {:>6} lines  {}",
+                        index.saturating_add(1),
+                        name,
+                        if document.is_dirty() { "+" } else { "" },
+                        document.len(),
+                        path
+                    );
+                    Some((score, index, label))
+                })
+                .collect();
+            matches.sort_by(|(a, a_index, _), (b, b_index, _)| b.cmp(a).then(a_index.cmp(b_index)));
+            selected = selected.min(matches.len().saturating_sub(1));
+            let height = self.terminal.size().height as usize;
+            self.overlay = Some(
+                matches
+                    .iter()
+                    .take(height.saturating_sub(1))
+                    .enumerate()
+                    .map(|(position, (_, _, label))| {
+                        let marker = if position == selected { '>' } else { ' ' };
+                        format!("{} {}", marker, label)
+                    })
+                    .collect(),
+            );
+            if self.status_message.text.is_empty() {
+                self.status_message = StatusMessage::from(format!(
+                    "Buffer (Enter to switch, Ctrl-D to close): {}",
+                    query
+                ));
+            }
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+            self.status_message = StatusMessage::from(String::new());
+            match self.next_key() {
+                Ok(Key::Char('\n')) => break matches.get(selected).map(|(_, index, _)| *index),
+                Ok(Key::Esc) | Err(_) => break None,
+                Ok(Key::Up | Key::Ctrl('p')) => selected = selected.saturating_sub(1),
+                Ok(Key::Down | Key::Ctrl('n')) => selected = selected.saturating_add(1),
+                Ok(Key::Ctrl('d')) => {
+                    if let Some((_, index, _)) = matches.get(selected) {
+                        self.close_buffer_at(*index);
                     }
-                },
-            )
-            .unwrap_or(None);
-        if query.is_none() {
-            self.cursor_position = old_position;
-            self.scroll();
+                }
+                Ok(Key::Backspace) => {
+                    query.pop();
+                    selected = 0;
+                }
+                Ok(Key::Char(c)) if !c.is_control() => {
+                    query.push(c);
+                    selected = 0;
+                }
+                Ok(_) => (),
+            }
+        };
+        self.overlay = None;
+        self.status_message = StatusMessage::from(String::new());
+        if let Some(index) = chosen {
+            self.switch_buffer(index);
         }
     }
-    fn process_keypress(&mut self) -> Result<(), std::io::Error> {
-        let pressed_key = Terminal::read_key()?;
-        match pressed_key {
-            Key::Ctrl('q') => {
-                if self.quit_times > 0 && self.document.is_dirty() {
-                    self.status_message = StatusMessage::from(format!(
-                        "WARNING! File has unsaved changes. Press Ctrl-Q {} more times to quit.",
-                        self.quit_times
-                    ));
-                    self.quit_times -= 1;
-                    return Ok(());
+    /// Picks a definition, file or word of the project from the search index to go to;
+    /// typing filters. A word lists its occurrences as `:search` does.
+    fn goto_anything(&mut self) {
+        if self.search_index.is_none() {
+            self.status_message =
+                StatusMessage::error("No search index: not in a project.".to_string());
+            return;
+        }
+        let mut query = String::new();
+        let mut selected = 0_usize;
+        let chosen = loop {
+            self.update_search_index();
+            let (mut matches, progress) = match &self.search_index {
+                Some(index) => (
+                    index.find(&query),
+                    if index.ready {
+                        String::new()
+                    } else {
+                        format!(" (indexing, {} files so far)", index.file_count())
+                    },
+                ),
+                None => break None,
+            };
+            selected = selected.min(matches.len().saturating_sub(1));
+            let height = self.terminal.size().height as usize;
+            self.overlay = Some(
+                matches
+                    .iter()
+                    .take(height.saturating_sub(1))
+                    .enumerate()
+                    .map(|(index, (_, label))| {
+                        let marker = if index == selected { '>' } else { ' ' };
+                        format!("{} {}", marker, label)
+                    })
+                    .collect(),
+            );
+            self.status_message = StatusMessage::from(format!("Go to{}: {}", progress, query));
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+            match self.next_key() {
+                Ok(Key::Char('\n')) => {
+                    break (selected < matches.len()).then(|| matches.swap_remove(selected).0)
                 }
-                self.should_quit = true
+                Ok(Key::Esc) | Err(_) => break None,
+                Ok(Key::Up | Key::Ctrl('p')) => selected = selected.saturating_sub(1),
+                Ok(Key::Down | Key::Ctrl('n')) => selected = selected.saturating_add(1),
+                Ok(Key::Backspace) => {
+                    query.pop();
+                    selected = 0;
+                }
+                Ok(Key::Char(c)) if !c.is_control() => {
+                    query.push(c);
+                    selected = 0;
+                }
+                Ok(_) => (),
+            }
+        };
+        self.overlay = None;
+        self.status_message = StatusMessage::from(String::new());
+        match chosen {
+            Some(Target::File(path)) => self.edit_file(&search_index::display_name(&path)),
+            Some(Target::Symbol(path, y)) => {
+                self.edit_file(&search_index::display_name(&path));
+                self.jump_to(&Position { x: 0, y });
             }
-            Key::Ctrl('s') => self.save(),
-            Key::Ctrl('g') => {
-                self.search();
+            Some(Target::Word(word)) => self.search_project(&word),
+            None => (),
+        }
+    }
+    /// Fills the quickfix list with the occurrences of `word`, or of the identifier
+    /// under the cursor, across the project's files.
+    fn search_project(&mut self, word: &str) {
+        let word = if word.is_empty() {
+            autocomplete::word_at(&self.document, &self.cursor_position)
+        } else {
+            word.to_string()
+        };
+        if word.is_empty() {
+            self.status_message = StatusMessage::error("No identifier under cursor.".to_string());
+            return;
+        }
+        let locations: Vec<(String, usize, usize)> = match &mut self.search_index {
+            Some(index) => {
+                index.poll();
+                index
+                    .occurrences(&word)
+                    .into_iter()
+                    .map(|(path, y, character)| (search_index::display_name(path), y, character))
+                    .collect()
             }
-            Key::Char(c) => {
-                self.document.insert(&self.cursor_position, c);
-                self.move_cursor(Key::Right);
+            None => {
+                self.status_message =
+                    StatusMessage::error("No search index: not in a project.".to_string());
+                return;
             }
-            Key::Delete => self.document.delete(&self.cursor_position),
-            Key::Backspace => {
-                if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
-                    self.move_cursor(Key::Left);
-                    self.document.delete(&self.cursor_position);
+        };
+        if locations.is_empty() {
+            self.status_message = StatusMessage::error(format!("Not found: {}", word));
+            return;
+        }
+        let entries = self.quickfix_entries(&locations);
+        self.quickfix.set(&format!("Search: {}", word), entries);
+        self.goto_quickfix(0);
+    }
+    /// Finds the changes replacing a pattern across the project's files would make,
+    /// `/pattern/replacement/[flags]` as for `:s`, and shows them in the replace
+    /// preview. Files open in buffers are read from the buffer.
+    fn plan_replace(&mut self, args: &str) {
+        let substitute = match substitute::parse(&format!("%s{}", args), 0, 0) {
+            Some(Ok(substitute)) => substitute,
+            Some(Err(error)) => {
+                self.status_message = StatusMessage::error(format!("ERR: {}", error));
+                return;
+            }
+            None => {
+                self.status_message = StatusMessage::error(
+                    "Usage: :replace /pattern/replacement/[flags]".to_string(),
+                );
+                return;
+            }
+        };
+        let options = search::Options {
+            case: substitute.case.unwrap_or(self.config.search_case),
+            whole_word: false,
+            regex: true,
+        };
+        let query = match Query::new(&substitute.pattern, options) {
+            Ok(query) => query,
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("ERR: {}", error));
+                return;
+            }
+        };
+        let mut paths: Vec<PathBuf> = match &mut self.search_index {
+            Some(index) => {
+                index.poll();
+                index.files().map(Path::to_path_buf).collect()
+            }
+            None => {
+                self.status_message =
+                    StatusMessage::error("No search index: not in a project.".to_string());
+                return;
+            }
+        };
+        paths.sort();
+        let mut files = Vec::new();
+        for path in paths {
+            let name = search_index::display_name(&path);
+            let target = recent::absolute(&path.display().to_string());
+            let open = std::iter::once(&self.document)
+                .chain(self.buffers.iter().map(|buffer| &buffer.document))
+                .find(|document| {
+                    document
+                        .file_name
+                        .as_deref()
+                        .is_some_and(|name| recent::absolute(name) == target)
+                });
+            let hunks = if let Some(document) = open {
+                replace::hunks(
+                    (0..document.len()).filter_map(|y| document.row(y).map(Row::as_str)),
+                    &query,
+                    &substitute.replacement,
+                    substitute.global,
+                )
+            } else if let Ok(text) = std::fs::read_to_string(&path) {
+                replace::hunks(
+                    text.lines(),
+                    &query,
+                    &substitute.replacement,
+                    substitute.global,
+                )
+            } else {
+                continue;
+            };
+            if !hunks.is_empty() {
+                files.push(replace::FileChange { path, name, hunks });
+            }
+        }
+        if files.is_empty() {
+            self.status_message =
+                StatusMessage::error(format!("Pattern not found: {}", substitute.pattern));
+            return;
+        }
+        let plan = Plan {
+            pattern: substitute.pattern,
+            replacement: substitute.replacement,
+            files,
+        };
+        self.show_special(
+            Document::special(Special::Replace, "[Replace]", &plan.text()),
+            true,
+        );
+        self.replace_plan = Some(plan);
+    }
+    /// Selects or deselects the change under the cursor of the replace preview.
+    fn toggle_replace_hunk(&mut self) {
+        let plan = if let Some(plan) = &mut self.replace_plan {
+            plan
+        } else {
+            return;
+        };
+        if plan.toggle(self.cursor_position.y) {
+            self.document = Document::special(Special::Replace, "[Replace]", &plan.text());
+        }
+    }
+    /// Opens the file of the change under the cursor of the replace preview at its line.
+    fn follow_replace_hunk(&mut self) {
+        let target = self.replace_plan.as_ref().and_then(|plan| {
+            let (file, hunk) = plan.hunk_at(self.cursor_position.y)?;
+            Some((
+                plan.files.get(file)?.path.display().to_string(),
+                plan.hunk(file, hunk)?.line,
+            ))
+        });
+        if let Some((name, y)) = target {
+            self.edit_file(&name);
+            self.jump_to(&Position { x: 0, y });
+        }
+    }
+    /// Makes the selected changes of the replace preview and closes it: in the buffers
+    /// of open files, which are left unsaved, and saved straight to disk for the
+    /// others. A file that changed since the preview is left alone.
+    fn apply_replace(&mut self) {
+        let plan = if let Some(plan) = self.replace_plan.take() {
+            plan
+        } else {
+            return;
+        };
+        let mut files = 0_usize;
+        let mut edits = 0_usize;
+        let mut failed = Vec::new();
+        for file in plan
+            .files
+            .iter()
+            .filter(|file| file.hunks.iter().any(|hunk| hunk.selected))
+        {
+            let path = file.path.display().to_string();
+            let target = recent::absolute(&path);
+            let is_file = |document: &Document| {
+                document
+                    .file_name
+                    .as_deref()
+                    .is_some_and(|name| recent::absolute(name) == target)
+            };
+            let current = self.current_buffer;
+            let open = self
+                .buffers
+                .iter_mut()
+                .enumerate()
+                .filter(|(index, _)| *index != current)
+                .map(|(_, buffer)| (&mut buffer.document, &mut buffer.cursor_position))
+                .find(|(document, _)| is_file(document));
+            let applied = if let Some((document, cursor)) = open {
+                replace::edits(document, file)
+                    .filter(|_| !document.read_only)
+                    .map(|file_edits| {
+                        document.apply_edits(&file_edits, std::slice::from_mut(cursor));
+                        file_edits.len()
+                    })
+            } else {
+                match Document::open(&path) {
+                    Ok(mut document) if !document.is_binary() && !document.read_only => {
+                        replace::edits(&document, file).and_then(|file_edits| {
+                            document.apply_edits(&file_edits, &mut []);
+                            document.save().ok().map(|()| file_edits.len())
+                        })
+                    }
+                    _ => None,
                 }
+            };
+            if let Some(applied) = applied {
+                files = files.saturating_add(1);
+                edits = edits.saturating_add(applied);
+            } else {
+                failed.push(file.name.clone());
             }
-            Key::Up
-            | Key::Left
-            | Key::Down
-            | Key::Right
-            | Key::PageUp
-            | Key::PageDown
-            | Key::End
-            | Key::Home => self.move_cursor(pressed_key),
-            _ => (),
         }
+        self.close_buffer(false);
+        self.status_message =
+            StatusMessage::from(format!("Replaced: {}", edit_summary(files, edits, &failed)));
+    }
+    /// Switches to the buffer holding `file_name`, opening it if no buffer does.
+    fn edit_file(&mut self, file_name: &str) {
+        if file_name.is_empty() {
+            return;
+        }
+        let target = recent::absolute(file_name);
+        let is_target = |document: &Document| {
+            document
+                .file_name
+                .as_deref()
+                .is_some_and(|name| recent::absolute(name) == target)
+        };
+        if is_target(&self.document) {
+            return;
+        }
+        if let Some(index) = self
+            .buffers
+            .iter()
+            .position(|buffer| is_target(&buffer.document))
+        {
+            self.switch_buffer(index);
+        } else {
+            self.open_buffer(file_name);
+        }
+    }
+    fn push_buffer(&mut self, document: Document) {
+        self.buffers.push(Buffer {
+            document,
+            ..Buffer::default()
+        });
+        self.switch_buffer(self.buffers.len().saturating_sub(1));
+    }
+    /// Shows the help buffer, opening it if needed, at `topic` if one is given.
+    fn help(&mut self, topic: &str) {
+        if let Some(index) = self.special_buffer(Special::Help) {
+            self.switch_buffer(index);
+        } else {
+            let text = help::text(&self.config.bindings);
+            self.push_buffer(Document::special(Special::Help, help::TITLE, &text));
+        }
+        if topic.is_empty() {
+            return;
+        }
+        if let Some(position) = help::find_topic(&self.document, topic) {
+            let old_position = self.cursor_position.clone();
+            self.jump_to(&position);
+            self.record_jump(old_position);
+            self.offset.y = position.y;
+        } else {
+            self.status_message = StatusMessage::error(format!("Sorry, no help for {}", topic));
+        }
+    }
+    fn follow_help_link(&mut self) {
+        if let Some(topic) = help::link_at(&self.document, &self.cursor_position) {
+            self.help(&topic);
+        }
+    }
+    /// Makes buffer `index` current, parking the current buffer's state in its slot.
+    fn switch_buffer(&mut self, index: usize) {
+        if index == self.current_buffer || index >= self.buffers.len() {
+            return;
+        }
+        self.finish_edit();
+        self.document.end_undo_step();
+        self.remember_position();
+        let current = Buffer {
+            document: std::mem::take(&mut self.document),
+            cursor_position: self.cursor_position.clone(),
+            offset: self.offset.clone(),
+        };
+        if let Some(slot) = self.buffers.get_mut(self.current_buffer) {
+            *slot = current;
+        }
+        if let Some(slot) = self.buffers.get_mut(index) {
+            let next = std::mem::take(slot);
+            self.document = next.document;
+            self.cursor_position = next.cursor_position;
+            self.offset = next.offset;
+        }
+        self.current_buffer = index;
+        if let Some(window) = self.windows.get_mut(self.current_window) {
+            window.buffer = index;
+        }
+        self.swap_revision = None;
+        self.disk_conflict_ignored = false;
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+    }
+    fn cycle_buffer(&mut self, forward: bool) {
+        let count = self.buffers.len();
+        if count < 2 {
+            return;
+        }
+        let index = if forward {
+            self.current_buffer.saturating_add(1) % count
+        } else {
+            self.current_buffer.saturating_add(count).saturating_sub(1) % count
+        };
+        self.switch_buffer(index);
+    }
+    /// Exchanges the editor's window fields with `state`.
+    fn swap_window_state(&mut self, state: &mut WindowState) {
+        std::mem::swap(&mut self.cursor_position, &mut state.cursor_position);
+        std::mem::swap(&mut self.offset, &mut state.offset);
+        std::mem::swap(&mut self.window, &mut state.options);
+        std::mem::swap(&mut self.drawn_rows, &mut state.drawn_rows);
+        std::mem::swap(&mut self.minimap, &mut state.minimap);
+        std::mem::swap(&mut self.rainbow, &mut state.rainbow);
+        std::mem::swap(&mut self.highlighter, &mut state.highlighter);
+        std::mem::swap(&mut self.column_view, &mut state.column_view);
+    }
+    /// Gives the editor's window fields to window `index`, parking the current
+    /// window's, and brings in the buffer it shows. This is all drawing another window
+    /// takes; `focus_window` is for the user moving there.
+    fn enter_window(&mut self, index: usize) {
+        if index == self.current_window {
+            return;
+        }
+        let mut state = match self
+            .windows
+            .get_mut(index)
+            .and_then(|window| window.state.take())
+        {
+            Some(state) => state,
+            None => return,
+        };
+        self.swap_window_state(&mut state);
+        if let Some(window) = self.windows.get_mut(self.current_window) {
+            window.state = Some(state);
+        }
+        self.current_window = index;
+        let buffer = self
+            .windows
+            .get(index)
+            .map_or(self.current_buffer, |window| window.buffer);
+        if buffer == self.current_buffer || buffer >= self.buffers.len() {
+            return;
+        }
+        if let Some(slot) = self.buffers.get_mut(self.current_buffer) {
+            slot.document = std::mem::take(&mut self.document);
+        }
+        if let Some(slot) = self.buffers.get_mut(buffer) {
+            self.document = std::mem::take(&mut slot.document);
+        }
+        self.current_buffer = buffer;
+    }
+    /// Scrolls the other windows with `scrollbind` set by as many lines as the current
+    /// one has scrolled since its top line was `from`, if it has it set too, keeping
+    /// their cursors on screen.
+    fn scroll_bound_windows(&mut self, from: usize) {
+        let lines = self.offset.y as isize - from as isize;
+        if lines == 0 || !self.window.scroll_bind || self.windows.len() < 2 {
+            return;
+        }
+        let current = self.current_window;
+        let bound: Vec<usize> = (0..self.windows.len())
+            .filter(|index| *index != current)
+            .filter(|index| {
+                self.windows
+                    .get(*index)
+                    .and_then(|window| window.state.as_ref())
+                    .is_some_and(|state| state.options.scroll_bind)
+            })
+            .collect();
+        for index in bound {
+            self.enter_window(index);
+            let last_line = self.document.len().saturating_sub(1);
+            self.offset.y = self.offset.y.saturating_add_signed(lines).min(last_line);
+            let margin = self.scroll_margin();
+            let first = self.offset.y.saturating_add(margin).min(last_line);
+            let last = self
+                .offset
+                .y
+                .saturating_add(self.text_height())
+                .saturating_sub(margin.saturating_add(1))
+                .max(first);
+            let Position { x, y } = self.cursor_position;
+            self.jump_to(&Position {
+                x,
+                y: y.clamp(first, last),
+            });
+        }
+        self.enter_window(current);
+    }
+    /// Moves to window `index` as the user asked to.
+    fn focus_window(&mut self, index: usize) {
+        if index == self.current_window || index >= self.windows.len() {
+            return;
+        }
+        self.finish_edit();
+        self.document.end_undo_step();
+        self.remember_position();
+        let buffer = self.current_buffer;
+        self.enter_window(index);
+        // Another window may have changed the buffer since the cursor was here.
+        let position = self.cursor_position.clone();
+        self.jump_to(&position);
+        if self.current_buffer != buffer {
+            self.swap_revision = None;
+            self.disk_conflict_ignored = false;
+        }
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+        self.sticky_column = None;
+        self.scroll_animation = None;
+    }
+    fn cycle_window(&mut self, forward: bool) {
+        let count = self.windows.len();
+        if count < 2 {
+            self.status_message = StatusMessage::error("Only one window.".to_string());
+            return;
+        }
+        let index = if forward {
+            self.current_window.saturating_add(1) % count
+        } else {
+            self.current_window.saturating_add(count).saturating_sub(1) % count
+        };
+        self.focus_window(index);
+    }
+    /// Where each window is, as `(top, height)` in rows: the heights last set, with the
+    /// last window taking the rows the others leave, and giving up rows first when
+    /// there are too few, down to one each.
+    fn window_layout(&self) -> Vec<(usize, usize)> {
+        let available = self.terminal.size().height as usize;
+        let bars = self.windows.len().saturating_sub(1);
+        let rows = available.saturating_sub(bars);
+        let mut heights: Vec<usize> = self
+            .windows
+            .iter()
+            .map(|window| window.height.max(1))
+            .collect();
+        let others: usize = heights.iter().rev().skip(1).sum();
+        if let Some(last) = heights.last_mut() {
+            *last = rows.saturating_sub(others).max(1);
+        }
+        let mut excess = heights.iter().sum::<usize>().saturating_sub(rows);
+        for height in heights.iter_mut().rev() {
+            let cut = excess.min(height.saturating_sub(1));
+            *height = height.saturating_sub(cut);
+            excess = excess.saturating_sub(cut);
+        }
+        let mut top = 0_usize;
+        heights
+            .into_iter()
+            .map(|height| {
+                let height = height.min(available.saturating_sub(top));
+                let area = (top, height);
+                top = top.saturating_add(height).saturating_add(1);
+                area
+            })
+            .collect()
+    }
+    fn set_window_heights(&mut self, heights: &[usize]) {
+        for (window, height) in self.windows.iter_mut().zip(heights) {
+            window.height = *height;
+        }
+    }
+    fn window_heights(&self) -> Vec<usize> {
+        self.window_layout()
+            .into_iter()
+            .map(|(_, height)| height)
+            .collect()
+    }
+    /// The first screen row of the current window.
+    fn window_top(&self) -> usize {
+        self.window_layout()
+            .get(self.current_window)
+            .map_or(0, |(top, _)| *top)
+    }
+    /// Rows of text in the current window.
+    fn text_height(&self) -> usize {
+        self.window_layout()
+            .get(self.current_window)
+            .map_or(0, |(_, height)| *height)
+    }
+    /// A new window's fields, showing what the current window does.
+    fn new_window_state(&self) -> WindowState {
+        WindowState {
+            cursor_position: self.cursor_position.clone(),
+            offset: self.offset.clone(),
+            options: self.window,
+            drawn_rows: None,
+            minimap: Minimap::default(),
+            rainbow: Rainbow::default(),
+            highlighter: Highlighter::start(self.terminal.waker()),
+            column_view: ColumnView::default(),
+        }
+    }
+    /// Splits the current window in two onto the same buffer and moves to the upper
+    /// half, which then opens `file_name` if one is given.
+    fn split_window(&mut self, file_name: &str) {
+        let height = self.text_height();
+        if height < 3 {
+            self.status_message = StatusMessage::error("No room for another window.".to_string());
+            return;
+        }
+        let upper = height.saturating_sub(1) / 2;
+        let mut heights = self.window_heights();
+        if let Some(lower) = heights.get_mut(self.current_window) {
+            *lower = height.saturating_sub(1).saturating_sub(upper);
+        }
+        heights.insert(self.current_window, upper);
+        let state = self.new_window_state();
+        self.windows.insert(
+            self.current_window,
+            Window {
+                buffer: self.current_buffer,
+                height: upper,
+                state: None,
+            },
+        );
+        if let Some(window) = self.windows.get_mut(self.current_window.saturating_add(1)) {
+            window.state = Some(state);
+        }
+        self.set_window_heights(&heights);
+        self.scroll();
+        self.edit_file(file_name);
+    }
+    /// Closes the current window, giving its rows to the one above it, or below it for
+    /// the top one, which becomes current.
+    fn close_window(&mut self) {
+        if self.windows.len() < 2 {
+            self.status_message = StatusMessage::error("Cannot close the last window.".to_string());
+            return;
+        }
+        let closed = self.current_window;
+        let target = if closed == 0 {
+            1
+        } else {
+            closed.saturating_sub(1)
+        };
+        let mut heights = self.window_heights();
+        let freed = heights
+            .get(closed)
+            .map_or(0, |height| height.saturating_add(1));
+        if let Some(height) = heights.get_mut(target) {
+            *height = height.saturating_add(freed);
+        }
+        heights.remove(closed);
+        self.focus_window(target);
+        self.windows.remove(closed);
+        if closed < self.current_window {
+            self.current_window = self.current_window.saturating_sub(1);
+        }
+        self.set_window_heights(&heights);
+        self.scroll();
+    }
+    /// Closes every window but the current one.
+    fn only_window(&mut self) {
+        let current = self.current_window;
+        self.windows.truncate(current.saturating_add(1));
+        self.windows.drain(..current);
+        self.current_window = 0;
+        self.scroll();
+    }
+    /// Sets the current window's height, within the rows it has together with the
+    /// window below it, or above it for the last one, which takes up the difference.
+    fn resize_window(&mut self, height: usize) {
+        let count = self.windows.len();
+        if count < 2 {
+            self.status_message = StatusMessage::error("Only one window.".to_string());
+            return;
+        }
+        let current = self.current_window;
+        let neighbour = if current.saturating_add(1) < count {
+            current.saturating_add(1)
+        } else {
+            current.saturating_sub(1)
+        };
+        let mut heights = self.window_heights();
+        let total = heights
+            .get(current)
+            .copied()
+            .unwrap_or_default()
+            .saturating_add(heights.get(neighbour).copied().unwrap_or_default());
+        let height = height.clamp(1, total.saturating_sub(1).max(1));
+        if let Some(slot) = heights.get_mut(current) {
+            *slot = height;
+        }
+        if let Some(slot) = heights.get_mut(neighbour) {
+            *slot = total.saturating_sub(height);
+        }
+        self.set_window_heights(&heights);
+        self.scroll();
+    }
+    /// `:resize`: `+n` or `-n` rows, or `n` rows in all.
+    fn resize_command(&mut self, arguments: &str) {
+        let height = self.text_height();
+        let parsed = if let Some(rows) = arguments.strip_prefix('+') {
+            rows.parse().map(|rows| height.saturating_add(rows))
+        } else if let Some(rows) = arguments.strip_prefix('-') {
+            rows.parse().map(|rows| height.saturating_sub(rows))
+        } else {
+            arguments.parse()
+        };
+        match parsed {
+            Ok(height) => self.resize_window(height),
+            Err(_) => {
+                self.status_message =
+                    StatusMessage::error(format!("Not a window height: {}", arguments));
+            }
+        }
+    }
+    /// Gives every window the same height, the top ones a row more when they do not
+    /// divide evenly.
+    fn equalize_windows(&mut self) {
+        let count = self.windows.len();
+        let rows = (self.terminal.size().height as usize).saturating_sub(count.saturating_sub(1));
+        let share = rows.checked_div(count).unwrap_or_default();
+        let extra = rows.checked_rem(count).unwrap_or_default();
+        let heights: Vec<usize> = (0..count)
+            .map(|index| share.saturating_add(usize::from(index < extra)))
+            .collect();
+        self.set_window_heights(&heights);
+        self.scroll();
+    }
+    /// Moves every window down one place and the last one to the top; the heights
+    /// stay where they are and the cursor goes with its window.
+    fn rotate_windows(&mut self) {
+        let count = self.windows.len();
+        if count < 2 {
+            return;
+        }
+        let heights = self.window_heights();
+        self.windows.rotate_right(1);
+        self.current_window = self.current_window.saturating_add(1) % count;
+        self.set_window_heights(&heights);
+        self.scroll();
+    }
+    /// Exchanges the current window with the one below it, or above it for the last
+    /// one; the heights stay where they are and the cursor goes with its window.
+    fn swap_window(&mut self) {
+        let count = self.windows.len();
+        if count < 2 {
+            return;
+        }
+        let current = self.current_window;
+        let other = if current.saturating_add(1) < count {
+            current.saturating_add(1)
+        } else {
+            current.saturating_sub(1)
+        };
+        let heights = self.window_heights();
+        self.windows.swap(current, other);
+        self.current_window = other;
+        self.set_window_heights(&heights);
+        self.scroll();
+    }
+    /// The window showing screen row `y`, and its first row; `None` on a bar.
+    fn window_at(&self, y: usize) -> Option<(usize, usize)> {
+        self.window_layout()
+            .into_iter()
+            .enumerate()
+            .find(|(_, (top, height))| *top <= y && y < top.saturating_add(*height))
+            .map(|(index, (top, _))| (index, top))
+    }
+    /// Removes buffer `index`, which must not be the current one, closing the other
+    /// windows onto it.
+    fn remove_buffer(&mut self, index: usize) {
+        if index == self.current_buffer || index >= self.buffers.len() {
+            return;
+        }
+        self.buffers.remove(index);
+        let closed_above = self
+            .windows
+            .iter()
+            .take(self.current_window)
+            .filter(|window| window.buffer == index)
+            .count();
+        self.windows.retain(|window| window.buffer != index);
+        self.current_window = self.current_window.saturating_sub(closed_above);
+        for window in &mut self.windows {
+            if window.buffer > index {
+                window.buffer = window.buffer.saturating_sub(1);
+            }
+        }
+        if index < self.current_buffer {
+            self.current_buffer = self.current_buffer.saturating_sub(1);
+        }
+    }
+    /// Reports whether the current buffer or any parked one has unsaved changes.
+    fn any_dirty(&self) -> bool {
+        self.document.is_dirty()
+            || self
+                .buffers
+                .iter()
+                .enumerate()
+                .any(|(index, buffer)| index != self.current_buffer && buffer.document.is_dirty())
+    }
+    /// Runs `commands` as if typed at the `:` prompt, for `-c`, until one quits or
+    /// fails, printing what they report to stderr. Returns whether the editor quit
+    /// without an error; changes not written by then are dropped.
+    pub fn exec(&mut self, commands: &[String]) -> bool {
+        // Opening the files may have failed; the help line shown otherwise is no use.
+        let message =
+            std::mem::replace(&mut self.status_message, StatusMessage::from(String::new()));
+        if message.error {
+            eprintln!("hecto: {}", message.text);
+            return false;
+        }
+        for command in commands {
+            if self.should_quit {
+                break;
+            }
+            self.execute_command(command.trim().trim_start_matches(':'));
+            let message =
+                std::mem::replace(&mut self.status_message, StatusMessage::from(String::new()));
+            if !message.text.is_empty() {
+                eprintln!("hecto: {}", message.text);
+            }
+            if message.error {
+                return false;
+            }
+        }
+        if !self.should_quit && self.any_dirty() {
+            eprintln!("hecto: changes were not written; end with 'wq' to save them");
+            return false;
+        }
+        true
+    }
+    /// Takes over the terminal until the user quits.
+    pub fn run(&mut self) {
+        self.start();
+        loop {
+            match self.step() {
+                Ok(true) => (),
+                Ok(false) => break,
+                Err(error) => die(error),
+            }
+        }
+    }
+    /// Gets ready to take keys: offers to restore the buffers of a crashed session and
+    /// to recover unsaved changes from a swap file, and raises `BufOpen` for the first
+    /// buffer. Call once before `step`.
+    pub fn start(&mut self) {
+        self.offer_journal();
+        self.offer_recovery();
+        self.last_cursor = (
+            self.document.file_name.clone(),
+            self.cursor_position.clone(),
+        );
+        self.last_mode = self.mode;
+        self.emit(Event::BufOpen);
+    }
+    /// Brings the screen up to date and handles the next key, waiting for one unless
+    /// some were queued with `feed_keys`. Returns `false` once the editor has quit.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the terminal can no longer be drawn on or read from.
+    pub fn step(&mut self) -> Result<bool, Error> {
+        self.emit_changes();
+        self.update_language_servers();
+        self.update_jobs();
+        self.highlighter.poll();
+        self.update_search_index();
+        self.update_terminal_pane();
+        self.refresh_symbols();
+        self.document.refresh_git_diff();
+        self.document.refresh_conflicts();
+        self.document.refresh_lints(self.config.lint);
+        self.document
+            .refresh_tab_stops(self.config.elastic_tabstops);
+        self.load_dictionary();
+        self.document.refresh_words();
+        self.sync_snippet();
+        self.refresh_word_count();
+        self.refresh_git_status();
+        self.check_tutor();
+        // Keys that arrive faster than frames are drawn, as from key repeat or a macro,
+        // are handled together: the screen is drawn once they run out, or once a frame
+        // is due.
+        let draw = self.should_quit
+            || self.last_frame.elapsed() >= FRAME_INTERVAL
+            || self.input_queue.is_empty() && !self.terminal.has_input();
+        self.refresh_column_view();
+        self.terminal.set_mouse(self.config.minimap);
+        let title = self.window_title();
+        self.terminal.set_title(&title);
+        self.terminal
+            .set_explicit_bidi(self.config.bidi != Direction::Off);
+        self.terminal
+            .set_keyboard_enhancement(self.config.kitty_keyboard);
+        self.refresh_folding_ranges();
+        self.open_folds_at_cursor();
+        if self.config.blame && self.mode != Mode::Insert {
+            self.document.refresh_blame(&self.terminal.waker());
+        }
+        if draw {
+            self.draw()?;
+        }
+        if self.should_quit {
+            self.remember_position();
+            self.remove_swap();
+            journal::remove_own();
+            return Ok(false);
+        }
+        let (buffer, window, offset) = (self.current_buffer, self.current_window, self.offset.y);
+        let top = self.animated_top().unwrap_or(offset);
+        self.process_keypress()?;
+        if self.current_buffer == buffer && self.current_window == window {
+            self.animate_scroll(top);
+            self.scroll_bound_windows(offset);
+        }
+        Ok(true)
+    }
+    /// Queues `keys` to be handled as if typed, ahead of the terminal's input.
+    pub fn feed_keys(&mut self, keys: impl IntoIterator<Item = Key>) {
+        self.input_queue.extend(keys);
+    }
+    /// Handles the keys queued with `feed_keys` without drawing in between.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the terminal can no longer be drawn on or read from.
+    pub fn handle_queued_keys(&mut self) -> Result<(), Error> {
+        while !self.input_queue.is_empty() && !self.should_quit {
+            self.process_keypress()?;
+        }
+        Ok(())
+    }
+    /// Draws a full frame now and returns how long it took.
+    ///
+    /// # Errors
+    ///
+    /// Fails when the terminal can no longer be drawn on.
+    pub fn draw(&mut self) -> Result<Duration, Error> {
+        let start = Instant::now();
+        self.refresh_minimap();
+        self.refresh_rainbow();
+        self.refresh_highlight();
+        self.refresh_preview();
+        if let Some(top) = self.animated_top() {
+            let target = std::mem::replace(&mut self.offset.y, top);
+            let result = self.refresh_screen();
+            self.offset.y = target;
+            result?;
+        } else {
+            self.refresh_screen()?;
+        }
+        self.last_frame = Instant::now();
+        let frame_time = start.elapsed();
+        if self.frame_times.len() >= FRAME_HISTORY {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        log::trace("render", format!("frame drawn in {:?}", frame_time));
+        Ok(frame_time)
+    }
+
+    /// Saves the current buffer as `:w` does, asking for a file name if it has none
+    /// and reporting how it went in the status bar.
+    pub fn save(&mut self) {
+        if self.document.special == Some(Special::Tutor) {
+            self.tutor.saved = true;
+            self.status_message = StatusMessage::from(
+                "Saved, as far as the tutor goes: it is never written.".to_string(),
+            );
+            return;
+        }
+        if self.document.read_only {
+            self.status_message = StatusMessage::error(
+                "File is read-only (use :w! to write anyway, :set noreadonly to edit).".to_string(),
+            );
+            return;
+        }
+        if self.document.file_name.is_none() {
+            let new_name = self
+                .prompt("Save as: ", Some(HistoryKind::FileName), |_, _, _| {})
+                .unwrap_or(None);
+            if new_name.is_none() {
+                self.status_message = StatusMessage::error("Save Aborted.".to_string());
+                return;
+            }
+            self.document.file_name = new_name;
+            // A scratch buffer saved under a name becomes that file's buffer.
+            self.document.special = None;
+            self.document.title = None;
+        }
+        if self.document.changed_on_disk() && !self.confirm_overwrite() {
+            return;
+        }
+        self.save_warning = None;
+        self.emit(Event::BufWritePre);
+        let mut result = self.write_document(false);
+        if matches!(&result, Err(error) if error.kind() == ErrorKind::PermissionDenied) {
+            if let Some(sudo_result) = self.sudo_save() {
+                result = sudo_result;
+            }
+        }
+        match result {
+            Ok(()) => {
+                log::info(
+                    "document",
+                    format!("saved {}", self.document.file_name.as_deref().unwrap_or("")),
+                );
+                self.disk_conflict_ignored = false;
+                if let (Some(index), Some(file_name)) =
+                    (&mut self.search_index, &self.document.file_name)
+                {
+                    index.refresh(Path::new(&recent::absolute(file_name)));
+                }
+                self.status_message =
+                    StatusMessage::from(if let Some(warning) = self.save_warning.take() {
+                        format!("File saved, but {}", warning)
+                    } else {
+                        "File Saved Successfully!".to_string()
+                    });
+                self.emit(Event::BufWritePost);
+            }
+            Err(error) => {
+                log::error("document", format!("saving failed: {}", error));
+                self.status_message = StatusMessage::error(format!("Error Saving File: {}", error));
+            }
+        }
+    }
+    /// Formats the buffer with its formatter command, or else with its language
+    /// server, changing only the lines that differ. Returns the number of changed lines,
+    /// or `None` when there is no way to format the buffer.
+    fn format_document(&mut self) -> Result<Option<usize>, Error> {
+        if self.document.is_binary() {
+            return Ok(None);
+        }
+        let text = self.document.text();
+        let formatted = if let Some(formatter) = &self.document.formatter {
+            formatter::run(formatter, &text)?
+        } else if let Some(formatted) = self.format_with_language_server(&text)? {
+            formatted
+        } else {
+            return Ok(None);
+        };
+        let hunks = self.document.apply_text(&formatted);
+        let y = diff::map_line(&hunks, self.cursor_position.y);
+        let x = self.cursor_position.x;
+        self.jump_to(&Position { x, y });
+        Ok(Some(
+            hunks
+                .iter()
+                .map(|hunk| cmp::max(hunk.old_len, hunk.new_len))
+                .sum(),
+        ))
+    }
+    /// Asks the buffer's language server to format `text`, waiting for the answer.
+    fn format_with_language_server(&mut self, text: &str) -> Result<Option<String>, Error> {
+        let (file_type, uri) = if let Some(server) = self.sync_language_server() {
+            server
+        } else {
+            return Ok(None);
+        };
+        let server = match self.language_servers.get_mut(&file_type) {
+            Some(Some(server)) if server.supports("documentFormattingProvider") => server,
+            _ => return Ok(None),
+        };
+        let indent = self.document.indent;
+        let params = json::Value::object(vec![
+            (
+                "textDocument",
+                json::Value::object(vec![("uri", json::Value::from(uri))]),
+            ),
+            (
+                "options",
+                json::Value::object(vec![
+                    (
+                        "tabSize",
+                        json::Value::from(if indent.use_tabs {
+                            indent.tab_width
+                        } else {
+                            indent.size
+                        }),
+                    ),
+                    ("insertSpaces", json::Value::from(!indent.use_tabs)),
+                ]),
+            ),
+        ]);
+        match server.request_blocking("textDocument/formatting", params, FORMAT_TIMEOUT) {
+            Some(Ok(edits)) => Ok(Some(lsp::apply_edits_to_text(
+                text,
+                &lsp::text_edits(&edits),
+            ))),
+            Some(Err(error)) => Err(Error::other(error)),
+            None => Err(Error::new(
+                ErrorKind::TimedOut,
+                "language server did not answer in time",
+            )),
+        }
+    }
+    /// Runs `command` and shows what it printed in a scratch buffer, replacing the one
+    /// left by an earlier command.
+    fn run_shell_command(&mut self, command: &str) {
+        let output = match shell::run(command) {
+            Ok(output) => output,
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("ERR: {}: {}", command, error));
+                return;
+            }
+        };
+        match output.code {
+            Some(0) => (),
+            Some(code) => {
+                self.status_message = StatusMessage::error(format!("Shell returned {}", code));
+            }
+            None => {
+                self.status_message = StatusMessage::error("Shell command was killed.".to_string());
+            }
+        }
+        if output.text.is_empty() {
+            if output.code == Some(0) {
+                self.status_message = StatusMessage::from("No output.".to_string());
+            }
+            return;
+        }
+        self.show_output(command, &output.text, true);
+    }
+    /// Puts what `command` printed in the buffer for command output, opening it if
+    /// there is none, and switches to it if `switch` is set.
+    fn show_output(&mut self, command: &str, text: &str, switch: bool) {
+        self.output_job = None;
+        let document = Document::special(Special::Output, &format!("!{}", command), text);
+        self.show_special(document, switch);
+    }
+    /// Opens the log file in the command output buffer at its last line.
+    fn show_log(&mut self) {
+        let path = if let Some(path) = log::path() {
+            path
+        } else {
+            self.status_message = StatusMessage::error("No log file.".to_string());
+            return;
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let title = path.display().to_string();
+                self.output_job = None;
+                self.show_special(Document::special(Special::Output, &title, &text), true);
+                self.jump_to_last_line();
+            }
+            Err(error) => {
+                self.status_message =
+                    StatusMessage::error(format!("ERR: {}: {}", path.display(), error));
+            }
+        }
+    }
+    /// The buffer of the `special` kind, which may be the current one.
+    fn special_buffer(&self, special: Special) -> Option<usize> {
+        if self.document.special == Some(special) {
+            return Some(self.current_buffer);
+        }
+        self.buffers
+            .iter()
+            .position(|buffer| buffer.document.special == Some(special))
+    }
+    /// Replaces the buffer of `document`'s special kind with it, opening one if there
+    /// is none, and switches to it if `switch` is set.
+    fn show_special(&mut self, document: Document, switch: bool) {
+        match document
+            .special
+            .and_then(|special| self.special_buffer(special))
+        {
+            Some(index) if index == self.current_buffer => {
+                self.document = document;
+                self.cursor_position = Position::default();
+                self.offset = Position::default();
+            }
+            Some(index) => {
+                if let Some(buffer) = self.buffers.get_mut(index) {
+                    *buffer = Buffer {
+                        document,
+                        ..Buffer::default()
+                    };
+                }
+                if switch {
+                    self.switch_buffer(index);
+                }
+            }
+            None if switch => self.push_buffer(document),
+            None => self.buffers.push(Buffer {
+                document,
+                ..Buffer::default()
+            }),
+        }
+    }
+    /// Switches to the tutor, opening it if needed. Started on nothing, hecto shows it
+    /// in place of the empty buffer.
+    fn tutor(&mut self) {
+        if let Some(index) = self.special_buffer(Special::Tutor) {
+            self.switch_buffer(index);
+            return;
+        }
+        let text = tutor::text(self.config.keymap, &self.config.bindings);
+        let document = Document::special(Special::Tutor, tutor::TITLE, &text);
+        if self.buffers.len() == 1
+            && self.document.file_name.is_none()
+            && self.document.special.is_none()
+            && self.document.is_empty()
+        {
+            self.document = document;
+            self.cursor_position = Position::default();
+            self.offset = Position::default();
+        } else {
+            self.push_buffer(document);
+        }
+    }
+    /// Tells when a lesson of the tutor was just finished.
+    fn check_tutor(&mut self) {
+        if self.document.special != Some(Special::Tutor) {
+            return;
+        }
+        let searched = self.last_search.is_some();
+        if let Some(message) = self
+            .tutor
+            .update(&self.document, &self.cursor_position, searched)
+        {
+            self.status_message = StatusMessage::from(message);
+        }
+    }
+    /// Switches to the scratch buffer, opening an empty one if there is none.
+    fn scratch(&mut self) {
+        if let Some(index) = self.special_buffer(Special::Scratch) {
+            self.switch_buffer(index);
+        } else {
+            self.push_buffer(Document::special(Special::Scratch, "[Scratch]", ""));
+        }
+    }
+    /// Closes the current buffer and switches to the one before it, unless it has
+    /// unsaved changes and `force` is not set.
+    fn close_buffer(&mut self, force: bool) {
+        if self.document.is_dirty() && !force {
+            self.status_message =
+                StatusMessage::error("No write since last change (add ! to override).".to_string());
+            return;
+        }
+        if self.buffers.len() < 2 {
+            self.status_message = StatusMessage::error("Cannot close the last buffer.".to_string());
+            return;
+        }
+        let closed = self.current_buffer;
+        self.switch_buffer(if closed == 0 {
+            1
+        } else {
+            closed.saturating_sub(1)
+        });
+        self.remove_buffer(closed);
+    }
+    /// Closes buffer `index`, which need not be the current one, unless it has unsaved
+    /// changes.
+    fn close_buffer_at(&mut self, index: usize) {
+        let dirty = if index == self.current_buffer {
+            self.document.is_dirty()
+        } else {
+            self.buffers
+                .get(index)
+                .is_some_and(|buffer| buffer.document.is_dirty())
+        };
+        if dirty {
+            self.status_message =
+                StatusMessage::error("No write since last change (save it first).".to_string());
+        } else if index == self.current_buffer {
+            self.close_buffer(false);
+        } else {
+            self.remove_buffer(index);
+        }
+    }
+    /// Does what a special buffer binds `action` to.
+    fn run_special_action(&mut self, action: special::Action) {
+        match (action, self.document.special) {
+            (special::Action::Close, _) => self.close_buffer(false),
+            (special::Action::Follow, Some(Special::Help)) => self.follow_help_link(),
+            (special::Action::Follow, Some(Special::Quickfix)) => {
+                // The first line is the list's title.
+                if let Some(index) = self.cursor_position.y.checked_sub(1) {
+                    self.goto_quickfix(index);
+                }
+            }
+            (special::Action::Follow, Some(Special::Replace)) => self.follow_replace_hunk(),
+            (special::Action::Follow, _) => (),
+            (special::Action::Toggle, _) => self.toggle_replace_hunk(),
+            (special::Action::Apply, _) => self.apply_replace(),
+            (special::Action::Search, _) => self.search(),
+            (special::Action::NextMatch, _) => self.goto_match(true),
+            (special::Action::PreviousMatch, _) => self.goto_match(false),
+            (special::Action::PageDown, _) => self.move_cursor(Key::PageDown),
+            (special::Action::PageUp, _) => self.move_cursor(Key::PageUp),
+            (special::Action::Top, _) => self.jump_to(&Position::default()),
+            (special::Action::Bottom, _) => {
+                self.follow_output = true;
+                self.jump_to_last_line();
+            }
+            (special::Action::Tail, _) => {
+                self.follow_output = !self.follow_output;
+                if self.follow_output {
+                    self.jump_to_last_line();
+                }
+                self.status_message = StatusMessage::from(
+                    if self.follow_output {
+                        "Following output (F to stop)."
+                    } else {
+                        "Stopped following output."
+                    }
+                    .to_string(),
+                );
+            }
+        }
+    }
+    fn jump_to_last_line(&mut self) {
+        let y = self.document.len().saturating_sub(1);
+        self.jump_to(&Position { x: 0, y });
+    }
+    /// Starts `command` in the background, streaming what it prints into the command
+    /// output buffer.
+    fn start_job(&mut self, command: &str) {
+        match self.jobs.spawn(command, &self.terminal.waker()) {
+            Ok(id) => {
+                self.show_output(command, "", false);
+                self.output_job = Some((id, 0));
+                self.follow_output = true;
+                self.status_message =
+                    StatusMessage::from(format!("Job {} started: {}", id, command));
+            }
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("ERR: {}: {}", command, error));
+            }
+        }
+    }
+    /// Adds `lines` a job printed to the command output buffer, keeping to the last
+    /// line while following. Returns whether the buffer was still there to take them.
+    fn stream_output(&mut self, lines: &[String]) -> bool {
+        let index = if let Some(index) = self.special_buffer(Special::Output) {
+            index
+        } else {
+            return false;
+        };
+        let (document, cursor_position) = if index == self.current_buffer {
+            (&mut self.document, &mut self.cursor_position)
+        } else if let Some(buffer) = self.buffers.get_mut(index) {
+            (&mut buffer.document, &mut buffer.cursor_position)
+        } else {
+            return false;
+        };
+        // Moving off the last line stops following, as in `less +F`.
+        let at_end = cursor_position.y.saturating_add(1) >= document.len();
+        document.append_lines(lines);
+        if self.follow_output && at_end {
+            *cursor_position = Position {
+                x: 0,
+                y: document.len().saturating_sub(1),
+            };
+        } else if !lines.is_empty() {
+            self.follow_output = false;
+        }
+        if index == self.current_buffer {
+            self.scroll();
+        }
+        true
+    }
+    /// Reports the jobs that finished, leaving their output in the command output
+    /// buffer.
+    fn update_jobs(&mut self) {
+        let finished = self.jobs.poll();
+        // The job streaming into the output buffer, if it is still there to take it.
+        let mut streamed = None;
+        if let Some((id, shown)) = self.output_job {
+            let lines = self
+                .jobs
+                .running()
+                .iter()
+                .chain(finished.iter().map(|finished| &finished.job))
+                .find(|job| job.id == id)
+                .map(|job| job.output.get(shown..).unwrap_or_default().to_vec());
+            self.output_job = lines
+                .filter(|lines| self.stream_output(lines))
+                .map(|lines| (id, shown.saturating_add(lines.len())));
+            streamed = self.output_job.map(|(id, _)| id);
+        }
+        for finished in finished {
+            if self.make_job == Some(finished.job.id) {
+                self.make_job = None;
+                self.finish_make(&finished);
+                continue;
+            }
+            let job = &finished.job;
+            let result = match finished.code {
+                Some(0) => "finished".to_string(),
+                Some(code) => format!("exited with {}", code),
+                None => "was killed".to_string(),
+            };
+            let mut message = format!("Job {} {}: {}", job.id, result, job.command);
+            if streamed == Some(job.id) {
+                self.output_job = None;
+            } else if !job.output.is_empty() {
+                let text = job.output.join("\n");
+                self.show_output(&job.command, &text, false);
+            }
+            if !job.output.is_empty() {
+                message.push_str(&format!(
+                    " ({} {} of output)",
+                    job.output.len(),
+                    stats::plural(job.output.len(), "line")
+                ));
+            }
+            self.status_message = StatusMessage::from(message);
+        }
+    }
+    fn update_search_index(&mut self) {
+        if let Some(index) = &mut self.search_index {
+            index.poll();
+        }
+    }
+    /// Starts the build command with `arguments` in the background; its errors fill
+    /// the quickfix list when it is done.
+    fn make(&mut self, arguments: &str) {
+        if let Some(id) = self.make_job {
+            self.status_message =
+                StatusMessage::error(format!("A build is already running (job {}).", id));
+            return;
+        }
+        let command = if arguments.is_empty() {
+            self.config.make.clone()
+        } else {
+            format!("{} {}", self.config.make, arguments)
+        };
+        match self.jobs.spawn(&command, &self.terminal.waker()) {
+            Ok(id) => {
+                self.make_job = Some(id);
+                self.status_message = StatusMessage::from(format!("Building: {}", command));
+            }
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("ERR: {}: {}", command, error));
+            }
+        }
+    }
+    fn finish_make(&mut self, finished: &Finished) {
+        let job = &finished.job;
+        if !job.output.is_empty() {
+            self.show_output(&job.command, &job.output.join("\n"), false);
+        }
+        let tool = self
+            .config
+            .error_format
+            .unwrap_or_else(|| errorformat::Tool::for_command(&job.command));
+        let entries = errorformat::parse(tool, &job.output);
+        if entries.is_empty() {
+            self.status_message = StatusMessage::from(match finished.code {
+                Some(0) => format!("Build finished: {}", job.command),
+                Some(code) => format!("Build exited with {}: {}", code, job.command),
+                None => format!("Build was killed: {}", job.command),
+            });
+            return;
+        }
+        self.quickfix
+            .set(&format!("make: {}", job.command), entries);
+        self.goto_quickfix(0);
+    }
+    fn show_jobs(&mut self) {
+        if !self.jobs.is_active() {
+            self.status_message = StatusMessage::from("No jobs running.".to_string());
+            return;
+        }
+        let mut lines = vec!["Jobs (:jobkill <n> stops one)".to_string()];
+        lines.extend(self.jobs.running().iter().map(|job| {
+            format!(
+                "{:>3}  {:>5}s  {:>6} {}  {}",
+                job.id,
+                job.elapsed().as_secs(),
+                job.output.len(),
+                stats::plural(job.output.len(), "line"),
+                job.command
+            )
+        }));
+        self.overlay = Some(lines);
+    }
+    fn show_plugins(&mut self) {
+        let plugins = self.plugins.describe();
+        if plugins.is_empty() {
+            self.status_message = StatusMessage::from("No plugins loaded.".to_string());
+            return;
+        }
+        let mut lines = vec!["Plugins".to_string()];
+        lines.extend(plugins);
+        self.overlay = Some(lines);
+    }
+    /// Runs export `export` of plugin `index` on the buffer, with `arguments`, and
+    /// applies what it asked for.
+    fn call_plugin(&mut self, index: usize, export: &str, arguments: &str) {
+        let context = plugin::Context {
+            text: if self.plugins.reads_text(index) {
+                self.document.text()
+            } else {
+                String::new()
+            },
+            file_name: self.document.file_name.clone().unwrap_or_default(),
+            cursor: (self.cursor_position.y, self.cursor_position.x),
+            arguments: arguments.to_string(),
+        };
+        match self.plugins.call(index, export, context) {
+            Ok(outcome) => {
+                if let Some(text) = outcome.text {
+                    self.document.apply_text(&text);
+                    self.jump_to(&self.cursor_position.clone());
+                }
+                if let Some((y, x)) = outcome.cursor {
+                    self.jump_to(&Position { x, y });
+                }
+                if let Some(message) = outcome.message {
+                    self.status_message = StatusMessage::from(message);
+                }
+            }
+            Err(error) => {
+                log::warn("plugin", &error);
+                self.status_message = StatusMessage::error(format!("ERR: {}", error));
+            }
+        }
+    }
+    /// Runs a script from its first request to its end, answering each request.
+    fn run_script(&mut self, started: Result<(script::Call, Step), String>) {
+        let (call, mut step) = match started {
+            Ok(started) => started,
+            Err(error) => {
+                self.script_failed(&error);
+                return;
+            }
+        };
+        while let Step::Request(request) = step {
+            let reply = self.answer_script(request);
+            step = match self
+                .scripts
+                .as_ref()
+                .map(|scripts| scripts.resume(&call, reply))
+            {
+                Some(Ok(step)) => step,
+                Some(Err(error)) => {
+                    self.script_failed(&error);
+                    return;
+                }
+                None => return,
+            };
+        }
+    }
+    fn script_failed(&mut self, error: &str) {
+        log::warn("lua", error);
+        self.status_message = StatusMessage::error(format!("ERR: lua: {}", error));
+    }
+    fn answer_script(&mut self, request: Request) -> Reply {
+        let len = self.document.len();
+        match request {
+            Request::Lines(first, last) => {
+                let first = first.unwrap_or(1).saturating_sub(1);
+                let last = last.unwrap_or(len).min(len);
+                Reply::Lines(
+                    (first..last)
+                        .filter_map(|y| self.document.row(y).map(|row| row.as_str().to_string()))
+                        .collect(),
+                )
+            }
+            Request::SetLines(first, last, _) if first > last.saturating_add(1) || last > len => {
+                Reply::Error(format!("no lines {} to {}", first, last))
+            }
+            Request::SetLines(_, _, _) if self.document.read_only => {
+                Reply::Error("the buffer is read-only".to_string())
+            }
+            Request::SetLines(first, last, lines) => {
+                let mut text = self.lines_text(0..first.saturating_sub(1));
+                for line in lines {
+                    text.push_str(&line);
+                    text.push('\n');
+                }
+                text.push_str(&self.lines_text(last..len));
+                self.document.apply_text(&text);
+                self.jump_to(&self.cursor_position.clone());
+                Reply::Nothing
+            }
+            Request::LineCount => Reply::Number(len),
+            Request::Cursor => Reply::Position(
+                self.cursor_position.y.saturating_add(1),
+                self.cursor_position.x.saturating_add(1),
+            ),
+            Request::SetCursor(line, column) => {
+                self.jump_to(&Position {
+                    x: column.saturating_sub(1),
+                    y: line.saturating_sub(1),
+                });
+                Reply::Nothing
+            }
+            Request::FileName => Reply::Text(self.document.file_name.clone().unwrap_or_default()),
+            Request::GetOption(option) => Reply::Value(self.option_value(option)),
+            Request::SetOption(option, value) => match self.set_option(option, value) {
+                Ok(()) => Reply::Nothing,
+                Err(error) => Reply::Error(error),
+            },
+            Request::Command(command) => {
+                let before =
+                    std::mem::replace(&mut self.status_message, StatusMessage::from(String::new()));
+                self.execute_command(command.trim().trim_start_matches(':'));
+                let message = std::mem::replace(&mut self.status_message, before);
+                if message.error {
+                    Reply::Error(message.text)
+                } else {
+                    if !message.text.is_empty() {
+                        self.status_message = message;
+                    }
+                    Reply::Nothing
+                }
+            }
+            Request::Message(text) => {
+                self.status_message = StatusMessage::from(text);
+                Reply::Nothing
+            }
+        }
+    }
+    fn kill_job(&mut self, id: &str) {
+        match id.parse::<usize>() {
+            Ok(id) if self.jobs.kill(id) => (),
+            _ => self.status_message = StatusMessage::error(format!("No job {}", id)),
+        }
+    }
+    /// Inserts what `command` prints at the cursor, leaving off its final line break.
+    fn read_shell_command(&mut self, command: &str) {
+        match shell::run(command) {
+            Ok(output) if output.code == Some(0) => {
+                let text = output.text.strip_suffix('\n').unwrap_or(&output.text);
+                let at = self.cursor_position.clone();
+                self.document.insert_block(&at, text);
+                self.jump_to(&at);
+            }
+            Ok(output) => {
+                let error = output.text.lines().next().unwrap_or_default().to_string();
+                self.status_message = StatusMessage::error(format!("ERR: {}: {}", command, error));
+            }
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("ERR: {}: {}", command, error));
+            }
+        }
+    }
+    /// Asks for a command and filters the selected lines, or else the whole buffer,
+    /// through it.
+    fn filter_command(&mut self) {
+        let (first, last) = self.selected_lines();
+        let command = self
+            .prompt("!", Some(HistoryKind::Command), |_, _, _| {})
+            .unwrap_or(None);
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+        if let Some(command) = command.filter(|command| !command.trim().is_empty()) {
+            self.filter_lines(first, last, command.trim());
+        }
+    }
+    /// The lines the selection covers in visual mode, otherwise the whole buffer.
+    fn selected_lines(&self) -> (usize, usize) {
+        if self.mode == Mode::Visual {
+            let (start, end) = self.selection();
+            // A selection ending at the start of a line does not take that line.
+            let last = if end.x == 0 && end.y > start.y {
+                end.y.saturating_sub(1)
+            } else {
+                end.y
+            };
+            (start.y, last)
+        } else {
+            (0, self.document.len().saturating_sub(1))
+        }
+    }
+    /// The text of buffer lines `lines`, each ending in a newline.
+    fn lines_text(&self, lines: impl Iterator<Item = usize>) -> String {
+        lines
+            .filter_map(|y| {
+                self.document
+                    .row(y)
+                    .map(|row| format!("{}\n", row.as_str()))
+            })
+            .collect()
+    }
+    /// Puts `output` in place of lines `first..=last` as one undo step.
+    fn replace_lines(&mut self, first: usize, last: usize, output: &str) {
+        let mut text = self.lines_text(0..first);
+        text.push_str(output);
+        if !output.is_empty() && !output.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push_str(&self.lines_text(last.saturating_add(1)..self.document.len()));
+        self.document.apply_text(&text);
+    }
+    /// Lines up `delimiter` across the selected lines, or the buffer, as one undo step.
+    /// Replaces matches of the substitution's pattern on its lines, as one undo step,
+    /// leaving the cursor on the last line changed.
+    fn substitute(&mut self, substitute: &Substitute) {
+        let (first, last) = match substitute.range {
+            substitute::Range::Selection if self.mode == Mode::Visual => self.selected_lines(),
+            substitute::Range::Selection => (self.cursor_position.y, self.cursor_position.y),
+            substitute::Range::All => (0, self.document.len().saturating_sub(1)),
+            substitute::Range::Lines(first, last) => (first, last),
+        };
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+        let options = search::Options {
+            case: substitute.case.unwrap_or(self.config.search_case),
+            whole_word: false,
+            regex: true,
+        };
+        let query = match Query::new(&substitute.pattern, options) {
+            Ok(query) => query,
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("ERR: {}", error));
+                return;
+            }
+        };
+        let mut count = 0_usize;
+        let edits: Vec<Edit> = (first..=last)
+            .filter_map(|y| {
+                let row = self.document.row(y)?;
+                let mut matches = query.find_all(row.as_str());
+                if !substitute.global {
+                    matches.truncate(1);
+                }
+                if matches.is_empty() {
+                    return None;
+                }
+                count = count.saturating_add(matches.len());
+                Some(Edit {
+                    start: Position { x: 0, y },
+                    end: Position { x: row.len(), y },
+                    text: substitute::replace(row.as_str(), &matches, &substitute.replacement),
+                })
+            })
+            .collect();
+        let y = match edits.last() {
+            Some(edit) => edit.start.y,
+            None => {
+                self.status_message =
+                    StatusMessage::error(format!("Pattern not found: {}", substitute.pattern));
+                return;
+            }
+        };
+        self.document.apply_edits(&edits, &mut []);
+        self.jump_to(&Position { x: 0, y });
+        self.status_message = StatusMessage::from(format!(
+            "{} {} on {} {}.",
+            count,
+            stats::plural(count, "substitution"),
+            edits.len(),
+            stats::plural(edits.len(), "line")
+        ));
+    }
+    fn align_lines(&mut self, delimiter: &str) {
+        let (first, last) = self.selected_lines();
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+        let delimiter = match align::Delimiter::parse(delimiter) {
+            Ok(delimiter) => delimiter,
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("ERR: {}", error));
+                return;
+            }
+        };
+        let lines: Vec<&str> = (first..=last)
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        let aligned = align::align(&lines, &delimiter);
+        let changed = lines
+            .iter()
+            .zip(&aligned)
+            .filter(|(line, aligned)| *line != aligned)
+            .count();
+        if changed > 0 {
+            let mut output = aligned.join("\n");
+            output.push('\n');
+            self.replace_lines(first, last, &output);
+            self.jump_to(&Position { x: 0, y: first });
+        }
+        self.status_message = StatusMessage::from(format!(
+            "{} {} aligned.",
+            changed,
+            stats::plural(changed, "line")
+        ));
+    }
+    /// Pretty-prints, or with `minify` compacts, the JSON in the selection or the
+    /// buffer. A parse error is reported, and the cursor put, where it was found.
+    fn format_json(&mut self, minify: bool) {
+        let (first, last) = self.selected_lines();
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+        let input = self.lines_text(first..last.saturating_add(1));
+        let indent = if self.document.indent.use_tabs {
+            "\t".to_string()
+        } else {
+            " ".repeat(self.document.indent.size)
+        };
+        match json::reformat(&input, (!minify).then_some(indent.as_str())) {
+            Ok(output) => {
+                self.replace_lines(first, last, &output);
+                self.jump_to(&Position { x: 0, y: first });
+                self.status_message = StatusMessage::from(if minify {
+                    "JSON minified.".to_string()
+                } else {
+                    "JSON formatted.".to_string()
+                });
+            }
+            Err((offset, message)) => {
+                let before: String = input.chars().take(offset).collect();
+                let y = first.saturating_add(before.matches('\n').count());
+                let column = before
+                    .rsplit('\n')
+                    .next()
+                    .map_or(0, |line| line.chars().count());
+                let x = self.document.row(y).map_or(0, |row| column.min(row.len()));
+                self.jump_to(&Position { x, y });
+                self.status_message = StatusMessage::error(format!(
+                    "Invalid JSON at line {}, column {}: {}",
+                    y.saturating_add(1),
+                    column.saturating_add(1),
+                    message
+                ));
+            }
+        }
+    }
+    /// Lists the transforms to pick one for the selection; typing filters.
+    fn transform_menu(&mut self) {
+        if self.mode != Mode::Visual {
+            self.status_message = StatusMessage::error("Select text to transform.".to_string());
+            return;
+        }
+        let mut query = String::new();
+        let mut selected = 0_usize;
+        let chosen = loop {
+            let lower = query.to_lowercase();
+            let matches: Vec<&(Transform, &str, &str)> = transform::TRANSFORMS
+                .iter()
+                .filter(|(_, name, description)| {
+                    name.contains(&lower) || description.to_lowercase().contains(&lower)
+                })
+                .collect();
+            selected = selected.min(matches.len().saturating_sub(1));
+            self.overlay = Some(
+                matches
+                    .iter()
+                    .enumerate()
+                    .map(|(index, (_, name, description))| {
+                        let marker = if index == selected { '>' } else { ' ' };
+                        format!("{} {:<14} {}", marker, description, name)
+                    })
+                    .collect(),
+            );
+            self.status_message = StatusMessage::from(format!("Transform: {}", query));
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+            match self.next_key() {
+                Ok(Key::Char('\n')) => {
+                    break matches.get(selected).map(|(transform, _, _)| *transform)
+                }
+                Ok(Key::Esc) | Err(_) => break None,
+                Ok(Key::Up | Key::Ctrl('p')) => selected = selected.saturating_sub(1),
+                Ok(Key::Down | Key::Ctrl('n')) => selected = selected.saturating_add(1),
+                Ok(Key::Backspace) => {
+                    query.pop();
+                    selected = 0;
+                }
+                Ok(Key::Char(c)) if !c.is_control() => {
+                    query.push(c);
+                    selected = 0;
+                }
+                Ok(_) => (),
+            }
+        };
+        self.overlay = None;
+        self.status_message = StatusMessage::from(String::new());
+        if let Some(transform) = chosen {
+            self.transform_selection(transform);
+        }
+    }
+    /// Replaces the selection with its text run through `transform`, as one undo step.
+    fn transform_selection(&mut self, transform: Transform) {
+        if self.mode != Mode::Visual {
+            self.status_message = StatusMessage::error("Select text to transform.".to_string());
+            return;
+        }
+        let (start, end) = self.selection();
+        let text = self.document.text_between(&start, &end);
+        match transform::apply(transform, &text) {
+            Ok(output) => {
+                self.document.replace_range(&start, &end, &output);
+                self.mode = Mode::Normal;
+                self.jump_to(&start);
+            }
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("Cannot transform: {}", error));
+            }
+        }
+    }
+    /// Works out `expression`, or the selected text when it is empty, with `calc`:
+    /// the result replaces the selection in visual mode and is otherwise put at the
+    /// cursor.
+    fn evaluate(&mut self, expression: &str) {
+        let selection = (self.mode == Mode::Visual).then(|| self.selection());
+        let expression = match &selection {
+            Some((start, end)) if expression.is_empty() => {
+                self.document.text_between(start, end).trim().to_string()
+            }
+            _ => expression.to_string(),
+        };
+        if expression.is_empty() {
+            self.status_message =
+                StatusMessage::error("Type an expression after =, or select one.".to_string());
+            return;
+        }
+        let result = match calc::evaluate(&expression) {
+            Ok(value) => value.to_string(),
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("ERR: {}", error));
+                return;
+            }
+        };
+        let (start, end) = selection
+            .unwrap_or_else(|| (self.cursor_position.clone(), self.cursor_position.clone()));
+        let after = self.document.replace_range(&start, &end, &result);
+        if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+        }
+        self.jump_to(&Position {
+            x: after.x.saturating_sub(1),
+            y: after.y,
+        });
+        self.status_message = StatusMessage::from(format!("{} = {}", expression, result));
+    }
+    /// Replaces lines `first..=last` with their output through the shell command
+    /// `command`, e.g. `sort` or `jq .`.
+    fn filter_lines(&mut self, first: usize, last: usize, command: &str) {
+        let input = self.lines_text(first..last.saturating_add(1));
+        let output = match formatter::run(command, &input) {
+            Ok(output) => output,
+            Err(error) => {
+                self.status_message = StatusMessage::error(format!("Filter failed: {}", error));
+                return;
+            }
+        };
+        self.replace_lines(first, last, &output);
+        self.jump_to(&Position { x: 0, y: first });
+        let lines = last.saturating_add(1).saturating_sub(first);
+        self.status_message = StatusMessage::from(format!(
+            "{} {} filtered through {}",
+            lines,
+            stats::plural(lines, "line"),
+            command
+        ));
+    }
+    fn format_command(&mut self) {
+        self.status_message = match self.format_document() {
+            Ok(Some(0)) => StatusMessage::from("Already formatted.".to_string()),
+            Ok(Some(lines)) => StatusMessage::from(format!("Formatted: {} lines changed.", lines)),
+            Ok(None) => {
+                StatusMessage::error("No formatter or language server for this buffer.".to_string())
+            }
+            Err(error) => StatusMessage::error(format!("Formatting failed: {}", error)),
+        };
+    }
+    fn ask(&mut self, question: &str) -> Option<char> {
+        self.status_message = StatusMessage::from(question.to_string());
+        if self.refresh_screen().is_err() {
+            return None;
+        }
+        let key = self.next_key().ok();
+        self.status_message = StatusMessage::from(String::new());
+        match key {
+            Some(Key::Char(c)) => Some(c.to_ascii_lowercase()),
+            _ => None,
+        }
+    }
+    /// Offers to save a file the user may not write through sudo. Returns `None` when
+    /// the user declines.
+    fn sudo_save(&mut self) -> Option<Result<(), Error>> {
+        let file_name = self.document.file_name.clone()?;
+        if remote::is_remote(&file_name)
+            || self.ask(&format!(
+                "Permission denied writing {}. Save with sudo? (y/n) ",
+                file_name
+            )) != Some('y')
+        {
+            return None;
+        }
+        if !sudo::is_authenticated() {
+            let password = self
+                .terminal
+                .read_line(&format!("[sudo] password for {}: ", file_name), true)
+                .ok()
+                .flatten()?;
+            if let Err(error) = sudo::authenticate(&password) {
+                return Some(Err(error));
+            }
+        }
+        log::info("document", format!("saving {} with sudo", file_name));
+        Some(self.write_document(true))
+    }
+    fn confirm_overwrite(&mut self) -> bool {
+        let overwrite = loop {
+            match self.ask("File changed on disk! (o)verwrite, (r)eload, (d)iff, (c)ancel? ") {
+                Some('o') => break true,
+                Some('r') => {
+                    self.reload_document();
+                    break false;
+                }
+                Some('d') => self.show_disk_diff(),
+                _ => {
+                    self.status_message = StatusMessage::error("Save Aborted.".to_string());
+                    break false;
+                }
+            }
+        };
+        self.overlay = None;
+        overwrite
+    }
+    fn reload_document(&mut self) {
+        self.disk_conflict_ignored = false;
+        if let Ok(hunks) = self.document.reload() {
+            self.remove_swap();
+            let y = diff::map_line(&hunks, self.cursor_position.y);
+            let x = self.cursor_position.x;
+            self.jump_to(&Position { x, y });
+            self.status_message = StatusMessage::from("Reloaded from disk.".to_string());
+        } else {
+            self.status_message = StatusMessage::error("Error reloading file!".to_string());
+        }
+    }
+    fn show_disk_diff(&mut self) {
+        let disk = self.document.disk_contents().unwrap_or_default();
+        let disk: Vec<&str> = disk.lines().collect();
+        let ours: Vec<&str> = (0..self.document.len())
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        let mut lines = vec!["Differences (- on disk, + in buffer):".to_string()];
+        for hunk in diff::lines_with(&disk, &ours, self.config.diff_algorithm) {
+            for y in hunk.old_start..hunk.old_start.saturating_add(hunk.old_len) {
+                let theirs = disk.get(y).copied().unwrap_or_default();
+                lines.push(format!("-{:>5}: {}", y.saturating_add(1), theirs));
+            }
+            for y in hunk.new_start..hunk.new_start.saturating_add(hunk.new_len) {
+                let ours = ours.get(y).copied().unwrap_or_default();
+                lines.push(format!("+{:>5}: {}", y.saturating_add(1), ours));
+            }
+        }
+        self.overlay = Some(lines);
+    }
+    /// How search matches, from the `search_*` settings.
+    fn search_options(&self) -> search::Options {
+        search::Options {
+            case: self.config.search_case,
+            whole_word: self.config.search_whole_word,
+            regex: self.config.search_regex,
+        }
+    }
+    /// Searches incrementally, highlighting the matches as the query is typed; Esc
+    /// goes back to where the cursor was and to the previous search.
+    fn search(&mut self) {
+        let old_position = self.cursor_position.clone();
+        let old_search = self.last_search.take();
+        let old_highlight = self.search_highlight;
+        let mut direction = SearchDirection::Forward;
+        let text = self
+            .prompt_with(
+                |editor| {
+                    let flags = editor.search_options().flags();
+                    format!(
+                        "Search{}{} (Esc to cancel, Left/Right for matches, \
+                         Alt-c/w/r for case/word/regex): ",
+                        if flags.is_empty() { "" } else { " " },
+                        flags
+                    )
+                },
+                Some(HistoryKind::Search),
+                |editor, key, text| {
+                    let mut moved = false;
+                    match key {
+                        Key::Right => {
+                            editor.move_cursor(Key::Right);
+                            direction = SearchDirection::Forward;
+                            moved = true;
+                        }
+                        Key::Left => direction = SearchDirection::Backward,
+                        Key::Alt('c') => {
+                            editor.config.search_case = editor.config.search_case.next();
+                        }
+                        Key::Alt('w') => {
+                            editor.config.search_whole_word = !editor.config.search_whole_word;
+                        }
+                        Key::Alt('r') => editor.config.search_regex = !editor.config.search_regex,
+                        _ => direction = SearchDirection::Forward,
+                    }
+                    // A regular expression being typed is often not yet valid.
+                    editor.last_search = Query::new(text, editor.search_options()).ok();
+                    editor.search_highlight = true;
+                    let position = editor.last_search.as_ref().and_then(|query| {
+                        editor
+                            .document
+                            .find(query, &editor.cursor_position, direction)
+                    });
+                    if let Some(position) = position {
+                        editor.cursor_position = position;
+                        editor.scroll();
+                    } else if moved {
+                        editor.move_cursor(Key::Left);
+                    }
+                },
+            )
+            .unwrap_or(None);
+        if let Some(text) = text {
+            self.record_jump(old_position);
+            if let Err(error) = Query::new(&text, self.search_options()) {
+                self.status_message = StatusMessage::error(error);
+            }
+        } else {
+            self.last_search = old_search;
+            self.search_highlight = old_highlight;
+            self.cursor_position = old_position;
+            self.scroll();
+        }
+    }
+    /// The last search while its matches are highlighted.
+    fn highlighted_search(&self) -> Option<&Query> {
+        self.last_search
+            .as_ref()
+            .filter(|_| self.search_highlight && !self.document.large)
+    }
+    /// Which match of the highlighted search the cursor is at, if any, and how many
+    /// there are in the buffer.
+    fn search_match_count(&self) -> Option<(Option<usize>, usize)> {
+        let query = self.highlighted_search()?;
+        let cursor = &self.cursor_position;
+        let mut index = None;
+        let mut count = 0_usize;
+        for y in 0..self.document.len() {
+            let row = self.document.row(y)?;
+            for (start, _) in row.matches(query) {
+                count = count.saturating_add(1);
+                if y == cursor.y && start == cursor.x {
+                    index = Some(count);
+                }
+            }
+        }
+        Some((index, count))
+    }
+    /// Goes to the next or previous match of the last search, wrapping around the
+    /// ends of the buffer, and highlights the matches again.
+    fn goto_match(&mut self, forward: bool) {
+        let query = if let Some(query) = &self.last_search {
+            query
+        } else {
+            self.status_message = StatusMessage::error("No previous search".to_string());
+            return;
+        };
+        let (from, direction, wrap_from) = if forward {
+            let from = Position {
+                x: self.cursor_position.x.saturating_add(1),
+                y: self.cursor_position.y,
+            };
+            (from, SearchDirection::Forward, Position::default())
+        } else {
+            let y = self.document.len().saturating_sub(1);
+            let end = Position {
+                x: self.document.row(y).map_or(0, Row::len),
+                y,
+            };
+            (self.cursor_position.clone(), SearchDirection::Backward, end)
+        };
+        let (position, wrapped) = match self.document.find(query, &from, direction) {
+            Some(position) => (position, false),
+            None => match self.document.find(query, &wrap_from, direction) {
+                Some(position) => (position, true),
+                None => {
+                    self.status_message = StatusMessage::error("Pattern not found".to_string());
+                    return;
+                }
+            },
+        };
+        self.search_highlight = true;
+        let old_position = self.cursor_position.clone();
+        self.jump_to(&position);
+        self.record_jump(old_position);
+        if wrapped {
+            self.status_message = StatusMessage::from(if forward {
+                "Search wrapped to the top".to_string()
+            } else {
+                "Search wrapped to the bottom".to_string()
+            });
+        }
+    }
+    fn goto_line(&mut self) {
+        let input = self
+            .prompt("Go to line: ", None, |_, _, _| {})
+            .unwrap_or(None);
+        let input = if let Some(input) = input {
+            input
+        } else {
+            return;
+        };
+        if let Ok(line) = input.trim().parse::<usize>() {
+            self.goto_line_number(line);
+        } else {
+            self.status_message = StatusMessage::error("Invalid line number.".to_string());
+        }
+    }
+    fn goto_line_number(&mut self, line: usize) {
+        let old_position = self.cursor_position.clone();
+        self.jump_to(&Position {
+            x: 0,
+            y: line.saturating_sub(1),
+        });
+        self.record_jump(old_position);
+    }
+    fn run_command(&mut self, command: Command) {
+        match command {
+            Command::Save => self.save(),
+            Command::Quit => self.quit(false),
+            Command::ForceQuit => self.quit(true),
+            Command::SaveAndQuit => {
+                self.save();
+                self.quit(false);
+            }
+            Command::Revert => self.revert(),
+            Command::ExLine => self.command_line(),
+            Command::Palette => self.command_palette(),
+            Command::Find => self.search(),
+            Command::NextMatch => self.goto_match(true),
+            Command::PreviousMatch => self.goto_match(false),
+            Command::ClearSearchHighlight => self.search_highlight = false,
+            Command::GotoLine => self.goto_line(),
+            Command::JumpBack => self.jump_back(),
+            Command::JumpForward => self.jump_forward(),
+            Command::NextBuffer => self.cycle_buffer(true),
+            Command::PreviousBuffer => self.cycle_buffer(false),
+            Command::ShowMarks => self.show_marks(),
+            Command::ShowOptions => self.show_options(),
+            Command::ToggleLineNumbers => self.set_command("number!"),
+            Command::ToggleWrap => self.set_command("wrap!"),
+            Command::ToggleReadOnly => self.set_command("readonly!"),
+            Command::Help => self.help(""),
+            Command::Complete => self.start_completion(),
+            Command::NextDiagnostic => self.goto_diagnostic(true),
+            Command::NextHunk => self.goto_hunk(true),
+            Command::PreviousHunk => self.goto_hunk(false),
+            Command::PreviousDiagnostic => self.goto_diagnostic(false),
+            Command::FixLint => self.fix_lints(false),
+            Command::Hover => self.request_hover(),
+            Command::GotoDefinition => self.request_location("textDocument/definition"),
+            Command::FindReferences => self.request_location("textDocument/references"),
+            Command::Rename => self.rename(None),
+            Command::CodeAction => self.request_code_actions(),
+            Command::Format => self.format_command(),
+            Command::Outline => self.toggle_outline(),
+            Command::ToggleBlame => self.config.blame = !self.config.blame,
+            Command::WritingMode => self.toggle_writing(),
+            Command::NextConflict => self.goto_conflict(true),
+            Command::PreviousConflict => self.goto_conflict(false),
+            Command::AcceptOurs => self.resolve_conflict(Resolution::Ours),
+            Command::AcceptTheirs => self.resolve_conflict(Resolution::Theirs),
+            Command::AcceptBoth => self.resolve_conflict(Resolution::Both),
+            Command::StageHunk => self.stage_hunk(),
+            Command::RevertHunk => self.revert_hunk(),
+            Command::PreviewHunk => self.preview_hunk(),
+            Command::DiffView => self.show_diff(false),
+            Command::ToggleSpell => self.config.spell = !self.config.spell,
+            Command::NextMisspelling => self.goto_misspelling(true),
+            Command::PreviousMisspelling => self.goto_misspelling(false),
+            Command::SpellSuggest => self.suggest_spelling(),
+            Command::SpellAddWord => self.add_word_to_dictionary(None),
+            Command::Thesaurus => self.look_up_synonyms(),
+            Command::InsertUnicode => self.unicode_picker(),
+            Command::InspectCharacter => self.inspect_character(),
+            Command::WordCount => self.count_words(),
+            Command::ToggleColorColumn => self.hide_color_columns = !self.hide_color_columns,
+            Command::ToggleMinimap => self.set_command("minimap!"),
+            Command::RecentFiles => self.recent_files(),
+            Command::BufferPicker => self.buffer_picker(),
+            Command::GotoAnything => self.goto_anything(),
+            Command::Filter => self.filter_command(),
+            Command::Terminal => self.focus_terminal_pane(),
+            Command::OpenUrl => self.open_url(),
+            Command::OpenFileUnderCursor => self.open_file_under_cursor(),
+            Command::ToggleCheckbox => self.toggle_checkbox(),
+            Command::AlignTable => self.align_table(),
+            Command::MarkdownPreview => self.toggle_preview(),
+            Command::GrowPanel => self.resize_panel(true),
+            Command::ShrinkPanel => self.resize_panel(false),
+            Command::EqualizePanels => self.equalize_panels(),
+            Command::SplitWindow => self.split_window(""),
+            Command::CloseWindow => self.close_window(),
+            Command::OnlyWindow => self.only_window(),
+            Command::NextWindow => self.cycle_window(true),
+            Command::PreviousWindow => self.cycle_window(false),
+            Command::GrowWindow => {
+                let rows = cmp::max(self.count, 1);
+                self.count = 0;
+                self.resize_window(self.text_height().saturating_add(rows));
+            }
+            Command::ShrinkWindow => {
+                let rows = cmp::max(self.count, 1);
+                self.count = 0;
+                self.resize_window(self.text_height().saturating_sub(rows));
+            }
+            Command::EqualizeWindows => self.equalize_windows(),
+            Command::RotateWindows => self.rotate_windows(),
+            Command::SwapWindow => self.swap_window(),
+            Command::DebugOverlay => self.debug_overlay = !self.debug_overlay,
+            Command::ExportHtml => self.export("", false),
+            Command::CopyHtml => self.copy(true),
+            Command::NextCell => self.move_by_cell(true),
+            Command::PreviousCell => self.move_by_cell(false),
+            Command::FormatJson => self.format_json(false),
+            Command::MinifyJson => self.format_json(true),
+            Command::Transform => self.transform_menu(),
+            Command::UndoTree => self.undo_tree(),
+            Command::OpenFold => self.open_fold(),
+            Command::CloseFold => self.close_fold(),
+            Command::ToggleFold => self.toggle_fold(),
+            Command::OpenAllFolds => {
+                self.document.folds.open_all();
+                self.scroll();
+            }
+            Command::CloseAllFolds => self.close_all_folds(),
+            Command::ScrollLineDown => self.scroll_view(true),
+            Command::ScrollLineUp => self.scroll_view(false),
+            Command::CenterCursorLine => {
+                let height = self.text_height();
+                self.scroll_cursor_to(height.saturating_sub(1) / 2);
+            }
+            Command::CursorLineToTop => self.scroll_cursor_to(self.scroll_margin()),
+            Command::CursorLineToBottom => {
+                let height = self.text_height();
+                self.scroll_cursor_to(
+                    height
+                        .saturating_sub(1)
+                        .saturating_sub(self.scroll_margin()),
+                );
+            }
+            Command::Undo => self.undo(false),
+            Command::Redo => self.undo(true),
+            Command::JumpToTag => self.jump_to_tag(None),
+            Command::PopTag => self.pop_tag(),
+        }
+    }
+    /// Commands matching the palette `query`, best match first.
+    fn palette_matches(query: &str) -> Vec<&'static CommandInfo> {
+        let mut matches: Vec<(usize, &'static CommandInfo)> = commands::COMMANDS
+            .iter()
+            .filter_map(|info| commands::fuzzy_score(query, info.name).map(|score| (score, info)))
+            .collect();
+        matches.sort_by(|(a, a_info), (b, b_info)| b.cmp(a).then(a_info.name.cmp(b_info.name)));
+        matches.into_iter().map(|(_, info)| info).collect()
+    }
+    fn command_palette(&mut self) {
+        let mut query = String::new();
+        let mut selected = 0_usize;
+        let chosen = loop {
+            let matches = Self::palette_matches(&query);
+            selected = selected.min(matches.len().saturating_sub(1));
+            let height = self.terminal.size().height as usize;
+            self.overlay = Some(
+                matches
+                    .iter()
+                    .take(height.saturating_sub(1))
+                    .enumerate()
+                    .map(|(index, info)| {
+                        let keys: Vec<String> = self
+                            .config
+                            .bindings
+                            .keys_for(info.command)
+                            .into_iter()
+                            .map(commands::key_name)
+                            .collect();
+                        let marker = if index == selected { '>' } else { ' ' };
+                        format!(
+                            "{} {:<22} {:<14} {}",
+                            marker,
+                            info.name,
+                            keys.join(", "),
+                            info.description
+                        )
+                    })
+                    .collect(),
+            );
+            self.status_message = StatusMessage::from(format!("Command: {}", query));
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+            match self.next_key() {
+                Ok(Key::Char('\n')) => break matches.get(selected).map(|info| info.command),
+                Ok(Key::Esc) | Err(_) => break None,
+                Ok(Key::Up | Key::Ctrl('p')) => selected = selected.saturating_sub(1),
+                Ok(Key::Down | Key::Ctrl('n')) => selected = selected.saturating_add(1),
+                Ok(Key::Backspace) => {
+                    query.pop();
+                    selected = 0;
+                }
+                Ok(Key::Char(c)) if !c.is_control() => {
+                    query.push(c);
+                    selected = 0;
+                }
+                Ok(_) => (),
+            }
+        };
+        self.overlay = None;
+        self.status_message = StatusMessage::from(String::new());
+        if let Some(command) = chosen {
+            self.run_command(command);
+        }
+    }
+    fn command_line(&mut self) {
+        if let Some(command) = self
+            .prompt(":", Some(HistoryKind::Command), |_, _, _| {})
+            .unwrap_or(None)
+        {
+            self.execute_command(command.trim());
+        }
+    }
+    fn execute_command(&mut self, command: &str) {
+        let last = self.document.len().saturating_sub(1);
+        if let Some(parsed) = substitute::parse(command, self.cursor_position.y, last) {
+            if self.hex_rejects(Ex::Substitute) {
+                return;
+            }
+            match parsed {
+                Ok(substitute) => self.substitute(&substitute),
+                Err(error) => self.status_message = StatusMessage::error(format!("ERR: {}", error)),
+            }
+            return;
+        }
+        // Commands that start with a symbol rather than a name.
+        let symbol = if let Some(shell_command) = command.strip_prefix("%!") {
+            Some((Ex::Filter, shell_command))
+        } else if let Some(shell_command) = command.strip_prefix('!') {
+            Some((Ex::Shell, shell_command))
+        } else if let Some(expression) = command.strip_prefix('=') {
+            Some((Ex::Evaluate, expression))
+        } else if command.parse::<usize>().is_ok() {
+            Some((Ex::GotoLine, command))
+        } else {
+            None
+        };
+        if let Some((ex, arguments)) = symbol {
+            self.run_ex(ex, false, arguments.trim());
+            return;
+        }
+        let (word, arguments) = command
+            .split_once(char::is_whitespace)
+            .map_or((command, ""), |(word, arguments)| (word, arguments.trim()));
+        let (name, bang) = word
+            .strip_suffix('!')
+            .map_or((word, false), |name| (name, true));
+        let found = commands::find_ex(name);
+        if found.is_none() && !bang {
+            if let Some((index, export)) = self.plugins.command(name) {
+                self.call_plugin(index, &export, arguments);
+                return;
+            }
+        }
+        let info = match found {
+            Some(info) if !bang || info.bang => info,
+            None if !bang
+                && self
+                    .scripts
+                    .as_ref()
+                    .is_some_and(|scripts| scripts.has_command(name)) =>
+            {
+                let started = self
+                    .scripts
+                    .as_ref()
+                    .and_then(|scripts| scripts.command(name, arguments));
+                if let Some(started) = started {
+                    self.run_script(started);
+                }
+                return;
+            }
+            _ => {
+                self.status_message =
+                    StatusMessage::error(format!("Not an editor command: {}", command));
+                return;
+            }
+        };
+        if !arguments.is_empty() && !info.takes_arguments() {
+            self.status_message =
+                StatusMessage::error(format!("Trailing characters: {}", arguments));
+        } else if arguments.is_empty() && info.needs_arguments() {
+            self.status_message =
+                StatusMessage::error(format!("Argument required: :{}", info.syntax()));
+        } else {
+            self.run_ex(info.ex, bang, arguments);
+        }
+    }
+    /// Refuses a command that rewrites lines when the buffer is shown as hex rows,
+    /// which are only a rendering of its bytes.
+    fn hex_rejects(&mut self, ex: Ex) -> bool {
+        let rejects = ex.edits_lines() && self.document.is_binary();
+        if rejects {
+            self.status_message = StatusMessage::error(
+                "Not in a hex view: type over the bytes to change them".to_string(),
+            );
+        }
+        rejects
+    }
+    /// Runs the ex command `ex`, typed with `!` when `bang` is set.
+    fn run_ex(&mut self, ex: Ex, bang: bool, arguments: &str) {
+        if self.hex_rejects(ex) {
+            return;
+        }
+        match ex {
+            Ex::Write if bang => {
+                let read_only = self.document.read_only;
+                self.document.read_only = false;
+                self.save();
+                self.document.read_only = read_only;
+            }
+            Ex::Write => self.save(),
+            Ex::Quit => self.quit(bang),
+            Ex::WriteQuit => {
+                self.save();
+                self.quit(false);
+            }
+            Ex::Edit if arguments.is_empty() && bang => self.revert(),
+            Ex::Edit if arguments.is_empty() => {
+                self.status_message = StatusMessage::error("No file name".to_string());
+            }
+            Ex::Edit => self.edit_file(arguments),
+            Ex::NextBuffer => self.cycle_buffer(true),
+            Ex::PreviousBuffer => self.cycle_buffer(false),
+            Ex::Buffer => match arguments.parse::<usize>() {
+                Ok(number) => self.switch_buffer(number.saturating_sub(1)),
+                Err(_) => {
+                    self.status_message =
+                        StatusMessage::error(format!("Not a buffer number: {}", arguments));
+                }
+            },
+            Ex::DeleteBuffer => self.close_buffer(bang),
+            Ex::Buffers => self.buffer_picker(),
+            Ex::Scratch => self.scratch(),
+            Ex::Tutor => self.tutor(),
+            Ex::Set => self.set_command(arguments),
+            Ex::FileFormat => {
+                let name = arguments.split_whitespace().next().unwrap_or_default();
+                if let Some(line_ending) = LineEnding::from_name(name) {
+                    self.document.set_line_ending(line_ending);
+                    self.status_message = StatusMessage::from(format!(
+                        "Line endings set to {}; save to convert the file.",
+                        line_ending.name()
+                    ));
+                } else {
+                    self.status_message =
+                        StatusMessage::error(format!("Unknown file format: {}", name));
+                }
+            }
+            Ex::Encoding => {
+                let name = arguments.split_whitespace().next().unwrap_or_default();
+                if let Some(encoding) = Encoding::from_name(name) {
+                    self.document.set_encoding(encoding);
+                    self.status_message = StatusMessage::from(format!(
+                        "Encoding set to {}; save to convert the file.",
+                        encoding.name()
+                    ));
+                } else {
+                    self.status_message =
+                        StatusMessage::error(format!("Unknown encoding: {}", name));
+                }
+            }
+            Ex::Bom => {
+                self.document.set_bom(true);
+                self.status_message =
+                    StatusMessage::from("Byte order mark will be written on save.".to_string());
+            }
+            Ex::NoBom => {
+                self.document.set_bom(false);
+                self.status_message =
+                    StatusMessage::from("Byte order mark will be removed on save.".to_string());
+            }
+            Ex::Help => self.help(arguments),
+            Ex::LanguageServers => self.show_language_servers(),
+            Ex::Definition => self.request_location("textDocument/definition"),
+            Ex::References => self.request_location("textDocument/references"),
+            Ex::NextQuickfix => self.step_quickfix(true),
+            Ex::PreviousQuickfix => self.step_quickfix(false),
+            Ex::Quickfix => match arguments.parse::<usize>() {
+                Ok(number) => self.goto_quickfix(number.saturating_sub(1)),
+                Err(_) => {
+                    self.status_message =
+                        StatusMessage::error(format!("Not an entry number: {}", arguments));
+                }
+            },
+            Ex::QuickfixList => self.show_quickfix(),
+            Ex::Rename if arguments.is_empty() => self.rename(None),
+            Ex::Rename => self.rename(Some(arguments)),
+            Ex::CodeAction => self.request_code_actions(),
+            Ex::Format => self.format_command(),
+            Ex::FixLint => self.fix_lints(true),
+            Ex::Outline => self.toggle_outline(),
+            Ex::Preview => self.toggle_preview(),
+            Ex::Json => self.format_json(false),
+            Ex::MinifyJson => self.format_json(true),
+            Ex::Blame => self.config.blame = !self.config.blame,
+            Ex::Writing => self.toggle_writing(),
+            Ex::Ours => self.resolve_conflict(Resolution::Ours),
+            Ex::Theirs => self.resolve_conflict(Resolution::Theirs),
+            Ex::Both => self.resolve_conflict(Resolution::Both),
+            Ex::Diff if arguments.is_empty() => self.show_diff(false),
+            Ex::Diff if arguments == "head" => self.show_diff(true),
+            Ex::Diff => {
+                self.status_message =
+                    StatusMessage::error(format!("Can only diff against head: {}", arguments));
+            }
+            Ex::SpellGood => self.add_word_to_dictionary(Some(arguments)),
+            Ex::Digraphs => self.show_digraphs(),
+            Ex::Ascii => self.inspect_character(),
+            Ex::UndoTree => self.undo_tree(),
+            Ex::Debug => self.debug_overlay = !self.debug_overlay,
+            Ex::Align => self.align_lines(arguments),
+            Ex::Transform if arguments.is_empty() => self.transform_menu(),
+            Ex::Transform => match transform::named(arguments) {
+                Some(transform) => self.transform_selection(transform),
+                None => {
+                    self.status_message =
+                        StatusMessage::error(format!("Unknown transform: {}", arguments));
+                }
+            },
+            Ex::Export => self.export(arguments, bang),
+            Ex::Log if arguments.is_empty() => {
+                self.status_message = StatusMessage::from(format!(
+                    "Log level: {} ({})",
+                    log::filter(),
+                    log::path().map_or_else(String::new, |path| path.display().to_string())
+                ));
+            }
+            Ex::Log => {
+                self.status_message = match log::set_filter(arguments) {
+                    Ok(()) => StatusMessage::from(format!("Log level: {}", log::filter())),
+                    Err(error) => StatusMessage::error(error),
+                };
+            }
+            Ex::Logs => self.show_log(),
+            Ex::Earlier => self.undo_travel(arguments, false),
+            Ex::Later => self.undo_travel(arguments, true),
+            Ex::Recent => self.recent_files(),
+            Ex::Goto => self.goto_anything(),
+            Ex::Search => self.search_project(arguments),
+            Ex::Replace => self.plan_replace(arguments),
+            Ex::Evaluate => self.evaluate(arguments),
+            Ex::Shell => self.run_shell_command(arguments),
+            Ex::Read => match arguments.strip_prefix('!') {
+                Some(shell_command) => self.read_shell_command(shell_command.trim()),
+                None => {
+                    self.status_message =
+                        StatusMessage::error("Only :r !<command> is supported.".to_string());
+                }
+            },
+            Ex::Filter => {
+                self.filter_lines(0, self.document.len().saturating_sub(1), arguments);
+            }
+            Ex::Job => self.start_job(arguments),
+            Ex::Jobs => self.show_jobs(),
+            Ex::Plugins => self.show_plugins(),
+            Ex::Lua => {
+                let started = self
+                    .scripts
+                    .as_ref()
+                    .map(|scripts| scripts.run_code(arguments, "=:lua"));
+                match started {
+                    Some(started) => self.run_script(started),
+                    None => {
+                        self.status_message =
+                            StatusMessage::error("Lua is not available".to_string());
+                    }
+                }
+            }
+            Ex::JobKill => self.kill_job(arguments),
+            Ex::Terminal => self.focus_terminal_pane(),
+            Ex::Autocmd => self.autocmd(arguments),
+            Ex::Make => self.make(arguments),
+            Ex::Session => self.make_session(arguments, bang),
+            Ex::Split => self.split_window(arguments),
+            Ex::Close => self.close_window(),
+            Ex::Only => self.only_window(),
+            Ex::Resize => self.resize_command(arguments),
+            Ex::WordCount => self.count_words(),
+            Ex::Abbreviate => self.abbreviate(arguments),
+            Ex::Undo => self.undo(false),
+            Ex::Redo => self.undo(true),
+            Ex::Tag => self.jump_to_tag(Some(arguments)),
+            Ex::Pop => self.pop_tag(),
+            Ex::Tags => self.show_tag_stack(),
+            Ex::NoHighlight => self.search_highlight = false,
+            Ex::GotoLine => {
+                if let Ok(line) = arguments.parse::<usize>() {
+                    self.goto_line_number(line);
+                }
+            }
+            // Recognized by its pattern before any name is looked up.
+            Ex::Substitute => (),
+        }
+    }
+    fn set_command(&mut self, arguments: &str) {
+        let arguments = options::split_arguments(arguments);
+        if arguments.is_empty() {
+            self.show_options();
+            return;
+        }
+        let mut messages = Vec::new();
+        let mut failed = false;
+        for argument in arguments {
+            let result = options::parse(&argument)
+                .and_then(|(option, action)| self.apply_option(option, action));
+            match result {
+                Ok(Some(message)) => messages.push(message),
+                Ok(None) => (),
+                Err(error) => {
+                    messages.push(error);
+                    failed = true;
+                    break;
+                }
+            }
+        }
+        let message = messages.join("  ");
+        self.status_message = if failed {
+            StatusMessage::error(message)
+        } else {
+            StatusMessage::from(message)
+        };
+    }
+    fn show_options(&mut self) {
+        let mut lines = vec!["option            value       scope   description".to_string()];
+        for option in options::OPTIONS {
+            lines.push(format!(
+                "{:<17} {:<11} {:<7} {}",
+                option.name,
+                self.option_value(option).to_string(),
+                option.scope.name(),
+                option.description
+            ));
+        }
+        self.overlay = Some(lines);
+    }
+    fn apply_option(
+        &mut self,
+        option: &OptionInfo,
+        action: options::Action,
+    ) -> Result<Option<String>, String> {
+        match action {
+            options::Action::Query => Ok(Some(match self.option_value(option) {
+                options::Value::Bool(true) => option.name.to_string(),
+                options::Value::Bool(false) => format!("no{}", option.name),
+                value => format!("{}={}", option.name, value),
+            })),
+            options::Action::Toggle => {
+                let value = self.option_value(option) != options::Value::Bool(true);
+                self.set_option(option, options::Value::Bool(value))
+                    .map(|_| None)
+            }
+            options::Action::Set(value) => self.set_option(option, value).map(|_| None),
+        }
+    }
+    fn option_value(&self, option: &OptionInfo) -> options::Value {
+        use options::Value::{Bool, Number, Text};
+        let document = &self.document;
+        match option.name {
+            "number" => Bool(self.window.line_numbers),
+            "wrap" => Bool(self.window.wrap),
+            "list" => Bool(self.window.list),
+            "listchars" => Text(self.config.list_chars.spec()),
+            "indentguides" => Bool(self.config.indent_guides),
+            "cursorline" => Bool(self.config.cursor_line),
+            "cursorcolumn" => Bool(self.config.cursor_column),
+            "blame" => Bool(self.config.blame),
+            "scrolloff" => Number(self.config.scroll_off),
+            "writingwidth" => Number(self.config.writing_width),
+            "bidi" => Text(self.config.bidi.name().to_string()),
+            "scrollbar" => Bool(self.config.scrollbar),
+            "minimap" => Bool(self.config.minimap),
+            "kittykeyboard" => Bool(self.config.kitty_keyboard),
+            "dimunfocused" => Bool(self.config.dim_unfocused),
+            "syntax" => Bool(self.config.syntax),
+            "rainbowbrackets" => Bool(self.config.rainbow_brackets),
+            "colorswatches" => Bool(self.config.color_swatches),
+            "columnview" => Bool(self.config.column_view),
+            "elastictabstops" => Bool(self.config.elastic_tabstops),
+            "softtabmotion" => Bool(self.config.soft_tab_motion),
+            "scrollbind" => Bool(self.window.scroll_bind),
+            "stickycolumn" => Bool(self.config.sticky_column),
+            "smoothscroll" => Number(self.config.smooth_scroll.map_or(0, |duration| {
+                usize::try_from(duration.as_millis()).unwrap_or(usize::MAX)
+            })),
+            "lint" => Bool(self.config.lint),
+            "spell" => Bool(self.config.spell),
+            "spelllang" => Text(self.config.spell_lang.clone()),
+            "wordcount" => Bool(self.config.word_count),
+            "searchcase" => Text(self.config.search_case.name().to_string()),
+            "diffalgorithm" => Text(self.config.diff_algorithm.name().to_string()),
+            "wholeword" => Bool(self.config.search_whole_word),
+            "searchregex" => Bool(self.config.search_regex),
+            "autosave" => Number(self.config.auto_save.map_or(0, |interval| {
+                usize::try_from(interval.as_secs()).unwrap_or(usize::MAX)
+            })),
+            "modeline" => Bool(self.config.modelines),
+            "theme" => Text(self.config.theme_name.clone()),
+            "tabstop" => Number(document.indent.tab_width),
+            "shiftwidth" => Number(document.indent.size),
+            "expandtab" => Bool(!document.indent.use_tabs),
+            "readonly" => Bool(document.read_only),
+            "fileformat" => Text(document.line_ending.name().to_string()),
+            "fileencoding" => Text(document.encoding.name().to_string()),
+            "bomb" => Bool(document.has_bom()),
+            "colorcolumn" => Text(
+                document
+                    .color_columns
+                    .iter()
+                    .map(usize::to_string)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            ),
+            "filetype" => Text(document.file_type.clone().unwrap_or_default()),
+            "comment" => Text(document.comment.clone().unwrap_or_default()),
+            "formatter" => Text(document.formatter.clone().unwrap_or_default()),
+            "formatonsave" => Bool(document.format_on_save),
+            "trimwhitespace" => Bool(document.save_options.trim_trailing_whitespace),
+            "fixendofline" => Bool(document.save_options.insert_final_newline),
+            "pathcompletion" => Bool(document.path_completion),
+            _ => Text(String::new()),
+        }
+    }
+    fn set_option(&mut self, option: &OptionInfo, value: options::Value) -> Result<(), String> {
+        use options::Value::{Bool, Number, Text};
+        let document = &mut self.document;
+        let optional = |text: String| (!text.is_empty()).then_some(text);
+        match (option.name, value) {
+            ("number", Bool(value)) => self.window.line_numbers = value,
+            ("wrap", Bool(value)) => {
+                self.window.wrap = value;
+                self.offset.x = 0;
+            }
+            ("autosave", Number(seconds)) => {
+                self.config.auto_save = (seconds > 0)
+                    .then(|| Duration::from_secs(u64::try_from(seconds).unwrap_or(u64::MAX)));
+            }
+            ("modeline", Bool(value)) => self.config.modelines = value,
+            ("list", Bool(value)) => self.window.list = value,
+            ("listchars", Text(spec)) => self.config.list_chars = ListChars::parse(&spec)?,
+            ("indentguides", Bool(value)) => self.config.indent_guides = value,
+            ("cursorline", Bool(value)) => self.config.cursor_line = value,
+            ("cursorcolumn", Bool(value)) => self.config.cursor_column = value,
+            ("blame", Bool(value)) => self.config.blame = value,
+            ("scrolloff", Number(lines)) => {
+                self.config.scroll_off = lines;
+                self.scroll();
+            }
+            ("writingwidth", Number(width)) => self.config.writing_width = width.max(1),
+            ("scrollbar", Bool(value)) => self.config.scrollbar = value,
+            ("minimap", Bool(value)) => self.config.minimap = value,
+            ("kittykeyboard", Bool(value)) => self.config.kitty_keyboard = value,
+            ("dimunfocused", Bool(value)) => self.config.dim_unfocused = value,
+            ("syntax", Bool(value)) => self.config.syntax = value,
+            ("rainbowbrackets", Bool(value)) => self.config.rainbow_brackets = value,
+            ("colorswatches", Bool(value)) => self.config.color_swatches = value,
+            ("bidi", Text(name)) => {
+                self.config.bidi = Direction::from_name(&name)
+                    .ok_or_else(|| format!("Unknown bidi direction: {}", name))?;
+            }
+            ("columnview", Bool(value)) => self.config.column_view = value,
+            ("elastictabstops", Bool(value)) => self.config.elastic_tabstops = value,
+            ("softtabmotion", Bool(value)) => self.config.soft_tab_motion = value,
+            ("scrollbind", Bool(value)) => self.window.scroll_bind = value,
+            ("stickycolumn", Bool(value)) => self.config.sticky_column = value,
+            ("smoothscroll", Number(millis)) => {
+                self.config.smooth_scroll = (millis > 0)
+                    .then(|| Duration::from_millis(u64::try_from(millis).unwrap_or(u64::MAX)));
+                self.scroll_animation = None;
+            }
+            ("lint", Bool(value)) => self.config.lint = value,
+            ("spell", Bool(value)) => self.config.spell = value,
+            ("spelllang", Text(lang)) => self.config.spell_lang = lang,
+            ("wordcount", Bool(value)) => self.config.word_count = value,
+            ("diffalgorithm", Text(name)) => {
+                self.config.diff_algorithm = diff::Algorithm::from_name(&name)
+                    .ok_or_else(|| format!("Unknown diff algorithm: {}", name))?;
+            }
+            ("searchcase", Text(name)) => {
+                self.config.search_case = Case::from_name(&name)
+                    .ok_or_else(|| format!("Unknown search case: {}", name))?;
+            }
+            ("wholeword", Bool(value)) => self.config.search_whole_word = value,
+            ("searchregex", Bool(value)) => self.config.search_regex = value,
+            ("theme", Text(name)) => {
+                self.config.theme =
+                    Theme::named(&name).ok_or_else(|| format!("Unknown theme: {}", name))?;
+                self.config.theme_name = name;
+            }
+            ("tabstop", Number(width)) => document.indent.tab_width = width.max(1),
+            ("shiftwidth", Number(size)) => document.indent.size = size.max(1),
+            ("expandtab", Bool(value)) => document.indent.use_tabs = !value,
+            ("readonly", Bool(value)) => document.read_only = value,
+            ("fileformat", Text(name)) => document.set_line_ending(
+                LineEnding::from_name(&name)
+                    .ok_or_else(|| format!("Unknown file format: {}", name))?,
+            ),
+            ("fileencoding", Text(name)) => document.set_encoding(
+                Encoding::from_name(&name).ok_or_else(|| format!("Unknown encoding: {}", name))?,
+            ),
+            ("bomb", Bool(value)) => document.set_bom(value),
+            ("colorcolumn", Text(columns)) => {
+                document.color_columns = columns
+                    .split(',')
+                    .filter(|column| !column.trim().is_empty())
+                    .map(|column| {
+                        column
+                            .trim()
+                            .parse::<usize>()
+                            .map_err(|_| format!("Invalid column: {}", column))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .into_iter()
+                    .filter(|column| *column > 0)
+                    .collect();
+            }
+            ("filetype", Text(name)) => {
+                if let Some(overrides) = self.config.filetypes.get(&name) {
+                    overrides.apply_to(document);
+                }
+                document.file_type = optional(name);
+            }
+            ("comment", Text(text)) => document.comment = optional(text),
+            ("formatter", Text(text)) => document.formatter = optional(text),
+            ("formatonsave", Bool(value)) => document.format_on_save = value,
+            ("trimwhitespace", Bool(value)) => {
+                document.save_options.trim_trailing_whitespace = value
+            }
+            ("fixendofline", Bool(value)) => document.save_options.insert_final_newline = value,
+            ("pathcompletion", Bool(value)) => document.path_completion = value,
+            _ => return Err(format!("Invalid argument: {}", option.name)),
+        }
+        self.scroll();
+        Ok(())
+    }
+    fn quit(&mut self, force: bool) {
+        if !force && self.any_dirty() {
+            self.status_message =
+                StatusMessage::error("No write since last change (add ! to override).".to_string());
+            return;
+        }
+        self.should_quit = true;
+    }
+    fn revert(&mut self) {
+        if self.document.file_name.is_none() {
+            self.status_message = StatusMessage::error("No file name to reload.".to_string());
+            return;
+        }
+        if self.document.is_dirty()
+            && self.ask("Discard unsaved changes and reload from disk? (y/n) ") != Some('y')
+        {
+            return;
+        }
+        self.reload_document();
+    }
+    fn jump_to(&mut self, position: &Position) {
+        let y = cmp::min(position.y, self.document.len());
+        let width = if let Some(row) = self.document.row(y) {
+            row.len()
+        } else {
+            0
+        };
+        self.cursor_position = Position {
+            x: cmp::min(position.x, width),
+            y,
+        };
+        self.scroll();
+    }
+    /// Remembers `old_position` in the jump list if the cursor has moved away from it.
+    fn record_jump(&mut self, old_position: Position) {
+        if self.cursor_position != old_position {
+            self.jump_list.push(Jump {
+                file_name: self.document.file_name.clone(),
+                position: old_position,
+            });
+        }
+    }
+    fn jump_back(&mut self) {
+        let current = Jump {
+            file_name: self.document.file_name.clone(),
+            position: self.cursor_position.clone(),
+        };
+        if let Some(jump) = self.jump_list.back(current) {
+            self.follow_jump(&jump);
+        }
+    }
+    fn jump_forward(&mut self) {
+        if let Some(jump) = self.jump_list.forward() {
+            self.follow_jump(&jump);
+        }
+    }
+    fn follow_jump(&mut self, jump: &Jump) {
+        if jump.file_name != self.document.file_name {
+            let index = self
+                .buffers
+                .iter()
+                .position(|buffer| buffer.document.file_name == jump.file_name);
+            if let Some(index) = index {
+                self.switch_buffer(index);
+            } else if let Some(file_name) = &jump.file_name {
+                self.open_buffer(file_name);
+            }
+        }
+        self.jump_to(&jump.position);
+    }
+    fn set_mark(&mut self, name: char) {
+        if name.is_ascii_lowercase() {
+            self.document.marks.set(name, &self.cursor_position);
+            self.status_message = StatusMessage::from(format!("Mark '{}' set.", name));
+        }
+    }
+    fn goto_mark(&mut self, name: char, exact: bool) {
+        if let Some(mark) = self.document.marks.get(name) {
+            let mut position = mark.clone();
+            if !exact {
+                position.x = 0;
+            }
+            let old_position = self.cursor_position.clone();
+            self.jump_to(&position);
+            self.record_jump(old_position);
+        } else {
+            self.status_message = StatusMessage::error(format!("Mark '{}' not set.", name));
+        }
+    }
+    fn show_marks(&mut self) {
+        if self.document.marks.is_empty() {
+            self.status_message = StatusMessage::from("No marks set.".to_string());
+            return;
+        }
+        let mut lines = vec!["mark  line   col  text".to_string()];
+        for (name, position) in self.document.marks.iter() {
+            let text = self
+                .document
+                .row(position.y)
+                .map(|row| row.render(0, row.len(), self.document.indent.tab_width))
+                .unwrap_or_default();
+            lines.push(format!(
+                " {}   {:>5}  {:>4}  {}",
+                name,
+                position.y.saturating_add(1),
+                position.x,
+                text.trim()
+            ));
+        }
+        self.overlay = Some(lines);
+    }
+    fn next_key(&mut self) -> Result<Key, std::io::Error> {
+        loop {
+            if let Some(key) = self.next_key_timeout(None)? {
+                return Ok(key);
+            }
+            // Clicks mean nothing to a prompt or picker waiting for a key; pasted
+            // text is typed into it on one line.
+            self.click = None;
+            self.woken = false;
+            if let Some(focused) = self.focus.take() {
+                self.focused = focused;
+            }
+            if let Some(text) = self.paste.take() {
+                self.input_queue.extend(
+                    text.chars()
+                        .map(|c| if c == '\n' { ' ' } else { c })
+                        .map(Key::Char),
+                );
+            }
+        }
+    }
+    /// The next key, or `None` after `timeout`, a click, a paste or a change of focus,
+    /// which are left in `click`, `paste` and `focus`.
+    fn next_key_timeout(&mut self, timeout: Option<Duration>) -> Result<Option<Key>, Error> {
+        if let Some(key) = self.input_queue.pop_front() {
+            return Ok(Some(key));
+        }
+        let input = if let Some(timeout) = timeout {
+            self.terminal.read_input_timeout(timeout)?
+        } else {
+            Some(self.terminal.read_input()?)
+        };
+        let key = match input {
+            Some(Input::Key(key)) => key,
+            Some(Input::Click(position)) => {
+                self.click = Some(position);
+                return Ok(None);
+            }
+            Some(Input::Paste(text)) => {
+                self.paste = Some(text);
+                return Ok(None);
+            }
+            Some(Input::Focus(focused)) => {
+                self.focus = Some(focused);
+                return Ok(None);
+            }
+            Some(Input::Resize | Input::Wake) => {
+                self.woken = true;
+                return Ok(None);
+            }
+            None => return Ok(None),
+        };
+        if let Some((_, keys)) = &mut self.recording {
+            keys.push(key);
+        }
+        Ok(Some(key))
+    }
+    fn idle_timeout(&self) -> Option<Duration> {
+        let swap = if self.swap_is_stale() {
+            Some(SWAP_IDLE)
+        } else {
+            None
+        };
+        let journal = self
+            .journal_is_stale()
+            .then(|| JOURNAL_INTERVAL.saturating_sub(self.last_journal.elapsed()));
+        let auto_save = self
+            .config
+            .auto_save
+            .filter(|_| self.document.is_dirty() && self.document.file_name.is_some())
+            .map(|interval| interval.saturating_sub(self.last_input.elapsed()));
+        let watch = self
+            .document
+            .file_name
+            .as_ref()
+            .filter(|_| self.focused)
+            .map(|_| WATCH_INTERVAL.saturating_sub(self.last_watch.elapsed()));
+        let hint = self
+            .config
+            .hint_delay
+            .filter(|_| !self.pending.is_empty() && self.overlay.is_none())
+            .map(|delay| delay.saturating_sub(self.last_input.elapsed()));
+        // Output wakes the loop; only a process whose output has ended is waited on.
+        let jobs = self.jobs.is_exiting().then_some(JOB_POLL);
+        let terminal_pane = self
+            .terminal_pane
+            .as_ref()
+            .filter(|pane| pane.is_closed())
+            .map(|_| JOB_POLL);
+        let message = (!self.status_message.text.is_empty())
+            .then(|| MESSAGE_TIMEOUT.saturating_sub(self.status_message.time.elapsed()))
+            .filter(|remaining| !remaining.is_zero());
+        let clock = self
+            .config
+            .status_bar
+            .contains(Segment::Clock)
+            .then(statusline::until_next_minute);
+        let animation = self
+            .scroll_animation
+            .as_ref()
+            .map(|_| FRAME_INTERVAL.saturating_sub(self.last_frame.elapsed()));
+        [
+            swap,
+            journal,
+            auto_save,
+            watch,
+            hint,
+            message,
+            jobs,
+            terminal_pane,
+            clock,
+            animation,
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+    fn on_idle(&mut self) {
+        if let Some(delay) = self.config.hint_delay {
+            if self.overlay.is_none() && self.last_input.elapsed() >= delay {
+                self.show_key_hints();
+            }
+        }
+        if self.focused && self.last_watch.elapsed() >= WATCH_INTERVAL {
+            self.check_disk_changes();
+        }
+        if self.swap_is_stale() {
+            self.write_swap();
+        }
+        if self.last_journal.elapsed() >= JOURNAL_INTERVAL && self.journal_is_stale() {
+            self.write_journal();
+        }
+        self.emit(Event::Idle);
+    }
+    /// Checks the file and its git status as soon as the user comes back to the
+    /// window; while they are away, watching the disk and git pauses.
+    fn on_focus(&mut self, focused: bool) {
+        self.focused = focused;
+        if focused {
+            self.git_status_checked = None;
+            self.check_disk_changes();
+        }
+    }
+    /// Runs the hooks registered for `event`. Ex command hooks do not run for events
+    /// raised by other hooks, so a hook cannot set itself off.
+    fn emit(&mut self, event: Event) {
+        for action in self.hooks.actions(event) {
+            match action {
+                Action::Builtin(builtin) => self.run_builtin(builtin),
+                Action::Command(command) if !self.in_hook => {
+                    self.in_hook = true;
+                    self.execute_command(&command);
+                    self.in_hook = false;
+                }
+                Action::Command(_) => (),
+            }
+        }
+        if !self.in_hook {
+            self.in_hook = true;
+            for (index, export) in self.plugins.hooks(event) {
+                self.call_plugin(index, &export, "");
+            }
+            let mut index = 0_usize;
+            while let Some(started) = self
+                .scripts
+                .as_ref()
+                .and_then(|scripts| scripts.hook(event, index))
+            {
+                self.run_script(started);
+                index = index.saturating_add(1);
+            }
+            self.in_hook = false;
+        }
+    }
+    fn run_builtin(&mut self, builtin: Builtin) {
+        match builtin {
+            Builtin::AutoSave => {
+                if let Some(interval) = self.config.auto_save {
+                    if self.last_input.elapsed() >= interval {
+                        self.auto_save();
+                    }
+                }
+            }
+            Builtin::FormatOnSave => {
+                if self.document.format_on_save {
+                    if let Err(error) = self.format_document() {
+                        self.save_warning = Some(format!("formatting failed: {}", error));
+                    }
+                }
+            }
+            Builtin::RefreshGit => {
+                self.document.git.invalidate();
+                self.git_status_checked = None;
+            }
+            Builtin::ScrollToCursor => self.scroll(),
+        }
+    }
+    /// Raises the events for what changed since the last pass of the main loop.
+    fn emit_changes(&mut self) {
+        if self.terminal.update_size() {
+            let size = self.terminal.size();
+            log::debug(
+                "terminal",
+                format!("resized to {}x{}", size.width, size.height),
+            );
+            self.emit(Event::Resize);
+        }
+        let cursor = (
+            self.document.file_name.clone(),
+            self.cursor_position.clone(),
+        );
+        if cursor != self.last_cursor {
+            self.last_cursor = cursor;
+            self.emit(Event::CursorMoved);
+        }
+        if self.mode != self.last_mode {
+            self.last_mode = self.mode;
+            self.emit(Event::ModeChanged);
+        }
+    }
+    /// Hooks `command` to `event` for the rest of the session, or lists the hooked
+    /// commands when given nothing.
+    fn autocmd(&mut self, arguments: &str) {
+        if arguments.is_empty() {
+            let lines: Vec<String> = self
+                .hooks
+                .commands()
+                .map(|(event, command)| format!("{:<16} {}", event.name(), command))
+                .collect();
+            if lines.is_empty() {
+                self.status_message = StatusMessage::from("No hooks.".to_string());
+            } else {
+                self.overlay = Some(lines);
+            }
+            return;
+        }
+        let (name, command) = arguments.split_once(' ').unwrap_or((arguments, ""));
+        match Event::from_name(name) {
+            Some(event) if !command.trim().is_empty() => {
+                self.hooks
+                    .on(event, Action::Command(command.trim().to_string()));
+            }
+            Some(_) => {
+                self.status_message =
+                    StatusMessage::error("Usage: autocmd <event> <command>".to_string());
+            }
+            None => {
+                let names: Vec<&str> = event::EVENTS.iter().map(|event| event.name()).collect();
+                self.status_message = StatusMessage::error(format!(
+                    "Unknown event {} (one of {})",
+                    name,
+                    names.join(", ")
+                ));
+            }
+        }
+    }
+    /// Shows which keys can complete the pending sequence; the popup does not swallow
+    /// the next key.
+    fn show_key_hints(&mut self) {
+        let mode = if self.mode == Mode::Visual {
+            KeyMode::Visual
+        } else {
+            KeyMode::Normal
+        };
+        let hints = key_hints::continuations(&self.pending, mode, &self.config.bindings);
+        if hints.is_empty() {
+            return;
+        }
+        let title = key_hints::title(&self.pending, &self.config.bindings);
+        let mut lines = vec![format!("{} …", title)];
+        lines.extend(
+            hints
+                .iter()
+                .map(|(keys, action)| format!("  {:<12} {}", keys, action)),
+        );
+        self.overlay = Some(lines);
+        self.hint_visible = true;
+    }
+    /// Reloads the buffer when its file changed on disk, or asks what to do if it is dirty.
+    fn check_disk_changes(&mut self) {
+        self.last_watch = Instant::now();
+        match self.document.disk_state() {
+            DiskState::Unchanged => return,
+            DiskState::Deleted => {
+                self.document.forget_disk_stamp();
+                self.status_message = StatusMessage::from(
+                    "File deleted on disk; saving writes it again.".to_string(),
+                );
+                return;
+            }
+            DiskState::Modified if self.disk_conflict_ignored => return,
+            DiskState::Modified => {}
+        }
+        if !self.document.is_dirty() {
+            self.reload_document();
+            return;
+        }
+        match self.ask("File changed on disk and buffer has unsaved changes. (r)eload or (k)eep? ")
+        {
+            Some('r') => self.reload_document(),
+            _ => {
+                self.disk_conflict_ignored = true;
+                self.status_message = StatusMessage::from(
+                    "Keeping buffer; saving will ask before overwriting.".to_string(),
+                );
+            }
+        }
+    }
+    fn swap_is_stale(&self) -> bool {
+        self.document.is_dirty()
+            && self.document.file_name.is_some()
+            && self.document.encryption.is_none()
+            && self.swap_revision != Some(self.document.revision())
+    }
+    fn write_swap(&mut self) {
+        if let Some(file_name) = &self.document.file_name {
+            let mut contents = Vec::new();
+            if self.document.write_to(&mut contents).is_ok()
+                && swap::write(file_name, &contents).is_ok()
+            {
+                self.swap_revision = Some(self.document.revision());
+            }
+        }
+        self.last_swap = Instant::now();
+    }
+    /// The buffers worth restoring after a crash; help and other special buffers, and
+    /// unnamed ones without changes, are not.
+    fn journal_state(&self) -> Vec<JournalState> {
+        self.buffers
+            .iter()
+            .enumerate()
+            .filter_map(|(index, buffer)| {
+                let (document, cursor_position) = if index == self.current_buffer {
+                    (&self.document, &self.cursor_position)
+                } else {
+                    (&buffer.document, &buffer.cursor_position)
+                };
+                // Encrypted text never reaches the disk unencrypted.
+                let revision = (document.is_dirty() && document.encryption.is_none())
+                    .then(|| document.revision());
+                if document.title.is_some()
+                    || document.is_binary()
+                    || document.file_name.is_none() && revision.is_none()
+                {
+                    return None;
+                }
+                Some((
+                    index,
+                    document.file_name.clone(),
+                    cursor_position.clone(),
+                    revision,
+                ))
+            })
+            .collect()
+    }
+    fn journal_is_stale(&self) -> bool {
+        self.config.journal
+            && !self.terminal.is_headless()
+            && self.journal_state() != self.journal_state
+    }
+    /// Writes the open buffers, their cursors and the text of those with unsaved
+    /// changes to the crash journal.
+    fn write_journal(&mut self) {
+        let state = self.journal_state();
+        let mut journal = Journal {
+            dir: env::current_dir().ok(),
+            ..Journal::default()
+        };
+        for (index, file_name, cursor_position, revision) in &state {
+            let (document, offset) = if *index == self.current_buffer {
+                (&self.document, &self.offset)
+            } else if let Some(buffer) = self.buffers.get(*index) {
+                (&buffer.document, &buffer.offset)
+            } else {
+                continue;
+            };
+            if *index == self.current_buffer {
+                journal.current = journal.buffers.len();
+            }
+            journal.buffers.push(journal::Entry {
+                file_name: file_name.clone(),
+                cursor_position: cursor_position.clone(),
+                offset: offset.clone(),
+                snapshot: revision.map(|_| document.text()),
+            });
+        }
+        match journal.write() {
+            Ok(()) => self.journal_state = state,
+            Err(error) => log::warn("journal", error),
+        }
+        self.last_journal = Instant::now();
+    }
+    /// Offers to reopen the buffers of a hecto that crashed, with their unsaved
+    /// changes, in place of the empty buffer this one started with.
+    fn offer_journal(&mut self) {
+        let (dir, journal) = if let Some(orphan) = self.orphan_journal.take() {
+            orphan
+        } else {
+            return;
+        };
+        let changed = journal
+            .buffers
+            .iter()
+            .filter(|buffer| buffer.snapshot.is_some())
+            .count();
+        let answer = self
+            .prompt(
+                &format!(
+                    "hecto did not exit cleanly. Restore its {} {} ({} unsaved)? (y/n): ",
+                    journal.buffers.len(),
+                    stats::plural(journal.buffers.len(), "buffer"),
+                    changed
+                ),
+                None,
+                |_, _, _| {},
+            )
+            .unwrap_or(None);
+        journal::remove(&dir);
+        if !answer.is_some_and(|answer| answer.eq_ignore_ascii_case("y")) {
+            return;
+        }
+        if let Some(cwd) = &journal.dir {
+            if let Err(error) = env::set_current_dir(cwd) {
+                self.status_message =
+                    StatusMessage::error(format!("ERR: journal: {}: {}", cwd.display(), error));
+            }
+        }
+        let mut buffers = Vec::new();
+        for entry in &journal.buffers {
+            let file_name = entry.file_name.as_deref().unwrap_or_default();
+            let (mut document, _) = open_document(file_name, &self.config, &self.terminal);
+            if let Some(snapshot) = &entry.snapshot {
+                document.load_contents(snapshot);
+            }
+            buffers.push(Buffer {
+                document,
+                ..Buffer::default()
+            });
+        }
+        if buffers.is_empty() {
+            return;
+        }
+        // Park the restored buffers behind the empty one, then drop it.
+        let first = self.buffers.len();
+        self.buffers.extend(buffers);
+        for (offset, entry) in journal.buffers.iter().enumerate() {
+            self.switch_buffer(first.saturating_add(offset));
+            self.jump_to(&entry.cursor_position);
+            self.offset = entry.offset.clone();
+        }
+        self.switch_buffer(first.saturating_add(journal.current));
+        for index in (0..first).rev() {
+            self.remove_buffer(index);
+        }
+        self.status_message = StatusMessage::from(format!(
+            "Restored {} {}. Save to keep unsaved changes.",
+            journal.buffers.len(),
+            stats::plural(journal.buffers.len(), "buffer")
+        ));
+    }
+    fn remove_swap(&mut self) {
+        if let Some(file_name) = &self.document.file_name {
+            swap::remove(file_name);
+        }
+        self.swap_revision = None;
+    }
+    fn offer_recovery(&mut self) {
+        let file_name = if let Some(file_name) = &self.document.file_name {
+            file_name.clone()
+        } else {
+            return;
+        };
+        let contents = if let Some(contents) = swap::read(&file_name) {
+            contents
+        } else {
+            return;
+        };
+        let answer = self
+            .prompt(
+                &format!("Recovery data found for {}. Restore it? (y/n): ", file_name),
+                None,
+                |_, _, _| {},
+            )
+            .unwrap_or(None);
+        if answer.is_some_and(|answer| answer.eq_ignore_ascii_case("y")) {
+            self.document.load_contents(&contents);
+            self.status_message =
+                StatusMessage::from("Recovered unsaved changes. Save to keep them.".to_string());
+        } else {
+            swap::remove(&file_name);
+        }
+    }
+    fn auto_save(&mut self) {
+        if !self.document.is_dirty() || self.document.file_name.is_none() {
+            return;
+        }
+        if self.document.changed_on_disk() {
+            self.status_message = StatusMessage::from(
+                "Auto-save skipped: file changed on disk since it was opened.".to_string(),
+            );
+            return;
+        }
+        if self.write_document(false).is_err() {
+            self.status_message = StatusMessage::error("Auto-save failed!".to_string());
+        }
+    }
+    /// Starts the language server for the buffer's file type if one is configured,
+    /// and sends it the latest text. Returns the server's file type and the buffer URI.
+    fn sync_language_server(&mut self) -> Option<(String, String)> {
+        // Sending a large file's text on every change would stall the server.
+        if self.document.large {
+            return None;
+        }
+        let file_type = self.document.file_type.clone()?;
+        // Language servers read the project from disk, which a remote file is not on.
+        let file_name = self
+            .document
+            .file_name
+            .as_ref()
+            .filter(|file_name| !remote::is_remote(file_name))?;
+        let uri = lsp::uri(Path::new(file_name));
+        if !self.language_servers.contains_key(&file_type) {
+            let command = self
+                .config
+                .filetypes
+                .get(&file_type)
+                .and_then(|overrides| overrides.language_server.clone())?;
+            let root = self.project_root.clone().map_or_else(env::current_dir, Ok);
+            let waker = self.terminal.waker();
+            let server = root.and_then(|root| LanguageServer::start(&command, &root, waker));
+            let server = server
+                .map_err(|error| {
+                    self.status_message = StatusMessage::error(format!(
+                        "ERR: Could not start language server `{}`: {}",
+                        command, error
+                    ));
+                })
+                .ok();
+            self.language_servers.insert(file_type.clone(), server);
+        }
+        let server = self.language_servers.get_mut(&file_type)?.as_mut()?;
+        let document = &self.document;
+        server.sync(
+            &uri,
+            lsp::language_id(&file_type),
+            document.revision(),
+            || document.text(),
+        );
+        Some((file_type, uri))
+    }
+    /// Keeps the current buffer in sync and handles whatever the servers sent.
+    fn update_language_servers(&mut self) {
+        self.sync_language_server();
+        let mut messages = Vec::new();
+        let mut exited = None;
+        for (file_type, slot) in &mut self.language_servers {
+            if let Some(server) = slot {
+                if server.is_running() {
+                    let polled = server.poll().into_iter();
+                    messages.extend(polled.map(|message| (file_type.clone(), message)));
+                } else {
+                    exited = Some(file_type.clone());
+                    *slot = None;
+                }
+            }
+        }
+        if let Some(file_type) = exited {
+            self.status_message =
+                StatusMessage::from(format!("Language server for {} exited.", file_type));
+        }
+        for (file_type, message) in messages {
+            match message {
+                lsp::Message::Request { id, method, params } if method == "workspace/applyEdit" => {
+                    let result = self.apply_edit_request(&params);
+                    if let Some(Some(server)) = self.language_servers.get_mut(&file_type) {
+                        server.respond(id, result);
+                    }
+                }
+                message => self.handle_language_server_message(message),
+            }
+        }
+    }
+    /// Applies the edit of a `workspace/applyEdit` request, returning the reply that
+    /// tells the server whether it was.
+    fn apply_edit_request(&mut self, params: &json::Value) -> json::Value {
+        let changes = params.get("edit").map(lsp::workspace_edit);
+        let (files, edits, failed) = self.apply_workspace_edit(&changes.unwrap_or_default());
+        self.status_message = StatusMessage::from(format!(
+            "{}: {}",
+            params
+                .get("label")
+                .and_then(json::Value::as_str)
+                .unwrap_or("Applied"),
+            edit_summary(files, edits, &failed)
+        ));
+        if failed.is_empty() {
+            return json::Value::object(vec![("applied", json::Value::from(true))]);
+        }
+        json::Value::object(vec![
+            ("applied", json::Value::from(false)),
+            (
+                "failureReason",
+                json::Value::from(format!("Could not edit {}", failed.join(", "))),
+            ),
+        ])
+    }
+    fn handle_language_server_message(&mut self, message: lsp::Message) {
+        match message {
+            lsp::Message::Notification { method, params }
+                if method == "textDocument/publishDiagnostics" =>
+            {
+                self.receive_diagnostics(&params);
+            }
+            lsp::Message::Notification { method, params } if method == "window/showMessage" => {
+                if let Some(text) = params.get("message").and_then(json::Value::as_str) {
+                    let line = text.lines().next().unwrap_or_default();
+                    self.status_message = StatusMessage::from(format!("LSP: {}", line));
+                }
+            }
+            lsp::Message::Response {
+                method,
+                result: Ok(result),
+            } if method == "textDocument/hover" => self.receive_hover(&result),
+            lsp::Message::Response {
+                method,
+                result: Ok(result),
+            } if method == "textDocument/definition" => self.receive_definition(&result),
+            lsp::Message::Response {
+                method,
+                result: Ok(result),
+            } if method == "textDocument/references" => self.receive_references(&result),
+            lsp::Message::Response {
+                method,
+                result: Ok(result),
+            } if method == "textDocument/rename" => self.receive_rename(&result),
+            lsp::Message::Response {
+                method,
+                result: Ok(result),
+            } if method == "textDocument/codeAction" => self.receive_code_actions(&result),
+            lsp::Message::Response { method, result }
+                if method == "textDocument/documentSymbol" =>
+            {
+                self.receive_symbols(result.ok().as_ref());
+            }
+            lsp::Message::Response { method, result } if method == "textDocument/foldingRange" => {
+                self.receive_folding_ranges(result.ok().as_ref());
+            }
+            lsp::Message::Response {
+                method,
+                result: Ok(result),
+            } if method == "textDocument/signatureHelp" => {
+                self.signature = if self.mode == Mode::Insert {
+                    Signature::from_lsp(&result)
+                } else {
+                    None
+                };
+            }
+            lsp::Message::Response {
+                method,
+                result: Ok(result),
+            } if method == "codeAction/resolve" => {
+                if let Some(action) =
+                    code_action::Action::from_lsp(&result).filter(|action| !action.needs_resolve())
+                {
+                    self.apply_code_action(action);
+                }
+            }
+            lsp::Message::Response { method, result } if method == "textDocument/completion" => {
+                self.receive_completions(result.ok().as_ref());
+            }
+            lsp::Message::Response {
+                method,
+                result: Err(error),
+            } => {
+                self.status_message =
+                    StatusMessage::error(format!("LSP {} failed: {}", method, error));
+            }
+            _ => (),
+        }
+    }
+    /// Sends `method` to the buffer's language server with the cursor position and any
+    /// `extra` params. Returns false when the buffer has no server.
+    fn request_at_cursor(&mut self, method: &str, extra: Vec<(&str, json::Value)>) -> bool {
+        let (file_type, uri) = if let Some(server) = self.sync_language_server() {
+            server
+        } else {
+            return false;
+        };
+        let Position { x, y } = self.cursor_position;
+        let character = self.document.row(y).map_or(0, |row| row.utf16_offset(x));
+        let mut params = vec![
+            (
+                "textDocument",
+                json::Value::object(vec![("uri", json::Value::from(uri))]),
+            ),
+            (
+                "position",
+                json::Value::object(vec![
+                    ("line", json::Value::from(y)),
+                    ("character", json::Value::from(character)),
+                ]),
+            ),
+        ];
+        params.extend(extra);
+        if let Some(Some(server)) = self.language_servers.get_mut(&file_type) {
+            server.request(method, json::Value::object(params));
+            return true;
+        }
+        false
+    }
+    fn request_hover(&mut self) {
+        if !self.request_at_cursor("textDocument/hover", Vec::new()) {
+            self.status_message =
+                StatusMessage::error("No language server for this buffer.".to_string());
+        }
+    }
+    /// Asks for the definition or the references of the symbol under the cursor.
+    fn request_location(&mut self, method: &str) {
+        let extra = if method == "textDocument/references" {
+            vec![(
+                "context",
+                json::Value::object(vec![("includeDeclaration", json::Value::from(true))]),
+            )]
+        } else {
+            Vec::new()
+        };
+        if self.request_at_cursor(method, extra) {
+            return;
+        }
+        if method == "textDocument/definition" && tags::find_file().is_some() {
+            self.jump_to_tag(None);
+        } else {
+            self.status_message =
+                StatusMessage::error("No language server for this buffer.".to_string());
+        }
+    }
+    /// Jumps to the tag `name`, or to the identifier under the cursor, pushing the
+    /// current position on the tag stack. Several matching tags fill the quickfix list.
+    fn jump_to_tag(&mut self, name: Option<&str>) {
+        let name = name.map_or_else(
+            || autocomplete::word_at(&self.document, &self.cursor_position),
+            str::to_string,
+        );
+        if name.is_empty() {
+            self.status_message = StatusMessage::error("No identifier under cursor.".to_string());
+            return;
+        }
+        if tags::find_file().is_none() {
+            self.status_message = StatusMessage::error("No tags file found.".to_string());
+            return;
+        }
+        let locations: Vec<(String, usize, usize)> = tags::lookup(&name)
+            .iter()
+            .map(|tag| (tag.file_name.clone(), self.tag_line(tag), 0))
+            .collect();
+        if locations.is_empty() {
+            self.status_message = StatusMessage::error(format!("Tag not found: {}", name));
+            return;
+        }
+        self.tag_stack.push(Jump {
+            file_name: self.document.file_name.clone(),
+            position: self.cursor_position.clone(),
+        });
+        if locations.len() > 1 {
+            let entries = self.quickfix_entries(&locations);
+            self.quickfix.set(&format!("Tags: {}", name), entries);
+            self.goto_quickfix(0);
+        } else if let Some((file_name, line, _)) = locations.first() {
+            self.goto_location(file_name, &Position { x: 0, y: *line });
+        }
+    }
+    /// The line a tag points at, searching the open buffer or else the file on disk.
+    fn tag_line(&self, tag: &Tag) -> usize {
+        let open = std::iter::once(&self.document)
+            .chain(self.buffers.iter().map(|buffer| &buffer.document))
+            .find(|document| document.file_name.as_deref() == Some(tag.file_name.as_str()));
+        let line = if let Some(document) = open {
+            tag.address
+                .line_in((0..document.len()).filter_map(|y| document.row(y).map(Row::as_str)))
+        } else {
+            let contents = std::fs::read_to_string(&tag.file_name).unwrap_or_default();
+            tag.address.line_in(contents.lines())
+        };
+        line.unwrap_or(0)
+    }
+    fn pop_tag(&mut self) {
+        if let Some(jump) = self.tag_stack.pop() {
+            self.follow_jump(&jump);
+        } else {
+            self.status_message = StatusMessage::error("Tag stack is empty.".to_string());
+        }
+    }
+    fn show_tag_stack(&mut self) {
+        if self.tag_stack.is_empty() {
+            self.status_message = StatusMessage::error("Tag stack is empty.".to_string());
+            return;
+        }
+        let mut lines = vec![" #   line  file".to_string()];
+        for (index, jump) in self.tag_stack.iter().enumerate() {
+            lines.push(format!(
+                "{:>2}  {:>5}  {}",
+                index.saturating_add(1),
+                jump.position.y.saturating_add(1),
+                jump.file_name.as_deref().unwrap_or("[No Name]")
+            ));
+        }
+        self.overlay = Some(lines);
+    }
+    /// Jumps to the first definition; several definitions also fill the quickfix list.
+    fn receive_definition(&mut self, result: &json::Value) {
+        let locations: Vec<(String, usize, usize)> = match result.as_array() {
+            Some(items) => items.iter().filter_map(lsp::location).collect(),
+            None => lsp::location(result).into_iter().collect(),
+        };
+        if locations.len() > 1 {
+            let entries = self.quickfix_entries(&locations);
+            self.quickfix.set("Definitions", entries);
+            self.goto_quickfix(0);
+        } else if let Some((file_name, line, character)) = locations.first() {
+            self.goto_location(file_name, &Position { x: 0, y: *line });
+            if let Some(row) = self.document.row(*line) {
+                let x = row.index_at_utf16(*character);
+                self.jump_to(&Position { x, y: *line });
+            }
+        } else {
+            self.status_message = StatusMessage::error("No definition found.".to_string());
+        }
+    }
+    fn receive_references(&mut self, result: &json::Value) {
+        let locations: Vec<(String, usize, usize)> = result
+            .as_array()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(lsp::location)
+            .collect();
+        if locations.is_empty() {
+            self.status_message = StatusMessage::error("No references found.".to_string());
+            return;
+        }
+        let entries = self.quickfix_entries(&locations);
+        self.quickfix.set("References", entries);
+        self.goto_quickfix(0);
+    }
+    fn rename(&mut self, new_name: Option<&str>) {
+        if self.sync_language_server().is_none() {
+            self.status_message =
+                StatusMessage::error("No language server for this buffer.".to_string());
+            return;
+        }
+        let new_name = match new_name {
+            Some(new_name) => Some(new_name.to_string()),
+            None => self
+                .prompt("Rename to: ", None, |_, _, _| {})
+                .unwrap_or(None),
+        };
+        if let Some(new_name) = new_name.filter(|name| !name.is_empty()) {
+            self.request_at_cursor(
+                "textDocument/rename",
+                vec![("newName", json::Value::from(new_name))],
+            );
+        }
+    }
+    fn receive_rename(&mut self, result: &json::Value) {
+        let changes = lsp::workspace_edit(result);
+        if changes.iter().all(|(_, edits)| edits.is_empty()) {
+            self.status_message = StatusMessage::error("Nothing to rename.".to_string());
+            return;
+        }
+        let (files, edits, failed) = self.apply_workspace_edit(&changes);
+        self.status_message =
+            StatusMessage::from(format!("Renamed: {}", edit_summary(files, edits, &failed)));
+    }
+    /// Asks for the code actions at the cursor, or for the selection in visual mode,
+    /// passing along the diagnostics on those lines.
+    fn request_code_actions(&mut self) {
+        let (file_type, uri) = if let Some(server) = self.sync_language_server() {
+            server
+        } else {
+            self.status_message =
+                StatusMessage::error("No language server for this buffer.".to_string());
+            return;
+        };
+        let (start, end) = if self.mode == Mode::Visual {
+            self.mode = Mode::Normal;
+            self.selection()
+        } else {
+            (self.cursor_position.clone(), self.cursor_position.clone())
+        };
+        let position = |position: &Position| {
+            let character = self
+                .document
+                .row(position.y)
+                .map_or(0, |row| row.utf16_offset(position.x));
+            json::Value::object(vec![
+                ("line", json::Value::from(position.y)),
+                ("character", json::Value::from(character)),
+            ])
+        };
+        let diagnostics = self
+            .document
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                !diagnostic.lint && diagnostic.start.0 <= end.y && diagnostic.end.0 >= start.y
+            })
+            .map(|diagnostic| diagnostic.raw.clone())
+            .collect();
+        let params = json::Value::object(vec![
+            (
+                "textDocument",
+                json::Value::object(vec![("uri", json::Value::from(uri))]),
+            ),
+            (
+                "range",
+                json::Value::object(vec![("start", position(&start)), ("end", position(&end))]),
+            ),
+            (
+                "context",
+                json::Value::object(vec![("diagnostics", json::Value::Array(diagnostics))]),
+            ),
+        ]);
+        if let Some(Some(server)) = self.language_servers.get_mut(&file_type) {
+            server.request("textDocument/codeAction", params);
+        }
+    }
+    fn receive_code_actions(&mut self, result: &json::Value) {
+        let actions = code_action::parse(result);
+        if actions.is_empty() {
+            self.status_message = StatusMessage::from("No code actions available.".to_string());
+        } else {
+            self.code_actions = Some(code_action::Menu::new(actions));
+        }
+    }
+    fn process_code_action_key(&mut self, mut menu: code_action::Menu, key: Key) {
+        let action = match key {
+            Key::Up | Key::BackTab | Key::Ctrl('p') | Key::Char('k') => {
+                menu.select_next(true);
+                self.code_actions = Some(menu);
+                return;
+            }
+            Key::Down | Key::Char('\t') | Key::Ctrl('n') | Key::Char('j') => {
+                menu.select_next(false);
+                self.code_actions = Some(menu);
+                return;
+            }
+            Key::Char('\n') => menu.take(None),
+            Key::Char(c) => c
+                .to_digit(10)
+                .and_then(|digit| usize::try_from(digit).ok())
+                .and_then(|digit| digit.checked_sub(1))
+                .and_then(|index| menu.take(Some(index))),
+            _ => None,
+        };
+        if let Some(action) = action {
+            self.apply_code_action(action);
+        }
+    }
+    /// Applies the action's edit, then runs its command on the server. Actions that
+    /// come without either are resolved first.
+    fn apply_code_action(&mut self, action: code_action::Action) {
+        let server = self.sync_language_server().map(|(file_type, _)| file_type);
+        if action.needs_resolve() {
+            if let Some(Some(server)) =
+                server.and_then(|file_type| self.language_servers.get_mut(&file_type))
+            {
+                server.request("codeAction/resolve", action.raw);
+            }
+            return;
+        }
+        if let Some(edit) = &action.edit {
+            let (files, edits, failed) = self.apply_workspace_edit(&lsp::workspace_edit(edit));
+            self.status_message = StatusMessage::from(format!(
+                "{}: {}",
+                action.title,
+                edit_summary(files, edits, &failed)
+            ));
+        }
+        if let Some(command) = &action.command {
+            let mut params = Vec::new();
+            if let Some(name) = command.get("command") {
+                params.push(("command", name.clone()));
+            }
+            if let Some(arguments) = command.get("arguments") {
+                params.push(("arguments", arguments.clone()));
+            }
+            if let Some(Some(server)) =
+                server.and_then(|file_type| self.language_servers.get_mut(&file_type))
+            {
+                server.request("workspace/executeCommand", json::Value::object(params));
+            }
+        }
+    }
+    /// Applies per-file text edits, to the open buffer for a file if there is one and
+    /// otherwise to the file on disk. Returns the files and edits changed, and the files
+    /// that could not be changed.
+    fn apply_workspace_edit(
+        &mut self,
+        changes: &[(String, Vec<lsp::TextEdit>)],
+    ) -> (usize, usize, Vec<String>) {
+        let mut files = 0_usize;
+        let mut edits = 0_usize;
+        let mut failed = Vec::new();
+        for (file_name, file_edits) in changes.iter().filter(|(_, edits)| !edits.is_empty()) {
+            let is_file = |document: &Document| document.file_name.as_deref() == Some(file_name);
+            let open = self
+                .buffers
+                .iter_mut()
+                .map(|buffer| (&mut buffer.document, &mut buffer.cursor_position))
+                .find(|(document, _)| is_file(document));
+            let applied = if is_file(&self.document) {
+                lsp::apply_edits(
+                    &mut self.document,
+                    file_edits,
+                    std::slice::from_mut(&mut self.cursor_position),
+                );
+                !self.document.read_only
+            } else if let Some((document, cursor)) = open {
+                lsp::apply_edits(document, file_edits, std::slice::from_mut(cursor));
+                !document.read_only
+            } else {
+                match Document::open(file_name) {
+                    Ok(mut document) if !document.is_binary() && !document.read_only => {
+                        lsp::apply_edits(&mut document, file_edits, &mut []);
+                        document.save().is_ok()
+                    }
+                    _ => false,
+                }
+            };
+            if applied {
+                files = files.saturating_add(1);
+                edits = edits.saturating_add(file_edits.len());
+            } else {
+                failed.push(file_name.clone());
+            }
+        }
+        let cursor = self.cursor_position.clone();
+        self.jump_to(&cursor);
+        (files, edits, failed)
+    }
+    fn undo(&mut self, redo: bool) {
+        let position = if redo {
+            self.document.redo()
+        } else {
+            self.document.undo()
+        };
+        if let Some(position) = position {
+            self.jump_to(&position);
+        } else if !self.document.read_only {
+            self.status_message = StatusMessage::from(if redo {
+                "Already at newest change.".to_string()
+            } else {
+                "Already at oldest change.".to_string()
+            });
+        }
+    }
+    /// Moves the buffer through its history by `arguments` changes, a time such as
+    /// `10m`, or a number of saves such as `2f`: back for `:earlier`, forward for `:later`.
+    fn undo_travel(&mut self, arguments: &str, later: bool) {
+        let span = match undo_tree::Span::parse(arguments) {
+            Ok(span) => span,
+            Err(error) => {
+                self.status_message = StatusMessage::error(error);
+                return;
+            }
+        };
+        if let Some(position) = self.document.undo_travel(span, later) {
+            self.jump_to(&position);
+        } else if !self.document.read_only {
+            self.status_message = StatusMessage::from(if later {
+                "Already at newest change.".to_string()
+            } else {
+                "Already at oldest change.".to_string()
+            });
+        }
+    }
+    /// Shows the undo history as a tree over the text. Moving through it restores
+    /// each state in the buffer as it is selected; Enter keeps the selected state and
+    /// Esc goes back to the one the buffer was in.
+    #[allow(clippy::integer_division)]
+    fn undo_tree(&mut self) {
+        let original = self.document.undo_state();
+        let cursor = self.cursor_position.clone();
+        let history = self.document.undo_history();
+        let mut selected = history
+            .iter()
+            .position(|(id, _)| *id == original)
+            .unwrap_or(0);
+        let kept = loop {
+            let lines = self.document.undo_history();
+            let height = (self.terminal.size().height as usize / 2).max(1);
+            let top = selected
+                .saturating_sub(height / 2)
+                .min(lines.len().saturating_sub(height));
+            self.overlay = Some(
+                lines
+                    .iter()
+                    .enumerate()
+                    .skip(top)
+                    .take(height)
+                    .map(|(index, (_, line))| {
+                        let marker = if index == selected { '>' } else { ' ' };
+                        format!("{} {}", marker, line)
+                    })
+                    .collect(),
+            );
+            self.status_message = StatusMessage::from(
+                "Undo tree: Up/Down to restore a state, Enter to keep it, Esc to go back"
+                    .to_string(),
+            );
+            if self.refresh_screen().is_err() {
+                break false;
+            }
+            let previous = selected;
+            match self.next_key() {
+                Ok(Key::Char('\n')) => break true,
+                Ok(Key::Esc) | Err(_) => break false,
+                Ok(Key::Up | Key::Ctrl('p') | Key::Char('k')) => {
+                    selected = selected.saturating_sub(1);
+                }
+                Ok(Key::Down | Key::Ctrl('n') | Key::Char('j')) => {
+                    selected = selected
+                        .saturating_add(1)
+                        .min(lines.len().saturating_sub(1));
+                }
+                Ok(Key::PageUp) => selected = selected.saturating_sub(height),
+                Ok(Key::PageDown) => {
+                    selected = selected
+                        .saturating_add(height)
+                        .min(lines.len().saturating_sub(1));
+                }
+                Ok(_) => (),
+            }
+            if selected != previous {
+                if let Some((id, _)) = lines.get(selected) {
+                    if let Some(position) = self.document.undo_jump(*id) {
+                        self.jump_to(&position);
+                    }
+                }
+            }
+        };
+        self.overlay = None;
+        self.status_message = StatusMessage::from(String::new());
+        if !kept && self.document.undo_state() != original {
+            self.document.undo_jump(original);
+            self.jump_to(&cursor);
+        }
+    }
+    /// Turns LSP locations into quickfix entries, showing each location's line from
+    /// the open buffer or else from the file on disk.
+    fn quickfix_entries(&self, locations: &[(String, usize, usize)]) -> Vec<quickfix::Entry> {
+        let mut files: HashMap<&str, Vec<String>> = HashMap::new();
+        locations
+            .iter()
+            .map(|(file_name, y, character)| {
+                let open = std::iter::once(&self.document)
+                    .chain(self.buffers.iter().map(|buffer| &buffer.document))
+                    .find(|document| document.file_name.as_deref() == Some(file_name.as_str()));
+                let line = match open {
+                    Some(document) => document
+                        .row(*y)
+                        .map(|row| row.as_str().to_string())
+                        .unwrap_or_default(),
+                    None => files
+                        .entry(file_name)
+                        .or_insert_with(|| {
+                            std::fs::read_to_string(file_name)
+                                .unwrap_or_default()
+                                .lines()
+                                .map(str::to_string)
+                                .collect()
+                        })
+                        .get(*y)
+                        .cloned()
+                        .unwrap_or_default(),
+                };
+                let row = Row::from(line.as_str());
+                quickfix::Entry {
+                    file_name: file_name.clone(),
+                    position: Position {
+                        x: row.index_at_utf16(*character),
+                        y: *y,
+                    },
+                    text: line.trim().to_string(),
+                }
+            })
+            .collect()
+    }
+    fn receive_hover(&mut self, result: &json::Value) {
+        let text = result.get("contents").map(hover::text).unwrap_or_default();
+        let width = (self.terminal.size().width as usize)
+            .saturating_sub(4)
+            .min(HOVER_WIDTH);
+        let mut lines = hover::lines(&text, width);
+        lines.truncate((self.terminal.size().height as usize).saturating_sub(1));
+        if lines.is_empty() {
+            self.status_message = StatusMessage::from("No hover information.".to_string());
+        } else {
+            self.hover = Some(lines);
+        }
+    }
+    /// Opens the completion menu with words from the buffer, asking the language
+    /// server for better candidates. After text that looks like a path it offers the
+    /// entries of its directory instead.
+    fn start_completion(&mut self) {
+        if self.mode != Mode::Insert {
+            return;
+        }
+        if self.path_before_cursor().is_some() {
+            self.start_path_completion();
+            if self.popup.is_none() {
+                self.status_message = StatusMessage::from("No completions.".to_string());
+            }
+            return;
+        }
+        let at = self.cursor_position.clone();
+        let (start, typed) = autocomplete::word_before(&self.document, &at);
+        let mut popup =
+            autocomplete::Popup::new(start, autocomplete::buffer_words(&self.document, &at));
+        popup.awaiting = self.request_at_cursor(
+            "textDocument/completion",
+            vec![(
+                "context",
+                json::Value::object(vec![("triggerKind", json::Value::from(1_usize))]),
+            )],
+        );
+        popup.filter(&typed);
+        if popup.is_empty() && !popup.awaiting {
+            self.status_message = StatusMessage::from("No completions.".to_string());
+            return;
+        }
+        self.popup = Some(popup);
+    }
+    /// Opens the completion menu on the directory of the path before the cursor.
+    fn start_path_completion(&mut self) {
+        let (start, dir, typed) = if let Some(path) = self.path_before_cursor() {
+            path
+        } else {
+            return;
+        };
+        // Relative paths are read from the buffer's directory, as includes are.
+        let base = self
+            .document
+            .file_name
+            .as_ref()
+            .and_then(|file_name| Path::new(file_name).parent())
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+        let mut popup =
+            autocomplete::Popup::new(start, autocomplete::path_items(&dir, &typed, &base));
+        popup.filter(&typed);
+        self.popup = (!popup.is_empty()).then_some(popup);
+    }
+    /// The path before the cursor, when the buffer completes paths.
+    fn path_before_cursor(&self) -> Option<(Position, String, String)> {
+        if !self.document.path_completion {
+            return None;
+        }
+        autocomplete::path_before(&self.document, &self.cursor_position)
+    }
+    fn receive_completions(&mut self, result: Option<&json::Value>) {
+        if let Some(popup) = self.popup.as_mut().filter(|popup| popup.awaiting) {
+            popup.awaiting = false;
+            let items = result.map(autocomplete::from_lsp).unwrap_or_default();
+            if !items.is_empty() {
+                popup.set_items(items);
+            }
+        }
+        self.update_popup();
+    }
+    /// Refilters the menu after an edit, closing it once the cursor leaves the word.
+    fn update_popup(&mut self) {
+        let popup = if let Some(popup) = &mut self.popup {
+            popup
+        } else {
+            return;
+        };
+        let (start, typed) = autocomplete::path_before(&self.document, &self.cursor_position)
+            .filter(|_| self.document.path_completion)
+            .map_or_else(
+                || autocomplete::word_before(&self.document, &self.cursor_position),
+                |(start, _, typed)| (start, typed),
+            );
+        if self.mode != Mode::Insert || start != popup.start {
+            self.popup = None;
+            return;
+        }
+        popup.filter(&typed);
+        if popup.is_empty() && !popup.awaiting {
+            self.popup = None;
+        }
+    }
+    /// Handles navigation keys while the completion menu is open; returns whether the
+    /// key was used.
+    fn process_popup_key(&mut self, key: Key) -> bool {
+        let popup = if let Some(popup) = &mut self.popup {
+            popup
+        } else {
+            return false;
+        };
+        match key {
+            Key::Up | Key::BackTab => popup.select_next(true),
+            Key::Down | Key::Char('\t') => popup.select_next(false),
+            Key::Char('\n') => self.accept_completion(),
+            Key::Esc => self.popup = None,
+            _ => return false,
+        }
+        true
+    }
+    /// Replaces the word before the cursor with the next (or previous) word from the open
+    /// buffers that it is a prefix of, returning to the typed prefix after the last.
+    /// Continues a Ctrl-V sequence: `u` and up to four hex digits (`U` and up to eight,
+    /// `x` and up to two) enter a code point, Tab and Enter insert themselves, and any
+    /// other two characters form a digraph. A key ending the digits early is handled
+    /// as usual once the character is in.
+    fn continue_char_input(&mut self, mut typed: String, key: Key) {
+        let c = match key {
+            Key::Char(c) => c,
+            _ => {
+                self.status_message = StatusMessage::from(String::new());
+                return;
+            }
+        };
+        let max_digits: usize = match typed.chars().next() {
+            Some('u') => 4,
+            Some('U') => 8,
+            Some('x') => 2,
+            _ => 0,
+        };
+        let digits = typed.get(1..).unwrap_or_default().to_string();
+        let inserted = if typed.is_empty() && (c == '\t' || c == '\n') {
+            Some(c)
+        } else if typed.is_empty() || max_digits > 0 && c.is_ascii_hexdigit() {
+            typed.push(c);
+            if digits.len() < max_digits.saturating_sub(1) || max_digits == 0 {
+                self.status_message = StatusMessage::from(format!("^V{}", typed));
+                self.char_input = Some(typed);
+                return;
+            }
+            unicode::codepoint(typed.get(1..).unwrap_or_default())
+        } else if !digits.is_empty() {
+            self.input_queue.push_front(key);
+            unicode::codepoint(&digits)
+        } else {
+            typed
+                .chars()
+                .next()
+                .and_then(|first| unicode::digraph(first, c))
+        };
+        match inserted {
+            Some(c) => {
+                self.status_message = StatusMessage::from(String::new());
+                self.document.insert(&self.cursor_position, c);
+                self.move_cursor(Key::Right);
+            }
+            None => {
+                self.status_message =
+                    StatusMessage::error(format!("No character for ^V{}{}", typed, c));
+            }
+        }
+    }
+    fn show_digraphs(&mut self) {
+        let entries: Vec<String> = unicode::DIGRAPHS
+            .iter()
+            .map(|(digraph, c, _)| format!("{} {}", digraph, c))
+            .collect();
+        self.overlay = Some(entries.chunks(12).map(|row| row.join("   ")).collect());
+    }
+    /// Reports the size of the visual selection, or of the whole buffer, like `wc`.
+    fn count_words(&mut self) {
+        let line_ending = self.document.line_ending.as_str();
+        let message = if self.mode == Mode::Visual {
+            let (start, end) = self.selection();
+            // A selection ending at the start of a line takes only the line break before it.
+            let takes_break = end.x == 0 && end.y > start.y;
+            let last = if takes_break {
+                end.y.saturating_sub(1)
+            } else {
+                end.y
+            };
+            let lines: Vec<String> = (start.y..=last)
+                .filter_map(|y| {
+                    let row = self.document.row(y)?;
+                    let from = if y == start.y { start.x } else { 0 };
+                    let to = if y == end.y { end.x } else { row.len() };
+                    Some(
+                        row.graphemes()
+                            .skip(from)
+                            .take(to.saturating_sub(from))
+                            .collect(),
+                    )
+                })
+                .collect();
+            let stats = Stats::of(lines.iter().map(String::as_str), line_ending, takes_break);
+            format!("Selected {}", stats.describe())
+        } else {
+            let lines = (0..self.document.len())
+                .filter_map(|y| self.document.row(y))
+                .map(Row::as_str);
+            Stats::of(lines, line_ending, self.document.ends_with_newline()).describe()
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    /// Recounts the words of prose buffers for the status bar when `wordcount` is on.
+    fn refresh_word_count(&mut self) {
+        if !self.config.word_count
+            || self.document.large
+            || !filetype::is_prose(self.document.file_type.as_deref())
+        {
+            self.word_count = None;
+            return;
+        }
+        let revision = self.document.revision();
+        if self.word_count.map(|(counted, _)| counted) == Some(revision) {
+            return;
+        }
+        let words = (0..self.document.len())
+            .filter_map(|y| self.document.row(y))
+            .map(|row| row.as_str().split_whitespace().count())
+            .sum();
+        self.word_count = Some((revision, words));
+    }
+    /// Describes the character under the cursor in the message bar: its code points,
+    /// UTF-8 bytes, names and how many columns a terminal gives it.
+    fn open_url(&mut self) {
+        let url = self
+            .document
+            .row(self.cursor_position.y)
+            .and_then(|row| url::at(row.as_str(), self.cursor_position.x));
+        let message = match url {
+            Some(url) => match url::open(&self.config.opener, &url) {
+                Ok(()) => format!("Opening {}", url),
+                Err(error) => format!("Could not run {}: {}", self.config.opener, error),
+            },
+            None => "No URL under the cursor.".to_string(),
+        };
+        self.status_message = StatusMessage::from(message);
+    }
+    /// Opens the file named under the cursor, at the line and column that may follow
+    /// it. Relative names are looked up from the current file's directory, then the
+    /// project root, then the working directory.
+    fn is_markdown(&self) -> bool {
+        self.document.file_type.as_deref() == Some("markdown")
+    }
+    /// Enter in markdown: a list item or quote carries on with its marker on the next
+    /// line, and Enter on an empty item ends the list.
+    fn markdown_newline(&mut self) {
+        let y = self.cursor_position.y;
+        let (before, at_end) = self.document.row(y).map_or((String::new(), true), |row| {
+            (
+                row.graphemes().take(self.cursor_position.x).collect(),
+                self.cursor_position.x >= row.len(),
+            )
+        });
+        match markdown::continuation(&before) {
+            Some(markdown::Continuation::End) if at_end => {
+                self.document
+                    .delete_range(&Position { x: 0, y }, &self.cursor_position);
+                self.cursor_position.x = 0;
+            }
+            Some(markdown::Continuation::Prefix(prefix)) => {
+                self.document.insert(&self.cursor_position, '\n');
+                self.cursor_position = self.document.insert_str(
+                    &Position {
+                        x: 0,
+                        y: y.saturating_add(1),
+                    },
+                    &prefix,
+                );
+            }
+            _ => {
+                self.document.insert(&self.cursor_position, '\n');
+                self.move_cursor(Key::Right);
+            }
+        }
+    }
+    fn toggle_checkbox(&mut self) {
+        if !self.is_markdown() {
+            self.status_message = StatusMessage::error("Not a markdown buffer.".to_string());
+            return;
+        }
+        let y = self.cursor_position.y;
+        let checkbox = self
+            .document
+            .row(y)
+            .and_then(|row| markdown::checkbox(row.as_str()));
+        if let Some((x, toggled)) = checkbox {
+            let at = Position { x, y };
+            self.document.begin();
+            self.document.delete(&at);
+            self.document.insert(&at, toggled);
+            self.document.commit();
+        } else {
+            self.status_message = StatusMessage::error("No task on this line.".to_string());
+        }
+    }
+    /// Lines up the columns of the pipe table around the cursor.
+    fn align_table(&mut self) {
+        if !self.is_markdown() {
+            self.status_message = StatusMessage::error("Not a markdown buffer.".to_string());
+            return;
+        }
+        let is_row = |y: usize| {
+            self.document
+                .row(y)
+                .is_some_and(|row| markdown::is_table_row(row.as_str()))
+        };
+        let y = self.cursor_position.y;
+        if !is_row(y) {
+            self.status_message = StatusMessage::error("No table under the cursor.".to_string());
+            return;
+        }
+        let mut first = y;
+        while first > 0 && is_row(first.saturating_sub(1)) {
+            first = first.saturating_sub(1);
+        }
+        let mut last = y;
+        while is_row(last.saturating_add(1)) {
+            last = last.saturating_add(1);
+        }
+        let rows: Vec<&str> = (first..=last)
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        let aligned = markdown::align_table(&rows);
+        let line = |y: usize| {
+            self.document
+                .row(y)
+                .map(|row| format!("{}\n", row.as_str()))
+        };
+        let mut text: String = (0..first).filter_map(line).collect();
+        for row in aligned {
+            text.push_str(&row);
+            text.push('\n');
+        }
+        text.extend((last.saturating_add(1)..self.document.len()).filter_map(line));
+        self.document.apply_text(&text);
+        self.cursor_position.x = 0;
+    }
+    fn open_file_under_cursor(&mut self) {
+        let found = self
+            .document
+            .row(self.cursor_position.y)
+            .and_then(|row| paths::file_at(row.as_str(), self.cursor_position.x));
+        let (name, line, column) = if let Some(found) = found {
+            found
+        } else {
+            self.status_message =
+                StatusMessage::error("No file name under the cursor.".to_string());
+            return;
+        };
+        let name = paths::expand_home(&name);
+        let current_dir = self
+            .document
+            .file_name
+            .as_ref()
+            .and_then(|file_name| Path::new(file_name).parent().map(Path::to_path_buf));
+        let path = if name.is_absolute() {
+            Some(name.clone()).filter(|path| path.is_file())
+        } else {
+            [
+                current_dir,
+                self.project_root.clone(),
+                Some(PathBuf::from(".")),
+            ]
+            .into_iter()
+            .flatten()
+            .map(|dir| dir.join(&name))
+            .find(|path| path.is_file())
+        };
+        let file_name = if let Some(path) = path {
+            path.to_string_lossy().into_owned()
+        } else {
+            self.status_message =
+                StatusMessage::error(format!("Can't find file {}", name.display()));
+            return;
+        };
+        if let Some(line) = line {
+            let position = Position {
+                x: column.unwrap_or(1).saturating_sub(1),
+                y: line.saturating_sub(1),
+            };
+            self.goto_location(&file_name, &position);
+        } else {
+            let from = Jump {
+                file_name: self.document.file_name.clone(),
+                position: self.cursor_position.clone(),
+            };
+            self.edit_file(&file_name);
+            if self.document.file_name != from.file_name {
+                self.jump_list.push(from);
+            }
+        }
+    }
+    fn inspect_character(&mut self) {
+        let grapheme = self
+            .document
+            .row(self.cursor_position.y)
+            .and_then(|row| row.graphemes().nth(self.cursor_position.x))
+            .map(str::to_string);
+        let grapheme = match grapheme {
+            Some(grapheme) => grapheme,
+            None => {
+                self.status_message = StatusMessage::from("End of line".to_string());
+                return;
+            }
+        };
+        let names = self.unicode_names.get_or_insert_with(unicode::names);
+        let codepoints: Vec<String> = grapheme
+            .chars()
+            .map(|c| format!("U+{:04X}", u32::from(c)))
+            .collect();
+        let bytes: Vec<String> = grapheme
+            .bytes()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+        let described: Vec<&str> = grapheme
+            .chars()
+            .filter_map(|c| {
+                names
+                    .iter()
+                    .find(|(named, _)| *named == c)
+                    .map(|(_, name)| name.as_str())
+            })
+            .collect();
+        let width: usize = grapheme.chars().map(unicode::width).sum();
+        let shown = if width == 0 {
+            String::new()
+        } else {
+            format!("<{}> ", grapheme)
+        };
+        let mut parts = vec![
+            format!("{}{}", shown, codepoints.join(" ")),
+            format!("bytes {}", bytes.join(" ")),
+        ];
+        // Names are only known for every character when UnicodeData.txt is installed.
+        if !described.is_empty() {
+            parts.push(described.join(", "));
+        }
+        parts.push(format!("width {}", width));
+        self.status_message = StatusMessage::from(parts.join("  "));
+    }
+    /// Searches characters by name and inserts the one picked at the cursor.
+    fn unicode_picker(&mut self) {
+        let names = self.unicode_names.take().unwrap_or_else(unicode::names);
+        let mut query = String::new();
+        let mut selected = 0_usize;
+        let chosen = loop {
+            let matches = unicode::search(&names, &query);
+            selected = selected.min(matches.len().saturating_sub(1));
+            let height = self.terminal.size().height as usize;
+            self.overlay = Some(
+                matches
+                    .iter()
+                    .take(height.saturating_sub(1))
+                    .enumerate()
+                    .map(|(index, (c, name))| {
+                        let marker = if index == selected { '>' } else { ' ' };
+                        let shown = if c.is_control() { ' ' } else { *c };
+                        format!("{} {}  U+{:04X}  {}", marker, shown, u32::from(*c), name)
+                    })
+                    .collect(),
+            );
+            self.status_message = StatusMessage::from(format!("Character name: {}", query));
+            if self.refresh_screen().is_err() {
+                break None;
+            }
+            match self.next_key() {
+                Ok(Key::Char('\n')) => break matches.get(selected).map(|(c, _)| *c),
+                Ok(Key::Esc) | Err(_) => break None,
+                Ok(Key::Up | Key::Ctrl('p')) => selected = selected.saturating_sub(1),
+                Ok(Key::Down | Key::Ctrl('n')) => selected = selected.saturating_add(1),
+                Ok(Key::Backspace) => {
+                    query.pop();
+                    selected = 0;
+                }
+                Ok(Key::Char(c)) if !c.is_control() => {
+                    query.push(c);
+                    selected = 0;
+                }
+                Ok(_) => (),
+            }
+        };
+        self.unicode_names = Some(names);
+        self.overlay = None;
+        self.status_message = StatusMessage::from(String::new());
+        if let Some(c) = chosen {
+            self.document.insert(&self.cursor_position, c);
+            if self.mode == Mode::Insert {
+                self.move_cursor(Key::Right);
+            }
+        }
+    }
+    /// Replaces the word before the cursor with its abbreviation, if it has one. The
+    /// buffer's file type may override the global abbreviations.
+    fn expand_abbreviation(&mut self) {
+        let (start, word) = autocomplete::word_before(&self.document, &self.cursor_position);
+        if word.is_empty() || self.document.read_only {
+            return;
+        }
+        let expansion = self
+            .document
+            .file_type
+            .as_ref()
+            .and_then(|file_type| self.config.filetypes.get(file_type))
+            .and_then(|overrides| overrides.abbreviations.get(&word))
+            .or_else(|| self.config.abbreviations.get(&word))
+            .cloned();
+        if let Some(expansion) = expansion {
+            let end = self.cursor_position.clone();
+            self.document.delete_range(&start, &end);
+            self.cursor_position = self.document.insert_str(&start, &expansion);
+        }
+    }
+    /// `:abbreviate <word> <expansion>` adds a global abbreviation for this session.
+    fn abbreviate(&mut self, args: &str) {
+        match args.trim().split_once(' ') {
+            Some((word, expansion)) if !expansion.trim().is_empty() => {
+                self.config
+                    .abbreviations
+                    .insert(word.to_string(), expansion.trim().to_string());
+            }
+            _ => {
+                self.status_message =
+                    StatusMessage::error("Usage: :abbreviate <word> <expansion>".to_string());
+            }
+        }
+    }
+    /// Tab and Shift-Tab in insert mode: move between the stops of the snippet being
+    /// filled in, or expand the snippet whose trigger is the word before the cursor.
+    /// Returns whether the key was used.
+    fn snippet_tab(&mut self, backward: bool) -> bool {
+        if let Some(session) = &self.snippet {
+            let index = if backward {
+                session.current.saturating_sub(1)
+            } else {
+                session.current.saturating_add(1)
+            };
+            self.enter_snippet_stop(index);
+            return true;
+        }
+        if backward || self.document.read_only {
+            return false;
+        }
+        let (start, trigger) = autocomplete::word_before(&self.document, &self.cursor_position);
+        if trigger.is_empty() {
+            return false;
+        }
+        let file_type = self.document.file_type.clone();
+        let body = self
+            .snippets
+            .entry(file_type.clone().unwrap_or_default())
+            .or_insert_with(|| snippet::load(file_type.as_deref()))
+            .iter()
+            .find(|snippet| snippet.trigger == trigger)
+            .map(|snippet| snippet.body.clone());
+        match body {
+            Some(body) => {
+                self.expand_snippet(&start, &body);
+                true
+            }
+            None => false,
+        }
+    }
+    /// Replaces the trigger typed from `start` to the cursor with `body`, indenting its
+    /// lines like the current one, and moves to the first stop.
+    fn expand_snippet(&mut self, start: &Position, body: &str) {
+        let expansion = snippet::expand(body);
+        let indent: String = self
+            .document
+            .row(start.y)
+            .map(|row| {
+                row.as_str()
+                    .chars()
+                    .take_while(|c| *c == ' ' || *c == '\t')
+                    .collect()
+            })
+            .unwrap_or_default();
+        let unit = self.document.indent.text_at(0);
+        // How far each line's stops move: the first line starts at the trigger, later
+        // lines get the current indentation and their leading tabs become indent units.
+        let mut offsets = Vec::new();
+        let mut lines = Vec::new();
+        for (index, line) in expansion.lines.iter().enumerate() {
+            if index == 0 || line.is_empty() {
+                offsets.push(if index == 0 { start.x } else { 0 });
+                lines.push(line.clone());
+                continue;
+            }
+            let text = line.trim_start_matches('\t');
+            let levels = line.len().saturating_sub(text.len());
+            let prefix = format!("{}{}", indent, unit.repeat(levels));
+            offsets.push(prefix.chars().count().saturating_sub(levels));
+            lines.push(prefix + text);
+        }
+        let end = self.cursor_position.clone();
+        self.document.begin();
+        self.document.delete_range(start, &end);
+        self.document.insert_str(start, &lines.join("\n"));
+        self.document.commit();
+        let stops = expansion
+            .stops
+            .iter()
+            .map(|ranges| {
+                ranges
+                    .iter()
+                    .map(|range| {
+                        let offset = offsets.get(range.y).copied().unwrap_or(0);
+                        Range {
+                            y: range.y.saturating_add(start.y),
+                            start: range.start.saturating_add(offset),
+                            end: range.end.saturating_add(offset),
+                        }
+                    })
+                    .collect()
+            })
+            .collect::<Vec<Vec<Range>>>();
+        let count = stops.len();
+        self.snippet = Some(snippet::Session {
+            stops,
+            current: 0,
+            pristine: false,
+            line_len: 0,
+            lines: 0,
+        });
+        for stop in 0..count {
+            self.fill_mirrors(stop);
+        }
+        self.enter_snippet_stop(0);
+    }
+    /// Moves the cursor to the end of snippet stop `index`. The last stop ends the snippet.
+    fn enter_snippet_stop(&mut self, index: usize) {
+        let mut session = match self.snippet.take() {
+            Some(session) => session,
+            None => return,
+        };
+        let range = match session.stops.get(index).and_then(|ranges| ranges.first()) {
+            Some(range) => *range,
+            None => return,
+        };
+        self.cursor_position = Position {
+            x: range.end,
+            y: range.y,
+        };
+        if index.saturating_add(1) < session.stops.len() {
+            session.current = index;
+            session.pristine = range.end > range.start;
+            session.line_len = self.document.row(range.y).map_or(0, Row::len);
+            session.lines = self.document.len();
+            self.snippet = Some(session);
+        }
+    }
+    /// Deletes the default text of the current snippet stop when typing starts in it.
+    /// Returns whether `key` was used up doing so.
+    fn clear_snippet_placeholder(&mut self, key: Key) -> bool {
+        let session = match &mut self.snippet {
+            Some(session) if session.pristine => session,
+            _ => return false,
+        };
+        session.pristine = false;
+        let range = match session.primary() {
+            Some(range) => range,
+            None => return false,
+        };
+        let typing = matches!(key, Key::Char(c) if c != '\t' && c != '\n');
+        let deleting = matches!(key, Key::Backspace | Key::Delete);
+        let at = &self.cursor_position;
+        if !(typing || deleting) || at.y != range.y || at.x < range.start || at.x > range.end {
+            return false;
+        }
+        let start = Position {
+            x: range.start,
+            y: range.y,
+        };
+        self.document.delete_range(
+            &start,
+            &Position {
+                x: range.end,
+                y: range.y,
+            },
+        );
+        self.cursor_position = start;
+        deleting
+    }
+    /// Follows edits at the current snippet stop, growing it by what was typed and
+    /// copying the text to its mirrors. Leaving the stop ends the snippet.
+    fn sync_snippet(&mut self) {
+        let mut session = match self.snippet.take() {
+            Some(session) => session,
+            None => return,
+        };
+        let primary = match session.primary() {
+            Some(primary) => primary,
+            None => return,
+        };
+        let line_len = self.document.row(primary.y).map_or(0, Row::len);
+        let delta = line_len as isize - session.line_len as isize;
+        let end = primary.end.saturating_add_signed(delta);
+        let at = &self.cursor_position;
+        if self.mode != Mode::Insert
+            || self.document.len() != session.lines
+            || at.y != primary.y
+            || at.x < primary.start
+            || at.x > end
+        {
+            return;
+        }
+        if delta != 0 {
+            session.resize(session.current, 0, delta);
+            session.pristine = false;
+        }
+        let current = session.current;
+        self.snippet = Some(session);
+        self.fill_mirrors(current);
+        let line_len = self.document.row(primary.y).map_or(0, Row::len);
+        if let Some(session) = &mut self.snippet {
+            session.line_len = line_len;
+        }
+    }
+    /// Copies the text of snippet stop `stop` to its other occurrences.
+    fn fill_mirrors(&mut self, stop: usize) {
+        let mut session = match self.snippet.take() {
+            Some(session) => session,
+            None => return,
+        };
+        let count = session.stops.get(stop).map_or(0, Vec::len);
+        let text = session
+            .stops
+            .get(stop)
+            .and_then(|ranges| ranges.first())
+            .map(|range| self.range_text(*range))
+            .unwrap_or_default();
+        for index in 1..count {
+            let mirror = match session.stops.get(stop).and_then(|ranges| ranges.get(index)) {
+                Some(mirror) => *mirror,
+                None => continue,
+            };
+            let old = self.range_text(mirror);
+            if old == text {
+                continue;
+            }
+            let start = Position {
+                x: mirror.start,
+                y: mirror.y,
+            };
+            self.document.delete_range(
+                &start,
+                &Position {
+                    x: mirror.end,
+                    y: mirror.y,
+                },
+            );
+            self.document.insert_str(&start, &text);
+            let delta =
+                Row::from(text.as_str()).len() as isize - Row::from(old.as_str()).len() as isize;
+            if self.cursor_position.y == mirror.y && self.cursor_position.x >= mirror.end {
+                self.cursor_position.x = self.cursor_position.x.saturating_add_signed(delta);
+            }
+            session.resize(stop, index, delta);
+        }
+        self.snippet = Some(session);
+    }
+    fn range_text(&self, range: Range) -> String {
+        self.document
+            .row(range.y)
+            .map(|row| {
+                row.graphemes()
+                    .skip(range.start)
+                    .take(range.end.saturating_sub(range.start))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    fn cycle_word_completion(&mut self, backward: bool) {
+        if self.word_cycle.is_none() {
+            let (start, prefix) = autocomplete::word_before(&self.document, &self.cursor_position);
+            self.document.refresh_words();
+            for buffer in &mut self.buffers {
+                buffer.document.refresh_words();
+            }
+            // Words of the current buffer come first, then those only found elsewhere.
+            let mut candidates: Vec<String> = self
+                .document
+                .words
+                .completions(&prefix)
+                .into_iter()
+                .map(|(word, _)| word.to_string())
+                .collect();
+            for (index, buffer) in self.buffers.iter().enumerate() {
+                if index == self.current_buffer {
+                    continue;
+                }
+                for (word, _) in buffer.document.words.completions(&prefix) {
+                    if !candidates.iter().any(|candidate| candidate == word) {
+                        candidates.push(word.to_string());
+                    }
+                }
+            }
+            if candidates.is_empty() {
+                self.status_message = StatusMessage::from("No completions.".to_string());
+                return;
+            }
+            self.popup = None;
+            self.word_cycle = Some(WordCycle {
+                start,
+                prefix,
+                candidates,
+                index: None,
+            });
+        }
+        let cycle = if let Some(cycle) = &mut self.word_cycle {
+            cycle
+        } else {
+            return;
+        };
+        let last = cycle.candidates.len().saturating_sub(1);
+        cycle.index = match (cycle.index, backward) {
+            (None, false) => Some(0),
+            (None, true) => Some(last),
+            (Some(index), false) => (index < last).then(|| index.saturating_add(1)),
+            (Some(index), true) => index.checked_sub(1),
+        };
+        let (text, message) = match cycle.index.and_then(|index| cycle.candidates.get(index)) {
+            Some(word) => (
+                word.clone(),
+                format!(
+                    "Match {} of {}",
+                    cycle.index.unwrap_or(0).saturating_add(1),
+                    cycle.candidates.len()
+                ),
+            ),
+            None => (cycle.prefix.clone(), "Back at original".to_string()),
+        };
+        let start = cycle.start.clone();
+        self.document.delete_range(&start, &self.cursor_position);
+        self.cursor_position = self.document.insert_str(&start, &text);
+        self.status_message = StatusMessage::from(message);
+    }
+    /// Replaces the word before the cursor with the selected item.
+    fn accept_completion(&mut self) {
+        let popup = if let Some(popup) = self.popup.take() {
+            popup
+        } else {
+            return;
+        };
+        let item = if let Some(item) = popup.selected() {
+            item
+        } else {
+            return;
+        };
+        let mut start = popup.start.clone();
+        if let Some((line, character)) = item.replace_start {
+            if let Some(row) = self.document.row(line).filter(|_| line == start.y) {
+                start.x = row.index_at_utf16(character).min(self.cursor_position.x);
+            }
+        }
+        self.document.delete_range(&start, &self.cursor_position);
+        self.cursor_position = self.document.insert_str(&start, &item.insert_text);
+        // Accepting a directory goes on to its entries.
+        if item.kind.is_some() && item.insert_text.ends_with('/') {
+            self.start_path_completion();
+        }
+    }
+    /// Rereads the repository status for the status bar in the background every few
+    /// seconds, and right away after switching to a buffer in another place. A status
+    /// read for another buffer's file is dropped when it arrives.
+    fn refresh_git_status(&mut self) {
+        if let Some((file_name, receiver)) = &self.git_status_pending {
+            match receiver.try_recv() {
+                Ok(status) => {
+                    if *file_name == self.document.file_name {
+                        self.git_status = status;
+                    }
+                    self.git_status_pending = None;
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => self.git_status_pending = None,
+            }
+        }
+        if !self.focused && self.git_status_checked.is_some() {
+            return;
+        }
+        let fresh = self
+            .git_status_checked
+            .as_ref()
+            .is_some_and(|(file_name, checked)| {
+                *file_name == self.document.file_name && checked.elapsed() < GIT_STATUS_INTERVAL
+            });
+        if !fresh {
+            let file_name = self.document.file_name.clone();
+            let receiver = git::Status::spawn(file_name.clone(), &self.terminal.waker());
+            self.git_status_pending = Some((file_name.clone(), receiver));
+            self.git_status_checked = Some((file_name, Instant::now()));
+        }
+    }
+    /// Keeps the current buffer's symbols up to date for the outline and the status
+    /// bar, from the language server when it offers them and otherwise from the text.
+    fn refresh_symbols(&mut self) {
+        let revision = self.document.revision();
+        if self.document.symbols_revision == Some(revision)
+            || self.symbols_request.is_some()
+            || self.document.is_binary()
+            || self.document.large
+        {
+            return;
+        }
+        if let Some((file_type, uri)) = self.sync_language_server() {
+            if let Some(Some(server)) = self.language_servers.get_mut(&file_type) {
+                if !server.is_ready() {
+                    return;
+                }
+                if server.supports("documentSymbolProvider") {
+                    server.request(
+                        "textDocument/documentSymbol",
+                        json::Value::object(vec![(
+                            "textDocument",
+                            json::Value::object(vec![("uri", json::Value::from(uri))]),
+                        )]),
+                    );
+                    self.symbols_request = Some((self.document.file_name.clone(), revision));
+                    return;
+                }
+            }
+        }
+        self.document.symbols = outline::from_text(&self.document);
+        self.document.symbols_revision = Some(revision);
+    }
+    fn receive_symbols(&mut self, result: Option<&json::Value>) {
+        let request = self.symbols_request.take();
+        let revision = self.document.revision();
+        if request != Some((self.document.file_name.clone(), revision)) {
+            return;
+        }
+        self.document.symbols = match result {
+            Some(result) => outline::from_lsp(result, &self.document),
+            None => outline::from_text(&self.document),
+        };
+        self.document.symbols_revision = Some(revision);
+    }
+    /// Keeps the language server's folding ranges for the current buffer up to date,
+    /// when it offers them.
+    fn refresh_folding_ranges(&mut self) {
+        let revision = self.document.revision();
+        if self.document.folding_ranges_revision == Some(revision)
+            || self.folding_request.is_some()
+            || self.document.is_binary()
+            || self.document.large
+        {
+            return;
+        }
+        if let Some((file_type, uri)) = self.sync_language_server() {
+            if let Some(Some(server)) = self.language_servers.get_mut(&file_type) {
+                if server.is_ready() && server.supports("foldingRangeProvider") {
+                    server.request(
+                        "textDocument/foldingRange",
+                        json::Value::object(vec![(
+                            "textDocument",
+                            json::Value::object(vec![("uri", json::Value::from(uri))]),
+                        )]),
+                    );
+                    self.folding_request = Some((self.document.file_name.clone(), revision));
+                }
+            }
+        }
+    }
+    fn receive_folding_ranges(&mut self, result: Option<&json::Value>) {
+        let request = self.folding_request.take();
+        let revision = self.document.revision();
+        if request != Some((self.document.file_name.clone(), revision)) {
+            return;
+        }
+        self.document.folding_ranges = result.map_or_else(Vec::new, fold::from_lsp);
+        self.document.folding_ranges_revision = Some(revision);
+    }
+    /// The folds the buffer can have: the language server's folding ranges when they
+    /// are current, otherwise sections under headings in markdown and blocks of deeper
+    /// indentation elsewhere.
+    fn fold_ranges(&self) -> Vec<(usize, usize)> {
+        if self.document.folding_ranges_revision == Some(self.document.revision())
+            && !self.document.folding_ranges.is_empty()
+        {
+            return self.document.folding_ranges.clone();
+        }
+        let lines: Vec<&str> = (0..self.document.len())
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        if self.is_markdown() {
+            return markdown::heading_folds(&lines);
+        }
+        fold::indent_folds(&lines, self.document.indent.tab_width)
+    }
+    /// The fold to close at the cursor: the innermost one starting on or containing the
+    /// cursor line.
+    fn fold_at_cursor(&self) -> Option<(usize, usize)> {
+        let y = self.cursor_position.y;
+        if self.document.folding_ranges_revision == Some(self.document.revision())
+            && !self.document.folding_ranges.is_empty()
+        {
+            return fold::innermost(&self.document.folding_ranges, y);
+        }
+        let lines: Vec<&str> = (0..self.document.len())
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        if self.is_markdown() {
+            return fold::innermost(&markdown::heading_folds(&lines), y);
+        }
+        fold::indent_fold(&lines, y, self.document.indent.tab_width)
+    }
+    fn close_fold(&mut self) {
+        if let Some((start, end)) = self.fold_at_cursor() {
+            self.document.folds.close(start, end);
+            self.cursor_position.y = self.document.folds.visible_line(start);
+            self.cursor_position.x = 0;
+            self.scroll();
+        } else {
+            self.status_message = StatusMessage::from("No fold found.".to_string());
+        }
+    }
+    fn open_fold(&mut self) {
+        if !self.document.folds.open(self.cursor_position.y) {
+            self.status_message = StatusMessage::from("No closed fold here.".to_string());
+        }
+        self.scroll();
+    }
+    fn toggle_fold(&mut self) {
+        if self.document.folds.at(self.cursor_position.y).is_some() {
+            self.open_fold();
+        } else {
+            self.close_fold();
+        }
+    }
+    fn close_all_folds(&mut self) {
+        for (start, end) in self.fold_ranges() {
+            self.document.folds.close(start, end);
+        }
+        self.cursor_position.y = self.document.folds.visible_line(self.cursor_position.y);
+        let width = self
+            .document
+            .row(self.cursor_position.y)
+            .map_or(0, Row::len);
+        self.cursor_position.x = self.cursor_position.x.min(width);
+        self.scroll();
+    }
+    /// Opens the folds hiding the cursor line after a jump into them, so the cursor is
+    /// never on a hidden line.
+    fn open_folds_at_cursor(&mut self) {
+        let y = self.cursor_position.y;
+        while self
+            .document
+            .folds
+            .at(y)
+            .is_some_and(|(start, _)| start != y)
+        {
+            self.document.folds.open(y);
+        }
+    }
+    fn toggle_outline(&mut self) {
+        if self.outline.take().is_none() {
+            self.outline = Some(Outline::default());
+            self.focus_outline();
+        }
+    }
+    /// Lets the outline panel take keys: typing filters the symbols, Up/Down select
+    /// and Enter jumps. Esc gives the keys back to the buffer.
+    fn focus_outline(&mut self) {
+        loop {
+            let outline = if let Some(outline) = &mut self.outline {
+                outline
+            } else {
+                return;
+            };
+            outline.focused = true;
+            let count = outline.matches(&self.document.symbols).len();
+            outline.selected = outline.selected.min(count.saturating_sub(1));
+            self.status_message = StatusMessage::from(
+                "Outline: type to filter, Up/Down to select, Enter to jump, Esc to leave"
+                    .to_string(),
+            );
+            if self.refresh_screen().is_err() {
+                break;
+            }
+            let key = match self.next_key_timeout(None) {
+                Ok(Some(key)) => key,
+                Ok(None) => {
+                    self.woken = false;
+                    self.update_language_servers();
+                    self.refresh_symbols();
+                    continue;
+                }
+                Err(_) => break,
+            };
+            let outline = if let Some(outline) = &mut self.outline {
+                outline
+            } else {
+                return;
+            };
+            match key {
+                Key::Up | Key::Ctrl('p') => outline.select_next(true, count),
+                Key::Down | Key::Ctrl('n') => outline.select_next(false, count),
+                Key::Backspace => {
+                    outline.query.pop();
+                    outline.selected = 0;
+                }
+                Key::Char('\n') => {
+                    let target = outline
+                        .matches(&self.document.symbols)
+                        .get(outline.selected)
+                        .and_then(|index| self.document.symbols.get(*index))
+                        .map(|symbol| symbol.position.clone());
+                    if let Some(position) = target {
+                        let old_position = self.cursor_position.clone();
+                        self.jump_to(&position);
+                        self.record_jump(old_position);
+                    }
+                    break;
+                }
+                Key::Char(c) if !c.is_control() => {
+                    outline.query.push(c);
+                    outline.selected = 0;
+                }
+                Key::Esc => break,
+                _ => (),
+            }
+        }
+        if let Some(outline) = &mut self.outline {
+            outline.focused = false;
+            outline.query.clear();
+        }
+        self.status_message = StatusMessage::from(String::new());
+    }
+    /// Opens the terminal pane if needed and hands it the keys until the terminal
+    /// command's key is pressed again or the shell exits.
+    fn focus_terminal_pane(&mut self) {
+        if self.terminal_pane.is_none() {
+            let width = (self.terminal.size().width as usize / 2).min(terminal_pane::WIDTH);
+            let height = self.terminal.size().height as usize;
+            match TerminalPane::open(width.saturating_sub(1), height, self.terminal.waker()) {
+                Ok(pane) => self.terminal_pane = Some(pane),
+                Err(error) => {
+                    self.status_message =
+                        StatusMessage::error(format!("ERR: Could not start a shell: {}", error));
+                    return;
+                }
+            }
+        }
+        let leave = self.config.bindings.keys_for(Command::Terminal);
+        self.status_message = StatusMessage::from(format!(
+            "Terminal: {} returns to the buffer",
+            leave
+                .first()
+                .cloned()
+                .map_or_else(|| ":terminal".to_string(), commands::key_name)
+        ));
+        let mut changed = true;
+        loop {
+            if let Some(pane) = &mut self.terminal_pane {
+                pane.focused = true;
+            }
+            changed |= self.update_terminal_pane();
+            if self.terminal_pane.is_none() {
+                break;
+            }
+            if changed && self.refresh_screen().is_err() {
+                break;
+            }
+            changed = false;
+            // Output wakes the wait; once it has ended, the shell's exit is polled for.
+            let timeout = self
+                .terminal_pane
+                .as_ref()
+                .filter(|pane| pane.is_closed())
+                .map(|_| TERMINAL_POLL);
+            let key = match self.next_key_timeout(timeout) {
+                Ok(Some(key)) => key,
+                Ok(None) => {
+                    self.woken = false;
+                    continue;
+                }
+                Err(_) => break,
+            };
+            if leave.contains(&key) {
+                break;
+            }
+            if let Some(pane) = &mut self.terminal_pane {
+                if pane.send_key(key).is_err() {
+                    break;
+                }
+            }
+        }
+        if let Some(pane) = &mut self.terminal_pane {
+            pane.focused = false;
+        }
+        self.status_message = StatusMessage::from(String::new());
+    }
+    /// Paints what the shell printed, closing the pane once the shell has exited.
+    /// Returns whether the pane changed.
+    fn update_terminal_pane(&mut self) -> bool {
+        let pane = if let Some(pane) = &mut self.terminal_pane {
+            pane
+        } else {
+            return false;
+        };
+        if pane.update() {
+            return true;
+        }
+        if !pane.is_running() {
+            self.terminal_pane = None;
+            return true;
+        }
+        false
+    }
+    fn terminal_pane_width(&self) -> usize {
+        if self.terminal_pane.is_some() {
+            (self.terminal.size().width as usize / 2).min(terminal_pane::WIDTH)
+        } else {
+            0
+        }
+    }
+    fn draw_terminal_pane(&self) {
+        let pane = if let Some(pane) = &self.terminal_pane {
+            pane
+        } else {
+            return;
+        };
+        let x = (self.terminal.size().width as usize).saturating_sub(self.terminal_pane_width());
+        for (y, row) in pane.screen.rows().enumerate() {
+            Terminal::cursor_position(&Position { x, y });
+            self.terminal.set_bg_color(self.config.theme.status_bg);
+            self.terminal.set_fg_color(self.config.theme.status_fg);
+            print!("│");
+            Terminal::reset_fg_color();
+            Terminal::reset_bg_color();
+            print!("{}", row);
+        }
+    }
+    fn outline_width(&self) -> usize {
+        if self.outline.is_some() {
+            self.outline_size
+                .min(self.terminal.size().width as usize / 2)
+        } else {
+            0
+        }
+    }
+    fn draw_outline(&self) {
+        let outline = if let Some(outline) = &self.outline {
+            outline
+        } else {
+            return;
+        };
+        let width = self.outline_width();
+        let height = self.terminal.size().height as usize;
+        let x = (self.terminal.size().width as usize)
+            .saturating_sub(self.terminal_pane_width())
+            .saturating_sub(self.preview_width())
+            .saturating_sub(width);
+        let lines = outline.lines(
+            &self.document.symbols,
+            self.cursor_position.y,
+            width.saturating_sub(1),
+            height,
+        );
+        for y in 0..height {
+            Terminal::cursor_position(&Position { x, y });
+            let (line, highlighted) = lines
+                .get(y)
+                .cloned()
+                .unwrap_or_else(|| (" ".repeat(width.saturating_sub(1)), false));
+            self.terminal.set_bg_color(self.config.theme.status_bg);
+            self.terminal.set_fg_color(self.config.theme.status_fg);
+            print!("│");
+            if highlighted {
+                self.terminal.set_bg_color(self.config.theme.selection_bg);
+            }
+            print!("{}", line);
+            Terminal::reset_fg_color();
+            Terminal::reset_bg_color();
+        }
+    }
+    /// Draws frame time, queued input and memory use in the top right corner.
+    fn draw_debug_overlay(&self) {
+        if !self.debug_overlay {
+            return;
+        }
+        let queued = self
+            .input_queue
+            .len()
+            .saturating_add(usize::from(self.terminal.has_input()));
+        let last = self.frame_times.back().copied().unwrap_or_default();
+        let slowest = self.frame_times.iter().max().copied().unwrap_or_default();
+        let average = self
+            .frame_times
+            .iter()
+            .sum::<Duration>()
+            .checked_div(u32::try_from(self.frame_times.len()).unwrap_or(u32::MAX))
+            .unwrap_or_default();
+        let milliseconds = |time: Duration| format!("{:.2} ms", time.as_secs_f64() * 1000.0);
+        #[allow(clippy::cast_precision_loss)]
+        let memory = log::resident_memory().map_or_else(
+            || "?".to_string(),
+            |bytes| format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+        );
+        let lines = [
+            format!("frame  {}", milliseconds(last)),
+            format!("avg    {}", milliseconds(average)),
+            format!("max    {}", milliseconds(slowest)),
+            format!("queue  {} {}", queued, stats::plural(queued, "event")),
+            format!("memory {}", memory),
+            format!("log    {}", log::filter()),
+        ];
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0)
+            .saturating_add(2);
+        let x = (self.terminal.size().width as usize).saturating_sub(width);
+        self.terminal.set_bg_color(self.config.theme.status_bg);
+        self.terminal.set_fg_color(self.config.theme.status_fg);
+        for (y, line) in lines.iter().enumerate() {
+            Terminal::cursor_position(&Position { x, y });
+            let line: String = format!(" {} ", line).chars().take(width).collect();
+            print!("{:<width$}", line, width = width);
+        }
+        Terminal::reset_fg_color();
+        Terminal::reset_bg_color();
+    }
+    fn toggle_preview(&mut self) {
+        if self.preview.take().is_none() {
+            if self.is_markdown() {
+                self.preview = Some(Preview::default());
+            } else {
+                self.status_message = StatusMessage::error("Not a markdown buffer.".to_string());
+            }
+        }
+    }
+    /// Widens (or narrows) the outline panel, or the preview pane when the outline is
+    /// closed, by `PANEL_STEP` columns.
+    fn resize_panel(&mut self, grow: bool) {
+        let half = self.terminal.size().width as usize / 2;
+        let (name, size) = if self.outline.is_some() {
+            ("Outline", &mut self.outline_size)
+        } else if self.preview.is_some() && self.is_markdown() {
+            ("Preview", &mut self.preview_size)
+        } else {
+            self.status_message =
+                StatusMessage::error("No outline or preview to resize.".to_string());
+            return;
+        };
+        // Sizes beyond what the screen allows are brought back first, so the panel
+        // moves at once.
+        let current = (*size).min(half);
+        *size = if grow {
+            current.saturating_add(PANEL_STEP).min(half)
+        } else {
+            current.saturating_sub(PANEL_STEP).max(MIN_PANEL_WIDTH)
+        };
+        self.status_message = StatusMessage::from(format!("{} width: {}", name, *size));
+    }
+    /// Shares the columns the terminal pane leaves equally between the text and the
+    /// open outline and preview.
+    fn equalize_panels(&mut self) {
+        let outline = self.outline.is_some();
+        let preview = self.preview.is_some() && self.is_markdown();
+        let panels = usize::from(outline).saturating_add(usize::from(preview));
+        if panels == 0 {
+            self.status_message =
+                StatusMessage::error("No outline or preview to resize.".to_string());
+            return;
+        }
+        let share = (self.terminal.size().width as usize)
+            .saturating_sub(self.terminal_pane_width())
+            / panels.saturating_add(1);
+        if outline {
+            self.outline_size = share.max(MIN_PANEL_WIDTH);
+        }
+        if preview {
+            self.preview_size = share.max(MIN_PANEL_WIDTH);
+        }
+    }
+    /// Columns of the preview pane, which is only shown while the buffer is markdown.
+    fn preview_width(&self) -> usize {
+        if self.preview.is_some() && self.is_markdown() {
+            (self.terminal.size().width as usize / 2).min(self.preview_size)
+        } else {
+            0
+        }
+    }
+    fn refresh_preview(&mut self) {
+        let width = self.preview_width().saturating_sub(1);
+        let revision = (
+            self.document.file_name.clone(),
+            self.document.revision(),
+            width,
+        );
+        let preview = match &mut self.preview {
+            Some(preview) if width > 0 && preview.revision.as_ref() != Some(&revision) => preview,
+            _ => return,
+        };
+        let lines: Vec<&str> = (0..self.document.len())
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        preview.update(&lines, revision);
+    }
+    /// Draws the rendered buffer between the outline and the terminal pane, scrolled
+    /// along with the cursor.
+    fn draw_preview(&self) {
+        let width = self.preview_width();
+        let preview = match &self.preview {
+            Some(preview) if width > 0 && self.overlay.is_none() => preview,
+            _ => return,
+        };
+        let height = self.terminal.size().height as usize;
+        let x = (self.terminal.size().width as usize)
+            .saturating_sub(self.terminal_pane_width())
+            .saturating_sub(width);
+        let theme = &self.config.theme;
+        let lines = preview.lines(preview.top(self.cursor_position.y, height), height);
+        for y in 0..height {
+            Terminal::cursor_position(&Position { x, y });
+            self.terminal.set_bg_color(theme.status_bg);
+            self.terminal.set_fg_color(theme.status_fg);
+            print!("│");
+            Terminal::reset_fg_color();
+            Terminal::reset_bg_color();
+            let mut used = 0_usize;
+            for (style, text) in lines.get(y).map_or(&[][..], |line| &line.spans) {
+                match style {
+                    preview::Style::Plain => (),
+                    preview::Style::Strong => Terminal::set_bold(true),
+                    preview::Style::Emphasis => Terminal::set_italic(true),
+                    preview::Style::Code => self.terminal.set_bg_color(theme.preview_code_bg),
+                    preview::Style::Link => {
+                        self.terminal.set_fg_color(theme.url_fg);
+                        Terminal::set_underline(true);
+                    }
+                    preview::Style::Heading => {
+                        self.terminal.set_fg_color(theme.preview_heading_fg);
+                        Terminal::set_bold(true);
+                    }
+                    preview::Style::Marker => self.terminal.set_fg_color(theme.preview_marker_fg),
+                }
+                print!("{}", text);
+                used = used.saturating_add(text.chars().map(unicode::width).sum::<usize>());
+                Terminal::set_bold(false);
+                Terminal::set_italic(false);
+                Terminal::set_underline(false);
+                Terminal::reset_fg_color();
+                Terminal::reset_bg_color();
+            }
+            print!(
+                "{}",
+                " ".repeat(width.saturating_sub(1).saturating_sub(used))
+            );
+        }
+    }
+    /// Opens a side-by-side comparison of the buffer with the saved file, or with the
+    /// version committed at `HEAD`, until Esc or q.
+    fn show_diff(&mut self, against_head: bool) {
+        let old = if against_head {
+            self.document.file_name.as_deref().and_then(git::head_lines)
+        } else {
+            self.document
+                .disk_contents()
+                .map(|contents| contents.lines().map(str::to_string).collect())
+        };
+        let old = if let Some(old) = old {
+            old
+        } else {
+            self.status_message = StatusMessage::error(if against_head {
+                "File is not committed in a git repository.".to_string()
+            } else {
+                "File has not been saved.".to_string()
+            });
+            return;
+        };
+        let new = (0..self.document.len())
+            .filter_map(|y| self.document.row(y).map(|row| row.as_str().to_string()))
+            .collect();
+        let title = if against_head { "HEAD" } else { "saved file" };
+        let mut view = DiffView::new(title.to_string(), old, new, self.config.diff_algorithm);
+        if view.is_unchanged() {
+            self.status_message =
+                StatusMessage::from(format!("No differences from the {}.", title));
+            return;
+        }
+        let height = self.terminal.size().height as usize;
+        view.selected = view.row_of_buffer_line(self.cursor_position.y);
+        view.move_by(0, height);
+        self.diff_view = Some(view);
+        self.focus_diff_view();
+    }
+    /// Lets the diff view take keys: j/k and the arrows scroll both panes, n/N jump
+    /// between changes and Enter goes to the selected line of the buffer.
+    fn focus_diff_view(&mut self) {
+        let height = self.terminal.size().height as usize;
+        loop {
+            let title = match &self.diff_view {
+                Some(view) => view.title.clone(),
+                None => return,
+            };
+            self.status_message = StatusMessage::from(format!(
+                "Diff against the {}: j/k to scroll, n/N for changes, Enter to jump, q to leave",
+                title
+            ));
+            if self.refresh_screen().is_err() {
+                break;
+            }
+            let key = match self.next_key() {
+                Ok(key) => key,
+                Err(_) => break,
+            };
+            let view = if let Some(view) = &mut self.diff_view {
+                view
+            } else {
+                return;
+            };
+            #[allow(clippy::as_conversions, clippy::cast_possible_wrap)]
+            let page = height as isize;
+            match key {
+                Key::Up | Key::Char('k') => view.move_by(-1, height),
+                Key::Down | Key::Char('j') => view.move_by(1, height),
+                Key::PageUp | Key::Ctrl('b') => view.move_by(-page, height),
+                Key::PageDown | Key::Ctrl('f') => view.move_by(page, height),
+                Key::Char('n') => view.goto_change(true, height),
+                Key::Char('N') => view.goto_change(false, height),
+                Key::Char('\n') => {
+                    let y = view.buffer_line();
+                    let old_position = self.cursor_position.clone();
+                    self.jump_to(&Position { x: 0, y });
+                    self.record_jump(old_position);
+                    break;
+                }
+                Key::Esc | Key::Char('q') => break,
+                _ => (),
+            }
+        }
+        self.diff_view = None;
+        self.status_message = StatusMessage::from(String::new());
+    }
+    /// Columns of each diff view pane; a separator sits between them.
+    fn diff_pane_width(&self) -> usize {
+        (self.terminal.size().width as usize).saturating_sub(1) / 2
+    }
+    fn draw_diff_view(&self, view: &DiffView) {
+        let width = self.diff_pane_width();
+        let height = self.terminal.size().height as usize;
+        let lines = view.lines(width, height, self.document.indent.tab_width);
+        for y in 0..height {
+            Terminal::clear_current_line();
+            if let Some((old, new)) = lines.get(y) {
+                self.draw_diff_cell(old, width);
+                self.terminal.set_fg_color(self.config.theme.line_number_fg);
+                print!("│");
+                Terminal::reset_fg_color();
+                self.draw_diff_cell(new, width);
+                println!("\r");
+            } else {
+                println!("~\r");
+            }
+        }
+    }
+    /// Prints one side of a diff row padded to `width`, colored by how it changed.
+    fn draw_diff_cell(&self, cell: &Cell, width: usize) {
+        let theme = &self.config.theme;
+        let padding = " ".repeat(width.saturating_sub(cell.text.chars().count()));
+        match cell.kind {
+            diff_view::Kind::Same => print!("{}{}", cell.text, padding),
+            diff_view::Kind::Filler => {
+                self.terminal.set_bg_color(theme.color_column_bg);
+                print!("{}", padding);
+                Terminal::reset_bg_color();
+            }
+            diff_view::Kind::Removed | diff_view::Kind::Added | diff_view::Kind::Changed => {
+                self.terminal.set_fg_color(match cell.kind {
+                    diff_view::Kind::Removed => theme.removed_fg,
+                    diff_view::Kind::Added => theme.added_fg,
+                    _ => theme.modified_fg,
+                });
+                let (start, end) = cell.span.unwrap_or((0, 0));
+                let part = |from: usize, to: usize| -> String {
+                    cell.text
+                        .chars()
+                        .skip(from)
+                        .take(to.saturating_sub(from))
+                        .collect()
+                };
+                print!("{}", part(0, start));
+                self.terminal.set_bg_color(theme.selection_bg);
+                print!("{}", part(start, end));
+                Terminal::reset_bg_color();
+                print!("{}{}", part(end, usize::MAX), padding);
+                Terminal::reset_fg_color();
+            }
+        }
+    }
+    /// Asks for signature help when a call is opened or an argument started, and
+    /// again after every edit while the signature is shown.
+    fn update_signature(&mut self, key: Key) {
+        if self.mode != Mode::Insert {
+            self.signature = None;
+            return;
+        }
+        let trigger = match key {
+            Key::Char(c @ ('(' | ',')) => Some(c),
+            Key::Char(')') => {
+                self.signature = None;
+                return;
+            }
+            Key::Char(_) | Key::Backspace | Key::Delete if self.signature.is_some() => None,
+            _ => return,
+        };
+        let mut context = vec![
+            (
+                "triggerKind",
+                json::Value::from(if trigger.is_some() { 2_usize } else { 3 }),
+            ),
+            ("isRetrigger", json::Value::from(self.signature.is_some())),
+        ];
+        if let Some(c) = trigger {
+            context.push(("triggerCharacter", json::Value::from(c.to_string())));
+        }
+        self.request_at_cursor(
+            "textDocument/signatureHelp",
+            vec![("context", json::Value::object(context))],
+        );
+    }
+    /// Draws the signature above the cursor line, or below it on the top line, with the
+    /// active parameter highlighted.
+    fn draw_signature(&self, cursor: &Position) {
+        let signature = if let Some(signature) = &self.signature {
+            signature
+        } else {
+            return;
+        };
+        let width = self.terminal.size().width as usize;
+        let (before, active, after) = signature.parts();
+        let length = before
+            .chars()
+            .count()
+            .saturating_add(active.chars().count())
+            .saturating_add(after.chars().count())
+            .min(width);
+        let y = cursor
+            .y
+            .checked_sub(1)
+            .unwrap_or_else(|| cursor.y.saturating_add(1));
+        let x = cursor.x.min(width.saturating_sub(length));
+        Terminal::cursor_position(&Position { x, y });
+        let mut remaining = length;
+        for (index, part) in [before, active, after].iter().enumerate() {
+            if index == 1 {
+                self.terminal.set_bg_color(self.config.theme.selection_bg);
+            } else {
+                self.terminal.set_bg_color(self.config.theme.status_bg);
+                self.terminal.set_fg_color(self.config.theme.status_fg);
+            }
+            let shown: String = part.chars().take(remaining).collect();
+            remaining = remaining.saturating_sub(shown.chars().count());
+            print!("{}", shown);
+            Terminal::reset_fg_color();
+            Terminal::reset_bg_color();
+        }
+    }
+    fn draw_popup(&self, cursor: &Position) {
+        self.draw_signature(cursor);
+        if let Some(menu) = &self.code_actions {
+            self.draw_floating(&menu.lines(), cursor.x, cursor);
+        }
+        if let Some(menu) = &self.spell_menu {
+            self.draw_floating(&menu.lines(), cursor.x, cursor);
+        }
+        if let Some(lines) = &self.hover {
+            let width = lines
+                .iter()
+                .map(|line| line.chars().count())
+                .max()
+                .unwrap_or(0);
+            let lines: Vec<(String, bool)> = lines
+                .iter()
+                .map(|line| (format!(" {:<width$} ", line, width = width), false))
+                .collect();
+            self.draw_floating(&lines, cursor.x, cursor);
+        }
+        let popup = if let Some(popup) = &self.popup {
+            popup
+        } else {
+            return;
+        };
+        let tab_width = self.document.indent.tab_width;
+        let word_width = self.document.row(popup.start.y).map_or(0, |row| {
+            row.column(self.cursor_position.x, tab_width)
+                .saturating_sub(row.column(popup.start.x, tab_width))
+        });
+        let x = cursor.x.saturating_sub(word_width).saturating_sub(2);
+        self.draw_floating(&popup.lines(), x, cursor);
+    }
+    /// Draws a box of equally wide `(line, is_selected)` lines starting at column `x`,
+    /// below the cursor, or above it when there is no room below.
+    fn draw_floating(&self, lines: &[(String, bool)], x: usize, cursor: &Position) {
+        let width = self.terminal.size().width as usize;
+        let height = self.terminal.size().height as usize;
+        let line_width = lines
+            .first()
+            .map_or(0, |(line, _)| line.chars().count())
+            .min(width);
+        let x = x.min(width.saturating_sub(line_width));
+        let below = cursor.y.saturating_add(1);
+        let top = if below.saturating_add(lines.len()) > height && cursor.y >= lines.len() {
+            cursor.y.saturating_sub(lines.len())
+        } else {
+            below
+        };
+        for (index, (line, selected)) in lines.iter().enumerate() {
+            let y = top.saturating_add(index);
+            if y >= height {
+                break;
+            }
+            Terminal::cursor_position(&Position { x, y });
+            if *selected {
+                self.terminal.set_bg_color(self.config.theme.selection_bg);
+            } else {
+                self.terminal.set_bg_color(self.config.theme.status_bg);
+                self.terminal.set_fg_color(self.config.theme.status_fg);
+            }
+            print!("{}", line.chars().take(line_width).collect::<String>());
+            Terminal::reset_fg_color();
+            Terminal::reset_bg_color();
+        }
+    }
+    /// Stores published diagnostics on the open buffer they belong to.
+    fn receive_diagnostics(&mut self, params: &json::Value) {
+        let (uri, diagnostics) = if let Some(parsed) = diagnostics::parse(params) {
+            parsed
+        } else {
+            return;
+        };
+        let belongs = |document: &Document| {
+            document
+                .file_name
+                .as_ref()
+                .is_some_and(|file_name| lsp::uri(Path::new(file_name)) == uri)
+        };
+        if belongs(&self.document) {
+            self.document.set_diagnostics(diagnostics);
+        } else if let Some(buffer) = self
+            .buffers
+            .iter_mut()
+            .find(|buffer| belongs(&buffer.document))
+        {
+            buffer.document.set_diagnostics(diagnostics);
+        }
+    }
+    /// Moves to the next (or previous) change against the committed file.
+    fn goto_hunk(&mut self, forward: bool) {
+        let old_position = self.cursor_position.clone();
+        let diff = &self.document.git;
+        let hunk = diff
+            .next_hunk(old_position.y, forward)
+            .and_then(|index| diff.hunks.get(index).map(|hunk| (index, *hunk)));
+        if let Some((index, hunk)) = hunk {
+            let total = diff.hunks.len();
+            self.jump_to(&Position {
+                x: 0,
+                y: git::marker_line(&hunk),
+            });
+            self.record_jump(old_position);
+            self.status_message =
+                StatusMessage::from(format!("Change {} of {}", index.saturating_add(1), total));
+        } else {
+            self.status_message = StatusMessage::from("No changes.".to_string());
+        }
+    }
+    fn stage_hunk(&mut self) {
+        self.document.refresh_git_diff();
+        let y = self.cursor_position.y;
+        let (file_name, hunk) = match (&self.document.file_name, self.document.git.hunk_at(y)) {
+            (Some(file_name), Some(hunk)) => (file_name, hunk),
+            _ => {
+                self.status_message =
+                    StatusMessage::error("No change under the cursor.".to_string());
+                return;
+            }
+        };
+        let new: Vec<&str> = (hunk.new_start..hunk.new_start.saturating_add(hunk.new_len))
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        let result = git::stage_hunk(file_name, &hunk, &self.document.git.base_lines(&hunk), &new);
+        self.status_message = match result {
+            Ok(()) => StatusMessage::from("Hunk staged.".to_string()),
+            Err(error) => StatusMessage::error(format!("Could not stage hunk: {}", error)),
+        };
+        self.git_status_checked = None;
+    }
+    fn revert_hunk(&mut self) {
+        if let Some(y) = self.document.revert_hunk(self.cursor_position.y) {
+            self.jump_to(&Position { x: 0, y });
+        } else {
+            self.status_message = StatusMessage::error("No change under the cursor.".to_string());
+        }
+    }
+    /// Shows the committed lines the change under the cursor replaced, above its new
+    /// lines, in the hover popup.
+    fn preview_hunk(&mut self) {
+        self.document.refresh_git_diff();
+        let hunk = if let Some(hunk) = self.document.git.hunk_at(self.cursor_position.y) {
+            hunk
+        } else {
+            self.status_message = StatusMessage::error("No change under the cursor.".to_string());
+            return;
+        };
+        let width = (self.terminal.size().width as usize)
+            .saturating_sub(4)
+            .min(HOVER_WIDTH);
+        let mut lines: Vec<String> = self
+            .document
+            .git
+            .base_lines(&hunk)
+            .iter()
+            .map(|line| format!("-{}", line))
+            .chain(
+                (hunk.new_start..hunk.new_start.saturating_add(hunk.new_len))
+                    .filter_map(|y| self.document.row(y))
+                    .map(|row| format!("+{}", row.as_str())),
+            )
+            .map(|line| line.chars().take(width).collect())
+            .collect();
+        lines.truncate((self.terminal.size().height as usize).saturating_sub(1));
+        self.hover = Some(lines);
+    }
+    /// Loads the dictionary for `spelllang` once spell checking is on, turning spell
+    /// checking back off when there is none.
+    fn load_dictionary(&mut self) {
+        if !self.config.spell
+            || self
+                .dictionary
+                .as_ref()
+                .map(|dictionary| dictionary.lang.as_str())
+                == Some(self.config.spell_lang.as_str())
+        {
+            return;
+        }
+        self.dictionary = Dictionary::load(&self.config.spell_lang);
+        if self.dictionary.is_none() {
+            self.config.spell = false;
+            self.status_message = StatusMessage::error(format!(
+                "No dictionary found for {}.",
+                self.config.spell_lang
+            ));
+        }
+    }
+    /// The misspelled words of `row`, when spell checking is on.
+    fn misspellings(&self, row: &Row) -> Vec<(usize, usize)> {
+        match &self.dictionary {
+            Some(dictionary) if self.config.spell => spell::misspellings(
+                dictionary,
+                row,
+                self.document.file_type.as_deref(),
+                self.document.comment.as_deref(),
+            ),
+            _ => Vec::new(),
+        }
+    }
+    /// Moves to the next (or previous) misspelled word, wrapping around the buffer.
+    fn goto_misspelling(&mut self, forward: bool) {
+        self.config.spell = true;
+        self.load_dictionary();
+        if self.dictionary.is_none() {
+            return;
+        }
+        let Position { x, y } = self.cursor_position;
+        let len = self.document.len();
+        // The cursor line comes first for the words past the cursor, and last again
+        // for the words before it.
+        let lines: Vec<usize> = if forward {
+            (y..len).chain(0..=y).collect()
+        } else {
+            (0..=y).rev().chain((y..len).rev()).collect()
+        };
+        let target = lines.into_iter().enumerate().find_map(|(step, line)| {
+            let words = self.misspellings(self.document.row(line)?);
+            let word = if forward {
+                words.iter().find(|(start, _)| step > 0 || *start > x)
+            } else {
+                words.iter().rev().find(|(start, _)| step > 0 || *start < x)
+            };
+            word.map(|(start, _)| Position { x: *start, y: line })
+        });
+        if let Some(position) = target {
+            let old_position = self.cursor_position.clone();
+            self.jump_to(&position);
+            self.record_jump(old_position);
+        } else {
+            self.status_message = StatusMessage::from("No misspelled words.".to_string());
+        }
+    }
+    /// The grapheme range of the word under the cursor.
+    fn word_at_cursor(&self) -> Option<(usize, usize)> {
+        let row = self.document.row(self.cursor_position.y)?;
+        let graphemes: Vec<&str> = row.graphemes().collect();
+        let is_letter = |index: usize| {
+            graphemes
+                .get(index)
+                .is_some_and(|grapheme| grapheme.chars().all(|c| c.is_alphabetic() || c == '\''))
+        };
+        let x = self.cursor_position.x;
+        if !is_letter(x) {
+            return None;
+        }
+        let start = (0..=x).rev().take_while(|index| is_letter(*index)).last()?;
+        let end = (x..graphemes.len())
+            .take_while(|index| is_letter(*index))
+            .last()?
+            .saturating_add(1);
+        Some((start, end))
+    }
+    fn suggest_spelling(&mut self) {
+        self.config.spell = true;
+        self.load_dictionary();
+        let (dictionary, row) = match (&self.dictionary, self.document.row(self.cursor_position.y))
+        {
+            (Some(dictionary), Some(row)) => (dictionary, row),
+            _ => return,
+        };
+        let x = self.cursor_position.x;
+        let (start, end) = if let Some((start, end)) = self.word_at_cursor() {
+            (start, end)
+        } else {
+            self.status_message = StatusMessage::error("No word under the cursor.".to_string());
+            return;
+        };
+        let word: String = row
+            .graphemes()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect();
+        let suggestions = dictionary.suggest(&word);
+        if suggestions.is_empty() {
+            self.status_message = StatusMessage::error(format!("No suggestions for \"{}\".", word));
+            return;
+        }
+        self.cursor_position.x = start.min(x);
+        self.spell_menu = Some(spell::Menu::new(
+            self.cursor_position.y,
+            start,
+            end,
+            suggestions,
+        ));
+    }
+    /// Offers synonyms of the word under the cursor in the popup that offers
+    /// corrections, each with its sense's part of speech; picking one replaces the word.
+    fn look_up_synonyms(&mut self) {
+        let path = match self
+            .config
+            .thesaurus
+            .clone()
+            .or_else(|| thesaurus::find(&self.config.spell_lang))
+        {
+            Some(path) => path,
+            None => {
+                self.status_message = StatusMessage::error(format!(
+                    "No thesaurus found for {}; set `thesaurus` to a MyThes .dat file.",
+                    self.config.spell_lang
+                ));
+                return;
+            }
+        };
+        let (start, end) = if let Some((start, end)) = self.word_at_cursor() {
+            (start, end)
+        } else {
+            self.status_message = StatusMessage::error("No word under the cursor.".to_string());
+            return;
+        };
+        let word: String = self
+            .document
+            .row(self.cursor_position.y)
+            .map(|row| {
+                row.graphemes()
+                    .skip(start)
+                    .take(end.saturating_sub(start))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let meanings = match thesaurus::look_up(&path, &word) {
+            Ok(meanings) => meanings,
+            Err(error) => {
+                self.status_message =
+                    StatusMessage::error(format!("ERR: {}: {}", path.display(), error));
+                return;
+            }
+        };
+        let mut synonyms: Vec<String> = Vec::new();
+        let mut notes = Vec::new();
+        for meaning in &meanings {
+            for (synonym, note) in &meaning.synonyms {
+                let synonym = match_case(synonym, &word);
+                if synonym == word || synonyms.contains(&synonym) {
+                    continue;
+                }
+                synonyms.push(synonym);
+                notes.push(
+                    [meaning.part.as_str(), note.as_str()]
+                        .iter()
+                        .filter(|text| !text.is_empty())
+                        .copied()
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                );
+            }
+        }
+        synonyms.truncate(MAX_SYNONYMS);
+        notes.truncate(MAX_SYNONYMS);
+        if synonyms.is_empty() {
+            self.status_message = StatusMessage::error(format!("No synonyms for \"{}\".", word));
+            return;
+        }
+        self.cursor_position.x = start.min(self.cursor_position.x);
+        self.spell_menu =
+            Some(spell::Menu::new(self.cursor_position.y, start, end, synonyms).with_notes(notes));
+    }
+    fn process_spell_menu_key(&mut self, mut menu: spell::Menu, key: Key) {
+        let (y, start, end) = (menu.y, menu.start, menu.end);
+        let word = match key {
+            Key::Up | Key::BackTab | Key::Ctrl('p') | Key::Char('k') => {
+                menu.select_next(true);
+                self.spell_menu = Some(menu);
+                return;
+            }
+            Key::Down | Key::Char('\t') | Key::Ctrl('n') | Key::Char('j') => {
+                menu.select_next(false);
+                self.spell_menu = Some(menu);
+                return;
+            }
+            Key::Char('\n') => menu.take(None),
+            Key::Char(c) => c
+                .to_digit(10)
+                .and_then(|digit| usize::try_from(digit).ok())
+                .and_then(|digit| digit.checked_sub(1))
+                .and_then(|index| menu.take(Some(index))),
+            _ => None,
+        };
+        if let Some(word) = word {
+            let start = Position { x: start, y };
+            self.cursor_position =
+                self.document
+                    .replace_range(&start, &Position { x: end, y }, &word);
+        }
+    }
+    /// Adds `word`, or the word under the cursor, to the user's dictionary.
+    fn add_word_to_dictionary(&mut self, word: Option<&str>) {
+        self.config.spell = true;
+        self.load_dictionary();
+        let word = match word {
+            Some(word) => word.to_string(),
+            None => match (
+                self.word_at_cursor(),
+                self.document.row(self.cursor_position.y),
+            ) {
+                (Some((start, end)), Some(row)) => row
+                    .graphemes()
+                    .skip(start)
+                    .take(end.saturating_sub(start))
+                    .collect(),
+                _ => {
+                    self.status_message =
+                        StatusMessage::error("No word under the cursor.".to_string());
+                    return;
+                }
+            },
+        };
+        if let Some(dictionary) = &mut self.dictionary {
+            self.status_message = match dictionary.add_user_word(&word) {
+                Ok(()) => StatusMessage::from(format!("Added \"{}\" to the dictionary.", word)),
+                Err(error) => {
+                    StatusMessage::error(format!("Could not save \"{}\": {}", word, error))
+                }
+            };
+        }
+    }
+    /// Moves to the next (or previous) merge conflict, wrapping around the buffer.
+    fn goto_conflict(&mut self, forward: bool) {
+        self.document.refresh_conflicts();
+        let y = self.cursor_position.y;
+        let conflicts = &self.document.conflicts;
+        let index = if forward {
+            conflicts
+                .iter()
+                .position(|conflict| conflict.start > y)
+                .unwrap_or(0)
+        } else {
+            conflicts
+                .iter()
+                .rposition(|conflict| conflict.start < y)
+                .unwrap_or_else(|| conflicts.len().saturating_sub(1))
+        };
+        if let Some(conflict) = conflicts.get(index) {
+            let total = conflicts.len();
+            let old_position = self.cursor_position.clone();
+            self.jump_to(&Position {
+                x: 0,
+                y: conflict.start,
+            });
+            self.record_jump(old_position);
+            self.status_message =
+                StatusMessage::from(format!("Conflict {} of {}", index.saturating_add(1), total));
+        } else {
+            self.status_message = StatusMessage::from("No conflicts.".to_string());
+        }
+    }
+    fn resolve_conflict(&mut self, resolution: Resolution) {
+        if let Some(start) = self
+            .document
+            .resolve_conflict(self.cursor_position.y, resolution)
+        {
+            self.jump_to(&Position { x: 0, y: start });
+        } else {
+            self.status_message = StatusMessage::error("No conflict under the cursor.".to_string());
+        }
+    }
+    /// Fills the quickfix list with the buffer's diagnostics and jumps to the one
+    /// after (or before) the cursor, wrapping around.
+    fn goto_diagnostic(&mut self, forward: bool) {
+        let file_name = self.document.file_name.clone().unwrap_or_default();
+        let entries: Vec<quickfix::Entry> = self
+            .document
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let (y, character) = diagnostic.start;
+                let x = self
+                    .document
+                    .row(y)
+                    .map_or(0, |row| row.index_at_utf16(character));
+                quickfix::Entry {
+                    file_name: file_name.clone(),
+                    position: Position { x, y },
+                    text: format!("{}: {}", diagnostic.severity.sign(), diagnostic.message),
+                }
+            })
+            .collect();
+        let cursor = (self.cursor_position.y, self.cursor_position.x);
+        let index = if forward {
+            entries
+                .iter()
+                .position(|entry| (entry.position.y, entry.position.x) > cursor)
+                .unwrap_or(0)
+        } else {
+            entries
+                .iter()
+                .rposition(|entry| (entry.position.y, entry.position.x) < cursor)
+                .unwrap_or_else(|| entries.len().saturating_sub(1))
+        };
+        if entries.is_empty() {
+            self.status_message = StatusMessage::from("No diagnostics.".to_string());
+            return;
+        }
+        self.quickfix.set("Diagnostics", entries);
+        self.goto_quickfix(index);
+    }
+    /// Fixes what the linter finds on the cursor line, or in the whole buffer, as one
+    /// undo step. Long lines are left for the user to break.
+    fn fix_lints(&mut self, whole: bool) {
+        let lines = if whole {
+            0..self.document.len()
+        } else {
+            self.cursor_position.y..self.cursor_position.y.saturating_add(1)
+        };
+        let edits: Vec<Edit> = lines
+            .filter_map(|y| {
+                let row = self.document.row(y)?;
+                let text = lint::fix(row.as_str(), &self.document.indent)?;
+                Some(Edit {
+                    start: Position { x: 0, y },
+                    end: Position { x: row.len(), y },
+                    text,
+                })
+            })
+            .collect();
+        if edits.is_empty() {
+            self.status_message = StatusMessage::from("Nothing to fix.".to_string());
+            return;
+        }
+        self.document.apply_edits(&edits, &mut []);
+        let position = self.cursor_position.clone();
+        self.jump_to(&position);
+        self.status_message = StatusMessage::from(match edits.len() {
+            1 => "Fixed 1 line.".to_string(),
+            count => format!("Fixed {} lines.", count),
+        });
+    }
+    fn goto_quickfix(&mut self, index: usize) {
+        if self.quickfix.select(index).is_none() {
+            self.status_message = StatusMessage::error("No such quickfix entry.".to_string());
+            return;
+        }
+        self.follow_quickfix(index);
+    }
+    fn step_quickfix(&mut self, forward: bool) {
+        if self.quickfix.is_empty() {
+            self.status_message = StatusMessage::error("Quickfix list is empty.".to_string());
+        } else if self.quickfix.step(forward).is_none() {
+            self.status_message = StatusMessage::error(
+                if forward {
+                    "No more quickfix entries."
+                } else {
+                    "Already at the first quickfix entry."
+                }
+                .to_string(),
+            );
+        } else if let Some(index) = self.quickfix.current_index() {
+            self.follow_quickfix(index);
+        }
+    }
+    fn follow_quickfix(&mut self, index: usize) {
+        let (file_name, position, text) = if let Some(entry) = self.quickfix.current() {
+            (
+                entry.file_name.clone(),
+                entry.position.clone(),
+                entry.text.clone(),
+            )
+        } else {
+            return;
+        };
+        self.goto_location(&file_name, &position);
+        self.status_message = StatusMessage::from(format!(
+            "({}/{}) {}",
+            index.saturating_add(1),
+            self.quickfix.len(),
+            text
+        ));
+    }
+    /// Lists the quickfix entries in their own buffer, where Enter goes to one.
+    fn show_quickfix(&mut self) {
+        if self.quickfix.is_empty() {
+            self.status_message = StatusMessage::error("Quickfix list is empty.".to_string());
+            return;
+        }
+        let text = self.quickfix.lines().join("\n");
+        self.show_special(
+            Document::special(Special::Quickfix, "[Quickfix]", &text),
+            true,
+        );
+        let y = self
+            .quickfix
+            .current_index()
+            .map_or(0, |index| index.saturating_add(1));
+        self.jump_to(&Position { x: 0, y });
+    }
+    /// Jumps to `position` in `file_name`, opening the file if needed, and records the
+    /// jump.
+    fn goto_location(&mut self, file_name: &str, position: &Position) {
+        let from = Jump {
+            file_name: self.document.file_name.clone(),
+            position: self.cursor_position.clone(),
+        };
+        self.follow_jump(&Jump {
+            file_name: Some(file_name.to_string()).filter(|name| !name.is_empty()),
+            position: position.clone(),
+        });
+        if self.document.file_name != from.file_name || self.cursor_position != from.position {
+            self.jump_list.push(from);
+        }
+    }
+    fn show_language_servers(&mut self) {
+        let mut servers: Vec<String> = self
+            .language_servers
+            .iter()
+            .map(|(file_type, server)| match server {
+                Some(server) => format!("{}: {}", file_type, server.command),
+                None => format!("{}: not running", file_type),
+            })
+            .collect();
+        servers.sort();
+        self.status_message = StatusMessage::from(if servers.is_empty() {
+            "No language servers started.".to_string()
+        } else {
+            servers.join(" | ")
+        });
+    }
+    /// Writes the buffer to its file, as root through sudo when `sudo` is set.
+    fn write_document(&mut self, sudo: bool) -> Result<(), Error> {
+        if let Some(file_name) = &self.document.file_name {
+            let backup = self.config.backup.write(file_name);
+            // A backup beside the file needs the rights a sudo save is for.
+            if !sudo {
+                backup?;
+            }
+        }
+        if sudo {
+            self.document.save_with(&mut sudo::write)?;
+        } else {
+            let terminal = &self.terminal;
+            self.document
+                .save_with_progress(&mut |message| terminal.draw_message(message))?;
+        }
+        if let Some((file_type, uri)) = self.sync_language_server() {
+            if let Some(Some(server)) = self.language_servers.get_mut(&file_type) {
+                server.did_save(&uri);
+            }
+        }
+        self.remove_swap();
+        let position = self.cursor_position.clone();
+        self.jump_to(&position);
+        Ok(())
+    }
+    fn toggle_recording(&mut self, name: char) {
+        if !Registers::is_valid_name(name) {
+            return;
+        }
+        self.recording = Some((name, Vec::new()));
+        self.status_message = StatusMessage::from(format!("Recording @{}", name));
+    }
+    fn stop_recording(&mut self) {
+        if let Some((name, mut keys)) = self.recording.take() {
+            // Drop the `q` that ended the recording.
+            keys.pop();
+            self.registers.set_macro(name, keys);
+            self.status_message = StatusMessage::from(format!("Recorded @{}", name));
+        }
+    }
+    fn play_macro(&mut self, name: char, count: usize) {
+        let name = if name == '@' {
+            if let Some(last) = self.last_macro {
+                last
+            } else {
+                return;
+            }
+        } else {
+            name
+        };
+        if let Some(keys) = self.registers.get_macro(name) {
+            for _ in 0..count {
+                for key in keys.iter().rev() {
+                    self.input_queue.push_front(*key);
+                }
+            }
+            self.last_macro = Some(name);
+        } else {
+            self.status_message = StatusMessage::from(format!("Register @{} is empty.", name));
+        }
+    }
+    fn finish_edit(&mut self) {
+        if self.current_edit.is_empty() && self.edit_prefix.is_empty() {
+            return;
+        }
+        self.last_edit = if self.edit_prefix.is_empty() {
+            vec![Key::Char('i')]
+        } else {
+            self.edit_prefix.drain(..).collect()
+        };
+        self.last_edit.append(&mut self.current_edit);
+        self.last_edit.push(Key::Esc);
+    }
+    fn repeat_edit(&mut self, count: usize) {
+        for _ in 0..count {
+            for key in self.last_edit.iter().rev() {
+                self.input_queue.push_front(*key);
+            }
+        }
+    }
+    fn delete_chars(&mut self, count: usize) {
+        for _ in 0..count {
+            self.document.delete(&self.cursor_position);
+        }
+        self.last_edit = count_prefix(count);
+        self.last_edit.push(Key::Char('x'));
+    }
+    fn replace_chars(&mut self, c: char, count: usize) {
+        let width = self
+            .document
+            .row(self.cursor_position.y)
+            .map_or(0, Row::len);
+        if c.is_control() || self.cursor_position.x.saturating_add(count) > width {
+            return;
+        }
+        let mut position = self.cursor_position.clone();
+        for _ in 0..count {
+            self.document.delete(&position);
+            self.document.insert(&position, c);
+            position.x = position.x.saturating_add(1);
+        }
+        self.last_edit = count_prefix(count);
+        self.last_edit.push(Key::Char('r'));
+        self.last_edit.push(Key::Char(c));
+    }
+    fn push_count_digit(&mut self, c: char) -> bool {
+        if let Some(digit) = c.to_digit(10) {
+            if digit > 0 || self.count > 0 {
+                self.count = self.count.saturating_mul(10).saturating_add(digit as usize);
+                return true;
+            }
+        }
+        false
+    }
+    fn process_normal_key(&mut self, c: char) {
+        if self.pending.is_empty() && self.push_count_digit(c) {
+            return;
+        }
+        self.pending.push(c);
+        let recording = self.recording.is_some();
+        let keys: Vec<Key> = self.pending.chars().map(Key::Char).collect();
+        let found = mode_keys::find(KeyMode::Normal, &keys, |action| {
+            action != KeyAction::StopRecording || recording
+        });
+        // Sequences bound to commands come first, so that they can take over keys.
+        let action = match (self.config.bindings.sequence(&self.pending), found) {
+            (Lookup::Found(command), _) => KeyAction::Command(command),
+            (_, Lookup::Found(action)) => action,
+            (Lookup::Pending, _) | (_, Lookup::Pending) => return,
+            (Lookup::Unknown, Lookup::Unknown) => {
+                self.pending.clear();
+                self.count = 0;
+                return;
+            }
+        };
+        let count = cmp::max(self.count, 1);
+        let keys: Vec<char> = std::mem::take(&mut self.pending).chars().collect();
+        self.count = 0;
+        let last = keys.last().copied().unwrap_or(c);
+        match (action, keys.as_slice()) {
+            (KeyAction::Command(command), _) => self.run_command(command),
+            (KeyAction::Move, _) => {
+                if let Some(key) = motion_key(last) {
+                    self.move_cursor_times(key, count);
+                }
+            }
+            (KeyAction::Insert, _) => {
+                self.edit_prefix.clear();
+                self.mode = Mode::Insert;
+            }
+            (KeyAction::Visual, _) => {
+                self.document
+                    .anchors
+                    .set(Anchor::Selection, &self.cursor_position);
+                self.mode = Mode::Visual;
+            }
+            (KeyAction::DeleteChar, _) => self.delete_chars(count),
+            (KeyAction::Repeat, _) => self.repeat_edit(count),
+            (KeyAction::ReplaceChar, _) => self.replace_chars(last, count),
+            (KeyAction::Delete, _) => self.delete_motion(last, count),
+            (KeyAction::Change, _) => self.change_motion(last, count),
+            (KeyAction::DeleteObject | KeyAction::ChangeObject, [operator, scope, object]) => {
+                self.apply_text_object(*operator, *scope == 'i', *object, count);
+            }
+            (KeyAction::SurroundObject, [_, _, scope, object, c]) => {
+                self.surround_text_object(*scope == 'i', *object, *c);
+                self.last_edit = keys.iter().map(|c| Key::Char(*c)).collect();
+            }
+            (KeyAction::DeleteSurround, [_, _, object]) => {
+                self.delete_surround(*object, None);
+                self.last_edit = keys.iter().map(|c| Key::Char(*c)).collect();
+            }
+            (KeyAction::ChangeSurround, [_, _, object, c]) => {
+                if let Some(delimiters) = self.surround_delimiters(*c) {
+                    self.delete_surround(*object, Some(&delimiters));
+                }
+                self.last_edit = keys.iter().map(|c| Key::Char(*c)).collect();
+            }
+            (KeyAction::SetMark, _) => self.set_mark(last),
+            (KeyAction::GotoMarkLine, _) => self.goto_mark(last, false),
+            (KeyAction::GotoMark, _) => self.goto_mark(last, true),
+            (KeyAction::StopRecording, _) => self.stop_recording(),
+            (KeyAction::Record, _) => self.toggle_recording(last),
+            (KeyAction::PlayMacro, _) => self.play_macro(last, count),
+            _ => (),
+        }
+    }
+    fn process_visual_key(&mut self, c: char) {
+        if self.pending.is_empty() && self.push_count_digit(c) {
+            return;
+        }
+        self.pending.push(c);
+        let keys: Vec<Key> = self.pending.chars().map(Key::Char).collect();
+        let action = match mode_keys::find(KeyMode::Visual, &keys, |_| true) {
+            Lookup::Found(action) => action,
+            Lookup::Pending => return,
+            Lookup::Unknown => {
+                self.pending.clear();
+                self.count = 0;
+                return;
+            }
+        };
+        let count = cmp::max(self.count, 1);
+        let keys: Vec<char> = std::mem::take(&mut self.pending).chars().collect();
+        self.count = 0;
+        let last = keys.last().copied().unwrap_or(c);
+        match (action, keys.as_slice()) {
+            (KeyAction::Move, _) => {
+                if let Some(key) = motion_key(last) {
+                    self.move_cursor_times(key, count);
+                }
+            }
+            (KeyAction::SelectObject, [scope, object]) => {
+                self.select_text_object(*scope == 'i', *object);
+            }
+            (KeyAction::SurroundSelection, _) => {
+                let (start, end) = self.selection();
+                self.add_surround(&start, &end, last);
+                self.mode = Mode::Normal;
+            }
+            (KeyAction::FilterSelection, _) => self.filter_command(),
+            (KeyAction::CopySelection, _) => self.copy(false),
+            (KeyAction::CopySelectionHtml, _) => self.copy(true),
+            (KeyAction::SwapSelectionEnds, _) => {
+                let anchor = self.selection_anchor();
+                self.document
+                    .anchors
+                    .set(Anchor::Selection, &self.cursor_position);
+                self.cursor_position = anchor;
+            }
+            (KeyAction::DeleteSelection | KeyAction::ChangeSelection, _) => {
+                let (start, end) = self.selection();
+                self.document.delete_range(&start, &end);
+                self.jump_to(&start);
+                self.mode = if action == KeyAction::ChangeSelection {
+                    self.edit_prefix.clear();
+                    Mode::Insert
+                } else {
+                    Mode::Normal
+                };
+            }
+            _ => (),
+        }
+    }
+    /// The end of the visual selection the cursor does not move, which follows edits
+    /// to the text before it.
+    fn selection_anchor(&self) -> Position {
+        self.document
+            .anchors
+            .get(Anchor::Selection)
+            .cloned()
+            .unwrap_or_else(|| self.cursor_position.clone())
+    }
+    /// Returns the visual selection as an ordered range with the end exclusive.
+    fn selection(&self) -> (Position, Position) {
+        let anchor = self.selection_anchor();
+        let cursor = &self.cursor_position;
+        let (start, last) = if (anchor.y, anchor.x) <= (cursor.y, cursor.x) {
+            (anchor.clone(), cursor.clone())
+        } else {
+            (cursor.clone(), anchor.clone())
+        };
+        let width = self.document.row(last.y).map_or(0, Row::len);
+        let end = if last.x < width || last.y.saturating_add(1) >= self.document.len() {
+            Position {
+                x: cmp::min(last.x.saturating_add(1), width),
+                y: last.y,
+            }
+        } else {
+            Position {
+                x: 0,
+                y: last.y.saturating_add(1),
+            }
+        };
+        (start, end)
+    }
+    fn select_text_object(&mut self, inner: bool, object: char) {
+        if let Some((start, end)) =
+            text_object::find(&self.document, &self.cursor_position, object, inner)
+        {
+            if start == end {
+                return;
+            }
+            let last = if end.x > 0 {
+                Position {
+                    x: end.x.saturating_sub(1),
+                    y: end.y,
+                }
+            } else {
+                let y = end.y.saturating_sub(1);
+                Position {
+                    x: self.document.row(y).map_or(0, Row::len),
+                    y,
+                }
+            };
+            self.document.anchors.set(Anchor::Selection, &start);
+            self.cursor_position = last;
+        }
+    }
+    fn apply_text_object(&mut self, operator: char, inner: bool, object: char, count: usize) {
+        let range = text_object::find(&self.document, &self.cursor_position, object, inner);
+        if let Some((start, end)) = range {
+            self.document.delete_range(&start, &end);
+            self.jump_to(&start);
+            let mut edit = count_prefix(count);
+            edit.push(Key::Char(operator));
+            edit.push(Key::Char(if inner { 'i' } else { 'a' }));
+            edit.push(Key::Char(object));
+            if operator == 'c' {
+                self.edit_prefix = edit;
+                self.mode = Mode::Insert;
+            } else {
+                self.last_edit = edit;
+            }
+        }
+    }
+    fn surround_delimiters(&mut self, c: char) -> Option<(String, String)> {
+        if c == 't' {
+            let tag = self.prompt("Tag: ", None, |_, _, _| {}).unwrap_or(None)?;
+            surround::tag_delimiters(&tag)
+        } else {
+            surround::delimiters(c)
+        }
+    }
+    fn add_surround(&mut self, start: &Position, end: &Position, c: char) {
+        if let Some((open, close)) = self.surround_delimiters(c) {
+            let edits = [
+                Edit {
+                    start: start.clone(),
+                    end: start.clone(),
+                    text: open,
+                },
+                Edit {
+                    start: end.clone(),
+                    end: end.clone(),
+                    text: close,
+                },
+            ];
+            self.document.apply_edits(&edits, &mut []);
+            self.jump_to(start);
+        }
+    }
+    fn surround_text_object(&mut self, inner: bool, object: char, c: char) {
+        if let Some((start, end)) =
+            text_object::find(&self.document, &self.cursor_position, object, inner)
+        {
+            self.add_surround(&start, &end, c);
+        }
+    }
+    fn delete_surround(&mut self, object: char, replacement: Option<&(String, String)>) {
+        if object != 't' && surround::delimiters(object).is_none() {
+            return;
+        }
+        let inner = text_object::find(&self.document, &self.cursor_position, object, true);
+        let outer = text_object::find(&self.document, &self.cursor_position, object, false);
+        if let (Some((inner_start, inner_end)), Some((outer_start, outer_end))) = (inner, outer) {
+            let (open, close) = replacement.cloned().unwrap_or_default();
+            let edits = [
+                Edit {
+                    start: outer_start.clone(),
+                    end: inner_start,
+                    text: open,
+                },
+                Edit {
+                    start: inner_end,
+                    end: outer_end,
+                    text: close,
+                },
+            ];
+            self.document.apply_edits(&edits, &mut []);
+            self.jump_to(&outer_start);
+        } else {
+            self.status_message = StatusMessage::error(format!("No surrounding {} found.", object));
+        }
+    }
+    fn change_motion(&mut self, c: char, count: usize) {
+        if c == 'c' {
+            let y = self.cursor_position.y;
+            let width = self.document.row(y).map_or(0, Row::len);
+            self.document
+                .delete_range(&Position { x: 0, y }, &Position { x: width, y });
+            self.jump_to(&Position { x: 0, y });
+        } else if motion_key(c).is_some() {
+            self.delete_motion(c, count);
+        } else {
+            return;
+        }
+        self.edit_prefix = count_prefix(count);
+        self.edit_prefix.push(Key::Char('c'));
+        self.edit_prefix.push(Key::Char(c));
+        self.mode = Mode::Insert;
+    }
+    /// Overwrites the nibble or character under the cursor in hex mode.
+    fn hex_edit(&mut self, c: char) {
+        let Position { x, y } = self.cursor_position;
+        let row_start = y.saturating_mul(hex::BYTES_PER_ROW);
+        match hex::column_at(x) {
+            Some(hex::Column::Hex { byte, high }) => {
+                let index = row_start.saturating_add(byte);
+                if let (Some(digit), Some(old)) = (c.to_digit(16), self.document.byte(index)) {
+                    let digit = u8::try_from(digit).unwrap_or_default();
+                    let value = if high {
+                        (old & 0x0F) | (digit << 4)
+                    } else {
+                        (old & 0xF0) | digit
+                    };
+                    self.document.set_byte(index, value);
+                    self.cursor_position.x = if high {
+                        x.saturating_add(1)
+                    } else {
+                        hex::hex_column(byte.saturating_add(1))
+                    };
+                }
+            }
+            Some(hex::Column::Ascii { byte }) => {
+                let index = row_start.saturating_add(byte);
+                if c.is_ascii() && !c.is_ascii_control() && self.document.byte(index).is_some() {
+                    self.document
+                        .set_byte(index, u8::try_from(c).unwrap_or_default());
+                    self.cursor_position.x = x.saturating_add(1);
+                }
+            }
+            None => (),
+        }
+        let position = self.cursor_position.clone();
+        self.jump_to(&position);
+    }
+    /// Moves `count` times; with `soft_tab_motion`, Left and Right in leading spaces
+    /// count a whole indent level as one move.
+    fn move_cursor_times(&mut self, key: Key, count: usize) {
+        for _ in 0..count {
+            for _ in 0..self.soft_tab_width(key) {
+                self.move_cursor(key);
+            }
+        }
+    }
+    /// How many characters one move of `key` crosses from the cursor.
+    fn soft_tab_width(&self, key: Key) -> usize {
+        let Position { x, y } = self.cursor_position;
+        let indent = &self.document.indent;
+        match (key, self.document.row(y)) {
+            (Key::Left, Some(row)) if self.config.soft_tab_motion => {
+                indent.soft_tab_before(row.as_str(), x)
+            }
+            (Key::Right, Some(row)) if self.config.soft_tab_motion => {
+                indent.soft_tab_after(row.as_str(), x)
+            }
+            _ => 1,
+        }
+    }
+    fn delete_motion(&mut self, c: char, count: usize) {
+        let start = self.cursor_position.clone();
+        match c {
+            'd' | 'j' | 'k' => {
+                // As in Vim, `dk` on the first line and `dj` on the last fail rather
+                // than take the current line alone.
+                let no_line_below = start.y.saturating_add(1) >= self.document.len();
+                if (c == 'k' && start.y < count) || (c == 'j' && no_line_below) {
+                    return;
+                }
+                let (first, lines) = match c {
+                    'd' => (start.y, count),
+                    'j' => (start.y, count.saturating_add(1)),
+                    _ => (start.y.saturating_sub(count), count.saturating_add(1)),
+                };
+                self.document.delete_rows(first, lines);
+                self.jump_to(&Position { x: 0, y: first });
+            }
+            _ => {
+                if let Some(key) = motion_key(c) {
+                    self.move_cursor_times(key, count);
+                    let end = self.cursor_position.clone();
+                    let (from, to) = if (end.y, end.x) < (start.y, start.x) {
+                        (end, start)
+                    } else {
+                        (start, end)
+                    };
+                    self.document.delete_range(&from, &to);
+                    self.jump_to(&from);
+                } else {
+                    return;
+                }
+            }
+        }
+        self.last_edit = count_prefix(count);
+        self.last_edit.push(Key::Char('d'));
+        self.last_edit.push(Key::Char(c));
+    }
+    fn process_keypress(&mut self) -> Result<(), std::io::Error> {
+        let timeout = self.idle_timeout();
+        let pressed_key = if let Some(key) = self.next_key_timeout(timeout)? {
+            key
+        } else if let Some(position) = self.click.take() {
+            self.on_click(&position);
+            return Ok(());
+        } else if let Some(text) = self.paste.take() {
+            self.last_input = Instant::now();
+            self.paste_text(&text);
+            return Ok(());
+        } else if let Some(focused) = self.focus.take() {
+            self.on_focus(focused);
+            return Ok(());
+        } else if std::mem::take(&mut self.woken) {
+            // The next pass of the main loop picks up what arrived.
+            return Ok(());
+        } else {
+            self.on_idle();
+            return Ok(());
+        };
+        let rejected_edits = self.document.rejected_edits();
+        self.last_input = Instant::now();
+        let hint_visible = std::mem::take(&mut self.hint_visible);
+        if self.overlay.take().is_some() && !hint_visible || self.hover.take().is_some() {
+            return Ok(());
+        }
+        if let Some(menu) = self.code_actions.take() {
+            self.process_code_action_key(menu, pressed_key);
+            return Ok(());
+        }
+        if let Some(menu) = self.spell_menu.take() {
+            self.process_spell_menu_key(menu, pressed_key);
+            return Ok(());
+        }
+        if let (Some(typed), Mode::Insert) = (self.char_input.take(), self.mode) {
+            self.continue_char_input(typed, pressed_key);
+            self.scroll();
+            return Ok(());
+        }
+        let insert_action = if self.mode == Mode::Insert {
+            mode_keys::first_key(KeyMode::Insert, pressed_key)
+        } else {
+            None
+        };
+        if insert_action == Some(KeyAction::CharInput) {
+            self.char_input = Some(String::new());
+            self.status_message =
+                StatusMessage::from("^V (u<hex> for a code point, or a digraph)".to_string());
+            return Ok(());
+        }
+        let cycle_key = match insert_action {
+            Some(KeyAction::NextWord) => Some(false),
+            // Ctrl-P opens the command palette in the default keymap until a cycle starts.
+            Some(KeyAction::PreviousWord)
+                if self.word_cycle.is_some() || self.config.keymap == Keymap::Vim =>
+            {
+                Some(true)
+            }
+            _ => None,
+        };
+        if let Some(backward) = cycle_key {
+            self.cycle_word_completion(backward);
+            self.scroll();
+            return Ok(());
+        }
+        self.word_cycle = None;
+        if self.mode == Mode::Insert && self.process_popup_key(pressed_key) {
+            self.scroll();
+            return Ok(());
+        }
+        if self.mode == Mode::Insert
+            && (insert_action == Some(KeyAction::SnippetStop)
+                && self.snippet_tab(pressed_key == Key::BackTab)
+                || self.clear_snippet_placeholder(pressed_key))
+        {
+            self.scroll();
+            return Ok(());
+        }
+        if self.mode == Mode::Insert
+            && matches!(pressed_key, Key::Char(_) | Key::Backspace | Key::Delete)
+        {
+            self.current_edit.push(pressed_key);
+        } else {
+            self.finish_edit();
+        }
+        let action = self
+            .document
+            .special
+            .filter(|_| self.pending.is_empty())
+            .and_then(|special| special.action_for(pressed_key));
+        if let Some(action) = action {
+            self.run_special_action(action);
+            return Ok(());
+        }
+        // Plain characters belong to the editing modes; every other key may be bound.
+        let command = match pressed_key {
+            Key::Char(_) => None,
+            // Vim's normal mode keys, such as Ctrl-Y to scroll, come before the bindings.
+            key if self.config.keymap == Keymap::Vim && self.mode != Mode::Insert => {
+                match mode_keys::find(KeyMode::Normal, &[key], |_| true) {
+                    Lookup::Found(KeyAction::Command(command)) => Some(command),
+                    _ => self.config.bindings.command_for(key),
+                }
+            }
+            key => self.config.bindings.command_for(key),
+        };
+        // Plugins bind keys the config leaves free.
+        let plugin_command = match command {
+            Some(_) => None,
+            None => self.plugins.command_for(pressed_key),
+        };
+        match pressed_key {
+            _ if command == Some(Command::Quit) => {
+                if self.quit_times > 0 && self.any_dirty() {
+                    self.status_message = StatusMessage::from(format!(
+                        "WARNING! File has unsaved changes. Press {} {} more times to quit.",
+                        commands::key_name(pressed_key),
+                        self.quit_times
+                    ));
+                    self.quit_times -= 1;
+                    return Ok(());
+                }
+                self.should_quit = true
+            }
+            _ if command.is_some() => {
+                if let Some(command) = command {
+                    self.run_command(command);
+                }
+            }
+            _ if plugin_command.is_some() => {
+                if let Some(command) = plugin_command {
+                    self.execute_command(&command);
+                }
+            }
+            Key::Esc => {
+                self.mode = Mode::Normal;
+                self.pending.clear();
+                self.count = 0;
+            }
+            Key::Char(c) if self.mode == Mode::Normal => self.process_normal_key(c),
+            Key::Char(c) if self.mode == Mode::Visual => self.process_visual_key(c),
+            Key::Char(c) if self.document.is_binary() => self.hex_edit(c),
+            Key::Char('\t') => {
+                self.expand_abbreviation();
+                let column = self.document.row(self.cursor_position.y).map_or(0, |row| {
+                    row.column(self.cursor_position.x, self.document.indent.tab_width)
+                });
+                let text = self.document.indent.text_at(column);
+                let end = self.document.insert_str(&self.cursor_position, &text);
+                if end != self.cursor_position {
+                    self.cursor_position = end;
+                }
+            }
+            Key::Char('\n') if self.is_markdown() => self.markdown_newline(),
+            Key::Char(c) => {
+                if !autocomplete::is_word_char(c.encode_utf8(&mut [0; 4])) {
+                    self.expand_abbreviation();
+                }
+                self.document.insert(&self.cursor_position, c);
+                self.move_cursor(Key::Right);
+                // A bare `/` is more often division than the root directory.
+                if c == '/'
+                    && self
+                        .path_before_cursor()
+                        .is_some_and(|(_, dir, _)| dir != "/")
+                {
+                    self.start_path_completion();
+                }
+            }
+            Key::Delete => self.document.delete(&self.cursor_position),
+            Key::Backspace => {
+                // In leading spaces, back to the previous indent stop.
+                let count = self.document.row(self.cursor_position.y).map_or(1, |row| {
+                    self.document
+                        .indent
+                        .soft_tab_before(row.as_str(), self.cursor_position.x)
+                });
+                for _ in 0..count {
+                    if self.cursor_position.x > 0 || self.cursor_position.y > 0 {
+                        self.move_cursor(Key::Left);
+                        self.document.delete(&self.cursor_position);
+                    }
+                }
+            }
+            Key::Up
+            | Key::Left
+            | Key::Down
+            | Key::Right
+            | Key::PageUp
+            | Key::PageDown
+            | Key::End
+            | Key::Home => {
+                let count = cmp::max(self.count, 1);
+                self.count = 0;
+                self.move_cursor_times(pressed_key, count);
+            }
+            _ => (),
+        }
+        if self.mode != Mode::Insert {
+            self.document.end_undo_step();
+        }
+        self.update_popup();
+        self.update_signature(pressed_key);
+        self.scroll();
+        if self.document.rejected_edits() != rejected_edits {
+            self.status_message = StatusMessage::from(
+                "WARNING! Buffer is read-only (:set noreadonly to edit).".to_string(),
+            );
+        }
+        if self.last_watch.elapsed() >= WATCH_INTERVAL {
+            self.check_disk_changes();
+        }
+        if self.swap_is_stale() && self.last_swap.elapsed() >= SWAP_INTERVAL {
+            self.write_swap();
+        }
+        if self.last_journal.elapsed() >= JOURNAL_INTERVAL && self.journal_is_stale() {
+            self.write_journal();
+        }
+        if self.quit_times < self.config.quit_times {
+            self.quit_times = self.config.quit_times;
+            self.status_message = StatusMessage::from(String::new());
+        }
+        Ok(())
+    }
+    /// Inserts pasted text at the cursor as it is, without indenting or pairing.
+    fn paste_text(&mut self, text: &str) {
+        if self.document.is_binary() {
+            return;
+        }
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.cursor_position = self.document.insert_block(&self.cursor_position, &text);
+        self.scroll();
+    }
+    /// Starts animating the view from line `from`, where it was shown before the last
+    /// key, when `smoothscroll` is on and the key moved it by half a screen or more, or
+    /// while an animation is already running. Over SSH, or when frames already take longer
+    /// than the frame interval, the view jumps as before.
+    fn animate_scroll(&mut self, from: usize) {
+        let duration = match self.config.smooth_scroll {
+            Some(duration) => duration,
+            None => return,
+        };
+        let height = self.text_height();
+        let distance = from.abs_diff(self.offset.y);
+        if distance == 0 {
+            self.scroll_animation = None;
+            return;
+        }
+        if distance < height / 2 && self.scroll_animation.is_none()
+            || !self.document.folds.is_empty()
+            || self.diff_view.is_some()
+            || terminal::is_remote()
+            || self.frames_are_slow()
+        {
+            return;
+        }
+        self.scroll_animation = Some(ScrollAnimation {
+            from,
+            start: Instant::now(),
+            duration,
+        });
+    }
+    /// Whether recent frames took longer on average than the frame interval.
+    fn frames_are_slow(&self) -> bool {
+        self.frame_times
+            .iter()
+            .sum::<Duration>()
+            .checked_div(u32::try_from(self.frame_times.len()).unwrap_or(u32::MAX))
+            .is_some_and(|average| average > FRAME_INTERVAL)
+    }
+    /// The top line to draw while a scroll animation runs, easing out towards
+    /// `offset.y`; ends the animation once it has arrived.
+    fn animated_top(&mut self) -> Option<usize> {
+        let animation = self.scroll_animation.as_ref()?;
+        let total = animation.duration.as_millis();
+        let remaining = total.saturating_sub(animation.start.elapsed().as_millis());
+        if remaining == 0 || animation.from == self.offset.y {
+            self.scroll_animation = None;
+            return None;
+        }
+        let distance = animation.from.abs_diff(self.offset.y);
+        // Quadratic ease-out: quick at first, slowing into place.
+        let left = u128::try_from(distance)
+            .unwrap_or(u128::MAX)
+            .saturating_mul(remaining.saturating_mul(remaining))
+            .checked_div(total.saturating_mul(total))
+            .unwrap_or_default();
+        let left = usize::try_from(left).unwrap_or(distance);
+        Some(if animation.from < self.offset.y {
+            self.offset.y.saturating_sub(left)
+        } else {
+            self.offset.y.saturating_add(left)
+        })
+    }
+    fn scroll(&mut self) {
+        let Position { x, y } = self.cursor_position;
+        let width = self.text_width();
+        let height = self.text_height();
+        if self.writing.is_some() {
+            // Typewriter scrolling: the text moves, the cursor line stays in the middle.
+            self.offset.y = self.top_line_for(&self.cursor_position.clone(), height / 2);
+            self.offset.x = 0;
+            return;
+        }
+        let tab_width = self.document.indent.tab_width;
+        let margin = self.scroll_margin();
+        // The last line that must stay on screen below the cursor.
+        let below = y
+            .saturating_add(margin)
+            .min(self.document.len().saturating_sub(1))
+            .max(y);
+        if self.wraps() || !self.document.folds.is_empty() {
+            // Screen lines no longer follow buffer lines one to one.
+            let top = self.document.folds.visible_line(y.saturating_sub(margin));
+            let target = if below == y {
+                self.cursor_position.clone()
+            } else {
+                Position {
+                    x: self.document.row(below).map_or(0, Row::len),
+                    y: below,
+                }
+            };
+            if top < self.offset.y {
+                self.offset.y = top;
+            } else if self.screen_lines_between(self.offset.y, &target) >= height {
+                self.offset.y = self.top_line_for(&target, height.saturating_sub(1));
+            }
+            if self.wraps() && self.column_delimiter().is_none() {
+                self.offset.x = 0;
+                return;
+            }
+        } else if y.saturating_sub(margin) < self.offset.y {
+            self.offset.y = y.saturating_sub(margin);
+        } else if below >= self.offset.y.saturating_add(height) {
+            self.offset.y = below.saturating_sub(height).saturating_add(1);
+        }
+        if let Some(delimiter) = self.column_delimiter() {
+            // Every row scrolls by the same screen columns so the columns stay lined up.
+            self.refresh_column_view();
+            self.offset.x = 0;
+            let column = self
+                .document
+                .row(y)
+                .map_or(0, |row| self.column_view.column(row.as_str(), delimiter, x));
+            let view = &mut self.column_view;
+            if column < view.offset {
+                view.offset = column;
+            } else if column >= view.offset.saturating_add(width) {
+                view.offset = column.saturating_sub(width).saturating_add(1);
+            }
+            return;
+        }
+        let offset = &mut self.offset;
+
+        if x < offset.x {
+            offset.x = x;
+        } else if let Some(row) = self.document.row(y) {
+            let column = row.column(x, tab_width);
+            if column >= row.column(offset.x, tab_width).saturating_add(width) {
+                offset.x = row
+                    .index_at_column(column.saturating_sub(width), tab_width)
+                    .saturating_add(1);
+            }
+        }
+    }
+    /// Lines of context kept above and below the cursor, as set by `scrolloff` but no
+    /// more than fits.
+    fn scroll_margin(&self) -> usize {
+        let height = self.text_height();
+        self.config.scroll_off.min(height.saturating_sub(1) / 2)
+    }
+    /// Scrolls the text by one line per count (up when `down`, as Vim's Ctrl-E), moving
+    /// the cursor only as far as needed to keep it on screen.
+    fn scroll_view(&mut self, down: bool) {
+        let count = cmp::max(self.count, 1);
+        self.count = 0;
+        let last_line = self.document.len().saturating_sub(1);
+        for _ in 0..count {
+            self.offset.y = if down {
+                self.document.folds.next_line(self.offset.y).min(last_line)
+            } else {
+                self.document.folds.previous_line(self.offset.y)
+            };
+        }
+        let margin = self.scroll_margin();
+        let first = self.offset.y;
+        let last = self.visible_lines().last().map_or(first, |(y, _, _)| *y);
+        // The margins give way at the ends of the buffer.
+        let top = if first == 0 {
+            0
+        } else {
+            first.saturating_add(margin).min(last)
+        };
+        let bottom = if last >= last_line {
+            last
+        } else {
+            last.saturating_sub(margin).max(top)
+        };
+        let y = self
+            .document
+            .folds
+            .visible_line(self.cursor_position.y.clamp(top, bottom));
+        if y != self.cursor_position.y {
+            let width = self.document.row(y).map_or(0, Row::len);
+            self.cursor_position = Position {
+                x: self.cursor_position.x.min(width),
+                y,
+            };
+        }
+    }
+    /// Scrolls so the cursor line is `screen_row` lines from the top of the screen, or as
+    /// close as the start of the buffer allows.
+    fn scroll_cursor_to(&mut self, screen_row: usize) {
+        self.offset.y = self.top_line_for(&self.cursor_position, screen_row);
+    }
+    /// Counts the screen lines from the top of row `from` down to the line holding `to`.
+    fn screen_lines_between(&self, from: usize, to: &Position) -> usize {
+        let folds = &self.document.folds;
+        let to_y = folds.visible_line(to.y);
+        let mut rows = 0_usize;
+        let mut y = folds.visible_line(from);
+        while y < to_y {
+            let lines = match self.document.row(y) {
+                Some(_) if folds.at(y).is_some() => 1,
+                Some(row) => self.row_segments(row).len(),
+                None => 0,
+            };
+            rows = rows.saturating_add(lines);
+            y = folds.next_line(y);
+        }
+        let within = match self.document.row(to_y) {
+            Some(row) if folds.at(to_y).is_none() => self
+                .row_segments(row)
+                .iter()
+                .skip(1)
+                .take_while(|(start, _)| *start <= to.x)
+                .count(),
+            _ => 0,
+        };
+        rows.saturating_add(within)
+    }
+    /// The top line that puts the line holding `to` `screen_row` screen lines down, or
+    /// as close to that as the start of the buffer allows.
+    fn top_line_for(&self, to: &Position, screen_row: usize) -> usize {
+        let folds = &self.document.folds;
+        let mut top = folds.visible_line(to.y);
+        while top > 0 {
+            let above = folds.previous_line(top);
+            if self.screen_lines_between(above, to) > screen_row {
+                break;
+            }
+            top = above;
+        }
+        top
+    }
+    /// The grapheme ranges of `row` shown on successive screen lines: the whole row
+    /// split to the text width when wrapping, otherwise the horizontally scrolled slice.
+    /// Whether long rows wrap: never in a large file, where wrapping every row would
+    /// cost too much.
+    fn wraps(&self) -> bool {
+        self.window.wrap && !self.document.large
+    }
+    fn row_segments(&self, row: &Row) -> Vec<(usize, usize)> {
+        let tab_width = self.document.indent.tab_width;
+        let width = self.text_width().max(1);
+        if self.column_delimiter().is_some() {
+            // Column view draws whole rows, clipped to the screen as one.
+            return vec![(0, row.len())];
+        }
+        if !self.wraps() {
+            let start = self.offset.x;
+            let end = row.index_at_column(
+                row.column(start, tab_width).saturating_add(width),
+                tab_width,
+            );
+            return vec![(start, end)];
+        }
+        let mut segments = Vec::new();
+        let mut start = 0;
+        loop {
+            let end = row
+                .index_at_column(
+                    row.column(start, tab_width).saturating_add(width),
+                    tab_width,
+                )
+                .max(start.saturating_add(1))
+                .min(row.len());
+            segments.push((start, end));
+            if end >= row.len() {
+                return segments;
+            }
+            start = end;
+        }
+    }
+    /// The document lines visible on screen, as `(y, start, end)` grapheme ranges.
+    fn visible_lines(&self) -> Vec<(usize, usize, usize)> {
+        let height = self.text_height();
+        let mut lines = Vec::new();
+        let folds = &self.document.folds;
+        let mut y = folds.visible_line(self.offset.y);
+        while lines.len() < height {
+            let row = if let Some(row) = self.document.row(y) {
+                row
+            } else {
+                break;
+            };
+            // A closed fold shows only the start of its first line.
+            let segments = self.row_segments(row);
+            let shown = if folds.at(y).is_some() {
+                1
+            } else {
+                segments.len()
+            };
+            for (start, end) in segments.into_iter().take(shown) {
+                lines.push((y, start, end));
+            }
+            y = folds.next_line(y);
+        }
+        lines.truncate(height);
+        lines
+    }
+    /// Width of the line number column, including its trailing space.
+    fn gutter_width(&self) -> usize {
+        self.writing_margin()
+            .saturating_add(self.line_number_width())
+            .saturating_add(self.sign_column_width())
+    }
+    /// The blank columns left of the text in writing mode, which center it between
+    /// the panes that stay.
+    fn writing_margin(&self) -> usize {
+        if self.writing.is_none() {
+            return 0;
+        }
+        // The outline is closed in writing mode.
+        let width = (self.terminal.size().width as usize).saturating_sub(self.side_width());
+        width.saturating_sub(self.config.writing_width) / 2
+    }
+    /// Turns writing mode on or off: text wrapped in a centered column of
+    /// `writingwidth`, typewriter scrolling, and no line numbers, signs or side panels.
+    fn toggle_writing(&mut self) {
+        if let Some(saved) = self.writing.take() {
+            self.window.line_numbers = saved.line_numbers;
+            self.window.wrap = saved.wrap;
+            self.config.scrollbar = saved.scrollbar;
+            self.config.minimap = saved.minimap;
+            if saved.outline && self.outline.is_none() {
+                self.outline = Some(Outline::default());
+            }
+            self.status_message = StatusMessage::from("Writing mode off.".to_string());
+        } else {
+            self.writing = Some(Writing {
+                line_numbers: self.window.line_numbers,
+                wrap: self.window.wrap,
+                scrollbar: self.config.scrollbar,
+                minimap: self.config.minimap,
+                outline: self.outline.take().is_some(),
+            });
+            self.window.line_numbers = false;
+            self.window.wrap = true;
+            self.config.scrollbar = false;
+            self.config.minimap = false;
+            self.status_message = StatusMessage::from("Writing mode on.".to_string());
+        }
+        self.scroll();
+    }
+    fn line_number_width(&self) -> usize {
+        let pager = self.document.special == Some(Special::Output);
+        if (self.window.line_numbers || pager) && !self.document.is_binary() {
+            self.document
+                .len()
+                .max(1)
+                .to_string()
+                .len()
+                .saturating_add(1)
+        } else {
+            0
+        }
+    }
+    /// Diagnostic and git change signs take two columns, only while the buffer has
+    /// either.
+    fn sign_column_width(&self) -> usize {
+        if self.writing.is_some()
+            || self.document.diagnostics.is_empty()
+                && self.document.git.hunks.is_empty()
+                && self.document.folds.is_empty()
+        {
+            0
+        } else {
+            2
+        }
+    }
+    /// The diagnostics touching line `y`.
+    fn line_diagnostics(&self, y: usize) -> impl Iterator<Item = &Diagnostic> {
+        self.document
+            .diagnostics
+            .iter()
+            .filter(move |diagnostic| diagnostic.start.0 <= y && y <= diagnostic.end.0)
+    }
+    fn text_width(&self) -> usize {
+        let width = (self.terminal.size().width as usize)
+            .saturating_sub(self.gutter_width())
+            .saturating_sub(self.side_width())
+            .saturating_sub(self.scrollbar_width())
+            .saturating_sub(self.minimap_width());
+        if self.writing.is_some() {
+            width.min(self.config.writing_width)
+        } else {
+            width
+        }
+    }
+    /// Columns of the panels beside every window: the outline, the preview and the
+    /// terminal pane.
+    fn side_width(&self) -> usize {
+        self.drawing_other.unwrap_or_else(|| {
+            self.outline_width()
+                .saturating_add(self.preview_width())
+                .saturating_add(self.terminal_pane_width())
+        })
+    }
+    fn minimap_width(&self) -> usize {
+        if self.config.minimap && !self.document.large {
+            minimap::WIDTH.min(self.terminal.size().width as usize / 4)
+        } else {
+            0
+        }
+    }
+    fn refresh_minimap(&mut self) {
+        let revision = (self.document.file_name.clone(), self.document.revision());
+        if !self.config.minimap
+            || self.document.large
+            || self.minimap.revision.as_ref() == Some(&revision)
+        {
+            return;
+        }
+        let lines: Vec<&str> = (0..self.document.len())
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        self.minimap
+            .update(&lines, self.document.indent.tab_width, revision);
+    }
+    fn refresh_rainbow(&mut self) {
+        let revision = (self.document.file_name.clone(), self.document.revision());
+        if !self.config.rainbow_brackets
+            || self.document.large
+            || self.rainbow.revision.as_ref() == Some(&revision)
+        {
+            return;
+        }
+        let lines: Vec<&str> = (0..self.document.len())
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        self.rainbow
+            .update(&lines, self.document.file_type.as_deref(), revision);
+    }
+    /// Sends the highlighting thread the lines that changed since it last had them.
+    fn refresh_highlight(&mut self) {
+        let revision = (self.document.file_name.clone(), self.document.revision());
+        let file_type =
+            self.document.file_type.as_deref().filter(|_| {
+                self.config.syntax && !self.document.large && !self.document.is_binary()
+            });
+        if self.highlighter.is_current(&revision, file_type) {
+            return;
+        }
+        let lines: Vec<&str> = (0..self.document.len())
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        let top = self.offset.y;
+        let visible = top..top.saturating_add(self.text_height());
+        self.highlighter
+            .update(&lines, file_type, visible, revision);
+    }
+    /// The delimiter to line up columns by, when column view applies to the buffer.
+    fn column_delimiter(&self) -> Option<char> {
+        if !self.config.column_view || self.document.is_binary() || self.document.large {
+            return None;
+        }
+        csv::delimiter(self.document.file_type.as_deref())
+    }
+    fn refresh_column_view(&mut self) {
+        let delimiter = if let Some(delimiter) = self.column_delimiter() {
+            delimiter
+        } else {
+            return;
+        };
+        let revision = (self.document.file_name.clone(), self.document.revision());
+        if self.column_view.revision.as_ref() == Some(&revision) {
+            return;
+        }
+        let lines: Vec<&str> = (0..self.document.len())
+            .filter_map(|y| self.document.row(y).map(Row::as_str))
+            .collect();
+        self.column_view.update(&lines, delimiter, revision);
+    }
+    /// The cursor's column for the status bar: its number and the name the first row
+    /// gives it.
+    fn column_header(&self) -> Option<String> {
+        let delimiter = self.column_delimiter()?;
+        let row = self.document.row(self.cursor_position.y)?;
+        let index = csv::cell_at(&csv::cells(row.as_str(), delimiter), self.cursor_position.x);
+        let header = self.document.row(0).and_then(|header| {
+            let (start, end) = csv::cells(header.as_str(), delimiter).get(index).copied()?;
+            let name: String = header
+                .graphemes()
+                .skip(start)
+                .take(end.saturating_sub(start))
+                .collect();
+            Some(name.trim().trim_matches('"').to_string()).filter(|name| !name.is_empty())
+        });
+        Some(match header {
+            Some(header) => format!("column {}: {}", index.saturating_add(1), header),
+            None => format!("column {}", index.saturating_add(1)),
+        })
+    }
+    /// Moves to the start of the next cell, or back to the start of this cell or the
+    /// one before, going on to the neighbouring row at either end.
+    fn move_by_cell(&mut self, forward: bool) {
+        let delimiter = if let Some(delimiter) = self.column_delimiter() {
+            delimiter
+        } else {
+            self.status_message =
+                StatusMessage::error("Not a CSV or TSV buffer in column view.".to_string());
+            return;
+        };
+        let Position { x, mut y } = self.cursor_position;
+        let starts = |y: usize| -> Vec<usize> {
+            self.document.row(y).map_or_else(Vec::new, |row| {
+                csv::cells(row.as_str(), delimiter)
+                    .into_iter()
+                    .map(|(start, _)| start)
+                    .collect()
+            })
+        };
+        let target = if forward {
+            starts(y).into_iter().find(|start| *start > x).or_else(|| {
+                y = y.saturating_add(1);
+                (y < self.document.len()).then_some(0)
+            })
+        } else {
+            starts(y)
+                .into_iter()
+                .rev()
+                .find(|start| *start < x)
+                .or_else(|| {
+                    y = y.checked_sub(1)?;
+                    starts(y).last().copied()
+                })
+        };
+        if let Some(x) = target {
+            self.cursor_position = Position { x, y };
+            self.scroll();
+        }
+    }
+    /// Draws a row in column view: each cell padded to its column's width, every other
+    /// column tinted, and the selection shown, clipped to the scrolled part of the screen.
+    fn draw_cells(&self, row: &Row, y: usize, delimiter: char) {
+        let view = &self.column_view;
+        let first = view.offset;
+        let last = first.saturating_add(self.text_width());
+        let selection = self.selection_columns(row, y);
+        let mut column = 0_usize;
+        let mut put = |text: &str, width: usize, bg: Option<Rgb>| {
+            let start = column;
+            column = column.saturating_add(width);
+            if column <= first || start >= last {
+                return;
+            }
+            match bg {
+                Some(color) => self.terminal.set_bg_color(color),
+                None => self.restore_line_bg(y),
+            }
+            if start < first || column > last {
+                // A wide character cut by an edge leaves blanks.
+                print!(
+                    "{}",
+                    " ".repeat(column.min(last).saturating_sub(start.max(first)))
+                );
+            } else {
+                print!("{}", text);
+            }
+        };
+        let graphemes: Vec<&str> = row.graphemes().collect();
+        let cells = csv::cells(row.as_str(), delimiter);
+        let count = cells.len();
+        for (index, (start, end)) in cells.into_iter().enumerate() {
+            let tint = (index % 2 == 1).then_some(self.config.theme.column_alt_bg);
+            let mut used = 0_usize;
+            for x in start..end {
+                let grapheme = graphemes.get(x).copied().unwrap_or_default();
+                let width = csv::grapheme_width(grapheme);
+                let selected = selection.is_some_and(|(from, to)| from <= x && x < to);
+                let bg = if selected {
+                    Some(self.config.theme.selection_bg)
+                } else {
+                    tint
+                };
+                put(grapheme, width, bg);
+                used = used.saturating_add(width);
+            }
+            let padding = view.width(index).saturating_sub(used);
+            put(&" ".repeat(padding), padding, tint);
+            if index.saturating_add(1) < count {
+                let shown = if delimiter == '\t' {
+                    " ".to_string()
+                } else {
+                    delimiter.to_string()
+                };
+                let selected = selection.is_some_and(|(from, to)| from <= end && end < to);
+                let bg = selected.then_some(self.config.theme.selection_bg);
+                put(&shown, 1, bg);
+                put(" ", 1, None);
+            }
+        }
+        self.restore_line_bg(y);
+    }
+    /// The lines on screen, as `first..last`, and the first line of the minimap.
+    fn minimap_lines(&self) -> (usize, usize, usize) {
+        let first = self.offset.y;
+        let last = self
+            .visible_lines()
+            .last()
+            .map_or(first, |(y, _, _)| y.saturating_add(1));
+        let top = self.minimap.top(first, last, self.text_height());
+        (first, last, top)
+    }
+    /// Draws the minimap between the text and the outline, with the rows showing the
+    /// lines on screen highlighted.
+    fn draw_minimap(&self) {
+        let width = self.minimap_width();
+        if width == 0 || self.overlay.is_some() {
+            return;
+        }
+        let height = self.text_height();
+        let (first, last, top) = self.minimap_lines();
+        let x = (self.terminal.size().width as usize)
+            .saturating_sub(self.side_width())
+            .saturating_sub(width);
+        let rows = self.minimap.rows(top, height, width.saturating_sub(1));
+        let window_top = self.window_top();
+        for (row, text) in rows.iter().enumerate() {
+            Terminal::cursor_position(&Position {
+                x,
+                y: window_top.saturating_add(row),
+            });
+            let y = top.saturating_add(row.saturating_mul(minimap::LINES_PER_ROW));
+            let in_view = y < last && first < y.saturating_add(minimap::LINES_PER_ROW);
+            print!(" ");
+            if in_view {
+                self.terminal
+                    .set_bg_color(self.config.theme.minimap_view_bg);
+            }
+            self.terminal.set_fg_color(self.config.theme.minimap_fg);
+            print!("{}", text);
+            Terminal::reset_fg_color();
+            Terminal::reset_bg_color();
+        }
+    }
+    /// Clicking a window moves to it. Clicking the minimap scrolls the lines it shows
+    /// there into the middle of the window and moves the cursor to them.
+    fn on_click(&mut self, position: &Position) {
+        let beside = (self.terminal.size().width as usize).saturating_sub(self.side_width());
+        if self.overlay.is_some() || self.diff_view.is_some() || position.x >= beside {
+            return;
+        }
+        let (index, top) = match self.window_at(position.y) {
+            Some(window) => window,
+            None => return,
+        };
+        self.focus_window(index);
+        let position = &Position {
+            x: position.x,
+            y: position.y.saturating_sub(top),
+        };
+        if self.click_text(position) {
+            return;
+        }
+        let width = self.minimap_width();
+        let height = self.text_height();
+        let x = (self.terminal.size().width as usize)
+            .saturating_sub(self.side_width())
+            .saturating_sub(width);
+        if width == 0 || position.x <= x || position.x >= x.saturating_add(width) {
+            return;
+        }
+        if position.y >= height || self.document.is_empty() {
+            return;
+        }
+        let (_, _, top) = self.minimap_lines();
+        let y = top
+            .saturating_add(position.y.saturating_mul(minimap::LINES_PER_ROW))
+            .min(self.document.len().saturating_sub(1));
+        let old_position = self.cursor_position.clone();
+        self.cursor_position = Position { x: 0, y };
+        self.offset.y = y.saturating_sub(height / 2);
         self.scroll();
-        if self.quit_times < QUIT_TIMES {
-            self.quit_times = QUIT_TIMES;
-            self.status_message = StatusMessage::from(String::new());
+        self.record_jump(old_position);
+    }
+    /// Moves the cursor to the grapheme clicked, when the click was on the text.
+    fn click_text(&mut self, position: &Position) -> bool {
+        let gutter = self.gutter_width();
+        if position.x < gutter || position.x >= gutter.saturating_add(self.text_width()) {
+            return false;
         }
-        Ok(())
+        let (y, start, end) = match self.visible_lines().get(position.y) {
+            Some(line) => *line,
+            None => return false,
+        };
+        let row = match self.document.row(y) {
+            Some(row) => row,
+            None => return false,
+        };
+        let column = position.x.saturating_sub(gutter);
+        let tab_width = self.document.indent.tab_width;
+        let x = match self.paragraph(row) {
+            Some(paragraph) => self.index_at_visual_column(row, &paragraph, start, end, column),
+            None => row
+                .index_at_column(
+                    row.column(start, tab_width).saturating_add(column),
+                    tab_width,
+                )
+                .min(end),
+        };
+        self.cursor_position = Position { x, y };
+        true
     }
-    fn scroll(&mut self) {
-        let Position { x, y } = self.cursor_position;
-        let width = self.terminal.size().width as usize;
-        let height = self.terminal.size().height as usize;
-        let offset = &mut self.offset;
-
-        if y < offset.y {
-            offset.y = y;
-        } else if y >= offset.y.saturating_add(height) {
-            offset.y = y.saturating_sub(height).saturating_add(1);
+    fn scrollbar_width(&self) -> usize {
+        usize::from(self.config.scrollbar)
+    }
+    /// Draws the scrollbar at the right edge of the text, with the thumb showing the
+    /// lines on screen and marks for search matches and diagnostics.
+    fn draw_scrollbar(&self) {
+        if !self.config.scrollbar || self.overlay.is_some() {
+            return;
         }
-        if x < offset.x {
-            offset.x = x;
-        } else if x >= offset.x.saturating_add(width) {
-            offset.x = x.saturating_sub(width).saturating_add(1);
+        let lines = self.visible_lines();
+        let first = self.offset.y;
+        let last = lines.last().map_or(first, |(y, _, _)| y.saturating_add(1));
+        let diagnostics = self.document.diagnostics.iter().map(|diagnostic| {
+            (
+                diagnostic.start.0,
+                scrollbar::Mark::Diagnostic(diagnostic.severity),
+            )
+        });
+        let matches = self.highlighted_search().into_iter().flat_map(|query| {
+            (0..self.document.len()).filter_map(move |y| {
+                self.document
+                    .row(y)
+                    .filter(|row| query.is_match(row.as_str()))
+                    .map(|_| (y, scrollbar::Mark::Search))
+            })
+        });
+        let height = self.text_height();
+        let cells = scrollbar::cells(
+            self.document.len(),
+            first,
+            last,
+            height,
+            diagnostics.chain(matches),
+        );
+        let x = (self.terminal.size().width as usize)
+            .saturating_sub(self.side_width())
+            .saturating_sub(self.minimap_width())
+            .saturating_sub(1);
+        let theme = &self.config.theme;
+        let top = self.window_top();
+        for (y, cell) in cells.iter().enumerate() {
+            Terminal::cursor_position(&Position {
+                x,
+                y: top.saturating_add(y),
+            });
+            self.terminal.set_bg_color(if cell.thumb {
+                theme.scrollbar_thumb_bg
+            } else {
+                theme.scrollbar_bg
+            });
+            match cell.mark {
+                Some(mark) => {
+                    self.terminal.set_fg_color(match mark {
+                        scrollbar::Mark::Diagnostic(severity) => theme.diagnostic_fg(severity),
+                        scrollbar::Mark::Search => theme.search_mark_fg,
+                    });
+                    print!("━");
+                    Terminal::reset_fg_color();
+                }
+                None => print!(" "),
+            }
+            Terminal::reset_bg_color();
         }
     }
     fn move_cursor(&mut self, key: Key) {
         let Position { mut x, mut y } = self.cursor_position;
-        let terminal_size = self.terminal.size().height as usize;
+        let terminal_size = self.text_height();
         let height = self.document.len();
         let mut width = if let Some(row) = self.document.row(y) {
             row.len()
@@ -234,10 +9211,10 @@ impl Editor {
             }
             Key::Down => {
                 if y < height {
-                    y = y.saturating_add(1);
+                    y = self.document.folds.next_line(y).min(height);
                 }
             }
-            Key::Up => y = y.saturating_sub(1),
+            Key::Up => y = self.document.folds.previous_line(y),
             Key::PageUp => {
                 y = if y > terminal_size {
                     y.saturating_sub(terminal_size)
@@ -256,50 +9233,182 @@ impl Editor {
             Key::End => x = width,
             _ => (),
         };
+        // A closed fold is passed over as one line.
+        y = self.document.folds.visible_line(y);
         width = if let Some(row) = self.document.row(y) {
             row.len()
         } else {
             0
         };
+        // Up and Down aim for the remembered column, not the index a shorter line
+        // clamped the cursor to.
+        let tab_width = self.document.indent.tab_width;
+        let remembered = self
+            .sticky_column
+            .take()
+            .filter(|(at, _)| *at == self.cursor_position)
+            .map(|(_, column)| column);
+        let column = match key {
+            _ if !self.config.sticky_column => None,
+            Key::End => Some(usize::MAX),
+            Key::Up | Key::Down | Key::PageUp | Key::PageDown => remembered.or_else(|| {
+                let Position { x, y } = self.cursor_position;
+                self.document.row(y).map(|row| row.column(x, tab_width))
+            }),
+            _ => None,
+        };
+        if let (Some(column), Some(row)) = (column, self.document.row(y)) {
+            x = row.index_at_column(column, tab_width);
+        }
         if x > width {
             x = width;
         }
-        self.cursor_position = Position { x, y }
+        self.cursor_position = Position { x, y };
+        if let Some(column) = column {
+            self.sticky_column = Some((self.cursor_position.clone(), column));
+        }
     }
-    fn refresh_screen(&self) -> Result<(), std::io::Error> {
+    fn refresh_screen(&mut self) -> Result<(), std::io::Error> {
+        if self.terminal.is_headless() {
+            return Ok(());
+        }
         // print!("\x1b[2J \x1b[1;1H");
         Terminal::cursor_hide();
+        let shape = self.cursor_shape();
+        self.terminal.set_cursor_shape(shape);
         Terminal::cursor_position(&Position::default());
         if self.should_quit {
             Terminal::clear_screen();
             println!("Goodbye.\r");
         } else {
-            self.draw_rows();
+            if self.terminal.size().is_too_small() || self.text_width() == 0 {
+                self.drawn_rows = None;
+                self.draw_too_small();
+                return Terminal::flush();
+            }
+            if let Some(view) = &self.diff_view {
+                self.drawn_rows = None;
+                self.draw_diff_view(view);
+                self.draw_status_bar();
+                self.draw_message_bar();
+                Terminal::cursor_position(&Position {
+                    x: self.diff_pane_width().saturating_add(1),
+                    y: view.selected.saturating_sub(view.offset),
+                });
+                Terminal::cursor_show();
+                return Terminal::flush();
+            }
+            self.draw_windows();
             self.draw_status_bar();
             self.draw_message_bar();
-            Terminal::cursor_position(&Position {
-                x: self.cursor_position.x.saturating_sub(self.offset.x),
-                y: self.cursor_position.y.saturating_sub(self.offset.y),
-            });
+            self.draw_outline();
+            self.draw_preview();
+            self.draw_terminal_pane();
+            self.draw_debug_overlay();
+            if let Some(pane) = self.terminal_pane.as_ref().filter(|pane| pane.focused) {
+                let (x, y) = pane.screen.cursor();
+                Terminal::cursor_position(&Position {
+                    x: (self.terminal.size().width as usize)
+                        .saturating_sub(self.terminal_pane_width())
+                        .saturating_add(1)
+                        .saturating_add(x),
+                    y,
+                });
+                Terminal::cursor_show();
+                return Terminal::flush();
+            }
+            let Position { x, y } = self.cursor_position;
+            let tab_width = self.document.indent.tab_width;
+            let lines = self.visible_lines();
+            let screen_y = lines
+                .iter()
+                .rposition(|(line_y, start, _)| *line_y == y && *start <= x)
+                .unwrap_or(lines.len());
+            let column = match (
+                self.document.row(y),
+                lines.get(screen_y),
+                self.column_delimiter(),
+            ) {
+                (Some(row), _, Some(delimiter)) => self
+                    .column_view
+                    .column(row.as_str(), delimiter, x)
+                    .saturating_sub(self.column_view.offset),
+                (Some(row), Some((_, start, end)), None) => match self.paragraph(row) {
+                    Some(paragraph) => self.visual_column(row, &paragraph, *start, *end, x),
+                    None => row
+                        .column(x, tab_width)
+                        .saturating_sub(row.column(*start, tab_width)),
+                },
+                _ => 0,
+            };
+            let cursor = Position {
+                x: self.gutter_width().saturating_add(column),
+                y: self.window_top().saturating_add(screen_y),
+            };
+            self.draw_popup(&cursor);
+            if self.prompting {
+                let size = self.terminal.size();
+                Terminal::cursor_position(&Position {
+                    x: unicode::text_width(&self.status_message.text)
+                        .min((size.width as usize).saturating_sub(1)),
+                    y: size.message_row(),
+                });
+            } else {
+                Terminal::cursor_position(&cursor);
+            }
+            if self.scroll_animation.is_some() {
+                // The cursor line may not be on screen until the view arrives.
+                return Terminal::flush();
+            }
         }
         Terminal::cursor_show();
         Terminal::flush()
     }
-    pub fn prompt<C>(&mut self, prompt: &str, mut callback: C) -> Result<Option<String>, Error>
+    /// Reads a line in the status bar. With a `history` kind, Up/Down recall earlier
+    /// entries that start with the text typed so far, and accepted input is remembered.
+    pub fn prompt<C>(
+        &mut self,
+        prompt: &str,
+        history: Option<HistoryKind>,
+        callback: C,
+    ) -> Result<Option<String>, Error>
     where
         C: FnMut(&mut Self, Key, &String),
+    {
+        self.prompt_with(|_| prompt.to_string(), history, callback)
+    }
+    /// Like `prompt`, with the prompt made again before each key, so it can show
+    /// state the callback changes.
+    pub fn prompt_with<P, C>(
+        &mut self,
+        prompt: P,
+        history: Option<HistoryKind>,
+        mut callback: C,
+    ) -> Result<Option<String>, Error>
+    where
+        P: Fn(&Self) -> String,
+        C: FnMut(&mut Self, Key, &String),
     {
         let mut result = String::new();
+        // Text typed before browsing, and how many matching entries back we are.
+        let mut draft = String::new();
+        let mut history_index = 0_usize;
         loop {
-            self.status_message = StatusMessage::from(format!("{}{}", prompt, result));
-            self.refresh_screen()?;
-
-            let key = Terminal::read_key()?;
+            self.status_message = StatusMessage::from(format!("{}{}", prompt(self), result));
+            self.prompting = true;
+            let key = self.refresh_screen().and_then(|()| self.next_key());
+            self.prompting = false;
+            let key = key?;
             match key {
                 Key::Backspace => {
-                    result.truncate(result.len().saturating_sub(1));
+                    result.pop();
                 }
                 Key::Char('\n') => break,
+                Key::Char('\t') | Key::BackTab => {
+                    if let Some(kind) = history {
+                        self.complete_prompt(&mut result, kind, key == Key::BackTab);
+                    }
+                }
                 Key::Char(c) => {
                     if !c.is_control() {
                         result.push(c);
@@ -309,58 +9418,292 @@ impl Editor {
                     result.truncate(0);
                     break;
                 }
+                Key::Up | Key::Down if history.is_some() => {
+                    if history_index == 0 {
+                        draft = result.clone();
+                    }
+                    let entries: Vec<&String> = history
+                        .map(|kind| self.history.entries(kind))
+                        .unwrap_or_default()
+                        .iter()
+                        .rev()
+                        .filter(|entry| entry.starts_with(&draft))
+                        .collect();
+                    history_index = if key == Key::Up {
+                        history_index.saturating_add(1).min(entries.len())
+                    } else {
+                        history_index.saturating_sub(1)
+                    };
+                    result = match history_index.checked_sub(1) {
+                        Some(index) => entries
+                            .get(index)
+                            .map_or_else(String::new, |e| (*e).clone()),
+                        None => draft.clone(),
+                    };
+                }
                 _ => (),
             }
+            if !matches!(key, Key::Up | Key::Down) {
+                history_index = 0;
+            }
+            if !matches!(key, Key::Char('\t') | Key::BackTab) {
+                self.completion = None;
+            }
             callback(self, key, &result);
         }
+        self.completion = None;
         self.status_message = StatusMessage::from(String::new());
         if result.is_empty() {
             return Ok(None);
         }
+        if let Some(kind) = history {
+            self.history.add(kind, &result);
+        }
         Ok(Some(result))
     }
+    /// Tab completion: the first press extends the word to the candidates' common
+    /// prefix and lists them; further presses cycle through the list.
+    fn complete_prompt(&mut self, result: &mut String, kind: HistoryKind, backward: bool) {
+        if let Some(menu) = &mut self.completion {
+            let start = menu.start;
+            if let Some(candidate) = menu.cycle(backward) {
+                result.truncate(start);
+                result.push_str(candidate);
+            }
+            return;
+        }
+        let (start, candidates) = match completion::complete(kind, result) {
+            Some((start, candidates)) if !candidates.is_empty() => (start, candidates),
+            _ => return,
+        };
+        result.truncate(start);
+        result.push_str(completion::common_prefix(&candidates));
+        if candidates.len() > 1 {
+            self.completion = Some(completion::Menu {
+                start,
+                candidates,
+                selected: None,
+            });
+        }
+    }
+    /// The text of one status bar segment; empty when it has nothing to show.
+    fn status_segment(&self, segment: Segment) -> String {
+        match segment {
+            Segment::Mode => match self.mode {
+                Mode::Normal => "-- NORMAL --".to_string(),
+                Mode::Visual => "-- VISUAL --".to_string(),
+                Mode::Insert => String::new(),
+            },
+            Segment::Recording => self
+                .recording
+                .as_ref()
+                .map_or_else(String::new, |(name, _)| format!("recording @{}", name)),
+            Segment::Buffers if self.buffers.len() > 1 => format!(
+                "[{}/{}]",
+                self.current_buffer.saturating_add(1),
+                self.buffers.len()
+            ),
+            Segment::Buffers => String::new(),
+            Segment::File => {
+                let mut file_name = self
+                    .document
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| "[No Name]".to_string());
+                if let Some(name) = &self.document.file_name {
+                    file_name = name.clone();
+                    file_name.truncate(20);
+                }
+                if self.document.is_dirty() {
+                    file_name.push_str(" (modified)");
+                } else if self.document.read_only {
+                    file_name.push_str(" [RO]");
+                }
+                if self.config.auto_save.is_some() {
+                    file_name.push_str(" [auto-save]");
+                }
+                if self.document.large {
+                    file_name.push_str(" [large]");
+                }
+                file_name
+            }
+            Segment::Lines => format!("{} lines", self.document.len()),
+            Segment::Search => match self.search_match_count() {
+                Some((Some(index), count)) => format!("match {} of {}", index, count),
+                Some((None, 0)) => "no matches".to_string(),
+                Some((None, 1)) => "1 match".to_string(),
+                Some((None, count)) => format!("{} matches", count),
+                None => String::new(),
+            },
+            Segment::Breadcrumb => self.column_header().unwrap_or_else(|| {
+                outline::enclosing(&self.document.symbols, self.cursor_position.y)
+                    .iter()
+                    .map(|symbol| symbol.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" > ")
+            }),
+            Segment::Words => self
+                .word_count
+                .map_or_else(String::new, |(_, words)| format!("{} words", words)),
+            Segment::Git => self
+                .git_status
+                .as_ref()
+                .map_or_else(String::new, git::Status::describe),
+            Segment::Diagnostics => {
+                let count = |severity| {
+                    self.document
+                        .diagnostics
+                        .iter()
+                        .filter(|diagnostic| diagnostic.severity == severity)
+                        .count()
+                };
+                [Severity::Error, Severity::Warning]
+                    .into_iter()
+                    .map(|severity| (severity, count(severity)))
+                    .filter(|(_, count)| *count > 0)
+                    .map(|(severity, count)| format!("{}{}", severity.sign(), count))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            }
+            Segment::Plugins => self.plugins.status(statusline::SEPARATOR),
+            Segment::Position => format!(
+                "{}/{}",
+                self.cursor_position.y.saturating_add(1),
+                self.document.len()
+            ),
+            Segment::Encoding => {
+                let bom = if self.document.is_binary() {
+                    " [hex]"
+                } else if self.document.has_bom() {
+                    " BOM"
+                } else {
+                    ""
+                };
+                format!("{}{}", self.document.encoding.name(), bom)
+            }
+            Segment::LineEnding => self.document.line_ending.name().to_string(),
+            Segment::FileType => self.document.file_type.clone().unwrap_or_default(),
+            Segment::Clock => statusline::clock(),
+        }
+    }
+    /// Draws the `status_bar` segments that have something to show, each group joined
+    /// by separators, the left group flush left and the right one flush right.
     fn draw_status_bar(&self) {
-        let mut status;
+        if !self.terminal.size().status_bar {
+            return;
+        }
         let width = self.terminal.size().width as usize;
-        let modified_indicator = if self.document.is_dirty() {
-            " (modified)"
+        let group = |segments: &[Segment]| -> Vec<(Segment, String)> {
+            segments
+                .iter()
+                .map(|segment| (*segment, self.status_segment(*segment)))
+                .filter(|(_, text)| !text.is_empty())
+                .collect()
+        };
+        let group_width = |group: &[(Segment, String)]| -> usize {
+            let separators = group.len().saturating_sub(1);
+            group
+                .iter()
+                .map(|(_, text)| text.chars().map(unicode::width).sum::<usize>())
+                .sum::<usize>()
+                .saturating_add(separators.saturating_mul(statusline::SEPARATOR.len()))
+        };
+        let left = group(&self.config.status_bar.left);
+        let right = group(&self.config.status_bar.right);
+        let padding = width
+            .saturating_sub(group_width(&left))
+            .saturating_sub(group_width(&right));
+        let dim = self.config.dim_unfocused && !self.focused;
+        let theme = &self.config.theme;
+        let plain = if dim {
+            theme.status_unfocused_fg
         } else {
-            ""
+            theme.status_fg
+        };
+        let mut remaining = width;
+        let mut print = |text: &str, color| {
+            let mut shown = String::new();
+            for c in text.chars() {
+                let c_width = unicode::width(c);
+                if c_width > remaining {
+                    remaining = 0;
+                    break;
+                }
+                remaining = remaining.saturating_sub(c_width);
+                shown.push(c);
+            }
+            self.terminal.set_fg_color(color);
+            print!("{}", shown);
         };
-        let mut file_name = "[No Name]".to_string();
-        if let Some(name) = &self.document.file_name {
-            file_name = name.clone();
-            file_name.truncate(20);
+        self.terminal.set_bg_color(theme.status_bg);
+        for (index, (segment, text)) in left.iter().enumerate() {
+            if index > 0 {
+                print(statusline::SEPARATOR, plain);
+            }
+            print(
+                text,
+                if dim {
+                    plain
+                } else {
+                    theme.segment_fg(*segment)
+                },
+            );
         }
-        status = format!(
-            "{} - {} lines{}",
-            file_name,
-            self.document.len(),
-            modified_indicator
-        );
-        let line_indicator = format!(
-            "{}/{}",
-            self.cursor_position.y.saturating_add(1),
-            self.document.len()
-        );
-        #[allow(clippy::integer_arithmetic)]
-        let len = status.len() + line_indicator.len();
-        status.push_str(&" ".repeat(width.saturating_sub(len)));
-        status = format!("{}{}", status, line_indicator);
-        status.truncate(width);
-        Terminal::set_bg_color(STATUS_BG_COLOR);
-        Terminal::set_fg_color(STATUS_FG_COLOR);
-        println!("{}\r", status);
+        print(&" ".repeat(padding), plain);
+        for (index, (segment, text)) in right.iter().enumerate() {
+            if index > 0 {
+                print(statusline::SEPARATOR, plain);
+            }
+            print(
+                text,
+                if dim {
+                    plain
+                } else {
+                    theme.segment_fg(*segment)
+                },
+            );
+        }
+        println!("\r");
         Terminal::reset_fg_color();
         Terminal::reset_bg_color();
     }
     fn draw_message_bar(&self) {
         Terminal::clear_current_line();
         let message = &self.status_message;
-        if Instant::now() - message.time < Duration::new(5, 0) {
-            let mut text = message.text.clone();
-            text.truncate(self.terminal.size().width as usize);
-            print!("{}", text);
+        let width = self.terminal.size().width as usize;
+        if message.time.elapsed() < MESSAGE_TIMEOUT && !message.text.is_empty() {
+            print!("{}", unicode::truncate(&message.text, width));
+        } else if let Some(diagnostic) = self
+            .line_diagnostics(self.cursor_position.y)
+            .min_by_key(|diagnostic| diagnostic.severity)
+        {
+            let text = format!(
+                "{}: {}",
+                diagnostic.severity.sign(),
+                diagnostic.message.lines().next().unwrap_or_default()
+            );
+            self.terminal
+                .set_fg_color(self.config.theme.diagnostic_fg(diagnostic.severity));
+            print!("{}", text.chars().take(width).collect::<String>());
+            Terminal::reset_fg_color();
+        }
+    }
+    /// Says the window is too small for the editor, on as many of its rows as it has
+    /// and cut to its width, in place of a layout that would not fit.
+    fn draw_too_small(&self) {
+        Terminal::clear_screen();
+        let size = self.terminal.size();
+        let rows = size.message_row().saturating_add(1);
+        let lines = [
+            "Terminal too small".to_string(),
+            format!(
+                "Make it at least {} columns wide and 2 rows tall, or close panes",
+                terminal::MIN_WIDTH
+            ),
+        ];
+        for (y, line) in lines.iter().enumerate().take(rows) {
+            Terminal::cursor_position(&Position { x: 0, y });
+            print!("{}", unicode::truncate(line, size.width as usize));
         }
     }
     fn draw_welcome_message(&self) {
@@ -375,29 +9718,954 @@ impl Editor {
         println!("{}\r", welcome_message);
     }
 
-    fn draw_row(&self, row: &Row) {
-        let start = self.offset.x;
+    fn draw_row(&self, row: &Row, y: usize, start: usize, end: usize, screen_y: usize) {
+        let first_line = start == 0 || !self.wraps();
+        print!("{}", " ".repeat(self.writing_margin()));
+        if self.sign_column_width() > 0 {
+            let severity = self
+                .line_diagnostics(y)
+                .filter(|diagnostic| diagnostic.start.0 == y)
+                .map(|diagnostic| diagnostic.severity)
+                .min()
+                .filter(|_| first_line);
+            let change = self.document.git.change_at(y).filter(|_| first_line);
+            if self.document.folds.at(y).is_some() {
+                self.terminal.set_fg_color(self.config.theme.fold_fg);
+                print!("▸ ");
+                Terminal::reset_fg_color();
+            } else if let Some(severity) = severity {
+                self.terminal
+                    .set_fg_color(self.config.theme.diagnostic_fg(severity));
+                print!("{} ", severity.sign());
+                Terminal::reset_fg_color();
+            } else if let Some(change) = change {
+                self.terminal
+                    .set_fg_color(self.config.theme.change_fg(change));
+                print!("{} ", change.sign());
+                Terminal::reset_fg_color();
+            } else {
+                print!("  ");
+            }
+        }
+        let numbers = self.line_number_width();
+        if numbers > 0 {
+            self.terminal.set_fg_color(self.config.theme.line_number_fg);
+            // Continuation lines of a wrapped row leave the gutter blank.
+            if first_line {
+                print!(
+                    "{:>width$} ",
+                    y.saturating_add(1),
+                    width = numbers.saturating_sub(1)
+                );
+            } else {
+                print!("{}", " ".repeat(numbers));
+            }
+            Terminal::reset_fg_color();
+        }
+        let line_bg = self.line_bg(y);
+        if line_bg.is_some() {
+            self.restore_line_bg(y);
+        }
+        let paragraph = self.paragraph(row);
+        if let Some(delimiter) = self.column_delimiter() {
+            self.draw_cells(row, y, delimiter);
+        } else if let Some(paragraph) = &paragraph {
+            self.draw_bidi(row, y, start, end, paragraph);
+        } else if let Some((selection_start, selection_end)) = self.selection_columns(row, y) {
+            let selection_start = cmp::min(cmp::max(selection_start, start), end);
+            let selection_end = cmp::min(cmp::max(selection_end, start), end);
+            self.draw_text(row, start, selection_start, None);
+            self.terminal.set_bg_color(self.config.theme.selection_bg);
+            self.draw_text(row, selection_start, selection_end, None);
+            self.restore_line_bg(y);
+            self.draw_text(row, selection_end, end, None);
+        } else if let Some(section) = self
+            .document
+            .conflicts
+            .iter()
+            .find_map(|conflict| conflict.section(y))
+        {
+            self.draw_conflict_line(row, y, section, start, end);
+        } else {
+            self.draw_spans(row, y, start, end);
+        }
+        if let Some((fold_start, fold_end)) = self.document.folds.at(y) {
+            self.terminal.set_fg_color(self.config.theme.fold_fg);
+            let hidden = fold_end.saturating_sub(fold_start);
+            print!(" ⋯ {} {}", hidden, stats::plural(hidden, "line"));
+            Terminal::reset_fg_color();
+        }
+        if let (true, Some(eol)) = (self.window.list, self.config.list_chars.eol) {
+            if end >= row.len() {
+                self.terminal.set_fg_color(self.config.theme.whitespace_fg);
+                print!("{}", eol);
+                Terminal::reset_fg_color();
+            }
+        }
+        self.draw_blame(row, y, start, end);
+        if line_bg.is_some() {
+            // Clearing to the end of the line paints the rest of it in the line color.
+            print!("{}", termion::clear::UntilNewline);
+        }
+        if self.column_delimiter().is_none() && paragraph.is_none() {
+            self.draw_color_columns(row, y, start, screen_y);
+        }
+        if line_bg.is_some() {
+            Terminal::reset_bg_color();
+        }
+        println!("\r");
+    }
+    /// How `row` is ordered on screen, when it has right-to-left text to order.
+    fn paragraph(&self, row: &Row) -> Option<Paragraph> {
+        if self.column_delimiter().is_some() || self.document.large {
+            return None;
+        }
+        Paragraph::new(row.as_str(), self.config.bidi)
+    }
+    /// Prints graphemes `start..end` of a row with right-to-left text in the order
+    /// they are read, with the selection and the colors of `draw_spans`. Brackets in
+    /// right-to-left runs face the other way.
+    fn draw_bidi(&self, row: &Row, y: usize, start: usize, end: usize, paragraph: &Paragraph) {
+        let tab_width = self.document.indent.tab_width;
+        let graphemes: Vec<&str> = row.graphemes().collect();
+        let selection = self.selection_columns(row, y);
+        let spans = self.spans(row, y);
+        let mut column = 0_usize;
+        for index in paragraph.order(start, end) {
+            let grapheme = graphemes.get(index).copied().unwrap_or_default();
+            let selected = selection.is_some_and(|(from, to)| from <= index && index < to);
+            let style = spans
+                .iter()
+                .rev()
+                .find(|(from, to, _)| *from <= index && index < *to)
+                .map(|(_, _, style)| *style);
+            if selected {
+                self.terminal.set_bg_color(self.config.theme.selection_bg);
+            }
+            match style {
+                Some(SpanStyle::Underline(color)) => {
+                    Terminal::set_underline(true);
+                    self.terminal.set_fg_color(color);
+                }
+                Some(SpanStyle::Fg(color)) => self.terminal.set_fg_color(color),
+                Some(SpanStyle::Swatch(color)) => {
+                    self.terminal.set_bg_color(color);
+                    self.terminal.set_fg_color(swatch::contrast(color));
+                }
+                Some(SpanStyle::Match(color)) => self.terminal.set_bg_color(color),
+                None => (),
+            }
+            let width = row.width(grapheme, column, tab_width);
+            let mut chars = grapheme.chars();
+            match (chars.next(), chars.next()) {
+                (Some('\t'), None) => print!("{}", " ".repeat(width)),
+                (Some(c), None) if paragraph.is_rtl(index) => print!("{}", bidi::mirror(c)),
+                _ => print!("{}", grapheme),
+            }
+            column = column.saturating_add(width);
+            if matches!(style, Some(SpanStyle::Underline(_))) {
+                Terminal::set_underline(false);
+            }
+            if style.is_some() {
+                Terminal::reset_fg_color();
+            }
+            if selected || matches!(style, Some(SpanStyle::Swatch(_) | SpanStyle::Match(_))) {
+                self.restore_line_bg(y);
+            }
+        }
+    }
+    /// The screen column, from the start of the line, where grapheme `x` of the
+    /// graphemes `start..end` shows when they are in bidirectional order.
+    fn visual_column(
+        &self,
+        row: &Row,
+        paragraph: &Paragraph,
+        start: usize,
+        end: usize,
+        x: usize,
+    ) -> usize {
+        let tab_width = self.document.indent.tab_width;
+        let graphemes: Vec<&str> = row.graphemes().collect();
+        let mut column = 0_usize;
+        for index in paragraph.order(start, end) {
+            if index == x {
+                return column;
+            }
+            let grapheme = graphemes.get(index).copied().unwrap_or_default();
+            column = column.saturating_add(row.width(grapheme, column, tab_width));
+        }
+        column
+    }
+    /// The grapheme of `start..end` shown at screen `column` of the line, the reverse
+    /// of `visual_column`.
+    fn index_at_visual_column(
+        &self,
+        row: &Row,
+        paragraph: &Paragraph,
+        start: usize,
+        end: usize,
+        column: usize,
+    ) -> usize {
+        let tab_width = self.document.indent.tab_width;
+        let graphemes: Vec<&str> = row.graphemes().collect();
+        let mut current = 0_usize;
+        for index in paragraph.order(start, end) {
+            let grapheme = graphemes.get(index).copied().unwrap_or_default();
+            current = current.saturating_add(row.width(grapheme, current, tab_width));
+            if current > column {
+                return index;
+            }
+        }
+        end
+    }
+    /// Prints graphemes `start..end` of `row`. In list mode whitespace is drawn as its
+    /// symbol in the whitespace color, going back to `fg` after each one.
+    fn draw_text(&self, row: &Row, start: usize, end: usize, fg: Option<Rgb>) {
+        let indent = &self.document.indent;
+        if !self.window.list && !self.config.indent_guides {
+            print!("{}", row.render(start, end, indent.tab_width));
+            return;
+        }
+        let symbols = self.window.list.then_some(&self.config.list_chars);
+        // Guides follow what one level of indentation looks like in this buffer.
+        let guide_width = self.config.indent_guides.then_some({
+            if indent.use_tabs {
+                indent.tab_width
+            } else {
+                indent.size
+            }
+        });
+        for (text, piece) in row.render_visible(start, end, indent.tab_width, symbols, guide_width)
+        {
+            let color = match piece {
+                Piece::Text => {
+                    print!("{}", text);
+                    continue;
+                }
+                Piece::Whitespace => self.config.theme.whitespace_fg,
+                Piece::Guide => self.config.theme.indent_guide_fg,
+            };
+            self.terminal.set_fg_color(color);
+            print!("{}", text);
+            match fg {
+                Some(color) => self.terminal.set_fg_color(color),
+                None => Terminal::reset_fg_color(),
+            }
+        }
+    }
+    /// The background of buffer line `y`, when `cursorline` highlights it.
+    fn line_bg(&self, y: usize) -> Option<Rgb> {
+        (self.config.cursor_line && y == self.cursor_position.y)
+            .then_some(self.config.theme.cursor_line_bg)
+    }
+    /// Goes back to the background of line `y` after coloring part of it.
+    fn restore_line_bg(&self, y: usize) {
+        match self.line_bg(y) {
+            Some(color) => self.terminal.set_bg_color(color),
+            None => Terminal::reset_bg_color(),
+        }
+    }
+    /// Renders a line of a merge conflict: the sides on their own backgrounds and the
+    /// markers dimmed.
+    fn draw_conflict_line(&self, row: &Row, y: usize, section: Section, start: usize, end: usize) {
+        let theme = &self.config.theme;
+        match section {
+            Section::Ours | Section::Theirs => {
+                self.terminal.set_bg_color(if section == Section::Ours {
+                    theme.ours_bg
+                } else {
+                    theme.theirs_bg
+                });
+                self.draw_text(row, start, end, None);
+                self.restore_line_bg(y);
+            }
+            Section::Marker => {
+                self.terminal.set_fg_color(theme.line_number_fg);
+                self.draw_text(row, start, end, Some(theme.line_number_fg));
+                Terminal::reset_fg_color();
+            }
+            Section::Base => self.draw_text(row, start, end, None),
+        }
+    }
+    /// Shows who last changed the cursor line as dimmed text after its last segment.
+    fn draw_blame(&self, row: &Row, y: usize, start: usize, end: usize) {
+        if !self.config.blame
+            || self.mode == Mode::Insert
+            || y != self.cursor_position.y
+            || end < row.len()
+            || self.document.blame.revision != Some(self.document.revision())
+        {
+            return;
+        }
+        let commit = if let Some(commit) = self.document.blame.commit_at(y) {
+            commit
+        } else {
+            return;
+        };
+        let tab_width = self.document.indent.tab_width;
+        let used = row
+            .column(row.len(), tab_width)
+            .saturating_sub(row.column(start, tab_width));
+        let available = self.text_width().saturating_sub(used).saturating_sub(4);
+        let text: String = commit.describe().chars().take(available).collect();
+        if text.is_empty() {
+            return;
+        }
+        self.terminal.set_fg_color(self.config.theme.hint_fg);
+        print!("    {}", text);
+        Terminal::reset_fg_color();
+    }
+    /// Renders graphemes `start..end` of the row, underlining diagnostic ranges in the
+    /// color of their severity, misspelled words in the spelling color and URLs. With
+    /// `rainbowbrackets`, brackets take the color of their depth, and with
+    /// `colorswatches`, color literals are drawn on the color they name. With `syntax`,
+    /// what the rest leaves uncolored takes the color of its keyword, string, comment
+    /// or number.
+    /// The colored spans of row `y`, sorted by where they start.
+    fn spans(&self, row: &Row, y: usize) -> Vec<(usize, usize, SpanStyle)> {
+        if self.document.large {
+            return Vec::new();
+        }
+        let mut spans: Vec<(usize, usize, SpanStyle)> = self
+            .line_diagnostics(y)
+            .map(|diagnostic| {
+                let span_start = if diagnostic.start.0 == y {
+                    row.index_at_utf16(diagnostic.start.1)
+                } else {
+                    0
+                };
+                let span_end = if diagnostic.end.0 == y {
+                    row.index_at_utf16(diagnostic.end.1)
+                } else {
+                    row.len()
+                };
+                // Empty ranges still mark the character they point at.
+                (
+                    span_start,
+                    span_end.max(span_start.saturating_add(1)),
+                    SpanStyle::Underline(self.config.theme.diagnostic_fg(diagnostic.severity)),
+                )
+            })
+            .collect();
+        if row.len() > MAX_HIGHLIGHT_LENGTH {
+            spans.sort_by_key(|(span_start, _, _)| *span_start);
+            return spans;
+        }
+        spans.extend(
+            self.misspellings(row)
+                .into_iter()
+                .map(|(span_start, span_end)| {
+                    (
+                        span_start,
+                        span_end,
+                        SpanStyle::Underline(self.config.theme.spell_fg),
+                    )
+                }),
+        );
+        if self.config.rainbow_brackets {
+            let colors = &self.config.theme.rainbow_fg;
+            spans.extend(
+                self.rainbow
+                    .brackets(y, row.as_str())
+                    .into_iter()
+                    .filter_map(|(index, depth)| {
+                        let color = colors.get(depth.checked_rem(colors.len())?)?;
+                        Some((index, index.saturating_add(1), SpanStyle::Fg(*color)))
+                    }),
+            );
+        }
+        spans.extend(
+            url::find(row.as_str())
+                .into_iter()
+                .map(|(span_start, span_end)| {
+                    (
+                        span_start,
+                        span_end,
+                        SpanStyle::Underline(self.config.theme.url_fg),
+                    )
+                }),
+        );
+        if self.config.color_swatches {
+            spans.extend(swatch::find(row.as_str()).into_iter().map(
+                |(span_start, span_end, color)| (span_start, span_end, SpanStyle::Swatch(color)),
+            ));
+        }
+        if let Some(query) = self.highlighted_search() {
+            let color = self.config.theme.search_match_bg;
+            spans.extend(
+                row.matches(query)
+                    .into_iter()
+                    .map(|(span_start, span_end)| (span_start, span_end, SpanStyle::Match(color))),
+            );
+        }
+        if self.config.syntax {
+            // Syntax colors go beneath the rest, only where nothing else is drawn.
+            let covered: Vec<(usize, usize)> = spans
+                .iter()
+                .map(|(span_start, span_end, _)| (*span_start, *span_end))
+                .collect();
+            spans.extend(
+                highlight::uncovered(self.highlighter.spans(y), &covered)
+                    .into_iter()
+                    .map(|(span_start, span_end, token)| {
+                        (
+                            span_start,
+                            span_end,
+                            SpanStyle::Fg(self.config.theme.token_fg(token)),
+                        )
+                    }),
+            );
+        }
+        spans.sort_by_key(|(span_start, _, _)| *span_start);
+        spans
+    }
+    fn draw_spans(&self, row: &Row, y: usize, start: usize, end: usize) {
+        let mut x = start;
+        for (span_start, span_end, style) in self.spans(row, y) {
+            let span_start = span_start.clamp(x, end);
+            let span_end = span_end.clamp(span_start, end);
+            if span_start == span_end {
+                continue;
+            }
+            self.draw_text(row, x, span_start, None);
+            let color = match style {
+                SpanStyle::Underline(color) => {
+                    Terminal::set_underline(true);
+                    Some(color)
+                }
+                SpanStyle::Fg(color) => Some(color),
+                SpanStyle::Swatch(color) => {
+                    self.terminal.set_bg_color(color);
+                    Some(swatch::contrast(color))
+                }
+                SpanStyle::Match(color) => {
+                    self.terminal.set_bg_color(color);
+                    None
+                }
+            };
+            if let Some(color) = color {
+                self.terminal.set_fg_color(color);
+            }
+            self.draw_text(row, span_start, span_end, color);
+            match style {
+                SpanStyle::Underline(_) => Terminal::set_underline(false),
+                SpanStyle::Swatch(_) | SpanStyle::Match(_) => self.restore_line_bg(y),
+                SpanStyle::Fg(_) => (),
+            }
+            Terminal::reset_fg_color();
+            x = span_end;
+        }
+        self.draw_text(row, x, end, None);
+    }
+    /// Tints the `colorcolumn` guides and, with `cursorcolumn`, the cursor's column.
+    fn draw_color_columns(&self, row: &Row, y: usize, start: usize, screen_y: usize) {
+        let tab_width = self.document.indent.tab_width;
+        let mut columns: Vec<(usize, Rgb)> = Vec::new();
+        if !self.hide_color_columns {
+            columns.extend(
+                self.document
+                    .color_columns
+                    .iter()
+                    .map(|column| (column.saturating_sub(1), self.config.theme.color_column_bg)),
+            );
+        }
+        if self.config.cursor_column && y != self.cursor_position.y {
+            let column = self
+                .document
+                .row(self.cursor_position.y)
+                .map_or(0, |row| row.column(self.cursor_position.x, tab_width));
+            columns.push((column, self.config.theme.cursor_line_bg));
+        }
+        let first_column = row.column(start, tab_width);
+        for (column, color) in columns {
+            if column < first_column || column.saturating_sub(first_column) >= self.text_width() {
+                continue;
+            }
+            let index = row.index_at_column(column, tab_width);
+            let grapheme = row
+                .graphemes()
+                .nth(index)
+                .filter(|grapheme| *grapheme != "\t" && row.column(index, tab_width) == column)
+                .unwrap_or(" ");
+            Terminal::cursor_position(&Position {
+                x: self
+                    .gutter_width()
+                    .saturating_add(column.saturating_sub(first_column)),
+                y: screen_y,
+            });
+            self.terminal.set_bg_color(color);
+            print!("{}", grapheme);
+            self.restore_line_bg(y);
+        }
+    }
+    fn selection_columns(&self, row: &Row, y: usize) -> Option<(usize, usize)> {
+        if self.mode != Mode::Visual || self.drawing_other.is_some() {
+            return None;
+        }
+        let (start, end) = self.selection();
+        if y < start.y || y > end.y {
+            return None;
+        }
+        let start_x = if y == start.y { start.x } else { 0 };
+        let end_x = if y == end.y { end.x } else { row.len() };
+        Some((start_x, end_x))
+    }
+    fn draw_overlay_line(&self, line: &str) {
         let width = self.terminal.size().width as usize;
-        let end = start.saturating_add(width);
-        let row = row.render(start, end);
-        println!("{}\r", row);
+        let mut line: String = line.chars().take(width).collect();
+        line.push_str(&" ".repeat(width.saturating_sub(line.chars().count())));
+        self.terminal.set_bg_color(self.config.theme.status_bg);
+        self.terminal.set_fg_color(self.config.theme.status_fg);
+        println!("{}\r", line);
+        Terminal::reset_fg_color();
+        Terminal::reset_bg_color();
     }
     #[allow(clippy::integer_arithmetic, clippy::integer_division)]
-    fn draw_rows(&self) {
-        let height = self.terminal.size().height;
+    /// The window title: the buffer's name, marked while it has unsaved changes.
+    fn window_title(&self) -> String {
+        let name = match &self.document.file_name {
+            Some(file_name) => Path::new(file_name).file_name().map_or_else(
+                || file_name.clone(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            None => self
+                .document
+                .title
+                .clone()
+                .unwrap_or_else(|| "[No Name]".to_string()),
+        };
+        if self.document.is_dirty() {
+            format!("{} (+) — hecto", name)
+        } else {
+            format!("{} — hecto", name)
+        }
+    }
+    /// A bar where typing inserts text, an underline while `r` waits for the
+    /// character to replace with, and a block otherwise.
+    fn cursor_shape(&self) -> CursorShape {
+        let pane_focused = self.terminal_pane.as_ref().is_some_and(|pane| pane.focused);
+        if self.prompting || self.mode == Mode::Insert && !pane_focused {
+            CursorShape::Bar
+        } else if self.mode == Mode::Normal && self.pending == "r" {
+            CursorShape::Underline
+        } else {
+            CursorShape::Block
+        }
+    }
+    /// What the text area depends on as a whole, for `draw_rows`.
+    fn view(&self) -> View {
+        let size = self.terminal.size();
+        let tab_width = self.document.indent.tab_width;
+        View {
+            file_name: self.document.file_name.clone(),
+            revision: self.document.revision(),
+            width: size.width,
+            top: self.window_top(),
+            height: self.text_height(),
+            gutter_width: self.gutter_width(),
+            text_width: self.text_width(),
+            offset_x: self.offset.x,
+            column_offset: self.column_delimiter().map(|_| self.column_view.offset),
+            theme: self.config.theme_name.clone(),
+            list: self.window.list,
+            list_chars: self.config.list_chars.spec(),
+            indent_guides: self.config.indent_guides,
+            cursor_column: self.config.cursor_column.then(|| {
+                self.document
+                    .row(self.cursor_position.y)
+                    .map_or(0, |row| row.column(self.cursor_position.x, tab_width))
+            }),
+            bidi: self.config.bidi,
+            tab_width,
+            indent_width: if self.document.indent.use_tabs {
+                tab_width
+            } else {
+                self.document.indent.size
+            },
+            color_columns: if self.hide_color_columns {
+                Vec::new()
+            } else {
+                self.document.color_columns.clone()
+            },
+            blame: self.config.blame,
+            blame_revision: self.document.blame.revision.filter(|_| self.config.blame),
+            floating: self.completion.is_some()
+                || self.overlay.is_some()
+                || self.popup.is_some()
+                || self.hover.is_some()
+                || self.signature.is_some()
+                || self.code_actions.is_some()
+                || self.spell_menu.is_some()
+                || self.debug_overlay,
+        }
+    }
+    /// What text row `terminal_row` shows, for `draw_rows`.
+    fn drawn_row(&self, terminal_row: usize, lines: &[(usize, usize, usize)]) -> DrawnRow {
+        let (y, start, end) = match lines.get(terminal_row) {
+            Some(line) => *line,
+            None => {
+                let height = self.text_height();
+                return DrawnRow::Filler(terminal_row == height / 3 && self.document.is_empty());
+            }
+        };
+        let row = self.document.row(y);
+        DrawnRow::Line {
+            y,
+            start,
+            end,
+            // A large file's rows are kept only as far as they are on screen.
+            text: match row {
+                Some(row) if self.document.large => {
+                    row.render(start, end, self.document.indent.tab_width)
+                }
+                Some(row) => row.as_str().to_string(),
+                None => String::new(),
+            },
+            spans: row.map(|row| self.spans(row, y)).unwrap_or_default(),
+            fold: self.document.folds.at(y),
+            change: self.document.git.change_at(y).map(git::Change::sign),
+            line_bg: self.line_bg(y).is_some(),
+            selection: row.and_then(|row| self.selection_columns(row, y)),
+        }
+    }
+    /// Draws every window: the others first, each having the editor's fields while it
+    /// is drawn, then the current one.
+    fn draw_windows(&mut self) {
+        let current = self.current_window;
+        if self.windows.len() > 1 {
+            let side_width = self.side_width();
+            for index in (0..self.windows.len()).filter(|index| *index != current) {
+                self.enter_window(index);
+                self.drawing_other = Some(side_width);
+                self.highlighter.poll();
+                self.refresh_minimap();
+                self.refresh_rainbow();
+                self.refresh_highlight();
+                self.refresh_column_view();
+                self.draw_window();
+                self.drawing_other = None;
+            }
+            self.enter_window(current);
+        }
+        self.draw_window();
+        // The status bar is drawn from here.
+        Terminal::cursor_position(&Position {
+            x: 0,
+            y: self.terminal.size().height as usize,
+        });
+    }
+    fn draw_window(&mut self) {
+        self.draw_rows();
+        self.draw_scrollbar();
+        self.draw_minimap();
+        self.draw_window_bar();
+    }
+    /// Draws the bar under the window, unless it is the last, naming its buffer; the
+    /// current window's bar is in the status bar's colors, the others' dimmed.
+    fn draw_window_bar(&self) {
+        if self.current_window.saturating_add(1) >= self.windows.len() {
+            return;
+        }
+        let y = self.window_top().saturating_add(self.text_height());
+        if y >= self.terminal.size().height as usize {
+            return;
+        }
+        Terminal::cursor_position(&Position { x: 0, y });
+        Terminal::clear_current_line();
+        if let Some(line) = self.overlay_line(y) {
+            self.draw_overlay_line(&line);
+            return;
+        }
+        let width = (self.terminal.size().width as usize).saturating_sub(self.side_width());
+        let text = unicode::truncate(&format!(" {}", self.status_segment(Segment::File)), width);
+        let theme = &self.config.theme;
+        self.terminal.set_bg_color(theme.status_bg);
+        self.terminal.set_fg_color(if self.drawing_other.is_some() {
+            theme.status_unfocused_fg
+        } else {
+            theme.status_fg
+        });
+        print!(
+            "{}{}",
+            text,
+            " ".repeat(width.saturating_sub(unicode::text_width(&text)))
+        );
+        Terminal::reset_fg_color();
+        Terminal::reset_bg_color();
+    }
+    /// What the prompt's completion menu, on the bottom rows, or the overlay shows on
+    /// screen row `y`, over the windows.
+    fn overlay_line(&self, y: usize) -> Option<String> {
+        let size = self.terminal.size();
+        let height = size.height as usize;
+        let menu = self
+            .completion
+            .as_ref()
+            .map_or_else(Vec::new, |menu| menu.lines(size.width as usize, height / 2));
+        y.checked_sub(height.saturating_sub(menu.len()))
+            .and_then(|index| menu.get(index))
+            .or_else(|| self.overlay.as_ref().and_then(|overlay| overlay.get(y)))
+            .cloned()
+    }
+    /// Draws the current window's rows. Rows that show what they did last time are left
+    /// alone, and when the view moved by a few lines, the terminal scrolls the rows that
+    /// stay in sight, so that only the rows coming into view are drawn.
+    fn draw_rows(&mut self) {
+        let (top, height) = (self.window_top(), self.text_height());
+        let lines = self.visible_lines();
+        let view = self.view();
+        let rows: Vec<DrawnRow> = (0..height)
+            .map(|terminal_row| self.drawn_row(terminal_row, &lines))
+            .collect();
+        let drawn = match self.drawn_rows.take() {
+            Some((drawn_view, drawn)) if drawn_view == view && !view.floating => drawn,
+            _ => Vec::new(),
+        };
+        let shift = scroll_shift(&drawn, &rows);
+        if shift != 0 {
+            Terminal::scroll(top, height, shift);
+        }
+        let text_end = view.gutter_width.saturating_add(view.text_width);
         for terminal_row in 0..height {
+            let screen_row = top.saturating_add(terminal_row);
+            let unchanged = (terminal_row as isize)
+                .checked_add(shift)
+                .and_then(|index| usize::try_from(index).ok())
+                .and_then(|index| drawn.get(index))
+                .is_some_and(|row| rows.get(terminal_row) == Some(row));
+            if unchanged {
+                // The panes beside the text are drawn again on top of a blank.
+                if text_end < view.width as usize {
+                    Terminal::cursor_position(&Position {
+                        x: text_end,
+                        y: screen_row,
+                    });
+                    print!("{}", termion::clear::UntilNewline);
+                }
+                continue;
+            }
+            Terminal::cursor_position(&Position {
+                x: 0,
+                y: screen_row,
+            });
             Terminal::clear_current_line();
-            if let Some(row) = self
-                .document
-                .row(self.offset.y.saturating_add(terminal_row as usize))
-            {
-                self.draw_row(row);
+            if let Some(line) = self.overlay_line(screen_row) {
+                self.draw_overlay_line(&line);
+            } else if let Some((y, start, end)) = lines.get(terminal_row) {
+                if let Some(row) = self.document.row(*y) {
+                    self.draw_row(row, *y, *start, *end, screen_row);
+                }
             } else if terminal_row == height / 3 && self.document.is_empty() {
                 self.draw_welcome_message();
             } else {
                 println!("~\r");
             }
         }
+        self.drawn_rows = Some((view, rows));
+    }
+}
+/// How many rows the text area moved up since `drawn`, or down when negative: the
+/// move, by up to half its height, that leaves the most rows showing what they did,
+/// if it leaves more than staying put.
+fn scroll_shift(drawn: &[DrawnRow], rows: &[DrawnRow]) -> isize {
+    if drawn.len() != rows.len() {
+        return 0;
+    }
+    let most = isize::try_from(rows.len() / 2).unwrap_or(0);
+    let kept = |shift: isize| {
+        rows.iter()
+            .enumerate()
+            .filter(|(index, row)| {
+                (*index as isize)
+                    .checked_add(shift)
+                    .and_then(|index| usize::try_from(index).ok())
+                    .and_then(|index| drawn.get(index))
+                    == Some(*row)
+            })
+            .count()
+    };
+    let mut best = (0, kept(0));
+    for shift in (-most..=most).filter(|shift| *shift != 0) {
+        let count = kept(shift);
+        if count > best.1 {
+            best = (shift, count);
+        }
+    }
+    best.0
+}
+/// `synonym` with the case of `word`: all capitals, or a capital first letter.
+fn match_case(synonym: &str, word: &str) -> String {
+    let mut letters = word.chars().filter(|c| c.is_alphabetic());
+    let first_upper = letters.next().is_some_and(char::is_uppercase);
+    if first_upper && word.chars().count() > 1 && letters.all(char::is_uppercase) {
+        return synonym.to_uppercase();
+    }
+    if first_upper {
+        let mut chars = synonym.chars();
+        return chars.next().map_or_else(String::new, |first| {
+            first.to_uppercase().chain(chars).collect()
+        });
+    }
+    synonym.to_string()
+}
+fn motion_key(c: char) -> Option<Key> {
+    match c {
+        'h' => Some(Key::Left),
+        'j' => Some(Key::Down),
+        'k' => Some(Key::Up),
+        'l' => Some(Key::Right),
+        '0' => Some(Key::Home),
+        '$' => Some(Key::End),
+        _ => None,
+    }
+}
+/// Opens `file_name` (or an empty document for an empty name), returning a status
+/// message worth showing about it.
+/// Decrypts `bytes`, asking for what it takes in the message bar.
+fn open_encrypted(
+    file_name: &str,
+    bytes: &[u8],
+    tool: crypt::Tool,
+    config: &Config,
+    terminal: &Terminal,
+) -> (Document, Option<String>) {
+    let mut ask = |prompt: &str, secret: bool| {
+        terminal
+            .read_line(&format!("{}: {}", file_name, prompt), secret)
+            .ok()
+            .flatten()
+    };
+    match crypt::open(tool, bytes, config.age_identity.as_deref(), &mut ask) {
+        Ok((plaintext, encryption)) => {
+            let status = format!(
+                "Decrypted {} ({}); it is saved encrypted and kept out of swap files.",
+                file_name,
+                encryption.describe()
+            );
+            let mut document = Document::from_contents(file_name, plaintext);
+            document.encryption = Some(encryption);
+            (document, Some(status))
+        }
+        Err(error) => (
+            Document::default(),
+            Some(format!("ERR: Could not decrypt {}: {}", file_name, error)),
+        ),
+    }
+}
+
+fn open_document(
+    file_name: &str,
+    config: &Config,
+    terminal: &Terminal,
+) -> (Document, Option<String>) {
+    let (mut document, mut status) = if file_name.is_empty() {
+        (Document::default(), None)
+    } else if let Some(url) = remote::Url::parse(file_name) {
+        match remote::read(&url, &mut |message| terminal.draw_message(message)) {
+            Ok(bytes) => match crypt::Tool::detect(file_name, &bytes) {
+                Some(tool) => open_encrypted(file_name, &bytes, tool, config, terminal),
+                None => (Document::from_contents(file_name, bytes), None),
+            },
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                let mut document = Document::default();
+                document.file_name = Some(file_name.to_string());
+                (document, None)
+            }
+            Err(error) => (
+                Document::default(),
+                Some(format!("ERR: Could not open {}: {}", url.describe(), error)),
+            ),
+        }
+    } else if file_name == "-" {
+        match Document::from_stdin() {
+            Ok(document) => (document, None),
+            Err(error) => (
+                Document::default(),
+                Some(format!("ERR: Could not read stdin: {}", error)),
+            ),
+        }
+    } else if let Some(tool) = crypt::detect_file(file_name) {
+        match std::fs::read(file_name) {
+            Ok(bytes) => open_encrypted(file_name, &bytes, tool, config, terminal),
+            Err(error) => (
+                Document::default(),
+                Some(format!(
+                    "ERR: Could not open file: {}: {}",
+                    file_name, error
+                )),
+            ),
+        }
+    } else {
+        match Document::open(file_name) {
+            Ok(document) => {
+                let status = if document.mixed_line_endings {
+                    Some(format!(
+                        "WARNING: Mixed line endings; the file will be saved with {}.",
+                        document.line_ending.name()
+                    ))
+                } else {
+                    None
+                };
+                (document, status)
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                let mut document = Document::default();
+                document.file_name = Some(file_name.to_string());
+                (document, None)
+            }
+            Err(_) => (
+                Document::default(),
+                Some(format!("ERR: Could not open file: {}", file_name)),
+            ),
+        }
+    };
+    document.save_options = config.save_options.clone();
+    document.indent = config.indent;
+    document.color_columns = config.color_columns.clone();
+    if let Some(file_name) = document.file_name.clone() {
+        let first_line = document.row(0).map(Row::as_str);
+        document.file_type = filetype::detect(&file_name, first_line).map(str::to_string);
+        if let Some(overrides) = document
+            .file_type
+            .as_ref()
+            .and_then(|file_type| config.filetypes.get(file_type))
+        {
+            overrides.apply_to(&mut document);
+        }
+        editorconfig::apply(&mut document, &file_name);
+    }
+    if config.modelines && !document.is_binary() {
+        modeline::apply(&mut document);
+    }
+    if !document.is_binary() {
+        document.detect_large(config.large_file_size, config.large_file_line_length);
+        if document.large && status.is_none() {
+            status = Some(
+                "Large file: highlighting, change markers, the word index and wrapping are off."
+                    .to_string(),
+            );
+        }
+    }
+    (document, status)
+}
+/// Describes the outcome of a workspace edit for the status bar.
+fn edit_summary(files: usize, edits: usize, failed: &[String]) -> String {
+    if failed.is_empty() {
+        format!("{} edits in {} files.", edits, files)
+    } else {
+        format!(
+            "{} edits in {} files; could not change {}.",
+            edits,
+            files,
+            failed.join(", ")
+        )
+    }
+}
+fn count_prefix(count: usize) -> Vec<Key> {
+    if count > 1 {
+        count.to_string().chars().map(Key::Char).collect()
+    } else {
+        Vec::new()
     }
 }
 fn die(e: std::io::Error) {
@@ -0,0 +1,204 @@
+use crate::Document;
+use crate::LineEnding;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Applies the `.editorconfig` files governing `file_name` to `document`, with files
+/// closer to it taking precedence.
+pub fn apply(document: &mut Document, file_name: &str) {
+    let path = Path::new(file_name);
+    let path = path
+        .canonicalize()
+        .or_else(|_| std::env::current_dir().map(|dir| dir.join(path)))
+        .unwrap_or_else(|_| path.to_path_buf());
+    for (name, value) in properties_for(&path) {
+        apply_property(document, &name, &value);
+    }
+}
+
+/// Collects the properties matching `path`, outermost file first so later entries win.
+fn properties_for(path: &Path) -> Vec<(String, String)> {
+    let mut files: Vec<(PathBuf, String)> = Vec::new();
+    for dir in path.ancestors().skip(1) {
+        let config = dir.join(".editorconfig");
+        if let Ok(text) = fs::read_to_string(&config) {
+            let root = is_root(&text);
+            files.push((dir.to_path_buf(), text));
+            if root {
+                break;
+            }
+        }
+    }
+    let mut properties = Vec::new();
+    for (dir, text) in files.iter().rev() {
+        let relative = path
+            .strip_prefix(dir)
+            .map(|relative| relative.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let mut matches = false;
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+                matches = section_matches(section, &relative);
+            } else if let Some((name, value)) = line.split_once('=') {
+                if matches {
+                    properties.push((name.trim().to_lowercase(), value.trim().to_lowercase()));
+                }
+            }
+        }
+    }
+    properties
+}
+
+fn is_root(text: &str) -> bool {
+    text.lines()
+        .map(str::trim)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| line.split_once('='))
+        .any(|(name, value)| {
+            name.trim().eq_ignore_ascii_case("root") && value.trim().eq_ignore_ascii_case("true")
+        })
+}
+
+fn apply_property(document: &mut Document, name: &str, value: &str) {
+    match (name, value) {
+        ("indent_style", "tab") => document.indent.use_tabs = true,
+        ("indent_style", "space") => document.indent.use_tabs = false,
+        ("indent_size", "tab") => document.indent.size = document.indent.tab_width,
+        ("indent_size", size) => {
+            if let Ok(size) = size.parse::<usize>() {
+                document.indent.size = size.max(1);
+            }
+        }
+        ("tab_width", width) => {
+            if let Ok(width) = width.parse::<usize>() {
+                document.indent.tab_width = width.max(1);
+            }
+        }
+        ("end_of_line", ending) => {
+            if let Some(ending) = LineEnding::from_name(ending) {
+                document.line_ending = ending;
+            }
+        }
+        ("trim_trailing_whitespace", flag) => {
+            document.save_options.trim_trailing_whitespace = flag == "true";
+        }
+        ("insert_final_newline", flag) => {
+            document.save_options.insert_final_newline = flag == "true";
+        }
+        _ => (),
+    }
+}
+
+/// Sections without a `/` match the file name in any directory; others are anchored
+/// at the directory of the `.editorconfig`.
+fn section_matches(section: &str, relative: &str) -> bool {
+    let pattern = if let Some(anchored) = section.strip_prefix('/') {
+        anchored.to_string()
+    } else if section.contains('/') {
+        section.to_string()
+    } else {
+        format!("**/{}", section)
+    };
+    expand_braces(&pattern).iter().any(|pattern| {
+        let pattern: Vec<char> = pattern.chars().collect();
+        let text: Vec<char> = relative.chars().collect();
+        glob_match(&pattern, &text)
+    })
+}
+
+/// Expands `{a,b}` alternatives into separate patterns.
+fn expand_braces(pattern: &str) -> Vec<String> {
+    let open = if let Some(open) = pattern.find('{') {
+        open
+    } else {
+        return vec![pattern.to_string()];
+    };
+    let close = if let Some(close) = pattern.get(open..).and_then(|rest| rest.find('}')) {
+        open.saturating_add(close)
+    } else {
+        return vec![pattern.to_string()];
+    };
+    let prefix = pattern.get(..open).unwrap_or_default();
+    let suffix = pattern.get(close.saturating_add(1)..).unwrap_or_default();
+    let alternatives = pattern
+        .get(open.saturating_add(1)..close)
+        .unwrap_or_default();
+    alternatives
+        .split(',')
+        .flat_map(|alternative| expand_braces(&format!("{}{}{}", prefix, alternative, suffix)))
+        .collect()
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern {
+        [] => text.is_empty(),
+        ['*', '*', '/', rest @ ..] => {
+            glob_match(rest, text)
+                || text.iter().enumerate().any(|(index, c)| {
+                    *c == '/'
+                        && glob_match(
+                            rest,
+                            text.get(index.saturating_add(1)..).unwrap_or_default(),
+                        )
+                })
+        }
+        ['*', '*', rest @ ..] => {
+            (0..=text.len()).any(|index| glob_match(rest, text.get(index..).unwrap_or_default()))
+        }
+        ['*', rest @ ..] => (0..=text.len())
+            .take_while(|index| *index == 0 || text.get(index.saturating_sub(1)) != Some(&'/'))
+            .any(|index| glob_match(rest, text.get(index..).unwrap_or_default())),
+        ['?', rest @ ..] => match text {
+            [c, text_rest @ ..] if *c != '/' => glob_match(rest, text_rest),
+            _ => false,
+        },
+        ['[', rest @ ..] => {
+            let close = if let Some(close) = rest.iter().position(|c| *c == ']') {
+                close
+            } else {
+                return text.first() == Some(&'[')
+                    && glob_match(rest, text.get(1..).unwrap_or_default());
+            };
+            let class = rest.get(..close).unwrap_or_default();
+            let after = rest.get(close.saturating_add(1)..).unwrap_or_default();
+            let (negated, class) = match class {
+                ['!', class @ ..] => (true, class),
+                _ => (false, class),
+            };
+            match text {
+                [c, text_rest @ ..] => {
+                    (class_contains(class, *c) != negated) && glob_match(after, text_rest)
+                }
+                [] => false,
+            }
+        }
+        [p, rest @ ..] => match text {
+            [c, text_rest @ ..] => p == c && glob_match(rest, text_rest),
+            [] => false,
+        },
+    }
+}
+
+fn class_contains(class: &[char], c: char) -> bool {
+    let mut index = 0;
+    while let Some(first) = class.get(index) {
+        if class.get(index.saturating_add(1)) == Some(&'-') {
+            if let Some(last) = class.get(index.saturating_add(2)) {
+                if (*first..=*last).contains(&c) {
+                    return true;
+                }
+                index = index.saturating_add(3);
+                continue;
+            }
+        }
+        if *first == c {
+            return true;
+        }
+        index = index.saturating_add(1);
+    }
+    false
+}
@@ -0,0 +1,70 @@
+use crate::unicode;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Spaces left between the widest cell of a column and the next column.
+const PADDING: usize = 2;
+
+/// The screen columns at which each tab of `lines` ends when tab-separated cells line
+/// up with the cells of the lines around them, as elastic tabstops have them.
+///
+/// A cell ended by a tab lines up with the cells in the same column of the adjacent
+/// lines that also have one, and the column is as wide as its widest cell plus some
+/// padding, but never narrower than `tab_width`, so indentation stays as it was. A
+/// line's last cell, which no tab ends, does not count.
+pub fn tab_stops(lines: &[&str], tab_width: usize) -> Vec<Vec<usize>> {
+    let cells: Vec<Vec<usize>> = lines
+        .iter()
+        .map(|line| {
+            let mut widths: Vec<usize> = line.split('\t').map(width).collect();
+            widths.pop();
+            widths
+        })
+        .collect();
+    let mut columns: Vec<Vec<usize>> = cells.iter().map(|widths| vec![0; widths.len()]).collect();
+    let deepest = cells.iter().map(Vec::len).max().unwrap_or(0);
+    for column in 0..deepest {
+        let mut start = 0;
+        while start < cells.len() {
+            if cells.get(start).is_none_or(|widths| widths.len() <= column) {
+                start = start.saturating_add(1);
+                continue;
+            }
+            let end = (start..cells.len())
+                .find(|y| cells.get(*y).is_none_or(|widths| widths.len() <= column))
+                .unwrap_or(cells.len());
+            let widest = cells
+                .get(start..end)
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|widths| widths.get(column))
+                .max()
+                .copied()
+                .unwrap_or(0);
+            let block_width = widest.saturating_add(PADDING).max(tab_width);
+            for widths in columns.get_mut(start..end).unwrap_or_default() {
+                if let Some(width) = widths.get_mut(column) {
+                    *width = block_width;
+                }
+            }
+            start = end;
+        }
+    }
+    columns
+        .into_iter()
+        .map(|widths| {
+            widths
+                .into_iter()
+                .scan(0_usize, |stop, width| {
+                    *stop = stop.saturating_add(width);
+                    Some(*stop)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+fn width(cell: &str) -> usize {
+    cell.graphemes(true)
+        .map(|grapheme| grapheme.chars().next().map_or(1, unicode::width).max(1))
+        .sum()
+}
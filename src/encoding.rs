@@ -0,0 +1,178 @@
+use std::io::{Error, ErrorKind};
+
+/// Windows-1252 code points for bytes 0x80..=0x9F; unassigned bytes map to C1 controls.
+const WINDOWS_1252: [u32; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x008D, 0x017D, 0x008F, 0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014,
+    0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+#[derive(PartialEq, Clone, Copy, Debug, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Latin1,
+    Windows1252,
+}
+
+impl Encoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16Le => "utf-16le",
+            Self::Utf16Be => "utf-16be",
+            Self::Latin1 => "latin1",
+            Self::Windows1252 => "cp1252",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().replace('_', "-").as_str() {
+            "utf-8" | "utf8" => Some(Self::Utf8),
+            "utf-16le" | "utf16le" | "utf-16" | "utf16" => Some(Self::Utf16Le),
+            "utf-16be" | "utf16be" => Some(Self::Utf16Be),
+            "latin1" | "latin-1" | "iso-8859-1" | "iso8859-1" => Some(Self::Latin1),
+            "cp1252" | "windows-1252" | "win1252" => Some(Self::Windows1252),
+            _ => None,
+        }
+    }
+    /// The byte order mark for this encoding, empty if it has none.
+    pub fn bom(self) -> &'static [u8] {
+        match self {
+            Self::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Self::Utf16Le => &[0xFF, 0xFE],
+            Self::Utf16Be => &[0xFE, 0xFF],
+            Self::Latin1 | Self::Windows1252 => &[],
+        }
+    }
+    /// Decodes file contents, returning the text and whether a byte order mark was present.
+    pub fn decode_file(self, bytes: &[u8]) -> (String, bool) {
+        let bom = self.bom();
+        if !bom.is_empty() && bytes.starts_with(bom) {
+            (
+                self.decode(bytes.get(bom.len()..).unwrap_or_default()),
+                true,
+            )
+        } else {
+            (self.decode(bytes), false)
+        }
+    }
+    /// Guesses the encoding of raw file contents. UTF-16 without a byte order mark is
+    /// looked for before UTF-8, since UTF-16 holding ASCII text is valid UTF-8 too.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&[0xFF, 0xFE]) {
+            return Self::Utf16Le;
+        }
+        if bytes.starts_with(&[0xFE, 0xFF]) {
+            return Self::Utf16Be;
+        }
+        if let Some(encoding) = Self::detect_utf16(bytes) {
+            return encoding;
+        }
+        if std::str::from_utf8(bytes).is_ok() {
+            return Self::Utf8;
+        }
+        if bytes.iter().any(|b| (0x80..=0x9F).contains(b)) {
+            Self::Windows1252
+        } else {
+            Self::Latin1
+        }
+    }
+    /// Recognizes UTF-16 without a byte order mark by the NUL high bytes of its Latin
+    /// characters: many on one side of each pair, hardly any on the other, with the
+    /// whole decoding cleanly.
+    fn detect_utf16(bytes: &[u8]) -> Option<Self> {
+        if bytes.is_empty() || !bytes.len().is_multiple_of(2) {
+            return None;
+        }
+        let pairs = bytes.len() / 2;
+        let odd_nuls = bytes.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+        let even_nuls = bytes.iter().step_by(2).filter(|b| **b == 0).count();
+        let mostly = |nuls: usize, others: usize| {
+            nuls.saturating_mul(3) > pairs && others.saturating_mul(10) < pairs
+        };
+        let encoding = if mostly(odd_nuls, even_nuls) {
+            Self::Utf16Le
+        } else if mostly(even_nuls, odd_nuls) {
+            Self::Utf16Be
+        } else {
+            return None;
+        };
+        let units = bytes.chunks_exact(2).map(|pair| match (encoding, pair) {
+            (Self::Utf16Le, [low, high]) | (Self::Utf16Be, [high, low]) => {
+                u16::from_le_bytes([*low, *high])
+            }
+            _ => 0,
+        });
+        char::decode_utf16(units)
+            .all(|c| c.is_ok())
+            .then_some(encoding)
+    }
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Self::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            Self::Utf16Le | Self::Utf16Be => {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|pair| match (self, pair) {
+                        (Self::Utf16Le, [low, high]) | (Self::Utf16Be, [high, low]) => {
+                            u16::from_le_bytes([*low, *high])
+                        }
+                        _ => 0,
+                    })
+                    .collect();
+                String::from_utf16_lossy(&units)
+            }
+            Self::Latin1 => bytes.iter().map(|b| char::from(*b)).collect(),
+            Self::Windows1252 => bytes
+                .iter()
+                .map(|b| {
+                    WINDOWS_1252
+                        .get(usize::from(b.wrapping_sub(0x80)))
+                        .filter(|_| *b >= 0x80)
+                        .and_then(|code| char::from_u32(*code))
+                        .unwrap_or_else(|| char::from(*b))
+                })
+                .collect(),
+        }
+    }
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Utf8 => Ok(text.as_bytes().to_vec()),
+            Self::Utf16Le | Self::Utf16Be => {
+                let mut bytes = Vec::with_capacity(text.len().saturating_mul(2));
+                for unit in text.encode_utf16() {
+                    if self == Self::Utf16Le {
+                        bytes.extend_from_slice(&unit.to_le_bytes());
+                    } else {
+                        bytes.extend_from_slice(&unit.to_be_bytes());
+                    }
+                }
+                Ok(bytes)
+            }
+            Self::Latin1 | Self::Windows1252 => {
+                text.chars().map(|c| self.encode_single_byte(c)).collect()
+            }
+        }
+    }
+    fn encode_single_byte(self, c: char) -> Result<u8, Error> {
+        let code = u32::from(c);
+        if self == Self::Windows1252 {
+            if let Some(index) = WINDOWS_1252.iter().position(|entry| *entry == code) {
+                return u8::try_from(index.saturating_add(0x80)).map_err(|_| unencodable(self, c));
+            }
+            if (0x80..=0x9F).contains(&code) {
+                return Err(unencodable(self, c));
+            }
+        }
+        u8::try_from(code).map_err(|_| unencodable(self, c))
+    }
+}
+
+fn unencodable(encoding: Encoding, c: char) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("{:?} cannot be encoded as {}", c, encoding.name()),
+    )
+}
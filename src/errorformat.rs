@@ -0,0 +1,125 @@
+use crate::quickfix::Entry;
+use crate::Position;
+
+/// The build tools whose error messages `parse` understands.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Tool {
+    /// `rustc` diagnostics with a `--> file:line:column` line under the message, and
+    /// test panics.
+    Cargo,
+    /// `file:line:column: message`, which most compilers and linters print.
+    Gcc,
+    /// Traceback locations `file:line: ExceptionType`, with the `E` lines above them.
+    Pytest,
+}
+
+impl Tool {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "cargo" => Some(Self::Cargo),
+            "gcc" => Some(Self::Gcc),
+            "pytest" => Some(Self::Pytest),
+            _ => None,
+        }
+    }
+    /// Guesses the tool from the program `command` runs.
+    pub fn for_command(command: &str) -> Self {
+        let program = command.split_whitespace().next().unwrap_or_default();
+        match program.rsplit('/').next().unwrap_or(program) {
+            "cargo" | "rustc" => Self::Cargo,
+            "pytest" | "py.test" => Self::Pytest,
+            _ if command.contains("pytest") => Self::Pytest,
+            _ => Self::Gcc,
+        }
+    }
+}
+
+/// Picks the errors and warnings out of the output of `tool`.
+pub fn parse(tool: Tool, lines: &[String]) -> Vec<Entry> {
+    match tool {
+        Tool::Cargo => parse_cargo(lines),
+        Tool::Gcc => lines.iter().filter_map(|line| location(line)).collect(),
+        Tool::Pytest => parse_pytest(lines),
+    }
+}
+
+fn parse_cargo(lines: &[String]) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut message: Option<&str> = None;
+    for line in lines {
+        let trimmed = line.trim();
+        if line.starts_with("error") || line.starts_with("warning") {
+            // Summaries such as `error: could not compile` point nowhere.
+            message = Some(line.as_str());
+        } else if let Some(place) = trimmed.strip_prefix("--> ") {
+            if let (Some(text), Some((file_name, position))) = (message.take(), place_of(place)) {
+                entries.push(Entry {
+                    file_name,
+                    position,
+                    text: text.to_string(),
+                });
+            }
+        } else if let Some((panic, place)) = line.split_once("' panicked at ") {
+            let place = place.trim_end_matches(':');
+            let place = place.split_once(", ").map_or(place, |(place, _)| place);
+            if let Some((file_name, position)) = place_of(place) {
+                entries.push(Entry {
+                    file_name,
+                    position,
+                    text: format!("{}' panicked", panic),
+                });
+            }
+        }
+    }
+    entries
+}
+
+fn parse_pytest(lines: &[String]) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut explanation: Option<&str> = None;
+    for line in lines {
+        if let Some(text) = line.strip_prefix("E ") {
+            explanation = explanation.or_else(|| Some(text.trim()));
+            continue;
+        }
+        if let Some(mut entry) = location(line).filter(|entry| entry.file_name.ends_with(".py")) {
+            if let Some(explanation) = explanation.take() {
+                entry.text = format!("{}: {}", entry.text, explanation);
+            }
+            entries.push(entry);
+        }
+    }
+    entries
+}
+
+/// Reads `file:line[:column]: message`.
+fn location(line: &str) -> Option<Entry> {
+    let mut fields = line.splitn(4, ':');
+    let file_name = fields
+        .next()
+        .filter(|name| !name.is_empty() && !name.contains(' '))?;
+    let y = one_based(fields.next()?)?;
+    let third = fields.next()?;
+    let (x, text) = match one_based(third) {
+        Some(x) => (x, fields.next()?),
+        None => (0, line.splitn(3, ':').nth(2)?),
+    };
+    Some(Entry {
+        file_name: file_name.to_string(),
+        position: Position { x, y },
+        text: text.trim().to_string(),
+    })
+}
+
+/// Reads `file:line:column`.
+fn place_of(place: &str) -> Option<(String, Position)> {
+    let mut fields = place.rsplitn(3, ':');
+    let x = one_based(fields.next()?)?;
+    let y = one_based(fields.next()?)?;
+    Some((fields.next()?.to_string(), Position { x, y }))
+}
+
+/// A 1-based number as a 0-based index.
+fn one_based(field: &str) -> Option<usize> {
+    field.trim().parse::<usize>().ok()?.checked_sub(1)
+}
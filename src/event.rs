@@ -0,0 +1,101 @@
+/// Something that happened in the editor, which hooks can act on.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Event {
+    /// A file was opened and shown.
+    BufOpen,
+    /// The buffer is about to be written.
+    BufWritePre,
+    /// The buffer was written.
+    BufWritePost,
+    CursorMoved,
+    ModeChanged,
+    /// The terminal window changed size.
+    Resize,
+    /// No key was pressed for a while.
+    Idle,
+}
+
+pub const EVENTS: [Event; 7] = [
+    Event::BufOpen,
+    Event::BufWritePre,
+    Event::BufWritePost,
+    Event::CursorMoved,
+    Event::ModeChanged,
+    Event::Resize,
+    Event::Idle,
+];
+
+impl Event {
+    /// The name used in the `[hooks]` config table and by `:autocmd`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::BufOpen => "buf_open",
+            Self::BufWritePre => "buf_write_pre",
+            Self::BufWritePost => "buf_write_post",
+            Self::CursorMoved => "cursor_moved",
+            Self::ModeChanged => "mode_changed",
+            Self::Resize => "resize",
+            Self::Idle => "idle",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        EVENTS.into_iter().find(|event| event.name() == name)
+    }
+}
+
+/// Editor features that run on events rather than being called directly.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Builtin {
+    AutoSave,
+    FormatOnSave,
+    RefreshGit,
+    /// Keeps the cursor on screen after the window shrinks.
+    ScrollToCursor,
+}
+
+#[derive(Clone, PartialEq)]
+pub enum Action {
+    Builtin(Builtin),
+    /// An ex command, as typed after `:`.
+    Command(String),
+}
+
+/// What runs on each event, in the order it was registered.
+#[derive(Clone, Default)]
+pub struct Hooks {
+    hooks: Vec<(Event, Action)>,
+}
+
+impl Hooks {
+    /// The hooks that make up the built-in features.
+    pub fn builtin() -> Self {
+        let mut hooks = Self::default();
+        hooks.on(Event::Idle, Action::Builtin(Builtin::AutoSave));
+        hooks.on(Event::BufWritePre, Action::Builtin(Builtin::FormatOnSave));
+        hooks.on(Event::BufWritePost, Action::Builtin(Builtin::RefreshGit));
+        hooks.on(Event::Resize, Action::Builtin(Builtin::ScrollToCursor));
+        hooks
+    }
+    pub fn on(&mut self, event: Event, action: Action) {
+        self.hooks.push((event, action));
+    }
+    pub fn extend(&mut self, other: &Self) {
+        self.hooks.extend(other.hooks.iter().cloned());
+    }
+    pub fn actions(&self, event: Event) -> Vec<Action> {
+        self.hooks
+            .iter()
+            .filter(|(other, _)| *other == event)
+            .map(|(_, action)| action.clone())
+            .collect()
+    }
+    /// The ex commands hooked to events, as `(event, command)`.
+    pub fn commands(&self) -> impl Iterator<Item = (Event, &str)> {
+        self.hooks
+            .iter()
+            .filter_map(|(event, action)| match action {
+                Action::Command(command) => Some((*event, command.as_str())),
+                Action::Builtin(_) => None,
+            })
+    }
+}
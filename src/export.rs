@@ -0,0 +1,139 @@
+use crate::color::{self, Depth};
+use crate::theme::Theme;
+use std::fmt::Write as _;
+use std::path::Path;
+use termion::color::Rgb;
+
+/// What `:export` writes.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// A standalone HTML page.
+    Html,
+    /// Text with terminal escape sequences, for `less -R` or `cat`.
+    Ansi,
+}
+
+impl Format {
+    /// HTML for `.html` and `.htm` files, ANSI text for anything else.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension)
+                if extension.eq_ignore_ascii_case("html")
+                    || extension.eq_ignore_ascii_case("htm") =>
+            {
+                Self::Html
+            }
+            _ => Self::Ansi,
+        }
+    }
+}
+
+/// How a piece of exported text looks; unset colors are the page's own.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Style {
+    pub fg: Option<Rgb>,
+    pub bg: Option<Rgb>,
+    /// Underlined in this color.
+    pub underline: Option<Rgb>,
+}
+
+/// A line of text, split where its style changes.
+pub type Line = Vec<(String, Style)>;
+
+/// Renders `lines` in the chosen format.
+pub fn render(format: Format, title: &str, lines: &[Line], theme: &Theme) -> String {
+    match format {
+        Format::Html => html(title, lines, theme),
+        Format::Ansi => ansi(lines, color::detect()),
+    }
+}
+
+/// A page showing `lines` in the theme's colors, with nothing to load from elsewhere.
+pub fn html(title: &str, lines: &[Line], theme: &Theme) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         </head>\n<body style=\"margin: 0; background: {}\">\n{}</body>\n</html>\n",
+        escape(title),
+        css(theme.text_bg),
+        html_fragment(lines, theme)
+    )
+}
+
+/// `lines` as a `<pre>` block carrying its own colors, to paste into other documents.
+pub fn html_fragment(lines: &[Line], theme: &Theme) -> String {
+    let mut block = format!(
+        "<pre style=\"margin: 0; padding: 1em; color: {}; background: {}; \
+         font-family: monospace\">",
+        css(theme.text_fg),
+        css(theme.text_bg),
+    );
+    for line in lines {
+        for (text, style) in line {
+            if *style == Style::default() {
+                block.push_str(&escape(text));
+                continue;
+            }
+            let mut rules = Vec::new();
+            if let Some(fg) = style.fg {
+                rules.push(format!("color: {}", css(fg)));
+            }
+            if let Some(bg) = style.bg {
+                rules.push(format!("background: {}", css(bg)));
+            }
+            if let Some(underline) = style.underline {
+                rules.push(format!(
+                    "text-decoration: underline {}; color: {}",
+                    css(underline),
+                    css(underline)
+                ));
+            }
+            let _ = write!(
+                block,
+                "<span style=\"{}\">{}</span>",
+                rules.join("; "),
+                escape(text)
+            );
+        }
+        block.push('\n');
+    }
+    block.push_str("</pre>\n");
+    block
+}
+
+/// `lines` with escape sequences for `depth` colors, on the terminal's own background.
+pub fn ansi(lines: &[Line], depth: Depth) -> String {
+    let mut text = String::new();
+    for line in lines {
+        for (piece, style) in line {
+            if *style == Style::default() {
+                text.push_str(piece);
+                continue;
+            }
+            if let Some(fg) = style.underline.or(style.fg) {
+                text.push_str(&color::sequence(fg, depth, false));
+            }
+            if let Some(bg) = style.bg {
+                text.push_str(&color::sequence(bg, depth, true));
+            }
+            if style.underline.is_some() {
+                text.push_str("\x1b[4m");
+            }
+            text.push_str(piece);
+            text.push_str("\x1b[0m");
+        }
+        text.push('\n');
+    }
+    text
+}
+
+fn css(color: Rgb) -> String {
+    let Rgb(r, g, b) = color;
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
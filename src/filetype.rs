@@ -0,0 +1,89 @@
+use std::path::Path;
+
+const EXTENSIONS: &[(&str, &[&str])] = &[
+    ("rust", &["rs"]),
+    ("python", &["py", "pyw"]),
+    ("c", &["c", "h"]),
+    ("cpp", &["cc", "cpp", "cxx", "hpp", "hh"]),
+    ("go", &["go"]),
+    ("javascript", &["js", "mjs", "cjs"]),
+    ("typescript", &["ts", "tsx"]),
+    ("java", &["java"]),
+    ("ruby", &["rb"]),
+    ("shell", &["sh", "bash", "zsh"]),
+    ("lua", &["lua"]),
+    ("toml", &["toml"]),
+    ("yaml", &["yml", "yaml"]),
+    ("json", &["json"]),
+    ("html", &["html", "htm"]),
+    ("css", &["css"]),
+    ("markdown", &["md", "markdown"]),
+    ("make", &["mk"]),
+    ("csv", &["csv"]),
+    ("tsv", &["tsv", "tab"]),
+];
+
+const FILE_NAMES: &[(&str, &str)] = &[
+    ("Makefile", "make"),
+    ("makefile", "make"),
+    ("GNUmakefile", "make"),
+    ("Cargo.lock", "toml"),
+    (".bashrc", "shell"),
+    (".profile", "shell"),
+];
+
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "python"),
+    ("sh", "shell"),
+    ("bash", "shell"),
+    ("zsh", "shell"),
+    ("ruby", "ruby"),
+    ("node", "javascript"),
+    ("lua", "lua"),
+];
+
+/// File types whose whole text is prose rather than code.
+const PROSE_TYPES: &[&str] = &["markdown", "text"];
+
+/// Whether buffers of `file_type` hold prose; buffers without a file type count as
+/// plain text.
+pub fn is_prose(file_type: Option<&str>) -> bool {
+    file_type.is_none_or(|file_type| PROSE_TYPES.contains(&file_type))
+}
+
+/// Guesses a file type name from the file name, falling back to a `#!` line.
+pub fn detect(file_name: &str, first_line: Option<&str>) -> Option<&'static str> {
+    let path = Path::new(file_name);
+    let base_name = path.file_name().and_then(|name| name.to_str());
+    if let Some(&(_, file_type)) = FILE_NAMES.iter().find(|(name, _)| Some(*name) == base_name) {
+        return Some(file_type);
+    }
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+        let extension = extension.to_ascii_lowercase();
+        if let Some(&(file_type, _)) = EXTENSIONS
+            .iter()
+            .find(|(_, extensions)| extensions.contains(&extension.as_str()))
+        {
+            return Some(file_type);
+        }
+    }
+    from_shebang(first_line?)
+}
+
+fn from_shebang(line: &str) -> Option<&'static str> {
+    let command = line.strip_prefix("#!")?;
+    let mut words = command.split_whitespace();
+    let mut program = words.next()?.rsplit('/').next()?;
+    if program == "env" {
+        program = words.find(|word| !word.starts_with('-'))?;
+    }
+    INTERPRETERS
+        .iter()
+        .find(|(interpreter, _)| {
+            program == *interpreter
+                || program
+                    .strip_prefix(interpreter)
+                    .is_some_and(|version| version.chars().all(|c| c.is_ascii_digit() || c == '.'))
+        })
+        .map(|(_, file_type)| *file_type)
+}
@@ -0,0 +1,151 @@
+use crate::json::Value;
+
+/// The closed folds of a buffer, as inclusive line ranges whose first line stays on
+/// screen as a summary of the rest. Folds may nest; the outermost one decides what
+/// is shown.
+#[derive(Default)]
+pub struct Folds {
+    closed: Vec<(usize, usize)>,
+}
+
+impl Folds {
+    pub fn is_empty(&self) -> bool {
+        self.closed.is_empty()
+    }
+    pub fn close(&mut self, start: usize, end: usize) {
+        if start < end && !self.closed.contains(&(start, end)) {
+            self.closed.push((start, end));
+        }
+    }
+    /// Opens the outermost closed fold containing line `y`, returning whether there
+    /// was one.
+    pub fn open(&mut self, y: usize) -> bool {
+        if let Some(fold) = self.at(y) {
+            self.closed.retain(|other| *other != fold);
+            true
+        } else {
+            false
+        }
+    }
+    pub fn open_all(&mut self) {
+        self.closed.clear();
+    }
+    /// The outermost closed fold containing line `y`.
+    pub fn at(&self, y: usize) -> Option<(usize, usize)> {
+        self.closed
+            .iter()
+            .filter(|(start, end)| *start <= y && y <= *end)
+            .min_by_key(|(start, end)| (*start, usize::MAX.saturating_sub(*end)))
+            .copied()
+    }
+    /// The line shown for line `y`: the first line of the fold hiding it, if any.
+    pub fn visible_line(&self, y: usize) -> usize {
+        self.at(y).map_or(y, |(start, _)| start)
+    }
+    /// The first line shown after line `y`.
+    pub fn next_line(&self, y: usize) -> usize {
+        self.at(y).map_or(y, |(_, end)| end).saturating_add(1)
+    }
+    /// The line shown before line `y`.
+    pub fn previous_line(&self, y: usize) -> usize {
+        self.visible_line(self.visible_line(y).saturating_sub(1))
+    }
+    /// Follows `count` lines at `at` being replaced by `new_count` lines. Folds touching
+    /// the replaced lines open; later ones move with their text.
+    pub fn replace_lines(&mut self, at: usize, count: usize, new_count: usize) {
+        let end = at.saturating_add(count);
+        self.closed
+            .retain(|(start, fold_end)| *fold_end < at || *start >= end);
+        for (start, fold_end) in &mut self.closed {
+            if *start >= end {
+                *start = start.saturating_add(new_count).saturating_sub(count);
+                *fold_end = fold_end.saturating_add(new_count).saturating_sub(count);
+            }
+        }
+    }
+}
+
+/// The indentation fold with its summary on line `y`, or else the one around `y`: the
+/// line followed by the lines indented deeper than it, up to the last such non-blank
+/// line.
+pub fn indent_fold(lines: &[&str], y: usize, tab_width: usize) -> Option<(usize, usize)> {
+    if let Some(fold) = block(lines, y, tab_width) {
+        return Some(fold);
+    }
+    // A blank line belongs with the text after it.
+    let level = lines
+        .iter()
+        .skip(y)
+        .find_map(|line| indent(line, tab_width))?;
+    let header = (0..y).rev().find(|above| {
+        lines
+            .get(*above)
+            .and_then(|line| indent(line, tab_width))
+            .is_some_and(|indent| indent < level)
+    })?;
+    block(lines, header, tab_width)
+}
+
+/// Every indentation fold of `lines`, outer ones first.
+pub fn indent_folds(lines: &[&str], tab_width: usize) -> Vec<(usize, usize)> {
+    (0..lines.len())
+        .filter_map(|y| block(lines, y, tab_width))
+        .collect()
+}
+
+fn block(lines: &[&str], header: usize, tab_width: usize) -> Option<(usize, usize)> {
+    let level = lines.get(header).and_then(|line| indent(line, tab_width))?;
+    let mut end = header;
+    for (y, line) in lines.iter().enumerate().skip(header.saturating_add(1)) {
+        match indent(line, tab_width) {
+            None => continue,
+            Some(indent) if indent > level => end = y,
+            Some(_) => break,
+        }
+    }
+    (end > header).then_some((header, end))
+}
+
+/// The width of the indentation of `line`, or `None` for a blank line.
+fn indent(line: &str, tab_width: usize) -> Option<usize> {
+    let mut width = 0_usize;
+    for c in line.chars() {
+        match c {
+            ' ' => width = width.saturating_add(1),
+            '\t' => {
+                let tab_width = tab_width.max(1);
+                width = width
+                    .saturating_add(tab_width)
+                    .saturating_sub(width % tab_width);
+            }
+            _ => return Some(width),
+        }
+    }
+    None
+}
+
+/// The innermost of `ranges` containing line `y`.
+pub fn innermost(ranges: &[(usize, usize)], y: usize) -> Option<(usize, usize)> {
+    ranges
+        .iter()
+        .filter(|(start, end)| *start <= y && y <= *end)
+        .min_by_key(|(start, end)| end.saturating_sub(*start))
+        .copied()
+}
+
+/// Reads a `textDocument/foldingRange` result as inclusive line ranges.
+pub fn from_lsp(result: &Value) -> Vec<(usize, usize)> {
+    let ranges = if let Some(ranges) = result.as_array() {
+        ranges
+    } else {
+        return Vec::new();
+    };
+    ranges
+        .iter()
+        .filter_map(|range| {
+            let start = range.get("startLine")?.as_usize()?;
+            let end = range.get("endLine")?.as_usize()?;
+            (start < end).then_some((start, end))
+        })
+        .collect()
+}
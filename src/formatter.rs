@@ -0,0 +1,36 @@
+use std::io::{Error, ErrorKind, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// Pipes `input` through the shell command `command` and returns its output.
+pub fn run(command: &str, input: &str) -> Result<String, Error> {
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| Error::new(ErrorKind::BrokenPipe, "formatter stdin unavailable"))?;
+    let input = input.to_string();
+    // Feed stdin from a thread so a formatter producing lots of output cannot deadlock.
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output()?;
+    writer
+        .join()
+        .map_err(|_| Error::other("formatter input thread panicked"))??;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let message = stderr
+            .lines()
+            .next()
+            .unwrap_or("formatter failed")
+            .to_string();
+        return Err(Error::other(message));
+    }
+    String::from_utf8(output.stdout)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "formatter output is not UTF-8"))
+}
@@ -0,0 +1,472 @@
+use crate::diff::{self, Hunk};
+use crate::terminal::Waker;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// How a buffer line differs from the committed file.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Added,
+    Modified,
+    /// Lines were deleted just below this one.
+    Removed,
+}
+
+impl Change {
+    pub fn sign(self) -> char {
+        match self {
+            Self::Added => '+',
+            Self::Modified => '~',
+            Self::Removed => '_',
+        }
+    }
+}
+
+/// A buffer's changes against the version of its file at `HEAD`.
+#[derive(Default)]
+pub struct Diff {
+    /// Lines of the committed file; `None` outside a repository or for untracked files.
+    base: Option<Vec<String>>,
+    loaded: bool,
+    pub hunks: Vec<Hunk>,
+    /// The document revision `hunks` were computed for.
+    pub revision: Option<usize>,
+}
+
+impl Diff {
+    /// Whether the committed file still has to be read.
+    pub fn needs_load(&self) -> bool {
+        !self.loaded
+    }
+    pub fn load(&mut self, file_name: Option<&str>) {
+        self.base = file_name.and_then(head_lines);
+        self.loaded = true;
+    }
+    /// Rereads the committed file on the next refresh, e.g. after a save.
+    pub fn invalidate(&mut self) {
+        self.loaded = false;
+        self.revision = None;
+    }
+    pub fn update(&mut self, lines: &[&str], revision: usize) {
+        self.hunks = match &self.base {
+            Some(base) => {
+                let base: Vec<&str> = base.iter().map(String::as_str).collect();
+                diff::lines(&base, lines)
+            }
+            None => Vec::new(),
+        };
+        self.revision = Some(revision);
+    }
+    /// The marker for line `y`, if it changed.
+    pub fn change_at(&self, y: usize) -> Option<Change> {
+        self.hunks.iter().find_map(|hunk| {
+            let end = hunk.new_start.saturating_add(hunk.new_len);
+            if hunk.new_len == 0 {
+                (y == marker_line(hunk)).then_some(Change::Removed)
+            } else if y >= hunk.new_start && y < end {
+                Some(if hunk.old_len == 0 {
+                    Change::Added
+                } else {
+                    Change::Modified
+                })
+            } else {
+                None
+            }
+        })
+    }
+    /// The hunk marked on line `y`.
+    pub fn hunk_at(&self, y: usize) -> Option<Hunk> {
+        self.hunks.iter().copied().find(|hunk| {
+            let start = marker_line(hunk);
+            y >= start && y < start.saturating_add(hunk.new_len.max(1))
+        })
+    }
+    /// The committed lines `hunk` replaces.
+    pub fn base_lines(&self, hunk: &Hunk) -> Vec<&str> {
+        self.base
+            .as_ref()
+            .and_then(|base| base.get(hunk.old_start..hunk.old_start.saturating_add(hunk.old_len)))
+            .unwrap_or_default()
+            .iter()
+            .map(String::as_str)
+            .collect()
+    }
+    /// The index of the hunk after (or before) line `y`, wrapping around the buffer.
+    pub fn next_hunk(&self, y: usize, forward: bool) -> Option<usize> {
+        let lines = self.hunks.iter().map(marker_line);
+        let index = if forward {
+            lines.clone().position(|line| line > y).unwrap_or(0)
+        } else {
+            lines
+                .clone()
+                .rposition(|line| line < y)
+                .unwrap_or_else(|| self.hunks.len().saturating_sub(1))
+        };
+        (index < self.hunks.len()).then_some(index)
+    }
+}
+
+/// The first line marked for `hunk`; deletions are marked on the line above.
+pub fn marker_line(hunk: &Hunk) -> usize {
+    if hunk.new_len == 0 {
+        hunk.new_start.saturating_sub(1)
+    } else {
+        hunk.new_start
+    }
+}
+
+/// The directory holding `file_name` and its name within it.
+fn split_path(file_name: &str) -> Option<(&Path, &str)> {
+    let path = Path::new(file_name);
+    let dir = path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    Some((dir, path.file_name()?.to_str()?))
+}
+
+/// Reads the lines of `file_name` as committed at `HEAD`.
+pub fn head_lines(file_name: &str) -> Option<Vec<String>> {
+    let (dir, name) = split_path(file_name)?;
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .arg("show")
+        .arg(format!("HEAD:./{}", name))
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+/// The branch and working tree state of the repository holding a file.
+#[derive(Default, PartialEq, Eq)]
+pub struct Status {
+    /// The checked out branch, or the abbreviated commit when detached.
+    pub branch: String,
+    /// Commits not yet pushed to, and not yet pulled from, the upstream branch.
+    pub ahead: usize,
+    pub behind: usize,
+    /// Whether any tracked file is modified or staged, or an untracked file exists.
+    pub dirty: bool,
+}
+
+impl Status {
+    /// Reads the status of the repository containing `file_name`, or of the working
+    /// directory for unnamed buffers. `None` outside a repository.
+    pub fn of(file_name: Option<&str>) -> Option<Self> {
+        let dir = file_name.map_or(Some(Path::new(".")), |file_name| {
+            split_path(file_name).map(|(dir, _)| dir)
+        })?;
+        let output = Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["status", "--porcelain=v2", "--branch"])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let mut status = Self::default();
+        let mut commit = "";
+        let output = String::from_utf8_lossy(&output.stdout);
+        for line in output.lines() {
+            if let Some(header) = line.strip_prefix("# ") {
+                let (key, value) = header.split_once(' ').unwrap_or((header, ""));
+                match key {
+                    "branch.oid" => commit = value,
+                    "branch.head" => status.branch = value.to_string(),
+                    "branch.ab" => {
+                        let mut counts = value
+                            .split_whitespace()
+                            .map(|count| count.get(1..).unwrap_or_default().parse().unwrap_or(0));
+                        status.ahead = counts.next().unwrap_or(0);
+                        status.behind = counts.next().unwrap_or(0);
+                    }
+                    _ => (),
+                }
+            } else if !line.is_empty() {
+                status.dirty = true;
+            }
+        }
+        if status.branch == "(detached)" {
+            status.branch = commit.chars().take(7).collect();
+        }
+        Some(status)
+    }
+    /// Reads the status like `of` on a thread of its own. The result arrives on the
+    /// returned channel, and `waker` tells the main loop it is there.
+    pub fn spawn(file_name: Option<String>, waker: &Waker) -> Receiver<Option<Self>> {
+        let (sender, receiver) = mpsc::channel();
+        let waker = waker.clone();
+        thread::spawn(move || {
+            if sender.send(Self::of(file_name.as_deref())).is_ok() {
+                waker.wake();
+            }
+        });
+        receiver
+    }
+    /// A short summary such as `main +2 -1 *`: commits ahead and behind the upstream,
+    /// and a star for uncommitted changes.
+    pub fn describe(&self) -> String {
+        let mut text = self.branch.clone();
+        if self.ahead > 0 {
+            text.push_str(&format!(" +{}", self.ahead));
+        }
+        if self.behind > 0 {
+            text.push_str(&format!(" -{}", self.behind));
+        }
+        if self.dirty {
+            text.push_str(" *");
+        }
+        text
+    }
+}
+
+/// Applies `hunk` of `file_name`, replacing committed lines `old` with buffer lines
+/// `new`, to the git index.
+pub fn stage_hunk(file_name: &str, hunk: &Hunk, old: &[&str], new: &[&str]) -> Result<(), Error> {
+    let (dir, name) = split_path(file_name)
+        .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "not a file name"))?;
+    let prefix = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["rev-parse", "--show-prefix"])
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()?;
+    if !prefix.status.success() {
+        return Err(Error::new(ErrorKind::NotFound, "not in a git repository"));
+    }
+    let path = format!(
+        "{}{}",
+        String::from_utf8_lossy(&prefix.stdout).trim_end(),
+        name
+    );
+    // Without context lines, a side's start is the line before an empty range.
+    let start = |start: usize, len: usize| {
+        if len == 0 {
+            start
+        } else {
+            start.saturating_add(1)
+        }
+    };
+    let mut patch = format!(
+        "--- a/{}\n+++ b/{}\n@@ -{},{} +{},{} @@\n",
+        path,
+        path,
+        start(hunk.old_start, hunk.old_len),
+        hunk.old_len,
+        start(hunk.new_start, hunk.new_len),
+        hunk.new_len
+    );
+    for line in old {
+        patch.push_str(&format!("-{}\n", line));
+    }
+    for line in new {
+        patch.push_str(&format!("+{}\n", line));
+    }
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["apply", "--cached", "--unidiff-zero", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child
+        .stdin
+        .take()
+        .ok_or_else(|| Error::new(ErrorKind::BrokenPipe, "git stdin unavailable"))?;
+    let writer = thread::spawn(move || stdin.write_all(patch.as_bytes()));
+    let output = child.wait_with_output()?;
+    writer
+        .join()
+        .map_err(|_| Error::other("git input thread panicked"))??;
+    if output.status.success() {
+        Ok(())
+    } else {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        Err(Error::other(
+            stderr
+                .lines()
+                .next()
+                .unwrap_or("git apply failed")
+                .to_string(),
+        ))
+    }
+}
+
+/// The commit that last touched a line.
+pub struct Commit {
+    pub author: String,
+    /// Author time in seconds since the Unix epoch.
+    pub time: u64,
+    pub summary: String,
+    /// False for lines changed in the buffer or working tree but not yet committed.
+    pub committed: bool,
+}
+
+impl Commit {
+    /// A one-line description such as `Jane Doe, 2024-03-01 • Fix the parser`.
+    pub fn describe(&self) -> String {
+        if self.committed {
+            format!("{}, {} • {}", self.author, date(self.time), self.summary)
+        } else {
+            "You • Uncommitted changes".to_string()
+        }
+    }
+}
+
+/// Who last changed each line of a buffer, computed on demand by `git blame` on a
+/// thread of its own.
+#[derive(Default)]
+pub struct Blame {
+    commits: HashMap<String, Commit>,
+    /// The commit hash for each buffer line.
+    lines: Vec<String>,
+    /// The document revision the blame was computed for.
+    pub revision: Option<usize>,
+    /// The revision being blamed and where its `git blame` output will arrive.
+    pending: Option<(usize, Receiver<Option<String>>)>,
+}
+
+impl Blame {
+    /// Starts blaming `contents`, the buffer text of `file_name`, against the
+    /// repository, unless a blame is already running; `poll` takes the result in and
+    /// `waker` tells the main loop it is there. Outside a repository or for untracked
+    /// files every line is left unblamed.
+    pub fn update(
+        &mut self,
+        file_name: Option<&str>,
+        contents: String,
+        revision: usize,
+        waker: &Waker,
+    ) {
+        if self.pending.is_some() {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        let file_name = file_name.map(str::to_string);
+        let waker = waker.clone();
+        thread::spawn(move || {
+            let output = file_name.and_then(|file_name| blame_porcelain(&file_name, &contents));
+            if sender.send(output).is_ok() {
+                waker.wake();
+            }
+        });
+        self.pending = Some((revision, receiver));
+    }
+    /// Takes in a finished blame. Returns whether one was waiting.
+    pub fn poll(&mut self) -> bool {
+        let (revision, output) = match &self.pending {
+            Some((revision, receiver)) => match receiver.try_recv() {
+                Ok(output) => (*revision, output),
+                Err(mpsc::TryRecvError::Empty) => return false,
+                Err(mpsc::TryRecvError::Disconnected) => (*revision, None),
+            },
+            None => return false,
+        };
+        self.pending = None;
+        self.commits.clear();
+        self.lines.clear();
+        if let Some(output) = output {
+            self.parse(&output);
+        }
+        self.revision = Some(revision);
+        true
+    }
+    pub fn commit_at(&self, y: usize) -> Option<&Commit> {
+        self.lines.get(y).and_then(|hash| self.commits.get(hash))
+    }
+    /// Reads `git blame --porcelain` output, where each line starts with a header
+    /// naming its commit, followed by the commit's details the first time it appears.
+    fn parse(&mut self, output: &str) {
+        let mut hash = String::new();
+        for line in output.lines() {
+            if line.starts_with('\t') {
+                self.lines.push(hash.clone());
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            if key.len() == 40 && key.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                hash = key.to_string();
+                self.commits.entry(hash.clone()).or_insert_with(|| Commit {
+                    author: String::new(),
+                    time: 0,
+                    summary: String::new(),
+                    committed: key.bytes().any(|byte| byte != b'0'),
+                });
+                continue;
+            }
+            if let Some(commit) = self.commits.get_mut(&hash) {
+                match key {
+                    "author" => commit.author = value.to_string(),
+                    "author-time" => commit.time = value.parse().unwrap_or_default(),
+                    "summary" => commit.summary = value.to_string(),
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+/// Runs `git blame` on `file_name` with `contents` standing in for the file.
+fn blame_porcelain(file_name: &str, contents: &str) -> Option<String> {
+    let (dir, name) = split_path(file_name)?;
+    let mut child = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["blame", "--porcelain", "--contents", "-", "--"])
+        .arg(name)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdin = child.stdin.take()?;
+    let input = contents.to_string();
+    let writer = thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().ok()?;
+    writer.join().ok()?.ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Formats a Unix time as a `YYYY-MM-DD` date in UTC.
+#[allow(clippy::integer_arithmetic, clippy::integer_division)]
+fn date(seconds: u64) -> String {
+    // Howard Hinnant's days-to-civil algorithm, shifted to start years in March.
+    let days = seconds / 86_400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,164 @@
+use crate::commands::{self, Bindings};
+use crate::mode_keys::{self, KeyMode};
+use crate::options;
+use crate::script;
+use crate::Document;
+use crate::Key;
+use crate::Position;
+
+pub const TITLE: &str = "[Help]";
+
+/// The keys of `mode` from the mode key registry, as help lines.
+fn mode_key_lines(mode: KeyMode) -> impl Iterator<Item = String> {
+    mode_keys::MODE_KEYS
+        .iter()
+        .filter(move |mode_key| mode_key.mode == mode)
+        .map(|mode_key| format!("  {:<22} {}", mode_key.name(), mode_key.description))
+}
+
+/// Generates the help text from the command, ex command, mode key and option registries.
+pub fn text(bindings: &Bindings) -> String {
+    let mut lines = vec![
+        "*help*  hecto help".to_string(),
+        String::new(),
+        "Topics: |keys| |commands| |ex-commands| |normal-mode| |visual-mode| |insert-mode| \
+         |options| |lua|"
+            .to_string(),
+        "Move onto a |topic| and press Enter to follow it; Ctrl-O jumps back.".to_string(),
+        String::new(),
+        "*keys*  Key bindings".to_string(),
+        String::new(),
+    ];
+    for info in commands::COMMANDS {
+        for key in bindings.keys_for(info.command) {
+            lines.push(format!("  {:<12} {}", commands::key_name(key), info.name));
+        }
+        for keys in bindings.sequences_for(info.command) {
+            lines.push(format!("  {:<12} {} (normal mode)", keys, info.name));
+        }
+    }
+    lines.push(String::new());
+    lines.push("*commands*  Commands (run them from the palette)".to_string());
+    lines.push(String::new());
+    for info in commands::COMMANDS {
+        let keys: Vec<String> = bindings
+            .keys_for(info.command)
+            .into_iter()
+            .map(commands::key_name)
+            .chain(
+                bindings
+                    .sequences_for(info.command)
+                    .into_iter()
+                    .map(str::to_string),
+            )
+            .collect();
+        let keys = if keys.is_empty() {
+            "unbound".to_string()
+        } else {
+            keys.join(", ")
+        };
+        lines.push(format!(
+            "  {:<22} {:<12} {}",
+            info.name, keys, info.description
+        ));
+        lines.push(format!("  {:<22} config: keys.{}", "", info.id));
+    }
+    lines.push(String::new());
+    lines.push("*ex-commands*  Command line (:)".to_string());
+    lines.push(String::new());
+    for info in commands::EX_COMMANDS {
+        lines.push(format!("  :{:<18} {}", info.syntax(), info.description));
+    }
+    lines.push(String::new());
+    lines.push("*normal-mode*  Normal mode (Esc)".to_string());
+    lines.push(String::new());
+    lines.extend(mode_key_lines(KeyMode::Normal));
+    lines.push(format!(
+        "  {:<22} one of {}",
+        "{motion}",
+        mode_keys::motions()
+    ));
+    for (keys, description) in mode_keys::OBJECTS {
+        lines.push(format!("  {:<22} {{object}}: {}", keys, description));
+    }
+    lines.push(format!(
+        "  {:<22} {} (config: leader)",
+        commands::LEADER,
+        commands::key_name(Key::Char(bindings.leader()))
+    ));
+    for info in commands::COMMANDS {
+        for keys in bindings.sequences_for(info.command) {
+            lines.push(format!("  {:<22} {}", keys, info.description));
+        }
+    }
+    lines.push(String::new());
+    lines.push("*visual-mode*  Visual mode (v)".to_string());
+    lines.push(String::new());
+    lines.extend(mode_key_lines(KeyMode::Visual));
+    lines.push(String::new());
+    lines.push("*insert-mode*  Insert mode".to_string());
+    lines.push(String::new());
+    lines.extend(mode_key_lines(KeyMode::Insert));
+    lines.push(String::new());
+    lines.push(
+        "*options*  Options (:set name, :set noname, :set name=value, :set name?)".to_string(),
+    );
+    lines.push(String::new());
+    for option in options::OPTIONS {
+        let name = if option.aliases.is_empty() {
+            option.name.to_string()
+        } else {
+            format!("{} ({})", option.name, option.aliases.join(", "))
+        };
+        lines.push(format!(
+            "  {:<22} {:<7} {}",
+            name,
+            option.scope.name(),
+            option.description
+        ));
+    }
+    lines.push(String::new());
+    lines.push("*lua*  Lua (init.lua in the config directory, :lua <code>)".to_string());
+    lines.push(String::new());
+    for (function, description) in script::API {
+        lines.push(format!("  hecto.{:<32} {}", function, description));
+    }
+    lines.join("\n")
+}
+
+/// Finds `*topic*`, or else a line describing `topic`, in the help document.
+pub fn find_topic(document: &Document, topic: &str) -> Option<Position> {
+    let tag = format!("*{}*", topic);
+    let lines = || (0..document.len()).filter_map(|y| document.row(y).map(|row| (y, row.as_str())));
+    lines()
+        .find(|(_, line)| line.starts_with(&tag))
+        .or_else(|| {
+            lines().find(|(_, line)| {
+                let line = line.trim_start().trim_start_matches(':');
+                line.split(|c: char| c.is_whitespace() || c == ',')
+                    .next()
+                    .is_some_and(|word| word.eq_ignore_ascii_case(topic))
+            })
+        })
+        .or_else(|| {
+            let topic = topic.to_ascii_lowercase();
+            lines().find(|(_, line)| line.to_ascii_lowercase().contains(&topic))
+        })
+        .map(|(y, _)| Position { x: 0, y })
+}
+
+/// Returns the `|topic|` link under `at`, if any.
+pub fn link_at(document: &Document, at: &Position) -> Option<String> {
+    let graphemes: Vec<&str> = document.row(at.y)?.graphemes().collect();
+    let start = graphemes.get(..=at.x)?.iter().rposition(|g| *g == "|")?;
+    let length = graphemes
+        .get(start.saturating_add(1)..)?
+        .iter()
+        .position(|g| *g == "|")?;
+    let end = start.saturating_add(1).saturating_add(length);
+    if at.x > end {
+        return None;
+    }
+    let topic = graphemes.get(start.saturating_add(1)..end)?.concat();
+    (!topic.is_empty() && !topic.contains(' ')).then_some(topic)
+}
@@ -0,0 +1,79 @@
+use crate::Encoding;
+use crate::Row;
+
+pub const BYTES_PER_ROW: usize = 16;
+const HEX_START: usize = 10;
+const ASCII_START: usize = HEX_START + BYTES_PER_ROW * 3 + 2;
+const SNIFF_LENGTH: usize = 8000;
+
+pub enum Column {
+    Hex { byte: usize, high: bool },
+    Ascii { byte: usize },
+}
+
+/// Treats contents with NUL bytes as binary, unless they look like UTF-16 text.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = bytes.get(..SNIFF_LENGTH).unwrap_or(bytes);
+    sample.contains(&0)
+        && !matches!(
+            Encoding::detect(bytes),
+            Encoding::Utf16Le | Encoding::Utf16Be
+        )
+}
+
+/// Lays out bytes as `offset  hex bytes  |ascii|` rows.
+pub fn render_rows(bytes: &[u8]) -> Vec<Row> {
+    bytes
+        .chunks(BYTES_PER_ROW)
+        .enumerate()
+        .map(|(index, chunk)| render_row(index, chunk))
+        .collect()
+}
+
+pub fn render_row(index: usize, chunk: &[u8]) -> Row {
+    let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let ascii: String = chunk
+        .iter()
+        .map(|byte| {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                char::from(*byte)
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    Row::from(
+        format!(
+            "{:08x}  {:<width$}  |{}|",
+            index.saturating_mul(BYTES_PER_ROW),
+            hex.join(" "),
+            ascii,
+            width = BYTES_PER_ROW * 3 - 1
+        )
+        .as_str(),
+    )
+}
+
+/// Maps a screen column of a hex row to the byte it edits.
+pub fn column_at(x: usize) -> Option<Column> {
+    if (HEX_START..ASCII_START.saturating_sub(3)).contains(&x) {
+        let offset = x.saturating_sub(HEX_START);
+        let byte = offset / 3;
+        match offset % 3 {
+            0 => Some(Column::Hex { byte, high: true }),
+            1 => Some(Column::Hex { byte, high: false }),
+            _ => None,
+        }
+    } else if (ASCII_START..ASCII_START.saturating_add(BYTES_PER_ROW)).contains(&x) {
+        Some(Column::Ascii {
+            byte: x.saturating_sub(ASCII_START),
+        })
+    } else {
+        None
+    }
+}
+
+/// The screen column of the high nibble of `byte` within a row.
+pub fn hex_column(byte: usize) -> usize {
+    HEX_START.saturating_add(byte.saturating_mul(3))
+}
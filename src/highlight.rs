@@ -0,0 +1,1169 @@
+use crate::terminal::Waker;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Rows highlighted between messages to the main loop, so the first ones show while
+/// the rest of a large buffer is still being worked through.
+const CHUNK: usize = 256;
+/// Lines longer than this, in bytes, are left plain and do not change the state the
+/// next line starts in, so a single huge line cannot hold up the rest of the buffer.
+const MAX_LINE_LENGTH: usize = 16_384;
+
+/// How a file type writes comments, strings and keywords.
+#[derive(Clone, Copy, Default, PartialEq)]
+pub struct Syntax {
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+    /// Characters that open and close a string.
+    pub quotes: &'static str,
+    /// Those of `quotes` whose strings may go on past the end of the line.
+    pub multiline_quotes: &'static str,
+    /// Whether a quote written three times opens a string that only the same three
+    /// close, as Python's docstrings, running over any number of lines.
+    pub triple_quotes: bool,
+    /// Whether `'` starts a character literal, and is otherwise a lifetime or label.
+    pub char_literals: bool,
+    pub keywords: &'static [&'static str],
+}
+
+const C_LIKE: Syntax = Syntax {
+    line_comment: Some("//"),
+    block_comment: Some(("/*", "*/")),
+    quotes: "\"",
+    multiline_quotes: "",
+    triple_quotes: false,
+    char_literals: true,
+    keywords: &[],
+};
+const HASH_COMMENTS: Syntax = Syntax {
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: "\"'",
+    multiline_quotes: "",
+    triple_quotes: false,
+    char_literals: false,
+    keywords: &[],
+};
+
+const SYNTAXES: &[(&str, Syntax)] = &[
+    (
+        "rust",
+        Syntax {
+            multiline_quotes: "\"",
+            keywords: &[
+                "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else",
+                "enum", "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match",
+                "mod", "move", "mut", "pub", "ref", "return", "self", "Self", "static", "struct",
+                "super", "trait", "true", "type", "unsafe", "use", "where", "while",
+            ],
+            ..C_LIKE
+        },
+    ),
+    (
+        "c",
+        Syntax {
+            keywords: C_KEYWORDS,
+            ..C_LIKE
+        },
+    ),
+    (
+        "cpp",
+        Syntax {
+            keywords: &[
+                "auto",
+                "bool",
+                "break",
+                "case",
+                "catch",
+                "char",
+                "class",
+                "const",
+                "constexpr",
+                "continue",
+                "default",
+                "delete",
+                "do",
+                "double",
+                "else",
+                "enum",
+                "explicit",
+                "false",
+                "float",
+                "for",
+                "friend",
+                "if",
+                "inline",
+                "int",
+                "long",
+                "namespace",
+                "new",
+                "nullptr",
+                "operator",
+                "private",
+                "protected",
+                "public",
+                "return",
+                "short",
+                "signed",
+                "sizeof",
+                "static",
+                "struct",
+                "switch",
+                "template",
+                "this",
+                "throw",
+                "true",
+                "try",
+                "typedef",
+                "typename",
+                "union",
+                "unsigned",
+                "using",
+                "virtual",
+                "void",
+                "while",
+            ],
+            ..C_LIKE
+        },
+    ),
+    (
+        "java",
+        Syntax {
+            keywords: &[
+                "abstract",
+                "boolean",
+                "break",
+                "byte",
+                "case",
+                "catch",
+                "char",
+                "class",
+                "continue",
+                "default",
+                "do",
+                "double",
+                "else",
+                "enum",
+                "extends",
+                "false",
+                "final",
+                "finally",
+                "float",
+                "for",
+                "if",
+                "implements",
+                "import",
+                "instanceof",
+                "int",
+                "interface",
+                "long",
+                "new",
+                "null",
+                "package",
+                "private",
+                "protected",
+                "public",
+                "return",
+                "short",
+                "static",
+                "super",
+                "switch",
+                "this",
+                "throw",
+                "throws",
+                "true",
+                "try",
+                "void",
+                "while",
+            ],
+            ..C_LIKE
+        },
+    ),
+    (
+        "go",
+        Syntax {
+            quotes: "\"`",
+            multiline_quotes: "`",
+            keywords: &[
+                "break",
+                "case",
+                "chan",
+                "const",
+                "continue",
+                "default",
+                "defer",
+                "else",
+                "false",
+                "fallthrough",
+                "for",
+                "func",
+                "go",
+                "goto",
+                "if",
+                "import",
+                "interface",
+                "map",
+                "nil",
+                "package",
+                "range",
+                "return",
+                "select",
+                "struct",
+                "switch",
+                "true",
+                "type",
+                "var",
+            ],
+            ..C_LIKE
+        },
+    ),
+    (
+        "javascript",
+        Syntax {
+            quotes: "\"'`",
+            multiline_quotes: "`",
+            char_literals: false,
+            keywords: JS_KEYWORDS,
+            ..C_LIKE
+        },
+    ),
+    (
+        "typescript",
+        Syntax {
+            quotes: "\"'`",
+            multiline_quotes: "`",
+            char_literals: false,
+            keywords: JS_KEYWORDS,
+            ..C_LIKE
+        },
+    ),
+    (
+        "python",
+        Syntax {
+            triple_quotes: true,
+            keywords: &[
+                "and", "as", "assert", "async", "await", "break", "class", "continue", "def",
+                "del", "elif", "else", "except", "False", "finally", "for", "from", "global", "if",
+                "import", "in", "is", "lambda", "None", "nonlocal", "not", "or", "pass", "raise",
+                "return", "True", "try", "while", "with", "yield",
+            ],
+            ..HASH_COMMENTS
+        },
+    ),
+    (
+        "ruby",
+        Syntax {
+            keywords: &[
+                "begin", "break", "case", "class", "def", "do", "else", "elsif", "end", "ensure",
+                "false", "for", "if", "in", "module", "next", "nil", "not", "require", "rescue",
+                "return", "self", "then", "true", "unless", "until", "when", "while", "yield",
+            ],
+            ..HASH_COMMENTS
+        },
+    ),
+    (
+        "shell",
+        Syntax {
+            keywords: &[
+                "case", "do", "done", "elif", "else", "esac", "exit", "export", "fi", "for",
+                "function", "if", "in", "local", "return", "then", "until", "while",
+            ],
+            ..HASH_COMMENTS
+        },
+    ),
+    (
+        "make",
+        Syntax {
+            keywords: &[
+                "define", "else", "endef", "endif", "export", "ifdef", "ifeq", "ifndef", "ifneq",
+                "include", "override",
+            ],
+            ..HASH_COMMENTS
+        },
+    ),
+    (
+        "toml",
+        Syntax {
+            keywords: &["false", "true"],
+            ..HASH_COMMENTS
+        },
+    ),
+    (
+        "yaml",
+        Syntax {
+            keywords: &["false", "null", "true"],
+            ..HASH_COMMENTS
+        },
+    ),
+    (
+        "lua",
+        Syntax {
+            line_comment: Some("--"),
+            block_comment: Some(("--[[", "]]")),
+            quotes: "\"'",
+            multiline_quotes: "",
+            triple_quotes: false,
+            char_literals: false,
+            keywords: &[
+                "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto",
+                "if", "in", "local", "nil", "not", "or", "repeat", "return", "then", "true",
+                "until", "while",
+            ],
+        },
+    ),
+    (
+        "json",
+        Syntax {
+            line_comment: None,
+            block_comment: None,
+            quotes: "\"",
+            multiline_quotes: "",
+            triple_quotes: false,
+            char_literals: false,
+            keywords: &["false", "null", "true"],
+        },
+    ),
+    (
+        "css",
+        Syntax {
+            line_comment: None,
+            block_comment: Some(("/*", "*/")),
+            quotes: "\"'",
+            multiline_quotes: "",
+            triple_quotes: false,
+            char_literals: false,
+            keywords: &["important", "inherit", "initial", "none", "unset"],
+        },
+    ),
+    (
+        "html",
+        Syntax {
+            line_comment: None,
+            block_comment: Some(("<!--", "-->")),
+            quotes: "\"",
+            multiline_quotes: "",
+            triple_quotes: false,
+            char_literals: false,
+            keywords: &[],
+        },
+    ),
+];
+
+const C_KEYWORDS: &[&str] = &[
+    "break", "case", "char", "const", "continue", "default", "do", "double", "else", "enum",
+    "extern", "float", "for", "goto", "if", "int", "long", "register", "return", "short", "signed",
+    "sizeof", "static", "struct", "switch", "typedef", "union", "unsigned", "void", "volatile",
+    "while",
+];
+const JS_KEYWORDS: &[&str] = &[
+    "async",
+    "await",
+    "break",
+    "case",
+    "catch",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "delete",
+    "do",
+    "else",
+    "export",
+    "extends",
+    "false",
+    "finally",
+    "for",
+    "from",
+    "function",
+    "if",
+    "import",
+    "in",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "of",
+    "return",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "type",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+    "while",
+    "yield",
+];
+
+/// How buffers of `file_type` are highlighted, if hecto knows the type.
+pub fn syntax(file_type: Option<&str>) -> Option<Syntax> {
+    SYNTAXES
+        .iter()
+        .find(|(name, _)| Some(*name) == file_type)
+        .map(|(_, syntax)| *syntax)
+}
+
+/// What a highlighted piece of text is.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Token {
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+/// Graphemes `start..end` of a line and what they are.
+pub type Span = (usize, usize, Token);
+
+/// What a line leaves open for the next one.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct State {
+    in_comment: bool,
+    /// The quote of a string still open, and whether it was tripled.
+    in_string: Option<(char, bool)>,
+}
+
+/// Highlights `line`, which starts in `state`, returning its spans and the state the
+/// next line starts in.
+pub fn line(line: &str, mut state: State, syntax: &Syntax) -> (Vec<Span>, State) {
+    let graphemes: Vec<(usize, &str)> = line.grapheme_indices(true).collect();
+    // The index of the grapheme at or after byte `offset`.
+    let at = |offset: usize| graphemes.partition_point(|(start, _)| *start < offset);
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut index = 0;
+    while let Some(&(offset, grapheme)) = graphemes.get(index) {
+        let rest = line.get(offset..).unwrap_or_default();
+        if state.in_comment {
+            index = match syntax
+                .block_comment
+                .filter(|(_, close)| rest.starts_with(close))
+            {
+                Some((_, close)) => {
+                    state.in_comment = false;
+                    let end = at(offset.saturating_add(close.len()));
+                    spans.push((start, end, Token::Comment));
+                    end
+                }
+                None => index.saturating_add(1),
+            };
+            continue;
+        }
+        if let Some((quote, tripled)) = state.in_string {
+            let count = if tripled { 3 } else { 1 };
+            index = if grapheme == "\\" {
+                index.saturating_add(2)
+            } else if opens_with(rest, quote, count) {
+                state.in_string = None;
+                let end = at(offset.saturating_add(quote.len_utf8().saturating_mul(count)));
+                spans.push((start, end, Token::String));
+                end
+            } else {
+                index.saturating_add(1)
+            };
+            continue;
+        }
+        start = index;
+        if let Some((open, _)) = syntax
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open))
+        {
+            state.in_comment = true;
+            index = at(offset.saturating_add(open.len()));
+            continue;
+        }
+        if syntax
+            .line_comment
+            .is_some_and(|marker| rest.starts_with(marker))
+        {
+            spans.push((index, graphemes.len(), Token::Comment));
+            return (spans, state);
+        }
+        let mut chars = grapheme.chars();
+        let c = match (chars.next(), chars.next()) {
+            (Some(c), None) => c,
+            _ => {
+                index = index.saturating_add(1);
+                continue;
+            }
+        };
+        if c == '\'' && syntax.char_literals {
+            index = match char_literal(rest) {
+                Some(length) => {
+                    let end = at(offset.saturating_add(length));
+                    spans.push((index, end, Token::String));
+                    end
+                }
+                None => index.saturating_add(1),
+            };
+            continue;
+        }
+        if syntax.quotes.contains(c) {
+            let tripled = syntax.triple_quotes && opens_with(rest, c, 3);
+            state.in_string = Some((c, tripled));
+            index =
+                at(offset.saturating_add(c.len_utf8().saturating_mul(if tripled { 3 } else { 1 })));
+            continue;
+        }
+        if is_word(grapheme) {
+            let number = c.is_ascii_digit();
+            let mut end = index.saturating_add(1);
+            while let Some(&(_, next)) = graphemes.get(end) {
+                // A decimal point belongs to a number when a digit follows it.
+                let point = number
+                    && next == "."
+                    && graphemes
+                        .get(end.saturating_add(1))
+                        .is_some_and(|(_, digit)| digit.starts_with(|c: char| c.is_ascii_digit()));
+                if !is_word(next) && !point {
+                    break;
+                }
+                end = end.saturating_add(1);
+            }
+            let word_end = graphemes.get(end).map_or(line.len(), |(offset, _)| *offset);
+            let word = line.get(offset..word_end).unwrap_or_default();
+            if number {
+                spans.push((index, end, Token::Number));
+            } else if syntax.keywords.contains(&word) {
+                spans.push((index, end, Token::Keyword));
+            }
+            index = end;
+            continue;
+        }
+        index = index.saturating_add(1);
+    }
+    if state.in_comment {
+        spans.push((start, graphemes.len(), Token::Comment));
+    }
+    if let Some((quote, tripled)) = state.in_string {
+        spans.push((start, graphemes.len(), Token::String));
+        if !tripled && !syntax.multiline_quotes.contains(quote) {
+            state.in_string = None;
+        }
+    }
+    (spans, state)
+}
+
+/// Whether `text` starts with `quote` written `count` times.
+fn opens_with(text: &str, quote: char, count: usize) -> bool {
+    text.chars().take(count).filter(|c| *c == quote).count() == count
+}
+
+fn is_word(grapheme: &str) -> bool {
+    grapheme.starts_with(|c: char| c.is_alphanumeric() || c == '_')
+}
+
+/// The length in bytes of a character literal such as `'x'` or `'\n'` at the start
+/// of `rest`, or `None` when the quote starts a lifetime or label instead.
+pub fn char_literal(rest: &str) -> Option<usize> {
+    let mut chars = rest.char_indices().skip(1);
+    let (_, first) = chars.next()?;
+    if first == '\\' {
+        chars.next()?;
+        // Escapes such as `'\u{1F600}'` run for a few more characters.
+        return chars
+            .take(8)
+            .find(|(_, c)| *c == '\'')
+            .map(|(index, _)| index.saturating_add(1));
+    }
+    let (index, second) = chars.next()?;
+    (second == '\'').then_some(index.saturating_add(1))
+}
+
+/// The parts of `spans` outside every range of `covered`, so that colors drawn on
+/// top of the syntax, such as diagnostics and search matches, are left whole.
+pub fn uncovered(spans: &[Span], covered: &[(usize, usize)]) -> Vec<Span> {
+    let mut pieces = Vec::new();
+    for &(start, end, token) in spans {
+        let mut from = start;
+        let mut cuts: Vec<&(usize, usize)> = covered
+            .iter()
+            .filter(|(cut_start, cut_end)| *cut_start < end && *cut_end > start)
+            .collect();
+        cuts.sort_by_key(|(cut_start, _)| *cut_start);
+        for (cut_start, cut_end) in cuts {
+            if *cut_start > from {
+                pieces.push((from, *cut_start, token));
+            }
+            from = from.max(*cut_end);
+        }
+        if from < end {
+            pieces.push((from, end, token));
+        }
+    }
+    pieces
+}
+
+/// What the main loop sends the highlighting thread.
+enum Request {
+    /// Start over on `lines`, highlighted as `syntax` says, or not at all.
+    Reset {
+        generation: usize,
+        lines: Vec<String>,
+        syntax: Option<Syntax>,
+        visible: Range<usize>,
+    },
+    /// Rows `at..at + removed` were replaced by `lines`.
+    Edit {
+        generation: usize,
+        at: usize,
+        removed: usize,
+        lines: Vec<String>,
+        visible: Range<usize>,
+    },
+}
+
+/// What the highlighting thread sends back.
+enum Reply {
+    /// The spans of the rows from `at`, as they were numbered at `generation`.
+    Rows {
+        generation: usize,
+        at: usize,
+        spans: Vec<Vec<Span>>,
+    },
+    /// Every row is highlighted as of `generation`.
+    Done(usize),
+}
+
+/// A change of lines the highlighting thread may not have taken in yet: at
+/// `generation`, rows `at..at + removed` became `inserted` rows.
+struct Splice {
+    generation: usize,
+    at: usize,
+    removed: usize,
+    inserted: usize,
+}
+
+impl Splice {
+    /// Where row `y` from before the splice is after it, unless it was replaced.
+    fn map(&self, y: usize) -> Option<usize> {
+        if y < self.at {
+            Some(y)
+        } else if y < self.at.saturating_add(self.removed) {
+            None
+        } else {
+            Some(y.saturating_sub(self.removed).saturating_add(self.inserted))
+        }
+    }
+}
+
+/// Syntax highlighting worked out on a thread of its own, so a large buffer never
+/// holds up typing. Each change is sent over as the lines that differ from the last
+/// ones sent; the thread highlights the visible rows first and then the rest in
+/// chunks, picking up after a change only as far as the state a line leaves open
+/// differs from before. `poll`, which the main loop calls, takes in what has arrived.
+pub struct Highlighter {
+    /// The lines last sent, to tell what changed.
+    lines: Vec<String>,
+    spans: Vec<Vec<Span>>,
+    file_type: Option<String>,
+    /// The buffer name and revision the lines were taken from.
+    revision: Option<(Option<String>, usize)>,
+    generation: usize,
+    /// The generation that started over on new lines; older replies are dropped.
+    reset: usize,
+    /// Changes sent since the oldest generation the thread may still reply about.
+    splices: VecDeque<Splice>,
+    /// The newest generation the thread finished.
+    done: usize,
+    requests: Sender<Request>,
+    replies: Receiver<Reply>,
+}
+
+impl Highlighter {
+    /// Starts the highlighting thread, which wakes the main loop with `waker` whenever
+    /// it has rows to hand over.
+    pub fn start(waker: Waker) -> Self {
+        let (requests, incoming) = mpsc::channel();
+        let (sender, replies) = mpsc::channel();
+        thread::spawn(move || Worker::default().run(&incoming, &sender, &waker));
+        Self {
+            lines: Vec::new(),
+            spans: Vec::new(),
+            file_type: None,
+            revision: None,
+            generation: 0,
+            reset: 0,
+            splices: VecDeque::new(),
+            done: 0,
+            requests,
+            replies,
+        }
+    }
+    /// Whether the lines last sent are those of `revision`, of `file_type`.
+    pub fn is_current(&self, revision: &(Option<String>, usize), file_type: Option<&str>) -> bool {
+        self.revision.as_ref() == Some(revision) && self.file_type.as_deref() == file_type
+    }
+    /// Sends the thread what changed in `lines`, highlighting them as `file_type`, or
+    /// clearing them for `None`, with rows `visible` first.
+    pub fn update(
+        &mut self,
+        lines: &[&str],
+        file_type: Option<&str>,
+        visible: Range<usize>,
+        revision: (Option<String>, usize),
+    ) {
+        let buffer_changed = self.revision.as_ref().map(|(name, _)| name) != Some(&revision.0);
+        self.revision = Some(revision);
+        let syntax = syntax(file_type);
+        if buffer_changed || self.file_type.as_deref() != file_type || syntax.is_none() {
+            self.generation = self.generation.wrapping_add(1);
+            self.reset = self.generation;
+            self.file_type = file_type.map(str::to_string);
+            self.lines = if syntax.is_some() {
+                lines.iter().map(|line| (*line).to_string()).collect()
+            } else {
+                Vec::new()
+            };
+            self.spans = vec![Vec::new(); self.lines.len()];
+            self.splices.clear();
+            let _ = self.requests.send(Request::Reset {
+                generation: self.generation,
+                lines: self.lines.clone(),
+                syntax,
+                visible,
+            });
+            return;
+        }
+        let prefix = self
+            .lines
+            .iter()
+            .zip(lines)
+            .take_while(|(old, new)| old.as_str() == **new)
+            .count();
+        let suffix = self
+            .lines
+            .iter()
+            .rev()
+            .zip(lines.iter().rev())
+            .take(self.lines.len().min(lines.len()).saturating_sub(prefix))
+            .take_while(|(old, new)| old.as_str() == **new)
+            .count();
+        let removed = self
+            .lines
+            .len()
+            .saturating_sub(prefix)
+            .saturating_sub(suffix);
+        let inserted: Vec<String> = lines
+            .get(prefix..lines.len().saturating_sub(suffix))
+            .unwrap_or_default()
+            .iter()
+            .map(|line| (*line).to_string())
+            .collect();
+        if removed == 0 && inserted.is_empty() {
+            return;
+        }
+        self.generation = self.generation.wrapping_add(1);
+        let end = prefix.saturating_add(removed);
+        self.lines.splice(prefix..end, inserted.iter().cloned());
+        // Rows that were edited keep their old colors until the new ones arrive.
+        let kept = removed.min(inserted.len());
+        self.spans.splice(
+            prefix.saturating_add(kept)..end,
+            vec![Vec::new(); inserted.len().saturating_sub(kept)],
+        );
+        self.splices.push_back(Splice {
+            generation: self.generation,
+            at: prefix,
+            removed,
+            inserted: inserted.len(),
+        });
+        let _ = self.requests.send(Request::Edit {
+            generation: self.generation,
+            at: prefix,
+            removed,
+            lines: inserted,
+            visible,
+        });
+    }
+    /// Takes in the rows highlighted since the last call. Returns whether there were
+    /// any.
+    pub fn poll(&mut self) -> bool {
+        let mut any = false;
+        while let Ok(reply) = self.replies.try_recv() {
+            self.take(reply);
+            any = true;
+        }
+        any
+    }
+    /// Waits until every row is highlighted, as before exporting the buffer.
+    pub fn wait(&mut self) {
+        while self.done != self.generation {
+            match self.replies.recv() {
+                Ok(reply) => self.take(reply),
+                Err(_) => return,
+            }
+        }
+    }
+    fn take(&mut self, reply: Reply) {
+        match reply {
+            Reply::Rows { generation, .. } if generation < self.reset => (),
+            Reply::Rows {
+                generation,
+                at,
+                spans,
+            } => {
+                // Replies about an older generation are moved past later changes.
+                while self
+                    .splices
+                    .front()
+                    .is_some_and(|splice| splice.generation <= generation)
+                {
+                    self.splices.pop_front();
+                }
+                for (y, row) in (at..).zip(spans) {
+                    let y = self.splices.iter().try_fold(y, |y, splice| splice.map(y));
+                    if let Some(slot) = y.and_then(|y| self.spans.get_mut(y)) {
+                        *slot = row;
+                    }
+                }
+            }
+            Reply::Done(generation) => self.done = generation,
+        }
+    }
+    /// The spans of row `y`.
+    pub fn spans(&self, y: usize) -> &[Span] {
+        self.spans.get(y).map_or(&[], Vec::as_slice)
+    }
+}
+
+/// The highlighting thread's copy of the buffer, with the state each row leaves
+/// open, or `None` for rows still to be highlighted.
+#[derive(Default)]
+struct Worker {
+    lines: Vec<String>,
+    ends: Vec<Option<State>>,
+    syntax: Option<Syntax>,
+    generation: usize,
+    visible: Range<usize>,
+}
+
+impl Worker {
+    /// Takes requests until the editor is gone.
+    fn run(&mut self, requests: &Receiver<Request>, replies: &Sender<Reply>, waker: &Waker) {
+        let mut waiting = true;
+        loop {
+            if waiting {
+                match requests.recv() {
+                    Ok(request) => self.apply(request),
+                    Err(_) => return,
+                }
+            }
+            loop {
+                match requests.try_recv() {
+                    Ok(request) => self.apply(request),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            }
+            // A new request stops the work in between chunks; what is left of it is
+            // picked up along with the request.
+            waiting = match self.highlight(requests, replies, waker) {
+                Ok(finished) => finished,
+                Err(()) => return,
+            };
+        }
+    }
+    fn apply(&mut self, request: Request) {
+        match request {
+            Request::Reset {
+                generation,
+                lines,
+                syntax,
+                visible,
+            } => {
+                self.ends = vec![None; lines.len()];
+                self.lines = lines;
+                self.syntax = syntax;
+                self.generation = generation;
+                self.visible = visible;
+            }
+            Request::Edit {
+                generation,
+                at,
+                removed,
+                lines,
+                visible,
+            } => {
+                let end = at.saturating_add(removed).min(self.lines.len());
+                let at = at.min(end);
+                self.ends.splice(at..end, vec![None; lines.len()]);
+                self.lines.splice(at..end, lines);
+                self.generation = generation;
+                self.visible = visible;
+            }
+        }
+    }
+    /// Highlights the rows still to be done, the visible ones first. Returns whether
+    /// it got through them all before another request came, or `Err` once the editor
+    /// is gone.
+    fn highlight(
+        &mut self,
+        requests: &Receiver<Request>,
+        replies: &Sender<Reply>,
+        waker: &Waker,
+    ) -> Result<bool, ()> {
+        let syntax = match self.syntax {
+            Some(syntax) => syntax,
+            None => {
+                return self
+                    .send(replies, waker, Reply::Done(self.generation))
+                    .map(|()| true)
+            }
+        };
+        let visible =
+            self.visible.start.min(self.lines.len())..self.visible.end.min(self.lines.len());
+        if self
+            .ends
+            .get(visible.clone())
+            .is_some_and(|ends| ends.contains(&None))
+        {
+            // Until the rows above are done, the visible ones start from a guess.
+            let mut state = visible
+                .start
+                .checked_sub(1)
+                .and_then(|y| self.ends.get(y).copied().flatten())
+                .unwrap_or_default();
+            let spans = self
+                .lines
+                .get(visible.clone())
+                .unwrap_or_default()
+                .iter()
+                .map(|text| {
+                    let (spans, end) = highlight_row(text, state, &syntax);
+                    state = end;
+                    spans
+                })
+                .collect();
+            self.send(
+                replies,
+                waker,
+                Reply::Rows {
+                    generation: self.generation,
+                    at: visible.start,
+                    spans,
+                },
+            )?;
+        }
+        let mut next = self.ends.iter().position(Option::is_none);
+        while let Some(first) = next {
+            let mut state = first
+                .checked_sub(1)
+                .and_then(|y| self.ends.get(y).copied().flatten())
+                .unwrap_or_default();
+            // What the row before used to leave open, for telling when the states
+            // have settled back into those of the rows after a change.
+            let mut previous = None;
+            let mut y = first;
+            let mut chunk = Vec::new();
+            while let Some(text) = self.lines.get(y) {
+                let old = self.ends.get(y).copied().flatten();
+                if old.is_some() && previous == Some(state) {
+                    break;
+                }
+                let (spans, end) = highlight_row(text, state, &syntax);
+                chunk.push(spans);
+                if let Some(slot) = self.ends.get_mut(y) {
+                    *slot = Some(end);
+                }
+                previous = old;
+                state = end;
+                y = y.saturating_add(1);
+                if chunk.len() >= CHUNK {
+                    self.send_rows(replies, waker, y, &mut chunk)?;
+                    match requests.try_recv() {
+                        Ok(request) => {
+                            // The row after has to be done again if the state it
+                            // starts in changed.
+                            if let Some(slot) =
+                                self.ends.get_mut(y).filter(|_| previous != Some(state))
+                            {
+                                *slot = None;
+                            }
+                            self.apply(request);
+                            return Ok(false);
+                        }
+                        Err(TryRecvError::Empty) => (),
+                        Err(TryRecvError::Disconnected) => return Err(()),
+                    }
+                }
+            }
+            self.send_rows(replies, waker, y, &mut chunk)?;
+            next = self
+                .ends
+                .get(y..)
+                .and_then(|ends| ends.iter().position(Option::is_none))
+                .map(|offset| y.saturating_add(offset));
+        }
+        self.send(replies, waker, Reply::Done(self.generation))?;
+        Ok(true)
+    }
+    /// Sends the rows of `chunk`, which end before row `end`.
+    fn send_rows(
+        &self,
+        replies: &Sender<Reply>,
+        waker: &Waker,
+        end: usize,
+        chunk: &mut Vec<Vec<Span>>,
+    ) -> Result<(), ()> {
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        let reply = Reply::Rows {
+            generation: self.generation,
+            at: end.saturating_sub(chunk.len()),
+            spans: std::mem::take(chunk),
+        };
+        self.send(replies, waker, reply)
+    }
+    fn send(&self, replies: &Sender<Reply>, waker: &Waker, reply: Reply) -> Result<(), ()> {
+        replies.send(reply).map_err(|_| ())?;
+        waker.wake();
+        Ok(())
+    }
+}
+
+/// Highlights a row, leaving overly long ones plain.
+fn highlight_row(text: &str, state: State, syntax: &Syntax) -> (Vec<Span>, State) {
+    if text.len() > MAX_LINE_LENGTH {
+        (Vec::new(), state)
+    } else {
+        line(text, state, syntax)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Terminal;
+
+    fn rust() -> Syntax {
+        syntax(Some("rust")).expect("rust syntax")
+    }
+
+    #[test]
+    fn keywords_strings_comments_and_numbers() {
+        let (spans, state) = line("let x = \"a\" + 1.5; // done", State::default(), &rust());
+        assert_eq!(
+            spans,
+            vec![
+                (0, 3, Token::Keyword),
+                (8, 11, Token::String),
+                (14, 17, Token::Number),
+                (19, 26, Token::Comment),
+            ]
+        );
+        assert_eq!(state, State::default());
+        let (spans, _) = line("'a' 'static", State::default(), &rust());
+        assert_eq!(spans, vec![(0, 3, Token::String), (5, 11, Token::Keyword)]);
+    }
+
+    #[test]
+    fn comments_and_strings_carry_over_lines() {
+        let (spans, state) = line("x /* open", State::default(), &rust());
+        assert_eq!(spans, vec![(2, 9, Token::Comment)]);
+        let (spans, state) = line("still */ fn", state, &rust());
+        assert_eq!(spans, vec![(0, 8, Token::Comment), (9, 11, Token::Keyword)]);
+        assert_eq!(state, State::default());
+        let python = syntax(Some("python")).expect("python syntax");
+        let (_, state) = line("\"\"\"Doc", State::default(), &python);
+        let (spans, state) = line("and \"x\" \"\"\" if", state, &python);
+        assert_eq!(
+            spans,
+            vec![(0, 11, Token::String), (12, 14, Token::Keyword)]
+        );
+        assert_eq!(state, State::default());
+        // A plain string ends with its line where it cannot go on.
+        let (_, state) = line("\"open", State::default(), &python);
+        assert_eq!(state, State::default());
+    }
+
+    #[test]
+    fn uncovered_leaves_room_for_other_spans() {
+        let spans = [(0, 10, Token::Comment)];
+        assert_eq!(
+            uncovered(&spans, &[(6, 8), (2, 4)]),
+            vec![
+                (0, 2, Token::Comment),
+                (4, 6, Token::Comment),
+                (8, 10, Token::Comment)
+            ]
+        );
+        assert_eq!(uncovered(&spans, &[(0, 12)]), vec![]);
+    }
+
+    #[test]
+    fn edits_are_highlighted_in_the_background() {
+        let mut highlighter = Highlighter::start(Terminal::headless().waker());
+        let lines = ["fn a() {}", "/* x", "y */", "let z = 1;"];
+        highlighter.update(&lines, Some("rust"), 0..2, (None, 1));
+        highlighter.wait();
+        assert_eq!(highlighter.spans(0), &[(0, 2, Token::Keyword)]);
+        assert_eq!(highlighter.spans(2), &[(0, 4, Token::Comment)]);
+        assert_eq!(highlighter.spans(3).first(), Some(&(0, 3, Token::Keyword)));
+        // Closing the comment early turns the rows below it back into code.
+        let lines = ["fn a() {}", "/* x */", "y */", "let z = 1;"];
+        highlighter.update(&lines, Some("rust"), 0..2, (None, 2));
+        highlighter.wait();
+        assert_eq!(highlighter.spans(1), &[(0, 7, Token::Comment)]);
+        assert_eq!(highlighter.spans(2), &[]);
+        // Rows inserted above move the colors of those below along.
+        let lines = ["", "", "fn a() {}", "/* x */", "y */", "let z = 1;"];
+        highlighter.update(&lines, Some("rust"), 0..2, (None, 3));
+        highlighter.wait();
+        assert_eq!(highlighter.spans(2), &[(0, 2, Token::Keyword)]);
+        assert_eq!(highlighter.spans(5).first(), Some(&(0, 3, Token::Keyword)));
+    }
+
+    /// How many rows the worker highlights for `request`.
+    fn rows_highlighted(worker: &mut Worker, request: Request) -> usize {
+        let (_requests, incoming) = mpsc::channel();
+        let (sender, replies) = mpsc::channel();
+        worker.apply(request);
+        assert_eq!(
+            worker.highlight(&incoming, &sender, &Terminal::headless().waker()),
+            Ok(true)
+        );
+        replies
+            .try_iter()
+            .map(|reply| match reply {
+                Reply::Rows { spans, .. } => spans.len(),
+                Reply::Done(_) => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn an_edit_stops_once_the_states_settle() {
+        let mut worker = Worker::default();
+        let lines = (0..1000).map(|n| format!("let x{} = {};", n, n)).collect();
+        let reset = Request::Reset {
+            generation: 1,
+            lines,
+            syntax: syntax(Some("rust")),
+            visible: 0..0,
+        };
+        assert_eq!(rows_highlighted(&mut worker, reset), 1000);
+        let edit = |generation, text: &str| Request::Edit {
+            generation,
+            at: 500,
+            removed: 1,
+            lines: vec![text.to_string()],
+            visible: 0..0,
+        };
+        // The row after the change shows the state is what it was.
+        assert_eq!(rows_highlighted(&mut worker, edit(2, "/* x */")), 2);
+        // An open comment runs on to the end.
+        assert_eq!(rows_highlighted(&mut worker, edit(3, "/* x")), 500);
+        assert_eq!(rows_highlighted(&mut worker, edit(4, "x")), 500);
+    }
+}
@@ -0,0 +1,114 @@
+use crate::paths;
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Entries kept per kind.
+const MAX_ENTRIES: usize = 100;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub enum HistoryKind {
+    Search,
+    Command,
+    FileName,
+}
+
+impl HistoryKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Search => "search",
+            Self::Command => "command",
+            Self::FileName => "file",
+        }
+    }
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "search" => Some(Self::Search),
+            "command" => Some(Self::Command),
+            "file" => Some(Self::FileName),
+            _ => None,
+        }
+    }
+}
+
+/// Minibuffer history, shared across sessions through an append-only file.
+#[derive(Default)]
+pub struct History {
+    entries: HashMap<HistoryKind, Vec<String>>,
+    path: Option<PathBuf>,
+}
+
+impl History {
+    /// Loads `$XDG_STATE_HOME/hecto/history`, compacting it when it has grown large.
+    pub fn load() -> Self {
+        let mut history = Self {
+            entries: HashMap::new(),
+            path: paths::state_dir().map(|dir| dir.join("history")),
+        };
+        let text = history
+            .path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        let mut lines = 0_usize;
+        for line in text.lines() {
+            lines = lines.saturating_add(1);
+            if let Some((kind, entry)) = line.split_once('\t') {
+                if let Some(kind) = HistoryKind::from_name(kind) {
+                    history.remember(kind, entry);
+                }
+            }
+        }
+        if lines > MAX_ENTRIES.saturating_mul(8) {
+            history.compact();
+        }
+        history
+    }
+    /// Entries of `kind`, oldest first.
+    pub fn entries(&self, kind: HistoryKind) -> &[String] {
+        self.entries.get(&kind).map_or(&[], Vec::as_slice)
+    }
+    pub fn add(&mut self, kind: HistoryKind, entry: &str) {
+        if entry.is_empty()
+            || entry.contains('\n')
+            || self.entries(kind).last().map(String::as_str) == Some(entry)
+        {
+            return;
+        }
+        self.remember(kind, entry);
+        if let Some(path) = &self.path {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) {
+                let _ = writeln!(file, "{}\t{}", kind.name(), entry);
+            }
+        }
+    }
+    fn remember(&mut self, kind: HistoryKind, entry: &str) {
+        let entries = self.entries.entry(kind).or_default();
+        entries.retain(|existing| existing != entry);
+        entries.push(entry.to_string());
+        if entries.len() > MAX_ENTRIES {
+            entries.remove(0);
+        }
+    }
+    fn compact(&self) {
+        let path = if let Some(path) = &self.path {
+            path
+        } else {
+            return;
+        };
+        let mut text = String::new();
+        for (kind, entries) in &self.entries {
+            for entry in entries {
+                text.push_str(kind.name());
+                text.push('\t');
+                text.push_str(entry);
+                text.push('\n');
+            }
+        }
+        let _ = fs::write(path, text);
+    }
+}
@@ -0,0 +1,87 @@
+use crate::json::Value;
+
+/// Extracts the text of a `textDocument/hover` result's `contents`, which may be
+/// markup content, a marked string or a list of marked strings.
+pub fn text(contents: &Value) -> String {
+    match contents {
+        Value::String(text) => text.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(text)
+            .filter(|text| !text.trim().is_empty())
+            .collect::<Vec<String>>()
+            .join("\n\n"),
+        Value::Object(_) => {
+            let value = contents
+                .get("value")
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            match contents.get("language").and_then(Value::as_str) {
+                Some(language) => format!("```{}\n{}\n```", language, value),
+                None => value.to_string(),
+            }
+        }
+        _ => String::new(),
+    }
+}
+
+/// Renders markdown as plain lines at most `width` columns wide: code blocks are kept
+/// verbatim, prose is stripped of emphasis markers and word-wrapped.
+pub fn lines(markdown: &str, width: usize) -> Vec<String> {
+    let width = width.max(10);
+    let mut lines: Vec<String> = Vec::new();
+    let mut in_code = false;
+    for line in markdown.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            lines.push(line.replace('\t', "    ").chars().take(width).collect());
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.len() >= 3 && trimmed.chars().all(|c| c == '-' || c == '*' || c == '_') {
+            lines.push("─".repeat(width.min(40)));
+            continue;
+        }
+        let prose = plain(line.trim_start_matches('#').trim());
+        if prose.is_empty() {
+            if lines.last().is_some_and(|last| !last.is_empty()) {
+                lines.push(String::new());
+            }
+            continue;
+        }
+        let mut current = String::new();
+        for word in prose.split_whitespace() {
+            if !current.is_empty()
+                && current.chars().count().saturating_add(word.chars().count()) >= width
+            {
+                lines.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+        }
+        lines.push(current);
+    }
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+    lines
+}
+
+/// Drops inline markdown markers and backslash escapes.
+fn plain(line: &str) -> String {
+    let mut text = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => text.extend(chars.next()),
+            '`' | '*' => (),
+            c => text.push(c),
+        }
+    }
+    text
+}
@@ -0,0 +1,56 @@
+use std::cmp;
+
+/// Per-buffer indentation: what the Tab key inserts and how wide tabs are drawn.
+#[derive(Clone, Copy)]
+pub struct Indent {
+    pub use_tabs: bool,
+    /// Columns per indentation level when indenting with spaces.
+    pub size: usize,
+    pub tab_width: usize,
+}
+
+impl Default for Indent {
+    fn default() -> Self {
+        Self {
+            use_tabs: true,
+            size: 4,
+            tab_width: 4,
+        }
+    }
+}
+
+impl Indent {
+    /// The text the Tab key inserts when the cursor is at screen column `column`.
+    pub fn text_at(&self, column: usize) -> String {
+        if self.use_tabs {
+            "\t".to_string()
+        } else {
+            let size = self.size.max(1);
+            " ".repeat(size.saturating_sub(column % size))
+        }
+    }
+    /// How many spaces before grapheme `x` of `line` make up a soft tab: back to the
+    /// previous indent stop when `x` is in the leading spaces of a buffer indented with
+    /// spaces, and otherwise 1.
+    pub fn soft_tab_before(&self, line: &str, x: usize) -> usize {
+        if self.use_tabs || x == 0 || x > leading_spaces(line) {
+            return 1;
+        }
+        let size = self.size.max(1);
+        x.saturating_sub(x.saturating_sub(1) / size * size)
+    }
+    /// How many spaces after grapheme `x` of `line` make up a soft tab: up to the next
+    /// indent stop, or the end of the leading spaces, when `x` is in them.
+    pub fn soft_tab_after(&self, line: &str, x: usize) -> usize {
+        let leading = leading_spaces(line);
+        if self.use_tabs || x >= leading {
+            return 1;
+        }
+        let size = self.size.max(1);
+        cmp::min((x / size).saturating_add(1).saturating_mul(size), leading).saturating_sub(x)
+    }
+}
+
+fn leading_spaces(line: &str) -> usize {
+    line.chars().take_while(|c| *c == ' ').count()
+}
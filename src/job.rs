@@ -0,0 +1,171 @@
+use crate::log;
+use crate::terminal::Waker;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// What the reader threads send: a line one of a job's streams printed, or that the
+/// stream ended.
+enum Message {
+    Line(usize, String),
+    Closed(usize),
+}
+
+/// An external process running in the background.
+pub struct Job {
+    pub id: usize,
+    pub command: String,
+    child: Child,
+    started: Instant,
+    /// Standard output and standard error lines in the order they arrived.
+    pub output: Vec<String>,
+    /// Streams still being read; the job is reported only once both are done.
+    open_streams: usize,
+}
+
+impl Job {
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+impl Drop for Job {
+    fn drop(&mut self) {
+        // Jobs still running when the editor quits do not outlive it.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// A job that exited, with everything it printed.
+pub struct Finished {
+    pub job: Job,
+    /// The exit code, if the process was not killed by a signal.
+    pub code: Option<i32>,
+}
+
+/// Runs shell commands without blocking the editor: their output is read on threads
+/// and handed over by `poll`, which the main loop calls between keys.
+pub struct Jobs {
+    running: Vec<Job>,
+    next_id: usize,
+    sender: Sender<Message>,
+    incoming: Receiver<Message>,
+}
+
+impl Default for Jobs {
+    fn default() -> Self {
+        let (sender, incoming) = mpsc::channel();
+        Self {
+            running: Vec::new(),
+            next_id: 1,
+            sender,
+            incoming,
+        }
+    }
+}
+
+impl Jobs {
+    /// Starts `command` through `sh -c`, returning the new job's number.
+    pub fn spawn(&mut self, command: &str, waker: &Waker) -> Result<usize, Error> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let id = self.next_id;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| Error::new(ErrorKind::BrokenPipe, "job stdout unavailable"))?;
+        let stderr = child
+            .stderr
+            .take()
+            .ok_or_else(|| Error::new(ErrorKind::BrokenPipe, "job stderr unavailable"))?;
+        self.read_lines(id, stdout, waker.clone());
+        self.read_lines(id, stderr, waker.clone());
+        self.next_id = self.next_id.saturating_add(1);
+        log::info("job", format!("job {} started: {}", id, command));
+        self.running.push(Job {
+            id,
+            command: command.to_string(),
+            child,
+            started: Instant::now(),
+            output: Vec::new(),
+            open_streams: 2,
+        });
+        Ok(id)
+    }
+    fn read_lines<R: Read + Send + 'static>(&self, id: usize, stream: R, waker: Waker) {
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            for line in BufReader::new(stream).lines() {
+                let line = if let Ok(line) = line { line } else { break };
+                if sender.send(Message::Line(id, line)).is_err() {
+                    return;
+                }
+                waker.wake();
+            }
+            let _ = sender.send(Message::Closed(id));
+            waker.wake();
+        });
+    }
+    pub fn is_active(&self) -> bool {
+        !self.running.is_empty()
+    }
+    /// Whether a job has closed its output but may not have exited yet, which only
+    /// polling notices.
+    pub fn is_exiting(&self) -> bool {
+        self.running.iter().any(|job| job.open_streams == 0)
+    }
+    pub fn running(&self) -> &[Job] {
+        &self.running
+    }
+    /// Stops job `id`; it is reported as finished by a later `poll`. Returns whether
+    /// there was such a job.
+    pub fn kill(&mut self, id: usize) -> bool {
+        self.running
+            .iter_mut()
+            .find(|job| job.id == id)
+            .is_some_and(|job| job.child.kill().is_ok())
+    }
+    /// Collects the output that arrived since the last call and returns the jobs that
+    /// have exited and printed all they will.
+    pub fn poll(&mut self) -> Vec<Finished> {
+        while let Ok(message) = self.incoming.try_recv() {
+            let (id, line) = match message {
+                Message::Line(id, line) => (id, Some(line)),
+                Message::Closed(id) => (id, None),
+            };
+            if let Some(job) = self.running.iter_mut().find(|job| job.id == id) {
+                match line {
+                    Some(line) => job.output.push(line),
+                    None => job.open_streams = job.open_streams.saturating_sub(1),
+                }
+            }
+        }
+        let mut finished = Vec::new();
+        let mut index = 0;
+        while let Some(job) = self.running.get_mut(index) {
+            let status = if job.open_streams == 0 {
+                job.child.try_wait().ok().flatten()
+            } else {
+                None
+            };
+            if let Some(status) = status {
+                log::info("job", format!("job {} exited: {}", job.id, status));
+                finished.push(Finished {
+                    job: self.running.remove(index),
+                    code: status.code(),
+                });
+            } else {
+                index = index.saturating_add(1);
+            }
+        }
+        finished
+    }
+}
@@ -0,0 +1,175 @@
+use crate::paths;
+use crate::Position;
+use std::fs;
+use std::io::{self, Error};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// The state file in a journal directory; the snapshots it names sit beside it.
+const STATE_FILE: &str = "state";
+/// Where the next state file is written before it replaces the last one.
+const STATE_TEMP: &str = "state.tmp";
+
+/// Numbers each write's snapshots, so that a write never touches the files the
+/// state it replaces still names.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// An open buffer as the journal keeps it.
+pub struct Entry {
+    /// `None` for a buffer that was never saved.
+    pub file_name: Option<String>,
+    pub cursor_position: Position,
+    pub offset: Position,
+    /// The buffer's text when it has unsaved changes.
+    pub snapshot: Option<String>,
+}
+
+/// What hecto was editing, written every so often while it runs and removed when it
+/// quits, so a journal left behind marks a crash and can be restored from. Unlike swap
+/// files it covers every buffer, unnamed ones included.
+#[derive(Default)]
+pub struct Journal {
+    /// The working directory, which relative file names are read against.
+    pub dir: Option<PathBuf>,
+    pub buffers: Vec<Entry>,
+    /// The index in `buffers` of the buffer that was shown.
+    pub current: usize,
+}
+
+/// Each running hecto journals to a directory named after its process ID.
+fn root() -> Option<PathBuf> {
+    paths::state_dir().map(|dir| dir.join("journal"))
+}
+
+fn own_dir() -> Option<PathBuf> {
+    root().map(|root| root.join(process::id().to_string()))
+}
+
+impl Journal {
+    /// Replaces this process's journal with `self`. The new snapshots go in files of
+    /// their own and the state naming them replaces the old one by a rename, so a
+    /// crash or a full disk part way leaves the last journal whole.
+    pub fn write(&self) -> Result<(), Error> {
+        let dir = own_dir().ok_or_else(|| Error::new(io::ErrorKind::NotFound, "no state dir"))?;
+        fs::create_dir_all(&dir)?;
+        let generation = GENERATION.fetch_add(1, Ordering::Relaxed);
+        let mut text = String::from("# hecto journal\n");
+        if let Some(cwd) = &self.dir {
+            text.push_str(&format!("cd\t{}\n", cwd.display()));
+        }
+        let mut kept = vec![STATE_FILE.to_string()];
+        for (index, buffer) in self.buffers.iter().enumerate() {
+            let snapshot = match &buffer.snapshot {
+                Some(snapshot) => {
+                    let name = format!("{}-{}.txt", generation, index);
+                    fs::write(dir.join(&name), snapshot)?;
+                    kept.push(name.clone());
+                    name
+                }
+                None => "-".to_string(),
+            };
+            text.push_str(&format!(
+                "buffer\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                buffer.cursor_position.y,
+                buffer.cursor_position.x,
+                buffer.offset.y,
+                buffer.offset.x,
+                snapshot,
+                buffer.file_name.as_deref().unwrap_or_default()
+            ));
+        }
+        text.push_str(&format!("current\t{}\n", self.current));
+        fs::write(dir.join(STATE_TEMP), text)?;
+        fs::rename(dir.join(STATE_TEMP), dir.join(STATE_FILE))?;
+        // Snapshots of buffers since saved or closed must not outlive them.
+        for entry in fs::read_dir(&dir)?.filter_map(Result::ok) {
+            let name = entry.file_name();
+            if !kept.iter().any(|kept| name.to_str() == Some(kept.as_str())) {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+        Ok(())
+    }
+    fn load(dir: &Path) -> Option<Self> {
+        let text = fs::read_to_string(dir.join(STATE_FILE)).ok()?;
+        let mut journal = Self::default();
+        for line in text.lines() {
+            let (key, rest) = line.split_once('\t').unwrap_or((line, ""));
+            match key {
+                "cd" => journal.dir = Some(PathBuf::from(rest)),
+                "current" => journal.current = rest.parse().ok()?,
+                "buffer" => {
+                    let fields: Vec<&str> = rest.splitn(6, '\t').collect();
+                    let number = |index: usize| {
+                        fields
+                            .get(index)
+                            .and_then(|field| field.parse::<usize>().ok())
+                    };
+                    let snapshot = match *fields.get(4)? {
+                        "-" => None,
+                        name => Some(fs::read_to_string(dir.join(name)).ok()?),
+                    };
+                    journal.buffers.push(Entry {
+                        file_name: fields
+                            .get(5)
+                            .filter(|name| !name.is_empty())
+                            .map(|name| (*name).to_string()),
+                        cursor_position: Position {
+                            x: number(1)?,
+                            y: number(0)?,
+                        },
+                        offset: Position {
+                            x: number(3)?,
+                            y: number(2)?,
+                        },
+                        snapshot,
+                    });
+                }
+                _ => (),
+            }
+        }
+        Some(journal)
+    }
+}
+
+/// A journal left by a hecto that is no longer running, and its directory.
+pub fn orphan() -> Option<(PathBuf, Journal)> {
+    fs::read_dir(root()?)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|dir| {
+            dir.file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.parse::<libc::pid_t>().ok())
+                .is_some_and(|pid| !is_running(pid))
+        })
+        .find_map(|dir| match Journal::load(&dir) {
+            Some(journal) => Some((dir, journal)),
+            None => {
+                // Unreadable leftovers would otherwise be looked at on every launch.
+                remove(&dir);
+                None
+            }
+        })
+}
+
+/// Deletes the journal in `dir`.
+pub fn remove(dir: &Path) {
+    let _ = fs::remove_dir_all(dir);
+}
+
+/// Deletes this process's journal, as on a clean exit.
+pub fn remove_own() {
+    if let Some(dir) = own_dir() {
+        remove(&dir);
+    }
+}
+
+fn is_running(pid: libc::pid_t) -> bool {
+    // SAFETY: `kill` takes no pointers, and signal 0 only checks that the process
+    // exists.
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
@@ -0,0 +1,367 @@
+use std::fmt;
+
+/// A JSON value. Objects keep their keys in insertion order.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Builds an object from `(key, value)` pairs.
+    pub fn object(entries: Vec<(&str, Value)>) -> Self {
+        Self::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (key.to_string(), value))
+                .collect(),
+        )
+    }
+    pub fn get(&self, key: &str) -> Option<&Self> {
+        match self {
+            Self::Object(entries) => entries
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value),
+            _ => None,
+        }
+    }
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(text) => Some(text),
+            _ => None,
+        }
+    }
+    #[allow(
+        clippy::as_conversions,
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss
+    )]
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Self::Number(number) if *number >= 0.0 && number.fract() == 0.0 => {
+                Some(*number as usize)
+            }
+            _ => None,
+        }
+    }
+    pub fn as_array(&self) -> Option<&[Self]> {
+        match self {
+            Self::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Self::String(text.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(text: String) -> Self {
+        Self::String(text)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<usize> for Value {
+    #[allow(clippy::as_conversions, clippy::cast_precision_loss)]
+    fn from(number: usize) -> Self {
+        Self::Number(number as f64)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Number(number) if number.fract() == 0.0 && number.abs() < 1e15 => {
+                write!(f, "{:.0}", number)
+            }
+            Self::Number(number) => write!(f, "{}", number),
+            Self::String(text) => write_string(f, text),
+            Self::Array(items) => {
+                write!(f, "[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Self::Object(entries) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if c.is_control() => write!(f, "\\u{:04x}", u32::from(c))?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+pub fn parse(text: &str) -> Result<Value, String> {
+    parse_at(text).map_err(|(position, message)| format!("{} at {}", message, position))
+}
+
+/// Parses `text`, giving the character offset of an error along with its message.
+pub fn parse_at(text: &str) -> Result<Value, (usize, String)> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+    };
+    let value = parser
+        .value()
+        .map_err(|message| (parser.position, message))?;
+    parser.skip_whitespace();
+    if parser.position < parser.chars.len() {
+        return Err((parser.position, "trailing characters".to_string()));
+    }
+    Ok(value)
+}
+
+/// Lays `text` out again, one member per line indented by `indent`, or all on one
+/// line without spaces for `None`. Strings and numbers are kept as written.
+pub fn reformat(text: &str, indent: Option<&str>) -> Result<String, (usize, String)> {
+    parse_at(text)?;
+    let mut output = String::new();
+    let mut depth = 0_usize;
+    let mut chars = text.chars().peekable();
+    let newline = |output: &mut String, depth: usize| {
+        if let Some(indent) = indent {
+            output.push('\n');
+            output.push_str(&indent.repeat(depth));
+        }
+    };
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                output.push(c);
+                while let Some(c) = chars.next() {
+                    output.push(c);
+                    if c == '\\' {
+                        output.extend(chars.next());
+                    } else if c == '"' {
+                        break;
+                    }
+                }
+            }
+            '{' | '[' => {
+                output.push(c);
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                // Empty objects and arrays stay on one line.
+                if matches!(chars.peek(), Some('}' | ']')) {
+                    output.extend(chars.next());
+                } else {
+                    depth = depth.saturating_add(1);
+                    newline(&mut output, depth);
+                }
+            }
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut output, depth);
+                output.push(c);
+            }
+            ',' => {
+                output.push(c);
+                newline(&mut output, depth);
+            }
+            ':' => output.push_str(if indent.is_some() { ": " } else { ":" }),
+            c if c.is_whitespace() => (),
+            c => output.push(c),
+        }
+    }
+    Ok(output)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position = self.position.saturating_add(1);
+        c
+    }
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.position = self.position.saturating_add(1);
+        }
+    }
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        // An error leaves the position at the offending character.
+        if self.peek() != Some(expected) {
+            return Err(format!("expected `{}`", expected));
+        }
+        self.position = self.position.saturating_add(1);
+        Ok(())
+    }
+    fn keyword(&mut self, word: &str, value: Value) -> Result<Value, String> {
+        for expected in word.chars() {
+            self.expect(expected)?;
+        }
+        Ok(value)
+    }
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('n') => self.keyword("null", Value::Null),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('"') => self.string().map(Value::String),
+            Some('[') => self.array(),
+            Some('{') => self.object(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            _ => Err("unexpected input".to_string()),
+        }
+    }
+    fn number(&mut self) -> Result<Value, String> {
+        let start = self.position;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(c))
+        {
+            self.position = self.position.saturating_add(1);
+        }
+        let text: String = self
+            .chars
+            .get(start..self.position)
+            .unwrap_or(&[])
+            .iter()
+            .collect();
+        text.parse()
+            .map(Value::Number)
+            .map_err(|_| format!("invalid number `{}`", text))
+    }
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                Some('"') => return Ok(text),
+                Some('\\') => match self.next() {
+                    Some('n') => text.push('\n'),
+                    Some('r') => text.push('\r'),
+                    Some('t') => text.push('\t'),
+                    Some('b') => text.push('\u{8}'),
+                    Some('f') => text.push('\u{c}'),
+                    Some('u') => {
+                        let mut code = self.hex4()?;
+                        // A surrogate pair encodes one character outside the BMP.
+                        if (0xd800..0xdc00).contains(&code) && self.peek() == Some('\\') {
+                            self.position = self.position.saturating_add(1);
+                            self.expect('u')?;
+                            let low = self.hex4()?;
+                            code = 0x10000_u32
+                                .saturating_add((code.saturating_sub(0xd800)) << 10)
+                                .saturating_add(low.saturating_sub(0xdc00));
+                        }
+                        text.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                    }
+                    Some(c) => text.push(c),
+                    None => break,
+                },
+                Some(c) => text.push(c),
+                None => break,
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+    fn hex4(&mut self) -> Result<u32, String> {
+        let mut code = 0_u32;
+        for _ in 0..4 {
+            let digit = self
+                .next()
+                .and_then(|c| c.to_digit(16))
+                .ok_or_else(|| "invalid escape".to_string())?;
+            code = code.saturating_mul(16).saturating_add(digit);
+        }
+        Ok(code)
+    }
+    fn array(&mut self) -> Result<Value, String> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.position = self.position.saturating_add(1);
+            return Ok(Value::Array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position = self.position.saturating_add(1),
+                Some(']') => {
+                    self.position = self.position.saturating_add(1);
+                    return Ok(Value::Array(items));
+                }
+                _ => return Err("expected `,` or `]`".to_string()),
+            }
+        }
+    }
+    fn object(&mut self) -> Result<Value, String> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.position = self.position.saturating_add(1);
+            return Ok(Value::Object(entries));
+        }
+        loop {
+            self.skip_whitespace();
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+            entries.push((key, self.value()?));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.position = self.position.saturating_add(1),
+                Some('}') => {
+                    self.position = self.position.saturating_add(1);
+                    return Ok(Value::Object(entries));
+                }
+                _ => return Err("expected `,` or `}`".to_string()),
+            }
+        }
+    }
+}
@@ -0,0 +1,45 @@
+use crate::Position;
+
+const MAX_JUMPS: usize = 100;
+
+#[derive(Clone, PartialEq)]
+pub struct Jump {
+    pub file_name: Option<String>,
+    pub position: Position,
+}
+
+#[derive(Default)]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    index: usize,
+}
+
+impl JumpList {
+    pub fn push(&mut self, jump: Jump) {
+        self.jumps.truncate(self.index);
+        if self.jumps.last() != Some(&jump) {
+            self.jumps.push(jump);
+        }
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
+    pub fn back(&mut self, current: Jump) -> Option<Jump> {
+        if self.index == 0 {
+            return None;
+        }
+        if self.index == self.jumps.len() {
+            self.jumps.push(current);
+        }
+        self.index = self.index.saturating_sub(1);
+        self.jumps.get(self.index).cloned()
+    }
+    pub fn forward(&mut self) -> Option<Jump> {
+        if self.index.saturating_add(1) >= self.jumps.len() {
+            return None;
+        }
+        self.index = self.index.saturating_add(1);
+        self.jumps.get(self.index).cloned()
+    }
+}
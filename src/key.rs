@@ -0,0 +1,167 @@
+use termion::event::Key as TermionKey;
+
+/// A key press: the keys termion decodes, plus the modified navigation keys it
+/// passes through as unsupported escape sequences.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Key {
+    Backspace,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    BackTab,
+    Delete,
+    Insert,
+    /// Function keys 1 through 12.
+    F(u8),
+    Char(char),
+    Alt(char),
+    Ctrl(char),
+    /// A letter held with Ctrl and Shift, stored in lowercase. Only terminals that
+    /// speak the kitty keyboard protocol can tell these apart from plain Ctrl.
+    CtrlShift(char),
+    /// Ctrl-Space.
+    Null,
+    Esc,
+    ShiftLeft,
+    ShiftRight,
+    ShiftUp,
+    ShiftDown,
+    ShiftHome,
+    ShiftEnd,
+    /// Shift-F3, which goes back through search matches; other shifted function keys
+    /// are not told apart.
+    ShiftF3,
+    AltLeft,
+    AltRight,
+    AltUp,
+    AltDown,
+    CtrlLeft,
+    CtrlRight,
+    CtrlUp,
+    CtrlDown,
+    CtrlHome,
+    CtrlEnd,
+}
+
+impl Key {
+    pub fn from_termion(key: TermionKey) -> Option<Self> {
+        Some(match key {
+            TermionKey::Backspace => Self::Backspace,
+            TermionKey::Left => Self::Left,
+            TermionKey::Right => Self::Right,
+            TermionKey::Up => Self::Up,
+            TermionKey::Down => Self::Down,
+            TermionKey::Home => Self::Home,
+            TermionKey::End => Self::End,
+            TermionKey::PageUp => Self::PageUp,
+            TermionKey::PageDown => Self::PageDown,
+            TermionKey::BackTab => Self::BackTab,
+            TermionKey::Delete => Self::Delete,
+            TermionKey::Insert => Self::Insert,
+            TermionKey::F(number) => Self::F(number),
+            TermionKey::Char(c) => Self::Char(c),
+            TermionKey::Alt(c) => Self::Alt(c),
+            TermionKey::Ctrl(c) => Self::Ctrl(c),
+            TermionKey::Null => Self::Null,
+            TermionKey::Esc => Self::Esc,
+            _ => return None,
+        })
+    }
+    /// Decodes the xterm sequence `ESC [ 1 ; <modifiers> <key>` for an arrow, Home or
+    /// End held with one of Shift, Alt or Ctrl, or for Shift-F3.
+    pub fn from_sequence(bytes: &[u8]) -> Option<Self> {
+        if bytes.last() == Some(&b'u') {
+            return Self::from_kitty(bytes);
+        }
+        let rest = bytes.strip_prefix(b"\x1b[1;")?;
+        let (modifiers, key) = match rest {
+            [modifiers, key] => (*modifiers, *key),
+            _ => return None,
+        };
+        Some(match (modifiers, key) {
+            (b'2', b'D') => Self::ShiftLeft,
+            (b'2', b'C') => Self::ShiftRight,
+            (b'2', b'A') => Self::ShiftUp,
+            (b'2', b'B') => Self::ShiftDown,
+            (b'2', b'H') => Self::ShiftHome,
+            (b'2', b'F') => Self::ShiftEnd,
+            (b'2', b'R') => Self::ShiftF3,
+            (b'3', b'D') => Self::AltLeft,
+            (b'3', b'C') => Self::AltRight,
+            (b'3', b'A') => Self::AltUp,
+            (b'3', b'B') => Self::AltDown,
+            (b'5', b'D') => Self::CtrlLeft,
+            (b'5', b'C') => Self::CtrlRight,
+            (b'5', b'A') => Self::CtrlUp,
+            (b'5', b'B') => Self::CtrlDown,
+            (b'5', b'H') => Self::CtrlHome,
+            (b'5', b'F') => Self::CtrlEnd,
+            _ => return None,
+        })
+    }
+    /// Decodes the kitty keyboard protocol's `ESC [ <code> ; <modifiers>[:<event>] u`,
+    /// where the code is a Unicode code point. Key releases are dropped.
+    fn from_kitty(bytes: &[u8]) -> Option<Self> {
+        let body = bytes.strip_prefix(b"\x1b[")?.strip_suffix(b"u")?;
+        let body = std::str::from_utf8(body).ok()?;
+        let (code, modifiers) = body.split_once(';').unwrap_or((body, "1"));
+        let (modifiers, event) = modifiers.split_once(':').unwrap_or((modifiers, "1"));
+        if event == "3" {
+            return None;
+        }
+        let code = code.split(':').next()?.parse::<u32>().ok()?;
+        let modifiers = modifiers.parse::<u32>().ok()?.saturating_sub(1);
+        let shift = modifiers & 1 != 0;
+        let alt = modifiers & 2 != 0;
+        let ctrl = modifiers & 4 != 0;
+        Some(match code {
+            27 => Self::Esc,
+            13 => Self::Char('\n'),
+            9 if shift => Self::BackTab,
+            9 => Self::Char('\t'),
+            127 => Self::Backspace,
+            32 if ctrl => Self::Null,
+            _ => {
+                let c = char::from_u32(code)?;
+                // Not only ASCII: Alt-Shift-é is Alt-É.
+                let upper = c.to_uppercase().next().unwrap_or(c);
+                match (ctrl, alt, shift) {
+                    (true, _, true) => Self::CtrlShift(c.to_ascii_lowercase()),
+                    (true, _, false) => Self::Ctrl(c),
+                    (false, true, true) => Self::Alt(upper),
+                    (false, true, false) => Self::Alt(c),
+                    (false, false, true) => Self::Char(upper),
+                    (false, false, false) => Self::Char(c),
+                }
+            }
+        })
+    }
+    /// The escape sequence a terminal sends for one of the modified navigation keys.
+    pub fn sequence(self) -> Option<&'static str> {
+        Some(match self {
+            Self::ShiftLeft => "\x1b[1;2D",
+            Self::ShiftRight => "\x1b[1;2C",
+            Self::ShiftUp => "\x1b[1;2A",
+            Self::ShiftDown => "\x1b[1;2B",
+            Self::ShiftHome => "\x1b[1;2H",
+            Self::ShiftEnd => "\x1b[1;2F",
+            Self::ShiftF3 => "\x1b[1;2R",
+            Self::AltLeft => "\x1b[1;3D",
+            Self::AltRight => "\x1b[1;3C",
+            Self::AltUp => "\x1b[1;3A",
+            Self::AltDown => "\x1b[1;3B",
+            Self::CtrlLeft => "\x1b[1;5D",
+            Self::CtrlRight => "\x1b[1;5C",
+            Self::CtrlUp => "\x1b[1;5A",
+            Self::CtrlDown => "\x1b[1;5B",
+            Self::CtrlHome => "\x1b[1;5H",
+            Self::CtrlEnd => "\x1b[1;5F",
+            _ => return None,
+        })
+    }
+}
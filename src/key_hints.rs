@@ -0,0 +1,69 @@
+use crate::commands::{self, Bindings};
+use crate::mode_keys::{self, KeyMode, Pattern};
+use crate::Key;
+
+/// Lists the keys that may follow the pending sequence in `mode`, with what they do,
+/// from the mode keys and the sequences `bindings` has for commands. A key that
+/// starts several command sequences lists their commands after a `+`.
+pub fn continuations(pending: &str, mode: KeyMode, bindings: &Bindings) -> Vec<(String, String)> {
+    let mut hints: Vec<(String, String)> = Vec::new();
+    let mut add = |keys: String, action: &str| {
+        if !hints.iter().any(|(listed, _)| *listed == keys) {
+            hints.push((keys, action.to_string()));
+        }
+    };
+    let typed: Vec<Key> = pending.chars().map(Key::Char).collect();
+    for mode_key in mode_keys::MODE_KEYS
+        .iter()
+        .filter(|mode_key| mode_key.mode == mode && mode_key.keys.len() > typed.len())
+        .filter(|mode_key| mode_keys::starts(mode_key.keys, &typed))
+    {
+        match mode_key.keys.get(typed.len()) {
+            Some(Pattern::Object) => {
+                for (keys, object) in mode_keys::OBJECTS {
+                    add((*keys).to_string(), object);
+                }
+            }
+            Some(Pattern::Motion) => add(mode_keys::motions(), mode_key.description),
+            Some(pattern) => add(pattern.name(), mode_key.description),
+            None => (),
+        }
+    }
+    if mode != KeyMode::Normal {
+        return hints;
+    }
+    let mut groups: Vec<(String, Vec<&str>)> = Vec::new();
+    for info in commands::COMMANDS {
+        for sequence in bindings.sequences_for(info.command) {
+            let sequence = bindings.expand(sequence);
+            let rest = match sequence.strip_prefix(pending) {
+                Some(rest) => rest,
+                None => continue,
+            };
+            let mut rest = rest.chars();
+            let next = match rest.next() {
+                Some(next) => commands::key_name(Key::Char(next)),
+                None => continue,
+            };
+            if rest.next().is_none() {
+                add(next, info.name);
+            } else if let Some((_, names)) = groups.iter_mut().find(|(key, _)| *key == next) {
+                names.push(info.name);
+            } else {
+                groups.push((next, vec![info.name]));
+            }
+        }
+    }
+    for (next, names) in groups {
+        add(next, &format!("+{}", names.join(", ")));
+    }
+    hints
+}
+
+/// The pending sequence as the hints title it, with the leader key named.
+pub fn title(pending: &str, bindings: &Bindings) -> String {
+    pending.strip_prefix(bindings.leader()).map_or_else(
+        || pending.to_string(),
+        |rest| format!("{}{}", commands::LEADER, rest),
+    )
+}
@@ -0,0 +1,141 @@
+//! The core of the hecto text editor.
+//!
+//! [`Editor`] ties everything together: build one from [`Args`], then either
+//! [`Editor::run`] it on the terminal or drive it yourself with [`Editor::feed_keys`]
+//! and [`Editor::step`]. A [`Document`] can also be used on its own: open a file with
+//! [`Document::open`], edit it through methods such as [`Document::insert_str`] and
+//! [`Document::delete_range`], and write it back with [`Document::save`].
+#![warn(clippy::all, clippy::pedantic, clippy::restriction)]
+#![allow(
+    clippy::missing_docs_in_private_items,
+    clippy::implicit_return,
+    clippy::shadow_reuse,
+    clippy::print_stdout,
+    clippy::wildcard_enum_match_arm,
+    clippy::else_if_without_else
+)]
+mod align;
+mod anchor;
+mod args;
+mod autocomplete;
+mod backup;
+mod bench;
+mod bidi;
+mod calc;
+mod clipboard;
+mod code_action;
+mod color;
+mod commands;
+mod completion;
+mod config;
+mod conflict;
+mod crypt;
+mod csv;
+mod diagnostics;
+mod diff;
+mod diff_view;
+mod document;
+mod editor;
+mod editorconfig;
+mod elastic;
+mod encoding;
+mod errorformat;
+mod event;
+mod export;
+mod filetype;
+mod fold;
+mod formatter;
+mod git;
+mod help;
+mod hex;
+mod highlight;
+mod history;
+mod hover;
+mod indent;
+mod job;
+mod journal;
+mod json;
+mod jump_list;
+mod key;
+mod key_hints;
+mod line_ending;
+mod lint;
+mod listchars;
+mod log;
+mod lsp;
+mod markdown;
+mod marks;
+mod minimap;
+mod mode_keys;
+mod modeline;
+mod options;
+mod outline;
+mod paths;
+mod plugin;
+mod preview;
+mod project;
+mod pty;
+mod quickfix;
+mod rainbow;
+mod recent;
+mod recording;
+mod regex;
+mod registers;
+mod remote;
+mod replace;
+mod row;
+mod save_options;
+mod script;
+mod scrollbar;
+mod search;
+mod search_index;
+mod session;
+mod shell;
+mod signature;
+mod snippet;
+mod special;
+mod spell;
+mod stats;
+mod statusline;
+mod substitute;
+mod sudo;
+mod surround;
+mod swap;
+mod swatch;
+mod tags;
+mod terminal;
+mod terminal_pane;
+mod text_object;
+mod theme;
+mod thesaurus;
+mod toml;
+mod transform;
+mod tutor;
+mod undo_tree;
+mod unicode;
+mod url;
+mod vt;
+mod word_index;
+pub use args::{Args, USAGE};
+pub use backup::Backup;
+pub use bench::{Benchmark, Samples};
+pub use config::Config;
+pub use document::Document;
+pub use editor::Editor;
+pub use editor::Position;
+pub use editor::SearchDirection;
+pub use encoding::Encoding;
+pub use history::History;
+pub use history::HistoryKind;
+pub use indent::Indent;
+pub use jump_list::Jump;
+pub use jump_list::JumpList;
+pub use key::Key;
+pub use line_ending::LineEnding;
+pub use listchars::ListChars;
+pub use marks::Marks;
+pub use registers::Registers;
+pub use row::Row;
+pub use save_options::SaveOptions;
+pub use terminal::Terminal;
+pub use theme::Theme;
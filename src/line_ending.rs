@@ -0,0 +1,39 @@
+#[derive(PartialEq, Clone, Copy, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// Picks the predominant line ending of `text`, and whether both kinds occur.
+    pub fn detect(text: &str) -> (Self, bool) {
+        let total = text.matches('\n').count();
+        let crlf = text.matches("\r\n").count();
+        let line_ending = if crlf > 0 && crlf.saturating_mul(2) >= total {
+            Self::CrLf
+        } else {
+            Self::Lf
+        };
+        (line_ending, crlf > 0 && crlf < total)
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "unix" | "lf" => Some(Self::Lf),
+            "dos" | "crlf" => Some(Self::CrLf),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "LF",
+            Self::CrLf => "CRLF",
+        }
+    }
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
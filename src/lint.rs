@@ -0,0 +1,120 @@
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::indent::Indent;
+use crate::json::Value;
+use crate::row;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Finds trailing whitespace, indentation mixing tabs with spaces, and lines wider than
+/// `limit` screen columns in `lines`, as warnings ordered by position.
+pub fn check(lines: &[&str], indent: &Indent, limit: Option<usize>) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut warn = |y: usize, start: usize, end: usize, message: String| {
+        diagnostics.push(Diagnostic {
+            start: (y, start),
+            end: (y, end),
+            severity: Severity::Warning,
+            message,
+            raw: Value::Null,
+            lint: true,
+        });
+    };
+    for (y, line) in lines.iter().enumerate() {
+        let leading = leading_whitespace(line);
+        if reindent(leading, indent).is_some() {
+            warn(
+                y,
+                0,
+                leading.len(),
+                "lint: indentation mixes tabs and spaces".to_string(),
+            );
+        }
+        if let Some(limit) = limit {
+            let mut column: usize = 0;
+            let mut offset: usize = 0;
+            for grapheme in line.graphemes(true) {
+                column =
+                    column.saturating_add(row::grapheme_width(grapheme, column, indent.tab_width));
+                if column > limit {
+                    warn(
+                        y,
+                        offset,
+                        utf16_len(line),
+                        format!("lint: line is longer than {} columns", limit),
+                    );
+                    break;
+                }
+                offset = offset.saturating_add(utf16_len(grapheme));
+            }
+        }
+        let trimmed = line.trim_end_matches(is_blank);
+        if trimmed.len() < line.len() {
+            warn(
+                y,
+                utf16_len(trimmed),
+                utf16_len(line),
+                "lint: trailing whitespace".to_string(),
+            );
+        }
+    }
+    diagnostics
+}
+
+/// `line` with its trailing whitespace removed and mixed indentation redone in the
+/// buffer's style, or `None` when there is nothing to fix. Long lines are left alone.
+pub fn fix(line: &str, indent: &Indent) -> Option<String> {
+    let leading = leading_whitespace(line);
+    let body = line
+        .get(leading.len()..)
+        .unwrap_or_default()
+        .trim_end_matches(is_blank);
+    let leading = if body.is_empty() {
+        String::new()
+    } else {
+        reindent(leading, indent).unwrap_or_else(|| leading.to_string())
+    };
+    let fixed = format!("{}{}", leading, body);
+    (fixed != line).then_some(fixed)
+}
+
+/// `leading` whitespace redone in the buffer's style when it mixes tabs with spaces
+/// other than as the alignment after tabs that indenting with tabs leaves, or `None`
+/// when it does not.
+fn reindent(leading: &str, indent: &Indent) -> Option<String> {
+    if !leading.contains(' ') || !leading.contains('\t') {
+        return None;
+    }
+    let width = leading.chars().fold(0, |column: usize, c| {
+        let width = if c == '\t' {
+            row::grapheme_width("\t", column, indent.tab_width)
+        } else {
+            1
+        };
+        column.saturating_add(width)
+    });
+    let reindented = if indent.use_tabs {
+        let tab_width = indent.tab_width.max(1);
+        format!(
+            "{}{}",
+            "\t".repeat(width / tab_width),
+            " ".repeat(width % tab_width)
+        )
+    } else {
+        " ".repeat(width)
+    };
+    (reindented != leading).then_some(reindented)
+}
+
+fn is_blank(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn leading_whitespace(line: &str) -> &str {
+    let end = line
+        .len()
+        .saturating_sub(line.trim_start_matches(is_blank).len());
+    line.get(..end).unwrap_or_default()
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
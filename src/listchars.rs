@@ -0,0 +1,65 @@
+/// The symbols `:set list` draws for whitespace, as given by the `listchars` option:
+/// `tab:» ,trail:·,nbsp:␣,eol:¬`. Leaving a kind out draws it as usual.
+#[derive(Clone)]
+pub struct ListChars {
+    /// Starts a tab; the second character fills the rest of its width.
+    pub tab: Option<(char, char)>,
+    /// Spaces after the last other character of a line.
+    pub trail: Option<char>,
+    pub nbsp: Option<char>,
+    /// Drawn after the last character of a line.
+    pub eol: Option<char>,
+}
+
+impl Default for ListChars {
+    fn default() -> Self {
+        Self {
+            tab: Some(('»', ' ')),
+            trail: Some('·'),
+            nbsp: Some('␣'),
+            eol: Some('¬'),
+        }
+    }
+}
+
+impl ListChars {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut list_chars = Self {
+            tab: None,
+            trail: None,
+            nbsp: None,
+            eol: None,
+        };
+        for item in spec.split(',').filter(|item| !item.is_empty()) {
+            let invalid = || format!("Invalid listchars item: {}", item);
+            let (kind, symbols) = item.split_once(':').ok_or_else(invalid)?;
+            let symbols: Vec<char> = symbols.chars().collect();
+            match (kind, symbols.as_slice()) {
+                ("tab", [first, fill]) => list_chars.tab = Some((*first, *fill)),
+                ("trail", [symbol]) => list_chars.trail = Some(*symbol),
+                ("nbsp", [symbol]) => list_chars.nbsp = Some(*symbol),
+                ("eol", [symbol]) => list_chars.eol = Some(*symbol),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(list_chars)
+    }
+    /// The option value that parses back to these symbols.
+    pub fn spec(&self) -> String {
+        let mut items = Vec::new();
+        if let Some((first, fill)) = self.tab {
+            items.push(format!("tab:{}{}", first, fill));
+        }
+        let singles = [
+            ("trail", self.trail),
+            ("nbsp", self.nbsp),
+            ("eol", self.eol),
+        ];
+        for (kind, symbol) in singles {
+            if let Some(symbol) = symbol {
+                items.push(format!("{}:{}", kind, symbol));
+            }
+        }
+        items.join(",")
+    }
+}
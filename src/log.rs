@@ -0,0 +1,185 @@
+use crate::paths;
+use crate::statusline;
+use std::fmt::Display;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How much a message matters; a filter keeps the messages at or above its level.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+const LEVELS: [(Level, &str); 6] = [
+    (Level::Off, "off"),
+    (Level::Error, "error"),
+    (Level::Warn, "warn"),
+    (Level::Info, "info"),
+    (Level::Debug, "debug"),
+    (Level::Trace, "trace"),
+];
+
+impl Level {
+    pub fn from_name(name: &str) -> Option<Self> {
+        LEVELS
+            .iter()
+            .find(|(_, level_name)| *level_name == name)
+            .map(|(level, _)| *level)
+    }
+    pub fn name(self) -> &'static str {
+        LEVELS
+            .iter()
+            .find(|(level, _)| *level == self)
+            .map_or("off", |(_, name)| name)
+    }
+}
+
+/// Which messages are written: a default level, and levels for single modules.
+struct Logger {
+    file: Option<File>,
+    default: Level,
+    modules: Vec<(String, Level)>,
+}
+
+static LOGGER: Mutex<Logger> = Mutex::new(Logger {
+    file: None,
+    default: Level::Warn,
+    modules: Vec::new(),
+});
+
+/// The file messages are appended to: `log` in the cache directory.
+pub fn path() -> Option<PathBuf> {
+    paths::cache_dir().map(|dir| dir.join("log"))
+}
+
+/// Sets the levels from a filter such as `info,lsp=debug,job=off`: a bare level is the
+/// default, and `module=level` overrides it for one module.
+pub fn set_filter(filter: &str) -> Result<(), String> {
+    let mut default = None;
+    let mut modules = Vec::new();
+    for item in filter
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+    {
+        let (module, name) = match item.split_once('=') {
+            Some((module, name)) => (Some(module.trim()), name.trim()),
+            None => (None, item),
+        };
+        let level = Level::from_name(name).ok_or_else(|| {
+            format!(
+                "unknown log level `{}` (use off, error, warn, info, debug or trace)",
+                name
+            )
+        })?;
+        match module {
+            Some(module) => modules.push((module.to_string(), level)),
+            None => default = Some(level),
+        }
+    }
+    if let Ok(mut logger) = LOGGER.lock() {
+        if let Some(default) = default {
+            logger.default = default;
+        }
+        for (module, level) in modules {
+            logger.modules.retain(|(name, _)| *name != module);
+            logger.modules.push((module, level));
+        }
+    }
+    Ok(())
+}
+
+/// The filter in force, in the form `set_filter` reads.
+pub fn filter() -> String {
+    LOGGER.lock().map_or_else(
+        |_| String::new(),
+        |logger| {
+            let mut items = vec![logger.default.name().to_string()];
+            items.extend(
+                logger
+                    .modules
+                    .iter()
+                    .map(|(module, level)| format!("{}={}", module, level.name())),
+            );
+            items.join(",")
+        },
+    )
+}
+
+/// Appends `message` to the log when the filter lets `level` through for `module`.
+/// Logging never fails the caller: a log that cannot be written is skipped.
+pub fn log(level: Level, module: &str, message: impl Display) {
+    let mut logger = match LOGGER.lock() {
+        Ok(logger) => logger,
+        Err(_) => return,
+    };
+    let threshold = logger
+        .modules
+        .iter()
+        .find(|(name, _)| name == module)
+        .map_or(logger.default, |(_, level)| *level);
+    if level == Level::Off || level > threshold {
+        return;
+    }
+    if logger.file.is_none() {
+        logger.file = path().and_then(|path| {
+            if let Some(dir) = path.parent() {
+                let _ = fs::create_dir_all(dir);
+            }
+            OpenOptions::new().create(true).append(true).open(path).ok()
+        });
+    }
+    if let Some(file) = &mut logger.file {
+        let now = SystemTime::now();
+        let millis = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.subsec_millis());
+        let _ = writeln!(
+            file,
+            "{}.{:03} {:<5} [{}] {}: {}",
+            statusline::time_of_day(now),
+            millis,
+            level.name().to_uppercase(),
+            std::process::id(),
+            module,
+            message
+        );
+    }
+}
+
+pub fn error(module: &str, message: impl Display) {
+    log(Level::Error, module, message);
+}
+
+pub fn warn(module: &str, message: impl Display) {
+    log(Level::Warn, module, message);
+}
+
+pub fn info(module: &str, message: impl Display) {
+    log(Level::Info, module, message);
+}
+
+pub fn debug(module: &str, message: impl Display) {
+    log(Level::Debug, module, message);
+}
+
+pub fn trace(module: &str, message: impl Display) {
+    log(Level::Trace, module, message);
+}
+
+/// The memory the process holds in RAM, in bytes, as Linux reports it.
+pub fn resident_memory() -> Option<usize> {
+    let statm = fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: usize = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: `sysconf` takes no pointers.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages.saturating_mul(usize::try_from(page_size).ok()?))
+}
@@ -0,0 +1,693 @@
+use crate::document::Edit;
+use crate::json::{self, Value};
+use crate::log;
+use crate::terminal::Waker;
+use crate::Document;
+use crate::Position;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SHUTDOWN_GRACE: Duration = Duration::from_millis(200);
+const CODE_ACTION_KINDS: &[&str] = &[
+    "",
+    "quickfix",
+    "refactor",
+    "refactor.extract",
+    "refactor.inline",
+    "refactor.rewrite",
+    "source",
+    "source.organizeImports",
+];
+
+pub enum Message {
+    /// Reply to a request, tagged with the request's method.
+    Response {
+        method: String,
+        result: Result<Value, String>,
+    },
+    Notification {
+        method: String,
+        params: Value,
+    },
+    /// A request the editor must act on before it is answered with `respond`.
+    Request {
+        id: Value,
+        method: String,
+        params: Value,
+    },
+}
+
+struct OpenDocument {
+    version: usize,
+    revision: usize,
+}
+
+/// A language server child process spoken to with JSON-RPC over its stdio.
+pub struct LanguageServer {
+    pub command: String,
+    child: Child,
+    stdin: ChildStdin,
+    incoming: Receiver<Value>,
+    next_id: usize,
+    /// Methods of requests still awaiting a response, by id.
+    pending: HashMap<usize, String>,
+    initialized: bool,
+    /// Messages held back until the server has answered `initialize`.
+    queued: Vec<Value>,
+    documents: HashMap<String, OpenDocument>,
+    /// What the server said it supports in its `initialize` result.
+    capabilities: Value,
+    /// Messages received while waiting on a blocking request.
+    deferred: Vec<Message>,
+}
+
+impl LanguageServer {
+    /// Spawns `command` through the shell and sends `initialize` for the workspace `root`.
+    pub fn start(command: &str, root: &Path, waker: Waker) -> Result<Self, Error> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| Error::new(ErrorKind::BrokenPipe, "server stdin unavailable"))?;
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| Error::new(ErrorKind::BrokenPipe, "server stdout unavailable"))?;
+        log::info(
+            "lsp",
+            format!("started `{}` in {}", command, root.display()),
+        );
+        let (sender, incoming) = mpsc::channel();
+        let name = command.to_string();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Some(body) = read_message(&mut reader) {
+                log::trace("lsp", format!("<- {}", body));
+                match json::parse(&body) {
+                    Ok(message) => {
+                        if sender.send(message).is_err() {
+                            break;
+                        }
+                        waker.wake();
+                    }
+                    Err(error) => log::warn("lsp", format!("unreadable message: {}", error)),
+                }
+            }
+            log::info("lsp", format!("`{}` closed its output", name));
+        });
+        let mut server = Self {
+            command: command.to_string(),
+            child,
+            stdin,
+            incoming,
+            next_id: 0,
+            pending: HashMap::new(),
+            initialized: false,
+            queued: Vec::new(),
+            documents: HashMap::new(),
+            capabilities: Value::Null,
+            deferred: Vec::new(),
+        };
+        let params = Value::object(vec![
+            (
+                "processId",
+                Value::from(usize::try_from(std::process::id()).unwrap_or(0)),
+            ),
+            ("rootUri", Value::from(uri(root))),
+            (
+                "clientInfo",
+                Value::object(vec![
+                    ("name", Value::from("hecto")),
+                    ("version", Value::from(env!("CARGO_PKG_VERSION"))),
+                ]),
+            ),
+            (
+                "capabilities",
+                Value::object(vec![
+                    (
+                        "workspace",
+                        Value::object(vec![
+                            ("applyEdit", Value::from(true)),
+                            (
+                                "workspaceEdit",
+                                Value::object(vec![("documentChanges", Value::from(true))]),
+                            ),
+                        ]),
+                    ),
+                    (
+                        "textDocument",
+                        Value::object(vec![
+                            (
+                                "synchronization",
+                                Value::object(vec![("didSave", Value::from(true))]),
+                            ),
+                            (
+                                "hover",
+                                Value::object(vec![(
+                                    "contentFormat",
+                                    Value::Array(vec![
+                                        Value::from("markdown"),
+                                        Value::from("plaintext"),
+                                    ]),
+                                )]),
+                            ),
+                            (
+                                "completion",
+                                Value::object(vec![(
+                                    "completionItem",
+                                    Value::object(vec![("snippetSupport", Value::from(false))]),
+                                )]),
+                            ),
+                            (
+                                "signatureHelp",
+                                Value::object(vec![(
+                                    "signatureInformation",
+                                    Value::object(vec![
+                                        (
+                                            "parameterInformation",
+                                            Value::object(vec![(
+                                                "labelOffsetSupport",
+                                                Value::from(true),
+                                            )]),
+                                        ),
+                                        ("activeParameterSupport", Value::from(true)),
+                                    ]),
+                                )]),
+                            ),
+                            (
+                                "rename",
+                                Value::object(vec![("prepareSupport", Value::from(false))]),
+                            ),
+                            (
+                                "foldingRange",
+                                Value::object(vec![("lineFoldingOnly", Value::from(true))]),
+                            ),
+                            (
+                                "codeAction",
+                                Value::object(vec![
+                                    (
+                                        "codeActionLiteralSupport",
+                                        Value::object(vec![(
+                                            "codeActionKind",
+                                            Value::object(vec![(
+                                                "valueSet",
+                                                Value::Array(
+                                                    CODE_ACTION_KINDS
+                                                        .iter()
+                                                        .map(|kind| Value::from(*kind))
+                                                        .collect(),
+                                                ),
+                                            )]),
+                                        )]),
+                                    ),
+                                    (
+                                        "resolveSupport",
+                                        Value::object(vec![(
+                                            "properties",
+                                            Value::Array(vec![Value::from("edit")]),
+                                        )]),
+                                    ),
+                                ]),
+                            ),
+                        ]),
+                    ),
+                ]),
+            ),
+        ]);
+        let message = server.request_message("initialize", params);
+        server.write(&message)?;
+        Ok(server)
+    }
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+    /// Sends a request; its response comes back from `poll` tagged with `method`.
+    pub fn request(&mut self, method: &str, params: Value) {
+        let message = self.request_message(method, params);
+        self.send(message);
+    }
+    /// Sends a notification; `Value::Null` params are left out.
+    pub fn notify(&mut self, method: &str, params: Value) {
+        self.send(message(
+            vec![
+                ("jsonrpc", Value::from("2.0")),
+                ("method", Value::from(method)),
+            ],
+            params,
+        ));
+    }
+    /// Opens the document at `uri` on the server, or sends its full text again when
+    /// `revision` moved since the last sync.
+    pub fn sync<F>(&mut self, uri: &str, language_id: &str, revision: usize, text: F)
+    where
+        F: FnOnce() -> String,
+    {
+        if let Some(document) = self.documents.get_mut(uri) {
+            if document.revision == revision {
+                return;
+            }
+            document.revision = revision;
+            document.version = document.version.saturating_add(1);
+            let version = document.version;
+            self.notify(
+                "textDocument/didChange",
+                Value::object(vec![
+                    (
+                        "textDocument",
+                        Value::object(vec![
+                            ("uri", Value::from(uri)),
+                            ("version", Value::from(version)),
+                        ]),
+                    ),
+                    (
+                        "contentChanges",
+                        Value::Array(vec![Value::object(vec![("text", Value::from(text()))])]),
+                    ),
+                ]),
+            );
+            return;
+        }
+        self.documents.insert(
+            uri.to_string(),
+            OpenDocument {
+                version: 1,
+                revision,
+            },
+        );
+        self.notify(
+            "textDocument/didOpen",
+            Value::object(vec![(
+                "textDocument",
+                Value::object(vec![
+                    ("uri", Value::from(uri)),
+                    ("languageId", Value::from(language_id)),
+                    ("version", Value::from(1_usize)),
+                    ("text", Value::from(text())),
+                ]),
+            )]),
+        );
+    }
+    pub fn did_save(&mut self, uri: &str) {
+        if self.documents.contains_key(uri) {
+            self.notify(
+                "textDocument/didSave",
+                Value::object(vec![(
+                    "textDocument",
+                    Value::object(vec![("uri", Value::from(uri))]),
+                )]),
+            );
+        }
+    }
+    /// Collects the messages received since the last call, answering server requests
+    /// and finishing the `initialize` handshake along the way. Server requests are
+    /// passed on as notifications too, so the editor can act on them.
+    pub fn poll(&mut self) -> Vec<Message> {
+        let mut messages = std::mem::take(&mut self.deferred);
+        while let Ok(message) = self.incoming.try_recv() {
+            messages.extend(self.receive(&message));
+        }
+        messages
+    }
+    /// Sends a request and waits up to `timeout` for its result. Messages arriving in
+    /// the meantime are handed out by the next `poll`.
+    pub fn request_blocking(
+        &mut self,
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Option<Result<Value, String>> {
+        let message = self.request_message(method, params);
+        let id = self.next_id;
+        self.send(message);
+        let deadline = Instant::now() + timeout;
+        loop {
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            let message = self.incoming.recv_timeout(remaining).ok()?;
+            if message.get("method").is_none()
+                && message.get("id").and_then(Value::as_usize) == Some(id)
+            {
+                self.pending.remove(&id);
+                return Some(response_result(&message));
+            }
+            let received = self.receive(&message);
+            self.deferred.extend(received);
+        }
+    }
+    /// Answers the server's request `id` with `result`.
+    pub fn respond(&mut self, id: Value, result: Value) {
+        self.send(Value::object(vec![
+            ("jsonrpc", Value::from("2.0")),
+            ("id", id),
+            ("result", result),
+        ]));
+    }
+    /// Whether the server has answered `initialize`.
+    pub fn is_ready(&self) -> bool {
+        self.initialized
+    }
+    /// Whether the server advertised `provider`, e.g. `documentFormattingProvider`.
+    pub fn supports(&self, provider: &str) -> bool {
+        !matches!(
+            self.capabilities.get(provider),
+            None | Some(Value::Null | Value::Bool(false))
+        )
+    }
+    fn receive(&mut self, message: &Value) -> Option<Message> {
+        let method = message.get("method").and_then(Value::as_str);
+        let id = message.get("id");
+        match (method, id) {
+            (Some(method), Some(id)) if method == "workspace/applyEdit" => Some(Message::Request {
+                id: id.clone(),
+                method: method.to_string(),
+                params: message.get("params").cloned().unwrap_or(Value::Null),
+            }),
+            (Some(method), Some(id)) => {
+                let result = reply_to_server_request(method, message);
+                self.respond(id.clone(), result);
+                Some(Message::Notification {
+                    method: method.to_string(),
+                    params: message.get("params").cloned().unwrap_or(Value::Null),
+                })
+            }
+            (Some(method), None) => Some(Message::Notification {
+                method: method.to_string(),
+                params: message.get("params").cloned().unwrap_or(Value::Null),
+            }),
+            (None, Some(id)) => {
+                let method = id.as_usize().and_then(|id| self.pending.remove(&id))?;
+                let result = response_result(message);
+                if method == "initialize" {
+                    self.finish_initialize(result.ok());
+                    None
+                } else {
+                    Some(Message::Response { method, result })
+                }
+            }
+            (None, None) => None,
+        }
+    }
+    fn finish_initialize(&mut self, result: Option<Value>) {
+        self.initialized = true;
+        if let Some(capabilities) = result
+            .as_ref()
+            .and_then(|result| result.get("capabilities"))
+        {
+            self.capabilities = capabilities.clone();
+        }
+        self.notify("initialized", Value::object(Vec::new()));
+        for message in std::mem::take(&mut self.queued) {
+            let _ = self.write(&message);
+        }
+    }
+    fn request_message(&mut self, method: &str, params: Value) -> Value {
+        self.next_id = self.next_id.saturating_add(1);
+        self.pending.insert(self.next_id, method.to_string());
+        message(
+            vec![
+                ("jsonrpc", Value::from("2.0")),
+                ("id", Value::from(self.next_id)),
+                ("method", Value::from(method)),
+            ],
+            params,
+        )
+    }
+    /// Writes `message`, or queues it while the server is still initializing.
+    fn send(&mut self, message: Value) {
+        if self.initialized || message.get("method").and_then(Value::as_str) == Some("initialized")
+        {
+            let _ = self.write(&message);
+        } else {
+            self.queued.push(message);
+        }
+    }
+    fn write(&mut self, message: &Value) -> Result<(), Error> {
+        let body = message.to_string();
+        log::trace("lsp", format!("-> {}", body));
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.stdin.flush()
+    }
+}
+
+impl Drop for LanguageServer {
+    fn drop(&mut self) {
+        if self.initialized {
+            self.request("shutdown", Value::Null);
+            self.notify("exit", Value::Null);
+            let deadline = Instant::now() + SHUTDOWN_GRACE;
+            while Instant::now() < deadline && self.is_running() {
+                thread::sleep(Duration::from_millis(10));
+            }
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// The result of a response, or the message of its error.
+fn response_result(message: &Value) -> Result<Value, String> {
+    match message.get("error") {
+        Some(error) => Err(error
+            .get("message")
+            .and_then(Value::as_str)
+            .unwrap_or("request failed")
+            .to_string()),
+        None => Ok(message.get("result").cloned().unwrap_or(Value::Null)),
+    }
+}
+
+fn message(mut fields: Vec<(&str, Value)>, params: Value) -> Value {
+    if params != Value::Null {
+        fields.push(("params", params));
+    }
+    Value::object(fields)
+}
+
+/// Answers requests the server makes of the client other than workspace edits, which
+/// the editor answers once it has tried them. Configuration items are left unset;
+/// everything else gets a null result.
+fn reply_to_server_request(method: &str, message: &Value) -> Value {
+    if method == "workspace/configuration" {
+        let items = message
+            .get("params")
+            .and_then(|params| params.get("items"))
+            .and_then(Value::as_array)
+            .map_or(0, <[Value]>::len);
+        return Value::Array(vec![Value::Null; items]);
+    }
+    Value::Null
+}
+
+/// Reads one `Content-Length` framed message body.
+fn read_message<R: BufRead>(reader: &mut R) -> Option<String> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let mut body = vec![0; length?];
+    reader.read_exact(&mut body).ok()?;
+    String::from_utf8(body).ok()
+}
+
+/// Turns a path into a `file://` URI, percent-encoding reserved bytes.
+pub fn uri(path: &Path) -> String {
+    let path = path
+        .canonicalize()
+        .or_else(|_| std::env::current_dir().map(|dir| dir.join(path)))
+        .unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-._~".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            uri.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    uri
+}
+
+/// Turns a `file://` URI back into a path, relative to the working directory when
+/// the file is inside it.
+pub fn path_from_uri(uri: &str) -> Option<String> {
+    let encoded = uri.strip_prefix("file://")?;
+    let mut bytes = Vec::new();
+    let mut rest = encoded.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(decoded) = escaped {
+            bytes.push(decoded);
+            rest = tail.get(2..).unwrap_or_default();
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    let path = PathBuf::from(String::from_utf8(bytes).ok()?);
+    let relative = std::env::current_dir()
+        .ok()
+        .and_then(|dir| dir.canonicalize().ok())
+        .and_then(|dir| path.strip_prefix(dir).ok().map(Path::to_path_buf));
+    Some(relative.unwrap_or(path).to_string_lossy().into_owned())
+}
+
+/// Reads a `Location` or `LocationLink` as a path, line and UTF-16 offset.
+pub fn location(value: &Value) -> Option<(String, usize, usize)> {
+    let uri = value
+        .get("uri")
+        .or_else(|| value.get("targetUri"))?
+        .as_str()?;
+    let range = value
+        .get("range")
+        .or_else(|| value.get("targetSelectionRange"))?;
+    let start = range.get("start")?;
+    Some((
+        path_from_uri(uri)?,
+        start.get("line")?.as_usize()?,
+        start.get("character")?.as_usize()?,
+    ))
+}
+
+/// A replacement of the text between two LSP positions (line, UTF-16 offset).
+pub struct TextEdit {
+    pub start: (usize, usize),
+    pub end: (usize, usize),
+    pub new_text: String,
+}
+
+/// Reads an array of `TextEdit`s.
+pub fn text_edits(value: &Value) -> Vec<TextEdit> {
+    let position = |value: &Value| -> Option<(usize, usize)> {
+        Some((
+            value.get("line")?.as_usize()?,
+            value.get("character")?.as_usize()?,
+        ))
+    };
+    value
+        .as_array()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|edit| {
+            let range = edit.get("range")?;
+            Some(TextEdit {
+                start: position(range.get("start")?)?,
+                end: position(range.get("end")?)?,
+                new_text: edit.get("newText")?.as_str()?.replace("\r\n", "\n"),
+            })
+        })
+        .collect()
+}
+
+/// Reads a `WorkspaceEdit` as the text edits for each file, from either its
+/// `documentChanges` or its `changes`. File create, rename and delete operations
+/// are not supported and are skipped.
+pub fn workspace_edit(value: &Value) -> Vec<(String, Vec<TextEdit>)> {
+    if let Some(changes) = value.get("documentChanges").and_then(Value::as_array) {
+        return changes
+            .iter()
+            .filter_map(|change| {
+                let uri = change.get("textDocument")?.get("uri")?.as_str()?;
+                Some((path_from_uri(uri)?, text_edits(change.get("edits")?)))
+            })
+            .collect();
+    }
+    match value.get("changes") {
+        Some(Value::Object(changes)) => changes
+            .iter()
+            .filter_map(|(uri, edits)| Some((path_from_uri(uri)?, text_edits(edits))))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Applies `edits`, which must not overlap, to `text`.
+pub fn apply_edits_to_text(text: &str, edits: &[TextEdit]) -> String {
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(
+            text.match_indices('\n')
+                .map(|(index, _)| index.saturating_add(1)),
+        )
+        .collect();
+    let offset = |(line, character): (usize, usize)| {
+        let start = if let Some(start) = line_starts.get(line) {
+            *start
+        } else {
+            return text.len();
+        };
+        let line_text = text.get(start..).unwrap_or_default();
+        let line_text = line_text.split('\n').next().unwrap_or_default();
+        let mut units = 0_usize;
+        for (index, c) in line_text.char_indices() {
+            if units >= character {
+                return start.saturating_add(index);
+            }
+            units = units.saturating_add(c.len_utf16());
+        }
+        start.saturating_add(line_text.len())
+    };
+    let mut edits: Vec<&TextEdit> = edits.iter().collect();
+    edits.sort_by_key(|edit| std::cmp::Reverse(edit.start));
+    let mut result = text.to_string();
+    for edit in edits {
+        let start = offset(edit.start);
+        let end = offset(edit.end).max(start);
+        if result.is_char_boundary(start) && result.is_char_boundary(end) {
+            result.replace_range(start..end, &edit.new_text);
+        }
+    }
+    result
+}
+
+/// Applies `edits`, which must not overlap, to `document` as a single undo step,
+/// moving `positions` with the text around them.
+pub fn apply_edits(document: &mut Document, edits: &[TextEdit], positions: &mut [Position]) {
+    let position = |(line, character): (usize, usize)| Position {
+        x: document
+            .row(line)
+            .map_or(0, |row| row.index_at_utf16(character)),
+        y: line.min(document.len()),
+    };
+    let edits: Vec<Edit> = edits
+        .iter()
+        .map(|edit| Edit {
+            start: position(edit.start),
+            end: position(edit.end),
+            text: edit.new_text.clone(),
+        })
+        .collect();
+    document.apply_edits(&edits, positions);
+}
+
+/// The LSP language identifier for one of hecto's file type names.
+pub fn language_id(file_type: &str) -> &str {
+    match file_type {
+        "shell" => "shellscript",
+        "make" => "makefile",
+        file_type => file_type,
+    }
+}
@@ -7,17 +7,48 @@
     clippy::wildcard_enum_match_arm,
     clippy::else_if_without_else
 )]
-mod document;
-mod editor;
-mod row;
-mod terminal;
-pub use document::Document;
-use editor::Editor;
-pub use editor::Position;
-pub use editor::SearchDirection;
-pub use row::Row;
-pub use terminal::Terminal;
+use hecto::{Args, Benchmark, Editor, USAGE};
+
 fn main() {
-    let mut editor = Editor::default();
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("hecto: {}\n\n{}", error, USAGE);
+            std::process::exit(2);
+        }
+    };
+    if args.help {
+        println!("{}", USAGE);
+        return;
+    }
+    if args.version {
+        println!("hecto {}", env!("CARGO_PKG_VERSION"));
+        return;
+    }
+    if let Some(lines) = args.bench {
+        let benchmark = Benchmark {
+            lines,
+            ..Benchmark::default()
+        };
+        match benchmark.run() {
+            Ok(samples) => {
+                println!("hecto --bench: {} lines", lines);
+                for samples in samples {
+                    println!("{}", samples);
+                }
+            }
+            Err(error) => {
+                eprintln!("hecto: bench: {}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let mut editor = Editor::new(&args);
+    if !args.exec.is_empty() {
+        let succeeded = editor.exec(&args.exec);
+        drop(editor);
+        std::process::exit(if succeeded { 0 } else { 1 });
+    }
     editor.run();
 }
@@ -0,0 +1,175 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// What Enter does at the end of a list item or quote.
+pub enum Continuation {
+    /// Starts the next line with this prefix: the indentation and the marker.
+    Prefix(String),
+    /// The item was left empty, so the list ends: the marker is removed instead.
+    End,
+}
+
+/// How Enter continues `line`, or `None` when it is not a list item or quote.
+pub fn continuation(line: &str) -> Option<Continuation> {
+    let (prefix, rest) = marker(line)?;
+    if rest.trim().is_empty() {
+        return Some(Continuation::End);
+    }
+    let indent: String = line.chars().take_while(|c| c.is_whitespace()).collect();
+    let marker = prefix.get(indent.len()..).unwrap_or_default();
+    let next = if let Some(number) = marker
+        .strip_suffix(". ")
+        .and_then(|number| number.parse::<usize>().ok())
+    {
+        format!("{}. ", number.saturating_add(1))
+    } else if marker.ends_with("] ") {
+        // A task list continues with an open task.
+        format!("{}[ ] ", marker.get(..2).unwrap_or_default())
+    } else {
+        marker.to_string()
+    };
+    Some(Continuation::Prefix(format!("{}{}", indent, next)))
+}
+
+/// Splits `line` into its list or quote marker, with the indentation before it, and
+/// the text after it.
+fn marker(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    let indent = line.len().saturating_sub(trimmed.len());
+    let length = if trimmed.starts_with("> ") {
+        2
+    } else if ["- ", "* ", "+ "]
+        .iter()
+        .any(|bullet| trimmed.starts_with(bullet))
+    {
+        let task = trimmed.get(2..).is_some_and(|rest| {
+            rest.starts_with("[ ] ") || rest.starts_with("[x] ") || rest.starts_with("[X] ")
+        });
+        if task {
+            6
+        } else {
+            2
+        }
+    } else {
+        let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 || !trimmed.get(digits..)?.starts_with(". ") {
+            return None;
+        }
+        digits.saturating_add(2)
+    };
+    let end = indent.saturating_add(length);
+    Some((line.get(..end)?, line.get(end..)?))
+}
+
+/// The grapheme index of the box of a `- [ ]` task in `line`, and what it should
+/// hold once toggled.
+pub fn checkbox(line: &str) -> Option<(usize, char)> {
+    let trimmed = line.trim_start();
+    let indent = line.len().saturating_sub(trimmed.len());
+    let rest = ["- ", "* ", "+ "]
+        .iter()
+        .find_map(|bullet| trimmed.strip_prefix(bullet))?;
+    let toggled = match rest.get(..3)? {
+        "[ ]" => 'x',
+        "[x]" | "[X]" => ' ',
+        _ => return None,
+    };
+    // Indentation and bullets are one byte per grapheme.
+    Some((indent.saturating_add(3), toggled))
+}
+
+/// Folds for each heading, running to the line before the next heading of the same
+/// or a higher level. Lines in fenced code blocks are not headings.
+pub fn heading_folds(lines: &[&str]) -> Vec<(usize, usize)> {
+    let mut in_fence = false;
+    let mut headings: Vec<(usize, usize)> = Vec::new();
+    for (y, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+        let level = line.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&level) && line.get(level..).is_some_and(|rest| rest.starts_with(' ')) {
+            headings.push((y, level));
+        }
+    }
+    let mut folds = Vec::new();
+    for (index, (start, level)) in headings.iter().enumerate() {
+        let next = headings
+            .iter()
+            .skip(index.saturating_add(1))
+            .find(|(_, next_level)| next_level <= level)
+            .map_or(lines.len(), |(y, _)| *y);
+        // Blank lines before the next heading stay outside the fold.
+        let mut end = next.saturating_sub(1);
+        while end > *start && lines.get(end).is_some_and(|line| line.trim().is_empty()) {
+            end = end.saturating_sub(1);
+        }
+        if end > *start {
+            folds.push((*start, end));
+        }
+    }
+    folds
+}
+
+/// Whether `line` is a row of a pipe table.
+pub fn is_table_row(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+/// Pads the cells of a pipe table so its columns line up, redrawing the delimiter
+/// row to the new widths while keeping its alignment colons.
+pub fn align_table(rows: &[&str]) -> Vec<String> {
+    let indent: String = rows
+        .first()
+        .map(|row| row.chars().take_while(|c| c.is_whitespace()).collect())
+        .unwrap_or_default();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            let row = row.trim();
+            let row = row.strip_prefix('|').unwrap_or(row);
+            let row = row.strip_suffix('|').unwrap_or(row);
+            row.split('|').map(|cell| cell.trim().to_string()).collect()
+        })
+        .collect();
+    let is_delimiter = |row: &[String]| {
+        row.iter()
+            .all(|cell| !cell.is_empty() && cell.chars().all(|c| c == '-' || c == ':'))
+    };
+    let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let mut widths = vec![3_usize; columns];
+    for row in cells.iter().filter(|row| !is_delimiter(row)) {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.graphemes(true).count());
+        }
+    }
+    cells
+        .iter()
+        .map(|row| {
+            let delimiter = is_delimiter(row);
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(index, width)| {
+                    let cell = row.get(index).map_or("", String::as_str);
+                    if delimiter {
+                        let left = if cell.starts_with(':') { ":" } else { "-" };
+                        let right = if cell.len() > 1 && cell.ends_with(':') {
+                            ":"
+                        } else {
+                            "-"
+                        };
+                        format!("{}{}{}", left, "-".repeat(width.saturating_sub(2)), right)
+                    } else {
+                        let padding = width.saturating_sub(cell.graphemes(true).count());
+                        format!("{}{}", cell, " ".repeat(padding))
+                    }
+                })
+                .collect();
+            format!("{}| {} |", indent, cells.join(" | "))
+        })
+        .collect()
+}
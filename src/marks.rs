@@ -0,0 +1,4 @@
+use crate::anchor::Anchors;
+
+/// The named positions set with `m`, which move with the text around them.
+pub type Marks = Anchors<char>;
@@ -0,0 +1,120 @@
+use crate::diff;
+
+/// Columns of the minimap pane, including the blank column separating it from the text.
+pub const WIDTH: usize = 12;
+/// Buffer lines shown by one screen row: a braille cell is four dots tall.
+pub const LINES_PER_ROW: usize = 4;
+/// Text columns each braille dot stands for.
+const COLUMNS_PER_DOT: usize = 4;
+
+/// A bird's-eye view of a buffer: each line becomes a row of dots, one per few columns
+/// holding text, drawn as braille. Kept current by re-reading only the lines that
+/// changed since the last update.
+#[derive(Default)]
+pub struct Minimap {
+    /// Which dots of each line are set, lowest bit leftmost.
+    dots: Vec<u64>,
+    /// The lines as of the last update.
+    lines: Vec<String>,
+    /// The buffer name and revision the dots match.
+    pub revision: Option<(Option<String>, usize)>,
+}
+
+impl Minimap {
+    pub fn update(&mut self, lines: &[&str], tab_width: usize, revision: (Option<String>, usize)) {
+        let hunks = {
+            let old: Vec<&str> = self.lines.iter().map(String::as_str).collect();
+            diff::lines(&old, lines)
+        };
+        for hunk in hunks.iter().rev() {
+            let new_lines = lines
+                .get(hunk.new_start..hunk.new_start.saturating_add(hunk.new_len))
+                .unwrap_or_default();
+            let end = hunk
+                .old_start
+                .saturating_add(hunk.old_len)
+                .min(self.lines.len());
+            let start = hunk.old_start.min(end);
+            self.dots.splice(
+                start..end,
+                new_lines.iter().map(|line| dots(line, tab_width)),
+            );
+            self.lines
+                .splice(start..end, new_lines.iter().map(|line| (*line).to_string()));
+        }
+        self.revision = Some(revision);
+    }
+    /// The first line shown when `height` rows are available and lines `first..last`
+    /// are on screen. Long buffers scroll the minimap in proportion to the text.
+    pub fn top(&self, first: usize, last: usize, height: usize) -> usize {
+        let capacity = height.saturating_mul(LINES_PER_ROW);
+        let visible = last.saturating_sub(first);
+        if self.dots.len() <= capacity || self.dots.len() <= visible {
+            return 0;
+        }
+        let top = first.saturating_mul(self.dots.len().saturating_sub(capacity))
+            / self.dots.len().saturating_sub(visible);
+        // Whole rows keep the same lines together in one cell as the view scrolls.
+        top.saturating_sub(top % LINES_PER_ROW)
+    }
+    /// The braille text of the `height` rows from line `top`, each `width` cells wide.
+    pub fn rows(&self, top: usize, height: usize, width: usize) -> Vec<String> {
+        (0..height)
+            .map(|row| {
+                let y = top.saturating_add(row.saturating_mul(LINES_PER_ROW));
+                let lines: Vec<u64> = (0..LINES_PER_ROW)
+                    .map(|offset| {
+                        self.dots
+                            .get(y.saturating_add(offset))
+                            .copied()
+                            .unwrap_or(0)
+                    })
+                    .collect();
+                (0..width)
+                    .map(|x| braille(&lines, x.saturating_mul(2)))
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// The dots for `line`: one per `COLUMNS_PER_DOT` columns, set where any of them is not
+/// whitespace.
+fn dots(line: &str, tab_width: usize) -> u64 {
+    let mut dots = 0_u64;
+    let mut column = 0_usize;
+    for c in line.chars() {
+        if c == '\t' {
+            let tab_width = tab_width.max(1);
+            column = column
+                .saturating_add(tab_width)
+                .saturating_sub(column % tab_width);
+            continue;
+        }
+        let dot = column / COLUMNS_PER_DOT;
+        if dot >= 64 {
+            break;
+        }
+        if !c.is_whitespace() {
+            dots |= 1 << dot;
+        }
+        column = column.saturating_add(1);
+    }
+    dots
+}
+
+/// The braille character for dot columns `x` and `x + 1` of four lines.
+fn braille(lines: &[u64], x: usize) -> char {
+    // Bits of the left and right dot of each row of a braille cell.
+    const BITS: [(u32, u32); 4] = [(0x01, 0x08), (0x02, 0x10), (0x04, 0x20), (0x40, 0x80)];
+    let mut code = 0x2800_u32;
+    for (line, (left, right)) in lines.iter().zip(BITS) {
+        if x < 64 && line & (1 << x) != 0 {
+            code |= left;
+        }
+        if x < 63 && line & (1 << x.saturating_add(1)) != 0 {
+            code |= right;
+        }
+    }
+    char::from_u32(code).unwrap_or(' ')
+}
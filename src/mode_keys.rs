@@ -0,0 +1,447 @@
+use crate::commands::{self, Command};
+use crate::Key;
+
+/// The mode a key sequence is typed in.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum KeyMode {
+    Normal,
+    Visual,
+    Insert,
+}
+
+/// What a modal key sequence does; the editor runs it with the keys that were typed.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum KeyAction {
+    /// Runs a command ahead of the key bindings.
+    Command(Command),
+    Move,
+    Delete,
+    Change,
+    DeleteObject,
+    ChangeObject,
+    SurroundObject,
+    DeleteSurround,
+    ChangeSurround,
+    SetMark,
+    GotoMarkLine,
+    GotoMark,
+    Record,
+    StopRecording,
+    PlayMacro,
+    ReplaceChar,
+    DeleteChar,
+    Insert,
+    Visual,
+    Repeat,
+    SelectObject,
+    SurroundSelection,
+    FilterSelection,
+    CopySelection,
+    CopySelectionHtml,
+    SwapSelectionEnds,
+    DeleteSelection,
+    ChangeSelection,
+    NextWord,
+    PreviousWord,
+    SnippetStop,
+    CharInput,
+}
+
+/// One key of a sequence: a fixed key, or a placeholder that takes any of several.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Pattern {
+    Key(Key),
+    Char(char),
+    /// One of the motions `h j k l 0 $`.
+    Motion,
+    /// A text object such as `w`, `"` or `(`.
+    Object,
+    /// Any character, shown as the name given.
+    Any(&'static str),
+}
+
+impl Pattern {
+    fn matches(self, key: Key) -> bool {
+        match (self, key) {
+            (Self::Key(expected), key) => expected == key,
+            (Self::Char(expected), Key::Char(c)) => expected == c,
+            (Self::Motion, Key::Char(c)) => MOTIONS.contains(c),
+            (Self::Object | Self::Any(_), Key::Char(_)) => true,
+            _ => false,
+        }
+    }
+    pub fn name(self) -> String {
+        match self {
+            Self::Key(key) => commands::key_name(key),
+            Self::Char(c) => c.to_string(),
+            Self::Motion => "{motion}".to_string(),
+            Self::Object => "{object}".to_string(),
+            Self::Any(name) => format!("{{{}}}", name),
+        }
+    }
+}
+
+/// The keys that move the cursor in normal and visual mode, and follow `d` and `c`.
+pub const MOTIONS: &str = "hjkl0$";
+
+/// The motions as help and key hints show them.
+pub fn motions() -> String {
+    MOTIONS
+        .chars()
+        .map(String::from)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The text objects accepted after `i` and `a`.
+pub const OBJECTS: &[(&str, &str)] = &[
+    ("w", "word"),
+    ("\" ' `", "quoted string"),
+    ("( ) b", "parentheses"),
+    ("[ ]", "brackets"),
+    ("{ } B", "braces"),
+    ("< >", "angle brackets"),
+    ("t", "XML/HTML tag"),
+];
+
+pub struct ModeKey {
+    pub mode: KeyMode,
+    pub keys: &'static [Pattern],
+    pub action: KeyAction,
+    pub description: &'static str,
+}
+
+impl ModeKey {
+    /// The keys as help shows them, e.g. `d{motion}` or `Ctrl-V u{hex}`.
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        for pattern in self.keys {
+            if name.ends_with(|c: char| c.is_ascii_alphanumeric())
+                && matches!(pattern, Pattern::Key(_))
+            {
+                name.push(' ');
+            }
+            name.push_str(&pattern.name());
+            if matches!(pattern, Pattern::Key(_)) {
+                name.push(' ');
+            }
+        }
+        name.trim_end().to_string()
+    }
+}
+
+use Pattern::{Any, Char, Motion, Object};
+
+/// The keys of the editing modes that are not commands: operators, marks, macros and
+/// the like, which take further keys, and the keys each mode handles itself.
+pub const MODE_KEYS: &[ModeKey] = &[
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Motion],
+        action: KeyAction::Move,
+        description: "Move; prefix a count to repeat",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('i')],
+        action: KeyAction::Insert,
+        description: "Return to insert mode",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('v')],
+        action: KeyAction::Visual,
+        description: "Start a visual selection",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('x')],
+        action: KeyAction::DeleteChar,
+        description: "Delete the character under the cursor",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('r'), Any("c")],
+        action: KeyAction::ReplaceChar,
+        description: "Replace the character under the cursor",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('d'), Char('d')],
+        action: KeyAction::Delete,
+        description: "Delete the line",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('d'), Motion],
+        action: KeyAction::Delete,
+        description: "Delete to where the motion goes",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('c'), Char('c')],
+        action: KeyAction::Change,
+        description: "Change the line",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('c'), Motion],
+        action: KeyAction::Change,
+        description: "Change to where the motion goes",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('d'), Char('i'), Object],
+        action: KeyAction::DeleteObject,
+        description: "Delete the inside of a text object",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('d'), Char('a'), Object],
+        action: KeyAction::DeleteObject,
+        description: "Delete a text object with its delimiters",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('c'), Char('i'), Object],
+        action: KeyAction::ChangeObject,
+        description: "Change the inside of a text object",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('c'), Char('a'), Object],
+        action: KeyAction::ChangeObject,
+        description: "Change a text object with its delimiters",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('y'), Char('s'), Char('i'), Object, Any("c")],
+        action: KeyAction::SurroundObject,
+        description: "Surround the inside of a text object",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('y'), Char('s'), Char('a'), Object, Any("c")],
+        action: KeyAction::SurroundObject,
+        description: "Surround a text object with its delimiters",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('d'), Char('s'), Any("c")],
+        action: KeyAction::DeleteSurround,
+        description: "Delete surrounding delimiters, e.g. ( \" t",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('c'), Char('s'), Any("old"), Any("new")],
+        action: KeyAction::ChangeSurround,
+        description: "Change surrounding delimiters",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('m'), Any("a-z")],
+        action: KeyAction::SetMark,
+        description: "Set a mark",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('\''), Any("a-z")],
+        action: KeyAction::GotoMarkLine,
+        description: "Jump to the line of a mark",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('`'), Any("a-z")],
+        action: KeyAction::GotoMark,
+        description: "Jump to the position of a mark",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('q')],
+        action: KeyAction::StopRecording,
+        description: "Stop recording a macro",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('q'), Any("reg")],
+        action: KeyAction::Record,
+        description: "Record a macro into a register",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('@'), Any("reg")],
+        action: KeyAction::PlayMacro,
+        description: "Play the macro in a register, @@ the last one",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Char('.')],
+        action: KeyAction::Repeat,
+        description: "Repeat the last edit",
+    },
+    ModeKey {
+        mode: KeyMode::Normal,
+        keys: &[Pattern::Key(Key::Ctrl('y'))],
+        action: KeyAction::Command(Command::ScrollLineUp),
+        description: "Scroll the text a line up without moving the cursor",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Motion],
+        action: KeyAction::Move,
+        description: "Extend the selection",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('i'), Object],
+        action: KeyAction::SelectObject,
+        description: "Select the inside of a text object",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('a'), Object],
+        action: KeyAction::SelectObject,
+        description: "Select a text object with its delimiters",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('S'), Any("c")],
+        action: KeyAction::SurroundSelection,
+        description: "Surround the selection, e.g. ( \" <",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('!')],
+        action: KeyAction::FilterSelection,
+        description: "Filter the selected lines through a shell command",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('y')],
+        action: KeyAction::CopySelection,
+        description: "Copy the selection to the clipboard",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('Y')],
+        action: KeyAction::CopySelectionHtml,
+        description: "Copy the selection to the clipboard as colored HTML",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('o')],
+        action: KeyAction::SwapSelectionEnds,
+        description: "Move the cursor to the other end of the selection",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('d')],
+        action: KeyAction::DeleteSelection,
+        description: "Delete the selection",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('x')],
+        action: KeyAction::DeleteSelection,
+        description: "Delete the selection",
+    },
+    ModeKey {
+        mode: KeyMode::Visual,
+        keys: &[Char('c')],
+        action: KeyAction::ChangeSelection,
+        description: "Change the selection",
+    },
+    ModeKey {
+        mode: KeyMode::Insert,
+        keys: &[Pattern::Key(Key::Ctrl('n'))],
+        action: KeyAction::NextWord,
+        description: "Complete the word before the cursor from words in the open buffers",
+    },
+    ModeKey {
+        mode: KeyMode::Insert,
+        keys: &[Pattern::Key(Key::Ctrl('p'))],
+        action: KeyAction::PreviousWord,
+        description: "The same, cycling backwards (Vim keymap, or once a cycle has started)",
+    },
+    ModeKey {
+        mode: KeyMode::Insert,
+        keys: &[Pattern::Key(Key::Char('\t'))],
+        action: KeyAction::SnippetStop,
+        description: "Expand the snippet named by the word before the cursor (from snippets/<filetype>.snippets or snippets/all.snippets in the config directory), or go to the next stop of the snippet being filled in",
+    },
+    ModeKey {
+        mode: KeyMode::Insert,
+        keys: &[Pattern::Key(Key::BackTab)],
+        action: KeyAction::SnippetStop,
+        description: "Go to the previous stop of the snippet being filled in",
+    },
+    ModeKey {
+        mode: KeyMode::Insert,
+        keys: &[Pattern::Key(Key::Ctrl('v')), Char('u'), Any("hex")],
+        action: KeyAction::CharInput,
+        description: "Insert a character by code point (U<hex> for more than four digits)",
+    },
+    ModeKey {
+        mode: KeyMode::Insert,
+        keys: &[Pattern::Key(Key::Ctrl('v')), Any("a"), Any("b")],
+        action: KeyAction::CharInput,
+        description: "Insert the character for a digraph, e.g. a: for ä (see :digraphs)",
+    },
+    ModeKey {
+        mode: KeyMode::Insert,
+        keys: &[Pattern::Key(Key::Ctrl('v')), Pattern::Key(Key::Char('\t'))],
+        action: KeyAction::CharInput,
+        description: "Insert a tab character whatever the indent style",
+    },
+];
+
+/// How far typed keys got towards a key sequence.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Lookup<T> {
+    /// The keys make up the sequence for this.
+    Found(T),
+    /// The keys start one or more sequences.
+    Pending,
+    Unknown,
+}
+
+/// Looks up `keys` among the keys of `mode`, skipping actions `available` turns down.
+/// A complete sequence wins over longer ones it starts.
+pub fn find(
+    mode: KeyMode,
+    keys: &[Key],
+    available: impl Fn(KeyAction) -> bool,
+) -> Lookup<KeyAction> {
+    let mut lookup = Lookup::Unknown;
+    for mode_key in MODE_KEYS
+        .iter()
+        .filter(|mode_key| mode_key.mode == mode && available(mode_key.action))
+    {
+        if !starts(mode_key.keys, keys) {
+            continue;
+        }
+        if mode_key.keys.len() == keys.len() {
+            return Lookup::Found(mode_key.action);
+        }
+        lookup = Lookup::Pending;
+    }
+    lookup
+}
+
+/// Whether `keys` begin the sequence `patterns`.
+pub fn starts(patterns: &[Pattern], keys: &[Key]) -> bool {
+    keys.len() <= patterns.len()
+        && patterns
+            .iter()
+            .zip(keys)
+            .all(|(pattern, key)| pattern.matches(*key))
+}
+
+/// The action of the first sequence of `mode` that `key` starts, for modes that read
+/// the keys after it themselves.
+pub fn first_key(mode: KeyMode, key: Key) -> Option<KeyAction> {
+    MODE_KEYS
+        .iter()
+        .find(|mode_key| mode_key.mode == mode && starts(mode_key.keys, &[key]))
+        .map(|mode_key| mode_key.action)
+}
@@ -0,0 +1,78 @@
+use crate::Document;
+use crate::LineEnding;
+
+/// How many lines at each end of the file are searched, as in Vim.
+const SCAN_LINES: usize = 5;
+
+/// Applies the settings of any Vim modeline (e.g. `# vim: sw=2 ts=2 et`) found near the
+/// start or end of `document`. Only indentation and file format options are honored.
+pub fn apply(document: &mut Document) {
+    let len = document.len();
+    let tail_start = len.saturating_sub(SCAN_LINES).max(SCAN_LINES.min(len));
+    let lines: Vec<String> = (0..SCAN_LINES.min(len))
+        .chain(tail_start..len)
+        .filter_map(|y| document.row(y).map(|row| row.as_str().to_string()))
+        .collect();
+    for line in lines {
+        if let Some(options) = parse(&line) {
+            for option in options {
+                apply_option(document, &option);
+            }
+        }
+    }
+}
+
+/// Extracts the options of a modeline in either the `vim: ts=2 et` or the
+/// `vim: set ts=2 et:` form.
+fn parse(line: &str) -> Option<Vec<String>> {
+    let start = ["vim:", "vi:", "ex:"]
+        .iter()
+        .filter_map(|marker| {
+            line.match_indices(marker)
+                .find(|(index, _)| {
+                    *index == 0
+                        || line
+                            .get(..*index)
+                            .and_then(|before| before.chars().last())
+                            .is_some_and(char::is_whitespace)
+                })
+                .map(|(index, marker)| index.saturating_add(marker.len()))
+        })
+        .min()?;
+    let rest = line.get(start..)?.trim_start();
+    let options = if let Some(set) = rest
+        .strip_prefix("set ")
+        .or_else(|| rest.strip_prefix("se "))
+    {
+        // In the `set` form everything after the closing `:` is ignored.
+        set.split(':')
+            .next()
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(str::to_string)
+            .collect()
+    } else {
+        rest.split(|c: char| c == ':' || c.is_whitespace())
+            .filter(|option| !option.is_empty())
+            .map(str::to_string)
+            .collect()
+    };
+    Some(options)
+}
+
+fn apply_option(document: &mut Document, option: &str) {
+    let (name, value) = option.split_once('=').unwrap_or((option, ""));
+    let number = value.parse::<usize>().ok().filter(|number| *number > 0);
+    match (name, number) {
+        ("ts" | "tabstop", Some(width)) => document.indent.tab_width = width,
+        ("sw" | "shiftwidth", Some(size)) => document.indent.size = size,
+        ("et" | "expandtab", _) => document.indent.use_tabs = false,
+        ("noet" | "noexpandtab", _) => document.indent.use_tabs = true,
+        ("ff" | "fileformat", _) => {
+            if let Some(ending) = LineEnding::from_name(value) {
+                document.line_ending = ending;
+            }
+        }
+        _ => (),
+    }
+}
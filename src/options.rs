@@ -0,0 +1,491 @@
+use std::fmt;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Scope {
+    /// Shared by every buffer.
+    Global,
+    /// Stored on the current document.
+    Buffer,
+    /// How the current window shows its buffer, kept by the window whichever buffer
+    /// it shows; a split starts out with its parent's.
+    Window,
+}
+
+impl Scope {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Global => "global",
+            Self::Buffer => "buffer",
+            Self::Window => "window",
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum Kind {
+    Bool,
+    Number,
+    Text,
+}
+
+pub struct OptionInfo {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub kind: Kind,
+    pub scope: Scope,
+    pub description: &'static str,
+}
+
+#[derive(PartialEq, Clone)]
+pub enum Value {
+    Bool(bool),
+    Number(usize),
+    Text(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Bool(value) => write!(f, "{}", value),
+            Self::Number(value) => write!(f, "{}", value),
+            Self::Text(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+pub enum Action {
+    Set(Value),
+    Toggle,
+    Query,
+}
+
+pub const OPTIONS: &[OptionInfo] = &[
+    OptionInfo {
+        name: "number",
+        aliases: &["nu"],
+        kind: Kind::Bool,
+        scope: Scope::Window,
+        description: "Show line numbers",
+    },
+    OptionInfo {
+        name: "wrap",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Window,
+        description: "Wrap long lines instead of scrolling sideways",
+    },
+    OptionInfo {
+        name: "list",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Window,
+        description: "Show tabs, trailing spaces, non-breaking spaces and line ends",
+    },
+    OptionInfo {
+        name: "listchars",
+        aliases: &["lcs"],
+        kind: Kind::Text,
+        scope: Scope::Global,
+        description: "Symbols for list mode, e.g. tab:» ,trail:·,nbsp:␣,eol:¬",
+    },
+    OptionInfo {
+        name: "indentguides",
+        aliases: &["ig"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Draw a guide at each indentation level",
+    },
+    OptionInfo {
+        name: "cursorline",
+        aliases: &["cul"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Highlight the line the cursor is on",
+    },
+    OptionInfo {
+        name: "cursorcolumn",
+        aliases: &["cuc"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Highlight the screen column the cursor is in",
+    },
+    OptionInfo {
+        name: "blame",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Show the commit that last changed the cursor line",
+    },
+    OptionInfo {
+        name: "scrolloff",
+        aliases: &["so"],
+        kind: Kind::Number,
+        scope: Scope::Global,
+        description: "Lines kept visible above and below the cursor",
+    },
+    OptionInfo {
+        name: "writingwidth",
+        aliases: &["ww"],
+        kind: Kind::Number,
+        scope: Scope::Global,
+        description: "Width of the centered text column in writing mode",
+    },
+    OptionInfo {
+        name: "scrollbar",
+        aliases: &["sb"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Show a scrollbar at the right edge of the text",
+    },
+    OptionInfo {
+        name: "minimap",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Show an overview of the buffer at the right, clickable to jump",
+    },
+    OptionInfo {
+        name: "kittykeyboard",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Tell keys apart with the kitty keyboard protocol if the terminal can",
+    },
+    OptionInfo {
+        name: "dimunfocused",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Dim the status bar while the terminal window is out of focus",
+    },
+    OptionInfo {
+        name: "syntax",
+        aliases: &["syn"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Color keywords, strings, comments and numbers by file type",
+    },
+    OptionInfo {
+        name: "rainbowbrackets",
+        aliases: &["rb"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Color brackets by nesting depth, skipping strings and comments",
+    },
+    OptionInfo {
+        name: "colorswatches",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Draw color literals such as #rrggbb on the color they name",
+    },
+    OptionInfo {
+        name: "bidi",
+        aliases: &[],
+        kind: Kind::Text,
+        scope: Scope::Global,
+        description: "Order right-to-left text on screen: off, auto, ltr or rtl per row",
+    },
+    OptionInfo {
+        name: "columnview",
+        aliases: &["cv"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Line up the columns of CSV and TSV buffers without changing the text",
+    },
+    OptionInfo {
+        name: "elastictabstops",
+        aliases: &["ets"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Line up tab-separated cells of adjacent lines to the widest one",
+    },
+    OptionInfo {
+        name: "softtabmotion",
+        aliases: &["stm"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Left and Right cross a whole indent level of leading spaces",
+    },
+    OptionInfo {
+        name: "scrollbind",
+        aliases: &["scb"],
+        kind: Kind::Bool,
+        scope: Scope::Window,
+        description: "Scroll together with the other windows that have it set",
+    },
+    OptionInfo {
+        name: "stickycolumn",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Up and Down return to the column left on a longer line",
+    },
+    OptionInfo {
+        name: "smoothscroll",
+        aliases: &["sms"],
+        kind: Kind::Number,
+        scope: Scope::Global,
+        description: "Milliseconds large scrolls animate over; 0 jumps at once",
+    },
+    OptionInfo {
+        name: "lint",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Warn about trailing whitespace, mixed indentation and long lines",
+    },
+    OptionInfo {
+        name: "spell",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Underline misspelled words",
+    },
+    OptionInfo {
+        name: "spelllang",
+        aliases: &["spl"],
+        kind: Kind::Text,
+        scope: Scope::Global,
+        description: "Dictionary used for spell checking, e.g. en_US",
+    },
+    OptionInfo {
+        name: "wordcount",
+        aliases: &["wc"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Show the word count of prose buffers in the status bar",
+    },
+    OptionInfo {
+        name: "searchcase",
+        aliases: &["sc"],
+        kind: Kind::Text,
+        scope: Scope::Global,
+        description: "How search matches case: sensitive, insensitive or smart",
+    },
+    OptionInfo {
+        name: "diffalgorithm",
+        aliases: &[],
+        kind: Kind::Text,
+        scope: Scope::Global,
+        description: "How the diff view lines up changes: myers or patience",
+    },
+    OptionInfo {
+        name: "wholeword",
+        aliases: &["ww"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Search matches only whole words",
+    },
+    OptionInfo {
+        name: "searchregex",
+        aliases: &["sre"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Search reads the query as a regular expression",
+    },
+    OptionInfo {
+        name: "autosave",
+        aliases: &["as"],
+        kind: Kind::Number,
+        scope: Scope::Global,
+        description: "Seconds of inactivity before saving; 0 disables",
+    },
+    OptionInfo {
+        name: "modeline",
+        aliases: &["ml"],
+        kind: Kind::Bool,
+        scope: Scope::Global,
+        description: "Apply modelines in files opened from now on",
+    },
+    OptionInfo {
+        name: "theme",
+        aliases: &[],
+        kind: Kind::Text,
+        scope: Scope::Global,
+        description: "Color theme (default, dark)",
+    },
+    OptionInfo {
+        name: "tabstop",
+        aliases: &["ts"],
+        kind: Kind::Number,
+        scope: Scope::Buffer,
+        description: "Display width of a tab",
+    },
+    OptionInfo {
+        name: "shiftwidth",
+        aliases: &["sw"],
+        kind: Kind::Number,
+        scope: Scope::Buffer,
+        description: "Columns per indentation level",
+    },
+    OptionInfo {
+        name: "expandtab",
+        aliases: &["et"],
+        kind: Kind::Bool,
+        scope: Scope::Buffer,
+        description: "Indent with spaces instead of tabs",
+    },
+    OptionInfo {
+        name: "readonly",
+        aliases: &["ro"],
+        kind: Kind::Bool,
+        scope: Scope::Buffer,
+        description: "Refuse edits and saves",
+    },
+    OptionInfo {
+        name: "fileformat",
+        aliases: &["ff"],
+        kind: Kind::Text,
+        scope: Scope::Buffer,
+        description: "Line endings written on save (unix, dos)",
+    },
+    OptionInfo {
+        name: "fileencoding",
+        aliases: &["fenc"],
+        kind: Kind::Text,
+        scope: Scope::Buffer,
+        description: "Encoding written on save",
+    },
+    OptionInfo {
+        name: "bomb",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Buffer,
+        description: "Write a byte order mark",
+    },
+    OptionInfo {
+        name: "colorcolumn",
+        aliases: &["cc"],
+        kind: Kind::Text,
+        scope: Scope::Buffer,
+        description: "Highlight these columns, e.g. 80,100; empty disables",
+    },
+    OptionInfo {
+        name: "filetype",
+        aliases: &["ft"],
+        kind: Kind::Text,
+        scope: Scope::Buffer,
+        description: "File type of the buffer",
+    },
+    OptionInfo {
+        name: "comment",
+        aliases: &["cms"],
+        kind: Kind::Text,
+        scope: Scope::Buffer,
+        description: "Line comment leader",
+    },
+    OptionInfo {
+        name: "formatter",
+        aliases: &["fp"],
+        kind: Kind::Text,
+        scope: Scope::Buffer,
+        description: "Command the buffer is piped through by :format",
+    },
+    OptionInfo {
+        name: "formatonsave",
+        aliases: &["fos"],
+        kind: Kind::Bool,
+        scope: Scope::Buffer,
+        description: "Format the buffer before an explicit save",
+    },
+    OptionInfo {
+        name: "trimwhitespace",
+        aliases: &[],
+        kind: Kind::Bool,
+        scope: Scope::Buffer,
+        description: "Trim trailing whitespace on save",
+    },
+    OptionInfo {
+        name: "fixendofline",
+        aliases: &["fixeol"],
+        kind: Kind::Bool,
+        scope: Scope::Buffer,
+        description: "Always end the file with a newline on save",
+    },
+    OptionInfo {
+        name: "pathcompletion",
+        aliases: &["pac"],
+        kind: Kind::Bool,
+        scope: Scope::Buffer,
+        description: "Complete text that starts like a path from the file system",
+    },
+];
+
+/// Splits `:set` arguments on whitespace, keeping backslash-escaped spaces.
+pub fn split_arguments(text: &str) -> Vec<String> {
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => current.extend(chars.next()),
+            _ if c.is_whitespace() => {
+                if !current.is_empty() {
+                    arguments.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        arguments.push(current);
+    }
+    arguments
+}
+
+pub fn find(name: &str) -> Option<&'static OptionInfo> {
+    OPTIONS
+        .iter()
+        .find(|option| option.name == name || option.aliases.contains(&name))
+}
+
+/// Parses one `:set` argument: `name`, `noname`, `invname`, `name!`, `name?`,
+/// `name=value` or `name:value`.
+pub fn parse(argument: &str) -> Result<(&'static OptionInfo, Action), String> {
+    let unknown = || format!("Unknown option: {}", argument);
+    if let Some((name, value)) = argument.split_once(['=', ':']) {
+        let option = find(name).ok_or_else(unknown)?;
+        let value = match option.kind {
+            Kind::Bool => match value {
+                "true" | "on" | "1" => Value::Bool(true),
+                "false" | "off" | "0" => Value::Bool(false),
+                _ => return Err(format!("Invalid value for {}: {}", option.name, value)),
+            },
+            Kind::Number => Value::Number(
+                value
+                    .parse()
+                    .map_err(|_| format!("Number required for {}: {}", option.name, value))?,
+            ),
+            Kind::Text => Value::Text(value.to_string()),
+        };
+        return Ok((option, Action::Set(value)));
+    }
+    if let Some(name) = argument.strip_suffix('?') {
+        return Ok((find(name).ok_or_else(unknown)?, Action::Query));
+    }
+    if let Some(name) = argument
+        .strip_suffix('!')
+        .or_else(|| argument.strip_prefix("inv"))
+    {
+        if let Some(option) = find(name).filter(|option| option.kind == Kind::Bool) {
+            return Ok((option, Action::Toggle));
+        }
+    }
+    if let Some(option) = find(argument) {
+        let action = if option.kind == Kind::Bool {
+            Action::Set(Value::Bool(true))
+        } else {
+            Action::Query
+        };
+        return Ok((option, action));
+    }
+    if let Some(option) = argument
+        .strip_prefix("no")
+        .and_then(find)
+        .filter(|option| option.kind == Kind::Bool)
+    {
+        return Ok((option, Action::Set(Value::Bool(false))));
+    }
+    Err(unknown())
+}
@@ -0,0 +1,331 @@
+use crate::commands;
+use crate::json::Value;
+use crate::Document;
+use crate::Position;
+
+/// Columns taken by the outline panel, at most a third of the screen.
+pub const WIDTH: usize = 32;
+
+/// A function, type or section of the buffer.
+#[derive(Clone)]
+pub struct Symbol {
+    pub name: String,
+    /// LSP `SymbolKind`.
+    pub kind: usize,
+    /// Nesting level; top-level symbols are 0.
+    pub depth: usize,
+    pub position: Position,
+    /// Last line of the symbol's body.
+    pub end_line: usize,
+}
+
+/// Reads a `textDocument/documentSymbol` result, flattening nested `DocumentSymbol`s
+/// in document order. Positions are converted with the rows of `document`.
+pub fn from_lsp(result: &Value, document: &Document) -> Vec<Symbol> {
+    let mut symbols = Vec::new();
+    for item in result.as_array().unwrap_or_default() {
+        push_symbol(item, 0, document, &mut symbols);
+    }
+    symbols.sort_by_key(|symbol| (symbol.position.y, symbol.position.x, symbol.depth));
+    symbols
+}
+
+fn push_symbol(item: &Value, depth: usize, document: &Document, symbols: &mut Vec<Symbol>) {
+    let name = if let Some(name) = item.get("name").and_then(Value::as_str) {
+        name
+    } else {
+        return;
+    };
+    // `DocumentSymbol` has its own ranges; `SymbolInformation` has a location.
+    let range = item.get("range").or_else(|| {
+        item.get("location")
+            .and_then(|location| location.get("range"))
+    });
+    let start = item
+        .get("selectionRange")
+        .or(range)
+        .and_then(|range| range.get("start"));
+    let line = |value: Option<&Value>| value?.get("line")?.as_usize();
+    let y = if let Some(y) = line(start) {
+        y
+    } else {
+        return;
+    };
+    let character = start
+        .and_then(|start| start.get("character"))
+        .and_then(Value::as_usize)
+        .unwrap_or(0);
+    let depth = if item
+        .get("containerName")
+        .and_then(Value::as_str)
+        .is_some_and(|name| !name.is_empty())
+    {
+        depth.saturating_add(1)
+    } else {
+        depth
+    };
+    symbols.push(Symbol {
+        name: name.to_string(),
+        kind: item.get("kind").and_then(Value::as_usize).unwrap_or(0),
+        depth,
+        position: Position {
+            x: document
+                .row(y)
+                .map_or(0, |row| row.index_at_utf16(character)),
+            y,
+        },
+        end_line: line(range.and_then(|range| range.get("end"))).unwrap_or(y),
+    });
+    for child in item
+        .get("children")
+        .and_then(Value::as_array)
+        .unwrap_or_default()
+    {
+        push_symbol(child, depth.saturating_add(1), document, symbols);
+    }
+}
+
+/// Keywords that start a definition, with the `SymbolKind` they introduce.
+const KEYWORDS: &[(&str, usize)] = &[
+    ("fn", 12),
+    ("def", 12),
+    ("func", 12),
+    ("function", 12),
+    ("class", 5),
+    ("struct", 23),
+    ("enum", 10),
+    ("trait", 11),
+    ("interface", 11),
+    ("impl", 5),
+    ("mod", 2),
+    ("module", 2),
+    ("type", 26),
+];
+
+/// Modifiers that may precede a definition keyword.
+const MODIFIERS: &[&str] = &[
+    "pub",
+    "async",
+    "unsafe",
+    "export",
+    "default",
+    "static",
+    "public",
+    "private",
+    "protected",
+    "abstract",
+    "final",
+];
+
+/// Finds symbols without a language server: Markdown headings, or lines that start
+/// with a definition keyword, nested by indentation.
+pub fn from_text(document: &Document) -> Vec<Symbol> {
+    let markdown = document.file_type.as_deref() == Some("markdown");
+    let indent_size = document.indent.size.max(1);
+    let mut symbols: Vec<Symbol> = Vec::new();
+    for y in 0..document.len() {
+        let line = document.row(y).map_or("", |row| row.as_str());
+        let symbol = if markdown {
+            heading(line)
+        } else {
+            definition(line, indent_size, document.indent.tab_width)
+        };
+        if let Some((name, kind, depth)) = symbol {
+            symbols.push(Symbol {
+                name,
+                kind,
+                depth,
+                position: Position { x: 0, y },
+                end_line: y,
+            });
+        }
+    }
+    // A symbol runs until the next one at the same or a shallower depth.
+    let last_line = document.len().saturating_sub(1);
+    for index in 0..symbols.len() {
+        let depth = symbols.get(index).map_or(0, |symbol| symbol.depth);
+        let end = symbols
+            .iter()
+            .skip(index.saturating_add(1))
+            .find(|next| next.depth <= depth)
+            .map_or(last_line, |next| next.position.y.saturating_sub(1));
+        if let Some(symbol) = symbols.get_mut(index) {
+            symbol.end_line = end.max(symbol.position.y);
+        }
+    }
+    symbols
+}
+
+/// The name and kind of the symbol `line` defines, for files read without a buffer.
+pub fn line_symbol(line: &str, markdown: bool) -> Option<(String, usize)> {
+    let symbol = if markdown {
+        heading(line)
+    } else {
+        definition(line, 1, 1)
+    };
+    symbol.map(|(name, kind, _)| (name, kind))
+}
+
+fn heading(line: &str) -> Option<(String, usize, usize)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let title = line.get(level..)?;
+    if level == 0 || !title.starts_with(' ') {
+        return None;
+    }
+    Some((title.trim().to_string(), 15, level.saturating_sub(1)))
+}
+
+fn definition(line: &str, indent_size: usize, tab_width: usize) -> Option<(String, usize, usize)> {
+    let trimmed = line.trim_start();
+    let indent: usize = line
+        .chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { tab_width } else { 1 })
+        .sum();
+    // Visibility like `pub(crate)` would otherwise split at its parenthesis.
+    let definition = trimmed
+        .strip_prefix("pub(")
+        .and_then(|rest| rest.split_once(')'))
+        .map_or(trimmed, |(_, rest)| rest);
+    let mut words = definition
+        .split(|c: char| c.is_whitespace() || c == '(' || c == '<' || c == ':' || c == '{')
+        .filter(|word| !word.is_empty())
+        .skip_while(|word| MODIFIERS.contains(word));
+    let keyword = words.next()?;
+    let &(_, kind) = KEYWORDS.iter().find(|(name, _)| *name == keyword)?;
+    let name = words.next()?;
+    if !name.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        return None;
+    }
+    let name = if keyword == "impl" {
+        trimmed
+            .trim_end_matches(|c: char| c == '{' || c.is_whitespace())
+            .to_string()
+    } else {
+        name.to_string()
+    };
+    Some((name, kind, indent.checked_div(indent_size).unwrap_or(0)))
+}
+
+/// The symbols enclosing line `y`, outermost first.
+pub fn enclosing(symbols: &[Symbol], y: usize) -> Vec<&Symbol> {
+    let mut path: Vec<&Symbol> = Vec::new();
+    for symbol in symbols {
+        if symbol.position.y > y {
+            break;
+        }
+        if symbol.end_line >= y {
+            path.retain(|outer| outer.depth < symbol.depth);
+            path.push(symbol);
+        }
+    }
+    path
+}
+
+/// A one-character marker for an LSP `SymbolKind`.
+pub fn kind_icon(kind: usize) -> char {
+    match kind {
+        2..=4 => 'm',
+        5 => 'C',
+        6 | 9 | 12 => 'f',
+        7 | 8 => '.',
+        10 => 'E',
+        11 => 'I',
+        13 => 'v',
+        14 => 'c',
+        15 => '#',
+        23 => 'S',
+        26 => 'T',
+        _ => '·',
+    }
+}
+
+/// The outline side panel: a filterable list of the buffer's symbols.
+#[derive(Default)]
+pub struct Outline {
+    pub query: String,
+    pub selected: usize,
+    /// Whether keys go to the panel rather than the buffer.
+    pub focused: bool,
+}
+
+impl Outline {
+    /// Indices of the symbols matching the query, in document order.
+    pub fn matches(&self, symbols: &[Symbol]) -> Vec<usize> {
+        symbols
+            .iter()
+            .enumerate()
+            .filter(|(_, symbol)| commands::fuzzy_score(&self.query, &symbol.name).is_some())
+            .map(|(index, _)| index)
+            .collect()
+    }
+    pub fn select_next(&mut self, backward: bool, count: usize) {
+        let last = count.saturating_sub(1);
+        self.selected = if backward {
+            self.selected.saturating_sub(1)
+        } else {
+            self.selected.saturating_add(1).min(last)
+        };
+    }
+    /// The panel's `(line, is_highlighted)` rows, `width` wide. The highlight follows
+    /// the selection while focused, and otherwise the symbol at the cursor.
+    pub fn lines(
+        &self,
+        symbols: &[Symbol],
+        cursor_line: usize,
+        width: usize,
+        height: usize,
+    ) -> Vec<(String, bool)> {
+        let matches = self.matches(symbols);
+        let highlighted = if self.focused {
+            Some(self.selected)
+        } else {
+            let current = enclosing(symbols, cursor_line)
+                .last()
+                .map(|symbol| symbol.position.y);
+            matches.iter().position(|index| {
+                symbols.get(*index).map(|symbol| Some(symbol.position.y)) == Some(current)
+            })
+        };
+        let title = if self.query.is_empty() {
+            "Outline".to_string()
+        } else {
+            format!("Outline: {}", self.query)
+        };
+        let rows = height.saturating_sub(1).max(1);
+        let first = highlighted.map_or(0, |index| index.saturating_sub(rows.saturating_sub(1)));
+        let pad = |text: String| {
+            let text: String = text.chars().take(width).collect();
+            format!("{:<width$}", text, width = width)
+        };
+        let mut lines = vec![(pad(format!(" {}", title)), false)];
+        lines.extend(
+            matches
+                .iter()
+                .enumerate()
+                .skip(first)
+                .take(rows)
+                .filter_map(|(position, index)| {
+                    let symbol = symbols.get(*index)?;
+                    let indent = if self.query.is_empty() {
+                        "  ".repeat(symbol.depth)
+                    } else {
+                        String::new()
+                    };
+                    Some((
+                        pad(format!(
+                            " {}{} {}",
+                            indent,
+                            kind_icon(symbol.kind),
+                            symbol.name
+                        )),
+                        highlighted == Some(position),
+                    ))
+                }),
+        );
+        if lines.len() == 1 {
+            lines.push((pad(" (no symbols)".to_string()), false));
+        }
+        lines
+    }
+}
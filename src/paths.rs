@@ -0,0 +1,80 @@
+use std::env;
+use std::path::PathBuf;
+use unicode_segmentation::UnicodeSegmentation;
+
+fn xdg_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(fallback)))
+        .map(|path| path.join("hecto"))
+}
+
+/// Returns `$XDG_CACHE_HOME/hecto`, falling back to `~/.cache/hecto`.
+pub fn cache_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CACHE_HOME", ".cache")
+}
+
+/// Returns `$XDG_CONFIG_HOME/hecto`, falling back to `~/.config/hecto`.
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config")
+}
+
+/// Returns `$XDG_STATE_HOME/hecto`, falling back to `~/.local/state/hecto`.
+pub fn state_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+}
+
+/// Expands a leading `~/` to the home directory.
+pub fn expand_home(path: &str) -> PathBuf {
+    match (path.strip_prefix("~/"), env::var_os("HOME")) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(path),
+    }
+}
+
+/// The file name under grapheme `x` of `line`, with the line and column that may
+/// follow it as in `src/main.rs:12:5`.
+pub fn file_at(line: &str, x: usize) -> Option<(String, Option<usize>, Option<usize>)> {
+    let is_delimiter = |grapheme: &str| {
+        grapheme
+            .chars()
+            .all(|c| c.is_whitespace() || "\"'`()<>[]{},;|=".contains(c))
+    };
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+    if graphemes
+        .get(x)
+        .is_none_or(|grapheme| is_delimiter(grapheme))
+    {
+        return None;
+    }
+    let start = graphemes
+        .get(..x)?
+        .iter()
+        .rposition(|grapheme| is_delimiter(grapheme))
+        .map_or(0, |index| index.saturating_add(1));
+    let end = graphemes
+        .get(x..)?
+        .iter()
+        .position(|grapheme| is_delimiter(grapheme))
+        .map_or(graphemes.len(), |index| index.saturating_add(x));
+    let token = graphemes.get(start..end)?.concat();
+    let mut parts = token.trim_end_matches(['.', ':']).split(':');
+    let name = parts.next().filter(|name| !name.is_empty())?.to_string();
+    let line_number = parts.next().and_then(|part| part.parse().ok());
+    let column = parts.next().and_then(|part| part.parse().ok());
+    Some((name, line_number, column))
+}
+
+/// Turns a file path into a single file name that can live in a flat directory.
+/// `%` and `/` are percent-escaped, so distinct paths never share a name.
+pub fn encode(file_name: &str) -> String {
+    let path = PathBuf::from(file_name);
+    let path = path
+        .canonicalize()
+        .or_else(|_| env::current_dir().map(|dir| dir.join(&path)))
+        .unwrap_or(path);
+    path.to_string_lossy()
+        .replace('%', "%25")
+        .replace('/', "%2F")
+}
@@ -0,0 +1,440 @@
+use crate::commands;
+use crate::event::Event;
+use crate::paths;
+use crate::Key;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use wasmi::core::Trap;
+use wasmi::{
+    Caller, Config, Engine, Extern, Instance, Linker, Memory, Module, Store, StoreLimits,
+    StoreLimitsBuilder,
+};
+
+/// Roughly how many instructions one call into a plugin may run, so that a plugin stuck
+/// in a loop cannot hang the editor.
+const FUEL: u64 = 100_000_000;
+/// The most memory a plugin may grow to, in bytes.
+const MEMORY_LIMIT: usize = 64 << 20;
+/// The module plugins import the editor's functions from.
+const IMPORTS: &str = "hecto";
+
+/// What a plugin sees of the editor during a call.
+#[derive(Default)]
+pub struct Context {
+    /// The buffer text, lines ended by `\n`; left empty for plugins that never read
+    /// it.
+    pub text: String,
+    pub file_name: String,
+    /// The cursor line and column, counted from 0.
+    pub cursor: (usize, usize),
+    /// What followed the name of the command being run.
+    pub arguments: String,
+}
+
+/// What a call into a plugin asked the editor to do.
+pub struct Outcome {
+    /// Text to put in place of the whole buffer.
+    pub text: Option<String>,
+    pub cursor: Option<(usize, usize)>,
+    pub message: Option<String>,
+}
+
+/// The store data of a plugin: what it registered, what it is given during a call and
+/// what it asked for.
+#[derive(Default)]
+struct Host {
+    limits: StoreLimits,
+    context: Context,
+    /// Ex command names with the exports that run them.
+    commands: Vec<(String, String)>,
+    /// Keys with the ex commands they run.
+    keys: Vec<(Key, String)>,
+    /// Events with the exports hooked to them.
+    hooks: Vec<(Event, String)>,
+    /// The text of the plugin's status bar segment.
+    status: String,
+    text: Option<String>,
+    cursor: Option<(usize, usize)>,
+    message: Option<String>,
+}
+
+/// A WebAssembly plugin, run sandboxed: it reaches the editor only through the
+/// functions it imports from `hecto`, with its fuel and memory limited.
+struct Plugin {
+    name: String,
+    store: Store<Host>,
+    instance: Instance,
+    /// The fuel given to the store so far.
+    fuel: u64,
+    /// Whether the plugin imports `buffer_text`; the others are not given the text.
+    reads_text: bool,
+}
+
+impl Plugin {
+    fn load(path: &Path) -> Result<Self, String> {
+        let bytes = fs::read(path).map_err(|error| error.to_string())?;
+        let mut config = Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module = Module::new(&engine, bytes.as_slice()).map_err(|error| error.to_string())?;
+        let reads_text = module
+            .imports()
+            .any(|import| import.module() == IMPORTS && import.name() == "buffer_text");
+        let host = Host {
+            limits: StoreLimitsBuilder::new().memory_size(MEMORY_LIMIT).build(),
+            ..Host::default()
+        };
+        let mut store = Store::new(&engine, host);
+        store.limiter(|host| &mut host.limits);
+        let mut linker = Linker::new(&engine);
+        define_imports(&mut linker).map_err(|error| error.to_string())?;
+        store.add_fuel(FUEL).map_err(|error| error.to_string())?;
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .and_then(|instance| instance.start(&mut store))
+            .map_err(|error| error.to_string())?;
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+        let mut plugin = Self {
+            name,
+            store,
+            instance,
+            fuel: FUEL,
+            reads_text,
+        };
+        if plugin.instance.get_export(&plugin.store, "init").is_some() {
+            plugin.call("init", Context::default())?;
+        }
+        Ok(plugin)
+    }
+    /// Runs the export `name`, which takes and returns nothing, with `context`.
+    fn call(&mut self, name: &str, context: Context) -> Result<Outcome, String> {
+        let host = self.store.data_mut();
+        host.context = context;
+        host.text = None;
+        host.cursor = None;
+        host.message = None;
+        self.refuel();
+        let function = self
+            .instance
+            .get_typed_func::<(), ()>(&self.store, name)
+            .map_err(|error| error.to_string())?;
+        function
+            .call(&mut self.store, ())
+            .map_err(|error| error.to_string())?;
+        let host = self.store.data_mut();
+        host.context = Context::default();
+        Ok(Outcome {
+            text: host.text.take(),
+            cursor: host.cursor.take(),
+            message: host.message.take(),
+        })
+    }
+    /// Tops the fuel up to `FUEL`, whatever earlier calls left over.
+    fn refuel(&mut self) {
+        let consumed = self.store.fuel_consumed().unwrap_or(0);
+        let remaining = self.fuel.saturating_sub(consumed);
+        let delta = FUEL.saturating_sub(remaining);
+        if self.store.add_fuel(delta).is_ok() {
+            self.fuel = self.fuel.saturating_add(delta);
+        }
+    }
+}
+
+fn trap(message: &str) -> Trap {
+    Trap::new(message.to_string())
+}
+
+fn memory(caller: &Caller<'_, Host>) -> Result<Memory, Trap> {
+    caller
+        .get_export("memory")
+        .and_then(Extern::into_memory)
+        .ok_or_else(|| trap("the plugin exports no memory"))
+}
+
+fn range(pointer: i32, length: i32) -> Result<Range<usize>, Trap> {
+    let start = usize::try_from(pointer).map_err(|_| trap("negative pointer"))?;
+    let length = usize::try_from(length).map_err(|_| trap("negative length"))?;
+    Ok(start..start.saturating_add(length))
+}
+
+/// The UTF-8 text of `length` bytes at `pointer` in the plugin's memory.
+fn read(caller: &Caller<'_, Host>, pointer: i32, length: i32) -> Result<String, Trap> {
+    let memory = memory(caller)?;
+    let bytes = memory
+        .data(caller)
+        .get(range(pointer, length)?)
+        .ok_or_else(|| trap("text out of bounds"))?;
+    String::from_utf8(bytes.to_vec()).map_err(|_| trap("text is not UTF-8"))
+}
+
+/// Copies as much of the text `field` picks from the call's context as fits in the
+/// `capacity` bytes at `pointer`, and returns the length of all of it, so that a plugin
+/// can make room and ask again.
+fn write(
+    caller: &mut Caller<'_, Host>,
+    pointer: i32,
+    capacity: i32,
+    field: fn(&Context) -> &str,
+) -> Result<i32, Trap> {
+    let memory = memory(caller)?;
+    let range = range(pointer, capacity)?;
+    let (data, host) = memory.data_and_store_mut(caller);
+    let text = field(&host.context).as_bytes();
+    let length = text.len().min(range.len());
+    let end = range.start.saturating_add(length);
+    data.get_mut(range.start..end)
+        .zip(text.get(..length))
+        .ok_or_else(|| trap("buffer out of bounds"))
+        .map(|(target, source)| target.copy_from_slice(source))?;
+    i32::try_from(text.len()).map_err(|_| trap("text too long"))
+}
+
+fn number(value: usize) -> i32 {
+    i32::try_from(value).unwrap_or(i32::MAX)
+}
+
+/// The functions plugins may import from `hecto`. Texts are passed as a pointer and a
+/// length in bytes; those coming back are copied into a buffer the plugin provides.
+fn define_imports(linker: &mut Linker<Host>) -> Result<(), wasmi::errors::LinkerError> {
+    linker.func_wrap(
+        IMPORTS,
+        "register_command",
+        |mut caller: Caller<'_, Host>,
+         name: i32,
+         name_length: i32,
+         export: i32,
+         export_length: i32| {
+            let name = read(&caller, name, name_length)?;
+            let export = read(&caller, export, export_length)?;
+            if name.is_empty() || !name.chars().all(char::is_alphanumeric) {
+                return Err(trap("command names are letters and digits"));
+            }
+            caller.data_mut().commands.push((name, export));
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORTS,
+        "bind_key",
+        |mut caller: Caller<'_, Host>,
+         key: i32,
+         key_length: i32,
+         command: i32,
+         command_length: i32| {
+            let name = read(&caller, key, key_length)?;
+            let command = read(&caller, command, command_length)?;
+            match commands::parse_key(&name) {
+                Some(Key::Char(_)) | None => Err(trap(&format!("cannot bind `{}`", name))),
+                Some(key) => {
+                    caller.data_mut().keys.push((key, command));
+                    Ok(())
+                }
+            }
+        },
+    )?;
+    linker.func_wrap(
+        IMPORTS,
+        "on_event",
+        |mut caller: Caller<'_, Host>,
+         event: i32,
+         event_length: i32,
+         export: i32,
+         export_length: i32| {
+            let name = read(&caller, event, event_length)?;
+            let export = read(&caller, export, export_length)?;
+            let event = Event::from_name(&name)
+                .ok_or_else(|| trap(&format!("unknown event `{}`", name)))?;
+            caller.data_mut().hooks.push((event, export));
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORTS,
+        "set_status",
+        |mut caller: Caller<'_, Host>, text: i32, length: i32| {
+            caller.data_mut().status = read(&caller, text, length)?;
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORTS,
+        "message",
+        |mut caller: Caller<'_, Host>, text: i32, length: i32| {
+            caller.data_mut().message = Some(read(&caller, text, length)?);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORTS,
+        "buffer_text",
+        |mut caller: Caller<'_, Host>, buffer: i32, capacity: i32| {
+            write(&mut caller, buffer, capacity, |context| &context.text)
+        },
+    )?;
+    linker.func_wrap(
+        IMPORTS,
+        "set_buffer_text",
+        |mut caller: Caller<'_, Host>, text: i32, length: i32| {
+            caller.data_mut().text = Some(read(&caller, text, length)?);
+            Ok(())
+        },
+    )?;
+    linker.func_wrap(
+        IMPORTS,
+        "file_name",
+        |mut caller: Caller<'_, Host>, buffer: i32, capacity: i32| {
+            write(&mut caller, buffer, capacity, |context| &context.file_name)
+        },
+    )?;
+    linker.func_wrap(
+        IMPORTS,
+        "arguments",
+        |mut caller: Caller<'_, Host>, buffer: i32, capacity: i32| {
+            write(&mut caller, buffer, capacity, |context| &context.arguments)
+        },
+    )?;
+    linker.func_wrap(IMPORTS, "cursor_line", |caller: Caller<'_, Host>| {
+        number(caller.data().context.cursor.0)
+    })?;
+    linker.func_wrap(IMPORTS, "cursor_column", |caller: Caller<'_, Host>| {
+        number(caller.data().context.cursor.1)
+    })?;
+    linker.func_wrap(
+        IMPORTS,
+        "set_cursor",
+        |mut caller: Caller<'_, Host>, line: i32, column: i32| {
+            let line = usize::try_from(line).unwrap_or(0);
+            let column = usize::try_from(column).unwrap_or(0);
+            caller.data_mut().cursor = Some((line, column));
+        },
+    )?;
+    Ok(())
+}
+
+/// The directory plugins are loaded from.
+fn dir() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join("plugins"))
+}
+
+/// The plugins loaded at startup, with the commands, keys, hooks and status segments
+/// they registered.
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Plugin>,
+}
+
+impl Plugins {
+    /// Loads every `.wasm` file in the `plugins` config directory, in name order.
+    /// Returns the plugins that loaded and why the others did not.
+    pub fn load() -> (Self, Vec<String>) {
+        let mut paths: Vec<PathBuf> = dir()
+            .and_then(|dir| fs::read_dir(dir).ok())
+            .into_iter()
+            .flatten()
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "wasm")
+            })
+            .collect();
+        paths.sort();
+        let mut plugins = Self::default();
+        let mut errors = Vec::new();
+        for path in paths {
+            match Plugin::load(&path) {
+                Ok(plugin) => plugins.plugins.push(plugin),
+                Err(error) => errors.push(format!("{}: {}", path.display(), error)),
+            }
+        }
+        (plugins, errors)
+    }
+    /// The plugin and export that run the ex command `name`.
+    pub fn command(&self, name: &str) -> Option<(usize, String)> {
+        self.plugins.iter().enumerate().find_map(|(index, plugin)| {
+            plugin
+                .store
+                .data()
+                .commands
+                .iter()
+                .find(|(command, _)| command == name)
+                .map(|(_, export)| (index, export.clone()))
+        })
+    }
+    /// Whether plugin `index` reads the buffer text, and so needs it in its context.
+    pub fn reads_text(&self, index: usize) -> bool {
+        self.plugins
+            .get(index)
+            .is_some_and(|plugin| plugin.reads_text)
+    }
+    /// The ex command a plugin bound `key` to.
+    pub fn command_for(&self, key: Key) -> Option<String> {
+        self.plugins.iter().find_map(|plugin| {
+            plugin
+                .store
+                .data()
+                .keys
+                .iter()
+                .find(|(bound, _)| *bound == key)
+                .map(|(_, command)| command.clone())
+        })
+    }
+    /// The plugins and exports hooked to `event`.
+    pub fn hooks(&self, event: Event) -> Vec<(usize, String)> {
+        let mut hooks = Vec::new();
+        for (index, plugin) in self.plugins.iter().enumerate() {
+            for (hooked, export) in &plugin.store.data().hooks {
+                if *hooked == event {
+                    hooks.push((index, export.clone()));
+                }
+            }
+        }
+        hooks
+    }
+    /// Runs export `name` of plugin `index`.
+    ///
+    /// # Errors
+    ///
+    /// Says which plugin failed and how, as when it trapped or ran out of fuel.
+    pub fn call(&mut self, index: usize, name: &str, context: Context) -> Result<Outcome, String> {
+        let plugin = self
+            .plugins
+            .get_mut(index)
+            .ok_or_else(|| "no such plugin".to_string())?;
+        plugin
+            .call(name, context)
+            .map_err(|error| format!("plugin {}: {}", plugin.name, error))
+    }
+    /// What the plugins show in the status bar, joined by `separator`.
+    pub fn status(&self, separator: &str) -> String {
+        self.plugins
+            .iter()
+            .map(|plugin| plugin.store.data().status.as_str())
+            .filter(|status| !status.is_empty())
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+    /// A line for each plugin, naming what it registered.
+    pub fn describe(&self) -> Vec<String> {
+        self.plugins
+            .iter()
+            .map(|plugin| {
+                let host = plugin.store.data();
+                let mut parts: Vec<String> = host
+                    .commands
+                    .iter()
+                    .map(|(name, _)| format!(":{}", name))
+                    .collect();
+                parts.extend(
+                    host.keys
+                        .iter()
+                        .map(|(key, command)| format!("{} :{}", commands::key_name(*key), command)),
+                );
+                parts.extend(host.hooks.iter().map(|(event, _)| event.name().to_string()));
+                format!("{:<16} {}", plugin.name, parts.join(", "))
+            })
+            .collect()
+    }
+}
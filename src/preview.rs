@@ -0,0 +1,327 @@
+use crate::unicode;
+
+/// Columns of the preview pane, including its border; at most half the screen.
+pub const WIDTH: usize = 64;
+
+/// How a run of preview text is drawn.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Strong,
+    Emphasis,
+    /// Inline code and the lines of fenced code blocks.
+    Code,
+    Link,
+    Heading,
+    /// Quote bars, list bullets and horizontal rules.
+    Marker,
+}
+
+/// A screen line of the preview, with the buffer line it was rendered from.
+pub struct Line {
+    pub source: usize,
+    pub spans: Vec<(Style, String)>,
+}
+
+/// The current markdown buffer rendered for reading: markers become styles, lists get
+/// bullets and prose is wrapped to the pane.
+#[derive(Default)]
+pub struct Preview {
+    lines: Vec<Line>,
+    /// The buffer name, revision and pane width the lines match.
+    pub revision: Option<(Option<String>, usize, usize)>,
+}
+
+impl Preview {
+    pub fn update(&mut self, lines: &[&str], revision: (Option<String>, usize, usize)) {
+        self.lines = render(lines, revision.2.max(10));
+        self.revision = Some(revision);
+    }
+    /// The first of `height` lines to show so that what buffer line `y` became sits a
+    /// third of the way down.
+    pub fn top(&self, y: usize, height: usize) -> usize {
+        let at = self
+            .lines
+            .iter()
+            .position(|line| line.source >= y)
+            .unwrap_or(self.lines.len());
+        at.saturating_sub(height / 3)
+            .min(self.lines.len().saturating_sub(height))
+    }
+    pub fn lines(&self, top: usize, height: usize) -> &[Line] {
+        let end = top.saturating_add(height).min(self.lines.len());
+        self.lines.get(top.min(end)..end).unwrap_or_default()
+    }
+}
+
+fn render(lines: &[&str], width: usize) -> Vec<Line> {
+    let mut rendered: Vec<Line> = Vec::new();
+    let mut in_code = false;
+    for (source, line) in lines.iter().enumerate() {
+        let mut push = |spans: Vec<(Style, String)>| rendered.push(Line { source, spans });
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            let code: String = line.replace('\t', "    ");
+            let code = unicode::truncate(&code, width);
+            let padding = width.saturating_sub(unicode::text_width(&code));
+            push(vec![(
+                Style::Code,
+                format!("{}{}", code, " ".repeat(padding)),
+            )]);
+            continue;
+        }
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            push(Vec::new());
+            continue;
+        }
+        let compact: String = trimmed.chars().filter(|c| *c != ' ').collect();
+        if compact.len() >= 3
+            && ['-', '*', '_']
+                .iter()
+                .any(|rule| compact.chars().all(|c| c == *rule))
+        {
+            push(vec![(Style::Marker, "─".repeat(width))]);
+            continue;
+        }
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&level)
+            && trimmed
+                .get(level..)
+                .is_some_and(|rest| rest.starts_with(' '))
+        {
+            let title = trimmed.get(level..).unwrap_or_default().trim();
+            let chars: Vec<(Style, char)> = inline(title)
+                .into_iter()
+                .map(|(_, c)| (Style::Heading, c))
+                .collect();
+            let wrapped = wrap(&chars, &[], width);
+            let title_width = wrapped
+                .iter()
+                .map(|spans| {
+                    spans
+                        .iter()
+                        .map(|(_, text)| unicode::text_width(text))
+                        .sum()
+                })
+                .max()
+                .unwrap_or(0);
+            for spans in wrapped {
+                push(spans);
+            }
+            // Top headings are underlined, as on a rendered page.
+            if level <= 2 {
+                let rule = if level == 1 { "═" } else { "─" };
+                push(vec![(Style::Heading, rule.repeat(title_width))]);
+            }
+            continue;
+        }
+        let indent = line.len().saturating_sub(line.trim_start().len());
+        let (prefix, text) = block_prefix(trimmed);
+        let mut first: Vec<(Style, char)> = " "
+            .repeat(indent)
+            .chars()
+            .map(|c| (Style::Plain, c))
+            .collect();
+        first.extend(prefix.chars().map(|c| (Style::Marker, c)));
+        // Wrapped lines of a list item line up under its text, and of a quote carry on
+        // its bar.
+        let hanging: Vec<(Style, char)> = if prefix.starts_with('│') {
+            first.clone()
+        } else {
+            first.iter().map(|_| (Style::Plain, ' ')).collect()
+        };
+        let mut chars = first;
+        chars.extend(inline(text));
+        for spans in wrap(&chars, &hanging, width) {
+            push(spans);
+        }
+    }
+    // Runs of blank lines read as one.
+    let mut previous_blank = true;
+    rendered.retain(|line| {
+        let blank = line.spans.is_empty();
+        let keep = !(blank && previous_blank);
+        previous_blank = blank;
+        keep
+    });
+    rendered
+}
+
+/// The marker drawn in place of a quote or list marker at the start of `line`, and
+/// the text after it.
+fn block_prefix(line: &str) -> (String, &str) {
+    if let Some(rest) = line.strip_prefix('>') {
+        return ("│ ".to_string(), rest.trim_start());
+    }
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(rest) = line.strip_prefix(bullet) {
+            if let Some(task) = rest.strip_prefix("[ ] ") {
+                return ("☐ ".to_string(), task);
+            }
+            if let Some(task) = rest
+                .strip_prefix("[x] ")
+                .or_else(|| rest.strip_prefix("[X] "))
+            {
+                return ("☑ ".to_string(), task);
+            }
+            return ("• ".to_string(), rest);
+        }
+    }
+    let digits = line.chars().take_while(char::is_ascii_digit).count();
+    if digits > 0 {
+        let marker = line.get(..digits.saturating_add(2)).unwrap_or_default();
+        if marker.ends_with(". ") || marker.ends_with(") ") {
+            let rest = line.get(marker.len()..).unwrap_or_default();
+            return (marker.to_string(), rest);
+        }
+    }
+    (String::new(), line)
+}
+
+/// Styles the characters of `text` by its inline markup, dropping the markers:
+/// `**strong**`, `*emphasis*`, `` `code` `` and `[links](url)`.
+fn inline(text: &str) -> Vec<(Style, char)> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut styled = Vec::new();
+    let mut strong = false;
+    let mut emphasis = false;
+    let mut index = 0;
+    let at = |index: usize| chars.get(index).copied();
+    let is_word = |c: Option<char>| c.is_some_and(char::is_alphanumeric);
+    while let Some(c) = at(index) {
+        let next = index.saturating_add(1);
+        match c {
+            '\\' if at(next).is_some_and(|c| c.is_ascii_punctuation()) => {
+                styled.extend(at(next).map(|c| (Style::Plain, c)));
+                index = next.saturating_add(1);
+                continue;
+            }
+            '`' => {
+                if let Some(length) = chars.iter().skip(next).position(|c| *c == '`') {
+                    let code = chars.iter().skip(next).take(length);
+                    styled.extend(code.map(|c| (Style::Code, *c)));
+                    index = next.saturating_add(length).saturating_add(1);
+                    continue;
+                }
+            }
+            '*' | '_' if at(next) == Some(c) => {
+                strong = !strong;
+                index = next.saturating_add(1);
+                continue;
+            }
+            // Underscores inside words, as in snake_case, are text.
+            '*' | '_'
+                if c == '*'
+                    || !is_word(index.checked_sub(1).and_then(at))
+                    || !is_word(at(next)) =>
+            {
+                emphasis = !emphasis;
+                index = next;
+                continue;
+            }
+            '[' | '!' => {
+                let start = if c == '!' && at(next) == Some('[') {
+                    next
+                } else {
+                    index
+                };
+                if let Some((label, end)) = link(&chars, start) {
+                    styled.extend(label.chars().map(|c| (Style::Link, c)));
+                    index = end;
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        let style = if strong {
+            Style::Strong
+        } else if emphasis {
+            Style::Emphasis
+        } else {
+            Style::Plain
+        };
+        styled.push((style, c));
+        index = next;
+    }
+    styled
+}
+
+/// A `[label](url)` link starting at `start`, as its label and the index after it.
+fn link(chars: &[char], start: usize) -> Option<(String, usize)> {
+    if chars.get(start) != Some(&'[') {
+        return None;
+    }
+    let close = start.saturating_add(1).saturating_add(
+        chars
+            .iter()
+            .skip(start.saturating_add(1))
+            .position(|c| *c == ']')?,
+    );
+    if chars.get(close.saturating_add(1)) != Some(&'(') {
+        return None;
+    }
+    let end = close.saturating_add(2).saturating_add(
+        chars
+            .iter()
+            .skip(close.saturating_add(2))
+            .position(|c| *c == ')')?,
+    );
+    let label = chars.get(start.saturating_add(1)..close)?.iter().collect();
+    Some((label, end.saturating_add(1)))
+}
+
+/// Word-wraps styled characters to `width` columns, starting lines after the first
+/// with `hanging`, and merges runs of one style into spans.
+fn wrap(
+    chars: &[(Style, char)],
+    hanging: &[(Style, char)],
+    width: usize,
+) -> Vec<Vec<(Style, String)>> {
+    let mut lines: Vec<Vec<(Style, char)>> = Vec::new();
+    let mut current: Vec<(Style, char)> = Vec::new();
+    let mut current_width = 0_usize;
+    for word in chars.split_inclusive(|(_, c)| *c == ' ') {
+        let word_width: usize = word
+            .iter()
+            .filter(|(_, c)| *c != ' ')
+            .map(|(_, c)| unicode::width(*c))
+            .sum();
+        if current_width.saturating_add(word_width) > width
+            && current.iter().any(|(_, c)| *c != ' ')
+            && current.len() > hanging.len()
+        {
+            while current.last().is_some_and(|(_, c)| *c == ' ') {
+                current.pop();
+            }
+            lines.push(std::mem::take(&mut current));
+            current.extend_from_slice(hanging);
+            current_width = hanging.iter().map(|(_, c)| unicode::width(*c)).sum();
+        }
+        current.extend_from_slice(word);
+        current_width = current_width
+            .saturating_add(word.iter().map(|(_, c)| unicode::width(*c)).sum::<usize>());
+    }
+    lines.push(current);
+    lines
+        .into_iter()
+        .map(|line| {
+            let mut spans: Vec<(Style, String)> = Vec::new();
+            let mut used = 0_usize;
+            for (style, c) in line {
+                used = used.saturating_add(unicode::width(c));
+                if used > width {
+                    break;
+                }
+                match spans.last_mut() {
+                    Some((last, text)) if *last == style => text.push(c),
+                    _ => spans.push((style, c.to_string())),
+                }
+            }
+            spans
+        })
+        .collect()
+}
@@ -0,0 +1,55 @@
+use crate::paths;
+use std::fs;
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Entries whose presence marks the root directory of a project.
+const MARKERS: [&str; 2] = [".hecto", ".git"];
+
+/// The nearest directory at or above `dir` holding one of the project markers.
+pub fn root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .find(|dir| MARKERS.iter().any(|marker| dir.join(marker).exists()))
+        .map(Path::to_path_buf)
+}
+
+/// Returns `<root>/.hecto/config.toml`, the project's overrides of the user settings.
+pub fn config_path(root: &Path) -> PathBuf {
+    root.join(".hecto").join("config.toml")
+}
+
+/// Whether the user trusts the project at `root` to run the programs its config,
+/// reading `text`, names. Any edit to the config takes the trust away.
+pub fn is_trusted(root: &Path, text: &str) -> bool {
+    let entry = trust_entry(root, text);
+    trusted_path()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .is_some_and(|trusted| trusted.lines().any(|line| line == entry))
+}
+
+/// Remembers that the user trusts the project at `root` with its config as `text`.
+pub fn trust(root: &Path, text: &str) -> Result<(), Error> {
+    let path =
+        trusted_path().ok_or_else(|| Error::new(ErrorKind::NotFound, "no state directory"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", trust_entry(root, text))
+}
+
+/// The trusted projects, one `<config hash>\t<root>` line each.
+fn trusted_path() -> Option<PathBuf> {
+    paths::state_dir().map(|dir| dir.join("trusted"))
+}
+
+fn trust_entry(root: &Path, text: &str) -> String {
+    // FNV-1a, which unlike the standard hasher is the same in every build.
+    let hash = text.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    format!("{:016x}\t{}", hash, root.display())
+}
@@ -0,0 +1,149 @@
+use crate::terminal::Waker;
+use std::ffi::CStr;
+use std::fs::{File, OpenOptions};
+use std::io::{Error, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+
+/// A program running on a pseudo-terminal whose other end the editor holds.
+pub struct Pty {
+    master: File,
+    child: Child,
+    output: Receiver<Vec<u8>>,
+    /// Set once the program and everything it started have closed the terminal.
+    closed: Arc<AtomicBool>,
+}
+
+impl Pty {
+    /// Starts `program` on a new pseudo-terminal of `width` by `height` cells.
+    pub fn spawn(program: &str, width: usize, height: usize, waker: Waker) -> Result<Self, Error> {
+        let master = open_master()?;
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(slave_name(&master)?)?;
+        set_size(&master, width, height)?;
+        let mut command = Command::new(program);
+        command
+            .env("TERM", "vt100")
+            .stdin(Stdio::from(slave.try_clone()?))
+            .stdout(Stdio::from(slave.try_clone()?))
+            .stderr(Stdio::from(slave));
+        // SAFETY: `new_session` makes only async-signal-safe calls, as is required
+        // between fork and exec.
+        unsafe {
+            command.pre_exec(new_session);
+        }
+        let child = command.spawn()?;
+        let mut reader = master.try_clone()?;
+        let (sender, output) = mpsc::channel();
+        let closed = Arc::new(AtomicBool::new(false));
+        let reader_closed = Arc::clone(&closed);
+        thread::spawn(move || {
+            let mut buffer = [0_u8; 4096];
+            // Reading fails once the program and everything it started are gone.
+            while let Ok(count) = reader.read(&mut buffer) {
+                let bytes = buffer.get(..count).unwrap_or_default();
+                if count == 0 || sender.send(bytes.to_vec()).is_err() {
+                    break;
+                }
+                waker.wake();
+            }
+            reader_closed.store(true, Ordering::Relaxed);
+            waker.wake();
+        });
+        Ok(Self {
+            master,
+            child,
+            output,
+            closed,
+        })
+    }
+    pub fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.master.write_all(bytes)
+    }
+    /// The output that arrived since the last call.
+    pub fn read(&self) -> Vec<u8> {
+        self.output.try_iter().flatten().collect()
+    }
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+    /// Whether the output has ended, so that the program is exiting or gone.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Pty {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Makes the program the leader of a new session whose controlling terminal is the
+/// pseudo-terminal, so Ctrl-C and job control reach it.
+fn new_session() -> Result<(), Error> {
+    // SAFETY: `setsid` takes no arguments.
+    if unsafe { libc::setsid() } < 0 {
+        return Err(Error::last_os_error());
+    }
+    // SAFETY: `TIOCSCTTY` takes an integer, and standard input is the pseudo-terminal.
+    if unsafe { libc::ioctl(0, libc::TIOCSCTTY, 0) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn open_master() -> Result<File, Error> {
+    // SAFETY: `posix_openpt` takes no pointers; the descriptor it returns is checked
+    // and then owned by the `File`.
+    let fd = unsafe { libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_CLOEXEC) };
+    if fd < 0 {
+        return Err(Error::last_os_error());
+    }
+    // SAFETY: `fd` was just opened and nothing else owns it.
+    let master = unsafe { File::from_raw_fd(fd) };
+    // SAFETY: `grantpt` only takes the descriptor, which `master` keeps open.
+    if unsafe { libc::grantpt(master.as_raw_fd()) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    // SAFETY: `unlockpt` only takes the descriptor, which `master` keeps open.
+    if unsafe { libc::unlockpt(master.as_raw_fd()) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(master)
+}
+
+fn slave_name(master: &File) -> Result<String, Error> {
+    let mut name = [0 as libc::c_char; 128];
+    // SAFETY: `name` is writable for the length passed, and `ptsname_r` terminates
+    // what it writes with a NUL.
+    let result = unsafe { libc::ptsname_r(master.as_raw_fd(), name.as_mut_ptr(), name.len()) };
+    if result != 0 {
+        return Err(Error::from_raw_os_error(result));
+    }
+    // SAFETY: `ptsname_r` succeeded, so `name` holds a NUL-terminated string.
+    let name = unsafe { CStr::from_ptr(name.as_ptr()) };
+    Ok(name.to_string_lossy().into_owned())
+}
+
+fn set_size(master: &File, width: usize, height: usize) -> Result<(), Error> {
+    let size = libc::winsize {
+        ws_row: u16::try_from(height).unwrap_or(u16::MAX),
+        ws_col: u16::try_from(width).unwrap_or(u16::MAX),
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    // SAFETY: `TIOCSWINSZ` reads a `winsize`, which `size` is.
+    if unsafe { libc::ioctl(master.as_raw_fd(), libc::TIOCSWINSZ, &size) } < 0 {
+        return Err(Error::last_os_error());
+    }
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use crate::Position;
+
+pub struct Entry {
+    pub file_name: String,
+    pub position: Position,
+    pub text: String,
+}
+
+/// A list of locations to step through, such as diagnostics or references.
+#[derive(Default)]
+pub struct Quickfix {
+    pub title: String,
+    entries: Vec<Entry>,
+    current: usize,
+}
+
+impl Quickfix {
+    pub fn set(&mut self, title: &str, entries: Vec<Entry>) {
+        self.title = title.to_string();
+        self.entries = entries;
+        self.current = 0;
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    pub fn current_index(&self) -> Option<usize> {
+        (self.current < self.entries.len()).then_some(self.current)
+    }
+    pub fn current(&self) -> Option<&Entry> {
+        self.entries.get(self.current)
+    }
+    /// Makes entry `index` current, returning it.
+    pub fn select(&mut self, index: usize) -> Option<&Entry> {
+        if index >= self.entries.len() {
+            return None;
+        }
+        self.current = index;
+        self.current()
+    }
+    /// Steps to the next (or previous) entry without wrapping around.
+    pub fn step(&mut self, forward: bool) -> Option<&Entry> {
+        let index = if forward {
+            self.current.checked_add(1)?
+        } else {
+            self.current.checked_sub(1)?
+        };
+        self.select(index)
+    }
+    /// One line per entry, marking the current one.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("{} ({} entries)", self.title, self.entries.len())];
+        lines.extend(self.entries.iter().enumerate().map(|(index, entry)| {
+            format!(
+                "{} {:>3} {}:{}:{}: {}",
+                if index == self.current { '>' } else { ' ' },
+                index.saturating_add(1),
+                entry.file_name,
+                entry.position.y.saturating_add(1),
+                entry.position.x.saturating_add(1),
+                entry.text
+            )
+        }));
+        lines
+    }
+}
@@ -0,0 +1,122 @@
+use crate::highlight::{self, Syntax};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Where a line starts: how deep in brackets, and whether inside a block comment.
+#[derive(Clone, Copy, Default)]
+struct State {
+    depth: usize,
+    in_comment: bool,
+}
+
+/// Bracket nesting for rainbow coloring. The state at the start of every line is
+/// kept, so drawing a line only rescans that line.
+#[derive(Default)]
+pub struct Rainbow {
+    starts: Vec<State>,
+    syntax: Syntax,
+    /// The buffer name and revision the states match.
+    pub revision: Option<(Option<String>, usize)>,
+}
+
+impl Rainbow {
+    pub fn update(
+        &mut self,
+        lines: &[&str],
+        file_type: Option<&str>,
+        revision: (Option<String>, usize),
+    ) {
+        self.syntax = highlight::syntax(file_type).unwrap_or_default();
+        self.starts.clear();
+        let mut state = State::default();
+        for line in lines {
+            self.starts.push(state);
+            state = scan(line, state, &self.syntax, |_, _| ());
+        }
+        self.revision = Some(revision);
+    }
+    /// The brackets of line `y`, whose text is `line`, as grapheme index and depth.
+    /// A closing bracket has the depth of the one it closes.
+    pub fn brackets(&self, y: usize, line: &str) -> Vec<(usize, usize)> {
+        let mut brackets = Vec::new();
+        if let Some(state) = self.starts.get(y) {
+            scan(line, *state, &self.syntax, |index, depth| {
+                brackets.push((index, depth));
+            });
+        }
+        brackets
+    }
+}
+
+/// Walks `line` from `state`, calling `visit` with the grapheme index and depth of
+/// each bracket outside strings and comments, and returns the state after it.
+fn scan(
+    line: &str,
+    mut state: State,
+    syntax: &Syntax,
+    mut visit: impl FnMut(usize, usize),
+) -> State {
+    let mut quote: Option<&str> = None;
+    let mut escaped = false;
+    // Graphemes before this byte belong to a comment marker or literal already read.
+    let mut skip_to = 0;
+    for (index, (offset, grapheme)) in line.grapheme_indices(true).enumerate() {
+        if offset < skip_to {
+            continue;
+        }
+        let rest = line.get(offset..).unwrap_or_default();
+        if state.in_comment {
+            if let Some((_, close)) = syntax
+                .block_comment
+                .filter(|(_, close)| rest.starts_with(close))
+            {
+                state.in_comment = false;
+                skip_to = offset.saturating_add(close.len());
+            }
+            continue;
+        }
+        if let Some(open) = quote {
+            if escaped {
+                escaped = false;
+            } else if grapheme == "\\" {
+                escaped = true;
+            } else if grapheme == open {
+                quote = None;
+            }
+            continue;
+        }
+        if let Some((open, _)) = syntax
+            .block_comment
+            .filter(|(open, _)| rest.starts_with(open))
+        {
+            state.in_comment = true;
+            skip_to = offset.saturating_add(open.len());
+            continue;
+        }
+        if syntax
+            .line_comment
+            .is_some_and(|marker| rest.starts_with(marker))
+        {
+            break;
+        }
+        if grapheme == "'" && syntax.char_literals {
+            skip_to = offset.saturating_add(highlight::char_literal(rest).unwrap_or(1));
+            continue;
+        }
+        if !grapheme.is_empty() && syntax.quotes.contains(grapheme) {
+            quote = Some(grapheme);
+            continue;
+        }
+        match grapheme {
+            "(" | "[" | "{" => {
+                visit(index, state.depth);
+                state.depth = state.depth.saturating_add(1);
+            }
+            ")" | "]" | "}" => {
+                state.depth = state.depth.saturating_sub(1);
+                visit(index, state.depth);
+            }
+            _ => (),
+        }
+    }
+    state
+}
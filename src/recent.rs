@@ -0,0 +1,93 @@
+use crate::paths;
+use crate::remote;
+use crate::Position;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Files remembered.
+const MAX_FILES: usize = 200;
+
+/// Recently opened files and where the cursor was in each, shared by every running
+/// editor through `$XDG_STATE_HOME/hecto/recent`.
+#[derive(Default)]
+pub struct Recent {
+    /// Absolute file names and cursor positions, most recent first.
+    files: Vec<(String, Position)>,
+    path: Option<PathBuf>,
+}
+
+impl Recent {
+    pub fn load() -> Self {
+        let mut recent = Self {
+            files: Vec::new(),
+            path: paths::state_dir().map(|dir| dir.join("recent")),
+        };
+        recent.reload();
+        recent
+    }
+    fn reload(&mut self) {
+        let text = self
+            .path
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .unwrap_or_default();
+        self.files = text
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let y = fields.next()?.parse().ok()?;
+                let x = fields.next()?.parse().ok()?;
+                Some((fields.next()?.to_string(), Position { x, y }))
+            })
+            .collect();
+    }
+    /// Absolute file names, most recent first.
+    pub fn files(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(|(file_name, _)| file_name.as_str())
+    }
+    /// Where the cursor was when `file_name` was last left.
+    pub fn position(&self, file_name: &str) -> Option<&Position> {
+        let file_name = absolute(file_name);
+        self.files
+            .iter()
+            .find(|(other, _)| *other == file_name)
+            .map(|(_, position)| position)
+    }
+    /// Moves `file_name` to the top of the list with the cursor at `position`. The file
+    /// is read again first so that what other editors recorded meanwhile is kept.
+    pub fn record(&mut self, file_name: &str, position: &Position) {
+        self.reload();
+        let file_name = absolute(file_name);
+        self.files.retain(|(other, _)| *other != file_name);
+        self.files.insert(0, (file_name, position.clone()));
+        self.files.truncate(MAX_FILES);
+        let path = if let Some(path) = &self.path {
+            path
+        } else {
+            return;
+        };
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let text: String = self
+            .files
+            .iter()
+            .map(|(file_name, position)| format!("{}\t{}\t{}\n", position.y, position.x, file_name))
+            .collect();
+        let _ = fs::write(path, text);
+    }
+}
+
+/// `file_name` as the absolute path recent files are known by.
+pub fn absolute(file_name: &str) -> String {
+    if remote::is_remote(file_name) {
+        return file_name.to_string();
+    }
+    let path = Path::new(file_name);
+    let path = fs::canonicalize(path).unwrap_or_else(|_| match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path.to_path_buf(),
+    });
+    path.display().to_string()
+}
@@ -0,0 +1,227 @@
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The first line of a recording, so that other files are not replayed by mistake.
+const HEADER: &str = "# hecto input recording";
+
+/// The window size a replay runs at while it lasts, shared with the terminal.
+pub type SizeOverride = Arc<Mutex<Option<(u16, u16)>>>;
+/// The recording in progress, if any, shared between the terminal and the thread
+/// that reads its input.
+pub type RecorderSlot = Arc<Mutex<Option<Recorder>>>;
+
+/// Something the terminal delivered, and when, counted from the start of the session.
+enum Entry {
+    /// Bytes read from the keyboard, as the terminal sent them.
+    Input(Vec<u8>),
+    /// The window changed to this size.
+    Resize(u16, u16),
+}
+
+/// Writes every byte of input and every resize to a file with its time, for
+/// `--record`. Lines look like `0.512 input 1b5b41` and `3.004 resize 120 40`.
+pub struct Recorder {
+    file: File,
+    start: Instant,
+}
+
+impl Recorder {
+    /// Starts a recording at `path` for a window of `width` by `height`.
+    pub fn create(path: &Path, width: u16, height: u16) -> Result<Self, io::Error> {
+        let mut file = File::create(path)?;
+        writeln!(file, "{}", HEADER)?;
+        writeln!(file, "size {} {}", width, height)?;
+        Ok(Self {
+            file,
+            start: Instant::now(),
+        })
+    }
+    pub fn resize(&mut self, width: u16, height: u16) {
+        self.write(&format!("resize {} {}", width, height));
+    }
+    fn input(&mut self, bytes: &[u8]) {
+        let hex = bytes.iter().fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{:02x}", byte);
+            hex
+        });
+        self.write(&format!("input {}", hex));
+    }
+    /// A recording that cannot be written is given up on quietly: the session itself
+    /// matters more.
+    fn write(&mut self, entry: &str) {
+        let _ = writeln!(
+            self.file,
+            "{:.3} {}",
+            self.start.elapsed().as_secs_f64(),
+            entry
+        );
+    }
+}
+
+/// Reads from `inner`, writing all it reads to the recording in progress.
+pub struct RecordingReader<R> {
+    inner: R,
+    recorder: RecorderSlot,
+}
+
+impl<R> RecordingReader<R> {
+    pub fn new(inner: R, recorder: RecorderSlot) -> Self {
+        Self { inner, recorder }
+    }
+}
+
+impl<R: Read> Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        if let Some(bytes) = buf.get(..count).filter(|bytes| !bytes.is_empty()) {
+            if let Ok(mut recorder) = self.recorder.lock() {
+                if let Some(recorder) = recorder.as_mut() {
+                    recorder.input(bytes);
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// A recording read back for `--replay`.
+pub struct Replay {
+    width: u16,
+    height: u16,
+    entries: Vec<(Duration, Entry)>,
+}
+
+impl Replay {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let mut lines = text.lines().enumerate();
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err("not a hecto input recording".to_string());
+        }
+        let mut replay = Self {
+            width: 0,
+            height: 0,
+            entries: Vec::new(),
+        };
+        for (index, line) in lines {
+            let invalid = || format!("line {}: cannot read `{}`", index.saturating_add(1), line);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => (),
+                ["size", width, height] => {
+                    replay.width = width.parse().map_err(|_| invalid())?;
+                    replay.height = height.parse().map_err(|_| invalid())?;
+                }
+                [time, kind, rest @ ..] => {
+                    let time = time
+                        .parse::<f64>()
+                        .ok()
+                        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                        .ok_or_else(invalid)?;
+                    let entry = match (*kind, rest) {
+                        ("input", [hex]) => Entry::Input(decode_hex(hex).ok_or_else(invalid)?),
+                        ("resize", [width, height]) => Entry::Resize(
+                            width.parse().map_err(|_| invalid())?,
+                            height.parse().map_err(|_| invalid())?,
+                        ),
+                        _ => return Err(invalid()),
+                    };
+                    replay.entries.push((time, entry));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(replay)
+    }
+    /// The window size the recording started at.
+    pub fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+}
+
+/// Hands out a recording's input at the pace it was recorded, then reads from
+/// `live`, so that the user takes over where the recording ends. Resizes set the
+/// size the terminal reports and call `on_resize`; once the recording is over, the
+/// window's own size is used again.
+pub struct ReplayReader<R> {
+    entries: std::vec::IntoIter<(Duration, Entry)>,
+    start: Instant,
+    live: R,
+    size: SizeOverride,
+    on_resize: Box<dyn Fn() + Send>,
+    /// Recorded bytes not yet handed out: the terminal may read fewer at a time than
+    /// were recorded in one go.
+    pending: Vec<u8>,
+    finished: bool,
+}
+
+impl<R> ReplayReader<R> {
+    pub fn new(
+        replay: Replay,
+        live: R,
+        size: SizeOverride,
+        on_resize: Box<dyn Fn() + Send>,
+    ) -> Self {
+        if let Ok(mut size) = size.lock() {
+            *size = Some(replay.size());
+        }
+        Self {
+            entries: replay.entries.into_iter(),
+            start: Instant::now(),
+            live,
+            size,
+            on_resize,
+            pending: Vec::new(),
+            finished: false,
+        }
+    }
+    fn set_size(&self, size: Option<(u16, u16)>) {
+        if let Ok(mut size_override) = self.size.lock() {
+            *size_override = size;
+        }
+        (self.on_resize)();
+    }
+}
+
+impl<R: Read> Read for ReplayReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending.is_empty() {
+            let (time, entry) = match self.entries.next() {
+                Some(next) => next,
+                None => break,
+            };
+            thread::sleep(time.saturating_sub(self.start.elapsed()));
+            match entry {
+                Entry::Input(bytes) => self.pending = bytes,
+                Entry::Resize(width, height) => self.set_size(Some((width, height))),
+            }
+        }
+        if !self.pending.is_empty() {
+            let count = self.pending.len().min(buf.len());
+            for (target, source) in buf.iter_mut().zip(self.pending.drain(..count)) {
+                *target = source;
+            }
+            return Ok(count);
+        }
+        if !self.finished {
+            self.finished = true;
+            self.set_size(None);
+        }
+        self.live.read(buf)
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(hex.get(index..index.saturating_add(2))?, 16).ok())
+        .collect()
+}
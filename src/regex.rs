@@ -0,0 +1,579 @@
+/// The most instructions a pattern may compile to, which bounds the work per character.
+const MAX_PROGRAM: usize = 10_000;
+
+/// A regular expression, compiled to a program that runs every way of matching in
+/// step over the text, so matching takes time in proportion to the text times the
+/// pattern and never backtracks. It knows literals, `.`, classes such as `[a-z_]`
+/// and `[^,]`, the shorthands `\d`, `\w` and `\s` and their negations, the anchors
+/// `^` and `$`, groups with `|`, and the greedy quantifiers `*`, `+`, `?` and `{n,m}`.
+/// Like a backtracking matcher, it finds the leftmost match, preferring the earlier
+/// alternative and the longer repetition.
+pub struct Regex {
+    program: Vec<Inst>,
+}
+
+enum Inst {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    /// Goes on at both, preferring the first.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+/// A node and how often it may repeat.
+struct Piece {
+    node: Node,
+    min: usize,
+    max: Option<usize>,
+}
+
+enum Node {
+    Char(char),
+    Any,
+    Class(Vec<ClassItem>, bool),
+    Start,
+    End,
+    Group(Vec<Vec<Piece>>),
+}
+
+#[derive(Clone)]
+enum ClassItem {
+    Range(char, char),
+    Shorthand(Shorthand, bool),
+}
+
+#[derive(Clone, Copy)]
+enum Shorthand {
+    Digit,
+    Word,
+    Space,
+}
+
+impl Shorthand {
+    fn from_letter(letter: char) -> Option<(Self, bool)> {
+        match letter {
+            'd' => Some((Self::Digit, false)),
+            'D' => Some((Self::Digit, true)),
+            'w' => Some((Self::Word, false)),
+            'W' => Some((Self::Word, true)),
+            's' => Some((Self::Space, false)),
+            'S' => Some((Self::Space, true)),
+            _ => None,
+        }
+    }
+    fn matches(self, c: char) -> bool {
+        match self {
+            Self::Digit => c.is_ascii_digit(),
+            Self::Word => c.is_alphanumeric() || c == '_',
+            Self::Space => c.is_whitespace(),
+        }
+    }
+}
+
+impl ClassItem {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Range(first, last) => (*first..=*last).contains(&c),
+            Self::Shorthand(shorthand, negated) => shorthand.matches(c) != *negated,
+        }
+    }
+}
+
+impl Regex {
+    /// Parses `pattern`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a message saying what is wrong with the pattern.
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        Self::with_case(pattern, false)
+    }
+    /// Parses `pattern`, matching letters in either case when `ignore_case` is set.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a message saying what is wrong with the pattern.
+    pub fn with_case(pattern: &str, ignore_case: bool) -> Result<Self, String> {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut parser = Parser {
+            chars: &chars,
+            position: 0,
+            ignore_case,
+        };
+        let alternatives = parser.alternatives()?;
+        if parser.position < chars.len() {
+            return Err("unmatched )".to_string());
+        }
+        let mut compiler = Compiler {
+            program: Vec::new(),
+        };
+        compiler.alternatives(&alternatives)?;
+        compiler.push(Inst::Match)?;
+        Ok(Self {
+            program: compiler.program,
+        })
+    }
+    /// The byte ranges of the matches in `text` that do not overlap, skipping empty ones.
+    pub fn find_all(&self, text: &str) -> Vec<(usize, usize)> {
+        let (chars, offsets) = decode(text);
+        let mut matches = Vec::new();
+        let mut start = 0;
+        while let Some((match_start, match_end)) = self.find_from(&chars, start) {
+            if match_end > match_start {
+                if let (Some(first), Some(last)) =
+                    (offsets.get(match_start), offsets.get(match_end))
+                {
+                    matches.push((*first, *last));
+                }
+                start = match_end;
+            } else {
+                start = match_start.saturating_add(1);
+            }
+            if start > chars.len() {
+                break;
+            }
+        }
+        matches
+    }
+    /// The character range of the first match starting at or after character `first`.
+    /// Threads are kept in order of preference, each with where its match started; a
+    /// new one starts at each character until some thread matches, and a match cuts
+    /// off the threads it is preferred to.
+    fn find_from(&self, chars: &[char], first: usize) -> Option<(usize, usize)> {
+        // The position each instruction was last added at, so it is added once per step.
+        let mut added = vec![usize::MAX; self.program.len()];
+        let mut threads: Vec<(usize, usize)> = Vec::new();
+        let mut found = None;
+        for position in first..=chars.len() {
+            if found.is_none() {
+                self.add(&mut threads, &mut added, 0, position, position, chars.len());
+            }
+            if threads.is_empty() {
+                if found.is_some() {
+                    break;
+                }
+                continue;
+            }
+            let after = position.saturating_add(1);
+            let mut next = Vec::new();
+            for (pc, start) in std::mem::take(&mut threads) {
+                let matches = match (self.program.get(pc), chars.get(position)) {
+                    (Some(Inst::Match), _) => {
+                        found = Some((start, position));
+                        break;
+                    }
+                    (Some(Inst::Char(expected)), Some(c)) => c == expected,
+                    (Some(Inst::Any), Some(c)) => *c != '\n',
+                    (Some(Inst::Class(items, negated)), Some(c)) => {
+                        items.iter().any(|item| item.matches(*c)) != *negated
+                    }
+                    _ => false,
+                };
+                if matches {
+                    self.add(
+                        &mut next,
+                        &mut added,
+                        pc.saturating_add(1),
+                        start,
+                        after,
+                        chars.len(),
+                    );
+                }
+            }
+            threads = next;
+        }
+        found
+    }
+    /// Adds the thread at `pc`, started at `start`, to `threads` for `position`,
+    /// following jumps, splits and anchors to the instructions that read a character
+    /// or match, in order of preference.
+    fn add(
+        &self,
+        threads: &mut Vec<(usize, usize)>,
+        added: &mut [usize],
+        pc: usize,
+        start: usize,
+        position: usize,
+        len: usize,
+    ) {
+        let mut stack = vec![pc];
+        while let Some(pc) = stack.pop() {
+            match added.get_mut(pc) {
+                Some(at) if *at != position => *at = position,
+                _ => continue,
+            }
+            match self.program.get(pc) {
+                Some(Inst::Jump(to)) => stack.push(*to),
+                Some(Inst::Split(first, second)) => {
+                    stack.push(*second);
+                    stack.push(*first);
+                }
+                Some(Inst::Start) if position == 0 => stack.push(pc.saturating_add(1)),
+                Some(Inst::End) if position == len => stack.push(pc.saturating_add(1)),
+                Some(Inst::Start | Inst::End) | None => (),
+                Some(_) => threads.push((pc, start)),
+            }
+        }
+    }
+}
+
+/// Turns parsed alternatives into a program.
+struct Compiler {
+    program: Vec<Inst>,
+}
+
+impl Compiler {
+    /// Appends `inst`, returning where it went.
+    fn push(&mut self, inst: Inst) -> Result<usize, String> {
+        if self.program.len() >= MAX_PROGRAM {
+            return Err("pattern too large".to_string());
+        }
+        self.program.push(inst);
+        Ok(self.program.len().saturating_sub(1))
+    }
+    /// Points the split or jump at `at` to `to`, and a split's first branch to the
+    /// instruction after it.
+    fn patch(&mut self, at: usize, to: usize) {
+        match self.program.get_mut(at) {
+            Some(Inst::Split(first, second)) => {
+                *first = at.saturating_add(1);
+                *second = to;
+            }
+            Some(Inst::Jump(target)) => *target = to,
+            _ => (),
+        }
+    }
+    fn alternatives(&mut self, alternatives: &[Vec<Piece>]) -> Result<(), String> {
+        let mut jumps = Vec::new();
+        for (index, sequence) in alternatives.iter().enumerate() {
+            if index.saturating_add(1) == alternatives.len() {
+                self.sequence(sequence)?;
+            } else {
+                let split = self.push(Inst::Split(0, 0))?;
+                self.sequence(sequence)?;
+                jumps.push(self.push(Inst::Jump(0))?);
+                let next = self.program.len();
+                self.patch(split, next);
+            }
+        }
+        let end = self.program.len();
+        for jump in jumps {
+            self.patch(jump, end);
+        }
+        Ok(())
+    }
+    fn sequence(&mut self, sequence: &[Piece]) -> Result<(), String> {
+        for piece in sequence {
+            for _ in 0..piece.min {
+                self.node(&piece.node)?;
+            }
+            match piece.max {
+                None => {
+                    let split = self.push(Inst::Split(0, 0))?;
+                    self.node(&piece.node)?;
+                    self.push(Inst::Jump(split))?;
+                    let end = self.program.len();
+                    self.patch(split, end);
+                }
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in piece.min..max {
+                        splits.push(self.push(Inst::Split(0, 0))?);
+                        self.node(&piece.node)?;
+                    }
+                    let end = self.program.len();
+                    for split in splits {
+                        self.patch(split, end);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    fn node(&mut self, node: &Node) -> Result<(), String> {
+        let inst = match node {
+            Node::Char(c) => Inst::Char(*c),
+            Node::Any => Inst::Any,
+            Node::Class(items, negated) => Inst::Class(items.clone(), *negated),
+            Node::Start => Inst::Start,
+            Node::End => Inst::End,
+            Node::Group(alternatives) => return self.alternatives(alternatives),
+        };
+        self.push(inst).map(|_| ())
+    }
+}
+
+/// `text` with every character that means something in a pattern escaped, so it
+/// matches itself.
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// The characters of `text`, and the byte offset of each with the end after them.
+fn decode(text: &str) -> (Vec<char>, Vec<usize>) {
+    let offsets = text
+        .char_indices()
+        .map(|(offset, _)| offset)
+        .chain(Some(text.len()))
+        .collect();
+    (text.chars().collect(), offsets)
+}
+
+/// The one character `c` becomes in the other case, if it has one.
+fn other_case(c: char) -> Option<char> {
+    let other: Vec<char> = if c.is_lowercase() {
+        c.to_uppercase().collect()
+    } else {
+        c.to_lowercase().collect()
+    };
+    match other.as_slice() {
+        [other] if *other != c => Some(*other),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    chars: &'a [char],
+    position: usize,
+    ignore_case: bool,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position = self.position.saturating_add(1);
+        Some(c)
+    }
+    /// Sequences separated by `|`, up to a `)` or the end.
+    fn alternatives(&mut self) -> Result<Vec<Vec<Piece>>, String> {
+        let mut alternatives = vec![Vec::new()];
+        while let Some(c) = self.peek() {
+            match c {
+                ')' => break,
+                '|' => {
+                    self.next();
+                    alternatives.push(Vec::new());
+                }
+                _ => {
+                    let node = self.node()?;
+                    let (min, max) = self.quantifier()?;
+                    if let Some(sequence) = alternatives.last_mut() {
+                        sequence.push(Piece { node, min, max });
+                    }
+                }
+            }
+        }
+        Ok(alternatives)
+    }
+    fn node(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some('(') => {
+                if self
+                    .chars
+                    .get(self.position..self.position.saturating_add(2))
+                    == Some(&['?', ':'])
+                {
+                    self.position = self.position.saturating_add(2);
+                }
+                let alternatives = self.alternatives()?;
+                if self.next() != Some(')') {
+                    return Err("unmatched (".to_string());
+                }
+                Ok(Node::Group(alternatives))
+            }
+            Some('[') => self.class(),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('*' | '+' | '?') => Err("nothing to repeat".to_string()),
+            Some('\\') => match self.next() {
+                Some(letter) => Ok(match Shorthand::from_letter(letter) {
+                    Some((shorthand, negated)) => {
+                        Node::Class(vec![ClassItem::Shorthand(shorthand, false)], negated)
+                    }
+                    None => self.char_node(escaped(letter)),
+                }),
+                None => Err("trailing \\".to_string()),
+            },
+            Some(c) => Ok(self.char_node(c)),
+            None => Err("unexpected end".to_string()),
+        }
+    }
+    /// A node matching `c`, or either case of it when case is ignored.
+    fn char_node(&self, c: char) -> Node {
+        match other_case(c).filter(|_| self.ignore_case) {
+            Some(other) => Node::Class(
+                vec![ClassItem::Range(c, c), ClassItem::Range(other, other)],
+                false,
+            ),
+            None => Node::Char(c),
+        }
+    }
+    fn class(&mut self) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.next();
+        }
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.next() {
+                Some(']') if !first => break,
+                Some('\\') => {
+                    let letter = self.next().ok_or_else(|| "unmatched [".to_string())?;
+                    if let Some((shorthand, negated)) = Shorthand::from_letter(letter) {
+                        items.push(ClassItem::Shorthand(shorthand, negated));
+                        first = false;
+                        continue;
+                    }
+                    escaped(letter)
+                }
+                Some(c) => c,
+                None => return Err("unmatched [".to_string()),
+            };
+            first = false;
+            let is_range = self.peek() == Some('-')
+                && self
+                    .chars
+                    .get(self.position.saturating_add(1))
+                    .is_some_and(|next| *next != ']');
+            if is_range {
+                self.next();
+                let last = self.next().ok_or_else(|| "unmatched [".to_string())?;
+                items.push(ClassItem::Range(c, last));
+            } else {
+                items.push(ClassItem::Range(c, c));
+            }
+        }
+        if self.ignore_case {
+            let other_cases: Vec<ClassItem> = items
+                .iter()
+                .filter_map(|item| match item {
+                    ClassItem::Range(first, last)
+                        if first.is_ascii_lowercase() == last.is_ascii_lowercase()
+                            && first.is_ascii_uppercase() == last.is_ascii_uppercase() =>
+                    {
+                        Some(ClassItem::Range(other_case(*first)?, other_case(*last)?))
+                    }
+                    _ => None,
+                })
+                .collect();
+            items.extend(other_cases);
+        }
+        Ok(Node::Class(items, negated))
+    }
+    fn quantifier(&mut self) -> Result<(usize, Option<usize>), String> {
+        let bounds = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => return self.bounds(),
+            _ => return Ok((1, Some(1))),
+        };
+        self.next();
+        Ok(bounds)
+    }
+    /// `{n}`, `{n,}` or `{n,m}`; a `{` that starts none of them is itself.
+    fn bounds(&mut self) -> Result<(usize, Option<usize>), String> {
+        let rest = self.chars.get(self.position..).unwrap_or_default();
+        let close = match rest.iter().position(|c| *c == '}') {
+            Some(close) => self.position.saturating_add(close),
+            None => return Ok((1, Some(1))),
+        };
+        let inside: String = self
+            .chars
+            .get(self.position.saturating_add(1)..close)
+            .unwrap_or_default()
+            .iter()
+            .collect();
+        let number = |text: &str| text.trim().parse::<usize>().ok();
+        let bounds = match inside.split_once(',') {
+            None => number(&inside).map(|count| (count, Some(count))),
+            Some((min, "")) => number(min).map(|min| (min, None)),
+            Some((min, max)) => number(min)
+                .zip(number(max))
+                .map(|(min, max)| (min, Some(max))),
+        };
+        match bounds {
+            Some((min, Some(max))) if max < min => Err("bad repetition bounds".to_string()),
+            Some(bounds) => {
+                self.position = close.saturating_add(1);
+                Ok(bounds)
+            }
+            None => Ok((1, Some(1))),
+        }
+    }
+}
+
+fn escaped(letter: char) -> char {
+    match letter {
+        'n' => '\n',
+        't' => '\t',
+        _ => letter,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Regex;
+
+    fn find_all(pattern: &str, text: &str) -> Vec<(usize, usize)> {
+        Regex::new(pattern).expect("pattern").find_all(text)
+    }
+
+    #[test]
+    fn prefers_the_leftmost_then_the_earlier_alternative() {
+        assert_eq!(find_all("(ab|a)(c|bcd)", "abcd"), vec![(0, 3)]);
+        assert_eq!(find_all("a|ab", "ab"), vec![(0, 1)]);
+        assert_eq!(find_all("b+", "abbcb"), vec![(1, 3), (4, 5)]);
+    }
+
+    #[test]
+    fn repeats_greedily_within_bounds() {
+        assert_eq!(find_all("a{2,3}", "aaaaaa"), vec![(0, 3), (3, 6)]);
+        assert_eq!(find_all("x.*y", "xayby"), vec![(0, 5)]);
+        assert_eq!(find_all("colou?r", "color colour"), vec![(0, 5), (6, 12)]);
+    }
+
+    #[test]
+    fn anchors_and_classes() {
+        assert_eq!(find_all("^\\w+", "foo bar"), vec![(0, 3)]);
+        assert_eq!(find_all("\\d+$", "a1 22"), vec![(3, 5)]);
+        assert_eq!(find_all("[^ ]+", "é b"), vec![(0, 2), (3, 4)]);
+        let regex = Regex::with_case("ABC", true).expect("pattern");
+        assert_eq!(regex.find_all("xabc"), vec![(1, 4)]);
+    }
+
+    #[test]
+    fn nested_empty_repetitions_end() {
+        assert!(find_all("(a*)*b", &"a".repeat(30)).is_empty());
+        assert_eq!(find_all("(a|)*b", "aab"), vec![(0, 3)]);
+    }
+
+    #[test]
+    fn long_lines_take_no_stack() {
+        let text = "a".repeat(200_000);
+        assert_eq!(find_all(".*", &text), vec![(0, 200_000)]);
+        assert!(find_all("a*b", &text).is_empty());
+    }
+
+    #[test]
+    fn rejects_patterns_too_large() {
+        assert!(Regex::new("a{100000}").is_err());
+        assert!(Regex::new("(a").is_err());
+    }
+}
@@ -0,0 +1,19 @@
+use crate::Key;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct Registers {
+    macros: HashMap<char, Vec<Key>>,
+}
+
+impl Registers {
+    pub fn is_valid_name(name: char) -> bool {
+        name.is_ascii_alphanumeric()
+    }
+    pub fn set_macro(&mut self, name: char, keys: Vec<Key>) {
+        self.macros.insert(name, keys);
+    }
+    pub fn get_macro(&self, name: char) -> Option<&Vec<Key>> {
+        self.macros.get(&name)
+    }
+}
@@ -0,0 +1,216 @@
+use crate::paths;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Error, ErrorKind, Read, Write};
+use std::process::{Command, Stdio};
+
+/// Bytes moved between progress reports.
+const CHUNK: usize = 64 * 1024;
+/// How long an idle shared connection is kept open for the next transfer, in seconds.
+const CONNECTION_PERSIST: u32 = 300;
+/// The exit status the download script gives when the file does not exist.
+const NOT_FOUND: i32 = 3;
+
+/// A file on another machine, named as `sftp://[user@]host[:port]/path`. A path
+/// starting with `/~/` is relative to the remote home directory.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Url {
+    /// `host` or `user@host`, as ssh takes it.
+    destination: String,
+    port: Option<u16>,
+    path: String,
+}
+
+impl Url {
+    pub fn parse(file_name: &str) -> Option<Self> {
+        let rest = file_name.strip_prefix("sftp://")?;
+        let (authority, path) = rest.split_at(rest.find('/')?);
+        let (destination, port) = match authority.rsplit_once(':') {
+            Some((destination, port)) => (destination, Some(port.parse().ok()?)),
+            None => (authority, None),
+        };
+        let host = destination.rsplit('@').next().unwrap_or(destination);
+        if host.is_empty() || path.len() < 2 {
+            return None;
+        }
+        let path = path.strip_prefix("/~/").unwrap_or(path);
+        Some(Self {
+            destination: destination.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+    /// `host:path`, for messages.
+    pub fn describe(&self) -> String {
+        format!("{}:{}", self.destination, self.path)
+    }
+    /// An ssh command for the host that shares one connection between transfers: the
+    /// first starts a master connection that later ones reuse while it lasts. Password
+    /// prompts would fight the editor for the terminal, so keys or an agent are needed.
+    fn ssh(&self, script: &str) -> Command {
+        let mut command = Command::new("ssh");
+        command.args(["-o", "BatchMode=yes"]);
+        if let Some(dir) = paths::cache_dir().map(|dir| dir.join("ssh")) {
+            if fs::create_dir_all(&dir).is_ok() {
+                command
+                    .args(["-o", "ControlMaster=auto"])
+                    .arg("-o")
+                    .arg(format!("ControlPath={}", dir.join("%C").display()))
+                    .arg("-o")
+                    .arg(format!("ControlPersist={}", CONNECTION_PERSIST));
+            }
+        }
+        if let Some(port) = self.port {
+            command.arg("-p").arg(port.to_string());
+        }
+        command.arg("--").arg(&self.destination).arg(script);
+        command
+    }
+}
+
+/// Downloads the file, calling `progress` with a message as it arrives.
+///
+/// # Errors
+///
+/// Fails with `NotFound` when the file does not exist, and otherwise with what ssh
+/// reported.
+pub fn read(url: &Url, progress: &mut dyn FnMut(&str)) -> Result<Vec<u8>, Error> {
+    let path = quote(&url.path);
+    let script = format!(
+        "test -e {path} || exit {}; wc -c < {path} && exec cat -- {path}",
+        NOT_FOUND,
+        path = path
+    );
+    let mut child = url
+        .ssh(&script)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| Error::other("no output from ssh"))?;
+    let mut reader = BufReader::new(stdout);
+    let mut size = String::new();
+    reader.read_line(&mut size)?;
+    let total = size.trim().parse::<usize>().ok();
+    let mut bytes = Vec::with_capacity(total.unwrap_or(0));
+    let mut chunk = vec![0; CHUNK];
+    loop {
+        progress(&report("Downloading", url, bytes.len(), total));
+        let count = reader.read(&mut chunk)?;
+        if count == 0 {
+            break;
+        }
+        bytes.extend_from_slice(chunk.get(..count).unwrap_or_default());
+    }
+    finish(child, url).map(|()| bytes)
+}
+
+/// Uploads `contents` in place of the file, calling `progress` with a message as it
+/// goes. The contents go to a temporary file beside it first, so that a dropped
+/// connection leaves the old file whole; copying the old file there first keeps its
+/// permissions.
+///
+/// # Errors
+///
+/// Fails with what ssh reported.
+pub fn write(url: &Url, contents: &[u8], progress: &mut dyn FnMut(&str)) -> Result<(), Error> {
+    let path = quote(&url.path);
+    let temp = quote(&format!("{}.hecto~", url.path));
+    let script = format!(
+        "{{ test ! -e {path} || cp -p -- {path} {temp}; }} && cat > {temp} && mv -f -- {temp} {path}",
+        path = path,
+        temp = temp
+    );
+    let mut child = url
+        .ssh(&script)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let mut sent = 0;
+        for chunk in contents.chunks(CHUNK) {
+            progress(&report("Uploading", url, sent, Some(contents.len())));
+            stdin.write_all(chunk)?;
+            sent = sent.saturating_add(chunk.len());
+        }
+    }
+    finish(child, url)
+}
+
+/// Waits for ssh to exit, turning a failure into an error carrying its message.
+fn finish(mut child: std::process::Child, url: &Url) -> Result<(), Error> {
+    let mut stderr = String::new();
+    if let Some(mut pipe) = child.stderr.take() {
+        let _ = pipe.read_to_string(&mut stderr);
+    }
+    let status = child.wait()?;
+    if status.success() {
+        return Ok(());
+    }
+    if status.code() == Some(NOT_FOUND) {
+        return Err(Error::new(ErrorKind::NotFound, url.describe()));
+    }
+    let message = stderr
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .map_or_else(|| status.to_string(), str::to_string);
+    Err(Error::other(message))
+}
+
+fn report(action: &str, url: &Url, done: usize, total: Option<usize>) -> String {
+    match total.filter(|total| *total > 0) {
+        Some(total) => format!(
+            "{} {}… {} of {} ({}%)",
+            action,
+            url.describe(),
+            size(done),
+            size(total),
+            done.saturating_mul(100) / total
+        ),
+        None => format!("{} {}… {}", action, url.describe(), size(done)),
+    }
+}
+
+/// A byte count in the largest unit that keeps it at least one.
+fn size(bytes: usize) -> String {
+    #[allow(clippy::cast_precision_loss)]
+    let mut amount = bytes as f64;
+    for unit in ["B", "KiB", "MiB"] {
+        if amount < 1024.0 {
+            return if unit == "B" {
+                format!("{} {}", bytes, unit)
+            } else {
+                format!("{:.1} {}", amount, unit)
+            };
+        }
+        amount /= 1024.0;
+    }
+    format!("{:.1} GiB", amount)
+}
+
+/// Quotes `text` for the remote shell.
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', r"'\''"))
+}
+
+/// Whether `file_name` names a file on another machine.
+pub fn is_remote(file_name: &str) -> bool {
+    Url::parse(file_name).is_some()
+}
+
+/// Reads the file without reporting progress: local files from disk, remote ones
+/// over ssh.
+///
+/// # Errors
+///
+/// Fails as `fs::read` or `read` do.
+pub fn read_file(file_name: &str) -> io::Result<Vec<u8>> {
+    match Url::parse(file_name) {
+        Some(url) => read(&url, &mut |_| ()),
+        None => fs::read(file_name),
+    }
+}
@@ -0,0 +1,164 @@
+use crate::document::Edit;
+use crate::search::Query;
+use crate::stats;
+use crate::substitute;
+use crate::Document;
+use crate::Position;
+use std::path::PathBuf;
+
+/// One changed line of a project-wide replace.
+pub struct Hunk {
+    /// 0-based line number.
+    pub line: usize,
+    pub old: String,
+    pub new: String,
+    /// Whether applying the plan makes this change; the preview toggles it.
+    pub selected: bool,
+}
+
+/// The changes to one file.
+pub struct FileChange {
+    pub path: PathBuf,
+    /// The path as shown in the preview.
+    pub name: String,
+    pub hunks: Vec<Hunk>,
+}
+
+/// A replace across the project's files, previewed as a unified diff before any of
+/// it is applied.
+pub struct Plan {
+    pub pattern: String,
+    pub replacement: String,
+    pub files: Vec<FileChange>,
+}
+
+/// Lines the preview gives each file before its hunks: `---` and `+++`.
+const FILE_HEADER_LINES: usize = 2;
+/// Lines of each hunk: `@@`, `-` and `+`.
+const HUNK_LINES: usize = 3;
+
+impl Plan {
+    /// The preview: a title line, then each file as a unified diff with one hunk a
+    /// changed line. Deselected hunks are marked as skipped.
+    pub fn text(&self) -> String {
+        let (hunks, files) = self.counts();
+        let mut text = format!(
+            "Replace /{}/ with \"{}\": {} {} in {} {} (Space toggles, a applies, q cancels)\n",
+            self.pattern,
+            self.replacement,
+            hunks,
+            stats::plural(hunks, "change"),
+            files,
+            stats::plural(files, "file")
+        );
+        for file in &self.files {
+            text.push_str(&format!("--- {}\n+++ {}\n", file.name, file.name));
+            for hunk in &file.hunks {
+                let line = hunk.line.saturating_add(1);
+                text.push_str(&format!(
+                    "@@ -{} +{} @@{}\n-{}\n+{}\n",
+                    line,
+                    line,
+                    if hunk.selected { "" } else { " skipped" },
+                    hunk.old,
+                    hunk.new
+                ));
+            }
+        }
+        text
+    }
+    /// The file and hunk indices of the hunk shown on preview line `y`.
+    pub fn hunk_at(&self, y: usize) -> Option<(usize, usize)> {
+        // The first line is the title.
+        let mut first = 1_usize;
+        for (file_index, file) in self.files.iter().enumerate() {
+            first = first.saturating_add(FILE_HEADER_LINES);
+            let end = first.saturating_add(file.hunks.len().saturating_mul(HUNK_LINES));
+            if y < first {
+                return None;
+            }
+            if y < end {
+                let hunk_index = y.saturating_sub(first).checked_div(HUNK_LINES)?;
+                return Some((file_index, hunk_index));
+            }
+            first = end;
+        }
+        None
+    }
+    pub fn hunk(&self, file: usize, hunk: usize) -> Option<&Hunk> {
+        self.files.get(file)?.hunks.get(hunk)
+    }
+    /// Selects or deselects the hunk on preview line `y`; returns whether there was one.
+    pub fn toggle(&mut self, y: usize) -> bool {
+        let hunk = self
+            .hunk_at(y)
+            .and_then(|(file, hunk)| self.files.get_mut(file)?.hunks.get_mut(hunk));
+        if let Some(hunk) = hunk {
+            hunk.selected = !hunk.selected;
+            true
+        } else {
+            false
+        }
+    }
+    /// How many hunks are selected, and in how many files.
+    pub fn counts(&self) -> (usize, usize) {
+        self.files
+            .iter()
+            .map(|file| file.hunks.iter().filter(|hunk| hunk.selected).count())
+            .filter(|selected| *selected > 0)
+            .fold((0, 0), |(hunks, files), selected| {
+                (hunks.saturating_add(selected), files.saturating_add(1))
+            })
+    }
+}
+
+/// The changes replacing the matches of `query` in `lines` would make: the first
+/// match on each line, or every one when `global` is set.
+pub fn hunks<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    query: &Query,
+    replacement: &str,
+    global: bool,
+) -> Vec<Hunk> {
+    lines
+        .enumerate()
+        .filter_map(|(line, text)| {
+            let mut matches = query.find_all(text);
+            if !global {
+                matches.truncate(1);
+            }
+            if matches.is_empty() {
+                return None;
+            }
+            let new = substitute::replace(text, &matches, replacement);
+            (new != text).then(|| Hunk {
+                line,
+                old: text.to_string(),
+                new,
+                selected: true,
+            })
+        })
+        .collect()
+}
+
+/// The edits making the selected hunks of `file` in `document`, or `None` when the
+/// document has changed since the preview so that a hunk's line reads differently.
+pub fn edits(document: &Document, file: &FileChange) -> Option<Vec<Edit>> {
+    file.hunks
+        .iter()
+        .filter(|hunk| hunk.selected)
+        .map(|hunk| {
+            let row = document
+                .row(hunk.line)
+                .filter(|row| row.as_str() == hunk.old)?;
+            Some(Edit {
+                start: Position { x: 0, y: hunk.line },
+                end: Position {
+                    x: row.len(),
+                    y: hunk.line,
+                },
+                text: hunk.new.clone(),
+            })
+        })
+        .collect()
+}
@@ -1,11 +1,30 @@
+use crate::search::Query;
+use crate::unicode;
+use crate::ListChars;
 use crate::SearchDirection;
+use std::cell::RefCell;
 use std::cmp;
 use unicode_segmentation::UnicodeSegmentation;
 
-#[derive(Default)]
+/// How many graphemes apart the checkpoints of a long row are.
+const CHECKPOINT_INTERVAL: usize = 1024;
+
+/// A tab width, and the byte offset and screen column of each checkpoint at it.
+type Checkpoints = (usize, Vec<(usize, usize)>);
+
+/// A line of a document, without its line break, measured in graphemes.
+#[derive(Default, Clone)]
 pub struct Row {
     string: String,
     len: usize,
+    /// The screen columns elastic tabstops end this row's tabs at, in order; tabs
+    /// past the last one fall back to regular stops.
+    tab_stops: Vec<usize>,
+    /// For rows longer than `CHECKPOINT_INTERVAL`, the byte offset and screen column
+    /// of every `CHECKPOINT_INTERVAL`th grapheme, with the tab width the columns were
+    /// measured for. Measuring from the nearest one keeps a line of megabytes from
+    /// being walked from its start for every column the screen asks about.
+    checkpoints: RefCell<Option<Checkpoints>>,
 }
 
 impl From<&str> for Row {
@@ -13,110 +32,307 @@ impl From<&str> for Row {
         Self {
             string: String::from(slice),
             len: slice.graphemes(true).count(),
+            tab_stops: Vec::new(),
+            checkpoints: RefCell::new(None),
         }
     }
 }
 
+/// What a piece of [`Row::render_visible`] output shows.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Piece {
+    Text,
+    /// A list mode symbol standing in for whitespace.
+    Whitespace,
+    /// An indentation guide.
+    Guide,
+}
+
+/// Drawn in the leading whitespace at each indentation level.
+const GUIDE: char = '│';
+
 impl Row {
-    pub fn render(&self, start: usize, end: usize) -> String {
+    /// Renders graphemes `start..end`, expanding tabs to the next multiple of `tab_width`
+    /// counted from the start of the row, or to its elastic tabstops.
+    pub fn render(&self, start: usize, end: usize, tab_width: usize) -> String {
         let end = cmp::min(end, self.string.len());
         let start = cmp::min(start, end);
+        let (offset, mut column) = self.seek(start, tab_width);
         let mut result = String::new();
-        #[allow(clippy::integer_arithmetic)]
-        for grapheme in self.string[..]
+        for grapheme in self
+            .string
+            .get(offset..)
+            .unwrap_or_default()
             .graphemes(true)
-            .skip(start)
-            .take(end - start)
+            .take(end.saturating_sub(start))
         {
+            let width = self.width(grapheme, column, tab_width);
             if grapheme == "\t" {
-                result.push_str(" ");
+                result.push_str(&" ".repeat(width));
             } else {
                 result.push_str(grapheme);
             }
+            column = column.saturating_add(width);
         }
         result
     }
+    /// Renders graphemes `start..end` like [`Row::render`], but with whitespace
+    /// replaced by its list mode `symbols`, if any, and indentation guides drawn every
+    /// `guide_width` columns of the leading whitespace, if given.
+    pub fn render_visible(
+        &self,
+        start: usize,
+        end: usize,
+        tab_width: usize,
+        symbols: Option<&ListChars>,
+        guide_width: Option<usize>,
+    ) -> Vec<(String, Piece)> {
+        let end = cmp::min(end, self.len);
+        let start = cmp::min(start, end);
+        let trailing = self.string[..]
+            .graphemes(true)
+            .rev()
+            .take_while(|grapheme| *grapheme == " ")
+            .count();
+        let trail_start = self.len.saturating_sub(trailing);
+        let indent_end = self.string[..]
+            .graphemes(true)
+            .take_while(|grapheme| *grapheme == " " || *grapheme == "\t")
+            .count();
+        let (offset, mut column) = self.seek(start, tab_width);
+        let mut pieces: Vec<(String, Piece)> = Vec::new();
+        let mut push = |text: String, piece: Piece| match pieces.last_mut() {
+            Some((last, last_piece)) if *last_piece == piece => last.push_str(&text),
+            _ => pieces.push((text, piece)),
+        };
+        for (index, grapheme) in self
+            .string
+            .get(offset..)
+            .unwrap_or_default()
+            .graphemes(true)
+            .enumerate()
+            .map(|(index, grapheme)| (index.saturating_add(start), grapheme))
+            .take(end.saturating_sub(start))
+        {
+            let width = self.width(grapheme, column, tab_width);
+            let symbol = match (grapheme, symbols) {
+                ("\t", Some(symbols)) => symbols.tab.map(|(first, fill)| {
+                    let mut text = first.to_string();
+                    text.push_str(&fill.to_string().repeat(width.saturating_sub(1)));
+                    text
+                }),
+                (" ", Some(symbols)) if index >= trail_start => symbols.trail.map(String::from),
+                ("\u{a0}", Some(symbols)) => symbols.nbsp.map(String::from),
+                _ => None,
+            };
+            let guide = index < indent_end
+                && guide_width.is_some_and(|guide_width| column % guide_width.max(1) == 0);
+            match symbol {
+                Some(text) => push(text, Piece::Whitespace),
+                None if guide => {
+                    push(GUIDE.to_string(), Piece::Guide);
+                    push(" ".repeat(width.saturating_sub(1)), Piece::Text);
+                }
+                None if grapheme == "\t" => push(" ".repeat(width), Piece::Text),
+                None => push(grapheme.to_string(), Piece::Text),
+            }
+            column = column.saturating_add(width);
+        }
+        pieces.retain(|(text, _)| !text.is_empty());
+        pieces
+    }
+    /// The screen column at which grapheme `index` starts.
+    pub fn column(&self, index: usize, tab_width: usize) -> usize {
+        self.seek(index, tab_width).1
+    }
+    /// The number of graphemes that fit entirely before screen column `column`.
+    pub fn index_at_column(&self, column: usize, tab_width: usize) -> usize {
+        let (first, offset, mut current) = self.with_checkpoints(tab_width, |checkpoints| {
+            let position = checkpoints
+                .partition_point(|(_, start)| *start <= column)
+                .saturating_sub(1);
+            checkpoints.get(position).map(|(offset, start)| {
+                (
+                    position.saturating_mul(CHECKPOINT_INTERVAL),
+                    *offset,
+                    *start,
+                )
+            })
+        });
+        for (index, grapheme) in self
+            .string
+            .get(offset..)
+            .unwrap_or_default()
+            .graphemes(true)
+            .enumerate()
+        {
+            current = current.saturating_add(self.width(grapheme, current, tab_width));
+            if current > column {
+                return index.saturating_add(first);
+            }
+        }
+        self.len
+    }
+    /// The byte offset and screen column of grapheme `index`, measured from the
+    /// nearest checkpoint before it.
+    fn seek(&self, index: usize, tab_width: usize) -> (usize, usize) {
+        let (first, mut offset, mut column) = self.with_checkpoints(tab_width, |checkpoints| {
+            let position = index
+                .checked_div(CHECKPOINT_INTERVAL)
+                .unwrap_or(0)
+                .min(checkpoints.len().saturating_sub(1));
+            checkpoints.get(position).map(|(offset, column)| {
+                (
+                    position.saturating_mul(CHECKPOINT_INTERVAL),
+                    *offset,
+                    *column,
+                )
+            })
+        });
+        for grapheme in self
+            .string
+            .get(offset..)
+            .unwrap_or_default()
+            .graphemes(true)
+            .take(index.saturating_sub(first))
+        {
+            column = column.saturating_add(self.width(grapheme, column, tab_width));
+            offset = offset.saturating_add(grapheme.len());
+        }
+        (offset, column)
+    }
+    /// Runs `find` on the checkpoints for `tab_width`, measuring them first if needed,
+    /// for the grapheme index, byte offset and column to start measuring from. Short
+    /// rows have no checkpoints and are measured from their start.
+    fn with_checkpoints(
+        &self,
+        tab_width: usize,
+        find: impl FnOnce(&[(usize, usize)]) -> Option<(usize, usize, usize)>,
+    ) -> (usize, usize, usize) {
+        if self.len <= CHECKPOINT_INTERVAL {
+            return (0, 0, 0);
+        }
+        let mut cache = self.checkpoints.borrow_mut();
+        if cache.as_ref().map(|(width, _)| *width) != Some(tab_width) {
+            let mut checkpoints = Vec::new();
+            let mut column = 0_usize;
+            for (index, (offset, grapheme)) in self.string.grapheme_indices(true).enumerate() {
+                if index.checked_rem(CHECKPOINT_INTERVAL) == Some(0) {
+                    checkpoints.push((offset, column));
+                }
+                column = column.saturating_add(self.width(grapheme, column, tab_width));
+            }
+            *cache = Some((tab_width, checkpoints));
+        }
+        cache
+            .as_ref()
+            .and_then(|(_, checkpoints)| find(checkpoints))
+            .unwrap_or((0, 0, 0))
+    }
+    /// Forgets the checkpoints once the text or its tab stops change.
+    fn invalidate(&mut self) {
+        *self.checkpoints.get_mut() = None;
+    }
+    /// The offset of grapheme `index` in UTF-16 code units, as language servers count.
+    pub fn utf16_offset(&self, index: usize) -> usize {
+        self.string[..]
+            .graphemes(true)
+            .take(index)
+            .fold(0, |offset, grapheme| {
+                offset.saturating_add(grapheme.encode_utf16().count())
+            })
+    }
+    /// The grapheme index at a UTF-16 `offset`.
+    pub fn index_at_utf16(&self, offset: usize) -> usize {
+        let mut current = 0_usize;
+        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
+            if current >= offset {
+                return index;
+            }
+            current = current.saturating_add(grapheme.encode_utf16().count());
+        }
+        self.len
+    }
+    /// The number of graphemes.
     pub fn len(&self) -> usize {
         self.len
     }
+    /// Whether the line has no text.
     pub fn is_empty(&self) -> bool {
         self.len == 0
     }
-    pub fn find(&self, query: &str, at: usize, direction: SearchDirection) -> Option<usize> {
+    /// The grapheme index of the first match of `query` at or after `at`, or of the
+    /// last one starting before `at` when searching backward.
+    pub fn find(&self, query: &Query, at: usize, direction: SearchDirection) -> Option<usize> {
         if at > self.len {
             return None;
         }
-        let start = if direction == SearchDirection::Forward {
-            at
-        } else {
-            0
-        };
-        let end = if direction == SearchDirection::Forward {
-            self.len
-        } else {
-            at
-        };
-        #[allow(clippy::integer_arithmetic)]
-        let substring: String = self.string[..]
-            .graphemes(true)
-            .skip(start)
-            .take(end - start)
-            .collect();
-        let matching_byte_index = if direction == SearchDirection::Forward {
-            substring.find(query)
+        let mut starts = self.matches(query).into_iter().map(|(start, _)| start);
+        if direction == SearchDirection::Forward {
+            starts.find(|start| *start >= at)
         } else {
-            substring.rfind(query)
-        };
-        if let Some(matching_byte_index) = matching_byte_index {
-            for (grapheme_index, (byte_index, _)) in
-                substring[..].grapheme_indices(true).enumerate()
-            {
-                if matching_byte_index == byte_index {
-                    #[allow(clippy::integer_arithmetic)]
-                    return Some(start + grapheme_index);
-                }
-            }
+            starts.take_while(|start| *start < at).last()
         }
-        None
     }
+    /// The grapheme ranges `query` matches, in order.
+    pub fn matches(&self, query: &Query) -> Vec<(usize, usize)> {
+        let ranges = query.find_all(&self.string);
+        if ranges.is_empty() {
+            return ranges;
+        }
+        let offsets: Vec<usize> = self
+            .string
+            .grapheme_indices(true)
+            .map(|(offset, _)| offset)
+            .collect();
+        ranges
+            .into_iter()
+            .map(|(start, end)| {
+                // A match may begin or end inside a grapheme; it then takes in all of it.
+                let start = offsets
+                    .partition_point(|offset| *offset <= start)
+                    .saturating_sub(1);
+                let end = offsets.partition_point(|offset| *offset < end);
+                (start, end)
+            })
+            .collect()
+    }
+    /// Inserts `c` before grapheme `at`, or at the end when `at` is past it.
     pub fn insert(&mut self, at: usize, c: char) {
+        self.invalidate();
         if at >= self.len() {
             self.string.push(c);
             self.len += 1;
             return;
         }
-        let mut result = String::new();
-        let mut length = 0;
-        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
-            length += 1;
-            if index == at {
-                length += 1;
-                result.push(c);
-            }
-            result.push_str(grapheme);
+        if let Some((offset, _)) = self.string.grapheme_indices(true).nth(at) {
+            self.string.insert(offset, c);
+            self.len = self.len.saturating_add(1);
         }
-        self.len = length;
-        self.string = result;
     }
+    /// Deletes grapheme `at`, if there is one.
     pub fn delete(&mut self, at: usize) {
         if at >= self.len() {
             return;
         }
-        let mut result: String = String::new();
-        let mut length = 0;
-        for (index, grapheme) in self.string[..].graphemes(true).enumerate() {
-            if index != at {
-                length += 1;
-                result.push_str(grapheme);
-            }
+        self.invalidate();
+        let range = self
+            .string
+            .grapheme_indices(true)
+            .nth(at)
+            .map(|(offset, grapheme)| offset..offset.saturating_add(grapheme.len()));
+        if let Some(range) = range {
+            self.string.replace_range(range, "");
+            self.len = self.len.saturating_sub(1);
         }
-        self.len = length;
-        self.string = result;
     }
+    /// Adds the text of `new` to the end.
     pub fn append(&mut self, new: &Self) {
+        self.invalidate();
         self.string = format!("{}{}", self.string, new.string);
         self.len += new.len;
     }
+    /// Cuts the row before grapheme `at`, returning the part after it.
     pub fn split(&mut self, at: usize) -> Self {
         let mut row: String = String::new();
         let mut length = 0;
@@ -132,14 +348,61 @@ impl Row {
             }
         }
 
+        self.invalidate();
         self.string = row;
         self.len = length;
         Self {
             string: splitted_row,
             len: splitted_length,
+            tab_stops: Vec::new(),
+            checkpoints: RefCell::new(None),
+        }
+    }
+    /// Removes trailing whitespace.
+    pub fn trim_end(&mut self) {
+        let trimmed = self.string.trim_end().len();
+        if trimmed != self.string.len() {
+            self.invalidate();
+            self.string.truncate(trimmed);
+            self.len = self.string[..].graphemes(true).count();
         }
     }
+    /// The graphemes of the line, in order.
+    pub fn graphemes(&self) -> impl Iterator<Item = &str> {
+        self.string[..].graphemes(true)
+    }
+    /// The columns `grapheme` takes at `column`, with tabs ending at this row's
+    /// elastic tabstops, if it has any.
+    pub fn width(&self, grapheme: &str, column: usize, tab_width: usize) -> usize {
+        match self.tab_stops.iter().find(|stop| **stop > column) {
+            Some(stop) if grapheme == "\t" => stop.saturating_sub(column),
+            _ => grapheme_width(grapheme, column, tab_width),
+        }
+    }
+    /// Sets the screen columns elastic tabstops end this row's tabs at.
+    pub fn set_tab_stops(&mut self, tab_stops: Vec<usize>) {
+        if tab_stops != self.tab_stops {
+            self.invalidate();
+        }
+        self.tab_stops = tab_stops;
+    }
+    /// The text of the line.
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+    /// The text of the line as UTF-8 bytes.
     pub fn as_bytes(&self) -> &[u8] {
         self.string.as_bytes()
     }
 }
+
+/// The columns `grapheme` takes at `column`. Wide characters take two, so that the
+/// cursor sits where the terminal, and its input method, put the next character;
+/// control characters still take one.
+pub fn grapheme_width(grapheme: &str, column: usize, tab_width: usize) -> usize {
+    if grapheme == "\t" {
+        tab_width.saturating_sub(column % tab_width.max(1))
+    } else {
+        grapheme.chars().next().map_or(1, unicode::width).max(1)
+    }
+}
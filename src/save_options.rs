@@ -0,0 +1,22 @@
+/// Transforms applied to the buffer right before it is written.
+#[derive(Default, Clone)]
+pub struct SaveOptions {
+    pub trim_trailing_whitespace: bool,
+    /// Always end the file with a newline, even if it was opened without one.
+    pub insert_final_newline: bool,
+    pub trim_trailing_blank_lines: bool,
+}
+
+impl SaveOptions {
+    /// Turns on a cleanup by its short name (`trim`, `final-newline` or
+    /// `trim-blank-lines`); returns false for unknown names.
+    pub fn enable(&mut self, name: &str) -> bool {
+        match name {
+            "trim" => self.trim_trailing_whitespace = true,
+            "final-newline" => self.insert_final_newline = true,
+            "trim-blank-lines" => self.trim_trailing_blank_lines = true,
+            _ => return false,
+        }
+        true
+    }
+}
@@ -0,0 +1,418 @@
+use crate::event::Event;
+use crate::options::{self, Kind, OptionInfo};
+use crate::paths;
+use mlua::{Function, HookTriggers, Lua, MultiValue, RegistryKey, Thread, ThreadStatus, Value};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long a script may run between two requests before it is stopped, so that one
+/// stuck in a loop cannot hang the editor.
+const TIME_LIMIT: Duration = Duration::from_secs(5);
+
+/// The `hecto` table scripts reach the editor through. Each function yields a request
+/// to the editor, which answers with `true` and the result or `false` and an error.
+const PRELUDE: &str = r#"
+local yield = coroutine.yield
+local register_command, register_hook = ...
+
+local function check(ok, ...)
+  if not ok then
+    error(..., 2)
+  end
+  return ...
+end
+
+local function request(...)
+  return check(yield(...))
+end
+
+hecto = {
+  lines = function(first, last) return request("lines", first, last) end,
+  set_lines = function(first, last, lines) return request("set_lines", first, last, lines) end,
+  line_count = function() return request("line_count") end,
+  cursor = function() return request("cursor") end,
+  set_cursor = function(line, column) return request("set_cursor", line, column) end,
+  file_name = function() return request("file_name") end,
+  option = function(name) return request("option", name) end,
+  set_option = function(name, value) return request("set_option", name, value) end,
+  command = function(command) return request("command", command) end,
+  message = function(text) return request("message", text) end,
+  create_command = register_command,
+  on = register_hook,
+}
+"#;
+
+/// The functions of the `hecto` table with what they do, for the help.
+pub const API: &[(&str, &str)] = &[
+    (
+        "lines([first, last])",
+        "The buffer lines first to last, or all of them",
+    ),
+    (
+        "set_lines(first, last, lines)",
+        "Replace lines first to last; last = first - 1 inserts",
+    ),
+    ("line_count()", "The number of lines in the buffer"),
+    ("cursor()", "The cursor line and column"),
+    ("set_cursor(line, column)", "Move the cursor"),
+    ("file_name()", "The buffer's file name"),
+    ("option(name)", "The value of an option"),
+    ("set_option(name, value)", "Set an option, as :set does"),
+    (
+        "command(text)",
+        "Run an ex command, raising its error if it fails",
+    ),
+    ("message(text)", "Show text in the message bar"),
+    (
+        "create_command(name, function)",
+        "Add an ex command, called with what follows it",
+    ),
+    (
+        "on(event, function)",
+        "Call function on an event, with the event's name",
+    ),
+];
+
+/// What a script asked the editor for. Lines and columns are counted from 1.
+pub enum Request {
+    /// Lines `first..=last`, all of them when not given.
+    Lines(Option<usize>, Option<usize>),
+    /// Puts `lines` in place of lines `first..=last`; `last` one before `first`
+    /// inserts them.
+    SetLines(usize, usize, Vec<String>),
+    LineCount,
+    Cursor,
+    SetCursor(usize, usize),
+    FileName,
+    GetOption(&'static OptionInfo),
+    SetOption(&'static OptionInfo, options::Value),
+    /// An ex command, as typed after `:`.
+    Command(String),
+    Message(String),
+}
+
+/// The editor's answer to a request.
+pub enum Reply {
+    Nothing,
+    Lines(Vec<String>),
+    Number(usize),
+    Position(usize, usize),
+    Text(String),
+    Value(options::Value),
+    Error(String),
+}
+
+/// How far a running script got.
+pub enum Step {
+    Request(Request),
+    Done,
+}
+
+/// A script started and not yet finished, run a request at a time.
+pub struct Call {
+    thread: RegistryKey,
+}
+
+/// The commands and hooks scripts have registered, with the functions to call.
+#[derive(Default)]
+struct Registered {
+    commands: Vec<(String, RegistryKey)>,
+    hooks: Vec<(Event, RegistryKey)>,
+}
+
+/// When the running script is stopped.
+struct Deadline(Instant);
+
+/// The Lua state scripts run in, shared by `init.lua`, `:lua` and the commands and
+/// hooks they register.
+pub struct Scripts {
+    lua: Lua,
+}
+
+impl Scripts {
+    /// # Errors
+    ///
+    /// Fails if the `hecto` table cannot be set up.
+    pub fn new() -> Result<Self, String> {
+        let lua = Lua::new();
+        lua.set_app_data(Registered::default());
+        let register_command = lua
+            .create_function(|lua, (name, function): (String, Function)| {
+                if name.is_empty() || !name.chars().all(char::is_alphanumeric) {
+                    return Err(mlua::Error::RuntimeError(
+                        "command names are letters and digits".to_string(),
+                    ));
+                }
+                let key = lua.create_registry_value(function)?;
+                if let Some(mut registered) = lua.app_data_mut::<Registered>() {
+                    registered.commands.retain(|(command, _)| *command != name);
+                    registered.commands.push((name, key));
+                }
+                Ok(())
+            })
+            .map_err(describe)?;
+        let register_hook = lua
+            .create_function(|lua, (name, function): (String, Function)| {
+                let event = Event::from_name(&name).ok_or_else(|| {
+                    mlua::Error::RuntimeError(format!("unknown event `{}`", name))
+                })?;
+                let key = lua.create_registry_value(function)?;
+                if let Some(mut registered) = lua.app_data_mut::<Registered>() {
+                    registered.hooks.push((event, key));
+                }
+                Ok(())
+            })
+            .map_err(describe)?;
+        lua.load(PRELUDE)
+            .set_name("prelude")
+            .call::<_, ()>((register_command, register_hook))
+            .map_err(describe)?;
+        Ok(Self { lua })
+    }
+    /// Starts `function` with `arguments`.
+    fn start<'lua>(
+        &'lua self,
+        function: Function<'lua>,
+        arguments: Vec<String>,
+    ) -> Result<(Call, Step), String> {
+        let thread = self.lua.create_thread(function).map_err(describe)?;
+        let call = Call {
+            thread: self.lua.create_registry_value(thread).map_err(describe)?,
+        };
+        let arguments = arguments
+            .into_iter()
+            .map(|argument| self.lua.create_string(&argument).map(Value::String))
+            .collect::<mlua::Result<Vec<_>>>()
+            .map_err(describe)?;
+        let step = self.run(&call, MultiValue::from_vec(arguments))?;
+        Ok((call, step))
+    }
+    /// Starts the Lua file at `path`.
+    ///
+    /// # Errors
+    ///
+    /// Reports a file that cannot be read or compiled, or fails before its first
+    /// request.
+    pub fn run_file(&self, path: &Path) -> Result<(Call, Step), String> {
+        let code =
+            fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+        self.run_code(&code, &format!("@{}", path.display()))
+    }
+    /// Starts the Lua `code`, named `name` in errors: a file name after `@`, or any
+    /// text after `=`.
+    ///
+    /// # Errors
+    ///
+    /// Reports code that does not compile or fails before its first request.
+    pub fn run_code(&self, code: &str, name: &str) -> Result<(Call, Step), String> {
+        let function = self
+            .lua
+            .load(code)
+            .set_name(name)
+            .into_function()
+            .map_err(describe)?;
+        self.start(function, Vec::new())
+    }
+    pub fn has_command(&self, name: &str) -> bool {
+        self.lua
+            .app_data_ref::<Registered>()
+            .is_some_and(|registered| {
+                registered
+                    .commands
+                    .iter()
+                    .any(|(command, _)| command == name)
+            })
+    }
+    /// Starts the command `name` registered by a script with `arguments`, if there is one.
+    pub fn command(&self, name: &str, arguments: &str) -> Option<Result<(Call, Step), String>> {
+        let function = {
+            let registered = self.lua.app_data_ref::<Registered>()?;
+            let (_, key) = registered
+                .commands
+                .iter()
+                .find(|(command, _)| command == name)?;
+            self.lua.registry_value::<Function>(key)
+        };
+        Some(
+            function
+                .map_err(describe)
+                .and_then(|function| self.start(function, vec![arguments.to_string()])),
+        )
+    }
+    /// Starts hook `index` of those registered for `event`, if there are that many.
+    pub fn hook(&self, event: Event, index: usize) -> Option<Result<(Call, Step), String>> {
+        let function = {
+            let registered = self.lua.app_data_ref::<Registered>()?;
+            let (_, key) = registered
+                .hooks
+                .iter()
+                .filter(|(hooked, _)| *hooked == event)
+                .nth(index)?;
+            self.lua.registry_value::<Function>(key)
+        };
+        Some(
+            function
+                .map_err(describe)
+                .and_then(|function| self.start(function, vec![event.name().to_string()])),
+        )
+    }
+    /// Hands `reply` to the script and runs it up to its next request.
+    ///
+    /// # Errors
+    ///
+    /// Reports the error that stopped the script.
+    pub fn resume(&self, call: &Call, reply: Reply) -> Result<Step, String> {
+        let values = self.reply_values(reply).map_err(describe)?;
+        self.run(call, values)
+    }
+    fn run<'lua>(&'lua self, call: &Call, mut values: MultiValue<'lua>) -> Result<Step, String> {
+        let thread = self
+            .lua
+            .registry_value::<Thread>(&call.thread)
+            .map_err(describe)?;
+        loop {
+            self.lua.set_app_data(Deadline(
+                Instant::now()
+                    .checked_add(TIME_LIMIT)
+                    .unwrap_or_else(Instant::now),
+            ));
+            thread.set_hook(
+                HookTriggers::new().every_nth_instruction(10_000),
+                |lua, _| match lua.app_data_ref::<Deadline>() {
+                    Some(deadline) if Instant::now() > deadline.0 => Err(
+                        mlua::Error::RuntimeError("the script ran for too long".to_string()),
+                    ),
+                    _ => Ok(()),
+                },
+            );
+            let yielded = thread.resume::<_, MultiValue>(values).map_err(describe)?;
+            if thread.status() != ThreadStatus::Resumable {
+                return Ok(Step::Done);
+            }
+            // A request the editor need not see, because it is malformed, is answered
+            // with its error right away.
+            match self.request(yielded) {
+                Ok(request) => return Ok(Step::Request(request)),
+                Err(error) => {
+                    values = self.reply_values(Reply::Error(error)).map_err(describe)?;
+                }
+            }
+        }
+    }
+    /// Reads what a script yielded into a request.
+    fn request(&self, values: MultiValue) -> Result<Request, String> {
+        let (kind, mut values): (String, MultiValue) = self
+            .lua
+            .unpack_multi(values)
+            .map_err(|_| "scripts may only yield through the hecto table".to_string())?;
+        let mut next = || values.pop_front().unwrap_or(Value::Nil);
+        let request = match kind.as_str() {
+            "lines" => Request::Lines(self.optional_line(next())?, self.optional_line(next())?),
+            "set_lines" => {
+                let first = self.line(next())?;
+                let last = self.number(next())?;
+                let lines = self
+                    .lua
+                    .unpack::<Vec<String>>(next())
+                    .map_err(|_| "lines must be a list of strings".to_string())?;
+                Request::SetLines(first, last, lines)
+            }
+            "line_count" => Request::LineCount,
+            "cursor" => Request::Cursor,
+            "set_cursor" => Request::SetCursor(self.line(next())?, self.line(next())?),
+            "file_name" => Request::FileName,
+            "option" => Request::GetOption(self.option(next())?),
+            "set_option" => {
+                let option = self.option(next())?;
+                let value = match (option.kind, next()) {
+                    (Kind::Bool, Value::Boolean(value)) => options::Value::Bool(value),
+                    (Kind::Number, value) => options::Value::Number(self.number(value)?),
+                    (Kind::Text, Value::String(text)) => {
+                        options::Value::Text(text.to_str().unwrap_or_default().to_string())
+                    }
+                    _ => return Err(format!("wrong type of value for {}", option.name)),
+                };
+                Request::SetOption(option, value)
+            }
+            "command" => Request::Command(self.text(next())?),
+            "message" => Request::Message(self.text(next())?),
+            _ => return Err(format!("unknown request `{}`", kind)),
+        };
+        Ok(request)
+    }
+    fn text(&self, value: Value) -> Result<String, String> {
+        self.lua
+            .unpack::<String>(value)
+            .map_err(|_| "a string is required".to_string())
+    }
+    fn number(&self, value: Value) -> Result<usize, String> {
+        self.lua
+            .unpack::<usize>(value)
+            .map_err(|_| "a number of 0 or more is required".to_string())
+    }
+    fn line(&self, value: Value) -> Result<usize, String> {
+        match self.number(value)? {
+            0 => Err("lines and columns are counted from 1".to_string()),
+            line => Ok(line),
+        }
+    }
+    fn optional_line(&self, value: Value) -> Result<Option<usize>, String> {
+        match value {
+            Value::Nil => Ok(None),
+            value => self.line(value).map(Some),
+        }
+    }
+    fn option(&self, value: Value) -> Result<&'static OptionInfo, String> {
+        let name = self.text(value)?;
+        options::find(&name).ok_or_else(|| format!("Unknown option: {}", name))
+    }
+    fn reply_values(&self, reply: Reply) -> mlua::Result<MultiValue<'_>> {
+        let lua = &self.lua;
+        let ok = Value::Boolean(true);
+        let values = match reply {
+            Reply::Nothing => vec![ok],
+            Reply::Lines(lines) => vec![ok, Value::Table(lua.create_sequence_from(lines)?)],
+            Reply::Number(number) => vec![ok, Value::Integer(integer(number))],
+            Reply::Position(line, column) => vec![
+                ok,
+                Value::Integer(integer(line)),
+                Value::Integer(integer(column)),
+            ],
+            Reply::Text(text) => vec![ok, Value::String(lua.create_string(&text)?)],
+            Reply::Value(options::Value::Bool(value)) => vec![ok, Value::Boolean(value)],
+            Reply::Value(options::Value::Number(number)) => {
+                vec![ok, Value::Integer(integer(number))]
+            }
+            Reply::Value(options::Value::Text(text)) => {
+                vec![ok, Value::String(lua.create_string(&text)?)]
+            }
+            Reply::Error(error) => vec![
+                Value::Boolean(false),
+                Value::String(lua.create_string(&error)?),
+            ],
+        };
+        Ok(MultiValue::from_vec(values))
+    }
+}
+
+/// The first line of a Lua error, without the traceback that follows.
+fn describe(error: mlua::Error) -> String {
+    let error = error.to_string();
+    let line = error.lines().next().unwrap_or_default();
+    line.strip_prefix("runtime error: ")
+        .or_else(|| line.strip_prefix("syntax error: "))
+        .unwrap_or(line)
+        .to_string()
+}
+
+fn integer(number: usize) -> i64 {
+    i64::try_from(number).unwrap_or(i64::MAX)
+}
+
+/// The script run at startup.
+pub fn init_file() -> Option<PathBuf> {
+    paths::config_dir()
+        .map(|dir| dir.join("init.lua"))
+        .filter(|path| path.is_file())
+}
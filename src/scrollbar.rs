@@ -0,0 +1,47 @@
+use crate::diagnostics::Severity;
+
+/// Something worth pointing out on the scrollbar, most important first.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Mark {
+    Diagnostic(Severity),
+    Search,
+}
+
+/// One screen row of the scrollbar.
+#[derive(Clone, Copy, Default)]
+pub struct Cell {
+    /// Whether the row is part of the thumb showing the viewport.
+    pub thumb: bool,
+    pub mark: Option<Mark>,
+}
+
+/// Lays out a scrollbar `height` rows tall for a buffer of `lines` lines with lines
+/// `first..last` on screen. Each of `marks` is a line and what to mark it with; a
+/// row covering several marked lines shows the most important mark.
+pub fn cells(
+    lines: usize,
+    first: usize,
+    last: usize,
+    height: usize,
+    marks: impl Iterator<Item = (usize, Mark)>,
+) -> Vec<Cell> {
+    let lines = lines.max(1);
+    let row_of = |y: usize| y.saturating_mul(height) / lines;
+    let thumb_start = row_of(first).min(height.saturating_sub(1));
+    // Round the end up so the thumb never shrinks below one row.
+    let thumb_end = last
+        .saturating_mul(height)
+        .saturating_add(lines.saturating_sub(1))
+        / lines;
+    let thumb_end = thumb_end.clamp(thumb_start.saturating_add(1), height);
+    let mut cells = vec![Cell::default(); height];
+    for (row, cell) in cells.iter_mut().enumerate() {
+        cell.thumb = thumb_start <= row && row < thumb_end;
+    }
+    for (y, mark) in marks {
+        if let Some(cell) = cells.get_mut(row_of(y)) {
+            cell.mark = Some(cell.mark.map_or(mark, |other| other.min(mark)));
+        }
+    }
+    cells
+}
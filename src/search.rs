@@ -0,0 +1,140 @@
+use crate::regex::{self, Regex};
+
+/// How letters in a search query match letters in the text.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Case {
+    Sensitive,
+    Insensitive,
+    /// Insensitive unless the query has an uppercase letter.
+    Smart,
+}
+
+impl Case {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "sensitive" => Some(Self::Sensitive),
+            "insensitive" => Some(Self::Insensitive),
+            "smart" => Some(Self::Smart),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sensitive => "sensitive",
+            Self::Insensitive => "insensitive",
+            Self::Smart => "smart",
+        }
+    }
+    /// The next way to match, for toggling in the search prompt.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Sensitive => Self::Insensitive,
+            Self::Insensitive => Self::Smart,
+            Self::Smart => Self::Sensitive,
+        }
+    }
+}
+
+/// How a search query matches, from the `search_*` settings.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Options {
+    pub case: Case,
+    /// Match only where the query is neither preceded nor followed by a word character.
+    pub whole_word: bool,
+    /// Read the query as a regular expression rather than as literal text.
+    pub regex: bool,
+}
+
+impl Options {
+    /// The options that are on, as shown in the search prompt, such as `[smart case, word]`.
+    pub fn flags(&self) -> String {
+        let mut flags = Vec::new();
+        match self.case {
+            Case::Sensitive => (),
+            Case::Insensitive => flags.push("ignore case"),
+            Case::Smart => flags.push("smart case"),
+        }
+        if self.whole_word {
+            flags.push("word");
+        }
+        if self.regex {
+            flags.push("regex");
+        }
+        if flags.is_empty() {
+            String::new()
+        } else {
+            format!("[{}]", flags.join(", "))
+        }
+    }
+}
+
+/// A search query ready to match lines.
+pub struct Query {
+    /// Case-sensitive literal text is found with `str::match_indices`; everything
+    /// else goes through a regular expression.
+    literal: Option<String>,
+    regex: Option<Regex>,
+    whole_word: bool,
+}
+
+impl Query {
+    /// Prepares `text` to be searched for with `options`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a message saying what is wrong with a regular expression.
+    pub fn new(text: &str, options: Options) -> Result<Self, String> {
+        let ignore_case = match options.case {
+            Case::Sensitive => false,
+            Case::Insensitive => true,
+            Case::Smart => !text.chars().any(char::is_uppercase),
+        };
+        let (literal, regex) = if options.regex {
+            (None, Some(Regex::with_case(text, ignore_case)?))
+        } else if ignore_case {
+            (None, Some(Regex::with_case(&regex::escape(text), true)?))
+        } else {
+            (Some(text.to_string()), None)
+        };
+        Ok(Self {
+            literal,
+            regex,
+            whole_word: options.whole_word,
+        })
+    }
+    /// The byte ranges of the matches in `line` that do not overlap, skipping empty ones.
+    pub fn find_all(&self, line: &str) -> Vec<(usize, usize)> {
+        let matches = match (&self.literal, &self.regex) {
+            (Some(literal), _) if !literal.is_empty() => line
+                .match_indices(literal.as_str())
+                .map(|(start, text)| (start, start.saturating_add(text.len())))
+                .collect(),
+            (_, Some(regex)) => regex.find_all(line),
+            _ => Vec::new(),
+        };
+        if self.whole_word {
+            matches
+                .into_iter()
+                .filter(|(start, end)| is_whole_word(line, *start, *end))
+                .collect()
+        } else {
+            matches
+        }
+    }
+    /// Whether the query matches anywhere in `line`.
+    pub fn is_match(&self, line: &str) -> bool {
+        !self.find_all(line).is_empty()
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether bytes `start..end` of `line` are neither preceded nor followed by a word
+/// character.
+fn is_whole_word(line: &str, start: usize, end: usize) -> bool {
+    let before = line.get(..start).and_then(|text| text.chars().next_back());
+    let after = line.get(end..).and_then(|text| text.chars().next());
+    !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+}
@@ -0,0 +1,373 @@
+use crate::commands;
+use crate::outline;
+use crate::stats;
+use crate::terminal::Waker;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// Files larger than this are left out of the index.
+const MAX_FILE_SIZE: u64 = 1_000_000;
+/// The most files indexed, so a huge tree cannot exhaust memory.
+const MAX_FILES: usize = 50_000;
+/// How long the indexing thread rests between walks looking for changed files.
+const RESCAN_INTERVAL: Duration = Duration::from_secs(5);
+/// How many files the first walk indexes between wakes of the main loop.
+const WAKE_EVERY: usize = 500;
+/// Directories of dependencies and build output rather than the project's own files.
+const SKIPPED_DIRS: &[&str] = &["node_modules", "target", "vendor", "dist", "__pycache__"];
+
+/// A definition found in an indexed file.
+pub struct Symbol {
+    pub name: String,
+    /// LSP `SymbolKind`.
+    pub kind: usize,
+    pub line: usize,
+}
+
+/// Where go to anything leads.
+pub enum Target {
+    File(PathBuf),
+    /// A definition's file and line.
+    Symbol(PathBuf, usize),
+    /// A word, to be found across the project.
+    Word(String),
+}
+
+/// What is indexed of one file.
+struct FileEntry {
+    /// Each word with the line and UTF-16 column of every occurrence.
+    words: HashMap<String, Vec<(usize, usize)>>,
+    symbols: Vec<Symbol>,
+}
+
+/// What the indexing thread sends: a file read again, or one that is gone.
+enum Message {
+    Indexed(PathBuf, FileEntry),
+    Removed(PathBuf),
+    Scanned,
+}
+
+/// The words and definitions of every file in a project, kept current by a thread that
+/// walks the tree again every few seconds and re-reads the files whose modification
+/// time changed. Results are taken in by `poll`, which the main loop calls.
+pub struct SearchIndex {
+    pub root: PathBuf,
+    files: HashMap<PathBuf, FileEntry>,
+    /// How many files each word appears in.
+    words: HashMap<String, usize>,
+    incoming: Receiver<Message>,
+    /// Whether the first walk over the project has finished.
+    pub ready: bool,
+}
+
+impl SearchIndex {
+    /// Starts indexing the files under `root`.
+    pub fn start(root: &Path, waker: Waker) -> Self {
+        let (sender, incoming) = mpsc::channel();
+        let thread_root = root.to_path_buf();
+        thread::spawn(move || scan(&thread_root, &sender, &waker));
+        Self {
+            root: root.to_path_buf(),
+            files: HashMap::new(),
+            words: HashMap::new(),
+            incoming,
+            ready: false,
+        }
+    }
+    /// Takes in the files indexed since the last call.
+    pub fn poll(&mut self) {
+        while let Ok(message) = self.incoming.try_recv() {
+            match message {
+                Message::Indexed(path, entry) => self.insert(path, entry),
+                Message::Removed(path) => self.remove(&path),
+                Message::Scanned => self.ready = true,
+            }
+        }
+    }
+    /// Reads `path` again at once, as after it is saved, rather than on the next walk.
+    pub fn refresh(&mut self, path: &Path) {
+        if !is_indexed(&self.root, path) {
+            return;
+        }
+        let entry = fs::metadata(path)
+            .ok()
+            .filter(|metadata| metadata.len() <= MAX_FILE_SIZE)
+            .and_then(|_| index_file(path));
+        match entry {
+            Some(entry) => self.insert(path.to_path_buf(), entry),
+            None => self.remove(path),
+        }
+    }
+    fn insert(&mut self, path: PathBuf, entry: FileEntry) {
+        self.remove(&path);
+        for word in entry.words.keys() {
+            let count = self.words.entry(word.clone()).or_insert(0);
+            *count = count.saturating_add(1);
+        }
+        self.files.insert(path, entry);
+    }
+    fn remove(&mut self, path: &Path) {
+        let entry = if let Some(entry) = self.files.remove(path) {
+            entry
+        } else {
+            return;
+        };
+        for word in entry.words.keys() {
+            if let Some(count) = self.words.get_mut(word) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    self.words.remove(word);
+                }
+            }
+        }
+    }
+    pub fn file_count(&self) -> usize {
+        self.files.len()
+    }
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+    pub fn symbols(&self) -> impl Iterator<Item = (&Path, &Symbol)> {
+        self.files.iter().flat_map(|(path, entry)| {
+            entry
+                .symbols
+                .iter()
+                .map(move |symbol| (path.as_path(), symbol))
+        })
+    }
+    /// The distinct words of the project with the number of files holding each.
+    pub fn words(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.words
+            .iter()
+            .map(|(word, count)| (word.as_str(), *count))
+    }
+    /// Every occurrence of `word` as a file, line and UTF-16 column, in file order.
+    pub fn occurrences(&self, word: &str) -> Vec<(&Path, usize, usize)> {
+        let mut occurrences: Vec<(&Path, usize, usize)> = self
+            .files
+            .iter()
+            .filter_map(|(path, entry)| Some((path, entry.words.get(word)?)))
+            .flat_map(|(path, positions)| {
+                positions
+                    .iter()
+                    .map(move |(line, column)| (path.as_path(), *line, *column))
+            })
+            .collect();
+        occurrences.sort_unstable();
+        occurrences
+    }
+    /// The definitions, files and words matching `query`, best first, as their place
+    /// and a line describing them. Words only match by containing `query`, and only a
+    /// non-empty one, since there are so many.
+    pub fn find(&self, query: &str) -> Vec<(Target, String)> {
+        let lower = query.to_lowercase();
+        let dir = env::current_dir().ok();
+        let display_name = |path: &Path| relative(path, dir.as_deref());
+        let symbols = self.symbols().filter_map(|(path, symbol)| {
+            let score = commands::fuzzy_score(query, &symbol.name)?;
+            let label = format!(
+                "{} {:<30} {}:{}",
+                outline::kind_icon(symbol.kind),
+                symbol.name,
+                display_name(path),
+                symbol.line.saturating_add(1)
+            );
+            Some((
+                score,
+                0,
+                Target::Symbol(path.to_path_buf(), symbol.line),
+                label,
+            ))
+        });
+        let files = self.files().filter_map(|path| {
+            let name = display_name(path);
+            let score = commands::fuzzy_score(query, &name)?;
+            let label = format!("  {:<30} file", name);
+            Some((score, 1, Target::File(path.to_path_buf()), label))
+        });
+        let words = self
+            .words()
+            .filter(|(word, _)| !lower.is_empty() && word.to_lowercase().contains(&lower))
+            .filter_map(|(word, count)| {
+                let score = commands::fuzzy_score(query, word)?;
+                let label = format!(
+                    "  {:<30} word in {} {}",
+                    word,
+                    count,
+                    stats::plural(count, "file")
+                );
+                Some((score, 2, Target::Word(word.to_string()), label))
+            });
+        let mut matches: Vec<(usize, usize, Target, String)> =
+            symbols.chain(files).chain(words).collect();
+        matches.sort_by(|(a, a_kind, _, a_label), (b, b_kind, _, b_label)| {
+            b.cmp(a)
+                .then(a_kind.cmp(b_kind))
+                .then(a_label.len().cmp(&b_label.len()))
+                .then(a_label.cmp(b_label))
+        });
+        matches
+            .into_iter()
+            .map(|(_, _, target, label)| (target, label))
+            .collect()
+    }
+}
+
+/// `path` relative to the working directory where it is inside it.
+pub fn display_name(path: &Path) -> String {
+    relative(path, env::current_dir().ok().as_deref())
+}
+
+fn relative(path: &Path, dir: Option<&Path>) -> String {
+    dir.and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Whether an entry is left out of the walk: hidden ones and dependency directories.
+fn is_skipped(name: &str) -> bool {
+    name.starts_with('.') || SKIPPED_DIRS.contains(&name)
+}
+
+/// Whether the walk from `root` would reach `path`.
+fn is_indexed(root: &Path, path: &Path) -> bool {
+    path.strip_prefix(root).is_ok_and(|relative| {
+        relative
+            .components()
+            .all(|component| !is_skipped(&component.as_os_str().to_string_lossy()))
+    })
+}
+
+/// The indexing thread: walks `root` over and over, sending the files that changed,
+/// until the index is dropped.
+fn scan(root: &Path, sender: &Sender<Message>, waker: &Waker) {
+    let mut known: HashMap<PathBuf, SystemTime> = HashMap::new();
+    let mut first = true;
+    loop {
+        let mut found = Vec::new();
+        walk(root, &mut found);
+        let mut changed = 0_usize;
+        for (path, modified) in &found {
+            if known.get(path) == Some(modified) {
+                continue;
+            }
+            let message = match index_file(path) {
+                Some(entry) => Message::Indexed(path.clone(), entry),
+                None => Message::Removed(path.clone()),
+            };
+            if sender.send(message).is_err() {
+                return;
+            }
+            known.insert(path.clone(), *modified);
+            changed = changed.saturating_add(1);
+            if first && changed.checked_rem(WAKE_EVERY) == Some(0) {
+                waker.wake();
+            }
+        }
+        let found: HashSet<&PathBuf> = found.iter().map(|(path, _)| path).collect();
+        let removed: Vec<PathBuf> = known
+            .keys()
+            .filter(|path| !found.contains(path))
+            .cloned()
+            .collect();
+        for path in removed {
+            known.remove(&path);
+            changed = changed.saturating_add(1);
+            if sender.send(Message::Removed(path)).is_err() {
+                return;
+            }
+        }
+        if sender.send(Message::Scanned).is_err() {
+            return;
+        }
+        if first || changed > 0 {
+            waker.wake();
+        }
+        first = false;
+        thread::sleep(RESCAN_INTERVAL);
+    }
+}
+
+/// Collects the files under `dir` small enough to index, with their modification times.
+fn walk(dir: &Path, found: &mut Vec<(PathBuf, SystemTime)>) {
+    let entries = if let Ok(entries) = fs::read_dir(dir) {
+        entries
+    } else {
+        return;
+    };
+    for entry in entries.flatten() {
+        if found.len() >= MAX_FILES {
+            return;
+        }
+        if is_skipped(&entry.file_name().to_string_lossy()) {
+            continue;
+        }
+        // Symbolic links are not followed, so a link to a parent cannot loop.
+        let file_type = if let Ok(file_type) = entry.file_type() {
+            file_type
+        } else {
+            continue;
+        };
+        if file_type.is_dir() {
+            walk(&entry.path(), found);
+        } else if file_type.is_file() {
+            let metadata = if let Ok(metadata) = entry.metadata() {
+                metadata
+            } else {
+                continue;
+            };
+            if metadata.len() <= MAX_FILE_SIZE {
+                if let Ok(modified) = metadata.modified() {
+                    found.push((entry.path(), modified));
+                }
+            }
+        }
+    }
+}
+
+/// Reads the words and definitions of `path`, or `None` for a binary or unreadable file.
+fn index_file(path: &Path) -> Option<FileEntry> {
+    let bytes = fs::read(path).ok()?;
+    if bytes.iter().take(8000).any(|byte| *byte == 0) {
+        return None;
+    }
+    let text = String::from_utf8(bytes).ok()?;
+    let markdown = path
+        .extension()
+        .is_some_and(|extension| extension == "md" || extension == "markdown");
+    let mut words: HashMap<String, Vec<(usize, usize)>> = HashMap::new();
+    let mut symbols = Vec::new();
+    for (y, line) in text.lines().enumerate() {
+        let mut column = 0_usize;
+        let mut start: Option<(usize, usize)> = None;
+        for (offset, c) in line.char_indices().chain(Some((line.len(), ' '))) {
+            if c.is_alphanumeric() || c == '_' {
+                start.get_or_insert((offset, column));
+            } else if let Some((start_offset, start_column)) = start.take() {
+                let word = line.get(start_offset..offset).unwrap_or_default();
+                // Single characters are too common to be worth finding.
+                if word.chars().nth(1).is_some() {
+                    words
+                        .entry(word.to_string())
+                        .or_default()
+                        .push((y, start_column));
+                }
+            }
+            column = column.saturating_add(c.len_utf16());
+        }
+        if let Some((name, kind)) = outline::line_symbol(line, markdown) {
+            symbols.push(Symbol {
+                name,
+                kind,
+                line: y,
+            });
+        }
+    }
+    Some(FileEntry { words, symbols })
+}
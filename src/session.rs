@@ -0,0 +1,183 @@
+use crate::outline;
+use crate::preview;
+use crate::Position;
+use std::fs;
+use std::io::Error;
+use std::path::{Path, PathBuf};
+
+/// The file `:mksession` writes when given no name.
+pub const DEFAULT_FILE: &str = "Session.hecto";
+
+/// An open file and where it was being edited.
+pub struct Buffer {
+    pub file_name: String,
+    pub cursor_position: Position,
+    pub offset: Position,
+}
+
+/// A window and what it shows.
+pub struct Window {
+    /// The index in `buffers` of the buffer it shows.
+    pub buffer: usize,
+    pub height: usize,
+    pub cursor_position: Position,
+    pub offset: Position,
+}
+
+/// What `:mksession` saves so `--session` can pick up the editing where it stopped.
+pub struct Session {
+    /// The working directory, which relative file names are read against.
+    pub dir: Option<PathBuf>,
+    pub buffers: Vec<Buffer>,
+    /// The index in `buffers` of the buffer that was shown.
+    pub current: usize,
+    /// The windows, top to bottom; none in files from before windows could be split.
+    pub windows: Vec<Window>,
+    /// The index in `windows` of the window that was current.
+    pub current_window: usize,
+    /// Whether the outline panel was open.
+    pub outline: bool,
+    /// Whether the markdown preview was open.
+    pub preview: bool,
+    /// The columns the outline panel and the preview were resized to.
+    pub outline_width: usize,
+    pub preview_width: usize,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            buffers: Vec::new(),
+            current: 0,
+            windows: Vec::new(),
+            current_window: 0,
+            outline: false,
+            preview: false,
+            outline_width: outline::WIDTH,
+            preview_width: preview::WIDTH,
+        }
+    }
+}
+
+impl Session {
+    /// Reads a session file: one `key<TAB>fields` line per setting, buffer and window.
+    /// Files from before panels could be resized leave their widths at the defaults.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|error| error.to_string())?;
+        let mut session = Self::default();
+        for (number, line) in text.lines().enumerate() {
+            let invalid = || {
+                format!(
+                    "{}:{}: invalid line",
+                    path.display(),
+                    number.saturating_add(1)
+                )
+            };
+            let (key, rest) = line.split_once('\t').unwrap_or((line, ""));
+            match key {
+                _ if key.is_empty() || key.starts_with('#') => (),
+                "cd" => session.dir = Some(PathBuf::from(rest)),
+                "current" => session.current = rest.parse().map_err(|_| invalid())?,
+                "current_window" => {
+                    session.current_window = rest.parse().map_err(|_| invalid())?;
+                }
+                "outline" => session.outline = true,
+                "preview" => session.preview = true,
+                "width" => {
+                    let (panel, width) = rest.split_once('\t').ok_or_else(invalid)?;
+                    let width = width.parse().map_err(|_| invalid())?;
+                    match panel {
+                        "outline" => session.outline_width = width,
+                        "preview" => session.preview_width = width,
+                        _ => return Err(invalid()),
+                    }
+                }
+                "buffer" => {
+                    let fields: Vec<&str> = rest.splitn(5, '\t').collect();
+                    let number = |index: usize| {
+                        fields
+                            .get(index)
+                            .and_then(|field| field.parse::<usize>().ok())
+                            .ok_or_else(invalid)
+                    };
+                    let file_name = fields.get(4).filter(|name| !name.is_empty());
+                    session.buffers.push(Buffer {
+                        file_name: file_name.ok_or_else(invalid)?.to_string(),
+                        cursor_position: Position {
+                            x: number(1)?,
+                            y: number(0)?,
+                        },
+                        offset: Position {
+                            x: number(3)?,
+                            y: number(2)?,
+                        },
+                    });
+                }
+                "window" => {
+                    let fields: Vec<usize> = rest
+                        .split('\t')
+                        .map(str::parse)
+                        .collect::<Result<_, _>>()
+                        .map_err(|_| invalid())?;
+                    match fields[..] {
+                        [buffer, height, y, x, offset_y, offset_x] => {
+                            session.windows.push(Window {
+                                buffer,
+                                height,
+                                cursor_position: Position { x, y },
+                                offset: Position {
+                                    x: offset_x,
+                                    y: offset_y,
+                                },
+                            });
+                        }
+                        _ => return Err(invalid()),
+                    }
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(session)
+    }
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let mut text = String::from("# hecto session\n");
+        if let Some(dir) = &self.dir {
+            text.push_str(&format!("cd\t{}\n", dir.display()));
+        }
+        for buffer in &self.buffers {
+            text.push_str(&format!(
+                "buffer\t{}\t{}\t{}\t{}\t{}\n",
+                buffer.cursor_position.y,
+                buffer.cursor_position.x,
+                buffer.offset.y,
+                buffer.offset.x,
+                buffer.file_name
+            ));
+        }
+        text.push_str(&format!("current\t{}\n", self.current));
+        for window in &self.windows {
+            text.push_str(&format!(
+                "window\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                window.buffer,
+                window.height,
+                window.cursor_position.y,
+                window.cursor_position.x,
+                window.offset.y,
+                window.offset.x
+            ));
+        }
+        if !self.windows.is_empty() {
+            text.push_str(&format!("current_window\t{}\n", self.current_window));
+        }
+        if self.outline {
+            text.push_str("outline\n");
+        }
+        if self.preview {
+            text.push_str("preview\n");
+        }
+        text.push_str(&format!("width\toutline\t{}\n", self.outline_width));
+        text.push_str(&format!("width\tpreview\t{}\n", self.preview_width));
+        fs::write(path, text)
+    }
+}
@@ -0,0 +1,25 @@
+use std::io::Error;
+use std::process::{Command, Stdio};
+
+/// What a shell command printed and how it exited.
+pub struct Output {
+    /// Standard output followed by standard error.
+    pub text: String,
+    /// The exit code, if the command was not killed by a signal.
+    pub code: Option<i32>,
+}
+
+/// Runs `command` through `sh -c` with no input, collecting what it prints.
+pub fn run(command: &str) -> Result<Output, Error> {
+    let output = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .stdin(Stdio::null())
+        .output()?;
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    text.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(Output {
+        text,
+        code: output.status.code(),
+    })
+}
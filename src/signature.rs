@@ -0,0 +1,95 @@
+use crate::json::Value;
+
+/// The signature of the call being typed, from `textDocument/signatureHelp`.
+pub struct Signature {
+    pub label: String,
+    /// Character range of the active parameter within `label`.
+    pub active: Option<(usize, usize)>,
+    /// Which of `count` overloads this is, counted from 0.
+    pub index: usize,
+    pub count: usize,
+}
+
+impl Signature {
+    /// Reads the active signature of a `SignatureHelp` result; `None` when the cursor
+    /// is not inside a call.
+    pub fn from_lsp(result: &Value) -> Option<Self> {
+        let signatures = result.get("signatures")?.as_array()?;
+        let index = result
+            .get("activeSignature")
+            .and_then(Value::as_usize)
+            .filter(|index| *index < signatures.len())
+            .unwrap_or(0);
+        let signature = signatures.get(index)?;
+        let label = signature.get("label")?.as_str()?.to_string();
+        let active_parameter = signature
+            .get("activeParameter")
+            .or_else(|| result.get("activeParameter"))
+            .and_then(Value::as_usize)
+            .unwrap_or(0);
+        let active = signature
+            .get("parameters")
+            .and_then(Value::as_array)
+            .and_then(|parameters| parameters.get(active_parameter))
+            .and_then(|parameter| parameter.get("label"))
+            .and_then(|parameter| parameter_range(&label, parameter));
+        Some(Self {
+            label,
+            active,
+            index,
+            count: signatures.len(),
+        })
+    }
+    /// The text shown in the popup, split around the active parameter.
+    pub fn parts(&self) -> (String, String, String) {
+        let overloads = if self.count > 1 {
+            format!(" ({}/{})", self.index.saturating_add(1), self.count)
+        } else {
+            String::new()
+        };
+        let (start, end) = self.active.unwrap_or((0, 0));
+        let before: String = self.label.chars().take(start).collect();
+        let active: String = self
+            .label
+            .chars()
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect();
+        let after: String = self.label.chars().skip(end).collect();
+        (
+            format!(" {}", before),
+            active,
+            format!("{}{} ", after, overloads),
+        )
+    }
+}
+
+/// Finds a parameter label, given as a substring or as UTF-16 offsets, in `label`.
+fn parameter_range(label: &str, parameter: &Value) -> Option<(usize, usize)> {
+    if let Some(name) = parameter.as_str() {
+        // Look after the opening parenthesis so a parameter named like the function
+        // is not found in the function's name.
+        let search_from = label.find('(').unwrap_or(0);
+        let byte = label
+            .get(search_from..)?
+            .find(name)?
+            .saturating_add(search_from);
+        let start = label.get(..byte)?.chars().count();
+        return Some((start, start.saturating_add(name.chars().count())));
+    }
+    let offsets = parameter.as_array()?;
+    let char_index = |utf16: usize| {
+        let mut units = 0_usize;
+        label
+            .chars()
+            .take_while(|c| {
+                units = units.saturating_add(c.len_utf16());
+                units <= utf16
+            })
+            .count()
+    };
+    Some((
+        char_index(offsets.first()?.as_usize()?),
+        char_index(offsets.get(1)?.as_usize()?),
+    ))
+}
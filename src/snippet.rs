@@ -0,0 +1,262 @@
+use crate::paths;
+use std::fs;
+use std::path::PathBuf;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// A snippet read from a snippet file.
+pub struct Snippet {
+    pub trigger: String,
+    /// The text to insert, with `$1` and `${2:default}` marking tab stops.
+    pub body: String,
+}
+
+/// Part of one line, as grapheme columns.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub y: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A snippet body with the tab stop markup taken out.
+pub struct Expansion {
+    pub lines: Vec<String>,
+    /// The occurrences of each tab stop in the order Tab visits them, relative to the
+    /// start of the expansion. The first occurrence of a stop is where it is typed; the
+    /// rest mirror it. The last stop is `$0`, or the end of the text if there is none.
+    pub stops: Vec<Vec<Range>>,
+}
+
+/// The snippets for `file_type` from `<file type>.snippets`, and those for every file
+/// type from `all.snippets` unless the file type defines the same trigger.
+pub fn load(file_type: Option<&str>) -> Vec<Snippet> {
+    let mut snippets = Vec::new();
+    for name in file_type.into_iter().chain(["all"]) {
+        let contents = dir()
+            .and_then(|dir| fs::read_to_string(dir.join(format!("{}.snippets", name))).ok())
+            .unwrap_or_default();
+        for snippet in parse(&contents) {
+            if !snippets
+                .iter()
+                .any(|other: &Snippet| other.trigger == snippet.trigger)
+            {
+                snippets.push(snippet);
+            }
+        }
+    }
+    snippets
+}
+
+fn dir() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join("snippets"))
+}
+
+/// Reads snipMate style definitions: a `snippet <trigger> [description]` line followed
+/// by the body, each line indented by one tab. Lines starting with `#` are comments.
+fn parse(contents: &str) -> Vec<Snippet> {
+    let mut snippets: Vec<Snippet> = Vec::new();
+    let mut body: Option<Vec<&str>> = None;
+    let finish = |snippets: &mut Vec<Snippet>, body: Option<Vec<&str>>| {
+        if let (Some(mut lines), Some(snippet)) = (body, snippets.last_mut()) {
+            while lines.last().is_some_and(|line| line.is_empty()) {
+                lines.pop();
+            }
+            snippet.body = lines.join("\n");
+        }
+    };
+    for line in contents.lines() {
+        if let Some(lines) = &mut body {
+            if let Some(text) = line.strip_prefix('\t') {
+                lines.push(text);
+                continue;
+            }
+            if line.is_empty() {
+                lines.push(line);
+                continue;
+            }
+        }
+        finish(&mut snippets, body.take());
+        if let Some(definition) = line.strip_prefix("snippet ") {
+            // Anything after the trigger describes the snippet.
+            let trigger = definition.split_whitespace().next().unwrap_or_default();
+            // The newest definition of a trigger replaces the older one.
+            snippets.retain(|snippet| snippet.trigger != trigger);
+            snippets.push(Snippet {
+                trigger: trigger.to_string(),
+                body: String::new(),
+            });
+            body = Some(Vec::new());
+        }
+    }
+    finish(&mut snippets, body);
+    snippets.retain(|snippet| !snippet.trigger.is_empty());
+    snippets
+}
+
+/// Takes the tab stops out of `body`. `\$`, `\}` and `\\` stand for the plain characters.
+pub fn expand(body: &str) -> Expansion {
+    let chars: Vec<char> = body.chars().collect();
+    let mut lines = vec![String::new()];
+    let mut found: Vec<(usize, Range)> = Vec::new();
+    // Placeholders whose closing brace has not been reached: `(number, y, start)`.
+    let mut open: Vec<(usize, usize, usize)> = Vec::new();
+    let mut index = 0;
+    while let Some(c) = chars.get(index).copied() {
+        index = index.saturating_add(1);
+        let y = lines.len().saturating_sub(1);
+        let x = lines.last().map_or(0, |line| width(line));
+        match c {
+            '\\' if matches!(chars.get(index), Some('$' | '}' | '\\')) => {
+                push(&mut lines, chars.get(index).copied().unwrap_or_default());
+                index = index.saturating_add(1);
+            }
+            '\n' => lines.push(String::new()),
+            '}' if !open.is_empty() => {
+                if let Some((number, start_y, start)) = open.pop() {
+                    let end = if start_y == y {
+                        x
+                    } else {
+                        lines.get(start_y).map_or(start, |line| width(line))
+                    };
+                    found.push((
+                        number,
+                        Range {
+                            y: start_y,
+                            start,
+                            end,
+                        },
+                    ));
+                }
+            }
+            '$' => {
+                if let Some((number, next)) = number(&chars, index) {
+                    found.push((
+                        number,
+                        Range {
+                            y,
+                            start: x,
+                            end: x,
+                        },
+                    ));
+                    index = next;
+                } else if chars.get(index) == Some(&'{') {
+                    match number(&chars, index.saturating_add(1)) {
+                        Some((number, next)) if chars.get(next) == Some(&':') => {
+                            open.push((number, y, x));
+                            index = next.saturating_add(1);
+                        }
+                        Some((number, next)) if chars.get(next) == Some(&'}') => {
+                            found.push((
+                                number,
+                                Range {
+                                    y,
+                                    start: x,
+                                    end: x,
+                                },
+                            ));
+                            index = next.saturating_add(1);
+                        }
+                        _ => push(&mut lines, c),
+                    }
+                } else {
+                    push(&mut lines, c);
+                }
+            }
+            c => push(&mut lines, c),
+        }
+    }
+    let mut numbers: Vec<usize> = found.iter().map(|(number, _)| *number).collect();
+    numbers.sort_unstable_by_key(|number| (*number == 0, *number));
+    numbers.dedup();
+    let mut stops: Vec<Vec<Range>> = numbers
+        .iter()
+        .map(|number| {
+            let mut ranges: Vec<Range> = found
+                .iter()
+                .filter(|(other, _)| other == number)
+                .map(|(_, range)| *range)
+                .collect();
+            ranges.sort_unstable_by_key(|range| (range.y, range.start));
+            // The occurrence holding the default text is the one typed in.
+            if let Some(primary) = ranges.iter().position(|range| range.end > range.start) {
+                let range = ranges.remove(primary);
+                ranges.insert(0, range);
+            }
+            ranges
+        })
+        .collect();
+    if numbers.last() != Some(&0) {
+        let y = lines.len().saturating_sub(1);
+        let x = lines.last().map_or(0, |line| width(line));
+        stops.push(vec![Range {
+            y,
+            start: x,
+            end: x,
+        }]);
+    }
+    Expansion { lines, stops }
+}
+
+fn push(lines: &mut [String], c: char) {
+    if let Some(line) = lines.last_mut() {
+        line.push(c);
+    }
+}
+
+fn width(line: &str) -> usize {
+    line.graphemes(true).count()
+}
+
+/// The number starting at `index` and the index after it.
+fn number(chars: &[char], index: usize) -> Option<(usize, usize)> {
+    let digits: String = chars
+        .iter()
+        .skip(index)
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    let number = digits.parse().ok()?;
+    Some((number, index.saturating_add(digits.len())))
+}
+
+/// An expanded snippet whose stops are being filled in.
+pub struct Session {
+    /// Buffer ranges of each stop, as in [`Expansion::stops`].
+    pub stops: Vec<Vec<Range>>,
+    pub current: usize,
+    /// Whether the current stop still holds its default text, which typing replaces.
+    pub pristine: bool,
+    /// The length of the current stop's line and the buffer's line count when last
+    /// seen, to tell how much the stop grew since.
+    pub line_len: usize,
+    pub lines: usize,
+}
+
+impl Session {
+    /// Where the current stop is typed.
+    pub fn primary(&self) -> Option<Range> {
+        self.stops
+            .get(self.current)
+            .and_then(|ranges| ranges.first())
+            .copied()
+    }
+    /// Moves the end of occurrence `index` of `stop` by `delta`, along with everything
+    /// after it on the line and the end of any placeholder around it.
+    pub fn resize(&mut self, stop: usize, index: usize, delta: isize) {
+        let range = match self.stops.get(stop).and_then(|ranges| ranges.get(index)) {
+            Some(range) => *range,
+            None => return,
+        };
+        for (other_stop, ranges) in self.stops.iter_mut().enumerate() {
+            for (other_index, other) in ranges.iter_mut().enumerate() {
+                if (other_stop, other_index) == (stop, index) {
+                    other.end = other.end.saturating_add_signed(delta);
+                } else if other.y == range.y && other.end >= range.end {
+                    if other.start >= range.end {
+                        other.start = other.start.saturating_add_signed(delta);
+                    }
+                    other.end = other.end.saturating_add_signed(delta);
+                }
+            }
+        }
+    }
+}
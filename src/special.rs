@@ -0,0 +1,68 @@
+use crate::Key;
+
+/// What a buffer that is not a file holds, which decides how it behaves.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Special {
+    /// Notes that are never asked to be saved.
+    Scratch,
+    Help,
+    /// What a shell command or job printed, or the log, paged with line numbers.
+    Output,
+    /// The quickfix list, such as search results or diagnostics, one entry a line.
+    Quickfix,
+    /// The preview of a replace across the project's files, as a unified diff.
+    Replace,
+    /// The lessons of `--tutor`, edited as they ask and never saved.
+    Tutor,
+}
+
+/// What a key does in a special buffer in place of its usual meaning.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Go to what the cursor line points at: a help link, a quickfix entry or a change.
+    Follow,
+    Close,
+    /// Paging keys of output and help buffers, as in `less`.
+    Search,
+    NextMatch,
+    PreviousMatch,
+    PageDown,
+    PageUp,
+    Top,
+    /// Go to the last line and keep to it as job output arrives.
+    Bottom,
+    /// Turn keeping to the last line as job output arrives on or off.
+    Tail,
+    /// Select or deselect the change under the cursor of a replace preview.
+    Toggle,
+    /// Make the selected changes of a replace preview.
+    Apply,
+}
+
+impl Special {
+    pub fn is_read_only(self) -> bool {
+        !matches!(self, Self::Scratch | Self::Tutor)
+    }
+    /// The buffer's own key bindings, which come before the usual ones.
+    pub fn action_for(self, key: Key) -> Option<Action> {
+        match (self, key) {
+            (Self::Scratch | Self::Tutor, _) => None,
+            (Self::Help | Self::Quickfix | Self::Replace, Key::Char('\n')) => Some(Action::Follow),
+            (Self::Replace, Key::Char(' ')) => Some(Action::Toggle),
+            (Self::Replace, Key::Char('a')) => Some(Action::Apply),
+            (_, Key::Char('q')) => Some(Action::Close),
+            (Self::Output | Self::Help, Key::Char(c)) => match c {
+                '/' => Some(Action::Search),
+                'n' => Some(Action::NextMatch),
+                'N' => Some(Action::PreviousMatch),
+                ' ' => Some(Action::PageDown),
+                'b' => Some(Action::PageUp),
+                'g' => Some(Action::Top),
+                'G' => Some(Action::Bottom),
+                'F' => Some(Action::Tail),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
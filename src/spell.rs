@@ -0,0 +1,479 @@
+use crate::filetype;
+use crate::paths;
+use crate::Row;
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::{Error, Write};
+use std::path::PathBuf;
+
+/// Directories searched for `<lang>.dic` and `<lang>.aff`, after the user's own.
+const SYSTEM_DIRS: &[&str] = &[
+    "/usr/share/hunspell",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/hunspell",
+];
+
+/// Letters accepted when guessing corrections.
+const ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz'";
+
+/// Corrections offered at most, so each can be picked with a digit.
+const MAX_SUGGESTIONS: usize = 9;
+
+/// How `.aff` flags are written after a word's `/`.
+#[derive(Clone, Copy)]
+enum FlagType {
+    Char,
+    /// Two characters per flag (`FLAG long`).
+    Long,
+    /// Comma-separated numbers (`FLAG num`).
+    Number,
+}
+
+/// One character of an affix condition.
+enum Pattern {
+    Any,
+    Char(char),
+    /// A `[...]` or `[^...]` class; the flag says whether it is negated.
+    Class(Vec<char>, bool),
+}
+
+impl Pattern {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Char(expected) => c == *expected,
+            Self::Class(chars, negated) => chars.contains(&c) != *negated,
+        }
+    }
+}
+
+/// A `PFX` or `SFX` rule: words carrying `flag` may swap `strip` for `add` when the
+/// stem matches `condition`.
+struct Affix {
+    flag: String,
+    strip: String,
+    add: String,
+    condition: Vec<Pattern>,
+}
+
+/// A hunspell dictionary plus the user's own word list.
+pub struct Dictionary {
+    pub lang: String,
+    /// Words and their affix flags.
+    words: HashMap<String, Vec<String>>,
+    prefixes: Vec<Affix>,
+    suffixes: Vec<Affix>,
+    user: HashSet<String>,
+}
+
+impl Dictionary {
+    /// Loads `<lang>.dic` with its optional `<lang>.aff` from the user's spell
+    /// directory or the usual system places.
+    pub fn load(lang: &str) -> Option<Self> {
+        let dir = dirs()
+            .into_iter()
+            .find(|dir| dir.join(format!("{}.dic", lang)).is_file())?;
+        let dic = fs::read(dir.join(format!("{}.dic", lang))).ok()?;
+        let aff = fs::read(dir.join(format!("{}.aff", lang))).unwrap_or_default();
+        let mut dictionary = Self {
+            lang: lang.to_string(),
+            words: HashMap::new(),
+            prefixes: Vec::new(),
+            suffixes: Vec::new(),
+            user: HashSet::new(),
+        };
+        let flag_type = dictionary.read_aff(&String::from_utf8_lossy(&aff));
+        // The first line only estimates the word count.
+        for line in String::from_utf8_lossy(&dic).lines().skip(1) {
+            let entry = line.split_whitespace().next().unwrap_or_default();
+            let (word, flags) = entry.split_once('/').unwrap_or((entry, ""));
+            if !word.is_empty() {
+                dictionary
+                    .words
+                    .insert(word.to_string(), split_flags(flags, flag_type));
+            }
+        }
+        if let Some(path) = user_path() {
+            if let Ok(contents) = fs::read_to_string(path) {
+                dictionary.user = contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|word| !word.is_empty())
+                    .map(str::to_string)
+                    .collect();
+            }
+        }
+        Some(dictionary)
+    }
+    fn read_aff(&mut self, aff: &str) -> FlagType {
+        let mut flag_type = FlagType::Char;
+        for line in aff.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields.as_slice() {
+                ["FLAG", "long", ..] => flag_type = FlagType::Long,
+                ["FLAG", "num", ..] => flag_type = FlagType::Number,
+                // Rule headers (`SFX flag cross_product count`) are one field short.
+                [kind @ ("PFX" | "SFX"), flag, strip, add, condition, ..] => {
+                    let add = add.split('/').next().unwrap_or_default();
+                    let affix = Affix {
+                        flag: (*flag).to_string(),
+                        strip: if *strip == "0" { "" } else { strip }.to_string(),
+                        add: if add == "0" { "" } else { add }.to_string(),
+                        condition: parse_condition(condition),
+                    };
+                    if *kind == "PFX" {
+                        self.prefixes.push(affix);
+                    } else {
+                        self.suffixes.push(affix);
+                    }
+                }
+                _ => (),
+            }
+        }
+        flag_type
+    }
+    /// Whether `word` is spelled correctly, allowing a capital first letter.
+    pub fn check(&self, word: &str) -> bool {
+        if self.check_exact(word) {
+            return true;
+        }
+        let lower = word.to_lowercase();
+        lower != word && self.check_exact(&lower)
+    }
+    fn check_exact(&self, word: &str) -> bool {
+        if self.words.contains_key(word) || self.user.contains(word) {
+            return true;
+        }
+        let has_flag = |stem: &str, flag: &str| {
+            self.words
+                .get(stem)
+                .is_some_and(|flags| flags.iter().any(|candidate| candidate == flag))
+        };
+        self.suffixes.iter().any(|affix| {
+            word.strip_suffix(affix.add.as_str()).is_some_and(|rest| {
+                let stem = format!("{}{}", rest, affix.strip);
+                let tail: Vec<char> = stem.chars().rev().take(affix.condition.len()).collect();
+                tail.len() == affix.condition.len()
+                    && affix
+                        .condition
+                        .iter()
+                        .rev()
+                        .zip(&tail)
+                        .all(|(pattern, c)| pattern.matches(*c))
+                    && has_flag(&stem, &affix.flag)
+            })
+        }) || self.prefixes.iter().any(|affix| {
+            word.strip_prefix(affix.add.as_str()).is_some_and(|rest| {
+                let stem = format!("{}{}", affix.strip, rest);
+                let head: Vec<char> = stem.chars().take(affix.condition.len()).collect();
+                head.len() == affix.condition.len()
+                    && affix
+                        .condition
+                        .iter()
+                        .zip(&head)
+                        .all(|(pattern, c)| pattern.matches(*c))
+                    && has_flag(&stem, &affix.flag)
+            })
+        })
+    }
+    /// Correctly spelled words one edit away from `word`, keeping its capitalization.
+    pub fn suggest(&self, word: &str) -> Vec<String> {
+        let lower: Vec<char> = word.to_lowercase().chars().collect();
+        let mut candidates = Vec::new();
+        let text = |from: usize, to: usize| -> String {
+            lower.get(from..to).unwrap_or_default().iter().collect()
+        };
+        let len = lower.len();
+        for index in 0..=len {
+            let before = text(0, index);
+            let after = text(index, len);
+            if let Some(first) = lower.get(index) {
+                let rest = text(index.saturating_add(1), len);
+                if let Some(second) = lower.get(index.saturating_add(1)) {
+                    let rest = text(index.saturating_add(2), len);
+                    candidates.push(format!("{}{}{}{}", before, second, first, rest));
+                }
+                for c in ALPHABET.chars().filter(|c| c != first) {
+                    candidates.push(format!("{}{}{}", before, c, rest));
+                }
+                candidates.push(format!("{}{}", before, rest));
+            }
+            for c in ALPHABET.chars() {
+                candidates.push(format!("{}{}{}", before, c, after));
+            }
+            if !before.is_empty() && !after.is_empty() {
+                candidates.push(format!("{} {}", before, after));
+            }
+        }
+        let capitalized = word.chars().next().is_some_and(char::is_uppercase);
+        let mut suggestions: Vec<String> = Vec::new();
+        for candidate in candidates {
+            if suggestions.len() >= MAX_SUGGESTIONS {
+                break;
+            }
+            if candidate.chars().eq(lower.iter().copied())
+                || !candidate.split(' ').all(|part| self.check(part))
+            {
+                continue;
+            }
+            let candidate = if capitalized {
+                capitalize(&candidate)
+            } else {
+                candidate
+            };
+            if !suggestions.contains(&candidate) {
+                suggestions.push(candidate);
+            }
+        }
+        suggestions
+    }
+    /// Accepts `word` from now on and appends it to the user's word list.
+    pub fn add_user_word(&mut self, word: &str) -> Result<(), Error> {
+        self.user.insert(word.to_string());
+        let path = user_path().ok_or_else(|| {
+            Error::new(
+                std::io::ErrorKind::NotFound,
+                "no config directory for the word list",
+            )
+        })?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", word)
+    }
+}
+
+/// The popup offering corrections for a misspelled word.
+pub struct Menu {
+    /// Line and grapheme range of the word.
+    pub y: usize,
+    pub start: usize,
+    pub end: usize,
+    suggestions: Vec<String>,
+    /// Shown beside each suggestion, such as a synonym's part of speech.
+    notes: Vec<String>,
+    selected: usize,
+}
+
+impl Menu {
+    pub fn new(y: usize, start: usize, end: usize, suggestions: Vec<String>) -> Self {
+        Self {
+            y,
+            start,
+            end,
+            suggestions,
+            notes: Vec::new(),
+            selected: 0,
+        }
+    }
+    pub fn with_notes(mut self, notes: Vec<String>) -> Self {
+        self.notes = notes;
+        self
+    }
+    pub fn select_next(&mut self, backward: bool) {
+        let last = self.suggestions.len().saturating_sub(1);
+        self.selected = if backward {
+            self.selected.checked_sub(1).unwrap_or(last)
+        } else if self.selected >= last {
+            0
+        } else {
+            self.selected.saturating_add(1)
+        };
+    }
+    /// The selected suggestion, or suggestion `index` when given.
+    pub fn take(self, index: Option<usize>) -> Option<String> {
+        let index = index.unwrap_or(self.selected);
+        self.suggestions.into_iter().nth(index)
+    }
+    /// Numbered `(line, is_selected)` lines, padded to equal width.
+    pub fn lines(&self) -> Vec<(String, bool)> {
+        let word_width = self
+            .suggestions
+            .iter()
+            .map(|word| word.chars().count())
+            .max()
+            .unwrap_or(0);
+        let lines: Vec<String> = self
+            .suggestions
+            .iter()
+            .enumerate()
+            .map(
+                |(index, word)| match self.notes.get(index).filter(|note| !note.is_empty()) {
+                    Some(note) => format!(
+                        " {}. {:<width$}  {}",
+                        index.saturating_add(1),
+                        word,
+                        note,
+                        width = word_width
+                    ),
+                    None => format!(" {}. {}", index.saturating_add(1), word),
+                },
+            )
+            .collect();
+        let width = lines
+            .iter()
+            .map(|line| line.chars().count())
+            .max()
+            .unwrap_or(0);
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(index, line)| {
+                (
+                    format!("{:<width$} ", line, width = width),
+                    index == self.selected,
+                )
+            })
+            .collect()
+    }
+}
+
+/// The grapheme ranges of misspelled words in `row`. Prose file types are checked
+/// throughout; in code only the text after the `comment` leader and inside string
+/// literals is, and identifier-like words are skipped.
+pub fn misspellings(
+    dictionary: &Dictionary,
+    row: &Row,
+    file_type: Option<&str>,
+    comment: Option<&str>,
+) -> Vec<(usize, usize)> {
+    let prose = filetype::is_prose(file_type);
+    let graphemes: Vec<&str> = row.graphemes().collect();
+    let checked = if prose {
+        vec![true; graphemes.len()]
+    } else {
+        code_text(&graphemes, comment)
+    };
+    let mut misspelled = Vec::new();
+    let mut index = 0;
+    while index < graphemes.len() {
+        let is_letter = |grapheme: &&str| grapheme.chars().all(char::is_alphabetic);
+        if !graphemes.get(index).is_some_and(is_letter) {
+            index = index.saturating_add(1);
+            continue;
+        }
+        let start = index;
+        let mut end = start;
+        while let Some(grapheme) = graphemes.get(end) {
+            let apostrophe =
+                *grapheme == "'" && graphemes.get(end.saturating_add(1)).is_some_and(is_letter);
+            if !is_letter(grapheme) && !apostrophe {
+                break;
+            }
+            end = end.saturating_add(1);
+        }
+        index = end;
+        // Words glued to digits or underscores are names, not prose.
+        let glued = |grapheme: Option<&&str>| {
+            grapheme
+                .is_some_and(|grapheme| grapheme.chars().all(|c| c.is_alphanumeric() || c == '_'))
+        };
+        if glued(
+            start
+                .checked_sub(1)
+                .and_then(|before| graphemes.get(before)),
+        ) || glued(graphemes.get(end))
+            || !checked.get(start).copied().unwrap_or(false)
+        {
+            continue;
+        }
+        let word: String = graphemes.get(start..end).unwrap_or_default().concat();
+        // Acronyms and camelCase names are left alone.
+        if word.chars().skip(1).any(char::is_uppercase) || word.chars().count() < 2 {
+            continue;
+        }
+        if !dictionary.check(&word) {
+            misspelled.push((start, end));
+        }
+    }
+    misspelled
+}
+
+/// Marks the graphemes of a code line that hold prose: string literal contents and
+/// everything after a line comment leader outside of strings.
+fn code_text(graphemes: &[&str], comment: Option<&str>) -> Vec<bool> {
+    let mut checked = vec![false; graphemes.len()];
+    let mut quote: Option<&str> = None;
+    let mut index = 0;
+    while index < graphemes.len() {
+        let grapheme = graphemes.get(index).copied().unwrap_or_default();
+        match quote {
+            Some(open) if grapheme == open => quote = None,
+            Some(_) if grapheme == "\\" => index = index.saturating_add(1),
+            Some(_) => {
+                if let Some(slot) = checked.get_mut(index) {
+                    *slot = true;
+                }
+            }
+            None if grapheme == "\"" => quote = Some(grapheme),
+            None => {
+                let rest = graphemes.get(index..).unwrap_or_default().concat();
+                if comment.is_some_and(|comment| rest.starts_with(comment)) {
+                    for slot in checked.iter_mut().skip(index) {
+                        *slot = true;
+                    }
+                    break;
+                }
+            }
+        }
+        index = index.saturating_add(1);
+    }
+    checked
+}
+
+fn dirs() -> Vec<PathBuf> {
+    paths::config_dir()
+        .map(|dir| dir.join("spell"))
+        .into_iter()
+        .chain(SYSTEM_DIRS.iter().map(PathBuf::from))
+        .collect()
+}
+
+/// The user's word list, one word per line.
+fn user_path() -> Option<PathBuf> {
+    paths::config_dir().map(|dir| dir.join("spell").join("user.dic"))
+}
+
+fn split_flags(flags: &str, flag_type: FlagType) -> Vec<String> {
+    match flag_type {
+        FlagType::Char => flags.chars().map(String::from).collect(),
+        FlagType::Long => {
+            let chars: Vec<char> = flags.chars().collect();
+            chars.chunks(2).map(|pair| pair.iter().collect()).collect()
+        }
+        FlagType::Number => flags.split(',').map(str::to_string).collect(),
+    }
+}
+
+/// Reads an affix condition such as `[^aeiou]y`; `.` matches anything.
+fn parse_condition(condition: &str) -> Vec<Pattern> {
+    if condition == "." {
+        return Vec::new();
+    }
+    let mut patterns = Vec::new();
+    let mut chars = condition.chars();
+    while let Some(c) = chars.next() {
+        patterns.push(match c {
+            '.' => Pattern::Any,
+            '[' => {
+                let mut class: Vec<char> = chars.by_ref().take_while(|c| *c != ']').collect();
+                let negated = class.first() == Some(&'^');
+                if negated {
+                    class.remove(0);
+                }
+                Pattern::Class(class, negated)
+            }
+            c => Pattern::Char(c),
+        });
+    }
+    patterns
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
@@ -0,0 +1,54 @@
+/// What `wc` would report for a piece of text.
+#[derive(Default)]
+pub struct Stats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub bytes: usize,
+}
+
+impl Stats {
+    /// Counts `lines`, each followed by `line_ending` unless it is the last one and
+    /// `ends_with_newline` is false. Words are runs of non-whitespace.
+    pub fn of<'a>(
+        lines: impl Iterator<Item = &'a str>,
+        line_ending: &str,
+        ends_with_newline: bool,
+    ) -> Self {
+        let mut stats = Self::default();
+        let mut lines = lines.peekable();
+        while let Some(line) = lines.next() {
+            stats.lines = stats.lines.saturating_add(1);
+            stats.words = stats.words.saturating_add(line.split_whitespace().count());
+            stats.chars = stats.chars.saturating_add(line.chars().count());
+            stats.bytes = stats.bytes.saturating_add(line.len());
+            if lines.peek().is_some() || ends_with_newline {
+                stats.chars = stats.chars.saturating_add(line_ending.chars().count());
+                stats.bytes = stats.bytes.saturating_add(line_ending.len());
+            }
+        }
+        stats
+    }
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {}, {} {}, {} {}, {} {}",
+            self.lines,
+            plural(self.lines, "line"),
+            self.words,
+            plural(self.words, "word"),
+            self.chars,
+            plural(self.chars, "character"),
+            self.bytes,
+            plural(self.bytes, "byte"),
+        )
+    }
+}
+
+/// `noun`, in the plural unless `count` is one.
+pub fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        noun.to_string()
+    } else {
+        format!("{}s", noun)
+    }
+}
@@ -0,0 +1,152 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A piece of information the status bar can show.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Segment {
+    Mode,
+    Recording,
+    Buffers,
+    File,
+    Lines,
+    /// Where the cursor is among the matches of the last search.
+    Search,
+    Breadcrumb,
+    Words,
+    Git,
+    Diagnostics,
+    /// What plugins show.
+    Plugins,
+    Position,
+    Encoding,
+    LineEnding,
+    FileType,
+    Clock,
+}
+
+/// Every segment with the name the config file uses for it.
+pub const SEGMENTS: [(Segment, &str); 16] = [
+    (Segment::Mode, "mode"),
+    (Segment::Recording, "recording"),
+    (Segment::Buffers, "buffers"),
+    (Segment::File, "file"),
+    (Segment::Lines, "lines"),
+    (Segment::Search, "search"),
+    (Segment::Breadcrumb, "breadcrumb"),
+    (Segment::Words, "words"),
+    (Segment::Git, "git"),
+    (Segment::Diagnostics, "diagnostics"),
+    (Segment::Plugins, "plugins"),
+    (Segment::Position, "position"),
+    (Segment::Encoding, "encoding"),
+    (Segment::LineEnding, "line_ending"),
+    (Segment::FileType, "filetype"),
+    (Segment::Clock, "clock"),
+];
+
+/// Goes between the segments of a group that have something to show.
+pub const SEPARATOR: &str = " | ";
+
+impl Segment {
+    pub fn from_name(name: &str) -> Option<Self> {
+        SEGMENTS
+            .iter()
+            .find(|(_, segment_name)| *segment_name == name)
+            .map(|(segment, _)| *segment)
+    }
+    /// The position of the segment in `SEGMENTS`, for per-segment settings.
+    pub fn index(self) -> usize {
+        SEGMENTS
+            .iter()
+            .position(|(segment, _)| *segment == self)
+            .unwrap_or(0)
+    }
+}
+
+/// Which segments the status bar shows, flush left and flush right.
+#[derive(Clone)]
+pub struct Layout {
+    pub left: Vec<Segment>,
+    pub right: Vec<Segment>,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            left: vec![
+                Segment::Mode,
+                Segment::Recording,
+                Segment::Buffers,
+                Segment::File,
+                Segment::Lines,
+                Segment::Breadcrumb,
+            ],
+            right: vec![
+                Segment::Search,
+                Segment::Words,
+                Segment::Git,
+                Segment::Diagnostics,
+                Segment::Plugins,
+                Segment::Encoding,
+                Segment::LineEnding,
+                Segment::Position,
+            ],
+        }
+    }
+}
+
+impl Layout {
+    pub fn contains(&self, segment: Segment) -> bool {
+        self.left.contains(&segment) || self.right.contains(&segment)
+    }
+}
+
+/// Reads a list of segment names.
+pub fn parse(names: &[&str]) -> Result<Vec<Segment>, String> {
+    names
+        .iter()
+        .map(|name| {
+            Segment::from_name(name).ok_or_else(|| format!("unknown status bar segment `{}`", name))
+        })
+        .collect()
+}
+
+/// The local time as `HH:MM`.
+pub fn clock() -> String {
+    let (hours, minutes, _) = local_time(SystemTime::now());
+    format!("{:02}:{:02}", hours, minutes)
+}
+
+/// `time` in local time as `HH:MM:SS`.
+pub fn time_of_day(time: SystemTime) -> String {
+    let (hours, minutes, seconds) = local_time(time);
+    format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+}
+
+/// How long until the clock shows the next minute.
+pub fn until_next_minute() -> std::time::Duration {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    std::time::Duration::from_secs(60_u64.saturating_sub(seconds % 60))
+}
+
+fn local_time(time: SystemTime) -> (i32, i32, i32) {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|elapsed| libc::time_t::try_from(elapsed.as_secs()).ok())
+        .unwrap_or(0);
+    local_tm(seconds).map_or((0, 0, 0), |tm| (tm.tm_hour, tm.tm_min, tm.tm_sec))
+}
+
+/// The Unix time `seconds` broken down in the local time zone, or `None` if it is out
+/// of range.
+pub fn local_tm(seconds: libc::time_t) -> Option<libc::tm> {
+    // SAFETY: `tm` is plain data that `localtime_r` fills in, and both pointers are
+    // valid for the call.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&seconds, &mut tm) }.is_null() {
+        return None;
+    }
+    Some(tm)
+}
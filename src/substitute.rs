@@ -0,0 +1,145 @@
+use crate::search::Case;
+
+/// The lines a `:s` command works on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Range {
+    /// The selected lines, or the cursor line outside visual mode.
+    Selection,
+    All,
+    /// First and last line, 0-based.
+    Lines(usize, usize),
+}
+
+/// A `:[range]s/pattern/replacement/[flags]` command. Any punctuation may stand in
+/// for `/`, and a backslash before it keeps it in the pattern or replacement.
+#[derive(Debug)]
+pub struct Substitute {
+    pub range: Range,
+    /// A regular expression.
+    pub pattern: String,
+    /// Inserted for each match; `&` stands for the match itself and `\&` for a `&`.
+    pub replacement: String,
+    /// Replace every match on a line rather than the first (`g`).
+    pub global: bool,
+    /// `i` ignores case and `I` matches it, overriding `search_case`.
+    pub case: Option<Case>,
+}
+
+/// Reads `command` as a substitution, or `None` when it is some other command.
+/// `current` and `last` are the cursor line and the last line, for `.` and `$`.
+///
+/// # Errors
+///
+/// Fails with a message when the command is a substitution that cannot be read.
+pub fn parse(command: &str, current: usize, last: usize) -> Option<Result<Substitute, String>> {
+    let start = command.find('s')?;
+    let (range, rest) = (
+        command.get(..start)?,
+        command.get(start.saturating_add(1)..)?,
+    );
+    let delimiter = rest.chars().next()?;
+    if delimiter.is_alphanumeric() || delimiter.is_whitespace() || delimiter == '\\' {
+        return None;
+    }
+    if !range
+        .chars()
+        .all(|c| c.is_ascii_digit() || ",.$% ".contains(c))
+    {
+        return None;
+    }
+    let range = match parse_range(range.trim(), current, last) {
+        Some(range) => range,
+        None => return Some(Err(format!("invalid range: {}", range))),
+    };
+    let mut parts = split(rest.get(delimiter.len_utf8()..)?, delimiter).into_iter();
+    let pattern = parts.next().unwrap_or_default();
+    let replacement = parts.next().unwrap_or_default();
+    let flags = parts.next().unwrap_or_default();
+    if pattern.is_empty() {
+        return Some(Err("empty pattern".to_string()));
+    }
+    let mut substitute = Substitute {
+        range,
+        pattern,
+        replacement,
+        global: false,
+        case: None,
+    };
+    for flag in flags.chars() {
+        match flag {
+            'g' => substitute.global = true,
+            'i' => substitute.case = Some(Case::Insensitive),
+            'I' => substitute.case = Some(Case::Sensitive),
+            _ => return Some(Err(format!("unknown flag: {}", flag))),
+        }
+    }
+    Some(Ok(substitute))
+}
+
+/// `%`, a line, or two lines separated by a comma, each a number, `.` or `$`.
+fn parse_range(range: &str, current: usize, last: usize) -> Option<Range> {
+    let line = |text: &str| match text.trim() {
+        "." => Some(current),
+        "$" => Some(last),
+        number => number
+            .parse::<usize>()
+            .ok()
+            .map(|number| number.saturating_sub(1).min(last)),
+    };
+    match range {
+        "" => Some(Range::Selection),
+        "%" => Some(Range::All),
+        _ => {
+            let (first, second) = range.split_once(',').unwrap_or((range, range));
+            let (first, second) = (line(first)?, line(second)?);
+            Some(Range::Lines(first.min(second), first.max(second)))
+        }
+    }
+}
+
+/// `text` split at each `delimiter` not escaped by a backslash, which is dropped
+/// from before the delimiter and kept everywhere else.
+fn split(text: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let part = parts.last_mut();
+        match (c, part) {
+            ('\\', Some(part)) if chars.peek() == Some(&delimiter) => {
+                part.push(delimiter);
+                chars.next();
+            }
+            ('\\', Some(part)) => {
+                part.push('\\');
+                if let Some(next) = chars.next() {
+                    part.push(next);
+                }
+            }
+            (_, _) if c == delimiter => parts.push(String::new()),
+            (_, Some(part)) => part.push(c),
+            (_, None) => (),
+        }
+    }
+    parts
+}
+
+/// `line` with the matches at byte ranges `matches` replaced by `replacement`.
+pub fn replace(line: &str, matches: &[(usize, usize)], replacement: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut end = 0;
+    for (start, match_end) in matches {
+        result.push_str(line.get(end..*start).unwrap_or_default());
+        let found = line.get(*start..*match_end).unwrap_or_default();
+        let mut chars = replacement.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '&' => result.push_str(found),
+                '\\' => result.extend(chars.next()),
+                _ => result.push(c),
+            }
+        }
+        end = *match_end;
+    }
+    result.push_str(line.get(end..).unwrap_or_default());
+    result
+}
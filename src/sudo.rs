@@ -0,0 +1,65 @@
+use std::io::{Error, ErrorKind, Write};
+use std::process::{Command, Stdio};
+
+/// Whether sudo will run a command without asking for a password, because the user
+/// needs none or entered it recently.
+pub fn is_authenticated() -> bool {
+    Command::new("sudo")
+        .args(["-n", "true"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+/// Gives sudo the user's password, so that the commands after it run without one.
+///
+/// # Errors
+///
+/// Fails with sudo's message when the password is wrong or sudo cannot be run.
+pub fn authenticate(password: &str) -> Result<(), Error> {
+    let mut child = Command::new("sudo")
+        .args(["-S", "-v", "-p", ""])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(password.as_bytes())?;
+        stdin.write_all(b"\n")?;
+    }
+    check(child.wait_with_output()?)
+}
+
+/// Writes `contents` to `file_name` as root with `sudo tee`, which keeps the file's
+/// owner and permissions. Run after `authenticate`: it never asks for a password.
+///
+/// # Errors
+///
+/// Fails with sudo's or tee's message.
+pub fn write(file_name: &str, contents: &[u8]) -> Result<(), Error> {
+    let mut child = Command::new("sudo")
+        .args(["-n", "tee", "--", file_name])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(contents)?;
+    }
+    check(child.wait_with_output()?)
+}
+
+fn check(output: std::process::Output) -> Result<(), Error> {
+    if output.status.success() {
+        return Ok(());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let message = stderr
+        .lines()
+        .map(str::trim)
+        .rfind(|line| !line.is_empty())
+        .unwrap_or("sudo failed");
+    Err(Error::new(ErrorKind::PermissionDenied, message.to_string()))
+}
@@ -0,0 +1,21 @@
+/// Returns the opening and closing strings inserted when surrounding with `c`.
+pub fn delimiters(c: char) -> Option<(String, String)> {
+    match c {
+        '(' | ')' | 'b' => Some(("(".to_string(), ")".to_string())),
+        '[' | ']' => Some(("[".to_string(), "]".to_string())),
+        '{' | '}' | 'B' => Some(("{".to_string(), "}".to_string())),
+        '<' | '>' => Some(("<".to_string(), ">".to_string())),
+        '"' | '\'' | '`' | '*' | '_' => Some((c.to_string(), c.to_string())),
+        _ => None,
+    }
+}
+
+/// Builds the opening and closing tags for a tag such as `div class="x"`.
+pub fn tag_delimiters(tag: &str) -> Option<(String, String)> {
+    let tag = tag.trim().trim_start_matches('<').trim_end_matches('>');
+    let name: String = tag.chars().take_while(|c| !c.is_whitespace()).collect();
+    if name.is_empty() {
+        return None;
+    }
+    Some((format!("<{}>", tag), format!("</{}>", name)))
+}
@@ -0,0 +1,31 @@
+use crate::paths;
+use std::fs;
+use std::io::Error;
+use std::path::PathBuf;
+
+fn swap_path(file_name: &str) -> Option<PathBuf> {
+    paths::cache_dir().map(|dir| {
+        dir.join("swap")
+            .join(format!("{}.swp", paths::encode(file_name)))
+    })
+}
+
+pub fn write(file_name: &str, contents: &[u8]) -> Result<(), Error> {
+    if let Some(path) = swap_path(file_name) {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, contents)?;
+    }
+    Ok(())
+}
+
+pub fn read(file_name: &str) -> Option<String> {
+    swap_path(file_name).and_then(|path| fs::read_to_string(path).ok())
+}
+
+pub fn remove(file_name: &str) {
+    if let Some(path) = swap_path(file_name) {
+        let _ = fs::remove_file(path);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::theme;
+use termion::color::Rgb;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The color literals in `line`, `#rgb`, `#rrggbb` and `rgb(r, g, b)`, as grapheme
+/// ranges with the color each one names.
+pub fn find(line: &str) -> Vec<(usize, usize, Rgb)> {
+    let mut swatches = Vec::new();
+    let mut offset = 0;
+    while let Some(found) = line.get(offset..).and_then(|rest| rest.find(['#', 'r'])) {
+        let start = offset.saturating_add(found);
+        let rest = line.get(start..).unwrap_or_default();
+        let after_word = line
+            .get(..start)
+            .and_then(|before| before.chars().next_back())
+            .is_some_and(|c| c.is_alphanumeric() || c == '_');
+        let literal = if rest.starts_with('#') {
+            hex(rest)
+        } else if after_word {
+            None
+        } else {
+            rgb(rest)
+        };
+        if let Some((length, color)) = literal {
+            let end = start.saturating_add(length);
+            swatches.push((
+                grapheme_index(line, start),
+                grapheme_index(line, end),
+                color,
+            ));
+            offset = end;
+        } else {
+            offset = start.saturating_add(1);
+        }
+    }
+    swatches
+}
+
+/// A contrasting text color to draw over `color`.
+pub fn contrast(color: Rgb) -> Rgb {
+    let Rgb(r, g, b) = color;
+    let luma = u32::from(r)
+        .saturating_mul(299)
+        .saturating_add(u32::from(g).saturating_mul(587))
+        .saturating_add(u32::from(b).saturating_mul(114));
+    if luma > 128_000 {
+        Rgb(0, 0, 0)
+    } else {
+        Rgb(255, 255, 255)
+    }
+}
+
+/// A `#rrggbb` or `#rgb` literal at the start of `text`, with its length in bytes.
+fn hex(text: &str) -> Option<(usize, Rgb)> {
+    let digits = text
+        .get(1..)?
+        .chars()
+        .take_while(char::is_ascii_hexdigit)
+        .count();
+    let next = text.get(1..)?.chars().nth(digits);
+    if next.is_some_and(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    match digits {
+        6 => theme::parse_color(text.get(..7)?).map(|color| (7, color)),
+        3 => {
+            let expanded: String = text.get(1..4)?.chars().flat_map(|c| [c, c]).collect();
+            theme::parse_color(&format!("#{}", expanded)).map(|color| (4, color))
+        }
+        _ => None,
+    }
+}
+
+/// An `rgb(r, g, b)` or `rgba(r, g, b, a)` literal at the start of `text`, with its
+/// length in bytes.
+fn rgb(text: &str) -> Option<(usize, Rgb)> {
+    let arguments = text
+        .strip_prefix("rgba(")
+        .or_else(|| text.strip_prefix("rgb("))?;
+    let close = arguments.find(')')?;
+    let channels: Vec<u8> = arguments
+        .get(..close)?
+        .split(',')
+        .take(3)
+        .map(|channel| channel.trim().parse::<u8>().ok())
+        .collect::<Option<_>>()?;
+    let (r, g, b) = match channels[..] {
+        [r, g, b] => (r, g, b),
+        _ => return None,
+    };
+    let length = text
+        .len()
+        .saturating_sub(arguments.len())
+        .saturating_add(close)
+        .saturating_add(1);
+    Some((length, Rgb(r, g, b)))
+}
+
+fn grapheme_index(line: &str, offset: usize) -> usize {
+    line.get(..offset)
+        .map_or(0, |before| before.graphemes(true).count())
+}
@@ -0,0 +1,130 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const FILE_NAMES: &[&str] = &["tags", ".tags"];
+
+/// Where a tag's definition is: a line number or a search pattern.
+pub enum Address {
+    Line(usize),
+    /// The text of the line, and whether the whole line must match.
+    Pattern(String, bool),
+}
+
+/// One entry of a ctags file.
+pub struct Tag {
+    pub name: String,
+    pub file_name: String,
+    pub address: Address,
+}
+
+impl Address {
+    /// The index of the line this address points at among `lines`.
+    pub fn line_in<'a, I>(&self, lines: I) -> Option<usize>
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        match self {
+            Self::Line(line) => Some(line.saturating_sub(1)),
+            Self::Pattern(text, whole_line) => lines.into_iter().position(|line| {
+                if *whole_line {
+                    line == text
+                } else {
+                    line.starts_with(text.as_str())
+                }
+            }),
+        }
+    }
+}
+
+/// The nearest tags file in the working directory or one of its parents.
+pub fn find_file() -> Option<PathBuf> {
+    let cwd = env::current_dir().ok()?;
+    cwd.ancestors()
+        .flat_map(|dir| FILE_NAMES.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// The tags named `name`.
+pub fn lookup(name: &str) -> Vec<Tag> {
+    read(|tag_name| tag_name == name)
+}
+
+/// Distinct tag names starting with `prefix`, sorted.
+pub fn names(prefix: &str) -> Vec<String> {
+    let mut names: Vec<String> = read(|name| name.starts_with(prefix))
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn read<F>(wanted: F) -> Vec<Tag>
+where
+    F: Fn(&str) -> bool,
+{
+    let path = if let Some(path) = find_file() {
+        path
+    } else {
+        return Vec::new();
+    };
+    let contents = fs::read_to_string(&path).unwrap_or_default();
+    let dir = path.parent().unwrap_or_else(|| Path::new("."));
+    contents
+        .lines()
+        .filter(|line| !line.starts_with("!_TAG_"))
+        .filter(|line| line.split('\t').next().is_some_and(&wanted))
+        .filter_map(|line| parse_line(line, dir))
+        .collect()
+}
+
+/// Parses `name<Tab>file<Tab>address;"<Tab>fields`, resolving the file against the
+/// directory of the tags file.
+fn parse_line(line: &str, dir: &Path) -> Option<Tag> {
+    let mut fields = line.splitn(3, '\t');
+    let name = fields.next()?.to_string();
+    let file = fields.next()?;
+    let rest = fields.next()?;
+    // Extension fields such as the kind follow `;"`.
+    let address = rest
+        .rfind(";\"")
+        .map_or(Some(rest), |index| rest.get(..index))?;
+    let address = if let Ok(line) = address.trim().parse() {
+        Address::Line(line)
+    } else {
+        let pattern = address
+            .trim()
+            .strip_prefix('/')
+            .and_then(|pattern| pattern.strip_suffix('/'))
+            .or_else(|| {
+                address
+                    .trim()
+                    .strip_prefix('?')
+                    .and_then(|pattern| pattern.strip_suffix('?'))
+            })?;
+        let pattern = pattern.strip_prefix('^').unwrap_or(pattern);
+        let (pattern, whole_line) = match pattern.strip_suffix('$') {
+            Some(pattern) if !pattern.ends_with('\\') => (pattern, true),
+            _ => (pattern, false),
+        };
+        Address::Pattern(
+            pattern
+                .replace("\\/", "/")
+                .replace("\\?", "?")
+                .replace("\\\\", "\\"),
+            whole_line,
+        )
+    };
+    let path = dir.join(file);
+    let file_name = env::current_dir()
+        .ok()
+        .and_then(|cwd| path.strip_prefix(cwd).ok().map(Path::to_path_buf))
+        .unwrap_or(path);
+    Some(Tag {
+        name,
+        file_name: file_name.to_string_lossy().into_owned(),
+        address,
+    })
+}
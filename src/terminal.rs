@@ -1,68 +1,520 @@
-use std::io::{self, stdout, Write};
+use std::cell::RefCell;
+use std::env;
+use std::fs::File;
+use std::io::{self, stdout, Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::path::Path;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
 use termion::color;
-use termion::event::Key;
+use termion::event::{Event, MouseButton, MouseEvent};
 use termion::input::TermRead;
 use termion::raw::{IntoRawMode, RawTerminal};
 
+use crate::clipboard;
+use crate::color::Depth;
+use crate::recording::{
+    Recorder, RecorderSlot, RecordingReader, Replay, ReplayReader, SizeOverride,
+};
+use crate::Key;
 use crate::Position;
+
+/// The narrowest window the editor is drawn in; a narrower one gets a notice instead.
+pub const MIN_WIDTH: u16 = 20;
+/// Windows with fewer rows give up the status bar, keeping the message bar and at
+/// least two lines of text.
+const STATUS_BAR_MIN_ROWS: u16 = 5;
+
+#[derive(PartialEq, Eq)]
 pub struct Size {
     pub width: u16,
+    /// Rows of text, without the bars below them.
     pub height: u16,
+    /// Whether there is room for the status bar, which a short window drops.
+    pub status_bar: bool,
+}
+
+impl Size {
+    /// The layout of a window `rows` tall: the message bar always, and the status
+    /// bar above it when there is room.
+    fn new(width: u16, rows: u16) -> Self {
+        let status_bar = rows >= STATUS_BAR_MIN_ROWS;
+        Self {
+            width,
+            height: rows.saturating_sub(1).saturating_sub(u16::from(status_bar)),
+            status_bar,
+        }
+    }
+    /// The screen row of the message bar.
+    pub fn message_row(&self) -> usize {
+        usize::from(self.height).saturating_add(usize::from(self.status_bar))
+    }
+    /// Whether the window cannot fit a line of text and the message bar, or is
+    /// narrower than `MIN_WIDTH`.
+    pub fn is_too_small(&self) -> bool {
+        self.width < MIN_WIDTH || self.height == 0
+    }
+}
+
+/// What the user did: pressed a key, clicked a screen position or pasted text, or
+/// the terminal window gained or lost focus.
+pub enum Input {
+    Key(Key),
+    Click(Position),
+    Paste(String),
+    Focus(bool),
+    /// The terminal window changed size.
+    Resize,
+    /// A background thread delivered something for the editor to pick up.
+    Wake,
+}
+
+/// What the main loop waits on: the input thread, the resize watcher and `Waker`s
+/// all send down one channel.
+enum Message {
+    Event(Result<Event, std::io::Error>),
+    Resize,
+    Wake,
+}
+
+/// Interrupts the main loop's wait for input, so that output a language server, a
+/// job or the terminal pane delivered on another thread is picked up right away.
+#[derive(Clone)]
+pub struct Waker(Sender<Message>);
+
+impl Waker {
+    pub fn wake(&self) {
+        let _ = self.0.send(Message::Wake);
+    }
+}
+
+/// How the cursor is drawn, which shows the mode at a glance.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    Bar,
+    Underline,
 }
 
+/// The terminal hecto draws on and reads keys from, in raw mode while it lives.
 pub struct Terminal {
     size: Size,
-    _stdout: RawTerminal<std::io::Stdout>,
+    /// Raw mode, left when dropped; `None` for a headless terminal.
+    stdout: Option<RawTerminal<std::io::Stdout>>,
+    messages: Receiver<Message>,
+    sender: Sender<Message>,
+    /// A message taken by `has_input` ahead of being read.
+    peeked: RefCell<Option<Message>>,
+    /// Whether the terminal reports mouse clicks.
+    mouse: bool,
+    /// Whether the kitty keyboard enhancement has been requested.
+    keyboard: bool,
+    /// Whether the terminal has been asked to show text in the order it is sent.
+    explicit_bidi: bool,
+    /// The cursor shape asked for, if any has been; the terminal's own is put back on
+    /// exit.
+    cursor_shape: Option<CursorShape>,
+    /// The window title set, if any has been; the one before is put back on exit.
+    title: Option<String>,
+    /// The colors the terminal can show; theme colors are brought down to them.
+    colors: Depth,
+    /// Where input is being recorded to with `--record`.
+    recorder: RecorderSlot,
+    /// The size a replay runs at, in place of the window's.
+    size_override: SizeOverride,
 }
 
 impl Terminal {
+    /// Takes over the terminal, reading input from the keyboard.
     pub fn default() -> Result<Self, std::io::Error> {
-        let size = termion::terminal_size()?;
+        Self::new(None)
+    }
+    /// Takes over the terminal, reading input from `replay` until it runs out.
+    pub fn new(replay: Option<Replay>) -> Result<Self, std::io::Error> {
+        let (sender, messages) = mpsc::channel();
+        // When stdin is a pipe (`cmd | hecto -`), keystrokes come from the controlling tty.
+        let mut input: Box<dyn io::Read + Send> = if termion::is_tty(&io::stdin()) {
+            Box::new(io::stdin())
+        } else {
+            Box::new(termion::get_tty()?)
+        };
+        let size_override = SizeOverride::default();
+        if let Some(replay) = replay {
+            let resizes = sender.clone();
+            input = Box::new(ReplayReader::new(
+                replay,
+                input,
+                size_override.clone(),
+                Box::new(move || {
+                    let _ = resizes.send(Message::Resize);
+                }),
+            ));
+        }
+        let size = window_size(&size_override)?;
+        let recorder = RecorderSlot::default();
+        let input = Utf8Reader::new(RecordingReader::new(input, recorder.clone()));
+        let events = sender.clone();
+        thread::spawn(move || {
+            for event in input.events() {
+                if events.send(Message::Event(event)).is_err() {
+                    break;
+                }
+            }
+        });
+        // Without the watcher, a new size is still noticed with the next key.
+        let _ = watch_resize(sender.clone());
+        print!("{}{}", PASTE_ON, FOCUS_ON);
         Ok(Self {
-            size: Size {
-                width: size.0,
-                height: size.1.saturating_sub(2),
-            },
-            _stdout: stdout().into_raw_mode()?,
+            size: Size::new(size.0, size.1),
+            stdout: Some(stdout().into_raw_mode()?),
+            messages,
+            sender,
+            peeked: RefCell::new(None),
+            mouse: false,
+            keyboard: false,
+            explicit_bidi: false,
+            cursor_shape: None,
+            title: None,
+            colors: crate::color::detect(),
+            recorder,
+            size_override,
         })
     }
-    pub fn set_bg_color(color: color::Rgb) {
-        print!("{}", color::Bg(color));
+    /// A terminal that is never drawn on and has no keys to read, for running ex
+    /// commands from a script with `-c`.
+    pub fn headless() -> Self {
+        let (sender, messages) = mpsc::channel();
+        Self {
+            size: Size::new(80, 24),
+            stdout: None,
+            messages,
+            sender,
+            peeked: RefCell::new(None),
+            mouse: false,
+            keyboard: false,
+            explicit_bidi: false,
+            cursor_shape: None,
+            title: None,
+            colors: crate::color::detect(),
+            recorder: RecorderSlot::default(),
+            size_override: SizeOverride::default(),
+        }
+    }
+    /// Whether there is no terminal to draw on, as when hecto runs headless.
+    pub fn is_headless(&self) -> bool {
+        self.stdout.is_none()
     }
+    /// Starts writing every input and resize to `path`, for `--record`.
+    pub fn start_recording(&self, path: &Path) -> Result<(), std::io::Error> {
+        let (width, height) = window_size(&self.size_override)?;
+        let recorder = Recorder::create(path, width, height)?;
+        if let Ok(mut slot) = self.recorder.lock() {
+            *slot = Some(recorder);
+        }
+        Ok(())
+    }
+    /// Sets the background color, brought down to the colors the terminal can show.
+    pub fn set_bg_color(&self, color: color::Rgb) {
+        print!("{}", crate::color::sequence(color, self.colors, true));
+    }
+    /// Puts back the terminal's own background color.
     pub fn reset_bg_color() {
         print!("{}", color::Bg(color::Reset));
     }
-    pub fn set_fg_color(color: color::Rgb) {
-        print!("{}", color::Fg(color));
+    /// Sets the text color, brought down to the colors the terminal can show.
+    pub fn set_fg_color(&self, color: color::Rgb) {
+        print!("{}", crate::color::sequence(color, self.colors, false));
     }
+    /// Puts back the terminal's own text color.
     pub fn reset_fg_color() {
         print!("{}", color::Fg(color::Reset));
     }
+    /// Turns underlining of the text that follows on or off.
+    pub fn set_underline(underline: bool) {
+        if underline {
+            print!("{}", termion::style::Underline);
+        } else {
+            print!("{}", termion::style::NoUnderline);
+        }
+    }
+    /// Turns bold text on or off.
+    pub fn set_bold(bold: bool) {
+        if bold {
+            print!("{}", termion::style::Bold);
+        } else {
+            // Normal intensity; termion's `NoBold` is read as double underline by some
+            // terminals.
+            print!("{}", termion::style::NoFaint);
+        }
+    }
+    /// Turns italic text on or off.
+    pub fn set_italic(italic: bool) {
+        if italic {
+            print!("{}", termion::style::Italic);
+        } else {
+            print!("{}", termion::style::NoItalic);
+        }
+    }
+    /// The size of the window as last read.
     pub fn size(&self) -> &Size {
         &self.size
     }
+    /// Rereads the window size, returning whether it changed.
+    pub fn update_size(&mut self) -> bool {
+        let (width, height) = if let Ok(size) = window_size(&self.size_override) {
+            size
+        } else {
+            return false;
+        };
+        let size = Size::new(width, height);
+        if size == self.size {
+            return false;
+        }
+        if let Ok(mut recorder) = self.recorder.lock() {
+            if let Some(recorder) = recorder.as_mut() {
+                recorder.resize(width, height);
+            }
+        }
+        self.size = size;
+        true
+    }
+    /// Writes `text` to the message bar straight away, for progress during work that
+    /// holds up the main loop.
+    pub fn draw_message(&self, text: &str) {
+        if self.is_headless() {
+            return;
+        }
+        Self::cursor_position(&Position {
+            x: 0,
+            y: self.size.message_row(),
+        });
+        Self::clear_current_line();
+        print!(
+            "{}",
+            text.chars()
+                .take(usize::from(self.size.width))
+                .collect::<String>()
+        );
+        let _ = Self::flush();
+    }
+    /// Asks for a line of text in the message bar, for questions that come before the
+    /// editor can prompt, as while files are opened. `secret` text shows as `*` and
+    /// is left out of a recording. Returns `None` when Esc is pressed.
+    pub fn read_line(&self, prompt: &str, secret: bool) -> Result<Option<String>, std::io::Error> {
+        let paused = if secret {
+            self.recorder.lock().ok().and_then(|mut slot| slot.take())
+        } else {
+            None
+        };
+        let mut text = String::new();
+        let answer = loop {
+            let shown = if secret {
+                "*".repeat(text.chars().count())
+            } else {
+                text.clone()
+            };
+            self.draw_message(&format!("{}{}", prompt, shown));
+            match self.read_input() {
+                Ok(Input::Key(Key::Char('\n'))) => break Ok(Some(text)),
+                Ok(Input::Key(Key::Esc | Key::Ctrl('c'))) => break Ok(None),
+                Ok(Input::Key(Key::Backspace)) => {
+                    text.pop();
+                }
+                Ok(Input::Key(Key::Char(c))) => text.push(c),
+                Ok(Input::Paste(pasted)) => text.push_str(&pasted),
+                Ok(_) => (),
+                Err(error) => break Err(error),
+            }
+        };
+        if let (Some(recorder), Ok(mut slot)) = (paused, self.recorder.lock()) {
+            *slot = Some(recorder);
+        }
+        answer
+    }
+    /// Asks the terminal to put `text` on the clipboard.
+    pub fn set_clipboard(text: &str) {
+        print!("{}", clipboard::osc52(text));
+    }
+    /// Blanks the row the cursor is on.
     pub fn clear_current_line() {
         print!("{}", termion::clear::CurrentLine);
     }
+    /// Moves what the `height` rows from row `top` show up by `lines`, or down when it
+    /// is negative, leaving blank rows behind; the rows around them stay as they are.
+    pub fn scroll(top: usize, height: usize, lines: isize) {
+        let count = lines.unsigned_abs();
+        let shift = if lines > 0 {
+            format!("\x1b[{}S", count)
+        } else {
+            format!("\x1b[{}T", count)
+        };
+        print!(
+            "\x1b[{};{}r{}\x1b[r",
+            top.saturating_add(1),
+            top.saturating_add(height),
+            shift
+        );
+    }
+    /// Shows the cursor.
     pub fn cursor_show() {
         print!("{}", termion::cursor::Show);
     }
+    /// Hides the cursor, as while a frame is drawn.
     pub fn cursor_hide() {
         print!("{}", termion::cursor::Hide);
     }
+    /// Blanks the whole screen.
     pub fn clear_screen() {
         print!("{}", termion::clear::All);
     }
+    /// Sends everything printed so far to the terminal.
     pub fn flush() -> Result<(), std::io::Error> {
         io::stdout().flush()
     }
-    pub fn read_key() -> Result<Key, std::io::Error> {
+    /// Turns reporting of mouse clicks on or off. Leaving it off keeps the terminal's
+    /// own text selection working.
+    pub fn set_mouse(&mut self, mouse: bool) {
+        if self.mouse != mouse {
+            self.mouse = mouse;
+            print!("{}", if mouse { MOUSE_ON } else { MOUSE_OFF });
+        }
+    }
+    /// Changes the cursor's shape, for terminals that support DECSCUSR; others
+    /// ignore it.
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) {
+        if self.cursor_shape != Some(shape) {
+            self.cursor_shape = Some(shape);
+            match shape {
+                CursorShape::Block => print!("{}", termion::cursor::SteadyBlock),
+                CursorShape::Bar => print!("{}", termion::cursor::SteadyBar),
+                CursorShape::Underline => print!("{}", termion::cursor::SteadyUnderline),
+            }
+        }
+    }
+    /// Sets the window title. The first time, the terminal's title is saved on its
+    /// title stack, for terminals that keep one.
+    pub fn set_title(&mut self, title: &str) {
+        if self.title.as_deref() == Some(title) {
+            return;
+        }
+        if self.title.is_none() {
+            print!("{}", TITLE_PUSH);
+        }
+        let shown: String = title.chars().filter(|c| !c.is_control()).collect();
+        print!("\x1b]2;{}\x07", shown);
+        self.title = Some(title.to_string());
+    }
+    /// Asks the terminal to disambiguate keys with the kitty keyboard protocol, or
+    /// to stop. Terminals without the protocol ignore the request and keep sending
+    /// legacy sequences, which are still understood.
+    pub fn set_keyboard_enhancement(&mut self, keyboard: bool) {
+        if self.keyboard != keyboard {
+            self.keyboard = keyboard;
+            print!("{}", if keyboard { KEYBOARD_ON } else { KEYBOARD_OFF });
+        }
+    }
+    /// Asks terminals that reorder right-to-left text themselves, such as VTE-based
+    /// ones, to leave it as sent, because the editor has ordered it already; or
+    /// lets them reorder it again. Other terminals never reorder and ignore this.
+    pub fn set_explicit_bidi(&mut self, explicit: bool) {
+        if self.explicit_bidi != explicit {
+            self.explicit_bidi = explicit;
+            print!(
+                "{}",
+                if explicit {
+                    BIDI_EXPLICIT
+                } else {
+                    BIDI_IMPLICIT
+                }
+            );
+        }
+    }
+    /// A handle other threads use to wake the main loop.
+    pub fn waker(&self) -> Waker {
+        Waker(self.sender.clone())
+    }
+    /// Waits for the next input.
+    pub fn read_input(&self) -> Result<Input, std::io::Error> {
+        loop {
+            if let Some(input) = self.read(None)? {
+                return Ok(input);
+            }
+        }
+    }
+    /// The next input, or `None` if none comes within `timeout`.
+    pub fn read_input_timeout(&self, timeout: Duration) -> Result<Option<Input>, std::io::Error> {
+        self.read(Some(timeout))
+    }
+    /// Whether input has arrived that has not been read yet.
+    pub fn has_input(&self) -> bool {
+        let mut peeked = self.peeked.borrow_mut();
+        if peeked.is_none() {
+            *peeked = self.messages.try_recv().ok();
+        }
+        peeked.is_some()
+    }
+    /// The next input, or `None` after `timeout` or for events that mean nothing.
+    fn read(&self, timeout: Option<Duration>) -> Result<Option<Input>, std::io::Error> {
+        match self.next_message(timeout)? {
+            Some(Message::Event(event)) => {
+                let event = event?;
+                if is_paste_start(&event) {
+                    self.read_paste().map(Some)
+                } else {
+                    Ok(input(event))
+                }
+            }
+            Some(Message::Resize) => Ok(Some(Input::Resize)),
+            Some(Message::Wake) => Ok(Some(Input::Wake)),
+            None => Ok(None),
+        }
+    }
+    fn next_message(&self, timeout: Option<Duration>) -> Result<Option<Message>, std::io::Error> {
+        if let Some(message) = self.peeked.take() {
+            return Ok(Some(message));
+        }
+        if self.is_headless() {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no terminal"));
+        }
+        let message = match timeout {
+            Some(timeout) => self.messages.recv_timeout(timeout),
+            None => self
+                .messages
+                .recv()
+                .map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match message {
+            Ok(message) => Ok(Some(message)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "stdin closed",
+            )),
+        }
+    }
+    /// The next terminal event; wakes and resizes in between are dropped, as the main
+    /// loop checks for both on its next pass anyway.
+    fn next_event(&self) -> Result<Event, std::io::Error> {
         loop {
-            if let Some(key) = io::stdin().lock().keys().next() {
-                return key;
+            if let Some(Message::Event(event)) = self.next_message(None)? {
+                return event;
             }
         }
     }
+    /// Collects the text of a bracketed paste, up to the sequence that closes it.
+    fn read_paste(&self) -> Result<Input, std::io::Error> {
+        let mut text = String::new();
+        loop {
+            match self.next_event()? {
+                Event::Unsupported(bytes) if bytes == PASTE_END => return Ok(Input::Paste(text)),
+                Event::Key(termion::event::Key::Char(c)) => text.push(c),
+                _ => (),
+            }
+        }
+    }
+    /// Moves the cursor to `cursor_position`, counted from the top left from zero.
     pub fn cursor_position(cursor_position: &Position) {
         let Position { mut x, mut y } = cursor_position;
         x = x.saturating_add(1);
@@ -71,3 +523,202 @@ impl Terminal {
         print!("{}", termion::cursor::Goto(x as u16, y as u16));
     }
 }
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        if self.is_headless() {
+            return;
+        }
+        self.set_mouse(false);
+        self.set_keyboard_enhancement(false);
+        self.set_explicit_bidi(false);
+        if self.cursor_shape.is_some() {
+            print!("{}", CURSOR_DEFAULT);
+        }
+        if self.title.is_some() {
+            print!("{}", TITLE_POP);
+        }
+        print!("{}{}", PASTE_OFF, FOCUS_OFF);
+        let _ = Self::flush();
+    }
+}
+
+/// Save and restore the window title on the terminal's title stack.
+const TITLE_PUSH: &str = "\x1b[22;0t";
+const TITLE_POP: &str = "\x1b[23;0t";
+/// Goes back to the cursor shape the terminal is configured with.
+const CURSOR_DEFAULT: &str = "\x1b[0 q";
+const MOUSE_ON: &str = "\x1b[?1000h\x1b[?1006h";
+const MOUSE_OFF: &str = "\x1b[?1006l\x1b[?1000l";
+/// Pushes the "disambiguate escape codes" flag only: reporting key releases would
+/// send `:`-separated fields that termion's parser cannot read.
+const KEYBOARD_ON: &str = "\x1b[>1u";
+const KEYBOARD_OFF: &str = "\x1b[<u";
+/// Bi-directional support mode: implicit has the terminal reorder text, explicit
+/// leaves that to the program.
+const BIDI_EXPLICIT: &str = "\x1b[8l";
+const BIDI_IMPLICIT: &str = "\x1b[8h";
+/// Bracketed paste: the terminal wraps pasted text in `PASTE_START` and `PASTE_END`.
+const PASTE_ON: &str = "\x1b[?2004h";
+const PASTE_OFF: &str = "\x1b[?2004l";
+const PASTE_START: &[u8] = b"\x1b[200~";
+const PASTE_END: &[u8] = b"\x1b[201~";
+
+/// Focus reporting: the terminal sends `FOCUS_IN` and `FOCUS_OUT` as its window
+/// gains and loses focus.
+const FOCUS_ON: &str = "\x1b[?1004h";
+const FOCUS_OFF: &str = "\x1b[?1004l";
+const FOCUS_IN: &[u8] = b"\x1b[I";
+const FOCUS_OUT: &[u8] = b"\x1b[O";
+
+/// The write end of the pipe `on_resize` signals on.
+static RESIZE_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+extern "C" fn on_resize(_: libc::c_int) {
+    let fd = RESIZE_PIPE.load(Ordering::Relaxed);
+    // SAFETY: `write` is async-signal-safe, and the byte outlives the call.
+    unsafe {
+        libc::write(fd, [0_u8].as_ptr().cast(), 1);
+    }
+}
+
+/// Sends `Message::Resize` each time the window changes size, from a thread the
+/// SIGWINCH handler wakes through a pipe.
+fn watch_resize(sender: Sender<Message>) -> Result<(), std::io::Error> {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two descriptors `pipe2` returns.
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let [read_fd, write_fd] = fds;
+    // SAFETY: the read end was just opened and nothing else owns it.
+    let mut reader = unsafe { File::from_raw_fd(read_fd) };
+    RESIZE_PIPE.store(write_fd, Ordering::Relaxed);
+    // SAFETY: `on_resize` makes only async-signal-safe calls.
+    unsafe {
+        libc::signal(libc::SIGWINCH, on_resize as *const () as libc::sighandler_t);
+    }
+    thread::spawn(move || {
+        let mut byte = [0_u8; 1];
+        while reader.read(&mut byte).is_ok_and(|count| count > 0) {
+            if sender.send(Message::Resize).is_err() {
+                break;
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Hands termion whole UTF-8 characters. termion gives an error for bytes that are
+/// not UTF-8, losing the key, so those, as a terminal in an 8-bit locale sends, are
+/// read as Latin-1. A character split between reads, as a long IME commit may be,
+/// is held back until the rest arrives.
+struct Utf8Reader<R> {
+    inner: R,
+    /// Decoded bytes not yet handed out.
+    ready: Vec<u8>,
+    /// The start of a character whose other bytes have not arrived yet.
+    partial: Vec<u8>,
+}
+
+impl<R> Utf8Reader<R> {
+    fn new(inner: R) -> Self {
+        Self {
+            inner,
+            ready: Vec::new(),
+            partial: Vec::new(),
+        }
+    }
+    fn decode(&mut self, mut bytes: &[u8]) {
+        loop {
+            let error = match std::str::from_utf8(bytes) {
+                Ok(text) => {
+                    self.ready.extend_from_slice(text.as_bytes());
+                    return;
+                }
+                Err(error) => error,
+            };
+            let (valid, rest) = bytes.split_at(error.valid_up_to());
+            self.ready.extend_from_slice(valid);
+            match error.error_len() {
+                Some(invalid) => {
+                    self.push_latin1(rest.get(..invalid).unwrap_or_default());
+                    bytes = rest.get(invalid..).unwrap_or_default();
+                }
+                None => {
+                    self.partial = rest.to_vec();
+                    return;
+                }
+            }
+        }
+    }
+    fn push_latin1(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            let mut encoded = [0; 4];
+            self.ready
+                .extend_from_slice(char::from(*byte).encode_utf8(&mut encoded).as_bytes());
+        }
+    }
+}
+
+impl<R: Read> Read for Utf8Reader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Each read hands out what one read of the input brought and no more, so that
+        // a lone Esc still arrives alone.
+        while self.ready.is_empty() {
+            let mut chunk = [0_u8; 1024];
+            let count = self.inner.read(&mut chunk)?;
+            let mut bytes = std::mem::take(&mut self.partial);
+            if count == 0 {
+                // The input is over: what was held back will never be completed.
+                if bytes.is_empty() {
+                    return Ok(0);
+                }
+                self.push_latin1(&bytes);
+                break;
+            }
+            bytes.extend_from_slice(chunk.get(..count).unwrap_or_default());
+            self.decode(&bytes);
+        }
+        let count = self.ready.len().min(buf.len());
+        for (target, source) in buf.iter_mut().zip(self.ready.drain(..count)) {
+            *target = source;
+        }
+        Ok(count)
+    }
+}
+
+/// The size of the window, or the one a replay set.
+fn window_size(size_override: &SizeOverride) -> Result<(u16, u16), std::io::Error> {
+    match size_override.lock().ok().and_then(|size| *size) {
+        Some(size) => Ok(size),
+        None => termion::terminal_size(),
+    }
+}
+
+/// Whether hecto runs over SSH, where every redrawn line crosses the network.
+pub fn is_remote() -> bool {
+    ["SSH_CONNECTION", "SSH_CLIENT", "SSH_TTY"]
+        .iter()
+        .any(|variable| env::var_os(variable).is_some())
+}
+
+fn is_paste_start(event: &Event) -> bool {
+    matches!(event, Event::Unsupported(bytes) if bytes == PASTE_START)
+}
+
+/// The input an event stands for; only left clicks of the mouse count. Keys termion
+/// does not know come through as unsupported sequences and are decoded here.
+fn input(event: Event) -> Option<Input> {
+    match event {
+        Event::Key(key) => Key::from_termion(key).map(Input::Key),
+        Event::Unsupported(bytes) if bytes == FOCUS_IN => Some(Input::Focus(true)),
+        Event::Unsupported(bytes) if bytes == FOCUS_OUT => Some(Input::Focus(false)),
+        Event::Unsupported(bytes) => Key::from_sequence(&bytes).map(Input::Key),
+        Event::Mouse(MouseEvent::Press(MouseButton::Left, x, y)) => Some(Input::Click(Position {
+            x: usize::from(x.saturating_sub(1)),
+            y: usize::from(y.saturating_sub(1)),
+        })),
+        _ => None,
+    }
+}
@@ -0,0 +1,74 @@
+use crate::pty::Pty;
+use crate::terminal::Waker;
+use crate::vt::Screen;
+use crate::Key;
+use std::env;
+use std::io::Error;
+
+/// Most columns the terminal pane takes from the text.
+pub const WIDTH: usize = 80;
+
+/// A shell running beside the text, drawn from the screen its output paints.
+pub struct TerminalPane {
+    pty: Pty,
+    pub screen: Screen,
+    /// Whether keys go to the shell rather than the buffer.
+    pub focused: bool,
+}
+
+impl TerminalPane {
+    /// Starts `$SHELL`, or `sh`, in a pane of `width` by `height` cells.
+    pub fn open(width: usize, height: usize, waker: Waker) -> Result<Self, Error> {
+        let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
+        Ok(Self {
+            pty: Pty::spawn(&shell, width, height, waker)?,
+            screen: Screen::new(width, height),
+            focused: false,
+        })
+    }
+    /// Paints the output that arrived, returning whether there was any.
+    pub fn update(&mut self) -> bool {
+        let output = self.pty.read();
+        self.screen.feed(&output);
+        !output.is_empty()
+    }
+    pub fn is_running(&mut self) -> bool {
+        self.pty.is_running()
+    }
+    pub fn is_closed(&self) -> bool {
+        self.pty.is_closed()
+    }
+    pub fn send_key(&mut self, key: Key) -> Result<(), Error> {
+        self.pty.write(&key_bytes(key))
+    }
+}
+
+/// What a VT100 sends for `key`.
+fn key_bytes(key: Key) -> Vec<u8> {
+    let text = |text: &str| text.as_bytes().to_vec();
+    match key {
+        Key::Char('\n') => vec![b'\r'],
+        Key::Char(c) => c.to_string().into_bytes(),
+        Key::Ctrl(c) | Key::CtrlShift(c) => vec![u8::try_from(c).unwrap_or(0) & 0x1F],
+        Key::Alt(c) => {
+            let mut bytes = vec![0x1B];
+            bytes.extend(c.to_string().into_bytes());
+            bytes
+        }
+        Key::Backspace => vec![0x7F],
+        Key::Esc => vec![0x1B],
+        Key::Up => text("\x1b[A"),
+        Key::Down => text("\x1b[B"),
+        Key::Right => text("\x1b[C"),
+        Key::Left => text("\x1b[D"),
+        Key::Home => text("\x1b[H"),
+        Key::End => text("\x1b[F"),
+        Key::Delete => text("\x1b[3~"),
+        Key::Insert => text("\x1b[2~"),
+        Key::PageUp => text("\x1b[5~"),
+        Key::PageDown => text("\x1b[6~"),
+        Key::BackTab => text("\x1b[Z"),
+        Key::F(number @ 1..=4) => vec![0x1B, b'O', b'P'.saturating_add(number).saturating_sub(1)],
+        _ => key.sequence().map_or_else(Vec::new, text),
+    }
+}
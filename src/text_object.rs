@@ -0,0 +1,283 @@
+use crate::Document;
+use crate::Position;
+
+#[derive(PartialEq, Clone, Copy)]
+enum CharClass {
+    Word,
+    Whitespace,
+    Punctuation,
+}
+
+fn char_class(grapheme: &str) -> CharClass {
+    match grapheme.chars().next() {
+        Some(c) if c.is_alphanumeric() || c == '_' => CharClass::Word,
+        Some(c) if c.is_whitespace() => CharClass::Whitespace,
+        _ => CharClass::Punctuation,
+    }
+}
+
+fn bracket_pair(object: char) -> Option<(&'static str, &'static str)> {
+    match object {
+        '(' | ')' | 'b' => Some(("(", ")")),
+        '[' | ']' => Some(("[", "]")),
+        '{' | '}' | 'B' => Some(("{", "}")),
+        '<' | '>' => Some(("<", ">")),
+        _ => None,
+    }
+}
+
+/// Returns the range covered by a text object at `at`, with the end exclusive.
+pub fn find(
+    document: &Document,
+    at: &Position,
+    object: char,
+    inner: bool,
+) -> Option<(Position, Position)> {
+    match object {
+        'w' => find_word(document, at, inner),
+        '"' | '\'' | '`' => find_quoted(document, at, object, inner),
+        't' => find_tag(document, at, inner),
+        _ => {
+            let (open, close) = bracket_pair(object)?;
+            find_bracketed(document, at, open, close, inner)
+        }
+    }
+}
+
+fn find_word(document: &Document, at: &Position, inner: bool) -> Option<(Position, Position)> {
+    let graphemes: Vec<&str> = document.row(at.y)?.graphemes().collect();
+    let class = char_class(graphemes.get(at.x)?);
+    let mut start = at.x;
+    while start > 0
+        && graphemes
+            .get(start.saturating_sub(1))
+            .is_some_and(|g| char_class(g) == class)
+    {
+        start = start.saturating_sub(1);
+    }
+    let mut end = at.x;
+    while graphemes.get(end).is_some_and(|g| char_class(g) == class) {
+        end = end.saturating_add(1);
+    }
+    if !inner && class != CharClass::Whitespace {
+        let trailing_end = skip_whitespace_forward(&graphemes, end);
+        if trailing_end > end {
+            end = trailing_end;
+        } else {
+            while start > 0
+                && graphemes
+                    .get(start.saturating_sub(1))
+                    .is_some_and(|g| char_class(g) == CharClass::Whitespace)
+            {
+                start = start.saturating_sub(1);
+            }
+        }
+    }
+    Some((Position { x: start, y: at.y }, Position { x: end, y: at.y }))
+}
+
+fn skip_whitespace_forward(graphemes: &[&str], mut at: usize) -> usize {
+    while graphemes
+        .get(at)
+        .is_some_and(|g| char_class(g) == CharClass::Whitespace)
+    {
+        at = at.saturating_add(1);
+    }
+    at
+}
+
+fn find_quoted(
+    document: &Document,
+    at: &Position,
+    quote: char,
+    inner: bool,
+) -> Option<(Position, Position)> {
+    let quote = quote.to_string();
+    let quotes: Vec<usize> = document
+        .row(at.y)?
+        .graphemes()
+        .enumerate()
+        .filter(|(_, g)| *g == quote)
+        .map(|(index, _)| index)
+        .collect();
+    let (open, close) = quotes
+        .chunks(2)
+        .filter_map(|pair| match pair {
+            [open, close] => Some((*open, *close)),
+            _ => None,
+        })
+        .find(|(_, close)| at.x <= *close)?;
+    let (start, end) = if inner {
+        (open.saturating_add(1), close)
+    } else {
+        (open, close.saturating_add(1))
+    };
+    Some((Position { x: start, y: at.y }, Position { x: end, y: at.y }))
+}
+
+fn find_bracketed(
+    document: &Document,
+    at: &Position,
+    open: &str,
+    close: &str,
+    inner: bool,
+) -> Option<(Position, Position)> {
+    let open_position = scan_backward(document, at, open, close)?;
+    let close_position = scan_forward(document, &open_position, open, close)?;
+    if inner {
+        Some((
+            Position {
+                x: open_position.x.saturating_add(1),
+                y: open_position.y,
+            },
+            close_position,
+        ))
+    } else {
+        Some((
+            open_position,
+            Position {
+                x: close_position.x.saturating_add(1),
+                y: close_position.y,
+            },
+        ))
+    }
+}
+
+/// Finds the unmatched `open` bracket enclosing `at` (or at `at` itself).
+fn scan_backward(document: &Document, at: &Position, open: &str, close: &str) -> Option<Position> {
+    let mut depth = 0_usize;
+    let mut y = at.y;
+    loop {
+        let graphemes: Vec<&str> = document.row(y)?.graphemes().collect();
+        let last = if y == at.y {
+            at.x
+        } else {
+            graphemes.len().saturating_sub(1)
+        };
+        for x in (0..=last).rev() {
+            match graphemes.get(x) {
+                Some(g) if *g == open => {
+                    if depth == 0 {
+                        return Some(Position { x, y });
+                    }
+                    depth = depth.saturating_sub(1);
+                }
+                Some(g) if *g == close && !(y == at.y && x == at.x) => {
+                    depth = depth.saturating_add(1);
+                }
+                _ => (),
+            }
+        }
+        if y == 0 {
+            return None;
+        }
+        y = y.saturating_sub(1);
+    }
+}
+
+/// Finds the `close` bracket matching the `open` bracket at `from`.
+fn scan_forward(document: &Document, from: &Position, open: &str, close: &str) -> Option<Position> {
+    let mut depth = 0_usize;
+    for y in from.y..document.len() {
+        let skip = if y == from.y {
+            from.x.saturating_add(1)
+        } else {
+            0
+        };
+        for (x, grapheme) in document.row(y)?.graphemes().enumerate().skip(skip) {
+            if grapheme == open {
+                depth = depth.saturating_add(1);
+            } else if grapheme == close {
+                if depth == 0 {
+                    return Some(Position { x, y });
+                }
+                depth = depth.saturating_sub(1);
+            }
+        }
+    }
+    None
+}
+
+struct Tag {
+    start: Position,
+    end: Position,
+    name: String,
+    closing: bool,
+}
+
+fn parse_tags(document: &Document) -> Vec<Tag> {
+    let mut tags = Vec::new();
+    for y in 0..document.len() {
+        let graphemes: Vec<&str> = document
+            .row(y)
+            .map(|row| row.graphemes().collect())
+            .unwrap_or_default();
+        for (x, grapheme) in graphemes.iter().enumerate() {
+            if *grapheme != "<" {
+                continue;
+            }
+            let rest = graphemes.get(x.saturating_add(1)..).unwrap_or_default();
+            let length = if let Some(length) = rest.iter().position(|g| *g == ">") {
+                length
+            } else {
+                continue;
+            };
+            let content: String = rest.get(..length).unwrap_or_default().concat();
+            if content.ends_with('/') || content.starts_with('!') {
+                continue;
+            }
+            let closing = content.starts_with('/');
+            let name: String = content
+                .trim_start_matches('/')
+                .chars()
+                .take_while(|c| !c.is_whitespace())
+                .collect();
+            if name.is_empty() {
+                continue;
+            }
+            tags.push(Tag {
+                start: Position { x, y },
+                end: Position {
+                    x: x.saturating_add(length).saturating_add(2),
+                    y,
+                },
+                name,
+                closing,
+            });
+        }
+    }
+    tags
+}
+
+fn find_tag(document: &Document, at: &Position, inner: bool) -> Option<(Position, Position)> {
+    let cursor = (at.y, at.x);
+    let mut open_tags: Vec<Tag> = Vec::new();
+    let mut best: Option<(Tag, Tag)> = None;
+    for tag in parse_tags(document) {
+        if !tag.closing {
+            open_tags.push(tag);
+            continue;
+        }
+        let index = if let Some(index) = open_tags.iter().rposition(|open| open.name == tag.name)
+        {
+            index
+        } else {
+            continue;
+        };
+        let open = open_tags.remove(index);
+        open_tags.truncate(index);
+        let encloses = (open.start.y, open.start.x) <= cursor && cursor < (tag.end.y, tag.end.x);
+        let innermost = best.as_ref().is_none_or(|(best_open, _)| {
+            (best_open.start.y, best_open.start.x) < (open.start.y, open.start.x)
+        });
+        if encloses && innermost {
+            best = Some((open, tag));
+        }
+    }
+    let (open, close) = best?;
+    if inner {
+        Some((open.end, close.start))
+    } else {
+        Some((open.start, close.end))
+    }
+}
@@ -0,0 +1,282 @@
+use crate::diagnostics::Severity;
+use crate::git::Change;
+use crate::highlight::Token;
+use crate::statusline::{Segment, SEGMENTS};
+use termion::color::Rgb;
+
+#[derive(Clone, Copy)]
+pub struct Theme {
+    pub status_fg: Rgb,
+    pub status_bg: Rgb,
+    /// The status bar text while the terminal window is out of focus.
+    pub status_unfocused_fg: Rgb,
+    /// Status bar segments in the order of `SEGMENTS`; unset ones use `status_fg`.
+    pub status_segment_fg: [Option<Rgb>; SEGMENTS.len()],
+    pub selection_bg: Rgb,
+    pub line_number_fg: Rgb,
+    pub color_column_bg: Rgb,
+    pub cursor_line_bg: Rgb,
+    pub whitespace_fg: Rgb,
+    pub indent_guide_fg: Rgb,
+    pub scrollbar_bg: Rgb,
+    pub scrollbar_thumb_bg: Rgb,
+    pub search_mark_fg: Rgb,
+    /// Behind the matches of the last search.
+    pub search_match_bg: Rgb,
+    pub minimap_fg: Rgb,
+    pub minimap_view_bg: Rgb,
+    pub fold_fg: Rgb,
+    pub error_fg: Rgb,
+    pub warning_fg: Rgb,
+    pub info_fg: Rgb,
+    pub hint_fg: Rgb,
+    pub added_fg: Rgb,
+    pub modified_fg: Rgb,
+    pub removed_fg: Rgb,
+    pub ours_bg: Rgb,
+    pub theirs_bg: Rgb,
+    pub spell_fg: Rgb,
+    pub url_fg: Rgb,
+    pub preview_heading_fg: Rgb,
+    pub preview_marker_fg: Rgb,
+    pub preview_code_bg: Rgb,
+    /// Every other column of a CSV or TSV buffer in column view.
+    pub column_alt_bg: Rgb,
+    /// Plain text and the page behind it in exports; the editor itself draws on the
+    /// terminal's own colors.
+    pub text_fg: Rgb,
+    pub text_bg: Rgb,
+    /// Brackets by nesting depth, for `rainbow_brackets`; deeper levels cycle.
+    pub rainbow_fg: [Rgb; 4],
+    pub keyword_fg: Rgb,
+    pub string_fg: Rgb,
+    pub comment_fg: Rgb,
+    pub number_fg: Rgb,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            status_fg: Rgb(63, 63, 63),
+            status_bg: Rgb(239, 239, 239),
+            status_unfocused_fg: Rgb(160, 160, 160),
+            status_segment_fg: segment_colors(Rgb(0, 110, 160), Rgb(0, 150, 60), Rgb(204, 0, 0)),
+            selection_bg: Rgb(68, 85, 119),
+            line_number_fg: Rgb(128, 128, 128),
+            color_column_bg: Rgb(224, 224, 224),
+            cursor_line_bg: Rgb(242, 242, 242),
+            whitespace_fg: Rgb(190, 190, 190),
+            indent_guide_fg: Rgb(215, 215, 215),
+            scrollbar_bg: Rgb(235, 235, 235),
+            scrollbar_thumb_bg: Rgb(190, 190, 190),
+            search_mark_fg: Rgb(200, 120, 0),
+            search_match_bg: Rgb(255, 226, 150),
+            minimap_fg: Rgb(150, 150, 150),
+            minimap_view_bg: Rgb(228, 228, 228),
+            fold_fg: Rgb(0, 110, 160),
+            error_fg: Rgb(204, 0, 0),
+            warning_fg: Rgb(196, 140, 0),
+            info_fg: Rgb(0, 110, 204),
+            hint_fg: Rgb(120, 120, 120),
+            added_fg: Rgb(0, 150, 60),
+            modified_fg: Rgb(0, 110, 204),
+            removed_fg: Rgb(204, 0, 0),
+            ours_bg: Rgb(210, 240, 210),
+            theirs_bg: Rgb(210, 225, 250),
+            spell_fg: Rgb(160, 60, 160),
+            url_fg: Rgb(0, 90, 180),
+            preview_heading_fg: Rgb(0, 110, 160),
+            preview_marker_fg: Rgb(150, 150, 150),
+            preview_code_bg: Rgb(238, 238, 238),
+            column_alt_bg: Rgb(236, 242, 248),
+            text_fg: Rgb(32, 32, 32),
+            text_bg: Rgb(255, 255, 255),
+            rainbow_fg: [
+                Rgb(200, 120, 0),
+                Rgb(160, 60, 160),
+                Rgb(0, 110, 204),
+                Rgb(0, 150, 60),
+            ],
+            keyword_fg: Rgb(0, 70, 160),
+            string_fg: Rgb(0, 130, 60),
+            comment_fg: Rgb(130, 130, 130),
+            number_fg: Rgb(180, 90, 0),
+        }
+    }
+}
+
+impl Theme {
+    pub fn named(name: &str) -> Option<Self> {
+        match name {
+            "default" | "light" => Some(Self::default()),
+            "dark" => Some(Self {
+                status_fg: Rgb(220, 220, 220),
+                status_bg: Rgb(48, 48, 48),
+                status_unfocused_fg: Rgb(120, 120, 120),
+                status_segment_fg: segment_colors(
+                    Rgb(110, 170, 210),
+                    Rgb(110, 200, 110),
+                    Rgb(240, 80, 80),
+                ),
+                selection_bg: Rgb(68, 85, 119),
+                line_number_fg: Rgb(100, 100, 100),
+                color_column_bg: Rgb(58, 58, 58),
+                cursor_line_bg: Rgb(40, 40, 40),
+                whitespace_fg: Rgb(85, 85, 85),
+                indent_guide_fg: Rgb(65, 65, 65),
+                scrollbar_bg: Rgb(35, 35, 35),
+                scrollbar_thumb_bg: Rgb(80, 80, 80),
+                search_mark_fg: Rgb(230, 160, 60),
+                search_match_bg: Rgb(100, 80, 30),
+                minimap_fg: Rgb(120, 120, 120),
+                minimap_view_bg: Rgb(50, 50, 50),
+                fold_fg: Rgb(110, 170, 210),
+                error_fg: Rgb(240, 80, 80),
+                warning_fg: Rgb(230, 190, 80),
+                info_fg: Rgb(100, 170, 240),
+                hint_fg: Rgb(150, 150, 150),
+                added_fg: Rgb(110, 200, 110),
+                modified_fg: Rgb(100, 170, 240),
+                removed_fg: Rgb(240, 80, 80),
+                ours_bg: Rgb(35, 65, 40),
+                theirs_bg: Rgb(35, 50, 80),
+                spell_fg: Rgb(210, 130, 210),
+                url_fg: Rgb(100, 170, 240),
+                preview_heading_fg: Rgb(110, 170, 210),
+                preview_marker_fg: Rgb(120, 120, 120),
+                preview_code_bg: Rgb(45, 45, 45),
+                column_alt_bg: Rgb(34, 40, 48),
+                text_fg: Rgb(220, 220, 220),
+                text_bg: Rgb(30, 30, 30),
+                rainbow_fg: [
+                    Rgb(230, 190, 80),
+                    Rgb(210, 130, 210),
+                    Rgb(100, 170, 240),
+                    Rgb(110, 200, 110),
+                ],
+                keyword_fg: Rgb(120, 170, 240),
+                string_fg: Rgb(140, 200, 120),
+                comment_fg: Rgb(120, 120, 120),
+                number_fg: Rgb(230, 160, 90),
+            }),
+            _ => None,
+        }
+    }
+    pub fn diagnostic_fg(&self, severity: Severity) -> Rgb {
+        match severity {
+            Severity::Error => self.error_fg,
+            Severity::Warning => self.warning_fg,
+            Severity::Information => self.info_fg,
+            Severity::Hint => self.hint_fg,
+        }
+    }
+    pub fn change_fg(&self, change: Change) -> Rgb {
+        match change {
+            Change::Added => self.added_fg,
+            Change::Modified => self.modified_fg,
+            Change::Removed => self.removed_fg,
+        }
+    }
+    pub fn token_fg(&self, token: Token) -> Rgb {
+        match token {
+            Token::Keyword => self.keyword_fg,
+            Token::String => self.string_fg,
+            Token::Comment => self.comment_fg,
+            Token::Number => self.number_fg,
+        }
+    }
+    pub fn segment_fg(&self, segment: Segment) -> Rgb {
+        self.status_segment_fg
+            .get(segment.index())
+            .copied()
+            .flatten()
+            .unwrap_or(self.status_fg)
+    }
+    /// Overrides a single color by name; returns false for unknown names.
+    pub fn set_color(&mut self, name: &str, color: Rgb) -> bool {
+        if let Some(segment) = name
+            .strip_prefix("status_")
+            .and_then(|name| name.strip_suffix("_fg"))
+            .and_then(Segment::from_name)
+        {
+            if let Some(slot) = self.status_segment_fg.get_mut(segment.index()) {
+                *slot = Some(color);
+            }
+            return true;
+        }
+        let slot = match name {
+            "status_fg" => &mut self.status_fg,
+            "status_bg" => &mut self.status_bg,
+            "status_unfocused_fg" => &mut self.status_unfocused_fg,
+            "selection_bg" => &mut self.selection_bg,
+            "line_number_fg" => &mut self.line_number_fg,
+            "color_column_bg" => &mut self.color_column_bg,
+            "cursor_line_bg" => &mut self.cursor_line_bg,
+            "whitespace_fg" => &mut self.whitespace_fg,
+            "indent_guide_fg" => &mut self.indent_guide_fg,
+            "scrollbar_bg" => &mut self.scrollbar_bg,
+            "scrollbar_thumb_bg" => &mut self.scrollbar_thumb_bg,
+            "search_mark_fg" => &mut self.search_mark_fg,
+            "search_match_bg" => &mut self.search_match_bg,
+            "minimap_fg" => &mut self.minimap_fg,
+            "minimap_view_bg" => &mut self.minimap_view_bg,
+            "fold_fg" => &mut self.fold_fg,
+            "error_fg" => &mut self.error_fg,
+            "warning_fg" => &mut self.warning_fg,
+            "info_fg" => &mut self.info_fg,
+            "hint_fg" => &mut self.hint_fg,
+            "added_fg" => &mut self.added_fg,
+            "modified_fg" => &mut self.modified_fg,
+            "removed_fg" => &mut self.removed_fg,
+            "ours_bg" => &mut self.ours_bg,
+            "theirs_bg" => &mut self.theirs_bg,
+            "spell_fg" => &mut self.spell_fg,
+            "url_fg" => &mut self.url_fg,
+            "preview_heading_fg" => &mut self.preview_heading_fg,
+            "preview_marker_fg" => &mut self.preview_marker_fg,
+            "preview_code_bg" => &mut self.preview_code_bg,
+            "column_alt_bg" => &mut self.column_alt_bg,
+            "text_fg" => &mut self.text_fg,
+            "text_bg" => &mut self.text_bg,
+            "rainbow_1_fg" => &mut self.rainbow_fg[0],
+            "rainbow_2_fg" => &mut self.rainbow_fg[1],
+            "rainbow_3_fg" => &mut self.rainbow_fg[2],
+            "rainbow_4_fg" => &mut self.rainbow_fg[3],
+            "keyword_fg" => &mut self.keyword_fg,
+            "string_fg" => &mut self.string_fg,
+            "comment_fg" => &mut self.comment_fg,
+            "number_fg" => &mut self.number_fg,
+            _ => return false,
+        };
+        *slot = color;
+        true
+    }
+}
+
+/// Segment colors that set the mode, the git branch and the diagnostics apart.
+fn segment_colors(mode: Rgb, git: Rgb, diagnostics: Rgb) -> [Option<Rgb>; SEGMENTS.len()] {
+    let mut colors = [None; SEGMENTS.len()];
+    for (segment, color) in [
+        (Segment::Mode, mode),
+        (Segment::Git, git),
+        (Segment::Diagnostics, diagnostics),
+    ] {
+        if let Some(slot) = colors.get_mut(segment.index()) {
+            *slot = Some(color);
+        }
+    }
+    colors
+}
+
+/// Parses a `#rrggbb` color.
+pub fn parse_color(text: &str) -> Option<Rgb> {
+    let hex = text.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |range| {
+        hex.get(range)
+            .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+    };
+    Some(Rgb(channel(0..2)?, channel(2..4)?, channel(4..6)?))
+}
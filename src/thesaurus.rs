@@ -0,0 +1,126 @@
+use crate::paths;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error};
+use std::path::{Path, PathBuf};
+
+/// Directories searched for `th_<lang>_v2.dat` and `th_<lang>.dat`, after the user's
+/// own.
+const SYSTEM_DIRS: &[&str] = &[
+    "/usr/share/mythes",
+    "/usr/share/myspell",
+    "/usr/share/myspell/dicts",
+    "/usr/local/share/mythes",
+];
+
+/// One sense of a word: its part of speech, if the thesaurus gives it, and the words
+/// that mean the same.
+pub struct Meaning {
+    pub part: String,
+    /// Each synonym with what the thesaurus notes about it, such as "generic term".
+    pub synonyms: Vec<(String, String)>,
+}
+
+/// The MyThes thesaurus for `lang`, as LibreOffice installs them, in the user's
+/// `thesaurus` directory or the usual system places.
+pub fn find(lang: &str) -> Option<PathBuf> {
+    let names = [format!("th_{}_v2.dat", lang), format!("th_{}.dat", lang)];
+    paths::config_dir()
+        .map(|dir| dir.join("thesaurus"))
+        .into_iter()
+        .chain(SYSTEM_DIRS.iter().map(PathBuf::from))
+        .flat_map(|dir| names.iter().map(move |name| dir.join(name)))
+        .find(|path| path.is_file())
+}
+
+/// Looks `word` up, ignoring case. `.dat` files are MyThes thesauri; any other file
+/// has a group of words meaning the same on each line, separated by commas.
+///
+/// # Errors
+///
+/// Fails when the file cannot be read.
+pub fn look_up(path: &Path, word: &str) -> Result<Vec<Meaning>, Error> {
+    let word = word.to_lowercase();
+    let lines = BufReader::new(File::open(path)?).lines();
+    if path.extension().is_some_and(|extension| extension == "dat") {
+        look_up_mythes(lines, &word)
+    } else {
+        look_up_groups(lines, &word)
+    }
+}
+
+/// After a line naming the encoding, each entry is a `word|count` line followed by
+/// `count` senses, each `(part of speech)|synonym|synonym (note)|...`.
+fn look_up_mythes(
+    mut lines: impl Iterator<Item = Result<String, Error>>,
+    word: &str,
+) -> Result<Vec<Meaning>, Error> {
+    lines.next().transpose()?;
+    while let Some(line) = lines.next().transpose()? {
+        let (entry, count) = match line.split_once('|') {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let count: usize = count.trim().parse().unwrap_or(0);
+        if entry.to_lowercase() != word {
+            for _ in 0..count {
+                lines.next().transpose()?;
+            }
+            continue;
+        }
+        let mut meanings = Vec::new();
+        for _ in 0..count {
+            let line = match lines.next().transpose()? {
+                Some(line) => line,
+                None => break,
+            };
+            let mut fields = line.split('|');
+            let part = fields
+                .next()
+                .unwrap_or_default()
+                .trim_matches(|c| c == '(' || c == ')')
+                .to_string();
+            let synonyms = fields.filter_map(split_note).collect();
+            meanings.push(Meaning { part, synonyms });
+        }
+        return Ok(meanings);
+    }
+    Ok(Vec::new())
+}
+
+fn look_up_groups(
+    lines: impl Iterator<Item = Result<String, Error>>,
+    word: &str,
+) -> Result<Vec<Meaning>, Error> {
+    let mut meanings = Vec::new();
+    for line in lines {
+        let line = line?;
+        let words: Vec<&str> = line
+            .split(',')
+            .map(str::trim)
+            .filter(|word| !word.is_empty())
+            .collect();
+        if words.iter().any(|other| other.to_lowercase() == word) {
+            meanings.push(Meaning {
+                part: String::new(),
+                synonyms: words
+                    .into_iter()
+                    .filter(|other| other.to_lowercase() != word)
+                    .map(|other| (other.to_string(), String::new()))
+                    .collect(),
+            });
+        }
+    }
+    Ok(meanings)
+}
+
+/// Splits `happiness (generic term)` into the word and its note.
+fn split_note(field: &str) -> Option<(String, String)> {
+    let field = field.trim();
+    if field.is_empty() {
+        return None;
+    }
+    Some(match field.split_once(" (") {
+        Some((word, note)) => (word.to_string(), note.trim_end_matches(')').to_string()),
+        None => (field.to_string(), String::new()),
+    })
+}
@@ -0,0 +1,241 @@
+use std::collections::BTreeMap;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A TOML value. Tables are flattened into dotted keys, so there is no table variant.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::String(_) => "string",
+            Self::Integer(_) => "integer",
+            Self::Float(_) => "float",
+            Self::Boolean(_) => "boolean",
+            Self::Array(_) => "array",
+        }
+    }
+}
+
+/// Maps dotted keys such as `backup.keep` to their values.
+pub type Table = BTreeMap<String, Value>;
+
+/// Parses the subset of TOML hecto's configuration needs: `[table]` headers, dotted
+/// keys, strings, integers, floats, booleans and (possibly multi-line) arrays.
+pub fn parse(text: &str) -> Result<Table, String> {
+    let mut table = Table::new();
+    let mut prefix = String::new();
+    let mut lines = text.lines().enumerate();
+    while let Some((index, line)) = lines.next() {
+        let line_number = index.saturating_add(1);
+        let error = |message: String| format!("line {}: {}", line_number, message);
+        let line = strip_comment(line);
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with("[[") {
+            return Err(error("arrays of tables are not supported".to_string()));
+        }
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header
+                .strip_suffix(']')
+                .ok_or_else(|| error("unterminated table header".to_string()))?;
+            prefix = parse_key(header).map_err(error)?;
+            continue;
+        }
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| error("expected `key = value`".to_string()))?;
+        let key = parse_key(key).map_err(error)?;
+        let mut value = value.trim().to_string();
+        // Arrays may span several lines; keep reading until the brackets balance.
+        while value.starts_with('[') && !brackets_balanced(&value) {
+            let (_, next) = lines
+                .next()
+                .ok_or_else(|| error("unterminated array".to_string()))?;
+            value.push(' ');
+            value.push_str(strip_comment(next).trim());
+        }
+        let mut chars = value.chars().peekable();
+        let parsed = parse_value(&mut chars).map_err(error)?;
+        skip_whitespace(&mut chars);
+        if chars.peek().is_some() {
+            return Err(error(format!("unexpected trailing text in `{}`", value)));
+        }
+        let key = if prefix.is_empty() {
+            key
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        if table.insert(key.clone(), parsed).is_some() {
+            return Err(error(format!("duplicate key `{}`", key)));
+        }
+    }
+    Ok(table)
+}
+
+/// Removes a trailing `#` comment, leaving `#` inside strings alone.
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(open), _) if c == open && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') => return line.get(..index).unwrap_or(line),
+            _ => (),
+        }
+        escaped = false;
+    }
+    line
+}
+
+fn brackets_balanced(value: &str) -> bool {
+    let mut depth = 0_usize;
+    let mut quote = None;
+    for c in value.chars() {
+        match (quote, c) {
+            (Some(open), _) if c == open => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '[') => depth = depth.saturating_add(1),
+            (None, ']') => depth = depth.saturating_sub(1),
+            _ => (),
+        }
+    }
+    depth == 0
+}
+
+fn parse_key(key: &str) -> Result<String, String> {
+    let mut parts = Vec::new();
+    for part in key.split('.') {
+        let part = part.trim();
+        let part = part
+            .strip_prefix('"')
+            .and_then(|part| part.strip_suffix('"'))
+            .unwrap_or(part);
+        let valid = !part.is_empty()
+            && part
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(format!("invalid key `{}`", key.trim()));
+        }
+        parts.push(part);
+    }
+    Ok(parts.join("."))
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().is_some_and(|c| c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    skip_whitespace(chars);
+    match chars.peek() {
+        Some('"') => {
+            chars.next();
+            parse_basic_string(chars).map(Value::String)
+        }
+        Some('\'') => {
+            chars.next();
+            let string: String = chars.by_ref().take_while(|c| *c != '\'').collect();
+            Ok(Value::String(string))
+        }
+        Some('[') => {
+            chars.next();
+            parse_array(chars)
+        }
+        Some('{') => Err("inline tables are not supported".to_string()),
+        Some(_) => {
+            let mut word = String::new();
+            while let Some(c) = chars.peek() {
+                if c.is_whitespace() || *c == ',' || *c == ']' {
+                    break;
+                }
+                word.push(*c);
+                chars.next();
+            }
+            parse_scalar(&word)
+        }
+        None => Err("missing value".to_string()),
+    }
+}
+
+fn parse_basic_string(chars: &mut Peekable<Chars>) -> Result<String, String> {
+    let mut string = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => return Ok(string),
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('\\') => '\\',
+                    Some('"') => '"',
+                    Some('u') => {
+                        let code: String = chars.by_ref().take(4).collect();
+                        u32::from_str_radix(&code, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape `\\u{}`", code))?
+                    }
+                    other => return Err(format!("invalid escape `\\{}`", other.unwrap_or(' '))),
+                };
+                string.push(escaped);
+            }
+            _ => string.push(c),
+        }
+    }
+    Err("unterminated string".to_string())
+}
+
+fn parse_array(chars: &mut Peekable<Chars>) -> Result<Value, String> {
+    let mut items = Vec::new();
+    loop {
+        skip_whitespace(chars);
+        if chars.peek() == Some(&']') {
+            chars.next();
+            return Ok(Value::Array(items));
+        }
+        items.push(parse_value(chars)?);
+        skip_whitespace(chars);
+        match chars.next() {
+            Some(',') => (),
+            Some(']') => return Ok(Value::Array(items)),
+            _ => return Err("expected `,` or `]` in array".to_string()),
+        }
+    }
+}
+
+fn parse_scalar(word: &str) -> Result<Value, String> {
+    match word {
+        "true" => return Ok(Value::Boolean(true)),
+        "false" => return Ok(Value::Boolean(false)),
+        _ => (),
+    }
+    let digits = word.replace('_', "");
+    if let Ok(integer) = digits.parse::<i64>() {
+        return Ok(Value::Integer(integer));
+    }
+    if digits.chars().any(|c| c.is_ascii_digit()) {
+        if let Ok(float) = digits.parse::<f64>() {
+            return Ok(Value::Float(float));
+        }
+    }
+    Err(format!("invalid value `{}`", word))
+}
@@ -0,0 +1,173 @@
+use std::fmt::Write;
+
+/// A rewrite of selected text.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    HexDump,
+    Rot13,
+}
+
+/// Every transform, with the name `:transform` takes and a description for the menu.
+pub const TRANSFORMS: &[(Transform, &str, &str)] = &[
+    (Transform::Base64Encode, "base64", "Base64 encode"),
+    (Transform::Base64Decode, "unbase64", "Base64 decode"),
+    (Transform::UrlEncode, "urlencode", "URL encode"),
+    (Transform::UrlDecode, "urldecode", "URL decode"),
+    (Transform::HexDump, "hexdump", "Hex dump"),
+    (Transform::Rot13, "rot13", "ROT13"),
+];
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub fn named(name: &str) -> Option<Transform> {
+    TRANSFORMS
+        .iter()
+        .find(|(_, transform_name, _)| *transform_name == name)
+        .map(|(transform, _, _)| *transform)
+}
+
+/// Applies `transform` to `text`, or says why the text cannot be decoded.
+pub fn apply(transform: Transform, text: &str) -> Result<String, String> {
+    match transform {
+        Transform::Base64Encode => Ok(base64_encode(text.as_bytes())),
+        Transform::Base64Decode => utf8(base64_decode(text)?),
+        Transform::UrlEncode => Ok(url_encode(text)),
+        Transform::UrlDecode => utf8(url_decode(text)?),
+        Transform::HexDump => Ok(hex_dump(text.as_bytes())),
+        Transform::Rot13 => Ok(text.chars().map(rot13).collect()),
+    }
+}
+
+fn utf8(bytes: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(bytes).map_err(|_| "the decoded bytes are not UTF-8 text".to_string())
+}
+
+/// Encodes `bytes` as standard Base64 with padding.
+#[allow(clippy::integer_arithmetic)]
+pub(crate) fn base64_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let b = |index: usize| u32::from(chunk.get(index).copied().unwrap_or(0));
+        let group = (b(0) << 16) | (b(1) << 8) | b(2);
+        for index in 0..4 {
+            if index <= chunk.len() {
+                let sextet = (group >> (18 - 6 * index)) & 0x3f;
+                encoded.extend(
+                    BASE64
+                        .get(usize::try_from(sextet).unwrap_or(0))
+                        .map(|c| char::from(*c)),
+                );
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes standard or URL-safe Base64, ignoring line breaks and missing padding.
+#[allow(clippy::integer_arithmetic)]
+fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut group = 0_u32;
+    let mut bits = 0;
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        let value = match c {
+            'A'..='Z' => u32::from(c) - u32::from('A'),
+            'a'..='z' => u32::from(c) - u32::from('a') + 26,
+            '0'..='9' => u32::from(c) - u32::from('0') + 52,
+            '+' | '-' => 62,
+            '/' | '_' => 63,
+            '=' => break,
+            c => return Err(format!("`{}` is not a Base64 character", c)),
+        };
+        group = (group << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push(u8::try_from((group >> bits) & 0xff).unwrap_or(0));
+        }
+    }
+    Ok(bytes)
+}
+
+/// Percent-encodes everything but the characters RFC 3986 leaves unreserved.
+fn url_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+/// Decodes `%XX` escapes, and `+` as a space as forms write it.
+fn url_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let mut input = text.bytes();
+    while let Some(byte) = input.next() {
+        match byte {
+            b'%' => {
+                let digits: Vec<u8> = input.by_ref().take(2).collect();
+                let hex = String::from_utf8_lossy(&digits).to_string();
+                let value = u8::from_str_radix(&hex, 16)
+                    .ok()
+                    .filter(|_| hex.len() == 2)
+                    .ok_or_else(|| format!("`%{}` is not a percent escape", hex))?;
+                bytes.push(value);
+            }
+            b'+' => bytes.push(b' '),
+            byte => bytes.push(byte),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Sixteen bytes a line as offset, hex pairs and printable ASCII, like `xxd`.
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = String::new();
+    for (line, chunk) in bytes.chunks(16).enumerate() {
+        let _ = write!(dump, "{:08x}: ", line.saturating_mul(16));
+        for index in 0..16 {
+            match chunk.get(index) {
+                Some(byte) => {
+                    let _ = write!(dump, "{:02x}", byte);
+                }
+                None => dump.push_str("  "),
+            }
+            if index % 2 == 1 {
+                dump.push(' ');
+            }
+        }
+        dump.push(' ');
+        dump.extend(chunk.iter().map(|byte| {
+            if byte.is_ascii_graphic() || *byte == b' ' {
+                char::from(*byte)
+            } else {
+                '.'
+            }
+        }));
+        dump.push('\n');
+    }
+    dump.pop();
+    dump
+}
+
+fn rot13(c: char) -> char {
+    let rotate = |base: u8| {
+        let offset = u8::try_from(c).unwrap_or(base).saturating_sub(base);
+        char::from(base.saturating_add(offset.saturating_add(13) % 26))
+    };
+    match c {
+        'a'..='z' => rotate(b'a'),
+        'A'..='Z' => rotate(b'A'),
+        c => c,
+    }
+}
@@ -0,0 +1,249 @@
+use crate::commands::{self, Bindings, Command};
+use crate::config::Keymap;
+use crate::Document;
+use crate::Position;
+
+pub const TITLE: &str = "[Tutor]";
+/// The width lessons are wrapped to.
+const WIDTH: usize = 72;
+
+/// What finishes a lesson, checked against its practice line: the one starting with
+/// the lesson's number and `>`.
+enum Goal {
+    /// The cursor at the end of the practice line: on its `X`, or after it as End
+    /// leaves it in insert mode.
+    ReachEnd,
+    /// The practice line reading this after its marker.
+    Text(&'static str),
+    /// A save, which the tutor takes without writing anything.
+    Saved,
+    /// A search that left the cursor on this word of the practice line.
+    Found(&'static str),
+}
+
+struct Lesson {
+    title: &'static str,
+    practice: &'static str,
+    goal: Goal,
+}
+
+const LESSONS: &[Lesson] = &[
+    Lesson {
+        title: "Moving the cursor",
+        practice: "Go to the end of this line, onto the X:  . . . . . . . . . . X",
+        goal: Goal::ReachEnd,
+    },
+    Lesson {
+        title: "Deleting text",
+        practice: "The ccow jumpedd over thhe mooon.",
+        goal: Goal::Text("The cow jumped over the moon."),
+    },
+    Lesson {
+        title: "Inserting text",
+        practice: "There is text missing this .",
+        goal: Goal::Text("There is some text missing from this line."),
+    },
+    Lesson {
+        title: "Saving",
+        practice: "Nothing to change here; just save.",
+        goal: Goal::Saved,
+    },
+    Lesson {
+        title: "Searching",
+        practice: "Somewhere on this line, among all these words, hides the treasure.",
+        goal: Goal::Found("treasure"),
+    },
+];
+
+/// How far the lessons of the tutor buffer have got.
+#[derive(Default)]
+pub struct Progress {
+    /// Whether the tutor buffer was saved, which finishes the saving lesson.
+    pub saved: bool,
+    done: Vec<bool>,
+}
+
+impl Progress {
+    /// Checks the lessons against the tutor buffer and the cursor in it, with
+    /// `searched` telling whether a search has been made. Returns the message to show
+    /// when a lesson was just finished.
+    pub fn update(
+        &mut self,
+        document: &Document,
+        cursor: &Position,
+        searched: bool,
+    ) -> Option<String> {
+        self.done.resize(LESSONS.len(), false);
+        let mut finished = None;
+        for (index, (lesson, done)) in LESSONS.iter().zip(&mut self.done).enumerate() {
+            if *done {
+                continue;
+            }
+            let line = practice_line(document, index);
+            let practice = line.and_then(|y| document.row(y)).map(|row| {
+                let text = row.as_str().trim_start();
+                text.split_once("> ").map_or("", |(_, rest)| rest)
+            });
+            let on_line = line == Some(cursor.y);
+            // Whether the cursor is in the word `text`.
+            let under_cursor = |text: &str| {
+                let offset = document
+                    .row(cursor.y)?
+                    .graphemes()
+                    .take(cursor.x)
+                    .map(str::len)
+                    .sum::<usize>();
+                let row = document.row(cursor.y)?.as_str();
+                let start = row
+                    .get(..offset)?
+                    .rfind(' ')
+                    .map_or(0, |space| space.saturating_add(1));
+                let end = row
+                    .get(offset..)?
+                    .find(|c: char| !c.is_alphanumeric())
+                    .map_or(row.len(), |end| offset.saturating_add(end));
+                Some(row.get(start..end)? == text)
+            };
+            *done = match lesson.goal {
+                Goal::ReachEnd => {
+                    on_line
+                        && document
+                            .row(cursor.y)
+                            .is_some_and(|row| cursor.x.saturating_add(1) >= row.len())
+                }
+                Goal::Text(expected) => practice == Some(expected),
+                Goal::Saved => self.saved,
+                Goal::Found(word) => searched && on_line && under_cursor(word) == Some(true),
+            };
+            if *done && finished.is_none() {
+                finished = Some(index);
+            }
+        }
+        let index = finished?;
+        let number = index.saturating_add(1);
+        Some(match self.done.iter().position(|done| !done) {
+            None => format!("Lesson {} done, and with it the tutor. Well done!", number),
+            Some(next) => format!(
+                "Lesson {} done! Next: lesson {}, {}.",
+                number,
+                next.saturating_add(1),
+                LESSONS.get(next).map_or("", |lesson| lesson.title)
+            ),
+        })
+    }
+}
+
+/// The line of lesson `index`'s practice text.
+fn practice_line(document: &Document, index: usize) -> Option<usize> {
+    let marker = format!("{}> ", index.saturating_add(1));
+    (0..document.len()).find(|y| {
+        document
+            .row(*y)
+            .is_some_and(|row| row.as_str().trim_start().starts_with(&marker))
+    })
+}
+
+/// The keys bound to `command`, as the tutor names them.
+fn keys(bindings: &Bindings, command: Command) -> String {
+    let keys: Vec<String> = bindings
+        .keys_for(command)
+        .into_iter()
+        .map(commands::key_name)
+        .collect();
+    if keys.is_empty() {
+        "(unbound)".to_string()
+    } else {
+        keys.join(" or ")
+    }
+}
+
+/// How to do lesson `index` with `keymap`.
+fn steps(index: usize, keymap: Keymap, bindings: &Bindings) -> String {
+    let vim = keymap == Keymap::Vim;
+    match index {
+        0 if vim => "In normal mode, h j k l move left, down, up and right, as do the arrow \
+            keys. 0 and $ go to the start and end of a line, and a count before a key, as \
+            in 5j, repeats it. Move down to the line below and onto its X."
+            .to_string(),
+        0 => "The arrow keys move the cursor, and Home and End go to the start and end of \
+            a line. Move down to the line below and onto its X."
+            .to_string(),
+        1 if vim => "x deletes the character under the cursor. Fix the line below by moving \
+            onto each doubled letter and pressing x; u undoes a mistake."
+            .to_string(),
+        1 => format!(
+            "Backspace deletes the character before the cursor and Delete the one under \
+            it. Take the doubled letters out of the line below; {} undoes a mistake.",
+            keys(bindings, Command::Undo)
+        ),
+        2 if vim => "i starts inserting before the cursor and a after it, and Esc goes back \
+            to normal mode. Type the missing words into the line below, so that it reads: \
+            There is some text missing from this line."
+            .to_string(),
+        2 => "Text you type goes in at the cursor. Type the missing words into the line \
+            below, so that it reads: There is some text missing from this line."
+            .to_string(),
+        3 if vim => "In normal mode, :w and Enter saves, :wq saves and quits, and :q! quits \
+            without saving. Save now. The tutor is only kept in memory, so nothing is \
+            written; in a file the changes would be."
+            .to_string(),
+        3 => format!(
+            "{} saves and {} quits. Save now. The tutor is only kept in memory, so nothing \
+            is written; in a file the changes would be.",
+            keys(bindings, Command::Save),
+            keys(bindings, Command::Quit)
+        ),
+        4 if vim => "/ searches forward: type a word and press Enter, then n and N go to the \
+            next and previous match. Search for treasure to find it below."
+            .to_string(),
+        _ => format!(
+            "{} searches: type a word and press Enter to go to it. Search for treasure to \
+            find it below.",
+            keys(bindings, Command::Find)
+        ),
+    }
+}
+
+/// Breaks `text` into lines of at most `WIDTH` characters between words.
+fn wrap(text: &str) -> Vec<String> {
+    let mut lines = vec![String::new()];
+    for word in text.split_whitespace() {
+        let line = lines
+            .last_mut()
+            .filter(|line| line.chars().count().saturating_add(word.chars().count()) < WIDTH);
+        match line {
+            Some(line) if !line.is_empty() => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            Some(line) => line.push_str(word),
+            None => lines.push(word.to_string()),
+        }
+    }
+    lines
+}
+
+/// Generates the tutor for `keymap`, naming the keys `bindings` has for commands.
+pub fn text(keymap: Keymap, bindings: &Bindings) -> String {
+    let mut lines = vec![
+        "Welcome to the hecto tutor".to_string(),
+        String::new(),
+        "This buffer is yours to edit: each lesson below has a practice line".to_string(),
+        "marked with its number and >, and the message bar says when a lesson".to_string(),
+        "is done. Nothing here is ever written to disk.".to_string(),
+    ];
+    for (index, lesson) in LESSONS.iter().enumerate() {
+        let number = index.saturating_add(1);
+        lines.push(String::new());
+        lines.push(format!("Lesson {}: {}", number, lesson.title));
+        lines.push(String::new());
+        lines.extend(wrap(&steps(index, keymap, bindings)));
+        lines.push(String::new());
+        lines.push(format!("  {}> {}", number, lesson.practice));
+    }
+    lines.push(String::new());
+    lines.push("That is the basics. :help lists every key, command and option.".to_string());
+    let mut text = lines.join("\n");
+    text.push('\n');
+    text
+}
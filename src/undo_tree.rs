@@ -0,0 +1,426 @@
+use crate::statusline;
+use crate::Position;
+use crate::Row;
+use std::cmp;
+use std::time::{Duration, SystemTime};
+
+/// Number of buffer states kept per buffer.
+const UNDO_LEVELS: usize = 200;
+
+/// How far `:earlier` and `:later` move through the history.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Span {
+    /// Changes in the order they were made, whatever their branch.
+    Steps(usize),
+    Time(Duration),
+    /// States the buffer was written in.
+    Saves(usize),
+}
+
+impl Span {
+    /// Reads a count with an optional unit, as in `10`, `30s`, `5m`, `2h`, `1d` or `3f`.
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let text = text.trim();
+        let digits = text.chars().take_while(char::is_ascii_digit).count();
+        let (count, unit) = text.split_at(digits);
+        let count = if count.is_empty() {
+            1
+        } else {
+            count
+                .parse::<u64>()
+                .map_err(|_| format!("`{}` is too large", count))?
+        };
+        let seconds = |scale: u64| Self::Time(Duration::from_secs(count.saturating_mul(scale)));
+        let count_usize = usize::try_from(count).unwrap_or(usize::MAX);
+        match unit {
+            "" => Ok(Self::Steps(count_usize)),
+            "s" => Ok(seconds(1)),
+            "m" => Ok(seconds(60)),
+            "h" => Ok(seconds(3600)),
+            "d" => Ok(seconds(86400)),
+            "f" => Ok(Self::Saves(count_usize)),
+            _ => Err(format!(
+                "`{}` is not a count of changes or a time such as 10s, 5m, 2h, 1d or 3f",
+                text
+            )),
+        }
+    }
+}
+
+/// Lines `start..start + removed.len()` of one state replaced by `added` in the next.
+struct Splice {
+    start: usize,
+    removed: Vec<Row>,
+    added: Vec<Row>,
+}
+
+impl Splice {
+    /// The lines between the common start and end of `before` and `after`, or `None`
+    /// when they are the same.
+    fn between(before: &[Row], after: &[Row]) -> Option<Self> {
+        let same = |a: &Row, b: &Row| a.as_str() == b.as_str();
+        let start = before
+            .iter()
+            .zip(after)
+            .take_while(|(a, b)| same(a, b))
+            .count();
+        let shorter = cmp::min(before.len(), after.len()).saturating_sub(start);
+        let end = before
+            .iter()
+            .rev()
+            .zip(after.iter().rev())
+            .take(shorter)
+            .take_while(|(a, b)| same(a, b))
+            .count();
+        let removed = before.get(start..before.len().saturating_sub(end))?;
+        let added = after.get(start..after.len().saturating_sub(end))?;
+        if removed.is_empty() && added.is_empty() {
+            return None;
+        }
+        Some(Self {
+            start,
+            removed: removed.to_vec(),
+            added: added.to_vec(),
+        })
+    }
+    fn apply(&self, rows: &mut Vec<Row>) {
+        replace(rows, self.start, self.removed.len(), &self.added);
+    }
+    fn revert(&self, rows: &mut Vec<Row>) {
+        replace(rows, self.start, self.added.len(), &self.removed);
+    }
+}
+
+fn replace(rows: &mut Vec<Row>, start: usize, len: usize, lines: &[Row]) {
+    let end = cmp::min(start.saturating_add(len), rows.len());
+    let start = cmp::min(start, end);
+    rows.splice(start..end, lines.iter().cloned());
+}
+
+/// A state of the buffer, reached by a change from its parent.
+struct Node {
+    id: usize,
+    parent: Option<usize>,
+    /// The splices that turn the parent state into this one, in order. Only the
+    /// changed lines are kept, so a long history of small edits stays small.
+    changes: Vec<Splice>,
+    /// Where the change that led here was made.
+    position: Position,
+    time: SystemTime,
+    /// Whether the buffer was written to its file in this state.
+    saved: bool,
+    /// The child redo goes to: the newest, or the one last undone or jumped through.
+    redo: Option<usize>,
+}
+
+/// Undo history that keeps every branch: a change made after undoing starts a new
+/// branch instead of discarding the undone states.
+pub struct UndoTree {
+    nodes: Vec<Node>,
+    current: usize,
+    next_id: usize,
+    /// The contents before the open step's change, until `finish` keeps just the
+    /// lines it changed.
+    before: Option<Vec<Row>>,
+}
+
+impl Default for UndoTree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node {
+                id: 0,
+                parent: None,
+                changes: Vec::new(),
+                position: Position::default(),
+                time: SystemTime::now(),
+                saved: false,
+                redo: None,
+            }],
+            current: 0,
+            next_id: 1,
+            before: None,
+        }
+    }
+}
+
+impl UndoTree {
+    /// Starts a new state as a child of the current one, given the contents before the
+    /// change and where it is made. The step stays open until `finish`.
+    pub fn record(&mut self, rows: &[Row], at: &Position) {
+        self.finish(rows);
+        let id = self.next_id;
+        self.next_id = self.next_id.saturating_add(1);
+        let parent = self.current;
+        if let Some(node) = self.node_mut(parent) {
+            node.redo = Some(id);
+        }
+        self.before = Some(rows.to_vec());
+        self.nodes.push(Node {
+            id,
+            parent: Some(parent),
+            changes: Vec::new(),
+            position: at.clone(),
+            time: SystemTime::now(),
+            saved: false,
+            redo: None,
+        });
+        self.current = id;
+        self.prune();
+    }
+    /// Closes the open step, keeping the lines it changed to reach `rows`.
+    pub fn finish(&mut self, rows: &[Row]) {
+        let splice = match self.before.take() {
+            Some(before) => Splice::between(&before, rows),
+            None => return,
+        };
+        let current = self.current;
+        if let (Some(node), Some(splice)) = (self.node_mut(current), splice) {
+            node.changes.push(splice);
+        }
+    }
+    /// Moves to the parent state, changing `rows` to its contents. Returns where the
+    /// undone change was made.
+    pub fn undo(&mut self, rows: &mut Vec<Row>) -> Option<Position> {
+        let node = self.node(self.current)?;
+        let (parent, position, child) = (node.parent?, node.position.clone(), node.id);
+        if let Some(parent) = self.node_mut(parent) {
+            parent.redo = Some(child);
+        }
+        self.move_to(parent, rows);
+        Some(position)
+    }
+    /// Moves to the state redo leads to, changing `rows` to its contents.
+    pub fn redo(&mut self, rows: &mut Vec<Row>) -> Option<Position> {
+        let child = self.node(self.current)?.redo?;
+        let position = self.node(child)?.position.clone();
+        self.move_to(child, rows);
+        Some(position)
+    }
+    /// Moves to state `id` on any branch, so that redo from its ancestors leads back
+    /// to it. Returns where the change that led there was made.
+    pub fn jump(&mut self, id: usize, rows: &mut Vec<Row>) -> Option<Position> {
+        let position = self.node(id)?.position.clone();
+        if id == self.current {
+            return None;
+        }
+        let mut child = id;
+        while let Some(parent) = self.node(child).and_then(|node| node.parent) {
+            if let Some(node) = self.node_mut(parent) {
+                node.redo = Some(child);
+            }
+            child = parent;
+        }
+        self.move_to(id, rows);
+        Some(position)
+    }
+    pub fn current(&self) -> usize {
+        self.current
+    }
+    /// Records that the buffer was written in the current state.
+    pub fn mark_saved(&mut self) {
+        let current = self.current;
+        if let Some(node) = self.node_mut(current) {
+            node.saved = true;
+        }
+    }
+    /// The state `span` before the current one, in the order states were reached, or
+    /// `None` when the current state is the oldest.
+    pub fn earlier(&self, span: Span) -> Option<usize> {
+        if span == Span::Steps(0) || span == Span::Saves(0) {
+            return None;
+        }
+        let current = self.node(self.current)?;
+        let before: Vec<&Node> = self
+            .nodes
+            .iter()
+            .filter(|node| node.id < current.id)
+            .collect();
+        let oldest = before.first()?.id;
+        let target = match span {
+            Span::Steps(count) => before
+                .len()
+                .checked_sub(count)
+                .and_then(|index| before.get(index))
+                .map(|node| node.id),
+            Span::Time(duration) => current.time.checked_sub(duration).and_then(|time| {
+                before
+                    .iter()
+                    .rev()
+                    .find(|node| node.time <= time)
+                    .map(|node| node.id)
+            }),
+            Span::Saves(count) => {
+                let saves: Vec<usize> = before
+                    .iter()
+                    .filter(|node| node.saved)
+                    .map(|node| node.id)
+                    .collect();
+                saves
+                    .len()
+                    .checked_sub(count)
+                    .and_then(|index| saves.get(index))
+                    .copied()
+            }
+        };
+        Some(target.unwrap_or(oldest))
+    }
+    /// The state `span` after the current one, or `None` when it is the newest.
+    pub fn later(&self, span: Span) -> Option<usize> {
+        if span == Span::Steps(0) || span == Span::Saves(0) {
+            return None;
+        }
+        let current = self.node(self.current)?;
+        let after: Vec<&Node> = self
+            .nodes
+            .iter()
+            .filter(|node| node.id > current.id)
+            .collect();
+        let newest = after.last()?.id;
+        let target = match span {
+            Span::Steps(count) => count
+                .checked_sub(1)
+                .and_then(|index| after.get(index))
+                .map(|node| node.id),
+            // Unlike counts, a time too short to reach the next state moves nowhere.
+            Span::Time(duration) => {
+                let time = current.time.checked_add(duration);
+                return after
+                    .iter()
+                    .take_while(|node| time.is_none_or(|time| node.time <= time))
+                    .last()
+                    .map(|node| node.id);
+            }
+            Span::Saves(count) => count.checked_sub(1).and_then(|index| {
+                after
+                    .iter()
+                    .filter(|node| node.saved)
+                    .nth(index)
+                    .map(|node| node.id)
+            }),
+        };
+        Some(target.unwrap_or(newest))
+    }
+    /// The tree drawn one state a line, oldest first, with the id of each state. Each
+    /// branch hangs off the state it left, and the newest branch carries on below.
+    pub fn lines(&self) -> Vec<(usize, String)> {
+        let mut lines = Vec::new();
+        if let Some(root) = self.nodes.iter().find(|node| node.parent.is_none()) {
+            self.render(root.id, String::new(), "", &mut lines);
+        }
+        lines
+    }
+    fn render(&self, mut id: usize, first: String, rest: &str, lines: &mut Vec<(usize, String)>) {
+        let mut prefix = first;
+        loop {
+            let marker = if id == self.current { '●' } else { '○' };
+            let (time, saved) = self.node(id).map_or_else(Default::default, |node| {
+                (statusline::time_of_day(node.time), node.saved)
+            });
+            let saved = if saved { "  saved" } else { "" };
+            lines.push((
+                id,
+                format!("{}{} {:>3}  {}{}", prefix, marker, id, time, saved),
+            ));
+            let children: Vec<usize> = self
+                .nodes
+                .iter()
+                .filter(|node| node.parent == Some(id))
+                .map(|node| node.id)
+                .collect();
+            let (newest, older) = match children.split_last() {
+                Some(split) => split,
+                None => break,
+            };
+            for child in older {
+                self.render(*child, format!("{}├─", rest), &format!("{}│ ", rest), lines);
+            }
+            id = *newest;
+            prefix = rest.to_string();
+        }
+    }
+    /// The states from `id` up to the root, `id` first.
+    fn ancestors(&self, id: usize) -> Vec<usize> {
+        let mut ancestors = vec![id];
+        let mut id = id;
+        while let Some(parent) = self.node(id).and_then(|node| node.parent) {
+            ancestors.push(parent);
+            id = parent;
+        }
+        ancestors
+    }
+    /// Changes `rows` from the current state to state `id`: back up to the state both
+    /// descend from, then forward along the branch to `id`.
+    fn move_to(&mut self, id: usize, rows: &mut Vec<Row>) {
+        self.finish(rows);
+        let up = self.ancestors(self.current);
+        let down = self.ancestors(id);
+        let common = down.iter().copied().find(|id| up.contains(id));
+        for id in up.iter().take_while(|id| Some(**id) != common) {
+            for splice in self
+                .node(*id)
+                .iter()
+                .flat_map(|node| node.changes.iter().rev())
+            {
+                splice.revert(rows);
+            }
+        }
+        let below = down.iter().take_while(|id| Some(**id) != common).count();
+        for id in down.iter().take(below).rev() {
+            for splice in self.node(*id).iter().flat_map(|node| node.changes.iter()) {
+                splice.apply(rows);
+            }
+        }
+        self.current = id;
+    }
+    /// Drops the oldest states beyond `UNDO_LEVELS`, splicing out states on a single
+    /// line of history so that no branch is cut off. The changes of a state spliced
+    /// out carry over to its child; those of the root are no longer needed.
+    fn prune(&mut self) {
+        while self.nodes.len() > UNDO_LEVELS {
+            let children_of = |id: usize| -> Vec<usize> {
+                self.nodes
+                    .iter()
+                    .filter(|node| node.parent == Some(id))
+                    .map(|node| node.id)
+                    .collect()
+            };
+            let oldest = self
+                .nodes
+                .iter()
+                .filter(|node| node.id != self.current)
+                .map(|node| (node.id, node.parent, children_of(node.id)))
+                .find(|(_, _, children)| children.len() <= 1);
+            let (id, parent, children) = match oldest {
+                Some(oldest) => oldest,
+                None => break,
+            };
+            let changes = self
+                .node_mut(id)
+                .map(|node| std::mem::take(&mut node.changes))
+                .unwrap_or_default();
+            if let Some(child) = children.first().and_then(|child| self.node_mut(*child)) {
+                if parent.is_some() {
+                    let later = std::mem::replace(&mut child.changes, changes);
+                    child.changes.extend(later);
+                } else {
+                    child.changes.clear();
+                }
+            }
+            for node in &mut self.nodes {
+                if children.contains(&node.id) {
+                    node.parent = parent;
+                }
+                if node.redo == Some(id) {
+                    node.redo = children.first().copied();
+                }
+            }
+            self.nodes.retain(|node| node.id != id);
+        }
+    }
+    fn node(&self, id: usize) -> Option<&Node> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+    fn node_mut(&mut self, id: usize) -> Option<&mut Node> {
+        self.nodes.iter_mut().find(|node| node.id == id)
+    }
+}
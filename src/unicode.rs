@@ -0,0 +1,532 @@
+use std::fs;
+
+/// Where distributions install the Unicode Character Database's list of names.
+const UNICODE_DATA: &[&str] = &[
+    "/usr/share/unicode/UnicodeData.txt",
+    "/usr/share/unicode-data/UnicodeData.txt",
+    "/usr/share/unicode/ucd/UnicodeData.txt",
+];
+
+/// RFC 1345 style digraphs: two keys for a character that is hard to type, such as
+/// `a:` for ä. The base letter comes first, then a mark: `!` grave, `'` acute,
+/// `>` circumflex, `?` tilde, `-` macron, `(` breve, `.` dot, `:` diaeresis, `0` ring,
+/// `"` double acute, `<` caron, `,` cedilla and `;` ogonek. Greek letters take `*`.
+pub const DIGRAPHS: &[(&str, char, &str)] = &[
+    ("A!", 'À', "LATIN CAPITAL LETTER A WITH GRAVE"),
+    ("A'", 'Á', "LATIN CAPITAL LETTER A WITH ACUTE"),
+    ("A>", 'Â', "LATIN CAPITAL LETTER A WITH CIRCUMFLEX"),
+    ("A?", 'Ã', "LATIN CAPITAL LETTER A WITH TILDE"),
+    ("A-", 'Ā', "LATIN CAPITAL LETTER A WITH MACRON"),
+    ("A(", 'Ă', "LATIN CAPITAL LETTER A WITH BREVE"),
+    ("A.", 'Ȧ', "LATIN CAPITAL LETTER A WITH DOT ABOVE"),
+    ("A:", 'Ä', "LATIN CAPITAL LETTER A WITH DIAERESIS"),
+    ("A0", 'Å', "LATIN CAPITAL LETTER A WITH RING ABOVE"),
+    ("A<", 'Ǎ', "LATIN CAPITAL LETTER A WITH CARON"),
+    ("A;", 'Ą', "LATIN CAPITAL LETTER A WITH OGONEK"),
+    ("a!", 'à', "LATIN SMALL LETTER A WITH GRAVE"),
+    ("a'", 'á', "LATIN SMALL LETTER A WITH ACUTE"),
+    ("a>", 'â', "LATIN SMALL LETTER A WITH CIRCUMFLEX"),
+    ("a?", 'ã', "LATIN SMALL LETTER A WITH TILDE"),
+    ("a-", 'ā', "LATIN SMALL LETTER A WITH MACRON"),
+    ("a(", 'ă', "LATIN SMALL LETTER A WITH BREVE"),
+    ("a.", 'ȧ', "LATIN SMALL LETTER A WITH DOT ABOVE"),
+    ("a:", 'ä', "LATIN SMALL LETTER A WITH DIAERESIS"),
+    ("a0", 'å', "LATIN SMALL LETTER A WITH RING ABOVE"),
+    ("a<", 'ǎ', "LATIN SMALL LETTER A WITH CARON"),
+    ("a;", 'ą', "LATIN SMALL LETTER A WITH OGONEK"),
+    ("C'", 'Ć', "LATIN CAPITAL LETTER C WITH ACUTE"),
+    ("C>", 'Ĉ', "LATIN CAPITAL LETTER C WITH CIRCUMFLEX"),
+    ("C.", 'Ċ', "LATIN CAPITAL LETTER C WITH DOT ABOVE"),
+    ("C<", 'Č', "LATIN CAPITAL LETTER C WITH CARON"),
+    ("C,", 'Ç', "LATIN CAPITAL LETTER C WITH CEDILLA"),
+    ("c'", 'ć', "LATIN SMALL LETTER C WITH ACUTE"),
+    ("c>", 'ĉ', "LATIN SMALL LETTER C WITH CIRCUMFLEX"),
+    ("c.", 'ċ', "LATIN SMALL LETTER C WITH DOT ABOVE"),
+    ("c<", 'č', "LATIN SMALL LETTER C WITH CARON"),
+    ("c,", 'ç', "LATIN SMALL LETTER C WITH CEDILLA"),
+    ("E!", 'È', "LATIN CAPITAL LETTER E WITH GRAVE"),
+    ("E'", 'É', "LATIN CAPITAL LETTER E WITH ACUTE"),
+    ("E>", 'Ê', "LATIN CAPITAL LETTER E WITH CIRCUMFLEX"),
+    ("E?", 'Ẽ', "LATIN CAPITAL LETTER E WITH TILDE"),
+    ("E-", 'Ē', "LATIN CAPITAL LETTER E WITH MACRON"),
+    ("E(", 'Ĕ', "LATIN CAPITAL LETTER E WITH BREVE"),
+    ("E.", 'Ė', "LATIN CAPITAL LETTER E WITH DOT ABOVE"),
+    ("E:", 'Ë', "LATIN CAPITAL LETTER E WITH DIAERESIS"),
+    ("E<", 'Ě', "LATIN CAPITAL LETTER E WITH CARON"),
+    ("E,", 'Ȩ', "LATIN CAPITAL LETTER E WITH CEDILLA"),
+    ("E;", 'Ę', "LATIN CAPITAL LETTER E WITH OGONEK"),
+    ("e!", 'è', "LATIN SMALL LETTER E WITH GRAVE"),
+    ("e'", 'é', "LATIN SMALL LETTER E WITH ACUTE"),
+    ("e>", 'ê', "LATIN SMALL LETTER E WITH CIRCUMFLEX"),
+    ("e?", 'ẽ', "LATIN SMALL LETTER E WITH TILDE"),
+    ("e-", 'ē', "LATIN SMALL LETTER E WITH MACRON"),
+    ("e(", 'ĕ', "LATIN SMALL LETTER E WITH BREVE"),
+    ("e.", 'ė', "LATIN SMALL LETTER E WITH DOT ABOVE"),
+    ("e:", 'ë', "LATIN SMALL LETTER E WITH DIAERESIS"),
+    ("e<", 'ě', "LATIN SMALL LETTER E WITH CARON"),
+    ("e,", 'ȩ', "LATIN SMALL LETTER E WITH CEDILLA"),
+    ("e;", 'ę', "LATIN SMALL LETTER E WITH OGONEK"),
+    ("G'", 'Ǵ', "LATIN CAPITAL LETTER G WITH ACUTE"),
+    ("G>", 'Ĝ', "LATIN CAPITAL LETTER G WITH CIRCUMFLEX"),
+    ("G-", 'Ḡ', "LATIN CAPITAL LETTER G WITH MACRON"),
+    ("G(", 'Ğ', "LATIN CAPITAL LETTER G WITH BREVE"),
+    ("G.", 'Ġ', "LATIN CAPITAL LETTER G WITH DOT ABOVE"),
+    ("G<", 'Ǧ', "LATIN CAPITAL LETTER G WITH CARON"),
+    ("G,", 'Ģ', "LATIN CAPITAL LETTER G WITH CEDILLA"),
+    ("g'", 'ǵ', "LATIN SMALL LETTER G WITH ACUTE"),
+    ("g>", 'ĝ', "LATIN SMALL LETTER G WITH CIRCUMFLEX"),
+    ("g-", 'ḡ', "LATIN SMALL LETTER G WITH MACRON"),
+    ("g(", 'ğ', "LATIN SMALL LETTER G WITH BREVE"),
+    ("g.", 'ġ', "LATIN SMALL LETTER G WITH DOT ABOVE"),
+    ("g<", 'ǧ', "LATIN SMALL LETTER G WITH CARON"),
+    ("g,", 'ģ', "LATIN SMALL LETTER G WITH CEDILLA"),
+    ("H>", 'Ĥ', "LATIN CAPITAL LETTER H WITH CIRCUMFLEX"),
+    ("H.", 'Ḣ', "LATIN CAPITAL LETTER H WITH DOT ABOVE"),
+    ("H:", 'Ḧ', "LATIN CAPITAL LETTER H WITH DIAERESIS"),
+    ("H<", 'Ȟ', "LATIN CAPITAL LETTER H WITH CARON"),
+    ("H,", 'Ḩ', "LATIN CAPITAL LETTER H WITH CEDILLA"),
+    ("h>", 'ĥ', "LATIN SMALL LETTER H WITH CIRCUMFLEX"),
+    ("h.", 'ḣ', "LATIN SMALL LETTER H WITH DOT ABOVE"),
+    ("h:", 'ḧ', "LATIN SMALL LETTER H WITH DIAERESIS"),
+    ("h<", 'ȟ', "LATIN SMALL LETTER H WITH CARON"),
+    ("h,", 'ḩ', "LATIN SMALL LETTER H WITH CEDILLA"),
+    ("I!", 'Ì', "LATIN CAPITAL LETTER I WITH GRAVE"),
+    ("I'", 'Í', "LATIN CAPITAL LETTER I WITH ACUTE"),
+    ("I>", 'Î', "LATIN CAPITAL LETTER I WITH CIRCUMFLEX"),
+    ("I?", 'Ĩ', "LATIN CAPITAL LETTER I WITH TILDE"),
+    ("I-", 'Ī', "LATIN CAPITAL LETTER I WITH MACRON"),
+    ("I(", 'Ĭ', "LATIN CAPITAL LETTER I WITH BREVE"),
+    ("I.", 'İ', "LATIN CAPITAL LETTER I WITH DOT ABOVE"),
+    ("I:", 'Ï', "LATIN CAPITAL LETTER I WITH DIAERESIS"),
+    ("I<", 'Ǐ', "LATIN CAPITAL LETTER I WITH CARON"),
+    ("I;", 'Į', "LATIN CAPITAL LETTER I WITH OGONEK"),
+    ("i!", 'ì', "LATIN SMALL LETTER I WITH GRAVE"),
+    ("i'", 'í', "LATIN SMALL LETTER I WITH ACUTE"),
+    ("i>", 'î', "LATIN SMALL LETTER I WITH CIRCUMFLEX"),
+    ("i?", 'ĩ', "LATIN SMALL LETTER I WITH TILDE"),
+    ("i-", 'ī', "LATIN SMALL LETTER I WITH MACRON"),
+    ("i(", 'ĭ', "LATIN SMALL LETTER I WITH BREVE"),
+    ("i:", 'ï', "LATIN SMALL LETTER I WITH DIAERESIS"),
+    ("i<", 'ǐ', "LATIN SMALL LETTER I WITH CARON"),
+    ("i;", 'į', "LATIN SMALL LETTER I WITH OGONEK"),
+    ("J>", 'Ĵ', "LATIN CAPITAL LETTER J WITH CIRCUMFLEX"),
+    ("j>", 'ĵ', "LATIN SMALL LETTER J WITH CIRCUMFLEX"),
+    ("j<", 'ǰ', "LATIN SMALL LETTER J WITH CARON"),
+    ("K'", 'Ḱ', "LATIN CAPITAL LETTER K WITH ACUTE"),
+    ("K<", 'Ǩ', "LATIN CAPITAL LETTER K WITH CARON"),
+    ("K,", 'Ķ', "LATIN CAPITAL LETTER K WITH CEDILLA"),
+    ("k'", 'ḱ', "LATIN SMALL LETTER K WITH ACUTE"),
+    ("k<", 'ǩ', "LATIN SMALL LETTER K WITH CARON"),
+    ("k,", 'ķ', "LATIN SMALL LETTER K WITH CEDILLA"),
+    ("L'", 'Ĺ', "LATIN CAPITAL LETTER L WITH ACUTE"),
+    ("L<", 'Ľ', "LATIN CAPITAL LETTER L WITH CARON"),
+    ("L,", 'Ļ', "LATIN CAPITAL LETTER L WITH CEDILLA"),
+    ("l'", 'ĺ', "LATIN SMALL LETTER L WITH ACUTE"),
+    ("l<", 'ľ', "LATIN SMALL LETTER L WITH CARON"),
+    ("l,", 'ļ', "LATIN SMALL LETTER L WITH CEDILLA"),
+    ("N!", 'Ǹ', "LATIN CAPITAL LETTER N WITH GRAVE"),
+    ("N'", 'Ń', "LATIN CAPITAL LETTER N WITH ACUTE"),
+    ("N?", 'Ñ', "LATIN CAPITAL LETTER N WITH TILDE"),
+    ("N.", 'Ṅ', "LATIN CAPITAL LETTER N WITH DOT ABOVE"),
+    ("N<", 'Ň', "LATIN CAPITAL LETTER N WITH CARON"),
+    ("N,", 'Ņ', "LATIN CAPITAL LETTER N WITH CEDILLA"),
+    ("n!", 'ǹ', "LATIN SMALL LETTER N WITH GRAVE"),
+    ("n'", 'ń', "LATIN SMALL LETTER N WITH ACUTE"),
+    ("n?", 'ñ', "LATIN SMALL LETTER N WITH TILDE"),
+    ("n.", 'ṅ', "LATIN SMALL LETTER N WITH DOT ABOVE"),
+    ("n<", 'ň', "LATIN SMALL LETTER N WITH CARON"),
+    ("n,", 'ņ', "LATIN SMALL LETTER N WITH CEDILLA"),
+    ("O!", 'Ò', "LATIN CAPITAL LETTER O WITH GRAVE"),
+    ("O'", 'Ó', "LATIN CAPITAL LETTER O WITH ACUTE"),
+    ("O>", 'Ô', "LATIN CAPITAL LETTER O WITH CIRCUMFLEX"),
+    ("O?", 'Õ', "LATIN CAPITAL LETTER O WITH TILDE"),
+    ("O-", 'Ō', "LATIN CAPITAL LETTER O WITH MACRON"),
+    ("O(", 'Ŏ', "LATIN CAPITAL LETTER O WITH BREVE"),
+    ("O.", 'Ȯ', "LATIN CAPITAL LETTER O WITH DOT ABOVE"),
+    ("O:", 'Ö', "LATIN CAPITAL LETTER O WITH DIAERESIS"),
+    ("O\"", 'Ő', "LATIN CAPITAL LETTER O WITH DOUBLE ACUTE"),
+    ("O<", 'Ǒ', "LATIN CAPITAL LETTER O WITH CARON"),
+    ("O;", 'Ǫ', "LATIN CAPITAL LETTER O WITH OGONEK"),
+    ("o!", 'ò', "LATIN SMALL LETTER O WITH GRAVE"),
+    ("o'", 'ó', "LATIN SMALL LETTER O WITH ACUTE"),
+    ("o>", 'ô', "LATIN SMALL LETTER O WITH CIRCUMFLEX"),
+    ("o?", 'õ', "LATIN SMALL LETTER O WITH TILDE"),
+    ("o-", 'ō', "LATIN SMALL LETTER O WITH MACRON"),
+    ("o(", 'ŏ', "LATIN SMALL LETTER O WITH BREVE"),
+    ("o.", 'ȯ', "LATIN SMALL LETTER O WITH DOT ABOVE"),
+    ("o:", 'ö', "LATIN SMALL LETTER O WITH DIAERESIS"),
+    ("o\"", 'ő', "LATIN SMALL LETTER O WITH DOUBLE ACUTE"),
+    ("o<", 'ǒ', "LATIN SMALL LETTER O WITH CARON"),
+    ("o;", 'ǫ', "LATIN SMALL LETTER O WITH OGONEK"),
+    ("R'", 'Ŕ', "LATIN CAPITAL LETTER R WITH ACUTE"),
+    ("R.", 'Ṙ', "LATIN CAPITAL LETTER R WITH DOT ABOVE"),
+    ("R<", 'Ř', "LATIN CAPITAL LETTER R WITH CARON"),
+    ("R,", 'Ŗ', "LATIN CAPITAL LETTER R WITH CEDILLA"),
+    ("r'", 'ŕ', "LATIN SMALL LETTER R WITH ACUTE"),
+    ("r.", 'ṙ', "LATIN SMALL LETTER R WITH DOT ABOVE"),
+    ("r<", 'ř', "LATIN SMALL LETTER R WITH CARON"),
+    ("r,", 'ŗ', "LATIN SMALL LETTER R WITH CEDILLA"),
+    ("S'", 'Ś', "LATIN CAPITAL LETTER S WITH ACUTE"),
+    ("S>", 'Ŝ', "LATIN CAPITAL LETTER S WITH CIRCUMFLEX"),
+    ("S.", 'Ṡ', "LATIN CAPITAL LETTER S WITH DOT ABOVE"),
+    ("S<", 'Š', "LATIN CAPITAL LETTER S WITH CARON"),
+    ("S,", 'Ş', "LATIN CAPITAL LETTER S WITH CEDILLA"),
+    ("s'", 'ś', "LATIN SMALL LETTER S WITH ACUTE"),
+    ("s>", 'ŝ', "LATIN SMALL LETTER S WITH CIRCUMFLEX"),
+    ("s.", 'ṡ', "LATIN SMALL LETTER S WITH DOT ABOVE"),
+    ("s<", 'š', "LATIN SMALL LETTER S WITH CARON"),
+    ("s,", 'ş', "LATIN SMALL LETTER S WITH CEDILLA"),
+    ("T.", 'Ṫ', "LATIN CAPITAL LETTER T WITH DOT ABOVE"),
+    ("T<", 'Ť', "LATIN CAPITAL LETTER T WITH CARON"),
+    ("T,", 'Ţ', "LATIN CAPITAL LETTER T WITH CEDILLA"),
+    ("t.", 'ṫ', "LATIN SMALL LETTER T WITH DOT ABOVE"),
+    ("t:", 'ẗ', "LATIN SMALL LETTER T WITH DIAERESIS"),
+    ("t<", 'ť', "LATIN SMALL LETTER T WITH CARON"),
+    ("t,", 'ţ', "LATIN SMALL LETTER T WITH CEDILLA"),
+    ("U!", 'Ù', "LATIN CAPITAL LETTER U WITH GRAVE"),
+    ("U'", 'Ú', "LATIN CAPITAL LETTER U WITH ACUTE"),
+    ("U>", 'Û', "LATIN CAPITAL LETTER U WITH CIRCUMFLEX"),
+    ("U?", 'Ũ', "LATIN CAPITAL LETTER U WITH TILDE"),
+    ("U-", 'Ū', "LATIN CAPITAL LETTER U WITH MACRON"),
+    ("U(", 'Ŭ', "LATIN CAPITAL LETTER U WITH BREVE"),
+    ("U:", 'Ü', "LATIN CAPITAL LETTER U WITH DIAERESIS"),
+    ("U0", 'Ů', "LATIN CAPITAL LETTER U WITH RING ABOVE"),
+    ("U\"", 'Ű', "LATIN CAPITAL LETTER U WITH DOUBLE ACUTE"),
+    ("U<", 'Ǔ', "LATIN CAPITAL LETTER U WITH CARON"),
+    ("U;", 'Ų', "LATIN CAPITAL LETTER U WITH OGONEK"),
+    ("u!", 'ù', "LATIN SMALL LETTER U WITH GRAVE"),
+    ("u'", 'ú', "LATIN SMALL LETTER U WITH ACUTE"),
+    ("u>", 'û', "LATIN SMALL LETTER U WITH CIRCUMFLEX"),
+    ("u?", 'ũ', "LATIN SMALL LETTER U WITH TILDE"),
+    ("u-", 'ū', "LATIN SMALL LETTER U WITH MACRON"),
+    ("u(", 'ŭ', "LATIN SMALL LETTER U WITH BREVE"),
+    ("u:", 'ü', "LATIN SMALL LETTER U WITH DIAERESIS"),
+    ("u0", 'ů', "LATIN SMALL LETTER U WITH RING ABOVE"),
+    ("u\"", 'ű', "LATIN SMALL LETTER U WITH DOUBLE ACUTE"),
+    ("u<", 'ǔ', "LATIN SMALL LETTER U WITH CARON"),
+    ("u;", 'ų', "LATIN SMALL LETTER U WITH OGONEK"),
+    ("W!", 'Ẁ', "LATIN CAPITAL LETTER W WITH GRAVE"),
+    ("W'", 'Ẃ', "LATIN CAPITAL LETTER W WITH ACUTE"),
+    ("W>", 'Ŵ', "LATIN CAPITAL LETTER W WITH CIRCUMFLEX"),
+    ("W.", 'Ẇ', "LATIN CAPITAL LETTER W WITH DOT ABOVE"),
+    ("W:", 'Ẅ', "LATIN CAPITAL LETTER W WITH DIAERESIS"),
+    ("w!", 'ẁ', "LATIN SMALL LETTER W WITH GRAVE"),
+    ("w'", 'ẃ', "LATIN SMALL LETTER W WITH ACUTE"),
+    ("w>", 'ŵ', "LATIN SMALL LETTER W WITH CIRCUMFLEX"),
+    ("w.", 'ẇ', "LATIN SMALL LETTER W WITH DOT ABOVE"),
+    ("w:", 'ẅ', "LATIN SMALL LETTER W WITH DIAERESIS"),
+    ("w0", 'ẘ', "LATIN SMALL LETTER W WITH RING ABOVE"),
+    ("Y!", 'Ỳ', "LATIN CAPITAL LETTER Y WITH GRAVE"),
+    ("Y'", 'Ý', "LATIN CAPITAL LETTER Y WITH ACUTE"),
+    ("Y>", 'Ŷ', "LATIN CAPITAL LETTER Y WITH CIRCUMFLEX"),
+    ("Y?", 'Ỹ', "LATIN CAPITAL LETTER Y WITH TILDE"),
+    ("Y-", 'Ȳ', "LATIN CAPITAL LETTER Y WITH MACRON"),
+    ("Y.", 'Ẏ', "LATIN CAPITAL LETTER Y WITH DOT ABOVE"),
+    ("Y:", 'Ÿ', "LATIN CAPITAL LETTER Y WITH DIAERESIS"),
+    ("y!", 'ỳ', "LATIN SMALL LETTER Y WITH GRAVE"),
+    ("y'", 'ý', "LATIN SMALL LETTER Y WITH ACUTE"),
+    ("y>", 'ŷ', "LATIN SMALL LETTER Y WITH CIRCUMFLEX"),
+    ("y?", 'ỹ', "LATIN SMALL LETTER Y WITH TILDE"),
+    ("y-", 'ȳ', "LATIN SMALL LETTER Y WITH MACRON"),
+    ("y.", 'ẏ', "LATIN SMALL LETTER Y WITH DOT ABOVE"),
+    ("y:", 'ÿ', "LATIN SMALL LETTER Y WITH DIAERESIS"),
+    ("y0", 'ẙ', "LATIN SMALL LETTER Y WITH RING ABOVE"),
+    ("Z'", 'Ź', "LATIN CAPITAL LETTER Z WITH ACUTE"),
+    ("Z>", 'Ẑ', "LATIN CAPITAL LETTER Z WITH CIRCUMFLEX"),
+    ("Z.", 'Ż', "LATIN CAPITAL LETTER Z WITH DOT ABOVE"),
+    ("Z<", 'Ž', "LATIN CAPITAL LETTER Z WITH CARON"),
+    ("z'", 'ź', "LATIN SMALL LETTER Z WITH ACUTE"),
+    ("z>", 'ẑ', "LATIN SMALL LETTER Z WITH CIRCUMFLEX"),
+    ("z.", 'ż', "LATIN SMALL LETTER Z WITH DOT ABOVE"),
+    ("z<", 'ž', "LATIN SMALL LETTER Z WITH CARON"),
+    ("D.", 'Ḋ', "LATIN CAPITAL LETTER D WITH DOT ABOVE"),
+    ("D<", 'Ď', "LATIN CAPITAL LETTER D WITH CARON"),
+    ("D,", 'Ḑ', "LATIN CAPITAL LETTER D WITH CEDILLA"),
+    ("d.", 'ḋ', "LATIN SMALL LETTER D WITH DOT ABOVE"),
+    ("d<", 'ď', "LATIN SMALL LETTER D WITH CARON"),
+    ("d,", 'ḑ', "LATIN SMALL LETTER D WITH CEDILLA"),
+    ("B.", 'Ḃ', "LATIN CAPITAL LETTER B WITH DOT ABOVE"),
+    ("b.", 'ḃ', "LATIN SMALL LETTER B WITH DOT ABOVE"),
+    ("F.", 'Ḟ', "LATIN CAPITAL LETTER F WITH DOT ABOVE"),
+    ("f.", 'ḟ', "LATIN SMALL LETTER F WITH DOT ABOVE"),
+    ("M'", 'Ḿ', "LATIN CAPITAL LETTER M WITH ACUTE"),
+    ("M.", 'Ṁ', "LATIN CAPITAL LETTER M WITH DOT ABOVE"),
+    ("m'", 'ḿ', "LATIN SMALL LETTER M WITH ACUTE"),
+    ("m.", 'ṁ', "LATIN SMALL LETTER M WITH DOT ABOVE"),
+    ("P'", 'Ṕ', "LATIN CAPITAL LETTER P WITH ACUTE"),
+    ("P.", 'Ṗ', "LATIN CAPITAL LETTER P WITH DOT ABOVE"),
+    ("p'", 'ṕ', "LATIN SMALL LETTER P WITH ACUTE"),
+    ("p.", 'ṗ', "LATIN SMALL LETTER P WITH DOT ABOVE"),
+    ("V?", 'Ṽ', "LATIN CAPITAL LETTER V WITH TILDE"),
+    ("v?", 'ṽ', "LATIN SMALL LETTER V WITH TILDE"),
+    ("X.", 'Ẋ', "LATIN CAPITAL LETTER X WITH DOT ABOVE"),
+    ("X:", 'Ẍ', "LATIN CAPITAL LETTER X WITH DIAERESIS"),
+    ("x.", 'ẋ', "LATIN SMALL LETTER X WITH DOT ABOVE"),
+    ("x:", 'ẍ', "LATIN SMALL LETTER X WITH DIAERESIS"),
+    ("AA", 'Å', "LATIN CAPITAL LETTER A WITH RING ABOVE"),
+    ("aa", 'å', "LATIN SMALL LETTER A WITH RING ABOVE"),
+    ("ss", 'ß', "LATIN SMALL LETTER SHARP S"),
+    ("ae", 'æ', "LATIN SMALL LETTER AE"),
+    ("AE", 'Æ', "LATIN CAPITAL LETTER AE"),
+    ("o/", 'ø', "LATIN SMALL LETTER O WITH STROKE"),
+    ("O/", 'Ø', "LATIN CAPITAL LETTER O WITH STROKE"),
+    ("oe", 'œ', "LATIN SMALL LIGATURE OE"),
+    ("OE", 'Œ', "LATIN CAPITAL LIGATURE OE"),
+    ("d-", 'ð', "LATIN SMALL LETTER ETH"),
+    ("D-", 'Ð', "LATIN CAPITAL LETTER ETH"),
+    ("d/", 'đ', "LATIN SMALL LETTER D WITH STROKE"),
+    ("D/", 'Đ', "LATIN CAPITAL LETTER D WITH STROKE"),
+    ("l/", 'ł', "LATIN SMALL LETTER L WITH STROKE"),
+    ("L/", 'Ł', "LATIN CAPITAL LETTER L WITH STROKE"),
+    ("th", 'þ', "LATIN SMALL LETTER THORN"),
+    ("TH", 'Þ', "LATIN CAPITAL LETTER THORN"),
+    ("i.", 'ı', "LATIN SMALL LETTER DOTLESS I"),
+    ("a*", 'α', "GREEK SMALL LETTER ALPHA"),
+    ("A*", 'Α', "GREEK CAPITAL LETTER ALPHA"),
+    ("b*", 'β', "GREEK SMALL LETTER BETA"),
+    ("B*", 'Β', "GREEK CAPITAL LETTER BETA"),
+    ("g*", 'γ', "GREEK SMALL LETTER GAMMA"),
+    ("G*", 'Γ', "GREEK CAPITAL LETTER GAMMA"),
+    ("d*", 'δ', "GREEK SMALL LETTER DELTA"),
+    ("D*", 'Δ', "GREEK CAPITAL LETTER DELTA"),
+    ("e*", 'ε', "GREEK SMALL LETTER EPSILON"),
+    ("E*", 'Ε', "GREEK CAPITAL LETTER EPSILON"),
+    ("z*", 'ζ', "GREEK SMALL LETTER ZETA"),
+    ("Z*", 'Ζ', "GREEK CAPITAL LETTER ZETA"),
+    ("y*", 'η', "GREEK SMALL LETTER ETA"),
+    ("Y*", 'Η', "GREEK CAPITAL LETTER ETA"),
+    ("h*", 'θ', "GREEK SMALL LETTER THETA"),
+    ("H*", 'Θ', "GREEK CAPITAL LETTER THETA"),
+    ("i*", 'ι', "GREEK SMALL LETTER IOTA"),
+    ("I*", 'Ι', "GREEK CAPITAL LETTER IOTA"),
+    ("k*", 'κ', "GREEK SMALL LETTER KAPPA"),
+    ("K*", 'Κ', "GREEK CAPITAL LETTER KAPPA"),
+    ("l*", 'λ', "GREEK SMALL LETTER LAMDA"),
+    ("L*", 'Λ', "GREEK CAPITAL LETTER LAMDA"),
+    ("m*", 'μ', "GREEK SMALL LETTER MU"),
+    ("M*", 'Μ', "GREEK CAPITAL LETTER MU"),
+    ("n*", 'ν', "GREEK SMALL LETTER NU"),
+    ("N*", 'Ν', "GREEK CAPITAL LETTER NU"),
+    ("c*", 'ξ', "GREEK SMALL LETTER XI"),
+    ("C*", 'Ξ', "GREEK CAPITAL LETTER XI"),
+    ("o*", 'ο', "GREEK SMALL LETTER OMICRON"),
+    ("O*", 'Ο', "GREEK CAPITAL LETTER OMICRON"),
+    ("p*", 'π', "GREEK SMALL LETTER PI"),
+    ("P*", 'Π', "GREEK CAPITAL LETTER PI"),
+    ("r*", 'ρ', "GREEK SMALL LETTER RHO"),
+    ("R*", 'Ρ', "GREEK CAPITAL LETTER RHO"),
+    ("s*", 'σ', "GREEK SMALL LETTER SIGMA"),
+    ("S*", 'Σ', "GREEK CAPITAL LETTER SIGMA"),
+    ("t*", 'τ', "GREEK SMALL LETTER TAU"),
+    ("T*", 'Τ', "GREEK CAPITAL LETTER TAU"),
+    ("u*", 'υ', "GREEK SMALL LETTER UPSILON"),
+    ("U*", 'Υ', "GREEK CAPITAL LETTER UPSILON"),
+    ("f*", 'φ', "GREEK SMALL LETTER PHI"),
+    ("F*", 'Φ', "GREEK CAPITAL LETTER PHI"),
+    ("x*", 'χ', "GREEK SMALL LETTER CHI"),
+    ("X*", 'Χ', "GREEK CAPITAL LETTER CHI"),
+    ("q*", 'ψ', "GREEK SMALL LETTER PSI"),
+    ("Q*", 'Ψ', "GREEK CAPITAL LETTER PSI"),
+    ("w*", 'ω', "GREEK SMALL LETTER OMEGA"),
+    ("W*", 'Ω', "GREEK CAPITAL LETTER OMEGA"),
+    ("*s", 'ς', "GREEK SMALL LETTER FINAL SIGMA"),
+    ("Eu", '€', "EURO SIGN"),
+    ("=e", '€', "EURO SIGN"),
+    ("Pd", '£', "POUND SIGN"),
+    ("Ye", '¥', "YEN SIGN"),
+    ("Ct", '¢', "CENT SIGN"),
+    ("Co", '©', "COPYRIGHT SIGN"),
+    ("Rg", '®', "REGISTERED SIGN"),
+    ("TM", '™', "TRADE MARK SIGN"),
+    ("SE", '§', "SECTION SIGN"),
+    ("PI", '¶', "PILCROW SIGN"),
+    ("DG", '°', "DEGREE SIGN"),
+    ("+-", '±', "PLUS-MINUS SIGN"),
+    ("*X", '×', "MULTIPLICATION SIGN"),
+    ("-:", '÷', "DIVISION SIGN"),
+    ("!=", '≠', "NOT EQUAL TO"),
+    ("=<", '≤', "LESS-THAN OR EQUAL TO"),
+    (">=", '≥', "GREATER-THAN OR EQUAL TO"),
+    ("?2", '≈', "ALMOST EQUAL TO"),
+    ("00", '∞', "INFINITY"),
+    ("->", '→', "RIGHTWARDS ARROW"),
+    ("<-", '←', "LEFTWARDS ARROW"),
+    ("-!", '↑', "UPWARDS ARROW"),
+    ("-v", '↓', "DOWNWARDS ARROW"),
+    ("<>", '↔', "LEFT RIGHT ARROW"),
+    ("=>", '⇒', "RIGHTWARDS DOUBLE ARROW"),
+    ("==", '⇔', "LEFT RIGHT DOUBLE ARROW"),
+    ("FA", '∀', "FOR ALL"),
+    ("TE", '∃', "THERE EXISTS"),
+    ("dP", '∂', "PARTIAL DIFFERENTIAL"),
+    ("NB", '∇', "NABLA"),
+    ("(-", '∈', "ELEMENT OF"),
+    ("RT", '√', "SQUARE ROOT"),
+    (".M", '·', "MIDDLE DOT"),
+    ("-N", '–', "EN DASH"),
+    ("-M", '—', "EM DASH"),
+    ("'6", '‘', "LEFT SINGLE QUOTATION MARK"),
+    ("'9", '’', "RIGHT SINGLE QUOTATION MARK"),
+    ("\"6", '“', "LEFT DOUBLE QUOTATION MARK"),
+    ("\"9", '”', "RIGHT DOUBLE QUOTATION MARK"),
+    (",.", '…', "HORIZONTAL ELLIPSIS"),
+    ("12", '½', "VULGAR FRACTION ONE HALF"),
+    ("14", '¼', "VULGAR FRACTION ONE QUARTER"),
+    ("34", '¾', "VULGAR FRACTION THREE QUARTERS"),
+    ("1S", '¹', "SUPERSCRIPT ONE"),
+    ("2S", '²', "SUPERSCRIPT TWO"),
+    ("3S", '³', "SUPERSCRIPT THREE"),
+    ("My", 'µ', "MICRO SIGN"),
+    ("!I", '¡', "INVERTED EXCLAMATION MARK"),
+    ("?I", '¿', "INVERTED QUESTION MARK"),
+    ("<<", '«', "LEFT-POINTING DOUBLE ANGLE QUOTATION MARK"),
+    (">>", '»', "RIGHT-POINTING DOUBLE ANGLE QUOTATION MARK"),
+    ("NS", '\u{a0}', "NO-BREAK SPACE"),
+    ("OK", '✓', "CHECK MARK"),
+    ("XX", '✗', "BALLOT X"),
+];
+
+/// The character for the digraph `first` `second`, trying the keys in either order.
+pub fn digraph(first: char, second: char) -> Option<char> {
+    let find = |keys: String| {
+        DIGRAPHS
+            .iter()
+            .find(|(digraph, _, _)| *digraph == keys)
+            .map(|(_, c, _)| *c)
+    };
+    find(format!("{}{}", first, second)).or_else(|| find(format!("{}{}", second, first)))
+}
+
+/// The character with the hexadecimal code point `hex`.
+pub fn codepoint(hex: &str) -> Option<char> {
+    u32::from_str_radix(hex, 16).ok().and_then(char::from_u32)
+}
+
+/// Every named character from the system's `UnicodeData.txt`, or only the digraph
+/// characters when it is not installed.
+pub fn names() -> Vec<(char, String)> {
+    let contents = UNICODE_DATA
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok());
+    let contents = match contents {
+        Some(contents) => contents,
+        None => {
+            return DIGRAPHS
+                .iter()
+                .map(|(_, c, name)| (*c, (*name).to_string()))
+                .collect()
+        }
+    };
+    contents
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(';');
+            let c = codepoint(fields.next()?)?;
+            let name = fields.next()?;
+            // Controls are named in the tenth field; ranges such as CJK ideographs have
+            // no names of their own.
+            let name = if name == "<control>" {
+                fields.nth(8).filter(|name| !name.is_empty())?
+            } else if name.starts_with('<') {
+                return None;
+            } else {
+                name
+            };
+            Some((c, name.to_string()))
+        })
+        .collect()
+}
+
+/// The characters whose names contain every word of `query`, shortest name first. A
+/// `U+` code point matches its character.
+pub fn search<'a>(names: &'a [(char, String)], query: &str) -> Vec<&'a (char, String)> {
+    let query = query.to_uppercase();
+    let words: Vec<&str> = query.split_whitespace().collect();
+    if words.is_empty() {
+        return Vec::new();
+    }
+    let code = query.trim().strip_prefix("U+").and_then(codepoint);
+    let mut matches: Vec<&(char, String)> = names
+        .iter()
+        .filter(|(c, name)| Some(*c) == code || words.iter().all(|word| name.contains(word)))
+        .collect();
+    matches.sort_by_key(|(c, name)| (Some(*c) != code, name.len()));
+    matches
+}
+
+/// Characters drawn without advancing the cursor: combining marks, zero-width
+/// spaces and joiners, direction marks and variation selectors.
+const ZERO_WIDTH: &[(u32, u32)] = &[
+    (0x0300, 0x036F),
+    (0x0483, 0x0489),
+    (0x0591, 0x05C7),
+    (0x0610, 0x061A),
+    (0x064B, 0x065F),
+    (0x0670, 0x0670),
+    (0x06D6, 0x06ED),
+    (0x0E31, 0x0E31),
+    (0x0E34, 0x0E3A),
+    (0x0E47, 0x0E4E),
+    (0x1AB0, 0x1AFF),
+    (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F),
+    (0x202A, 0x202E),
+    (0x2060, 0x2064),
+    (0x20D0, 0x20FF),
+    (0xFE00, 0xFE0F),
+    (0xFE20, 0xFE2F),
+    (0xFEFF, 0xFEFF),
+    (0xE0100, 0xE01EF),
+];
+
+/// East Asian wide and fullwidth characters and emoji, which take two columns.
+const WIDE: &[(u32, u32)] = &[
+    (0x1100, 0x115F),
+    (0x2E80, 0x303E),
+    (0x3041, 0x33FF),
+    (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF),
+    (0xA000, 0xA4CF),
+    (0xAC00, 0xD7A3),
+    (0xF900, 0xFAFF),
+    (0xFE30, 0xFE4F),
+    (0xFF00, 0xFF60),
+    (0xFFE0, 0xFFE6),
+    (0x1F300, 0x1F64F),
+    (0x1F900, 0x1F9FF),
+    (0x20000, 0x2FFFD),
+    (0x30000, 0x3FFFD),
+];
+
+/// The columns a terminal gives `text`.
+pub fn text_width(text: &str) -> usize {
+    text.chars().map(width).sum()
+}
+
+/// The start of `text` that fits in `width` columns.
+pub fn truncate(text: &str, width: usize) -> String {
+    let mut used = 0_usize;
+    text.chars()
+        .take_while(|c| {
+            used = used.saturating_add(self::width(*c));
+            used <= width
+        })
+        .collect()
+}
+
+/// The columns a terminal gives `c`.
+pub fn width(c: char) -> usize {
+    let code = u32::from(c);
+    let within = |ranges: &[(u32, u32)]| {
+        ranges
+            .iter()
+            .any(|(first, last)| (*first..=*last).contains(&code))
+    };
+    if c.is_control() || within(ZERO_WIDTH) {
+        0
+    } else if within(WIDE) {
+        2
+    } else {
+        1
+    }
+}
@@ -0,0 +1,91 @@
+use std::io::Error;
+use std::process::{Command, Stdio};
+use std::thread;
+use unicode_segmentation::UnicodeSegmentation;
+
+const SCHEMES: [&str; 3] = ["https://", "http://", "file://"];
+
+/// The URLs in `line`, as grapheme ranges. Punctuation ending a sentence, and a
+/// closing parenthesis that opened before the URL, are left out.
+pub fn find(line: &str) -> Vec<(usize, usize)> {
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+    let mut urls = Vec::new();
+    let mut offset = 0;
+    let mut index = 0;
+    while index < graphemes.len() {
+        let rest = line.get(offset..).unwrap_or_default();
+        if !SCHEMES.iter().any(|scheme| rest.starts_with(scheme)) {
+            offset = offset.saturating_add(graphemes.get(index).map_or(1, |g| g.len()));
+            index = index.saturating_add(1);
+            continue;
+        }
+        let start = index;
+        while graphemes
+            .get(index)
+            .is_some_and(|grapheme| is_url_grapheme(grapheme))
+        {
+            offset = offset.saturating_add(graphemes.get(index).map_or(0, |g| g.len()));
+            index = index.saturating_add(1);
+        }
+        let mut end = index;
+        while end > start {
+            let last = graphemes
+                .get(end.saturating_sub(1))
+                .copied()
+                .unwrap_or_default();
+            let text: String = graphemes.get(start..end).unwrap_or_default().concat();
+            let unbalanced = last == ")" && text.matches('(').count() < text.matches(')').count();
+            if !matches!(last, "." | "," | ";" | ":" | "!" | "?" | "'") && !unbalanced {
+                break;
+            }
+            end = end.saturating_sub(1);
+        }
+        urls.push((start, end));
+    }
+    urls
+}
+
+/// The URL under grapheme `x` of `line`.
+pub fn at(line: &str, x: usize) -> Option<String> {
+    let (start, end) = find(line)
+        .into_iter()
+        .find(|(start, end)| (*start..*end).contains(&x))?;
+    Some(
+        line.graphemes(true)
+            .skip(start)
+            .take(end.saturating_sub(start))
+            .collect(),
+    )
+}
+
+/// Hands `url` to `opener`, a program followed by any arguments, without waiting
+/// for it.
+pub fn open(opener: &str, url: &str) -> Result<(), Error> {
+    let mut words = opener.split_whitespace();
+    let program = words.next().unwrap_or("xdg-open");
+    let mut child = Command::new(program)
+        .args(words)
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Reap the opener when it exits so it does not linger as a zombie.
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// The program that opens URLs on this system.
+pub fn default_opener() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    }
+}
+
+fn is_url_grapheme(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| {
+        c.is_ascii_graphic() && !matches!(c, '"' | '<' | '>' | '`' | '{' | '}' | '|' | '\\')
+    })
+}
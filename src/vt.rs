@@ -0,0 +1,320 @@
+use std::cmp;
+use std::str;
+
+/// Where the parser is within an escape sequence.
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Ground,
+    Escape,
+    /// After `ESC [`, collecting parameters.
+    Csi,
+    /// After `ESC ]`, skipping an operating system command.
+    Osc,
+    OscEscape,
+    /// After `ESC (` or `ESC )`, skipping the character set name.
+    Charset,
+}
+
+/// The screen of a VT100: a grid of characters that program output is written onto.
+/// Colors and attributes are dropped; cursor movement, erasing and scrolling are kept.
+pub struct Screen {
+    width: usize,
+    height: usize,
+    rows: Vec<Vec<char>>,
+    x: usize,
+    y: usize,
+    /// The last column was written; the next character goes on a new line.
+    wrap_pending: bool,
+    saved: (usize, usize),
+    /// The scrolling region, as first and last rows.
+    top: usize,
+    bottom: usize,
+    state: State,
+    params: String,
+    /// Bytes of a character split across reads.
+    utf8: Vec<u8>,
+}
+
+impl Screen {
+    pub fn new(width: usize, height: usize) -> Self {
+        let width = width.max(1);
+        let height = height.max(1);
+        Self {
+            width,
+            height,
+            rows: vec![vec![' '; width]; height],
+            x: 0,
+            y: 0,
+            wrap_pending: false,
+            saved: (0, 0),
+            top: 0,
+            bottom: height.saturating_sub(1),
+            state: State::Ground,
+            params: String::new(),
+            utf8: Vec::new(),
+        }
+    }
+    pub fn rows(&self) -> impl Iterator<Item = String> + '_ {
+        self.rows.iter().map(|row| row.iter().collect())
+    }
+    /// The cursor as column and row.
+    pub fn cursor(&self) -> (usize, usize) {
+        (self.x, self.y)
+    }
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.feed_byte(*byte);
+        }
+    }
+    fn feed_byte(&mut self, byte: u8) {
+        match self.state {
+            State::Ground => self.ground(byte),
+            State::Escape => self.escape(byte),
+            State::Csi => match byte {
+                0x30..=0x3F => self.params.push(char::from(byte)),
+                0x40..=0x7E => {
+                    self.state = State::Ground;
+                    self.csi(char::from(byte));
+                }
+                // Intermediate bytes select variants nothing here needs.
+                0x20..=0x2F => (),
+                _ => self.state = State::Ground,
+            },
+            State::Osc => match byte {
+                0x07 => self.state = State::Ground,
+                0x1B => self.state = State::OscEscape,
+                _ => (),
+            },
+            State::OscEscape | State::Charset => self.state = State::Ground,
+        }
+    }
+    fn ground(&mut self, byte: u8) {
+        if byte >= 0x80 {
+            self.utf8.push(byte);
+            match str::from_utf8(&self.utf8) {
+                Ok(text) => {
+                    let text = text.to_string();
+                    self.utf8.clear();
+                    text.chars().for_each(|c| self.print(c));
+                }
+                Err(error) if error.error_len().is_some() => {
+                    self.utf8.clear();
+                    self.print(char::REPLACEMENT_CHARACTER);
+                }
+                Err(_) => (),
+            }
+            return;
+        }
+        self.utf8.clear();
+        match byte {
+            0x08 => {
+                self.x = self.x.saturating_sub(1);
+                self.wrap_pending = false;
+            }
+            b'\t' => {
+                self.x = cmp::min(
+                    (self.x / 8).saturating_add(1).saturating_mul(8),
+                    self.last_column(),
+                );
+            }
+            b'\n' | 0x0B | 0x0C => self.line_feed(),
+            b'\r' => {
+                self.x = 0;
+                self.wrap_pending = false;
+            }
+            0x1B => self.state = State::Escape,
+            0x20..=0x7E => self.print(char::from(byte)),
+            _ => (),
+        }
+    }
+    fn escape(&mut self, byte: u8) {
+        self.state = State::Ground;
+        match byte {
+            b'[' => {
+                self.params.clear();
+                self.state = State::Csi;
+            }
+            b']' => self.state = State::Osc,
+            b'(' | b')' => self.state = State::Charset,
+            b'7' => self.saved = (self.x, self.y),
+            b'8' => (self.x, self.y) = self.saved,
+            b'D' => self.line_feed(),
+            b'E' => {
+                self.x = 0;
+                self.line_feed();
+            }
+            b'M' => {
+                if self.y == self.top {
+                    self.scroll_down(1);
+                } else {
+                    self.y = self.y.saturating_sub(1);
+                }
+            }
+            b'c' => *self = Self::new(self.width, self.height),
+            _ => (),
+        }
+    }
+    fn csi(&mut self, command: char) {
+        let private = self.params.starts_with('?');
+        let params: Vec<usize> = self
+            .params
+            .trim_start_matches('?')
+            .split(';')
+            .map(|param| param.parse().unwrap_or(0))
+            .collect();
+        let param = |index: usize| params.get(index).copied().unwrap_or(0);
+        // Counts and positions treat a missing or zero parameter as one.
+        let count = param(0).max(1);
+        self.wrap_pending = false;
+        match command {
+            'A' => self.y = self.y.saturating_sub(count).max(self.top.min(self.y)),
+            'B' => self.y = cmp::min(self.y.saturating_add(count), self.last_row()),
+            'C' => self.x = cmp::min(self.x.saturating_add(count), self.last_column()),
+            'D' => self.x = self.x.saturating_sub(count),
+            'E' | 'F' => {
+                self.y = if command == 'E' {
+                    cmp::min(self.y.saturating_add(count), self.last_row())
+                } else {
+                    self.y.saturating_sub(count)
+                };
+                self.x = 0;
+            }
+            'G' | '`' => self.x = cmp::min(count.saturating_sub(1), self.last_column()),
+            'd' => self.y = cmp::min(count.saturating_sub(1), self.last_row()),
+            'H' | 'f' => {
+                self.y = cmp::min(count.saturating_sub(1), self.last_row());
+                self.x = cmp::min(param(1).max(1).saturating_sub(1), self.last_column());
+            }
+            'J' => match param(0) {
+                0 => {
+                    self.clear_line(self.y, self.x, self.width);
+                    (self.y.saturating_add(1)..self.height)
+                        .for_each(|y| self.clear_line(y, 0, self.width));
+                }
+                1 => {
+                    (0..self.y).for_each(|y| self.clear_line(y, 0, self.width));
+                    self.clear_line(self.y, 0, self.x.saturating_add(1));
+                }
+                _ => (0..self.height).for_each(|y| self.clear_line(y, 0, self.width)),
+            },
+            'K' => match param(0) {
+                0 => self.clear_line(self.y, self.x, self.width),
+                1 => self.clear_line(self.y, 0, self.x.saturating_add(1)),
+                _ => self.clear_line(self.y, 0, self.width),
+            },
+            'L' | 'M' if (self.top..=self.bottom).contains(&self.y) => {
+                let top = self.top;
+                self.top = self.y;
+                if command == 'L' {
+                    self.scroll_down(count);
+                } else {
+                    self.scroll_up(count);
+                }
+                self.top = top;
+            }
+            '@' | 'P' => {
+                let width = self.width;
+                let x = self.x;
+                if let Some(row) = self.rows.get_mut(self.y) {
+                    let count = cmp::min(count, width.saturating_sub(x));
+                    if command == '@' {
+                        row.splice(x..x, std::iter::repeat_n(' ', count));
+                        row.truncate(width);
+                    } else {
+                        row.drain(x..x.saturating_add(count));
+                        row.resize(width, ' ');
+                    }
+                }
+            }
+            'X' => self.clear_line(self.y, self.x, self.x.saturating_add(count)),
+            'S' => self.scroll_up(count),
+            'T' => self.scroll_down(count),
+            'r' => {
+                let top = param(0).max(1).saturating_sub(1);
+                let bottom = if param(1) == 0 {
+                    self.last_row()
+                } else {
+                    cmp::min(param(1).saturating_sub(1), self.last_row())
+                };
+                if top < bottom {
+                    self.top = top;
+                    self.bottom = bottom;
+                    self.x = 0;
+                    self.y = 0;
+                }
+            }
+            's' => self.saved = (self.x, self.y),
+            'u' => (self.x, self.y) = self.saved,
+            // Switching to and from the alternate screen starts with a clean one.
+            'h' | 'l' if private && matches!(param(0), 47 | 1047 | 1049) => {
+                if command == 'h' {
+                    self.saved = (self.x, self.y);
+                }
+                (0..self.height).for_each(|y| self.clear_line(y, 0, self.width));
+                if command == 'l' {
+                    (self.x, self.y) = self.saved;
+                }
+            }
+            _ => (),
+        }
+    }
+    fn print(&mut self, c: char) {
+        if self.wrap_pending {
+            self.wrap_pending = false;
+            self.x = 0;
+            self.line_feed();
+        }
+        if let Some(cell) = self
+            .rows
+            .get_mut(self.y)
+            .and_then(|row| row.get_mut(self.x))
+        {
+            *cell = c;
+        }
+        if self.x >= self.last_column() {
+            self.wrap_pending = true;
+        } else {
+            self.x = self.x.saturating_add(1);
+        }
+    }
+    fn line_feed(&mut self) {
+        if self.y == self.bottom {
+            self.scroll_up(1);
+        } else {
+            self.y = cmp::min(self.y.saturating_add(1), self.last_row());
+        }
+    }
+    /// Moves the lines of the scrolling region up, blanking the bottom ones.
+    fn scroll_up(&mut self, count: usize) {
+        for _ in 0..cmp::min(count, self.height) {
+            if self.top < self.rows.len() {
+                self.rows.remove(self.top);
+            }
+            let at = cmp::min(self.bottom, self.rows.len());
+            self.rows.insert(at, vec![' '; self.width]);
+        }
+    }
+    /// Moves the lines of the scrolling region down, blanking the top ones.
+    fn scroll_down(&mut self, count: usize) {
+        for _ in 0..cmp::min(count, self.height) {
+            if self.bottom < self.rows.len() {
+                self.rows.remove(self.bottom);
+            }
+            self.rows.insert(self.top, vec![' '; self.width]);
+        }
+    }
+    fn clear_line(&mut self, y: usize, from: usize, to: usize) {
+        if let Some(row) = self.rows.get_mut(y) {
+            let to = cmp::min(to, row.len());
+            if let Some(cells) = row.get_mut(from..to) {
+                cells.fill(' ');
+            }
+        }
+    }
+    fn last_column(&self) -> usize {
+        self.width.saturating_sub(1)
+    }
+    fn last_row(&self) -> usize {
+        self.height.saturating_sub(1)
+    }
+}
@@ -0,0 +1,67 @@
+use crate::diff;
+use std::collections::HashMap;
+
+/// How often each word occurs in a buffer, kept current by re-reading only the lines
+/// that changed since the last update.
+#[derive(Default)]
+pub struct WordIndex {
+    counts: HashMap<String, usize>,
+    /// The lines as of the last update.
+    lines: Vec<String>,
+    /// The document revision the index matches.
+    pub revision: Option<usize>,
+}
+
+impl WordIndex {
+    pub fn update(&mut self, lines: &[&str], revision: usize) {
+        let hunks = {
+            let old: Vec<&str> = self.lines.iter().map(String::as_str).collect();
+            diff::lines(&old, lines)
+        };
+        for hunk in hunks.iter().rev() {
+            let old_end = hunk.old_start.saturating_add(hunk.old_len);
+            for line in self.lines.get(hunk.old_start..old_end).unwrap_or_default() {
+                for word in words(line) {
+                    if let Some(count) = self.counts.get_mut(word) {
+                        *count = count.saturating_sub(1);
+                        if *count == 0 {
+                            self.counts.remove(word);
+                        }
+                    }
+                }
+            }
+            let new_lines = lines
+                .get(hunk.new_start..hunk.new_start.saturating_add(hunk.new_len))
+                .unwrap_or_default();
+            for line in new_lines {
+                for word in words(line) {
+                    let count = self.counts.entry(word.to_string()).or_insert(0);
+                    *count = count.saturating_add(1);
+                }
+            }
+            let end = old_end.min(self.lines.len());
+            let start = hunk.old_start.min(end);
+            self.lines
+                .splice(start..end, new_lines.iter().map(|line| (*line).to_string()));
+        }
+        self.revision = Some(revision);
+    }
+    /// Words starting with `prefix` (other than `prefix` itself), most frequent first.
+    pub fn completions(&self, prefix: &str) -> Vec<(&str, usize)> {
+        let mut matches: Vec<(&str, usize)> = self
+            .counts
+            .iter()
+            .filter(|(word, _)| word.starts_with(prefix) && word.as_str() != prefix)
+            .map(|(word, count)| (word.as_str(), *count))
+            .collect();
+        matches.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+        matches
+    }
+}
+
+/// The words of `line` worth completing: runs of letters, digits and underscores
+/// longer than one character.
+pub fn words(line: &str) -> impl Iterator<Item = &str> {
+    line.split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|word| word.chars().count() > 1)
+}
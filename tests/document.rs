@@ -0,0 +1,58 @@
+use hecto::{Document, LineEnding, Position, Row};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A file named after `test` in the temporary directory, holding `contents`.
+fn temp_file(test: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("hecto-document-{}-{}", test, std::process::id()));
+    fs::write(&path, contents).expect("temp file");
+    path
+}
+
+fn open(path: &Path) -> Document {
+    Document::open(path.to_str().expect("UTF-8 path")).expect("open")
+}
+
+#[test]
+fn edits_are_written_back() {
+    let path = temp_file("edit", "one\ntwo\nthree\n");
+    let mut document = open(&path);
+    assert_eq!(document.len(), 3);
+    let end = document.insert_str(&Position { x: 3, y: 0 }, " and\na half");
+    assert!(end == Position { x: 6, y: 1 });
+    document.delete_range(&Position { x: 0, y: 2 }, &Position { x: 0, y: 3 });
+    assert_eq!(document.text(), "one and\na half\nthree\n");
+    assert!(document.is_dirty());
+    document.save().expect("save");
+    assert!(!document.is_dirty());
+    assert_eq!(
+        fs::read_to_string(&path).expect("read"),
+        "one and\na half\nthree\n"
+    );
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn line_endings_are_kept() {
+    let path = temp_file("crlf", "a\r\nb\r\n");
+    let mut document = open(&path);
+    assert!(document.line_ending == LineEnding::CrLf);
+    assert_eq!(document.row(1).map(Row::as_str), Some("b"));
+    document.insert(&Position { x: 1, y: 1 }, 'c');
+    let mut written = Vec::new();
+    document.write_to(&mut written).expect("write");
+    assert_eq!(written, b"a\r\nbc\r\n");
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn rows_count_graphemes() {
+    let mut row = Row::from("e\u{301}te\u{301}");
+    assert_eq!(row.len(), 3);
+    let tail = row.split(1);
+    assert_eq!(row.as_str(), "e\u{301}");
+    assert_eq!(tail.graphemes().collect::<Vec<_>>(), ["t", "e\u{301}"]);
+    row.append(&tail);
+    row.delete(0);
+    assert_eq!(row.as_str(), "te\u{301}");
+}
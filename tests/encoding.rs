@@ -0,0 +1,44 @@
+use hecto::{Document, Encoding};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A file named after `test` in the temporary directory, holding `bytes`.
+fn temp_file(test: &str, bytes: &[u8]) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("hecto-encoding-{}-{}", test, std::process::id()));
+    fs::write(&path, bytes).expect("temp file");
+    path
+}
+
+fn open(path: &Path) -> Document {
+    Document::open(path.to_str().expect("UTF-8 path")).expect("open")
+}
+
+#[test]
+fn utf16_without_bom_opens_as_text() {
+    assert_eq!(Encoding::detect(b"h\0i\0\n\0"), Encoding::Utf16Le);
+    assert_eq!(Encoding::detect(b"\0h\0i\0\n"), Encoding::Utf16Be);
+    let path = temp_file("nobom", b"h\0i\0\n\0");
+    let document = open(&path);
+    assert!(!document.is_binary());
+    assert_eq!(document.text(), "hi\n");
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn plain_text_stays_utf8() {
+    assert_eq!(Encoding::detect(b"hi\n"), Encoding::Utf8);
+    assert_eq!(Encoding::detect("h\u{e9}!\n".as_bytes()), Encoding::Utf8);
+    assert_eq!(Encoding::detect(b""), Encoding::Utf8);
+}
+
+#[test]
+fn converting_to_utf16_writes_a_bom() {
+    let path = temp_file("convert", b"hi\n");
+    let mut document = open(&path);
+    document.set_encoding(Encoding::Utf16Le);
+    document.save().expect("save");
+    assert_eq!(fs::read(&path).expect("read"), b"\xFF\xFEh\0i\0\n\0");
+    let document = open(&path);
+    assert_eq!(document.text(), "hi\n");
+    fs::remove_file(path).ok();
+}
@@ -0,0 +1,49 @@
+use hecto::{Document, Position};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+/// An empty directory named after `test` in the temporary directory.
+fn temp_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hecto-save-{}-{}", test, std::process::id()));
+    fs::create_dir_all(&dir).expect("temp dir");
+    dir
+}
+
+#[test]
+fn saving_keeps_the_mode_and_leaves_no_temp_file() {
+    let dir = temp_dir("mode");
+    let path = dir.join("file.txt");
+    fs::write(&path, "a\n").expect("write");
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).expect("chmod");
+    let mut document = Document::open(path.to_str().expect("UTF-8 path")).expect("open");
+    document.insert_str(&Position { x: 0, y: 0 }, "b");
+    document.save().expect("save");
+    assert_eq!(fs::read_to_string(&path).expect("read"), "ba\n");
+    let mode = fs::metadata(&path).expect("metadata").permissions().mode();
+    assert_eq!(mode & 0o777, 0o640);
+    let names: Vec<_> = fs::read_dir(&dir)
+        .expect("read dir")
+        .map(|entry| entry.expect("entry").file_name())
+        .collect();
+    assert_eq!(names, ["file.txt"]);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn saving_through_a_symlink_writes_its_target() {
+    let dir = temp_dir("link");
+    let target = dir.join("target.txt");
+    let link = dir.join("link.txt");
+    fs::write(&target, "a\n").expect("write");
+    std::os::unix::fs::symlink(&target, &link).expect("symlink");
+    let mut document = Document::open(link.to_str().expect("UTF-8 path")).expect("open");
+    document.insert_str(&Position { x: 0, y: 0 }, "b");
+    document.save().expect("save");
+    assert_eq!(fs::read_to_string(&target).expect("read"), "ba\n");
+    assert!(fs::symlink_metadata(&link)
+        .expect("link")
+        .file_type()
+        .is_symlink());
+    fs::remove_dir_all(dir).ok();
+}
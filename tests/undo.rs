@@ -0,0 +1,58 @@
+use hecto::{Document, Position};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A file named after `test` in the temporary directory, holding `contents`.
+fn temp_file(test: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("hecto-undo-{}-{}", test, std::process::id()));
+    fs::write(&path, contents).expect("temp file");
+    path
+}
+
+fn open(path: &Path) -> Document {
+    Document::open(path.to_str().expect("UTF-8 path")).expect("open")
+}
+
+#[test]
+fn block_insert_after_a_change_undoes_in_order() {
+    let path = temp_file("block", "a\nb\nc\n");
+    let mut document = open(&path);
+    document.apply_text("a\nb\nC\n");
+    document.insert_block(&Position { x: 0, y: 2 }, "X\nY\nZ");
+    assert_eq!(document.text(), "a\nb\nX\nY\nZC\n");
+    document.undo();
+    assert_eq!(document.text(), "a\nb\nC\n");
+    document.undo();
+    assert_eq!(document.text(), "a\nb\nc\n");
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn cleanup_on_save_is_an_undo_step() {
+    let path = temp_file("clean", "a  \nb\n");
+    let mut document = open(&path);
+    document.save_options.trim_trailing_whitespace = true;
+    document.insert_str(&Position { x: 0, y: 1 }, "x");
+    document.save_with(&mut |_, _| Ok(())).expect("save");
+    assert_eq!(document.text(), "a\nxb\n");
+    document.undo();
+    assert_eq!(document.text(), "a  \nxb\n");
+    document.undo();
+    assert_eq!(document.text(), "a  \nb\n");
+    fs::remove_file(path).ok();
+}
+
+#[test]
+fn reload_is_an_undo_step() {
+    let path = temp_file("reload", "a\nb\nc\n");
+    let mut document = open(&path);
+    document.insert_str(&Position { x: 0, y: 0 }, "x");
+    fs::write(&path, "a\nB\nc\nd\n").expect("rewrite");
+    document.reload().expect("reload");
+    assert_eq!(document.text(), "a\nB\nc\nd\n");
+    document.undo();
+    assert_eq!(document.text(), "xa\nb\nc\n");
+    document.undo();
+    assert_eq!(document.text(), "a\nb\nc\n");
+    fs::remove_file(path).ok();
+}